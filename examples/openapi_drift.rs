@@ -0,0 +1,33 @@
+//! Compares the models with a Firecracker swagger spec and prints the
+//! differences, failing on breaking ones.
+//!
+//!     cargo run --example openapi_drift --features openapi -- [SPEC.json [ALLOWLIST]]
+//!
+//! Without arguments, the bundled spec and allowlist are used.
+
+use firecracker_http_client::openapi::{check_drift, Allowlist, BUNDLED_ALLOWLIST, BUNDLED_SPEC};
+use std::error::Error;
+use std::process::ExitCode;
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let spec = match args.next() {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => BUNDLED_SPEC.to_string(),
+    };
+    let allowlist = match args.next() {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => BUNDLED_ALLOWLIST.to_string(),
+    };
+
+    let report = check_drift(
+        &serde_json::from_str(&spec)?,
+        &allowlist.parse::<Allowlist>()?,
+    )?;
+    print!("{}", report);
+    Ok(if report.breaking().next().is_some() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
@@ -1,6 +1,9 @@
 use firecracker_http_client::{
     action::InstanceActionInfo,
-    snapshot::{SnapshotCreateParams, SnapshotLoadParams, SnapshotOperations},
+    snapshot::{
+        MemBackend, NetworkOverride, SnapshotCreateParams, SnapshotLoadParams, SnapshotOperations,
+        SnapshotType,
+    },
     FirecrackerClient,
 };
 use std::error::Error;
@@ -11,34 +14,31 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let client = FirecrackerClient::new("http://localhost:8080").await?;
 
     // Create a snapshot
-    let snapshot_params = SnapshotCreateParams {
-        snapshot_path: "/tmp/snapshot".to_string(),
-        mem_file_path: "/tmp/snapshot.mem".to_string(),
-        version: Some("1.0".to_string()),
-        snapshot_type: Some("Full".to_string()),
-    };
+    let snapshot_params = SnapshotCreateParams::builder("/tmp/snapshot", "/tmp/snapshot.mem")
+        .snapshot_type(SnapshotType::Full)
+        .version("1.0")
+        .build()?;
     client.create_snapshot(&snapshot_params).await?;
 
     // Pause the VM before loading snapshot
-    let pause_action = InstanceActionInfo {
-        action_type: "Pause".to_string(),
-    };
+    let pause_action = InstanceActionInfo::new("Pause");
     client.create_sync_action(&pause_action).await?;
 
-    // Load a snapshot
-    let load_params = SnapshotLoadParams {
-        snapshot_path: "/tmp/snapshot".to_string(),
-        mem_file_path: "/tmp/snapshot.mem".to_string(),
-        enable_diff_snapshots: Some(true),
-    };
+    // Load a snapshot and resume the VM in the same request, attaching the
+    // guest's eth0 to this host's tap device
+    let mut load_params = SnapshotLoadParams::with_mem_backend(
+        "/tmp/snapshot",
+        MemBackend::File {
+            backend_path: "/tmp/snapshot.mem".to_string(),
+        },
+    );
+    load_params.enable_diff_snapshots = Some(true);
+    load_params.resume_vm = Some(true);
+    load_params
+        .network_overrides
+        .push(NetworkOverride::new("eth0", "tap1"));
     client.load_snapshot(&load_params).await?;
 
-    // Resume the VM after loading snapshot
-    let resume_action = InstanceActionInfo {
-        action_type: "Resume".to_string(),
-    };
-    client.create_sync_action(&resume_action).await?;
-
     println!("Snapshot operations completed successfully!");
     Ok(())
 }
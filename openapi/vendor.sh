@@ -0,0 +1,25 @@
+#!/bin/sh
+# Vendors Firecracker's OpenAPI spec as `openapi::BUNDLED_SPEC` expects it:
+#
+#   openapi/vendor.sh v1.7.0
+#
+# fetches src/firecracker/swagger/firecracker.yaml at that tag, converts it
+# to JSON unchanged with mikefarah's yq, and records where it came from and
+# the SHA-256 of the YAML as fetched in firecracker-<tag>.source.
+set -eu
+
+tag=${1:?usage: $0 <firecracker tag, e.g. v1.7.0>}
+url="https://raw.githubusercontent.com/firecracker-microvm/firecracker/$tag/src/firecracker/swagger/firecracker.yaml"
+dir=$(dirname "$0")
+yaml=$(mktemp)
+trap 'rm -f "$yaml"' EXIT
+
+curl -sSfL "$url" -o "$yaml"
+yq -o=json '.' "$yaml" > "$dir/firecracker-$tag.json"
+sha256=$(sha256sum "$yaml" | cut -d ' ' -f 1)
+cat > "$dir/firecracker-$tag.source" <<SOURCE
+url: $url
+sha256: $sha256
+converted: yq -o=json '.'
+SOURCE
+echo "Vendored $url ($sha256)"
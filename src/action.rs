@@ -1,25 +1,112 @@
 use crate::error::FirecrackerError;
 use async_trait::async_trait;
-use lazy_static::lazy_static;
-use regex::Regex;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The action `PUT /actions` performs. Strings this client does not know
+/// parse and deserialize into `Custom`, so stored payloads and actions added
+/// by newer Firecracker releases keep working; the client only sends a
+/// `Custom` action when validation is off.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ActionType {
+    FlushMetrics,
+    InstanceStart,
+    InstanceHalt,
+    SendCtrlAltDel,
+    Custom(String),
+}
+
+const ACTION_TYPES: &[&str] = &[
+    "FlushMetrics",
+    "InstanceStart",
+    "InstanceHalt",
+    "SendCtrlAltDel",
+];
+
+impl ActionType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ActionType::FlushMetrics => "FlushMetrics",
+            ActionType::InstanceStart => "InstanceStart",
+            ActionType::InstanceHalt => "InstanceHalt",
+            ActionType::SendCtrlAltDel => "SendCtrlAltDel",
+            ActionType::Custom(action) => action,
+        }
+    }
+}
+
+impl fmt::Display for ActionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Matches the canonical spelling exactly, like Firecracker does
+impl FromStr for ActionType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "FlushMetrics" => ActionType::FlushMetrics,
+            "InstanceStart" => ActionType::InstanceStart,
+            "InstanceHalt" => ActionType::InstanceHalt,
+            "SendCtrlAltDel" => ActionType::SendCtrlAltDel,
+            _ => ActionType::Custom(s.to_string()),
+        })
+    }
+}
+
+impl Serialize for ActionType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Ok(action_type) = String::deserialize(deserializer)?.parse();
+        Ok(action_type)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[non_exhaustive]
 pub struct InstanceActionInfo {
-    pub action_type: String,
+    #[validate(custom = "validate_action_type")]
+    pub action_type: ActionType,
 }
 
 impl InstanceActionInfo {
     pub fn new(action_type: &str) -> Self {
-        Self {
-            action_type: action_type.to_string(),
-        }
+        let Ok(action_type) = action_type.parse();
+        Self { action_type }
     }
 }
 
-lazy_static! {
-    static ref ACTION_TYPE_REGEX: Regex =
-        Regex::new(r"^(InstanceStart|InstanceHalt|SendCtrlAltDel)$").unwrap();
+impl From<ActionType> for InstanceActionInfo {
+    fn from(action_type: ActionType) -> Self {
+        Self { action_type }
+    }
+}
+
+fn validate_action_type(action_type: &ActionType) -> Result<(), ValidationError> {
+    match action_type {
+        ActionType::Custom(_) => {
+            let mut err = ValidationError::new("regex");
+            err.message = Some(
+                format!(
+                    "Invalid action type. Must be one of: {}",
+                    ACTION_TYPES.join(", ")
+                )
+                .into(),
+            );
+            Err(err)
+        }
+        _ => Ok(()),
+    }
 }
 
 #[async_trait]
@@ -34,16 +121,6 @@ impl ActionOperations for crate::FirecrackerClient {
         &self,
         action: &InstanceActionInfo,
     ) -> Result<(), FirecrackerError> {
-        let url = self.url("actions")?;
-        let response = self.client.put(url).json(action).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        self.send_action(action).await
     }
 }
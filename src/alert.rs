@@ -0,0 +1,309 @@
+//! Alerts on metrics crossing thresholds, e.g. block device errors going up
+//! or network receive failures spiking. An [`AlertEngine`] evaluates its
+//! [`AlertRule`]s against every metrics document it's given and reports
+//! when a rule starts and stops firing.
+//!
+//! Time is taken from the documents' `utc_timestamp_ms`, so a rule's
+//! durations count in Firecracker's flushes, not in when the documents
+//! happen to be read.
+
+use crate::metrics::FirecrackerMetrics;
+use futures_util::stream::{Stream, StreamExt};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// How many events a subscriber can fall behind before it misses some.
+pub const EVENT_CAPACITY: usize = 64;
+
+/// How a metric value is compared with a rule's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+impl Comparison {
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Above => value > threshold,
+            Comparison::AtLeast => value >= threshold,
+            Comparison::Below => value < threshold,
+            Comparison::AtMost => value <= threshold,
+        }
+    }
+}
+
+/// Which value of a metric a rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evaluation {
+    /// The value in the latest document, counter or gauge
+    Absolute,
+    /// What the counter counted since the previous flush, which is the value
+    /// Firecracker writes for it. Gauges, such as latencies, never match.
+    Delta,
+}
+
+/// A condition on one metric, named so it can be replaced or removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub name: String,
+    /// The metric's dotted path, as in [`FirecrackerMetrics::values`],
+    /// e.g. `block.execute_fails` or `net.rx_fails`
+    pub metric: String,
+    pub evaluation: Evaluation,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    /// How long the condition has to hold before the rule fires
+    pub for_duration: Duration,
+    /// How long the condition has to be clear before a firing rule resolves
+    pub resolve_after: Duration,
+}
+
+impl AlertRule {
+    /// A rule on the absolute value of `metric` that fires as soon as the
+    /// condition holds and resolves as soon as it doesn't.
+    pub fn new(
+        name: impl Into<String>,
+        metric: impl Into<String>,
+        comparison: Comparison,
+        threshold: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            metric: metric.into(),
+            evaluation: Evaluation::Absolute,
+            comparison,
+            threshold,
+            for_duration: Duration::ZERO,
+            resolve_after: Duration::ZERO,
+        }
+    }
+
+    /// Looks at the metric's count since the previous flush, which only
+    /// counters have.
+    pub fn on_delta(mut self) -> Self {
+        self.evaluation = Evaluation::Delta;
+        self
+    }
+
+    pub fn for_duration(mut self, duration: Duration) -> Self {
+        self.for_duration = duration;
+        self
+    }
+
+    pub fn resolve_after(mut self, duration: Duration) -> Self {
+        self.resolve_after = duration;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// A rule starting or stopping to fire.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub state: AlertState,
+    /// The value that completed the transition
+    pub value: f64,
+    /// `utc_timestamp_ms` of the document that completed the transition
+    pub at_ms: u64,
+}
+
+/// Called with every event; see [`AlertEngine::on_event`].
+pub type AlertCallback = Box<dyn Fn(&AlertEvent) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Clear,
+    // The condition holds since the given time, but not yet for long enough
+    Pending(u64),
+    Firing,
+    // The condition is clear since the given time, but not yet for long
+    // enough
+    Resolving(u64),
+}
+
+struct RuleState {
+    rule: AlertRule,
+    phase: Phase,
+}
+
+impl RuleState {
+    fn evaluate(&mut self, value: f64, at_ms: u64) -> Option<AlertState> {
+        let rule = &self.rule;
+        let holds = rule.comparison.holds(value, rule.threshold);
+        let lasted = |since: u64, duration: Duration| {
+            u128::from(at_ms.saturating_sub(since)) >= duration.as_millis()
+        };
+        let phase = match (self.phase, holds) {
+            (Phase::Clear, true) => Phase::Pending(at_ms),
+            (Phase::Pending(_), false) => Phase::Clear,
+            (Phase::Firing, false) => Phase::Resolving(at_ms),
+            (Phase::Resolving(_), true) => Phase::Firing,
+            (phase, _) => phase,
+        };
+        // A zero duration completes the transition with the same document
+        let (phase, event) = match phase {
+            Phase::Pending(since) if lasted(since, rule.for_duration) => {
+                (Phase::Firing, Some(AlertState::Firing))
+            }
+            Phase::Resolving(since) if lasted(since, rule.resolve_after) => {
+                (Phase::Clear, Some(AlertState::Resolved))
+            }
+            phase => (phase, None),
+        };
+        self.phase = phase;
+        event
+    }
+}
+
+/// Evaluates rules against a VM's metrics. Rules can be added and removed
+/// while the engine runs; callbacks are called and events sent in the order
+/// the documents arrive.
+pub struct AlertEngine {
+    rules: Mutex<BTreeMap<String, RuleState>>,
+    last_ms: Mutex<Option<u64>>,
+    callbacks: Mutex<Vec<AlertCallback>>,
+    events: broadcast::Sender<AlertEvent>,
+}
+
+impl Default for AlertEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self {
+            rules: Mutex::new(BTreeMap::new()),
+            last_ms: Mutex::new(None),
+            callbacks: Mutex::new(Vec::new()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Adds a rule, replacing the one with the same name, which is returned.
+    /// The new rule starts out clear, without an event for the one it
+    /// replaces.
+    pub fn add_rule(&self, rule: AlertRule) -> Option<AlertRule> {
+        let state = RuleState {
+            rule,
+            phase: Phase::Clear,
+        };
+        self.rules
+            .lock()
+            .unwrap()
+            .insert(state.rule.name.clone(), state)
+            .map(|state| state.rule)
+    }
+
+    /// Removes a rule without an event, even if it was firing.
+    pub fn remove_rule(&self, name: &str) -> Option<AlertRule> {
+        self.rules
+            .lock()
+            .unwrap()
+            .remove(name)
+            .map(|state| state.rule)
+    }
+
+    pub fn rules(&self) -> Vec<AlertRule> {
+        self.rules
+            .lock()
+            .unwrap()
+            .values()
+            .map(|state| state.rule.clone())
+            .collect()
+    }
+
+    /// The names of the rules firing now.
+    pub fn firing(&self) -> Vec<String> {
+        self.rules
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|state| matches!(state.phase, Phase::Firing | Phase::Resolving(_)))
+            .map(|state| state.rule.name.clone())
+            .collect()
+    }
+
+    /// Calls `callback` with every event. It runs inline while the document
+    /// is evaluated, so it should be quick.
+    pub fn on_event(&self, callback: impl Fn(&AlertEvent) + Send + Sync + 'static) {
+        self.callbacks.lock().unwrap().push(Box::new(callback));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AlertEvent> {
+        self.events.subscribe()
+    }
+
+    /// Evaluates every rule against the next document and returns the
+    /// events it caused, which have also gone to the callbacks and
+    /// subscribers. Documents older than the previous one are ignored.
+    pub fn observe(&self, metrics: &FirecrackerMetrics) -> Vec<AlertEvent> {
+        let at_ms = metrics.utc_timestamp_ms;
+        {
+            let mut last_ms = self.last_ms.lock().unwrap();
+            if last_ms.is_some_and(|last| at_ms < last) {
+                return Vec::new();
+            }
+            *last_ms = Some(at_ms);
+        }
+        let values = metrics.values();
+        let counters = metrics.counters();
+
+        let events: Vec<AlertEvent> = {
+            let mut rules = self.rules.lock().unwrap();
+            rules
+                .values_mut()
+                .filter_map(|state| {
+                    let value = match state.rule.evaluation {
+                        Evaluation::Absolute => *values.get(&state.rule.metric)?,
+                        Evaluation::Delta => *counters.get(&state.rule.metric)?,
+                    } as f64;
+                    let alert = state.evaluate(value, at_ms)?;
+                    Some(AlertEvent {
+                        rule: state.rule.name.clone(),
+                        state: alert,
+                        value,
+                        at_ms,
+                    })
+                })
+                .collect()
+        };
+
+        let callbacks = self.callbacks.lock().unwrap();
+        for event in &events {
+            for callback in callbacks.iter() {
+                callback(event);
+            }
+            // Nobody subscribed is fine
+            let _ = self.events.send(event.clone());
+        }
+        events
+    }
+
+    /// Observes every document of `metrics`, e.g. from
+    /// [`stream_metrics`](crate::metrics::stream_metrics), until it ends.
+    /// Documents that fail to parse are skipped.
+    pub async fn run<S, E>(&self, metrics: S)
+    where
+        S: Stream<Item = Result<FirecrackerMetrics, E>>,
+    {
+        futures_util::pin_mut!(metrics);
+        while let Some(item) = metrics.next().await {
+            if let Ok(metrics) = item {
+                self.observe(&metrics);
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! A journal of every request a client sends, for answering who changed a
+//! VM and when. Attach a sink with
+//! [`FirecrackerClient::with_audit_log`](crate::FirecrackerClient::with_audit_log).
+//! Bodies are never recorded as they are: only their length and a digest,
+//! or nothing at all for paths the [`AuditPolicy`] omits, such as MMDS
+//! data, which often carries credentials.
+
+use crate::error::FirecrackerError;
+use crate::paths;
+use crate::redaction::path_matches;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One request as sent by the client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch when the request was sent
+    pub timestamp_ms: u64,
+    /// The client's base URL, or its API socket
+    pub vm: String,
+    /// The jailer id, when the client has a jailer context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vm_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BodySummary>,
+    /// `None` when no response arrived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Why no response arrived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+/// What is recorded of a request body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "recorded", rename_all = "snake_case")]
+pub enum BodySummary {
+    /// The hex SHA-256 digest of the body, which tells two bodies apart
+    /// without revealing them
+    Digest { len: usize, sha256: String },
+    /// Omitted by the audit policy
+    Omitted { len: usize },
+}
+
+/// How request bodies are recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyRecording {
+    Digest,
+    Omit,
+}
+
+/// Which request bodies are recorded how, by API path. A rule ending in `/`
+/// covers the paths under it, e.g. `drives/`; any other rule only its exact
+/// path, so `mmds` leaves `mmds/config` to the default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditPolicy {
+    pub rules: Vec<(String, BodyRecording)>,
+    pub default: BodyRecording,
+}
+
+impl Default for AuditPolicy {
+    /// Digests every body except MMDS data.
+    fn default() -> Self {
+        Self {
+            rules: vec![(paths::MMDS.to_string(), BodyRecording::Omit)],
+            default: BodyRecording::Digest,
+        }
+    }
+}
+
+impl AuditPolicy {
+    pub fn with_rule(mut self, path: impl Into<String>, recording: BodyRecording) -> Self {
+        self.rules.push((path.into(), recording));
+        self
+    }
+
+    /// How bodies sent to `path` are recorded; the last matching rule wins.
+    pub fn recording(&self, path: &str) -> BodyRecording {
+        let path = path.trim_matches('/');
+        self.rules
+            .iter()
+            .rev()
+            .find(|(rule, _)| path_matches(rule, path))
+            .map_or(self.default, |(_, recording)| *recording)
+    }
+
+    pub(crate) fn summarize(&self, path: &str, body: &[u8]) -> BodySummary {
+        match self.recording(path) {
+            BodyRecording::Digest => BodySummary::Digest {
+                len: body.len(),
+                sha256: sha256_hex(body),
+            },
+            BodyRecording::Omit => BodySummary::Omitted { len: body.len() },
+        }
+    }
+}
+
+/// Where audit records go. `record` is called inline after every request,
+/// so it should be quick; it can't fail the request, so a sink has to keep
+/// track of its own errors.
+pub trait AuditLog: Send + Sync {
+    fn record(&self, record: &AuditRecord);
+}
+
+/// Appends records to a file as JSON lines, synced to disk after each one.
+/// The writes happen on a thread of its own, so `record` only queues the
+/// line; [`flush`](Self::flush) waits for everything queued so far.
+#[derive(Debug)]
+pub struct JsonlAuditLog {
+    path: PathBuf,
+    queue: Option<mpsc::Sender<Queued>>,
+    writer: Option<JoinHandle<()>>,
+    last_error: Arc<Mutex<Option<String>>>,
+}
+
+#[derive(Debug)]
+enum Queued {
+    Line(String),
+    Flush(mpsc::Sender<()>),
+}
+
+impl JsonlAuditLog {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, FirecrackerError> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|source| FirecrackerError::FileSystem {
+                path: path.clone(),
+                source,
+            })?;
+        let last_error = Arc::new(Mutex::new(None));
+        let (queue, queued) = mpsc::channel();
+        let writer = std::thread::Builder::new()
+            .name("audit-journal".to_string())
+            .spawn({
+                let last_error = last_error.clone();
+                move || write_queued(file, queued, &last_error)
+            })
+            .map_err(|e| FirecrackerError::Internal(format!("Audit journal writer: {}", e)))?;
+        Ok(Self {
+            path,
+            queue: Some(queue),
+            writer: Some(writer),
+            last_error,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Blocks until every record queued so far is written and synced.
+    pub fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        if let Some(queue) = &self.queue {
+            if queue.send(Queued::Flush(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+
+    /// The last error writing a record, if any.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    fn set_error(&self, err: String) {
+        *self.last_error.lock().unwrap() = Some(err);
+    }
+}
+
+impl AuditLog for JsonlAuditLog {
+    fn record(&self, record: &AuditRecord) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(err) => return self.set_error(err.to_string()),
+        };
+        let queued = self
+            .queue
+            .as_ref()
+            .is_some_and(|queue| queue.send(Queued::Line(line)).is_ok());
+        if !queued {
+            self.set_error("The audit journal writer has stopped".to_string());
+        }
+    }
+}
+
+impl Drop for JsonlAuditLog {
+    /// Writes out what is still queued before the file is closed.
+    fn drop(&mut self) {
+        drop(self.queue.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn write_queued(
+    mut file: File,
+    queued: mpsc::Receiver<Queued>,
+    last_error: &Mutex<Option<String>>,
+) {
+    for item in queued {
+        match item {
+            Queued::Line(line) => {
+                if let Err(err) = writeln!(file, "{}", line).and_then(|()| file.sync_data()) {
+                    *last_error.lock().unwrap() = Some(err.to_string());
+                }
+            }
+            Queued::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Reads the records of a journal written by [`JsonlAuditLog`].
+pub fn read_journal(path: &Path) -> Result<Vec<AuditRecord>, FirecrackerError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|source| FirecrackerError::FileSystem {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Into::into))
+        .collect()
+}
+
+pub(crate) fn timestamp_ms(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+pub(crate) fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    openssl::sha::sha256(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
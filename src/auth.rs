@@ -0,0 +1,46 @@
+//! Bearer tokens for Firecracker APIs reached through an authenticating
+//! gateway, e.g. one that exposes each VM's API per tenant.
+//!
+//! With a [`TokenProvider`] set through
+//! [`FirecrackerClient::with_token_provider`](crate::FirecrackerClient::with_token_provider),
+//! every request asks it for a token and sends `Authorization: Bearer
+//! <token>`. A 401 makes the client call [`TokenProvider::refresh`] and retry
+//! once with the new token; a second 401 is returned as is.
+
+use async_trait::async_trait;
+
+/// What a provider fails with; it is returned as
+/// [`FirecrackerError::TokenProvider`](crate::FirecrackerError::TokenProvider).
+pub type TokenError = Box<dyn std::error::Error + Send + Sync>;
+
+#[async_trait]
+pub trait TokenProvider: Send + Sync {
+    /// The token for the next request. Called before every request, so a
+    /// provider whose tokens rotate should cache them.
+    async fn token(&self) -> Result<String, TokenError>;
+
+    /// A token to retry with after the gateway refused the last one with a
+    /// 401. Providers that cache should drop the cached token here; the
+    /// default just asks [`token`](TokenProvider::token) again.
+    async fn refresh(&self) -> Result<String, TokenError> {
+        self.token().await
+    }
+}
+
+/// The same token for every request, e.g. a long-lived one from a secret.
+#[derive(Clone)]
+pub struct StaticToken(pub String);
+
+#[async_trait]
+impl TokenProvider for StaticToken {
+    async fn token(&self) -> Result<String, TokenError> {
+        Ok(self.0.clone())
+    }
+}
+
+// Tokens are secrets; keep them out of logs
+impl std::fmt::Debug for StaticToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StaticToken(..)")
+    }
+}
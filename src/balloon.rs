@@ -1,18 +1,63 @@
 use crate::models::{Balloon, BalloonStats};
+use crate::paths;
+use crate::validation::{ValidatePatch, ValidationTarget};
 use crate::FirecrackerError;
 use async_trait::async_trait;
+use futures_util::stream::{self, Stream};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use validator::{Validate, ValidationErrors};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[non_exhaustive]
 pub struct BalloonUpdate {
+    /// At most `MAX_BALLOON_MIB`
+    #[validate(range(max = "crate::validation::MAX_BALLOON_MIB"))]
     pub amount_mib: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl BalloonUpdate {
+    pub fn new(amount_mib: u32) -> Self {
+        Self { amount_mib }
+    }
+}
+
+impl ValidatePatch for BalloonUpdate {
+    fn validate_patch(&self) -> Result<(), ValidationErrors> {
+        self.validate()
+    }
+
+    fn patch_target(&self) -> ValidationTarget<'_> {
+        ValidationTarget::BalloonUpdate(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[non_exhaustive]
 pub struct BalloonStatsUpdate {
+    /// At most `MAX_STATS_POLLING_INTERVAL_S`
+    #[validate(range(max = "crate::validation::MAX_STATS_POLLING_INTERVAL_S"))]
     pub stats_polling_interval_s: u32,
 }
 
+impl BalloonStatsUpdate {
+    pub fn new(stats_polling_interval_s: u32) -> Self {
+        Self {
+            stats_polling_interval_s,
+        }
+    }
+}
+
+impl ValidatePatch for BalloonStatsUpdate {
+    fn validate_patch(&self) -> Result<(), ValidationErrors> {
+        self.validate()
+    }
+
+    fn patch_target(&self) -> ValidationTarget<'_> {
+        ValidationTarget::BalloonStatsUpdate(self)
+    }
+}
+
 #[async_trait]
 pub trait BalloonOperations {
     async fn get_balloon_config(&self) -> Result<Balloon, FirecrackerError>;
@@ -28,75 +73,44 @@ pub trait BalloonOperations {
 #[async_trait]
 impl BalloonOperations for crate::FirecrackerClient {
     async fn get_balloon_config(&self) -> Result<Balloon, FirecrackerError> {
-        let url = self.url("balloon")?;
-        let response = self.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(response.json().await?)
+        self.get_json(paths::BALLOON).await
     }
 
     async fn put_balloon_config(&self, config: &Balloon) -> Result<(), FirecrackerError> {
-        let url = self.url("balloon")?;
-        let response = self.client.put(url).json(config).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        self.put_linted(self.validated_put(paths::BALLOON, config))
+            .await
     }
 
     async fn patch_balloon_config(&self, update: &BalloonUpdate) -> Result<(), FirecrackerError> {
-        let url = self.url("balloon")?;
-        let response = self.client.patch(url).json(update).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        self.validated_patch(paths::BALLOON, update).await
     }
 
     async fn get_balloon_stats(&self) -> Result<BalloonStats, FirecrackerError> {
-        let url = self.url("balloon/statistics")?;
-        let response = self.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(response.json().await?)
+        self.get_json(paths::BALLOON_STATISTICS).await
     }
 
     async fn patch_balloon_stats(
         &self,
         update: &BalloonStatsUpdate,
     ) -> Result<(), FirecrackerError> {
-        let url = self.url("balloon/statistics")?;
-        let response = self.client.patch(url).json(update).send().await?;
+        self.validated_patch(paths::BALLOON_STATISTICS, update)
+            .await
+    }
+}
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+/// Polls `GET /balloon/statistics` every `interval`, starting right away.
+/// Failed polls yield the error and polling carries on, so stop consuming
+/// the stream if the balloon turns out not to be configured. Firecracker only
+/// refreshes the statistics every `stats_polling_interval_s`, so polling
+/// faster than that repeats the same values.
+pub fn stream_balloon_stats(
+    client: &crate::FirecrackerClient,
+    interval: Duration,
+) -> impl Stream<Item = Result<BalloonStats, FirecrackerError>> + '_ {
+    stream::unfold(false, move |started| async move {
+        if started {
+            tokio::time::sleep(interval).await;
         }
-
-        Ok(())
-    }
+        Some((client.get_balloon_stats().await, true))
+    })
 }
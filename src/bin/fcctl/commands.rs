@@ -0,0 +1,754 @@
+use crate::output::OutputFormat;
+use crate::profiles::ProfilesCommand;
+use crate::watch::{watch, WatchArgs};
+use clap::{Args, Subcommand};
+use firecracker_http_client::action::{ActionOperations, ActionType, InstanceActionInfo};
+use firecracker_http_client::balloon::BalloonOperations;
+use firecracker_http_client::boot::BootSourceOperations;
+use firecracker_http_client::config::{read_config_file, ConfigOperations};
+use firecracker_http_client::drive::DriveUpdate;
+use firecracker_http_client::error::FirecrackerError;
+use firecracker_http_client::instance::InstanceOperations;
+use firecracker_http_client::machine::MachineConfigOperations;
+use firecracker_http_client::mmds::{mmds_set_patch, MmdsOperations, MMDS_DEFAULT_SIZE_LIMIT};
+use firecracker_http_client::network::NetworkInterfaceUpdate;
+use firecracker_http_client::snapshot::{
+    restore, MemBackend, NetworkOverride, RestoreOptions, SafeSnapshotOptions, SnapshotChain,
+    SnapshotCreateParams, SnapshotLoadParams, SnapshotType,
+};
+use firecracker_http_client::version::VersionOperations;
+use firecracker_http_client::{
+    Balloon, BootSource, Drive, DriveOperations, FirecrackerClient, MachineConfig,
+    NetworkInterface, NetworkInterfaceOperations, Patchable, RateLimiter, SnapshotOperations,
+    VmOperations,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Show or set the vCPU and memory configuration
+    #[command(subcommand)]
+    MachineConfig(MachineConfigCommand),
+    /// Show the whole VM configuration
+    #[command(subcommand)]
+    VmConfig(VmConfigCommand),
+    /// Attach or update block devices
+    #[command(subcommand)]
+    Drive(DriveCommand),
+    /// Attach or update network interfaces
+    #[command(subcommand)]
+    Net(NetCommand),
+    /// Set the kernel to boot
+    #[command(subcommand)]
+    BootSource(BootSourceCommand),
+    /// Start the VM, send Ctrl+Alt+Del or flush metrics
+    #[command(subcommand)]
+    Action(ActionCommand),
+    /// Create, load or restore snapshots
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+    /// Configure the balloon device and read its statistics
+    #[command(subcommand)]
+    Balloon(BalloonCommand),
+    /// Read or write the MMDS data store
+    #[command(subcommand)]
+    Mmds(MmdsCommand),
+    /// Print the Firecracker version
+    Version,
+    /// Print the instance id, state and VMM version
+    Instance,
+    /// Configure the VM from a config file
+    Apply(ApplyArgs),
+    /// Follow the instance state, balloon statistics and metrics until Ctrl-C
+    Watch(WatchArgs),
+    /// List, add or remove connection profiles
+    #[command(subcommand)]
+    Profiles(ProfilesCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyArgs {
+    /// Config file in Firecracker's `--config-file` layout, or a serialized
+    /// `FullVmConfiguration` with snake_case section names
+    #[arg(short, long)]
+    file: PathBuf,
+    /// Validate and print the requests that would be sent, without sending
+    /// them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ApplyReport {
+    dry_run: bool,
+    sections: Vec<SectionReport>,
+    warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SectionReport {
+    section: String,
+    request: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<Value>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MachineConfigCommand {
+    Get,
+    Set(MachineConfigArgs),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VmConfigCommand {
+    Get,
+}
+
+#[derive(Debug, Args)]
+pub struct MachineConfigArgs {
+    /// Number of vCPUs
+    #[arg(long)]
+    vcpus: u32,
+    /// Guest memory in MiB
+    #[arg(long)]
+    mem_mib: u32,
+    /// Enable simultaneous multithreading
+    #[arg(long)]
+    smt: bool,
+    /// Track dirty pages, needed for diff snapshots
+    #[arg(long)]
+    track_dirty_pages: bool,
+    /// Back guest memory with huge pages, e.g. `2M`
+    #[arg(long)]
+    huge_pages: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DriveCommand {
+    Put(DrivePutArgs),
+    Patch(DrivePatchArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct DrivePutArgs {
+    drive_id: String,
+    /// Backing file on the host
+    #[arg(long, conflicts_with = "socket", required_unless_present = "socket")]
+    path: Option<String>,
+    /// Socket of a vhost-user block backend
+    #[arg(long)]
+    socket: Option<String>,
+    /// Boot from this drive
+    #[arg(long)]
+    root: bool,
+    #[arg(long)]
+    read_only: bool,
+    /// Partition UUID of the root partition
+    #[arg(long)]
+    partuuid: Option<String>,
+    /// `Unsafe` or `Writeback`
+    #[arg(long)]
+    cache_type: Option<String>,
+    /// `Sync` or `Async`
+    #[arg(long)]
+    io_engine: Option<String>,
+    /// Limit the drive to this many bytes per second
+    #[arg(long)]
+    bandwidth: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct DrivePatchArgs {
+    drive_id: String,
+    /// New backing file on the host
+    #[arg(long)]
+    path: Option<String>,
+    /// Limit the drive to this many bytes per second
+    #[arg(long, conflicts_with = "no_rate_limiter")]
+    bandwidth: Option<u64>,
+    /// Remove the drive's rate limiter
+    #[arg(long)]
+    no_rate_limiter: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NetCommand {
+    Put(NetPutArgs),
+    Patch(NetPatchArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct NetPutArgs {
+    iface_id: String,
+    /// Host tap device to attach to
+    #[arg(long)]
+    host_dev: String,
+    /// MAC address of the guest interface
+    #[arg(long)]
+    mac: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct NetPatchArgs {
+    iface_id: String,
+    /// Limit received traffic to this many bytes per second
+    #[arg(long, conflicts_with = "no_rx_rate_limiter")]
+    rx_bandwidth: Option<u64>,
+    /// Remove the receive rate limiter
+    #[arg(long)]
+    no_rx_rate_limiter: bool,
+    /// Limit sent traffic to this many bytes per second
+    #[arg(long, conflicts_with = "no_tx_rate_limiter")]
+    tx_bandwidth: Option<u64>,
+    /// Remove the transmit rate limiter
+    #[arg(long)]
+    no_tx_rate_limiter: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BootSourceCommand {
+    Set(BootSourceArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct BootSourceArgs {
+    /// Uncompressed kernel image on the host
+    #[arg(long)]
+    kernel: String,
+    #[arg(long)]
+    initrd: Option<String>,
+    /// Kernel command line
+    #[arg(long)]
+    boot_args: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ActionCommand {
+    Start,
+    CtrlAltDel,
+    FlushMetrics,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotCommand {
+    Create(SnapshotCreateArgs),
+    Load(SnapshotLoadArgs),
+    /// Load the latest snapshot of a chain manifest into a fresh VMM
+    Restore(SnapshotRestoreArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotCreateArgs {
+    /// Where to write the VM state
+    #[arg(long)]
+    path: String,
+    /// Where to write guest memory
+    #[arg(long)]
+    mem: String,
+    /// `full` or `diff`
+    #[arg(long = "type", default_value = "full")]
+    snapshot_type: SnapshotType,
+    /// Pause the VM for the snapshot and resume it afterwards
+    #[arg(long)]
+    pause: bool,
+    /// Keep the VM paused after a `--pause` snapshot
+    #[arg(long, requires = "pause")]
+    leave_paused: bool,
+    /// Record the snapshot in this chain manifest, creating it if needed
+    #[arg(long, value_name = "MANIFEST")]
+    chain: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotLoadArgs {
+    /// VM state file to load
+    #[arg(long)]
+    path: String,
+    /// Guest memory file to load
+    #[arg(long, required_unless_present = "mem_uds", conflicts_with = "mem_uds")]
+    mem: Option<String>,
+    /// Socket of a userfaultfd page fault handler serving guest memory
+    #[arg(long)]
+    mem_uds: Option<String>,
+    /// Keep tracking dirty pages for later diff snapshots
+    #[arg(long)]
+    enable_diff_snapshots: bool,
+    /// Resume the VM once loaded
+    #[arg(long)]
+    resume: bool,
+    /// Attach a guest interface to another tap device, as IFACE=TAP
+    #[arg(long = "net-override", value_name = "IFACE=TAP", value_parser = parse_net_override)]
+    net_overrides: Vec<NetworkOverride>,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotRestoreArgs {
+    /// Chain manifest whose latest snapshot is restored
+    #[arg(long, value_name = "MANIFEST")]
+    chain: PathBuf,
+    /// Leave the VM paused instead of resuming it
+    #[arg(long)]
+    paused: bool,
+    /// Attach a guest interface to another tap device, as IFACE=TAP
+    #[arg(long = "net-override", value_name = "IFACE=TAP", value_parser = parse_net_override)]
+    net_overrides: Vec<NetworkOverride>,
+    /// Seconds to wait for the API and the restore to finish
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BalloonCommand {
+    Get,
+    Set(BalloonArgs),
+    Stats,
+}
+
+#[derive(Debug, Args)]
+pub struct BalloonArgs {
+    /// Target balloon size in MiB
+    #[arg(long)]
+    amount_mib: u32,
+    /// Deflate the balloon when the guest runs low on memory
+    #[arg(long)]
+    deflate_on_oom: bool,
+    /// Seconds between statistics updates; 0 disables them
+    #[arg(long)]
+    stats_interval: Option<u32>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MmdsCommand {
+    /// Print the data store, or the value at `--pointer`
+    Get {
+        /// JSON pointer of the value to print, e.g. `/latest/meta-data`
+        #[arg(long)]
+        pointer: Option<String>,
+    },
+    /// Replace the data store with a JSON file
+    Put {
+        /// JSON file to send, or `-` for stdin
+        #[arg(short, long)]
+        file: PathBuf,
+        #[command(flatten)]
+        limit: MmdsSizeLimit,
+    },
+    /// Merge a JSON file into the data store
+    Patch {
+        /// JSON merge patch to send, or `-` for stdin
+        #[arg(short, long)]
+        file: PathBuf,
+        #[command(flatten)]
+        limit: MmdsSizeLimit,
+    },
+    /// Set the value at a JSON pointer, creating the objects above it
+    Set {
+        /// e.g. `/latest/meta-data/instance-id`
+        pointer: String,
+        /// Parsed as JSON if it is valid JSON, otherwise sent as a string
+        value: String,
+        /// Send the value as a string even if it is valid JSON
+        #[arg(long)]
+        string: bool,
+        #[command(flatten)]
+        limit: MmdsSizeLimit,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct MmdsSizeLimit {
+    /// Largest body to send in bytes; match Firecracker's --mmds-size-limit
+    #[arg(long, default_value_t = MMDS_DEFAULT_SIZE_LIMIT)]
+    size_limit: usize,
+}
+
+impl Command {
+    /// The MMDS body size limit the command was given, for the client to
+    /// enforce.
+    pub fn mmds_size_limit(&self) -> Option<usize> {
+        match self {
+            Command::Mmds(
+                MmdsCommand::Put { limit, .. }
+                | MmdsCommand::Patch { limit, .. }
+                | MmdsCommand::Set { limit, .. },
+            ) => Some(limit.size_limit),
+            _ => None,
+        }
+    }
+
+    pub async fn run(
+        self,
+        client: &FirecrackerClient,
+        output: OutputFormat,
+    ) -> Result<(), FirecrackerError> {
+        match self {
+            Command::MachineConfig(MachineConfigCommand::Get) => {
+                output.print(&client.get_machine_config().await?)
+            }
+            Command::VmConfig(VmConfigCommand::Get) => output.print(&client.get_vm_config().await?),
+            Command::MachineConfig(MachineConfigCommand::Set(args)) => {
+                let mut builder = MachineConfig::builder(args.vcpus, args.mem_mib);
+                if args.smt {
+                    builder = builder.smt(true);
+                }
+                if args.track_dirty_pages {
+                    builder = builder.track_dirty_pages(true);
+                }
+                if let Some(huge_pages) = args.huge_pages {
+                    builder = builder.huge_pages(huge_pages);
+                }
+                client.put_machine_config(&builder.build()?).await
+            }
+            Command::Drive(DriveCommand::Put(args)) => {
+                let mut builder = Drive::builder(&args.drive_id)
+                    .root_device(args.root)
+                    .read_only(args.read_only);
+                if let Some(path) = args.path {
+                    builder = builder.path_on_host(path);
+                }
+                if let Some(socket) = args.socket {
+                    builder = builder.socket(socket);
+                }
+                if let Some(partuuid) = args.partuuid {
+                    builder = builder.partuuid(partuuid);
+                }
+                if let Some(cache_type) = args.cache_type {
+                    builder = builder.cache_type(cache_type);
+                }
+                if let Some(io_engine) = args.io_engine {
+                    builder = builder.io_engine(io_engine);
+                }
+                if let Some(bandwidth) = args.bandwidth {
+                    builder = builder.rate_limiter(RateLimiter::bandwidth(bandwidth));
+                }
+                client.put_drive(&args.drive_id, &builder.build()?).await
+            }
+            Command::Drive(DriveCommand::Patch(args)) => {
+                let mut update = DriveUpdate::new(&args.drive_id);
+                update.path_on_host = args.path;
+                update.rate_limiter = patch_limiter(args.bandwidth, args.no_rate_limiter);
+                client.patch_drive(&args.drive_id, &update).await
+            }
+            Command::Net(NetCommand::Put(args)) => {
+                let mut builder = NetworkInterface::builder(&args.iface_id, args.host_dev);
+                if let Some(mac) = args.mac {
+                    builder = builder.guest_mac(mac);
+                }
+                client
+                    .put_network_interface(&args.iface_id, &builder.build()?)
+                    .await
+            }
+            Command::Net(NetCommand::Patch(args)) => {
+                let mut update = NetworkInterfaceUpdate::new(&args.iface_id);
+                update.rx_rate_limiter = patch_limiter(args.rx_bandwidth, args.no_rx_rate_limiter);
+                update.tx_rate_limiter = patch_limiter(args.tx_bandwidth, args.no_tx_rate_limiter);
+                client
+                    .patch_network_interface(&args.iface_id, &update)
+                    .await
+            }
+            Command::BootSource(BootSourceCommand::Set(args)) => {
+                let mut builder = BootSource::builder(args.kernel);
+                if let Some(initrd) = args.initrd {
+                    builder = builder.initrd_path(initrd);
+                }
+                if let Some(boot_args) = args.boot_args {
+                    builder = builder.boot_args(boot_args);
+                }
+                client.put_boot_source(&builder.build()?).await
+            }
+            Command::Action(action) => {
+                let action_type = match action {
+                    ActionCommand::Start => ActionType::InstanceStart,
+                    ActionCommand::CtrlAltDel => ActionType::SendCtrlAltDel,
+                    ActionCommand::FlushMetrics => ActionType::FlushMetrics,
+                };
+                ActionOperations::create_sync_action(client, &InstanceActionInfo::from(action_type))
+                    .await
+            }
+            Command::Snapshot(SnapshotCommand::Create(args)) => {
+                output.print(&create_snapshot(client, args).await?)
+            }
+            Command::Snapshot(SnapshotCommand::Load(args)) => {
+                output.print(&load_snapshot(client, args).await?)
+            }
+            Command::Snapshot(SnapshotCommand::Restore(args)) => {
+                output.print(&restore_snapshot(client, args).await?)
+            }
+            Command::Balloon(BalloonCommand::Get) => {
+                output.print(&client.get_balloon_config().await?)
+            }
+            Command::Balloon(BalloonCommand::Set(args)) => {
+                let mut builder = Balloon::builder(args.amount_mib);
+                if args.deflate_on_oom {
+                    builder = builder.deflate_on_oom(true);
+                }
+                if let Some(interval) = args.stats_interval {
+                    builder = builder.stats_polling_interval_s(interval);
+                }
+                client.put_balloon_config(&builder.build()?).await
+            }
+            Command::Balloon(BalloonCommand::Stats) => {
+                output.print(&client.get_balloon_stats().await?)
+            }
+            Command::Mmds(MmdsCommand::Get { pointer }) => {
+                let data = client.get_mmds().await?;
+                match pointer {
+                    Some(pointer) => output.print(data.pointer(&pointer).ok_or_else(|| {
+                        FirecrackerError::Config(format!("nothing in the MMDS at {}", pointer))
+                    })?),
+                    None => output.print(&data),
+                }
+            }
+            Command::Mmds(MmdsCommand::Put { file, .. }) => {
+                client.put_mmds(read_json(&file)?).await
+            }
+            Command::Mmds(MmdsCommand::Patch { file, .. }) => {
+                client.patch_mmds(read_json(&file)?).await
+            }
+            Command::Mmds(MmdsCommand::Set {
+                pointer,
+                value,
+                string,
+                ..
+            }) => {
+                let value = match serde_json::from_str(&value) {
+                    Ok(json) if !string => json,
+                    _ => Value::String(value),
+                };
+                client.patch_mmds(mmds_set_patch(&pointer, value)?).await
+            }
+            Command::Version => output.print(&client.get_version().await?),
+            Command::Instance => output.print(&client.describe_instance().await?),
+            Command::Apply(args) => output.print(&apply(client, args).await?),
+            Command::Watch(args) => watch(client, args, output == OutputFormat::Json).await,
+            Command::Profiles(_) => unreachable!("profiles commands run without a client"),
+        }
+    }
+}
+
+// Validates before sending anything, so a bad file fails with every problem
+// listed rather than at the first section Firecracker rejects
+async fn apply(
+    client: &FirecrackerClient,
+    args: ApplyArgs,
+) -> Result<ApplyReport, FirecrackerError> {
+    let config = read_config_file(&args.file)?;
+    client.validate_config(&config)?;
+    let plan = client.plan_config(&config)?;
+    if !args.dry_run {
+        // Applying lints the config again, so keep one copy of its warnings
+        client.take_warnings();
+        client.apply_config(&config).await?;
+    }
+
+    Ok(ApplyReport {
+        dry_run: args.dry_run,
+        sections: plan
+            .into_iter()
+            .map(|request| SectionReport {
+                section: request.section,
+                request: format!("{} /{}", request.method, request.path),
+                status: if args.dry_run { "planned" } else { "applied" },
+                body: args.dry_run.then_some(request.body),
+            })
+            .collect(),
+        warnings: client
+            .take_warnings()
+            .iter()
+            .map(|warning| format!("warning {}", warning))
+            .collect(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotReport {
+    snapshot_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mem_file_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mem_uds_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot_type: Option<SnapshotType>,
+    /// VM state afterwards, when the command knows it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    /// Sizes of the snapshot files found on this host; empty when the VMM
+    /// writes them somewhere this host can't see
+    files: Vec<LocalFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct LocalFile {
+    path: String,
+    size_bytes: u64,
+}
+
+fn local_files(paths: &[&str]) -> Vec<LocalFile> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+            Some(LocalFile {
+                path: path.to_string(),
+                size_bytes: metadata.len(),
+            })
+        })
+        .collect()
+}
+
+// A Diff snapshot without a parent in the chain is refused before the VM is
+// paused; the manifest is only written once the snapshot exists
+async fn create_snapshot(
+    client: &FirecrackerClient,
+    args: SnapshotCreateArgs,
+) -> Result<SnapshotReport, FirecrackerError> {
+    let params = SnapshotCreateParams::builder(&args.path, &args.mem)
+        .snapshot_type(args.snapshot_type)
+        .build()?;
+    let mut chain = match &args.chain {
+        Some(path) if path.exists() => Some(SnapshotChain::load(path)?),
+        Some(path) => Some(SnapshotChain::new(path)),
+        None => None,
+    };
+    if let Some(chain) = chain.as_mut() {
+        chain.record(&params)?;
+    }
+
+    let state = if args.pause {
+        let options = SafeSnapshotOptions {
+            leave_paused: args.leave_paused,
+            ..Default::default()
+        };
+        client
+            .create_snapshot_safe(&params, options)
+            .await?
+            .into_result()?;
+        Some(
+            if args.leave_paused {
+                "Paused"
+            } else {
+                "Running"
+            }
+            .to_string(),
+        )
+    } else {
+        client.create_snapshot(&params).await?;
+        None
+    };
+    if let Some(chain) = chain {
+        chain.save()?;
+    }
+
+    Ok(SnapshotReport {
+        files: local_files(&[&args.path, &args.mem]),
+        snapshot_path: args.path,
+        mem_file_path: Some(args.mem),
+        mem_uds_path: None,
+        snapshot_type: Some(args.snapshot_type),
+        state,
+    })
+}
+
+async fn load_snapshot(
+    client: &FirecrackerClient,
+    args: SnapshotLoadArgs,
+) -> Result<SnapshotReport, FirecrackerError> {
+    let mut params = match (&args.mem, &args.mem_uds) {
+        (Some(mem), _) => SnapshotLoadParams::with_mem_file(&args.path, mem),
+        (None, Some(socket)) => SnapshotLoadParams::with_mem_backend(
+            &args.path,
+            MemBackend::Uds {
+                backend_path: socket.clone(),
+            },
+        ),
+        // clap requires one of the two
+        (None, None) => unreachable!(),
+    };
+    if args.enable_diff_snapshots {
+        params.enable_diff_snapshots = Some(true);
+    }
+    if args.resume {
+        params.resume_vm = Some(true);
+    }
+    params.network_overrides = args.net_overrides;
+    client.load_snapshot(&params).await?;
+
+    let mut files = vec![args.path.as_str()];
+    files.extend(args.mem.as_deref());
+    Ok(SnapshotReport {
+        files: local_files(&files),
+        snapshot_path: args.path,
+        mem_file_path: args.mem,
+        mem_uds_path: args.mem_uds,
+        snapshot_type: None,
+        state: None,
+    })
+}
+
+async fn restore_snapshot(
+    client: &FirecrackerClient,
+    args: SnapshotRestoreArgs,
+) -> Result<SnapshotReport, FirecrackerError> {
+    let chain = SnapshotChain::load(&args.chain)?;
+    let plan = chain.restore_plan()?;
+    let options = RestoreOptions {
+        resume: !args.paused,
+        network_overrides: args.net_overrides,
+        timeout: Duration::from_secs(args.timeout),
+    };
+    let info = restore(client, chain, options).await?;
+
+    Ok(SnapshotReport {
+        files: local_files(&[&plan.snapshot_path, &plan.base_mem_file_path]),
+        snapshot_path: plan.snapshot_path,
+        mem_file_path: Some(plan.base_mem_file_path),
+        mem_uds_path: None,
+        snapshot_type: None,
+        state: Some(info.state),
+    })
+}
+
+fn parse_net_override(value: &str) -> Result<NetworkOverride, String> {
+    match value.split_once('=') {
+        Some((iface_id, host_dev_name)) if !iface_id.is_empty() && !host_dev_name.is_empty() => {
+            Ok(NetworkOverride::new(iface_id, host_dev_name))
+        }
+        _ => Err(format!("expected IFACE=TAP, got `{}`", value)),
+    }
+}
+
+// The PATCH value for a rate limiter given `--bandwidth` and its `--no-*`
+// counterpart; clap rejects passing both
+fn patch_limiter(bandwidth: Option<u64>, remove: bool) -> Patchable<RateLimiter> {
+    match bandwidth {
+        Some(bytes_per_sec) => Patchable::Value(RateLimiter::bandwidth(bytes_per_sec)),
+        None if remove => Patchable::Null,
+        None => Patchable::Absent,
+    }
+}
+
+// Reads JSON from `path`, or stdin for `-`. Malformed input is bad data
+// rather than a protocol error, so it fails like validation does.
+fn read_json(path: &Path) -> Result<Value, FirecrackerError> {
+    let fs_error = |source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    };
+    let (name, contents) = if path == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(fs_error)?;
+        ("stdin".to_string(), contents)
+    } else {
+        let contents = std::fs::read_to_string(path).map_err(fs_error)?;
+        (path.display().to_string(), contents)
+    };
+    serde_json::from_str(&contents)
+        .map_err(|e| FirecrackerError::Config(format!("{} is not valid JSON: {}", name, e)))
+}
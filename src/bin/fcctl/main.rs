@@ -0,0 +1,216 @@
+//! `fcctl`, a command line client for the Firecracker API. Each subcommand
+//! maps onto one of the client's operations.
+
+mod commands;
+mod output;
+mod profiles;
+mod watch;
+
+use clap::Parser;
+use commands::Command;
+use firecracker_http_client::error::FirecrackerError;
+use firecracker_http_client::validation::{error_paths, ConfigWarning};
+use firecracker_http_client::FirecrackerClient;
+use output::OutputFormat;
+use profiles::{ConnectionArgs, ProfilesFile};
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+// Exit codes follow sysexits(3) where one fits, so scripts can tell bad input
+// apart from an unreachable VMM or one that refused the request
+const EXIT_API_ERROR: u8 = 1;
+const EXIT_DATAERR: u8 = 65;
+const EXIT_UNAVAILABLE: u8 = 69;
+const EXIT_SOFTWARE: u8 = 70;
+const EXIT_IOERR: u8 = 74;
+const EXIT_PROTOCOL: u8 = 76;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "fcctl",
+    version,
+    about = "Control a Firecracker microVM over its API"
+)]
+struct Cli {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// How to print responses
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Pretty)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+fn exit_code(err: &FirecrackerError) -> u8 {
+    match err {
+        FirecrackerError::Api { .. } => EXIT_API_ERROR,
+        FirecrackerError::Validation(_)
+        | FirecrackerError::InvalidConfig(_)
+        | FirecrackerError::InvalidPath(_)
+        | FirecrackerError::Config(_) => EXIT_DATAERR,
+        FirecrackerError::HttpClient(e) if e.is_decode() => EXIT_PROTOCOL,
+        FirecrackerError::HttpClient(_)
+        | FirecrackerError::ApiSocket { .. }
+        | FirecrackerError::Timeout { .. }
+        | FirecrackerError::RequestTimeout { .. }
+        | FirecrackerError::Unsupported { .. }
+        | FirecrackerError::UnsupportedVmmVersion { .. } => EXIT_UNAVAILABLE,
+        FirecrackerError::Decode { .. } | FirecrackerError::Serialization(_) => EXIT_PROTOCOL,
+        FirecrackerError::FileSystem { .. } | FirecrackerError::InsufficientSpace { .. } => {
+            EXIT_IOERR
+        }
+        _ => EXIT_SOFTWARE,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorReport<'a> {
+    exit_code: u8,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldError<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    findings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WarningReport<'a> {
+    warning: &'static str,
+    context: &'a str,
+    message: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct FieldError<'a> {
+    field: String,
+    message: &'a str,
+}
+
+fn field_errors(err: &FirecrackerError) -> Vec<FieldError<'_>> {
+    match err {
+        FirecrackerError::Validation(errors) => error_paths(errors)
+            .into_iter()
+            .map(|(field, error)| FieldError {
+                field,
+                message: error
+                    .message
+                    .as_deref()
+                    .unwrap_or_else(|| error.code.as_ref()),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Validation failures list one problem per line with the field it is about.
+// With `--output json` the error is a single JSON object instead, so scripts
+// never have to parse the text.
+fn report_error(err: &FirecrackerError, output: OutputFormat) {
+    if output == OutputFormat::Json {
+        let report = ErrorReport {
+            exit_code: exit_code(err),
+            message: err.to_string(),
+            fields: field_errors(err),
+            findings: match err {
+                FirecrackerError::InvalidConfig(report) => {
+                    report.findings.iter().map(ToString::to_string).collect()
+                }
+                _ => Vec::new(),
+            },
+        };
+        match serde_json::to_string(&report) {
+            Ok(json) => eprintln!("{}", json),
+            Err(_) => eprintln!("error: {}", err),
+        }
+        return;
+    }
+
+    match err {
+        FirecrackerError::Validation(_) => {
+            eprintln!("error: validation failed");
+            for FieldError { field, message } in field_errors(err) {
+                if field == "__all__" {
+                    eprintln!("  {}", message);
+                } else {
+                    eprintln!("  {}: {}", field, message);
+                }
+            }
+        }
+        FirecrackerError::InvalidConfig(report) => {
+            eprintln!("error: invalid VM configuration");
+            for finding in &report.findings {
+                eprintln!("  {}", finding);
+            }
+        }
+        err => eprintln!("error: {}", err),
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), FirecrackerError> {
+    let env = |name: &str| std::env::var(name).ok();
+    let profiles_path = cli.connection.profiles_path(env);
+
+    if let Command::Profiles(command) = cli.command {
+        let path = profiles_path.ok_or_else(|| {
+            FirecrackerError::Config(
+                "no profiles file; pass --profiles-file or set HOME".to_string(),
+            )
+        })?;
+        return cli.output.print(&command.run(&path, &cli.connection)?);
+    }
+
+    let file = match &profiles_path {
+        Some(path) => ProfilesFile::load(path)?,
+        None => ProfilesFile::default(),
+    };
+    let connection = cli.connection.resolve(env, &file)?;
+    let mut client = FirecrackerClient::new(&connection.url).await?;
+    if let Some(timeout) = connection.timeout {
+        client = client.with_timeout(timeout);
+    }
+    if let Some(limit) = cli.command.mmds_size_limit() {
+        client = client.with_max_mmds_body_size(limit);
+    }
+    let result = cli.command.run(&client, cli.output).await;
+    report_warnings(&client.take_warnings(), cli.output);
+    result
+}
+
+// Warnings go to stderr, in yellow on a terminal, so they never mix with
+// the command's output
+fn report_warnings(warnings: &[ConfigWarning], output: OutputFormat) {
+    let color = std::io::stderr().is_terminal();
+    for warning in warnings {
+        if output == OutputFormat::Json {
+            let report = WarningReport {
+                warning: warning.rule,
+                context: &warning.context,
+                message: &warning.message,
+            };
+            if let Ok(json) = serde_json::to_string(&report) {
+                eprintln!("{}", json);
+            }
+        } else if color {
+            eprintln!("\x1b[33mwarning {}\x1b[0m", warning);
+        } else {
+            eprintln!("warning {}", warning);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let output = cli.output;
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            report_error(&err, output);
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
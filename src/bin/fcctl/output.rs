@@ -0,0 +1,162 @@
+//! Rendering of responses for every subcommand. JSON and YAML serialize the
+//! models as they are; the table flattens them into one row per field.
+
+use clap::ValueEnum;
+use firecracker_http_client::error::FirecrackerError;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Indented JSON
+    Pretty,
+    /// One line of JSON per response
+    Json,
+    /// YAML
+    Yaml,
+    /// One `field value` row per field, nested fields joined with dots
+    Table,
+}
+
+impl OutputFormat {
+    pub fn print<T: Serialize>(self, value: &T) -> Result<(), FirecrackerError> {
+        print!("{}", self.render(value)?);
+        Ok(())
+    }
+
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String, FirecrackerError> {
+        let mut rendered = match self {
+            OutputFormat::Pretty => serde_json::to_string_pretty(value)?,
+            OutputFormat::Json => serde_json::to_string(value)?,
+            OutputFormat::Yaml => yaml(&serde_json::to_value(value)?),
+            OutputFormat::Table => table(&serde_json::to_value(value)?),
+        };
+        if !rendered.ends_with('\n') {
+            rendered.push('\n');
+        }
+        Ok(rendered)
+    }
+}
+
+// Block-style YAML. Strings are only quoted where YAML would read them as
+// something else, using JSON escapes, which YAML double quotes accept.
+fn yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_yaml(&mut out, value, 0),
+        Value::Array(items) if !items.is_empty() => write_yaml(&mut out, value, 0),
+        scalar => out.push_str(&yaml_scalar(scalar)),
+    }
+    out
+}
+
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                out.push_str(&format!("{}{}:", pad, yaml_string(key)));
+                match value {
+                    Value::Object(map) if !map.is_empty() => {
+                        out.push('\n');
+                        write_yaml(out, value, indent + 2);
+                    }
+                    Value::Array(items) if !items.is_empty() => {
+                        out.push('\n');
+                        write_yaml(out, value, indent);
+                    }
+                    scalar => out.push_str(&format!(" {}\n", yaml_scalar(scalar))),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::Object(map) if !map.is_empty() => {
+                        // Rendered one level deeper, then the first line's
+                        // indentation becomes the item marker
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, indent + 2);
+                        out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                    }
+                    Value::Array(inner) if !inner.is_empty() => {
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, indent + 2);
+                        out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                    }
+                    scalar => out.push_str(&format!("{}- {}\n", pad, yaml_scalar(scalar))),
+                }
+            }
+        }
+        scalar => out.push_str(&format!("{}{}\n", pad, yaml_scalar(scalar))),
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_string(s),
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Only strings that start with a letter, `/` or `_` are left plain, since
+// anything starting with a digit, sign or `.` may read back as a number in
+// some YAML version (`0x1F`, `1_000`, `1:20`, `.inf`); of those, only the
+// YAML 1.1 booleans and null need quoting
+fn yaml_string(s: &str) -> String {
+    const RESERVED: &[&str] = &["true", "false", "null", "yes", "no", "on", "off", "y", "n"];
+    let plain = s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '_')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || " _-./:=,()".contains(c))
+        && !s.ends_with([' ', ':'])
+        && !s.contains(": ")
+        && !RESERVED.contains(&s.to_ascii_lowercase().as_str());
+    if plain {
+        s.to_string()
+    } else {
+        Value::String(s.to_string()).to_string()
+    }
+}
+
+fn table(value: &Value) -> String {
+    let mut rows = Vec::new();
+    flatten(String::new(), value, &mut rows);
+    let width = rows.iter().map(|(field, _)| field.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(field, value)| {
+            if field.is_empty() {
+                format!("{}\n", value)
+            } else {
+                format!("{:width$}  {}\n", field, value, width = width)
+            }
+        })
+        .collect()
+}
+
+fn flatten(prefix: String, value: &Value, rows: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                flatten(join(key), value, rows);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(format!("{}[{}]", prefix, i), item, rows);
+            }
+        }
+        Value::Object(_) | Value::Array(_) => rows.push((prefix, "-".to_string())),
+        Value::Null => rows.push((prefix, "-".to_string())),
+        Value::String(s) => rows.push((prefix, s.clone())),
+        other => rows.push((prefix, other.to_string())),
+    }
+}
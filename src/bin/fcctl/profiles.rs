@@ -0,0 +1,271 @@
+//! Named connection profiles, kept in `~/.config/fcctl/profiles.toml`:
+//!
+//! ```toml
+//! default = "vm42"
+//!
+//! [profiles.vm42]
+//! url = "http://10.0.0.42:8080"
+//! timeout_secs = 10
+//! ```
+//!
+//! Each connection setting is taken from the first place that has it: the
+//! command line flag, then the environment, then the selected profile, then
+//! the built-in default.
+
+use clap::{Args, Subcommand};
+use firecracker_http_client::error::FirecrackerError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_URL: &str = "http://localhost:8080";
+
+pub const ENV_URL: &str = "FCCTL_URL";
+pub const ENV_PROFILE: &str = "FCCTL_PROFILE";
+pub const ENV_TIMEOUT: &str = "FCCTL_TIMEOUT";
+pub const ENV_PROFILES_FILE: &str = "FCCTL_PROFILES";
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfilesFile {
+    /// Profile used when none is selected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Base URL of the Firecracker API
+    pub url: String,
+    /// Limit for each request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl ProfilesFile {
+    /// Reads the profiles at `path`; a missing file has none.
+    pub fn load(path: &Path) -> Result<Self, FirecrackerError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(FirecrackerError::FileSystem {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        let file: Self = toml::from_str(&contents)
+            .map_err(|e| FirecrackerError::Config(format!("{}: {}", path.display(), e)))?;
+        if let Some(default) = &file.default {
+            if !file.profiles.contains_key(default) {
+                return Err(FirecrackerError::Config(format!(
+                    "{}: default profile {:?} is not defined",
+                    path.display(),
+                    default
+                )));
+            }
+        }
+        Ok(file)
+    }
+
+    /// Writes the profiles to `path`, creating its directory if needed and
+    /// replacing the previous file atomically.
+    pub fn save(&self, path: &Path) -> Result<(), FirecrackerError> {
+        let fs_error = |path: &Path, source| FirecrackerError::FileSystem {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| fs_error(dir, e))?;
+        }
+        let contents = toml::to_string(self)
+            .map_err(|e| FirecrackerError::Config(format!("{}: {}", path.display(), e)))?;
+        let tmp_path = path.with_extension("toml.tmp");
+        std::fs::write(&tmp_path, contents).map_err(|e| fs_error(&tmp_path, e))?;
+        std::fs::rename(&tmp_path, path).map_err(|e| fs_error(path, e))
+    }
+}
+
+/// Where the profiles live: `$FCCTL_PROFILES`, else `fcctl/profiles.toml`
+/// under `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn default_path(env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    if let Some(path) = env(ENV_PROFILES_FILE) {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = match env("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("fcctl").join("profiles.toml"))
+}
+
+/// The connection flags given on the command line.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConnectionArgs {
+    /// Base URL of the Firecracker API [env: FCCTL_URL] [default: http://localhost:8080]
+    #[arg(long, global = true)]
+    pub url: Option<String>,
+
+    /// Connection profile to use [env: FCCTL_PROFILE]
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Seconds each request may take [env: FCCTL_TIMEOUT]
+    #[arg(long, global = true, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Profiles file to use instead of ~/.config/fcctl/profiles.toml [env: FCCTL_PROFILES]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub profiles_file: Option<PathBuf>,
+}
+
+/// Where to connect, after applying the precedence rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub url: String,
+    pub timeout: Option<Duration>,
+}
+
+impl ConnectionArgs {
+    pub fn profiles_path(&self, env: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+        self.profiles_file.clone().or_else(|| default_path(env))
+    }
+
+    /// Resolves the connection settings from the flags, `env` and the
+    /// profiles in `file`. Naming a profile that doesn't exist is an error,
+    /// even when the flags override all of its settings.
+    pub fn resolve(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+        file: &ProfilesFile,
+    ) -> Result<Connection, FirecrackerError> {
+        let name = self
+            .profile
+            .clone()
+            .or_else(|| env(ENV_PROFILE))
+            .or_else(|| file.default.clone());
+        let profile =
+            match &name {
+                Some(name) => Some(file.profiles.get(name).ok_or_else(|| {
+                    FirecrackerError::Config(format!("unknown profile {:?}", name))
+                })?),
+                None => None,
+            };
+
+        let env_timeout = env(ENV_TIMEOUT)
+            .map(|secs| {
+                secs.parse::<u64>().map_err(|_| {
+                    FirecrackerError::Config(format!(
+                        "{} must be a number of seconds, got {:?}",
+                        ENV_TIMEOUT, secs
+                    ))
+                })
+            })
+            .transpose()?;
+
+        Ok(Connection {
+            url: self
+                .url
+                .clone()
+                .or_else(|| env(ENV_URL))
+                .or_else(|| profile.map(|p| p.url.clone()))
+                .unwrap_or_else(|| DEFAULT_URL.to_string()),
+            timeout: self
+                .timeout
+                .or(env_timeout)
+                .or_else(|| profile.and_then(|p| p.timeout_secs))
+                .map(Duration::from_secs),
+        })
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfilesCommand {
+    /// Show the saved profiles
+    List,
+    /// Save the `--url` and `--timeout` given as a profile, replacing any
+    /// with the same name
+    Add(AddProfileArgs),
+    /// Delete a profile
+    Remove { name: String },
+}
+
+#[derive(Debug, Args)]
+pub struct AddProfileArgs {
+    name: String,
+    /// Use this profile when none is selected
+    #[arg(long)]
+    default: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileListing {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout_secs: Option<u64>,
+    default: bool,
+}
+
+impl ProfilesCommand {
+    /// Runs the command against the profiles at `path` and returns the
+    /// profiles afterwards.
+    pub fn run(
+        self,
+        path: &Path,
+        connection: &ConnectionArgs,
+    ) -> Result<BTreeMap<String, ProfileListing>, FirecrackerError> {
+        let mut file = ProfilesFile::load(path)?;
+        match self {
+            ProfilesCommand::List => {}
+            ProfilesCommand::Add(args) => {
+                let url = connection.url.clone().ok_or_else(|| {
+                    FirecrackerError::Config("profiles add needs --url".to_string())
+                })?;
+                url::Url::parse(&url).map_err(|e| {
+                    FirecrackerError::Config(format!("invalid URL {:?}: {}", url, e))
+                })?;
+                file.profiles.insert(
+                    args.name.clone(),
+                    Profile {
+                        url,
+                        timeout_secs: connection.timeout,
+                    },
+                );
+                if args.default {
+                    file.default = Some(args.name);
+                }
+                file.save(path)?;
+            }
+            ProfilesCommand::Remove { name } => {
+                if file.profiles.remove(&name).is_none() {
+                    return Err(FirecrackerError::Config(format!(
+                        "unknown profile {:?}",
+                        name
+                    )));
+                }
+                if file.default.as_ref() == Some(&name) {
+                    file.default = None;
+                }
+                file.save(path)?;
+            }
+        }
+
+        Ok(file
+            .profiles
+            .into_iter()
+            .map(|(name, profile)| {
+                let listing = ProfileListing {
+                    default: file.default.as_ref() == Some(&name),
+                    url: profile.url,
+                    timeout_secs: profile.timeout_secs,
+                };
+                (name, listing)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,179 @@
+//! `fcctl watch`: follows the instance state, balloon statistics and the
+//! metrics FIFO until interrupted.
+
+use clap::Args;
+use firecracker_http_client::balloon::stream_balloon_stats;
+use firecracker_http_client::error::FirecrackerError;
+use firecracker_http_client::instance::watch_instance;
+use firecracker_http_client::metrics::{stream_metrics, FirecrackerMetrics};
+use firecracker_http_client::{BalloonStats, FirecrackerClient, InstanceInfo};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Metrics FIFO or file to follow, as configured with PUT /metrics
+    #[arg(long, value_name = "PATH")]
+    metrics: Option<PathBuf>,
+    /// How often to poll the API, e.g. `1s` or `500ms`
+    #[arg(long, default_value = "1s", value_parser = parse_interval)]
+    interval: Duration,
+    /// Don't poll the balloon statistics
+    #[arg(long)]
+    no_balloon: bool,
+    /// Print newline-delimited JSON events
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum WatchEvent {
+    Instance(InstanceInfo),
+    Balloon(BalloonStats),
+    /// Counters Firecracker counted since the previous metrics document
+    Metrics {
+        elapsed_ms: u128,
+        changes: BTreeMap<String, u64>,
+    },
+    Error {
+        source: &'static str,
+        message: String,
+    },
+}
+
+impl WatchEvent {
+    fn error(source: &'static str, err: &FirecrackerError) -> Self {
+        WatchEvent::Error {
+            source,
+            message: err.to_string(),
+        }
+    }
+
+    fn print(&self, json: bool) -> Result<(), FirecrackerError> {
+        if json {
+            println!("{}", serde_json::to_string(self)?);
+            return Ok(());
+        }
+        match self {
+            WatchEvent::Instance(info) => println!("instance {} {}", info.id, info.state),
+            WatchEvent::Balloon(stats) => println!(
+                "balloon  actual {} MiB, target {} MiB{}",
+                stats.actual_mib,
+                stats.target_mib,
+                stats
+                    .free_memory
+                    .map(|free| format!(", guest free {} MiB", free / (1024 * 1024)))
+                    .unwrap_or_default()
+            ),
+            WatchEvent::Metrics {
+                elapsed_ms,
+                changes,
+            } => {
+                let changes: Vec<_> = changes
+                    .iter()
+                    .map(|(key, value)| format!("{} +{}", key, value))
+                    .collect();
+                println!("metrics  +{}ms {}", elapsed_ms, changes.join(", "));
+            }
+            WatchEvent::Error { source, message } => eprintln!("{}: {}", source, message),
+        }
+        Ok(())
+    }
+}
+
+// Only changes are printed: state transitions, balloon statistics that
+// differ from the previous poll and counters that grew
+pub async fn watch(
+    client: &FirecrackerClient,
+    args: WatchArgs,
+    json: bool,
+) -> Result<(), FirecrackerError> {
+    let json = args.json || json;
+    let mut instance = watch_instance(client, args.interval).boxed();
+    let mut balloon: Option<BoxStream<'_, _>> =
+        (!args.no_balloon).then(|| stream_balloon_stats(client, args.interval).boxed());
+    let mut metrics: Option<BoxStream<'_, _>> = args
+        .metrics
+        .as_ref()
+        .map(|path| stream_metrics(path).boxed());
+    let mut last_stats: Option<BalloonStats> = None;
+    let mut last_metrics: Option<FirecrackerMetrics> = None;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        let event = tokio::select! {
+            // Dropping the streams is enough to stop: the metrics FIFO is
+            // read without blocking, so nothing holds up the exit
+            _ = &mut ctrl_c => return Ok(()),
+            Some(item) = instance.next() => match item {
+                Ok(info) => WatchEvent::Instance(info),
+                Err(err) => WatchEvent::error("instance", &err),
+            },
+            item = balloon.as_mut().unwrap().next(), if balloon.is_some() => match item {
+                Some(Ok(stats)) if last_stats.as_ref() == Some(&stats) => continue,
+                Some(Ok(stats)) => {
+                    last_stats = Some(stats.clone());
+                    WatchEvent::Balloon(stats)
+                }
+                // No balloon device; stop asking
+                Some(Err(err @ FirecrackerError::Api { .. })) => {
+                    balloon = None;
+                    WatchEvent::error("balloon", &err)
+                }
+                Some(Err(err)) => WatchEvent::error("balloon", &err),
+                None => {
+                    balloon = None;
+                    continue;
+                }
+            },
+            item = metrics.as_mut().unwrap().next(), if metrics.is_some() => match item {
+                Some(Ok(current)) => {
+                    let previous = last_metrics.replace(current.clone());
+                    // The first document's counts cover an unknown span, so
+                    // it only marks where the elapsed time starts
+                    let Some(previous) = previous else { continue };
+                    let delta = current.delta(&previous);
+                    WatchEvent::Metrics {
+                        elapsed_ms: delta.elapsed.as_millis(),
+                        changes: delta.values.into_iter().filter(|(_, v)| *v > 0).collect(),
+                    }
+                }
+                Some(Err(err)) => WatchEvent::error("metrics", &err),
+                // Only I/O errors end the stream, and they were reported
+                None => {
+                    metrics = None;
+                    continue;
+                }
+            },
+        };
+        event.print(json)?;
+    }
+}
+
+// Accepts `500ms`, `1s`, `2m` or a bare number of seconds
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid interval `{}`", value))?;
+    let interval = match unit {
+        "ms" => Duration::from_millis(number),
+        "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number * 60),
+        _ => return Err(format!("unknown unit in `{}`; use ms, s or m", value)),
+    };
+    if interval.is_zero() {
+        return Err("interval must be greater than zero".to_string());
+    }
+    Ok(interval)
+}
@@ -1,6 +1,9 @@
 use crate::models::BootSource;
+use crate::paths;
+use crate::validation::{combine_errors, validate_existing_path};
 use crate::FirecrackerError;
 use async_trait::async_trait;
+use validator::ValidationErrors;
 
 #[async_trait]
 pub trait BootSourceOperations {
@@ -10,16 +13,49 @@ pub trait BootSourceOperations {
 #[async_trait]
 impl BootSourceOperations for crate::FirecrackerClient {
     async fn put_boot_source(&self, boot_source: &BootSource) -> Result<(), FirecrackerError> {
-        let url = self.url("boot-source")?;
-        let response = self.client.put(url).json(boot_source).send().await?;
+        let boot_source = boot_source.clone();
+        let boot_source = self
+            .on_local_fs(move |local| {
+                local.check_boot_source_paths(&boot_source)?;
+                local.resolve_boot_source_paths(&boot_source)
+            })
+            .await?;
+        self.put_linted(self.validated_put(paths::BOOT_SOURCE, &boot_source))
+            .await
+    }
+}
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
+impl crate::LocalPaths {
+    // Firecracker opens both images when the VM starts
+    pub(crate) fn check_boot_source_paths(
+        &self,
+        boot_source: &BootSource,
+    ) -> Result<(), ValidationErrors> {
+        combine_errors([
+            self.check_local_path(
+                "kernel_image_path",
+                &boot_source.kernel_image_path,
+                validate_existing_path,
+            ),
+            boot_source.initrd_path.as_ref().map_or(Ok(()), |path| {
+                self.check_local_path("initrd_path", path, validate_existing_path)
+            }),
+        ])
+    }
 
-        Ok(())
+    pub(crate) fn resolve_boot_source_paths(
+        &self,
+        boot_source: &BootSource,
+    ) -> Result<BootSource, FirecrackerError> {
+        Ok(BootSource {
+            kernel_image_path: self
+                .resolve_path("kernel_image_path", &boot_source.kernel_image_path)?,
+            initrd_path: boot_source
+                .initrd_path
+                .as_deref()
+                .map(|path| self.resolve_path("initrd_path", path))
+                .transpose()?,
+            ..boot_source.clone()
+        })
     }
 }
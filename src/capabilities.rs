@@ -0,0 +1,102 @@
+//! Which Firecracker releases support which API features, for code that
+//! needs the answer without a live VMM, e.g. a scheduler deciding which
+//! hosts can run a VM with vhost-user drives:
+//!
+//! ```
+//! use firecracker_http_client::capabilities::{self, Feature};
+//! use firecracker_http_client::version::Version;
+//!
+//! let capabilities = capabilities::for_version(&Version::new(1, 6, 0));
+//! assert!(capabilities.supports(Feature::Entropy));
+//! assert!(!capabilities.supports(Feature::VhostUserBlock));
+//! assert_eq!(Feature::VhostUserBlock.introduced_in(), Version::new(1, 7, 0));
+//! ```
+//!
+//! [`VersionOperations::negotiate`](crate::version::VersionOperations::negotiate)
+//! builds the connected VMM's capabilities from the same table.
+
+use crate::version::{
+    meets, Version, ENTROPY_MIN_VERSION, HUGE_PAGES_MIN_VERSION, MEM_BACKEND_MIN_VERSION,
+    MMDS_V2_MIN_VERSION, NETWORK_OVERRIDES_MIN_VERSION, SNAPSHOT_RESUME_VM_MIN_VERSION,
+    VHOST_USER_BLOCK_MIN_VERSION,
+};
+use std::fmt;
+
+pub use crate::version::Capabilities;
+
+/// An API feature that only some Firecracker releases have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    /// `PUT /entropy`, since 1.3.0
+    Entropy,
+    /// Drives backed by a vhost-user block device, since 1.7.0
+    VhostUserBlock,
+    /// `resume_vm` in snapshot load requests, since 1.0.0
+    SnapshotResumeVm,
+    /// `mem_backend` in snapshot load requests, since 1.1.0
+    SnapshotMemBackend,
+    /// `network_overrides` in snapshot load requests, since 1.6.0
+    NetworkOverrides,
+    /// MMDS version 2, with session tokens, since 1.0.0
+    MmdsV2,
+    /// `huge_pages` in the machine config, since 1.7.0
+    HugePages,
+}
+
+impl Feature {
+    /// Every feature in the table.
+    pub const ALL: &'static [Feature] = &[
+        Feature::Entropy,
+        Feature::VhostUserBlock,
+        Feature::SnapshotResumeVm,
+        Feature::SnapshotMemBackend,
+        Feature::NetworkOverrides,
+        Feature::MmdsV2,
+        Feature::HugePages,
+    ];
+
+    /// The first release with the feature.
+    pub fn introduced_in(self) -> Version {
+        match self {
+            Feature::Entropy => ENTROPY_MIN_VERSION,
+            Feature::VhostUserBlock => VHOST_USER_BLOCK_MIN_VERSION,
+            Feature::SnapshotResumeVm => SNAPSHOT_RESUME_VM_MIN_VERSION,
+            Feature::SnapshotMemBackend => MEM_BACKEND_MIN_VERSION,
+            Feature::NetworkOverrides => NETWORK_OVERRIDES_MIN_VERSION,
+            Feature::MmdsV2 => MMDS_V2_MIN_VERSION,
+            Feature::HugePages => HUGE_PAGES_MIN_VERSION,
+        }
+    }
+
+    /// Whether release `version` has the feature. Prerelease builds count as
+    /// the release they lead up to.
+    pub fn is_supported_by(self, version: &Version) -> bool {
+        meets(version, &self.introduced_in())
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Feature::Entropy => "entropy",
+            Feature::VhostUserBlock => "vhost_user_block",
+            Feature::SnapshotResumeVm => "snapshot_resume_vm",
+            Feature::SnapshotMemBackend => "snapshot_mem_backend",
+            Feature::NetworkOverrides => "network_overrides",
+            Feature::MmdsV2 => "mmds_v2",
+            Feature::HugePages => "huge_pages",
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What release `version` supports, as
+/// [`VersionSource::Manual`](crate::version::VersionSource::Manual)
+/// capabilities.
+pub fn for_version(version: &Version) -> Capabilities {
+    Capabilities::for_version(version.clone())
+}
@@ -0,0 +1,828 @@
+use crate::entropy::EntropyDeviceOperations;
+use crate::mmds::MmdsOperations;
+use crate::models::{
+    Balloon, BootSource, Drive, EntropyDevice, FullVmConfiguration, Logger, MachineConfig,
+    MmdsConfig, NetworkInterface, Vsock,
+};
+use crate::paths;
+use crate::redaction::redacted;
+use crate::validation::{
+    combine_errors, validate_host_support, validate_vm_config, AsValidationTarget, ValidationMode,
+    ValidationReport,
+};
+use crate::version::Version;
+use crate::vm::VmOperations;
+use crate::{FirecrackerError, NetworkInterfaceOperations};
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+/// Options for [`ConfigOperations::apply_config_with`].
+#[derive(Debug, Clone)]
+pub struct ApplyOptions {
+    /// Run [`validate_vm_config`] before sending anything and stop on errors.
+    /// Skipped when the client's validation mode is `Off`.
+    pub lint: bool,
+    /// Read the current configuration before sending anything and, if a
+    /// section fails, put back what the sections before it replaced. The
+    /// error is then `ConfigApply`, with a [`RollbackReport`].
+    pub rollback: bool,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            lint: true,
+            rollback: false,
+        }
+    }
+}
+
+/// What became of a section that was applied before a later one failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RollbackOutcome {
+    /// Its previous value was sent again
+    RolledBack,
+    /// Left applied, since the API has no way to undo it
+    Unrevertible(String),
+    /// Left applied, since sending the previous value failed
+    RollbackFailed(String),
+}
+
+/// The sections a failed [`ConfigOperations::apply_config_with`] had
+/// applied, in the order they were sent, and what rolling them back did.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RollbackReport {
+    pub sections: Vec<(String, RollbackOutcome)>,
+}
+
+impl RollbackReport {
+    /// Whether the VMM is back to its configuration from before the apply.
+    pub fn is_clean(&self) -> bool {
+        self.sections
+            .iter()
+            .all(|(_, outcome)| *outcome == RollbackOutcome::RolledBack)
+    }
+}
+
+impl std::fmt::Display for RollbackReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.sections.is_empty() {
+            return write!(f, "nothing was applied");
+        }
+        for (i, (section, outcome)) in self.sections.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match outcome {
+                RollbackOutcome::RolledBack => write!(f, "{} rolled back", section)?,
+                RollbackOutcome::Unrevertible(reason) => {
+                    write!(f, "{} left applied ({})", section, reason)?
+                }
+                RollbackOutcome::RollbackFailed(reason) => {
+                    write!(f, "{} left applied, rollback failed ({})", section, reason)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A VM configuration laid out the way Firecracker's `--config-file` expects
+/// it, with kebab-case section names such as `boot-source`. The sections
+/// themselves use the API models, so only the top level differs from a
+/// serialized [`FullVmConfiguration`], whose snake_case section names are
+/// accepted too.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+pub struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balloon: Option<Balloon>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "boot_source"
+    )]
+    pub boot_source: Option<BootSource>,
+    #[serde(default)]
+    pub drives: Vec<Drive>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "machine_config"
+    )]
+    pub machine_config: Option<MachineConfig>,
+    #[serde(default, alias = "network_interfaces")]
+    pub network_interfaces: Vec<NetworkInterface>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        alias = "mmds_config"
+    )]
+    pub mmds_config: Option<MmdsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vsock: Option<Vsock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logger: Option<Logger>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<crate::metrics::Metrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy: Option<EntropyDevice>,
+    // Custom CPU templates can't be applied through this client, so only
+    // the `null` Firecracker writes when there is none is accepted
+    #[serde(default, skip_serializing, rename = "cpu-config", alias = "cpu_config")]
+    _cpu_config: Option<()>,
+}
+
+impl From<FullVmConfiguration> for ConfigFile {
+    fn from(config: FullVmConfiguration) -> Self {
+        Self {
+            balloon: config.balloon,
+            boot_source: config.boot_source,
+            drives: config.drives,
+            machine_config: config.machine_config,
+            network_interfaces: config.network_interfaces,
+            mmds_config: config.mmds_config,
+            vsock: config.vsock,
+            logger: config.logger,
+            metrics: config.metrics,
+            entropy: config.entropy,
+            _cpu_config: None,
+        }
+    }
+}
+
+impl From<ConfigFile> for FullVmConfiguration {
+    fn from(file: ConfigFile) -> Self {
+        Self {
+            balloon: file.balloon,
+            boot_source: file.boot_source,
+            drives: file.drives,
+            machine_config: file.machine_config,
+            network_interfaces: file.network_interfaces,
+            mmds_config: file.mmds_config,
+            vsock: file.vsock,
+            logger: file.logger,
+            metrics: file.metrics,
+            entropy: file.entropy,
+        }
+    }
+}
+
+/// Reads a Firecracker config file from `path`.
+pub fn read_config_file(path: &Path) -> Result<FullVmConfiguration, FirecrackerError> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|source| FirecrackerError::FileSystem {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let file: ConfigFile = serde_json::from_str(&contents)?;
+    Ok(file.into())
+}
+
+/// Writes `config` to `path` as a Firecracker config file, which can be
+/// passed to `firecracker --config-file` or read back with
+/// [`read_config_file`].
+pub fn write_config_file(
+    config: &FullVmConfiguration,
+    path: &Path,
+) -> Result<(), FirecrackerError> {
+    let contents = serde_json::to_string_pretty(&ConfigFile::from(config.clone()))?;
+    std::fs::write(path, contents).map_err(|source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// A request [`ConfigOperations::apply_config`] would send for one section.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedRequest {
+    /// The section it applies, e.g. `drives[0]`
+    pub section: String,
+    pub method: &'static str,
+    pub path: String,
+    pub body: serde_json::Value,
+}
+
+#[async_trait]
+pub trait ConfigOperations {
+    /// Checks every section of `config` the way the individual `put_*` calls
+    /// would, without sending anything, and collects all the failures into a
+    /// single `Validation` error keyed by section and field, e.g.
+    /// `drives[1].path_on_host`. Errors from [`validate_vm_config`] are added
+    /// under `__all__`; if the sections are otherwise valid they are returned
+    /// as `InvalidConfig` instead. On success returns the lint warnings,
+    /// which also go to the client's warnings.
+    fn validate_config(
+        &self,
+        config: &FullVmConfiguration,
+    ) -> Result<ValidationReport, FirecrackerError>;
+
+    /// Lists the requests applying `config` sends, in order, with paths
+    /// rewritten by the client's path policy, without sending them. Bodies
+    /// the client's [`Redaction`](crate::redaction::Redaction) covers are
+    /// replaced with their length. Does not validate `config`;
+    /// use [`ConfigOperations::validate_config`] first.
+    fn plan_config(
+        &self,
+        config: &FullVmConfiguration,
+    ) -> Result<Vec<PlannedRequest>, FirecrackerError>;
+
+    /// Applies every section of `config` with the default options.
+    async fn apply_config(
+        &self,
+        config: &FullVmConfiguration,
+    ) -> Result<ValidationReport, FirecrackerError>;
+
+    /// Applies every section that is set in `config`, in an order that
+    /// satisfies the dependencies between them: logger and metrics first so
+    /// they capture the rest, and network interfaces before the MMDS config
+    /// that refers to them. Returns the lint report, which only holds
+    /// warnings on success.
+    ///
+    /// Sections are sent one at a time, so a failing section leaves the ones
+    /// before it applied unless [`ApplyOptions::rollback`] is set.
+    async fn apply_config_with(
+        &self,
+        config: &FullVmConfiguration,
+        options: ApplyOptions,
+    ) -> Result<ValidationReport, FirecrackerError>;
+
+    /// Reads a Firecracker config file from `path` (see [`ConfigFile`]) and
+    /// applies it with [`ConfigOperations::apply_config`].
+    async fn apply_config_file(&self, path: &Path) -> Result<ValidationReport, FirecrackerError>;
+}
+
+#[async_trait]
+impl ConfigOperations for crate::FirecrackerClient {
+    fn validate_config(
+        &self,
+        config: &FullVmConfiguration,
+    ) -> Result<ValidationReport, FirecrackerError> {
+        let paths = self.local_paths().check_config_paths(config);
+        self.check_config(config, true, paths)
+    }
+
+    fn plan_config(
+        &self,
+        config: &FullVmConfiguration,
+    ) -> Result<Vec<PlannedRequest>, FirecrackerError> {
+        let mut plan = Plan::default();
+        let resolved = self
+            .local_paths()
+            .resolve_config_paths(config, self.vmm_version().as_ref())?;
+
+        if let Some(logger) = &resolved.logger {
+            plan.put("logger", paths::LOGGER, logger)?;
+        }
+        if let Some(metrics) = &resolved.metrics {
+            plan.put("metrics", paths::METRICS, metrics)?;
+        }
+        if let Some(machine_config) = &resolved.machine_config {
+            plan.put("machine_config", paths::MACHINE_CONFIG, machine_config)?;
+        }
+        if let Some(boot_source) = &resolved.boot_source {
+            plan.put("boot_source", paths::BOOT_SOURCE, boot_source)?;
+        }
+        for (i, drive) in resolved.drives.iter().enumerate() {
+            plan.put(
+                format!("drives[{}]", i),
+                self.device_path(paths::DRIVES, "drive_id", &drive.drive_id)?,
+                drive,
+            )?;
+        }
+        for (i, interface) in resolved.network_interfaces.iter().enumerate() {
+            plan.put(
+                format!("network_interfaces[{}]", i),
+                self.device_path(paths::NETWORK_INTERFACES, "iface_id", &interface.iface_id)?,
+                interface,
+            )?;
+        }
+        if let Some(mmds_config) = &resolved.mmds_config {
+            plan.put("mmds_config", paths::MMDS_CONFIG, mmds_config)?;
+        }
+        if let Some(balloon) = &resolved.balloon {
+            plan.put("balloon", paths::BALLOON, balloon)?;
+        }
+        if let Some(vsock) = &resolved.vsock {
+            plan.put("vsock", paths::VSOCK, vsock)?;
+        }
+        if let Some(entropy) = &resolved.entropy {
+            plan.put("entropy", paths::ENTROPY, entropy)?;
+        }
+
+        let mut requests = plan.0;
+        for request in &mut requests {
+            if self.redaction().is_sensitive(&request.path) {
+                request.body = Value::String(redacted(request.body.to_string().as_bytes()));
+            }
+        }
+        Ok(requests)
+    }
+
+    async fn apply_config(
+        &self,
+        config: &FullVmConfiguration,
+    ) -> Result<ValidationReport, FirecrackerError> {
+        self.apply_config_with(config, ApplyOptions::default())
+            .await
+    }
+
+    async fn apply_config_with(
+        &self,
+        config: &FullVmConfiguration,
+        options: ApplyOptions,
+    ) -> Result<ValidationReport, FirecrackerError> {
+        // The paths are checked and resolved once, here, and the sections
+        // are then sent as resolved instead of through the `put_*` calls,
+        // which would check them again and repeat the lint warnings
+        let (checked, version) = (config.clone(), self.vmm_version());
+        let (paths, resolved) = self
+            .on_local_fs(move |local| {
+                let paths = local.check_config_paths(&checked);
+                let resolved = local.resolve_config_paths(&checked, version.as_ref());
+                Ok((paths, resolved))
+            })
+            .await?;
+        let report = self.check_config(config, options.lint, paths)?;
+        let resolved = resolved?;
+        let previous = if options.rollback {
+            Some(self.get_vm_config().await?)
+        } else {
+            None
+        };
+        let mut applied = Applied {
+            client: self,
+            previous,
+            sections: Vec::new(),
+        };
+
+        // Keep the section order in sync with `plan_config`
+        if let Some(logger) = &resolved.logger {
+            applied
+                .send(
+                    "logger",
+                    Section::Logger,
+                    self.validated_put(paths::LOGGER, logger),
+                )
+                .await?;
+        }
+        if let Some(metrics) = &resolved.metrics {
+            applied
+                .send(
+                    "metrics",
+                    Section::Metrics,
+                    self.validated_put(paths::METRICS, metrics),
+                )
+                .await?;
+        }
+        if let Some(machine_config) = &config.machine_config {
+            applied
+                .send(
+                    "machine_config",
+                    Section::MachineConfig,
+                    self.validated_put(paths::MACHINE_CONFIG, machine_config),
+                )
+                .await?;
+        }
+        if let Some(boot_source) = &resolved.boot_source {
+            applied
+                .send(
+                    "boot_source",
+                    Section::BootSource,
+                    self.validated_put(paths::BOOT_SOURCE, boot_source),
+                )
+                .await?;
+        }
+        for (i, (drive, body)) in config.drives.iter().zip(&resolved.drives).enumerate() {
+            applied
+                .send(
+                    format!("drives[{}]", i),
+                    Section::Drive(&drive.drive_id),
+                    async {
+                        let path = self.device_path(paths::DRIVES, "drive_id", &drive.drive_id)?;
+                        self.validated_put(&path, body).await
+                    },
+                )
+                .await?;
+        }
+        for (i, interface) in config.network_interfaces.iter().enumerate() {
+            applied
+                .send(
+                    format!("network_interfaces[{}]", i),
+                    Section::NetworkInterface(&interface.iface_id),
+                    self.put_network_interface(&interface.iface_id, interface),
+                )
+                .await?;
+        }
+        if let Some(mmds_config) = &config.mmds_config {
+            applied
+                .send(
+                    "mmds_config",
+                    Section::MmdsConfig,
+                    self.put_mmds_config(mmds_config),
+                )
+                .await?;
+        }
+        if let Some(balloon) = &config.balloon {
+            applied
+                .send(
+                    "balloon",
+                    Section::Balloon,
+                    self.validated_put(paths::BALLOON, balloon),
+                )
+                .await?;
+        }
+        if let (Some(vsock), Some(body)) = (&config.vsock, &resolved.vsock) {
+            applied
+                .send("vsock", Section::Vsock, self.send_vsock(vsock, body))
+                .await?;
+        }
+        if let Some(entropy) = &config.entropy {
+            applied
+                .send(
+                    "entropy",
+                    Section::Entropy,
+                    self.put_entropy_device(entropy),
+                )
+                .await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn apply_config_file(&self, path: &Path) -> Result<ValidationReport, FirecrackerError> {
+        let config = read_config_file(path)?;
+        self.apply_config(&config).await
+    }
+}
+
+impl crate::FirecrackerClient {
+    // Validates every section up front so a bad config reports all of its
+    // problems at once; the `put_*` calls still stop at the first one
+    fn check_config(
+        &self,
+        config: &FullVmConfiguration,
+        lint: bool,
+        paths: ConfigPaths,
+    ) -> Result<ValidationReport, FirecrackerError> {
+        if self.validation_mode() == ValidationMode::Off {
+            return Ok(ValidationReport::default());
+        }
+
+        let mut report = ValidationReport::default();
+        if lint {
+            report = validate_vm_config(config);
+            if let Some(release) = &paths.host_kernel {
+                report
+                    .findings
+                    .extend(validate_host_support(config, release).findings);
+            }
+        }
+
+        let mut errors = SectionErrors::default();
+        if let Some(logger) = &config.logger {
+            errors.section("logger", self.check_section(logger, paths.logger));
+        }
+        if let Some(metrics) = &config.metrics {
+            errors.section("metrics", self.check_section(metrics, paths.metrics));
+        }
+        if let Some(machine_config) = &config.machine_config {
+            errors.section("machine_config", self.check_section(machine_config, Ok(())));
+        }
+        if let Some(boot_source) = &config.boot_source {
+            errors.section(
+                "boot_source",
+                self.check_section(boot_source, paths.boot_source),
+            );
+        }
+        errors.list(
+            "drives",
+            config
+                .drives
+                .iter()
+                .zip(paths.drives)
+                .map(|(drive, paths)| self.check_section(drive, paths)),
+        );
+        errors.list(
+            "network_interfaces",
+            config
+                .network_interfaces
+                .iter()
+                .map(|interface| self.check_section(interface, Ok(()))),
+        );
+        if let Some(mmds_config) = &config.mmds_config {
+            errors.section("mmds_config", self.check_section(mmds_config, Ok(())));
+        }
+        if let Some(balloon) = &config.balloon {
+            errors.section("balloon", self.check_section(balloon, Ok(())));
+        }
+        if let Some(vsock) = &config.vsock {
+            errors.section("vsock", self.check_section(vsock, paths.vsock));
+        }
+        if let Some(entropy) = &config.entropy {
+            errors.section("entropy", self.check_section(entropy, Ok(())));
+        }
+
+        match errors.0 {
+            Some(mut errors) => {
+                for finding in report.errors() {
+                    errors.add("__all__", finding.into());
+                }
+                Err(errors.into())
+            }
+            None => {
+                let report = report.into_result()?;
+                for finding in report.warnings() {
+                    self.warn(finding.into());
+                }
+                Ok(report)
+            }
+        }
+    }
+
+    fn check_section<T>(
+        &self,
+        model: &T,
+        paths: Result<(), ValidationErrors>,
+    ) -> Result<(), ValidationErrors>
+    where
+        T: Validate + AsValidationTarget,
+    {
+        combine_errors([
+            model.validate(),
+            paths,
+            self.custom_validation(&model.validation_target()),
+        ])
+    }
+}
+
+// What the path checks of `check_config` found, per section; sections that
+// aren't set pass
+struct ConfigPaths {
+    logger: Result<(), ValidationErrors>,
+    metrics: Result<(), ValidationErrors>,
+    boot_source: Result<(), ValidationErrors>,
+    drives: Vec<Result<(), ValidationErrors>>,
+    vsock: Result<(), ValidationErrors>,
+    host_kernel: Option<String>,
+}
+
+impl crate::LocalPaths {
+    fn check_config_paths(&self, config: &FullVmConfiguration) -> ConfigPaths {
+        ConfigPaths {
+            logger: config.logger.as_ref().map_or(Ok(()), |logger| {
+                self.check_writable_path("log_path", &logger.log_path)
+            }),
+            metrics: config.metrics.as_ref().map_or(Ok(()), |metrics| {
+                self.check_writable_path("metrics_path", &metrics.metrics_path)
+            }),
+            boot_source: config.boot_source.as_ref().map_or(Ok(()), |boot_source| {
+                self.check_boot_source_paths(boot_source)
+            }),
+            drives: config
+                .drives
+                .iter()
+                .map(|drive| self.check_drive_paths(drive))
+                .collect(),
+            vsock: config
+                .vsock
+                .as_ref()
+                .map_or(Ok(()), |vsock| self.check_vsock_path(vsock)),
+            host_kernel: self.host_kernel(),
+        }
+    }
+
+    // `config` with its host paths rewritten the way the `put_*` calls
+    // rewrite them, and the vsock device as sent to a VMM of `version`
+    fn resolve_config_paths(
+        &self,
+        config: &FullVmConfiguration,
+        version: Option<&Version>,
+    ) -> Result<FullVmConfiguration, FirecrackerError> {
+        Ok(FullVmConfiguration {
+            logger: config
+                .logger
+                .as_ref()
+                .map(|logger| self.resolve_logger_path(logger))
+                .transpose()?,
+            metrics: config
+                .metrics
+                .as_ref()
+                .map(|metrics| self.resolve_metrics_path(metrics))
+                .transpose()?,
+            boot_source: config
+                .boot_source
+                .as_ref()
+                .map(|boot_source| self.resolve_boot_source_paths(boot_source))
+                .transpose()?,
+            drives: config
+                .drives
+                .iter()
+                .map(|drive| self.resolve_drive_path(drive))
+                .collect::<Result<_, _>>()?,
+            vsock: config
+                .vsock
+                .as_ref()
+                .map(|vsock| self.vsock_body(vsock, version))
+                .transpose()?,
+            ..config.clone()
+        })
+    }
+}
+
+// A section `apply_config_with` sent, by what identifies it in the
+// configuration read back from the VMM
+enum Section<'a> {
+    Logger,
+    Metrics,
+    MachineConfig,
+    BootSource,
+    Drive(&'a str),
+    NetworkInterface(&'a str),
+    MmdsConfig,
+    Balloon,
+    Vsock,
+    Entropy,
+}
+
+impl Section<'_> {
+    // The request putting back the section's value from `previous`, or why
+    // there is none. Firecracker has no DELETE for any of these, so a device
+    // that didn't exist before stays.
+    fn revert(
+        &self,
+        previous: &FullVmConfiguration,
+    ) -> Result<(String, serde_json::Value), String> {
+        fn put<T: Serialize>(
+            path: impl Into<String>,
+            value: Option<&T>,
+            missing: &str,
+        ) -> Result<(String, serde_json::Value), String> {
+            let value = value.ok_or_else(|| missing.to_string())?;
+            let body = serde_json::to_value(value).map_err(|e| e.to_string())?;
+            Ok((path.into(), body))
+        }
+
+        match self {
+            Section::Logger => Err("the logger can only be configured once".to_string()),
+            Section::Metrics => Err("metrics can only be configured once".to_string()),
+            Section::MachineConfig => put(
+                paths::MACHINE_CONFIG,
+                previous.machine_config.as_ref(),
+                "there was no machine config to restore",
+            ),
+            Section::BootSource => put(
+                paths::BOOT_SOURCE,
+                previous.boot_source.as_ref(),
+                "the boot source was unset and can't be removed",
+            ),
+            Section::Drive(id) => put(
+                paths::drive(id),
+                previous.drives.iter().find(|drive| drive.drive_id == *id),
+                "the drive is new and drives can't be removed",
+            ),
+            Section::NetworkInterface(id) => put(
+                paths::network_interface(id),
+                previous
+                    .network_interfaces
+                    .iter()
+                    .find(|interface| interface.iface_id == *id),
+                "the interface is new and interfaces can't be removed",
+            ),
+            Section::MmdsConfig => put(
+                paths::MMDS_CONFIG,
+                previous.mmds_config.as_ref(),
+                "MMDS was unconfigured and can't be reset",
+            ),
+            Section::Balloon => put(
+                paths::BALLOON,
+                previous.balloon.as_ref(),
+                "the balloon is new and can't be removed",
+            ),
+            Section::Vsock => put(
+                paths::VSOCK,
+                previous.vsock.as_ref(),
+                "the vsock device is new and can't be removed",
+            ),
+            Section::Entropy => put(
+                paths::ENTROPY,
+                previous.entropy.as_ref(),
+                "the entropy device is new and can't be removed",
+            ),
+        }
+    }
+}
+
+// The sections `apply_config_with` has sent so far, and the configuration
+// from before the first one when rolling back
+struct Applied<'a> {
+    client: &'a crate::FirecrackerClient,
+    previous: Option<FullVmConfiguration>,
+    sections: Vec<(String, Section<'a>)>,
+}
+
+impl<'a> Applied<'a> {
+    async fn send(
+        &mut self,
+        name: impl Into<String>,
+        section: Section<'a>,
+        request: impl std::future::Future<Output = Result<(), FirecrackerError>>,
+    ) -> Result<(), FirecrackerError> {
+        let name = name.into();
+        let err = match request.await {
+            Ok(()) => {
+                self.sections.push((name, section));
+                return Ok(());
+            }
+            Err(err) => err,
+        };
+        let Some(previous) = &self.previous else {
+            return Err(err);
+        };
+
+        // Undo in reverse, so e.g. MMDS goes back before the interfaces it
+        // refers to
+        let mut report = RollbackReport::default();
+        for (applied, section) in self.sections.iter().rev() {
+            let outcome = match section.revert(previous) {
+                Ok((path, body)) => {
+                    // The previous values come from the VMM itself, with
+                    // paths it already resolved, so they are sent as they are
+                    match self.client.send_json(Method::PUT, &path, &body).await {
+                        Ok(()) => RollbackOutcome::RolledBack,
+                        Err(e) => RollbackOutcome::RollbackFailed(e.to_string()),
+                    }
+                }
+                Err(reason) => RollbackOutcome::Unrevertible(reason),
+            };
+            report.sections.push((applied.clone(), outcome));
+        }
+        report.sections.reverse();
+        Err(FirecrackerError::ConfigApply {
+            section: name,
+            source: Box::new(err),
+            rollback: report,
+        })
+    }
+}
+
+// The requests of `plan_config`, in the order they are added
+#[derive(Default)]
+struct Plan(Vec<PlannedRequest>);
+
+impl Plan {
+    fn put(
+        &mut self,
+        section: impl Into<String>,
+        path: impl Into<String>,
+        body: &impl Serialize,
+    ) -> Result<(), FirecrackerError> {
+        self.0.push(PlannedRequest {
+            section: section.into(),
+            method: "PUT",
+            path: path.into(),
+            body: serde_json::to_value(body)?,
+        });
+        Ok(())
+    }
+}
+
+// Collects section errors nested under the section name, and list items
+// under their index, the way a derived `Validate` nests them
+#[derive(Default)]
+struct SectionErrors(Option<ValidationErrors>);
+
+impl SectionErrors {
+    fn section(&mut self, name: &'static str, result: Result<(), ValidationErrors>) {
+        if let Err(errors) = result {
+            self.insert(name, ValidationErrorsKind::Struct(Box::new(errors)));
+        }
+    }
+
+    fn list(
+        &mut self,
+        name: &'static str,
+        results: impl Iterator<Item = Result<(), ValidationErrors>>,
+    ) {
+        let items: BTreeMap<_, _> = results
+            .enumerate()
+            .filter_map(|(i, result)| result.err().map(|errors| (i, Box::new(errors))))
+            .collect();
+        if !items.is_empty() {
+            self.insert(name, ValidationErrorsKind::List(items));
+        }
+    }
+
+    fn insert(&mut self, name: &'static str, kind: ValidationErrorsKind) {
+        self.0
+            .get_or_insert_with(ValidationErrors::new)
+            .errors_mut()
+            .insert(name, kind);
+    }
+}
@@ -1,13 +1,27 @@
+use crate::paths;
+use crate::validation::validate_cpu_template_name;
 use crate::FirecrackerError;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[non_exhaustive]
 pub struct CpuConfig {
+    /// One of the `CpuTemplate` names
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_cpu_template_name")]
     pub template: Option<String>,
 }
 
+impl CpuConfig {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: Some(template.into()),
+        }
+    }
+}
+
 #[async_trait]
 pub trait CpuConfigOperations {
     async fn put_cpu_config(&self, config: &CpuConfig) -> Result<(), FirecrackerError>;
@@ -16,16 +30,6 @@ pub trait CpuConfigOperations {
 #[async_trait]
 impl CpuConfigOperations for crate::FirecrackerClient {
     async fn put_cpu_config(&self, config: &CpuConfig) -> Result<(), FirecrackerError> {
-        let url = self.url("cpu-config")?;
-        let response = self.client.put(url).json(config).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        self.validated_put(paths::CPU_CONFIG, config).await
     }
 }
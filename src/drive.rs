@@ -1,40 +1,148 @@
-use crate::models::Drive;
+use crate::models::{Drive, FullVmConfiguration, Patchable, RateLimiter};
+use crate::paths;
+use crate::validation::{
+    validate_existing_path, validate_host_support, validate_socket_path, validate_unix_path,
+    ConfigWarning, SocketPathOptions, ValidatePatch, ValidationTarget,
+};
 use crate::FirecrackerError;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+/// The fields of a drive that can change after boot. Leave `path_on_host`
+/// unset to keep the current backing file, and set `rate_limiter` to
+/// [`Patchable::Null`] to remove the drive's limiter.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DriveUpdate {
+    pub drive_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_on_host: Option<String>,
+    #[serde(default, skip_serializing_if = "Patchable::is_absent")]
+    pub rate_limiter: Patchable<RateLimiter>,
+}
+
+impl DriveUpdate {
+    /// An update to `drive_id` that changes nothing until fields are set.
+    pub fn new(drive_id: impl Into<String>) -> Self {
+        Self {
+            drive_id: drive_id.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl ValidatePatch for DriveUpdate {
+    fn validate_patch(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.drive_id.is_empty() {
+            errors.add("drive_id", ValidationError::new("length"));
+        }
+        if let Some(path) = &self.path_on_host {
+            if let Err(e) = validate_unix_path(path) {
+                errors.add("path_on_host", e);
+            }
+        }
+        let result = if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        };
+        ValidationErrors::merge(
+            result,
+            "rate_limiter",
+            self.rate_limiter.value().map_or(Ok(()), Validate::validate),
+        )
+    }
+
+    fn patch_target(&self) -> ValidationTarget<'_> {
+        ValidationTarget::DriveUpdate(self)
+    }
+}
 
 #[async_trait]
 pub trait DriveOperations {
     async fn put_drive(&self, drive_id: &str, drive: &Drive) -> Result<(), FirecrackerError>;
-    async fn patch_drive(&self, drive_id: &str, drive: &Drive) -> Result<(), FirecrackerError>;
+    async fn patch_drive(
+        &self,
+        drive_id: &str,
+        update: &DriveUpdate,
+    ) -> Result<(), FirecrackerError>;
 }
 
 #[async_trait]
 impl DriveOperations for crate::FirecrackerClient {
     async fn put_drive(&self, drive_id: &str, drive: &Drive) -> Result<(), FirecrackerError> {
-        let url = self.url(&format!("drives/{}", drive_id))?;
-        let response = self.client.put(url).json(drive).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+        let drive = drive.clone();
+        let (drive, host_kernel) = self
+            .on_local_fs(move |local| {
+                local.check_drive_paths(&drive)?;
+                Ok((local.resolve_drive_path(&drive)?, local.host_kernel()))
+            })
+            .await?;
+        let path = self.device_path(paths::DRIVES, "drive_id", drive_id)?;
+        self.put_linted(self.validated_put(&path, &drive)).await?;
+        if let Some(release) = host_kernel {
+            let config = FullVmConfiguration {
+                drives: vec![drive],
+                ..Default::default()
+            };
+            for finding in validate_host_support(&config, &release).warnings() {
+                self.warn(ConfigWarning {
+                    context: format!("drives.{}.io_engine", drive_id),
+                    ..ConfigWarning::from(finding)
+                });
+            }
         }
-
         Ok(())
     }
 
-    async fn patch_drive(&self, drive_id: &str, drive: &Drive) -> Result<(), FirecrackerError> {
-        let url = self.url(&format!("drives/{}", drive_id))?;
-        let response = self.client.patch(url).json(drive).send().await?;
+    async fn patch_drive(
+        &self,
+        drive_id: &str,
+        update: &DriveUpdate,
+    ) -> Result<(), FirecrackerError> {
+        let update = update.clone();
+        let update = self
+            .on_local_fs(move |local| {
+                Ok(DriveUpdate {
+                    path_on_host: update
+                        .path_on_host
+                        .as_deref()
+                        .map(|path| local.resolve_path("path_on_host", path))
+                        .transpose()?,
+                    ..update.clone()
+                })
+            })
+            .await?;
+        let path = self.device_path(paths::DRIVES, "drive_id", drive_id)?;
+        self.validated_patch(&path, &update).await
+    }
+}
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+impl crate::LocalPaths {
+    // vhost-user drives connect to a socket the backend already created
+    pub(crate) fn check_drive_paths(&self, drive: &Drive) -> Result<(), ValidationErrors> {
+        match &drive.socket {
+            Some(socket) => self.check_local_path("socket", socket, |path| {
+                validate_socket_path(path, &SocketPathOptions::default())
+            }),
+            None => {
+                self.check_local_path("path_on_host", &drive.path_on_host, validate_existing_path)
+            }
         }
+    }
 
-        Ok(())
+    // vhost-user drives have no path
+    pub(crate) fn resolve_drive_path(&self, drive: &Drive) -> Result<Drive, FirecrackerError> {
+        let path_on_host = if drive.path_on_host.is_empty() {
+            String::new()
+        } else {
+            self.resolve_path("path_on_host", &drive.path_on_host)?
+        };
+        Ok(Drive {
+            path_on_host,
+            ..drive.clone()
+        })
     }
 }
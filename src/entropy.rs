@@ -1,30 +1,28 @@
+use crate::paths;
+use crate::version::ENTROPY_MIN_VERSION;
 use crate::FirecrackerError;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EntropyDevice {
-    pub rate_limiter: Option<crate::models::RateLimiter>,
-}
+/// Kept so code importing `entropy::EntropyDevice` keeps compiling; it is the
+/// same type as [`crate::models::EntropyDevice`].
+#[deprecated(note = "use firecracker_http_client::models::EntropyDevice instead")]
+pub type EntropyDevice = crate::models::EntropyDevice;
 
 #[async_trait]
 pub trait EntropyDeviceOperations {
-    async fn put_entropy_device(&self, device: &EntropyDevice) -> Result<(), FirecrackerError>;
+    async fn put_entropy_device(
+        &self,
+        device: &crate::models::EntropyDevice,
+    ) -> Result<(), FirecrackerError>;
 }
 
 #[async_trait]
 impl EntropyDeviceOperations for crate::FirecrackerClient {
-    async fn put_entropy_device(&self, device: &EntropyDevice) -> Result<(), FirecrackerError> {
-        let url = self.url("entropy")?;
-        let response = self.client.put(url).json(device).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+    async fn put_entropy_device(
+        &self,
+        device: &crate::models::EntropyDevice,
+    ) -> Result<(), FirecrackerError> {
+        self.ensure_supported("PUT /entropy", &ENTROPY_MIN_VERSION)?;
+        self.validated_put(paths::ENTROPY, device).await
     }
 }
@@ -18,14 +18,53 @@ pub enum FirecrackerError {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    /// A response body did not match the expected model, e.g. because a
+    /// required field was missing. Carries the body as received.
+    #[error("Could not decode response: {message}")]
+    Decode { message: String, body: String },
+
     /// Error validating input
     #[error("Validation error: {0}")]
     Validation(#[from] ValidationErrors),
 
+    /// A full VM configuration failed `validate_vm_config`
+    #[error("Invalid VM configuration: {0}")]
+    InvalidConfig(crate::validation::ValidationReport),
+
+    /// A section of a configuration failed to apply with rollback enabled.
+    /// The report says which of the sections before it were put back.
+    #[error("Applying {section} failed: {source}; {rollback}")]
+    ConfigApply {
+        section: String,
+        source: Box<FirecrackerError>,
+        rollback: crate::config::RollbackReport,
+    },
+
+    /// A step of [`snapshot::restore`](crate::snapshot::restore) failed.
+    /// Carries the step's own error.
+    #[error("Restoring the snapshot failed while {step}: {source}")]
+    Restore {
+        step: crate::snapshot::RestoreStep,
+        source: Box<FirecrackerError>,
+    },
+
+    /// A request over the API socket of a client from
+    /// [`FirecrackerClient::new_unix`](crate::FirecrackerClient::new_unix)
+    /// failed, e.g. because nothing listens on it
+    #[error("API socket error for {path}: {source}")]
+    ApiSocket {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     /// Error from Firecracker API
     #[error("Firecracker API error: {status_code} - {message}")]
     Api { status_code: u16, message: String },
 
+    /// The token provider couldn't produce a bearer token
+    #[error("Token provider error: {0}")]
+    TokenProvider(#[source] crate::auth::TokenError),
+
     /// Error with invalid paths
     #[error("Invalid path: {0}")]
     InvalidPath(String),
@@ -56,10 +95,52 @@ pub enum FirecrackerError {
         expected_states: Vec<String>,
     },
 
+    /// The operation does not exist on the connected Firecracker version
+    #[error("{operation} is unsupported on firecracker {version}")]
+    Unsupported { operation: String, version: String },
+
+    /// The VMM version does not satisfy the client's requirement
+    #[error("Firecracker {found} does not satisfy the required version {required}")]
+    UnsupportedVmmVersion { found: String, required: String },
+
+    /// The guest side refused a host-initiated vsock connection
+    #[error("Vsock connection to port {port} rejected: {response:?}")]
+    VsockHandshake { port: u32, response: String },
+
+    /// Not enough free space for a file the VMM is about to write
+    #[error("Not enough space in {path}: need about {required_bytes} bytes, {available_bytes} available")]
+    InsufficientSpace {
+        path: PathBuf,
+        required_bytes: u64,
+        available_bytes: u64,
+    },
+
+    /// The firecracker process exited, e.g. before its API came up. Carries
+    /// the last lines it wrote.
+    #[error("Firecracker process exited ({status}): {output}")]
+    ProcessExited { status: String, output: String },
+
     /// Timeout error
     #[error("Operation timed out after {duration_secs} seconds")]
     Timeout { duration_secs: u64 },
 
+    /// A request ran out of one of the client's time budgets, e.g. its
+    /// read timeout
+    #[error("{operation} exceeded the {budget} timeout of {limit:?}")]
+    RequestTimeout {
+        operation: String,
+        budget: crate::timeout::TimeoutBudget,
+        limit: std::time::Duration,
+    },
+
+    /// Timed out waiting for an MMDS value to satisfy a condition
+    #[error("Timed out after {duration_ms} ms waiting for MMDS key {pointer} (last observed: {last_value:?})")]
+    MmdsWaitTimeout {
+        pointer: String,
+        duration_ms: u64,
+        last_value: Option<serde_json::Value>,
+    },
+
     /// Generic error for cases that don't fit other categories
     #[error("Internal error: {0}")]
     Internal(String),
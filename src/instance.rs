@@ -1,25 +1,126 @@
-use crate::models::InstanceInfo;
-use crate::FirecrackerError;
+use crate::models::{InstanceInfo, InstanceState};
+use crate::paths;
+use crate::{FirecrackerError, InstanceCache};
 use async_trait::async_trait;
+use futures_util::stream::{self, Stream};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+// How often wait_for_api retries while the API socket is not up
+const API_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 #[async_trait]
 pub trait InstanceOperations {
+    /// Also updates the client's last known state, which its lifecycle
+    /// tracker, if it has one, reads, so a VM started or loaded by someone
+    /// else is noticed.
     async fn describe_instance(&self) -> Result<InstanceInfo, FirecrackerError>;
+
+    /// Polls `GET /` until the API answers successfully, e.g. right after
+    /// starting the firecracker process. Fails with the last error seen
+    /// once `timeout` has passed.
+    async fn wait_for_api(&self, timeout: Duration) -> Result<InstanceInfo, FirecrackerError>;
 }
 
 #[async_trait]
 impl InstanceOperations for crate::FirecrackerClient {
     async fn describe_instance(&self) -> Result<InstanceInfo, FirecrackerError> {
-        let url = self.url("")?;
-        let response = self.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+        let info: InstanceInfo = self.get_json(paths::INSTANCE).await?;
+        self.observe_instance(&info);
+        Ok(info)
+    }
+
+    async fn wait_for_api(&self, timeout: Duration) -> Result<InstanceInfo, FirecrackerError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let err = match self.describe_instance().await {
+                Ok(info) => return Ok(info),
+                Err(err) => err,
+            };
+
+            if Instant::now() + API_POLL_INTERVAL > deadline {
+                return Err(err);
+            }
+            sleep(API_POLL_INTERVAL).await;
         }
+    }
+}
+
+impl crate::FirecrackerClient {
+    /// The instance state from the last `GET /` this client, or a background
+    /// task of it such as [`MicroVm::watch_state`](crate::MicroVm::watch_state),
+    /// made, and when the response arrived, or from the last successful
+    /// `InstanceStart`, pause, resume or snapshot load it sent, and when that
+    /// succeeded. `None` until the first one.
+    pub fn last_known_state(&self) -> Option<(InstanceState, std::time::Instant)> {
+        self.last_instance_info()
+            .map(|(info, at)| (info.instance_state(), at))
+    }
 
-        Ok(response.json().await?)
+    /// Like [`last_known_state`](Self::last_known_state), with the whole
+    /// response. A state change the client made only updates `state`; the
+    /// other fields are empty until the first `GET /`.
+    pub fn last_instance_info(&self) -> Option<(InstanceInfo, std::time::Instant)> {
+        self.last_instance.read().unwrap().clone()
     }
+
+    /// Asks the VMM for its state now, updating the last known state.
+    pub async fn refresh_state(&self) -> Result<InstanceState, FirecrackerError> {
+        Ok(self.describe_instance().await?.instance_state())
+    }
+
+    // Everything that reads `GET /` goes through here
+    pub(crate) fn observe_instance(&self, info: &InstanceInfo) {
+        *self.last_instance.write().unwrap() = Some((info.clone(), std::time::Instant::now()));
+    }
+
+    // After a successful request that moves the VM to `state`
+    pub(crate) fn observe_state(&self, state: InstanceState) {
+        set_cached_state(&self.last_instance, state);
+    }
+}
+
+pub(crate) fn set_cached_state(cache: &InstanceCache, state: InstanceState) {
+    let mut cached = cache.write().unwrap();
+    let mut info = cached
+        .take()
+        .map(|(info, _)| info)
+        .unwrap_or_else(|| InstanceInfo {
+            app_name: String::new(),
+            id: String::new(),
+            state: String::new(),
+            vmm_version: String::new(),
+        });
+    info.state = state.as_str().to_string();
+    *cached = Some((info, std::time::Instant::now()));
+}
+
+/// Polls `GET /` every `interval` and yields the instance info whenever its
+/// state changes, starting with the current state. Failed polls yield the
+/// error and polling carries on; the next successful poll is reported even
+/// if the state is unchanged. The stream never ends on its own.
+pub fn watch_instance(
+    client: &crate::FirecrackerClient,
+    interval: Duration,
+) -> impl Stream<Item = Result<InstanceInfo, FirecrackerError>> + '_ {
+    stream::unfold(
+        (None::<String>, false),
+        move |(mut last, started)| async move {
+            if started {
+                sleep(interval).await;
+            }
+            loop {
+                match client.describe_instance().await {
+                    Ok(info) if last.as_deref() == Some(info.state.as_str()) => {}
+                    Ok(info) => {
+                        last = Some(info.state.clone());
+                        return Some((Ok(info), (last, true)));
+                    }
+                    Err(err) => return Some((Err(err), (None, true))),
+                }
+                sleep(interval).await;
+            }
+        },
+    )
 }
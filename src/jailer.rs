@@ -0,0 +1,112 @@
+//! Paths for a Firecracker running under the jailer.
+//!
+//! The jailer chroots the VMM into `<chroot_base>/<exec_file_name>/<id>/root`,
+//! so every path in a request has to be the path as seen from inside that
+//! directory. Attach a [`JailerContext`] with
+//! [`FirecrackerClient::with_jailer`](crate::FirecrackerClient::with_jailer)
+//! and keep using host paths: drive images, kernels, snapshot files, the
+//! logger and the metrics file are rewritten before they are sent. Local
+//! validation still checks the host path, so a file that was never copied
+//! or linked into the chroot is caught before the request.
+
+use crate::validation::path_validation_error;
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use validator::ValidationError;
+
+/// The chroot base directory the jailer uses unless told otherwise.
+pub const DEFAULT_CHROOT_BASE: &str = "/srv/jailer";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JailerContext {
+    /// Passed to the jailer as `--chroot-base-dir`
+    pub chroot_base: PathBuf,
+    /// The VM id, passed as `--id`
+    pub id: String,
+    /// The user the VMM runs as
+    pub uid: u32,
+    /// The group the VMM runs as
+    pub gid: u32,
+    /// File name of the binary passed as `--exec-file`, which the jailer
+    /// puts in the chroot path
+    pub exec_file_name: String,
+}
+
+impl JailerContext {
+    pub fn new(chroot_base: impl Into<PathBuf>, id: impl Into<String>, uid: u32, gid: u32) -> Self {
+        Self {
+            chroot_base: chroot_base.into(),
+            id: id.into(),
+            uid,
+            gid,
+            exec_file_name: "firecracker".to_string(),
+        }
+    }
+
+    /// The host directory that becomes `/` for the VMM.
+    pub fn chroot_dir(&self) -> PathBuf {
+        self.chroot_base
+            .join(&self.exec_file_name)
+            .join(&self.id)
+            .join("root")
+    }
+
+    /// The host path of `path` as seen from inside the chroot.
+    pub fn host_path(&self, path: &str) -> PathBuf {
+        self.chroot_dir().join(path.trim_start_matches('/'))
+    }
+
+    /// Rewrites a host path inside the chroot into the path the VMM sees,
+    /// e.g. `/srv/jailer/firecracker/vm1/root/rootfs.ext4` into
+    /// `/rootfs.ext4`. Paths outside the chroot, including ones that leave it
+    /// through `..`, are rejected since the VMM can't open them.
+    pub fn translate(&self, path: &str) -> Result<String, ValidationError> {
+        let chroot = self.chroot_dir();
+        let normalized = normalize(Path::new(path));
+        let relative = normalized
+            .strip_prefix(&chroot)
+            .ok()
+            .map(Path::to_path_buf)
+            .or_else(|| canonical_relative(&normalized, &chroot));
+        match relative {
+            Some(relative) => Ok(format!("/{}", relative.display())),
+            None => {
+                let mut err = path_validation_error(format!(
+                    "{} is outside the jailer chroot {}",
+                    path,
+                    chroot.display()
+                ));
+                err.add_param(Cow::from("check"), &"inside_chroot");
+                Err(err)
+            }
+        }
+    }
+}
+
+// The path may have been canonicalized by the path policy while the chroot
+// base is reached through a symlink
+#[cfg(not(feature = "no-fs-validation"))]
+fn canonical_relative(path: &Path, chroot: &Path) -> Option<PathBuf> {
+    let canonical = std::fs::canonicalize(chroot).ok()?;
+    path.strip_prefix(canonical).ok().map(Path::to_path_buf)
+}
+
+#[cfg(feature = "no-fs-validation")]
+fn canonical_relative(_path: &Path, _chroot: &Path) -> Option<PathBuf> {
+    None
+}
+
+// Resolves `.` and `..` without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,354 @@
+//! Starting and stopping the `firecracker` process.
+//!
+//! [`FirecrackerProcess::spawn`] hands out a client that talks to the API
+//! socket directly, see [`FirecrackerClient::new_unix`].
+//!
+//! With a [`JailerContext`] in the options the jailer is started instead and
+//! runs Firecracker in its chroot. The paths in the options stay host paths
+//! inside the chroot and are translated for Firecracker, and the client gets
+//! the same context so the paths in its requests are translated too.
+
+use crate::instance::InstanceOperations;
+use crate::jailer::JailerContext;
+use crate::orchestration::{ResourceKind, TeardownPlan};
+use crate::validation::field_validation_errors;
+use crate::{FirecrackerClient, FirecrackerError};
+use std::collections::VecDeque;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::net::UnixStream;
+use tokio::process::{Child, Command};
+
+// Lines of stdout and stderr kept for error reports
+const OUTPUT_LINES: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// The `firecracker` binary
+    pub binary_path: PathBuf,
+    /// Passed as `--api-sock`; its directory is created if needed, except
+    /// under the jailer
+    pub api_sock: PathBuf,
+    /// Passed as `--id`; under the jailer, the context's id is used
+    pub id: Option<String>,
+    /// Passed as `--log-path`, e.g. a FIFO from `logger::create_log_fifo`
+    pub log_fifo: Option<PathBuf>,
+    /// Appended after the other arguments
+    pub extra_args: Vec<String>,
+    /// How long to wait for the API to answer after starting the process
+    pub api_timeout: Duration,
+    /// Run `binary_path` under the jailer
+    pub jailer: Option<JailerContext>,
+    /// The `jailer` binary
+    pub jailer_binary: PathBuf,
+    /// Root of [`FirecrackerProcess::teardown_plan`]. Defaults to the
+    /// jailer's chroot, or else the directory holding the API socket (the
+    /// first directory that already existed, if the launcher created some).
+    pub teardown_root: Option<PathBuf>,
+}
+
+impl LaunchOptions {
+    pub fn new(binary_path: impl Into<PathBuf>, api_sock: impl Into<PathBuf>) -> Self {
+        Self {
+            binary_path: binary_path.into(),
+            api_sock: api_sock.into(),
+            id: None,
+            log_fifo: None,
+            extra_args: Vec::new(),
+            api_timeout: Duration::from_secs(5),
+            jailer: None,
+            jailer_binary: PathBuf::from("jailer"),
+            teardown_root: None,
+        }
+    }
+
+    // The program to run and its arguments
+    fn command(&self) -> Result<(&Path, Vec<String>), FirecrackerError> {
+        let Some(jailer) = &self.jailer else {
+            let args = self
+                .firecracker_args(self.id.as_deref(), |_, path| Ok(path.display().to_string()))?;
+            return Ok((&self.binary_path, args));
+        };
+
+        if let Some(id) = self.id.as_ref().filter(|id| **id != jailer.id) {
+            return Err(FirecrackerError::Config(format!(
+                "id {:?} differs from the jailer id {:?}",
+                id, jailer.id
+            )));
+        }
+        let exec_file_name = self.binary_path.file_name().and_then(|name| name.to_str());
+        if exec_file_name != Some(jailer.exec_file_name.as_str()) {
+            return Err(FirecrackerError::Config(format!(
+                "{} does not match the jailer exec file name {:?}",
+                self.binary_path.display(),
+                jailer.exec_file_name
+            )));
+        }
+
+        // The documented order: jailer arguments, then Firecracker's after `--`
+        let mut args = vec![
+            "--id".to_string(),
+            jailer.id.clone(),
+            "--exec-file".to_string(),
+            self.binary_path.display().to_string(),
+            "--uid".to_string(),
+            jailer.uid.to_string(),
+            "--gid".to_string(),
+            jailer.gid.to_string(),
+            "--chroot-base-dir".to_string(),
+            jailer.chroot_base.display().to_string(),
+            "--".to_string(),
+        ];
+        // The jailer passes `--id` on to Firecracker
+        args.extend(self.firecracker_args(None, |field, path| {
+            jailer
+                .translate(&path.display().to_string())
+                .map_err(|e| field_validation_errors(field, e).into())
+        })?);
+        Ok((&self.jailer_binary, args))
+    }
+
+    fn teardown_plan(&self, created_dirs: &[PathBuf]) -> TeardownPlan {
+        let root = self.teardown_root.clone().unwrap_or_else(|| {
+            match (&self.jailer, created_dirs.first()) {
+                (Some(jailer), _) => jailer.chroot_dir(),
+                (None, Some(dir)) => dir.parent().unwrap_or(dir).to_path_buf(),
+                (None, None) => self
+                    .api_sock
+                    .parent()
+                    .unwrap_or(&self.api_sock)
+                    .to_path_buf(),
+            }
+        });
+        let mut plan = TeardownPlan::new(root);
+        for dir in created_dirs {
+            plan.add(ResourceKind::Directory, dir);
+        }
+        plan.add(ResourceKind::ApiSocket, &self.api_sock);
+        if let Some(log_fifo) = &self.log_fifo {
+            plan.add(ResourceKind::LogFifo, log_fifo);
+        }
+        plan
+    }
+
+    fn firecracker_args(
+        &self,
+        id: Option<&str>,
+        path: impl Fn(&'static str, &Path) -> Result<String, FirecrackerError>,
+    ) -> Result<Vec<String>, FirecrackerError> {
+        let mut args = vec!["--api-sock".to_string(), path("api_sock", &self.api_sock)?];
+        if let Some(id) = id {
+            args.extend(["--id".to_string(), id.to_string()]);
+        }
+        if let Some(log_fifo) = &self.log_fifo {
+            args.extend(["--log-path".to_string(), path("log_fifo", log_fifo)?]);
+        }
+        args.extend(self.extra_args.iter().cloned());
+        Ok(args)
+    }
+}
+
+/// A running `firecracker` process. Dropping the handle kills the process.
+pub struct FirecrackerProcess {
+    child: Child,
+    api_sock: PathBuf,
+    output: Arc<Mutex<VecDeque<String>>>,
+    teardown: TeardownPlan,
+}
+
+impl FirecrackerProcess {
+    /// Starts the process and waits for its API to answer `GET /`. Fails
+    /// with `ProcessExited` if the process exits first, or with the last
+    /// connection error once `api_timeout` has passed; either way nothing is
+    /// left running.
+    pub async fn spawn(
+        options: LaunchOptions,
+    ) -> Result<(Self, FirecrackerClient), FirecrackerError> {
+        let (program, args) = options.command()?;
+        // The jailer sets up the chroot itself and refuses one that exists
+        let created_dirs = prepare_socket_path(&options.api_sock, options.jailer.is_none()).await?;
+        let teardown = options.teardown_plan(&created_dirs);
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|source| FirecrackerError::FileSystem {
+                path: program.to_path_buf(),
+                source,
+            })?;
+
+        let output = Arc::new(Mutex::new(VecDeque::new()));
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(capture(stdout, output.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(capture(stderr, output.clone()));
+        }
+
+        let mut process = Self {
+            child,
+            api_sock: options.api_sock.clone(),
+            output,
+            teardown,
+        };
+        let mut client = FirecrackerClient::new_unix(&options.api_sock).await?;
+        if let Some(jailer) = &options.jailer {
+            client = client.with_jailer(jailer.clone());
+        }
+
+        let ready = tokio::select! {
+            status = process.child.wait() => match status {
+                Ok(status) => Err(process.exited(status).await),
+                Err(e) => Err(FirecrackerError::Internal(e.to_string())),
+            },
+            result = client.wait_for_api(options.api_timeout) => result.map(|_| ()),
+        };
+        match ready {
+            Ok(()) => Ok((process, client)),
+            // The startup error says what went wrong; failing to clean up
+            // after it doesn't
+            Err(err) => {
+                let _ = process.kill().await;
+                Err(err)
+            }
+        }
+    }
+
+    /// The process ID, until the process has been waited for.
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    pub fn api_sock(&self) -> &Path {
+        &self.api_sock
+    }
+
+    /// The API socket, the log FIFO and the directories created for the
+    /// socket, for [`orchestration::teardown`](crate::orchestration::teardown)
+    /// once the process is gone.
+    pub fn teardown_plan(&self) -> TeardownPlan {
+        self.teardown.clone()
+    }
+
+    /// The most recent lines the process wrote to stdout and stderr.
+    pub fn output(&self) -> Vec<String> {
+        self.output.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Fails with `ProcessExited` if the process is no longer running.
+    pub async fn check(&mut self) -> Result<(), FirecrackerError> {
+        match self.child.try_wait() {
+            Ok(None) => Ok(()),
+            Ok(Some(status)) => Err(self.exited(status).await),
+            Err(e) => Err(FirecrackerError::Internal(e.to_string())),
+        }
+    }
+
+    /// Waits for the process to exit. An exit other than a clean one, e.g.
+    /// a crash or a kill signal, is reported as `ProcessExited`.
+    pub async fn wait(&mut self) -> Result<ExitStatus, FirecrackerError> {
+        let status = self
+            .child
+            .wait()
+            .await
+            .map_err(|e| FirecrackerError::Internal(e.to_string()))?;
+        if status.success() {
+            Ok(status)
+        } else {
+            Err(self.exited(status).await)
+        }
+    }
+
+    /// Kills the process, waits for it and removes its API socket. Does
+    /// nothing to a process that already exited apart from the cleanup.
+    pub async fn kill(&mut self) -> Result<(), FirecrackerError> {
+        let internal = |e: std::io::Error| FirecrackerError::Internal(e.to_string());
+        if self.child.try_wait().map_err(internal)?.is_none() {
+            self.child.start_kill().map_err(internal)?;
+            self.child.wait().await.map_err(internal)?;
+        }
+        match tokio::fs::remove_file(&self.api_sock).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(FirecrackerError::FileSystem {
+                    path: self.api_sock.clone(),
+                    source: e,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn exited(&self, status: ExitStatus) -> FirecrackerError {
+        // Let the readers drain what the process wrote before it exited
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        FirecrackerError::ProcessExited {
+            status: status.to_string(),
+            output: self.output().join("\n"),
+        }
+    }
+}
+
+// Firecracker refuses to start if the socket exists. One left behind by a
+// process that is gone is removed; one that still answers is an error.
+// Returns the directories it created, outermost first.
+async fn prepare_socket_path(
+    api_sock: &Path,
+    create_dir: bool,
+) -> Result<Vec<PathBuf>, FirecrackerError> {
+    let fs_error = |path: &Path, source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut created = Vec::new();
+    let dir = api_sock.parent().filter(|dir| !dir.as_os_str().is_empty());
+    if let Some(dir) = dir.filter(|_| create_dir) {
+        let mut missing = Some(dir);
+        while let Some(dir) = missing.filter(|dir| !dir.as_os_str().is_empty() && !dir.exists()) {
+            created.insert(0, dir.to_path_buf());
+            missing = dir.parent();
+        }
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| fs_error(dir, e))?;
+    }
+    let metadata = match tokio::fs::symlink_metadata(api_sock).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(created),
+        Err(e) => return Err(fs_error(api_sock, e)),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(FirecrackerError::InvalidPath(format!(
+            "{} exists and is not a socket",
+            api_sock.display()
+        )));
+    }
+    if UnixStream::connect(api_sock).await.is_ok() {
+        return Err(FirecrackerError::InvalidPath(format!(
+            "{} is in use by another process",
+            api_sock.display()
+        )));
+    }
+    tokio::fs::remove_file(api_sock)
+        .await
+        .map_err(|e| fs_error(api_sock, e))?;
+    Ok(created)
+}
+
+async fn capture(stream: impl AsyncRead + Unpin, output: Arc<Mutex<VecDeque<String>>>) {
+    let mut lines = BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut output = output.lock().unwrap();
+        if output.len() == OUTPUT_LINES {
+            output.pop_front();
+        }
+        output.push_back(line);
+    }
+}
@@ -1,54 +1,958 @@
-use crate::{action::InstanceActionInfo, error::FirecrackerError};
-use reqwest::{Client, StatusCode};
+use crate::audit::{AuditLog, AuditPolicy, AuditRecord};
+use crate::auth::TokenProvider;
+use crate::jailer::JailerContext;
+use crate::lifecycle::LifecycleTracker;
+use crate::mmds::MMDS_DEFAULT_SIZE_LIMIT;
+use crate::redaction::Redaction;
+use crate::snapshot::SnapshotChain;
+use crate::timeout::{OperationKind, DEFAULT_SNAPSHOT_READ_TIMEOUT};
+use crate::transport::{Budgets, Outgoing, SendError, Transport};
+use crate::validation::{
+    apply_path_policy, field_validation_errors, host_kernel_release, validate_body_size,
+    validate_unix_path, validate_writable_path, AsValidationTarget, ConfigWarning, CustomValidator,
+    PathPolicy, ValidatePatch, ValidationMode, ValidationTarget,
+};
+use crate::version::{meets, Capabilities, Version, VersionReq};
+use crate::{
+    action::{ActionType, InstanceActionInfo},
+    error::FirecrackerError,
+};
+use reqwest::{Client, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 use url::Url;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 #[cfg(test)]
 mod tests;
 
 pub mod action;
+pub mod alert;
+pub mod audit;
+pub mod auth;
 pub mod balloon;
 pub mod boot;
+pub mod capabilities;
+pub mod config;
 pub mod cpu;
 pub mod drive;
 pub mod entropy;
 pub mod error;
 pub mod instance;
+pub mod jailer;
+#[cfg(all(unix, feature = "launcher"))]
+pub mod launcher;
+pub mod lifecycle;
 pub mod logger;
 pub mod machine;
+pub mod manager;
 pub mod metrics;
+pub mod microvm;
 pub mod mmds;
 pub mod models;
 pub mod network;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+pub mod orchestration;
+pub mod paths;
+pub mod pool;
+pub mod preflight;
+#[cfg(feature = "prometheus")]
+pub mod prometheus_exporter;
+pub mod redaction;
+#[cfg(feature = "schemars")]
+pub mod schema;
 pub mod snapshot;
+pub mod status;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timeout;
+mod transport;
 pub mod validation;
 pub mod version;
 pub mod vm;
 pub mod vsock;
 
+// `models` is the only glob re-export at the crate root and owns or
+// re-exports every API model type, so names can't collide between globs.
+// Items from other modules are re-exported explicitly.
 pub use drive::DriveOperations;
+pub use microvm::MicroVm;
 pub use models::*;
 pub use network::NetworkInterfaceOperations;
 pub use snapshot::SnapshotOperations;
 pub use vm::VmOperations;
 
+// The last instance info seen and when
+pub(crate) type InstanceCache = Arc<RwLock<Option<(InstanceInfo, Instant)>>>;
+
 pub struct FirecrackerClient {
     base_url: String,
-    client: Client,
+    transport: Transport,
+    strict_mmds_root: bool,
+    validation_mode: ValidationMode,
+    path_policy: PathPolicy,
+    jailer: Option<JailerContext>,
+    lifecycle: Option<LifecycleTracker>,
+    audit: Option<(Arc<dyn AuditLog>, AuditPolicy)>,
+    validators: Vec<CustomValidator>,
+    capabilities: OnceLock<Capabilities>,
+    min_version: Option<VersionReq>,
+    strict_snapshot_checks: bool,
+    full_snapshot_taken: AtomicBool,
+    snapshot_chain: Mutex<Option<SnapshotChain>>,
+    snapshot_compat_check: bool,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    connect_retry: Option<ConnectRetry>,
+    redaction: Redaction,
+    // Shared with `reader` and `detached` clients, so what their polls see
+    // is known here too, and read by the lifecycle tracker
+    last_instance: InstanceCache,
+    max_body_size: usize,
+    max_mmds_body_size: usize,
+    warnings: Mutex<Vec<ConfigWarning>>,
+    warning_callback: Option<WarningCallback>,
+    connect_timeout: Option<Duration>,
+    total_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    operation_timeouts: HashMap<OperationKind, Duration>,
+    // What `preflight_check` falls back on and the `put_*` calls lint
+    sent_config: Mutex<FullVmConfiguration>,
+    preflight: bool,
+}
+
+// Requests over the API socket carry only a path, so the host part of the
+// URLs they are built from is never used
+#[cfg(unix)]
+const UNIX_BASE_URL: &str = "http://localhost";
+
+type WarningCallback = Arc<dyn Fn(&ConfigWarning) + Send + Sync>;
+
+// The client's settings for host paths; see `FirecrackerClient::on_local_fs`
+#[derive(Clone)]
+pub(crate) struct LocalPaths {
+    validation_mode: ValidationMode,
+    path_policy: PathPolicy,
+    jailer: Option<JailerContext>,
+}
+
+impl LocalPaths {
+    // Runs `check` against the local filesystem under StrictLocal and only
+    // the syntax check under Syntactic
+    pub(crate) fn check_local_path(
+        &self,
+        field: &'static str,
+        path: &str,
+        check: impl Fn(&str) -> Result<(), ValidationError>,
+    ) -> Result<(), ValidationErrors> {
+        let result = match self.validation_mode {
+            ValidationMode::Off => Ok(()),
+            ValidationMode::Syntactic => validate_unix_path(path),
+            ValidationMode::StrictLocal => check(path),
+        };
+        result.map_err(|e| field_validation_errors(field, e))
+    }
+
+    pub(crate) fn check_writable_path(
+        &self,
+        field: &'static str,
+        path: &str,
+    ) -> Result<(), ValidationErrors> {
+        self.check_local_path(field, path, validate_writable_path)
+    }
+
+    // The host's kernel release for the checks that depend on it, only under
+    // StrictLocal, where the VMM is taken to run on this host
+    pub(crate) fn host_kernel(&self) -> Option<String> {
+        if self.validation_mode != ValidationMode::StrictLocal || cfg!(feature = "no-fs-validation")
+        {
+            return None;
+        }
+        host_kernel_release()
+    }
+
+    // The path to send for `path` under the client's path policy, inside
+    // the jailer's chroot if there is one
+    pub(crate) fn resolve_path(
+        &self,
+        field: &'static str,
+        path: &str,
+    ) -> Result<String, FirecrackerError> {
+        let path = match self.validation_mode {
+            ValidationMode::StrictLocal => apply_path_policy(field, path, &self.path_policy)?,
+            ValidationMode::Off | ValidationMode::Syntactic => path.to_string(),
+        };
+        match &self.jailer {
+            Some(jailer) => Ok(jailer
+                .translate(&path)
+                .map_err(|e| field_validation_errors(field, e))?),
+            None => Ok(path),
+        }
+    }
+}
+
+// How long refused connections are retried; see `with_connect_retry`
+#[derive(Debug, Clone, Copy)]
+struct ConnectRetry {
+    window: Duration,
+    interval: Duration,
 }
 
 impl FirecrackerClient {
     pub async fn new(base_url: &str) -> Result<Self, FirecrackerError> {
-        Ok(Self {
-            base_url: base_url.to_string(),
-            client: Client::new(),
+        Ok(Self::with_transport(
+            base_url.to_string(),
+            Transport::Http(Client::new()),
+        ))
+    }
+
+    /// A client that talks to Firecracker over its API socket, the
+    /// `--api-sock` path, opening a connection per request.
+    #[cfg(unix)]
+    pub async fn new_unix(socket_path: impl AsRef<Path>) -> Result<Self, FirecrackerError> {
+        Ok(Self::with_transport(
+            UNIX_BASE_URL.to_string(),
+            Transport::Unix(socket_path.as_ref().to_path_buf()),
+        ))
+    }
+
+    fn with_transport(base_url: String, transport: Transport) -> Self {
+        Self {
+            base_url,
+            transport,
+            strict_mmds_root: true,
+            validation_mode: ValidationMode::default(),
+            path_policy: PathPolicy::default(),
+            jailer: None,
+            lifecycle: None,
+            audit: None,
+            validators: Vec::new(),
+            capabilities: OnceLock::new(),
+            min_version: None,
+            strict_snapshot_checks: false,
+            full_snapshot_taken: AtomicBool::new(false),
+            snapshot_chain: Mutex::new(None),
+            snapshot_compat_check: false,
+            token_provider: None,
+            connect_retry: None,
+            redaction: Redaction::default(),
+            last_instance: Arc::default(),
+            max_body_size: validation::DEFAULT_MAX_BODY_SIZE,
+            max_mmds_body_size: MMDS_DEFAULT_SIZE_LIMIT,
+            warnings: Mutex::new(Vec::new()),
+            warning_callback: None,
+            connect_timeout: None,
+            total_timeout: None,
+            read_timeout: None,
+            operation_timeouts: HashMap::new(),
+            sent_config: Mutex::default(),
+            preflight: false,
+        }
+    }
+
+    // A client with default settings for the same VMM, sharing the
+    // connection pool, for background tasks that only read its state
+    pub(crate) fn reader(&self) -> Self {
+        self.background(self.transport.clone())
+    }
+
+    // Like `reader`, with a connection pool of its own, for use from another
+    // Tokio runtime
+    pub(crate) fn detached(&self) -> Result<Self, FirecrackerError> {
+        let transport = match &self.transport {
+            Transport::Http(_) => Transport::Http(self.build_http_client()?),
+            #[cfg(unix)]
+            Transport::Unix(socket) => Transport::Unix(socket.clone()),
+        };
+        Ok(self.background(transport))
+    }
+
+    // What `reader` and `detached` keep: how to reach the VMM and what it
+    // was seen doing
+    fn background(&self, transport: Transport) -> Self {
+        Self {
+            token_provider: self.token_provider.clone(),
+            connect_retry: self.connect_retry,
+            redaction: self.redaction.clone(),
+            last_instance: self.last_instance.clone(),
+            connect_timeout: self.connect_timeout,
+            total_timeout: self.total_timeout,
+            read_timeout: self.read_timeout,
+            operation_timeouts: self.operation_timeouts.clone(),
+            ..Self::with_transport(self.base_url.clone(), transport)
+        }
+    }
+
+    /// The Firecracker version this client talks to, once it is known from
+    /// `negotiate`, `get_version` or set by hand.
+    pub fn vmm_version(&self) -> Option<Version> {
+        self.capabilities().map(|c| c.version.clone())
+    }
+
+    /// Records the Firecracker version so version-dependent calls can fail
+    /// fast instead of hitting endpoints the VMM doesn't have. A client talks
+    /// to a single VMM, so once a version is known, recording a different one
+    /// fails with [`Config`](FirecrackerError::Config). A version below
+    /// [`with_min_version`](Self::with_min_version) is recorded but fails
+    /// with [`UnsupportedVmmVersion`](FirecrackerError::UnsupportedVmmVersion).
+    pub fn set_vmm_version(&self, version: Version) -> Result<(), FirecrackerError> {
+        self.set_capabilities(Capabilities::for_version(version.clone()));
+        let capabilities = self.capabilities().expect("capabilities were just set");
+        if capabilities.version != version {
+            return Err(FirecrackerError::Config(format!(
+                "VMM version is already known to be {}, not {}",
+                capabilities.version, version
+            )));
+        }
+        if let Some(req) = self.min_version() {
+            crate::version::check_version(capabilities, req)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_capabilities(&self, capabilities: Capabilities) {
+        let _ = self.capabilities.set(capabilities);
+    }
+
+    /// Capabilities of the VMM, or `None` until its version is known.
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.get()
+    }
+
+    /// Whether the VMM supports `PUT /entropy`, or `None` if its version is
+    /// not known yet.
+    pub fn supports_entropy(&self) -> Option<bool> {
+        self.capabilities().map(|c| c.supports_entropy)
+    }
+    /// Requires the VMM version to satisfy `req`, e.g. `^1.4`. The check
+    /// runs whenever the version is learned or looked up: by `negotiate`,
+    /// `get_version` and `set_vmm_version`.
+    pub fn with_min_version(mut self, req: &str) -> Result<Self, FirecrackerError> {
+        let req = VersionReq::parse(req).map_err(|e| {
+            FirecrackerError::Config(format!("Invalid version requirement {:?}: {}", req, e))
+        })?;
+        self.min_version = Some(req);
+        Ok(self)
+    }
+
+    pub fn min_version(&self) -> Option<&VersionReq> {
+        self.min_version.as_ref()
+    }
+
+    /// Fails requests that take longer than `timeout` in total, connecting
+    /// included. Snapshot operations get at least their read timeout, or
+    /// [`DEFAULT_SNAPSHOT_READ_TIMEOUT`] without one, so a total timeout
+    /// meant for configuration requests doesn't cut a snapshot short. See
+    /// [`timeout`](crate::timeout).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.total_timeout = Some(timeout);
+        self
+    }
+
+    /// Fails requests that can't connect to the VMM within `timeout`, e.g.
+    /// because its socket isn't there.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Result<Self, FirecrackerError> {
+        self.connect_timeout = Some(timeout);
+        self.rebuild_http_client()?;
+        Ok(self)
+    }
+
+    /// Fails requests whose response takes longer than `timeout` once
+    /// connected, unless their [`OperationKind`] has a timeout of its own.
+    /// Snapshot operations get at least [`DEFAULT_SNAPSHOT_READ_TIMEOUT`].
+    ///
+    /// Over HTTP the time it takes to connect can't be told apart, so the
+    /// read timeout is extended by the connect timeout; without one,
+    /// connecting counts toward the read timeout.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Overrides the read timeout for requests of `kind`.
+    pub fn with_operation_timeout(mut self, kind: OperationKind, timeout: Duration) -> Self {
+        self.operation_timeouts.insert(kind, timeout);
+        self
+    }
+
+    /// The read timeout requests of `kind` get, if any.
+    pub fn read_timeout(&self, kind: OperationKind) -> Option<Duration> {
+        if let Some(timeout) = self.operation_timeouts.get(&kind) {
+            return Some(*timeout);
+        }
+        match kind {
+            OperationKind::Snapshot => self
+                .read_timeout
+                .map(|timeout| timeout.max(DEFAULT_SNAPSHOT_READ_TIMEOUT)),
+            _ => self.read_timeout,
+        }
+    }
+
+    // The total timeout requests of `kind` get: snapshot operations outlast
+    // it when their read timeout is longer
+    fn total_timeout(&self, kind: OperationKind) -> Option<Duration> {
+        let total = self.total_timeout?;
+        Some(match kind {
+            OperationKind::Snapshot => total.max(
+                self.read_timeout(kind)
+                    .unwrap_or(DEFAULT_SNAPSHOT_READ_TIMEOUT),
+            ),
+            _ => total,
         })
     }
 
+    // Only the connect timeout is the client's; the others are applied per
+    // request, as they are for clients on the API socket
+    fn rebuild_http_client(&mut self) -> Result<(), FirecrackerError> {
+        if let Transport::Http(_) = self.transport {
+            self.transport = Transport::Http(self.build_http_client()?);
+        }
+        Ok(())
+    }
+
+    fn build_http_client(&self) -> Result<Client, FirecrackerError> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Retries requests whose connection is refused, or whose API socket
+    /// doesn't exist yet, every `interval` for up to `window` after the
+    /// first attempt, e.g. while a freshly spawned Firecracker hasn't started
+    /// listening yet. Anything else, other connection errors such as a host
+    /// that doesn't resolve, HTTP errors and timeouts included, fails as
+    /// usual. The timeout set with
+    /// `with_timeout` applies to each attempt.
+    pub fn with_connect_retry(mut self, window: Duration, interval: Duration) -> Self {
+        self.connect_retry = Some(ConnectRetry { window, interval });
+        self
+    }
+
+    /// Refuses to send request bodies larger than `limit` bytes once
+    /// serialized, failing with a validation error instead. Defaults to
+    /// [`DEFAULT_MAX_BODY_SIZE`](validation::DEFAULT_MAX_BODY_SIZE). MMDS
+    /// data has a limit of its own, see `with_max_mmds_body_size`.
+    pub fn with_max_body_size(mut self, limit: usize) -> Self {
+        self.max_body_size = limit;
+        self
+    }
+
+    /// Like `with_max_body_size`, for `PUT` and `PATCH /mmds`. Defaults to
+    /// [`MMDS_DEFAULT_SIZE_LIMIT`]; set it to the VMM's `--mmds-size-limit`
+    /// when that is changed.
+    pub fn with_max_mmds_body_size(mut self, limit: usize) -> Self {
+        self.max_mmds_body_size = limit;
+        self
+    }
+
+    /// Sends `Authorization: Bearer <token>` with every request, taking the
+    /// token from `provider`. A 401 is retried once with a refreshed token.
+    pub fn with_token_provider(mut self, provider: Arc<dyn TokenProvider>) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    /// Sets which request and response bodies are too sensitive to show in
+    /// errors, the audit journal and planned requests. See
+    /// [`redaction`](crate::redaction).
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    pub fn redaction(&self) -> &Redaction {
+        &self.redaction
+    }
+
+    /// Before creating a Diff snapshot, check that dirty-page tracking is on
+    /// and that this client already took a Full snapshot.
+    pub fn with_strict_snapshot_checks(mut self, enabled: bool) -> Self {
+        self.strict_snapshot_checks = enabled;
+        self
+    }
+
+    /// Before loading a snapshot, read its header and fail if the VMM can't
+    /// load it. Only runs under `ValidationMode::StrictLocal` once the VMM
+    /// version is known, and never in `no-fs-validation` builds.
+    pub fn with_snapshot_compat_check(mut self, enabled: bool) -> Self {
+        self.snapshot_compat_check = enabled;
+        self
+    }
+
+    /// Records every snapshot created through this client in `chain` and
+    /// saves its manifest after each one.
+    pub fn with_snapshot_chain(self, chain: SnapshotChain) -> Self {
+        *self.snapshot_chain.lock().unwrap() = Some(chain);
+        self
+    }
+
+    /// A copy of the snapshot chain, if one is attached.
+    pub fn snapshot_chain(&self) -> Option<SnapshotChain> {
+        self.snapshot_chain.lock().unwrap().clone()
+    }
+
+    /// Sets how much validation runs locally before requests are sent. See
+    /// [`ValidationMode`] for the checks each mode runs.
+    pub fn with_validation_mode(mut self, mode: ValidationMode) -> Self {
+        self.validation_mode = mode;
+        self
+    }
+
+    pub fn validation_mode(&self) -> ValidationMode {
+        self.validation_mode
+    }
+
+    /// Sets how host paths are resolved before they are sent. See
+    /// [`PathPolicy`]; `no-fs-validation` builds skip it, so paths are sent
+    /// unresolved and symlinks are never rejected.
+    pub fn with_path_policy(mut self, policy: PathPolicy) -> Self {
+        self.path_policy = policy;
+        self
+    }
+
+    pub fn path_policy(&self) -> PathPolicy {
+        self.path_policy
+    }
+
+    /// Rewrites the host paths in requests into paths inside the jailer's
+    /// chroot. See [`jailer`](crate::jailer).
+    pub fn with_jailer(mut self, jailer: JailerContext) -> Self {
+        self.jailer = Some(jailer);
+        self
+    }
+
+    pub fn jailer(&self) -> Option<&JailerContext> {
+        self.jailer.as_ref()
+    }
+
+    /// Rejects boot-time-only requests locally once the VM has started. See
+    /// [`lifecycle`](crate::lifecycle). The tracker goes by the last known
+    /// state, see [`last_known_state`](Self::last_known_state).
+    pub fn with_lifecycle_tracking(mut self, enabled: bool) -> Self {
+        self.lifecycle = enabled.then(|| LifecycleTracker::over(self.last_instance.clone()));
+        self
+    }
+
+    pub fn lifecycle(&self) -> Option<&LifecycleTracker> {
+        self.lifecycle.as_ref()
+    }
+
+    /// Runs [`preflight_check`](Self::preflight_check) before every
+    /// `InstanceStart` and fails with `InvalidConfig` instead of sending it
+    /// when the VM can't boot. See [`preflight`](crate::preflight).
+    pub fn with_preflight_check(mut self, enabled: bool) -> Self {
+        self.preflight = enabled;
+        self
+    }
+
+    /// Records every request this client sends in `log`, with bodies
+    /// recorded as the default [`AuditPolicy`] says. See
+    /// [`audit`](crate::audit).
+    pub fn with_audit_log(self, log: Arc<dyn AuditLog>) -> Self {
+        self.with_audit_log_policy(log, AuditPolicy::default())
+    }
+
+    pub fn with_audit_log_policy(mut self, log: Arc<dyn AuditLog>, policy: AuditPolicy) -> Self {
+        self.audit = Some((log, policy));
+        self
+    }
+
+    /// Hands every [`ConfigWarning`] to `callback` as it is found, instead of
+    /// collecting it for `take_warnings`.
+    pub fn with_warning_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&ConfigWarning) + Send + Sync + 'static,
+    {
+        self.warning_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// The warnings collected since the last call, oldest first.
+    pub fn take_warnings(&self) -> Vec<ConfigWarning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+
+    pub(crate) fn warn(&self, warning: ConfigWarning) {
+        match &self.warning_callback {
+            Some(callback) => callback(&warning),
+            None => self.warnings.lock().unwrap().push(warning),
+        }
+    }
+
+    /// Registers a validator that runs on every request after the built-in
+    /// validation for its model, e.g. to enforce where drive images may live.
+    /// Validators are skipped when the validation mode is `Off`.
+    pub fn add_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&ValidationTarget<'_>) -> Vec<ValidationError> + Send + Sync + 'static,
+    {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// Controls whether MMDS payloads must have a JSON object at their root.
+    /// Firecracker rejects anything else, so this is on by default; turn it
+    /// off only for VMM builds that accept other root types.
+    pub fn with_strict_mmds_root(mut self, enabled: bool) -> Self {
+        self.strict_mmds_root = enabled;
+        self
+    }
+
+    pub(crate) fn ensure_supported(
+        &self,
+        operation: &str,
+        min_version: &Version,
+    ) -> Result<(), FirecrackerError> {
+        match self.vmm_version() {
+            Some(version) if !meets(&version, min_version) => Err(FirecrackerError::Unsupported {
+                operation: operation.to_string(),
+                version: version.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Runs the model's own checks, then the custom validators, unless
+    // validation is off
+    pub(crate) fn validate_model<T>(&self, model: &T) -> Result<(), FirecrackerError>
+    where
+        T: Validate + AsValidationTarget,
+    {
+        match self.validation_mode {
+            ValidationMode::Off => Ok(()),
+            ValidationMode::Syntactic | ValidationMode::StrictLocal => {
+                model.validate()?;
+                self.run_validators(&model.validation_target())
+            }
+        }
+    }
+
+    // Checks a PATCH body against its relaxed rules unless validation is off
+    pub(crate) fn validate_patch(
+        &self,
+        model: &impl ValidatePatch,
+    ) -> Result<(), FirecrackerError> {
+        match self.validation_mode {
+            ValidationMode::Off => Ok(()),
+            ValidationMode::Syntactic | ValidationMode::StrictLocal => {
+                model.validate_patch()?;
+                self.run_validators(&model.patch_target())
+            }
+        }
+    }
+
+    pub(crate) fn run_validators(
+        &self,
+        target: &ValidationTarget<'_>,
+    ) -> Result<(), FirecrackerError> {
+        Ok(self.custom_validation(target)?)
+    }
+
+    // Custom validator errors aren't tied to one field, so they are reported
+    // under `__all__` like schema-level errors
+    pub(crate) fn custom_validation(
+        &self,
+        target: &ValidationTarget<'_>,
+    ) -> Result<(), ValidationErrors> {
+        if self.validation_mode == ValidationMode::Off {
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+        for error in self
+            .validators
+            .iter()
+            .flat_map(|validator| validator(target))
+        {
+            errors.add("__all__", error);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub(crate) async fn validated_put<T>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<(), FirecrackerError>
+    where
+        T: Serialize + Validate + AsValidationTarget + Sync,
+    {
+        self.validate_model(body)?;
+        self.send_json(Method::PUT, path, body).await
+    }
+
+    pub(crate) async fn validated_patch<T>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<(), FirecrackerError>
+    where
+        T: Serialize + ValidatePatch + Sync,
+    {
+        self.validate_patch(body)?;
+        self.send_json(Method::PATCH, path, body).await
+    }
+
+    // Sends `body` without validating it, for callers that already did
+    pub(crate) async fn send_json<T>(
+        &self,
+        method: Method,
+        path: &str,
+        body: &T,
+    ) -> Result<(), FirecrackerError>
+    where
+        T: Serialize + Sync + ?Sized,
+    {
+        if let Some(lifecycle) = &self.lifecycle {
+            lifecycle.check(&method, path)?;
+        }
+        let body = serde_json::to_vec(body)?;
+        self.execute(method, path, Some(body)).await?;
+        Ok(())
+    }
+
+    // GETs `path` and decodes the response
+    pub(crate) async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<T, FirecrackerError> {
+        models::decode_response(self.execute(Method::GET, path, None).await?)
+    }
+
+    // Sends a request and returns the response body, failing on an error
+    // status. Every request the client makes goes through here.
+    pub(crate) async fn execute(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<String, FirecrackerError> {
+        let url = self.url(path)?;
+        if let Some(body) = &body {
+            let path = path.trim_matches('/');
+            let limit = if path == paths::MMDS {
+                self.max_mmds_body_size
+            } else {
+                self.max_body_size
+            };
+            validate_body_size(&format!("{} /{}", method, path), body.len(), limit)
+                .map_err(|e| field_validation_errors("body", e))?;
+        }
+        let mut record = self.audit.as_ref().map(|(_, policy)| AuditRecord {
+            timestamp_ms: audit::timestamp_ms(SystemTime::now()),
+            vm: self.endpoint(),
+            vm_id: self.jailer.as_ref().map(|jailer| jailer.id.clone()),
+            method: method.to_string(),
+            path: path.trim_start_matches('/').to_string(),
+            body: body.as_deref().map(|body| policy.summarize(path, body)),
+            status: None,
+            error: None,
+            latency_ms: 0,
+        });
+
+        let token = match &self.token_provider {
+            Some(provider) => Some(
+                provider
+                    .token()
+                    .await
+                    .map_err(FirecrackerError::TokenProvider)?,
+            ),
+            None => None,
+        };
+        let started = Instant::now();
+        let mut sent = self
+            .send_request(&method, path, &url, body.as_deref(), token.as_deref())
+            .await;
+        // A gateway that refuses the token gets one fresh token and one retry
+        let mut refresh_error = None;
+        if let (Some(provider), Ok((StatusCode::UNAUTHORIZED, _))) = (&self.token_provider, &sent) {
+            match provider.refresh().await {
+                Ok(token) => {
+                    sent = self
+                        .send_request(&method, path, &url, body.as_deref(), Some(&token))
+                        .await
+                }
+                Err(err) => refresh_error = Some(FirecrackerError::TokenProvider(err)),
+            }
+        }
+
+        if let (Some((log, _)), Some(record)) = (&self.audit, record.as_mut()) {
+            record.latency_ms = audit::millis(started.elapsed());
+            match &sent {
+                Ok((status, _)) => record.status = Some(status.as_u16()),
+                Err(err) => record.error = Some(err.to_string()),
+            }
+            log.record(record);
+        }
+
+        if let Some(err) = refresh_error {
+            return Err(err);
+        }
+        let (status, text) = sent?;
+        if !status.is_success() {
+            // Firecracker's faults can quote the rejected body back
+            return Err(FirecrackerError::Api {
+                status_code: status.as_u16(),
+                message: self.redaction.apply(path, &text),
+            });
+        }
+        // Kept for `preflight_check` and the `put_*` lint warnings
+        if let (&Method::PUT, Some(body)) = (&method, &body) {
+            self.record_sent(path, body);
+        }
+        Ok(text)
+    }
+
+    // Sends a request, retrying refused connections within the connect retry
+    // window, if one is set. Each attempt gets the read timeout.
+    async fn send_request(
+        &self,
+        method: &Method,
+        path: &str,
+        url: &Url,
+        body: Option<&[u8]>,
+        token: Option<&str>,
+    ) -> Result<(StatusCode, String), FirecrackerError> {
+        let deadline = self
+            .connect_retry
+            .map(|retry| (Instant::now() + retry.window, retry.interval));
+        let kind = OperationKind::of(method, path);
+        let budgets = Budgets {
+            connect: self.connect_timeout,
+            read: self.read_timeout(kind),
+            total: self.total_timeout(kind),
+        };
+        loop {
+            let err = match self.send_once(method, url, body, token, budgets).await {
+                Ok(sent) => return Ok(sent),
+                Err(err) => err,
+            };
+            // A request that timed out already used up its budget
+            let timed_out = err
+                .timed_out()
+                .and_then(|budget| Some((budget, budgets.limit(budget)?)));
+            if let Some((budget, limit)) = timed_out {
+                return Err(FirecrackerError::RequestTimeout {
+                    operation: format!("{} /{}", method, path.trim_matches('/')),
+                    budget,
+                    limit,
+                });
+            }
+            match &deadline {
+                Some((deadline, interval)) if err.is_refused() && Instant::now() < *deadline => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    tokio::time::sleep((*interval).min(remaining)).await;
+                }
+                _ => return Err(err.into()),
+            }
+        }
+    }
+
+    async fn send_once(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: Option<&[u8]>,
+        token: Option<&str>,
+        budgets: Budgets,
+    ) -> Result<(StatusCode, String), SendError> {
+        let request = Outgoing {
+            method,
+            url,
+            body,
+            token,
+        };
+        self.transport.send(request, budgets).await
+    }
+
+    // Where requests go, as recorded in the audit journal: the base URL, or
+    // the API socket
+    fn endpoint(&self) -> String {
+        match &self.transport {
+            Transport::Http(_) => self.base_url.clone(),
+            #[cfg(unix)]
+            Transport::Unix(socket) => socket.display().to_string(),
+        }
+    }
+
+    pub(crate) async fn send_action(
+        &self,
+        action: &InstanceActionInfo,
+    ) -> Result<(), FirecrackerError> {
+        if self.preflight && action.action_type == ActionType::InstanceStart {
+            self.preflight_check().await?.into_result()?;
+        }
+        self.validated_put(paths::ACTIONS, action).await?;
+        if action.action_type == ActionType::InstanceStart {
+            self.observe_state(InstanceState::Running);
+        }
+        Ok(())
+    }
+
+    // What the path checks and path resolution need, copied out of the
+    // client so they can run on the blocking pool
+    pub(crate) fn local_paths(&self) -> LocalPaths {
+        LocalPaths {
+            validation_mode: self.validation_mode,
+            path_policy: self.path_policy,
+            jailer: self.jailer.clone(),
+        }
+    }
+
+    // Runs `f` on Tokio's blocking pool under StrictLocal, where the checks
+    // stat and canonicalize host paths. The other modes don't touch the
+    // filesystem, so `f` runs in place.
+    pub(crate) async fn on_local_fs<T, F>(&self, f: F) -> Result<T, FirecrackerError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&LocalPaths) -> Result<T, FirecrackerError> + Send + 'static,
+    {
+        let local = self.local_paths();
+        if local.validation_mode != ValidationMode::StrictLocal {
+            return f(&local);
+        }
+        tokio::task::spawn_blocking(move || f(&local))
+            .await
+            .map_err(|e| FirecrackerError::Internal(format!("Path checks failed: {}", e)))?
+    }
+
+    // The path of device `id` under `collection`, e.g. `drives/rootfs`, with
+    // the id percent-encoded into a single segment. Ids that can't be one,
+    // empty, `.` or `..`, which URL parsing would resolve away, fail as a
+    // validation error on `field`.
+    pub(crate) fn device_path(
+        &self,
+        collection: &str,
+        field: &'static str,
+        id: &str,
+    ) -> Result<String, FirecrackerError> {
+        if id.is_empty() || id == "." || id == ".." {
+            let mut err = ValidationError::new("invalid_path_segment");
+            err.message = Some(format!("{:?} can't be used as a path segment", id).into());
+            return Err(field_validation_errors(field, err).into());
+        }
+        Ok(paths::device(collection, id))
+    }
+
+    // Paths are joined without their leading and trailing slashes, so only
+    // `GET /` ends in one
     pub(crate) fn url(&self, path: &str) -> Result<Url, FirecrackerError> {
         let url = format!(
             "{}/{}",
             self.base_url.trim_end_matches('/'),
-            path.trim_start_matches('/')
+            path.trim_matches('/')
         );
         Url::parse(&url).map_err(FirecrackerError::UrlParseError)
     }
@@ -57,19 +961,6 @@ impl FirecrackerClient {
         &self,
         action: &InstanceActionInfo,
     ) -> Result<(), FirecrackerError> {
-        let url = self.url("/actions")?;
-
-        let response = self.client.put(url).json(&action).send().await?;
-
-        match response.status() {
-            StatusCode::NO_CONTENT => Ok(()),
-            status => {
-                let error_msg = response.text().await?;
-                Err(FirecrackerError::Api {
-                    status_code: status.as_u16(),
-                    message: error_msg,
-                })
-            }
-        }
+        self.send_action(action).await
     }
 }
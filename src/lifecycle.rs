@@ -0,0 +1,101 @@
+//! Local enforcement of Firecracker's boot-time-only operations.
+//!
+//! Most of the VM's configuration can only be set before `InstanceStart`;
+//! afterwards Firecracker answers with a 400. With a [`LifecycleTracker`]
+//! attached through
+//! [`FirecrackerClient::with_lifecycle_tracking`](crate::FirecrackerClient::with_lifecycle_tracking)
+//! those requests fail locally with `InvalidState` once the VM has started.
+
+use crate::error::FirecrackerError;
+use crate::instance::set_cached_state;
+use crate::models::{InstanceInfo, InstanceState};
+use crate::paths;
+use crate::InstanceCache;
+use reqwest::Method;
+use std::time::Instant;
+
+// Requests Firecracker only accepts before the VM starts, by method and path
+// prefix. Prefixes ending in `/` match the per-device paths under them.
+const PRE_BOOT_ONLY: &[(&str, &str)] = &[
+    ("PUT", paths::BOOT_SOURCE),
+    ("PUT", paths::MACHINE_CONFIG),
+    ("PATCH", paths::MACHINE_CONFIG),
+    ("PUT", "drives/"),
+    ("PUT", "network-interfaces/"),
+    ("PUT", paths::MMDS_CONFIG),
+    ("PUT", paths::VSOCK),
+    ("PUT", paths::ENTROPY),
+    ("PUT", paths::CPU_CONFIG),
+    ("PUT", paths::LOGGER),
+    ("PUT", paths::METRICS),
+    ("PUT", paths::BALLOON),
+    ("PUT", paths::SNAPSHOT_LOAD),
+];
+
+/// Whether the VM behind a client has started, as far as the client knows.
+/// A client's tracker reads the client's last known state, see
+/// [`last_known_state`](crate::FirecrackerClient::last_known_state), which
+/// follows successful `InstanceStart`s, pauses, resumes and snapshot loads
+/// through the client as well as every `GET /`.
+#[derive(Debug, Default)]
+pub struct LifecycleTracker {
+    instance: InstanceCache,
+}
+
+impl LifecycleTracker {
+    /// A tracker with a state of its own, not attached to any client.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn over(instance: InstanceCache) -> Self {
+        Self { instance }
+    }
+
+    /// Anything but "Not started" means the VM has booted, paused or not.
+    pub fn is_started(&self) -> bool {
+        self.instance
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|(info, _)| info.instance_state().is_started())
+    }
+
+    /// Records the VM as running, unless it is already known to have
+    /// started.
+    pub fn mark_started(&self) {
+        if !self.is_started() {
+            set_cached_state(&self.instance, InstanceState::Running);
+        }
+    }
+
+    /// Takes the state from `GET /`.
+    pub fn resync(&self, info: &InstanceInfo) {
+        *self.instance.write().unwrap() = Some((info.clone(), Instant::now()));
+    }
+
+    /// Fails with `InvalidState` if the VM has started and `method` on
+    /// `path` is a boot-time-only operation. Runtime operations, such as
+    /// drive and interface PATCHes, balloon updates, MMDS data and
+    /// snapshots, always pass.
+    pub fn check(&self, method: &Method, path: &str) -> Result<(), FirecrackerError> {
+        if !self.is_started() || !is_pre_boot_only(method, path) {
+            return Ok(());
+        }
+        Err(FirecrackerError::InvalidState {
+            current_state: "Started".to_string(),
+            expected_states: vec!["Not started".to_string()],
+        })
+    }
+}
+
+fn is_pre_boot_only(method: &Method, path: &str) -> bool {
+    let path = path.trim_matches('/');
+    PRE_BOOT_ONLY.iter().any(|(m, prefix)| {
+        method.as_str() == *m
+            && match prefix.strip_suffix('/') {
+                Some(dir) => path.starts_with(prefix) && path.len() > dir.len() + 1,
+                None => path == *prefix,
+            }
+    })
+}
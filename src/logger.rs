@@ -1,49 +1,247 @@
-use crate::validation::validate_writable_path;
+use crate::paths;
 use crate::FirecrackerError;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
-use validator::Validate;
+use chrono::NaiveDateTime;
+use thiserror::Error;
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
-pub struct Logger {
-    #[validate(custom = "validate_writable_path")]
-    pub log_path: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[validate(regex(
-        path = "LOG_LEVEL_REGEX",
-        message = "Invalid log level. Must be one of: Error, Warning, Info, Debug"
-    ))]
-    pub level: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub show_level: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub show_log_origin: Option<bool>,
-}
+#[cfg(unix)]
+use futures_util::stream::{self, Stream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+#[cfg(unix)]
+use tokio::net::unix::pipe;
 
-lazy_static::lazy_static! {
-    static ref LOG_LEVEL_REGEX: regex::Regex = regex::Regex::new(r"^(Error|Warning|Info|Debug)$").unwrap();
-}
+pub use crate::models::LogLevel;
+
+/// Kept so code importing `logger::Logger` keeps compiling; it is the same
+/// type as [`crate::models::Logger`].
+#[deprecated(note = "use firecracker_http_client::models::Logger instead")]
+pub type Logger = crate::models::Logger;
 
 #[async_trait]
 pub trait LoggerOperations {
-    async fn put_logger(&self, logger: &Logger) -> Result<(), FirecrackerError>;
+    async fn put_logger(&self, logger: &crate::models::Logger) -> Result<(), FirecrackerError>;
 }
 
 #[async_trait]
 impl LoggerOperations for crate::FirecrackerClient {
-    async fn put_logger(&self, logger: &Logger) -> Result<(), FirecrackerError> {
-        logger.validate()?;
+    async fn put_logger(&self, logger: &crate::models::Logger) -> Result<(), FirecrackerError> {
+        let logger = logger.clone();
+        let logger = self
+            .on_local_fs(move |local| {
+                local.check_writable_path("log_path", &logger.log_path)?;
+                local.resolve_logger_path(&logger)
+            })
+            .await?;
+        self.validated_put(paths::LOGGER, &logger).await
+    }
+}
 
-        let url = self.url("logger")?;
-        let response = self.client.put(url).json(logger).send().await?;
+impl crate::LocalPaths {
+    pub(crate) fn resolve_logger_path(
+        &self,
+        logger: &crate::models::Logger,
+    ) -> Result<crate::models::Logger, FirecrackerError> {
+        Ok(crate::models::Logger {
+            log_path: self.resolve_path("log_path", &logger.log_path)?,
+            ..logger.clone()
+        })
+    }
+}
+
+/// Reasons a line could not be parsed as a structured Firecracker log line.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("line has no timestamp")]
+    MissingTimestamp,
+    #[error("invalid timestamp: {0}")]
+    InvalidTimestamp(String),
+    #[error("line has no [instance] header")]
+    MissingHeader,
+}
+
+/// Source location attached to a log line when `show_log_origin` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogOrigin {
+    pub file: String,
+    pub line: u32,
+}
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+/// A single line read from a Firecracker log. Lines in the standard
+/// `<timestamp> [<instance>:<thread>:<LEVEL>:<file>:<line>] <message>` format
+/// are split into their parts; the thread, level and origin are optional
+/// since they depend on the Firecracker version and logger settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub timestamp: Option<NaiveDateTime>,
+    pub instance_id: Option<String>,
+    pub thread: Option<String>,
+    pub level: Option<LogLevel>,
+    pub origin: Option<LogOrigin>,
+    pub message: String,
+    pub raw: String,
+}
+
+impl LogLine {
+    /// Parses `line`, keeping it verbatim in `message` if it is not in the
+    /// standard format.
+    pub fn parse(line: &str) -> Self {
+        parse_line(line).unwrap_or_else(|_| LogLine {
+            timestamp: None,
+            instance_id: None,
+            thread: None,
+            level: None,
+            origin: None,
+            message: line.to_string(),
+            raw: line.to_string(),
+        })
+    }
+
+    /// Extracts the guest boot time in microseconds from the
+    /// `Guest-boot-time = <n> us ...` line Firecracker logs once the guest
+    /// signals that boot completed.
+    pub fn boot_time_us(&self) -> Option<u64> {
+        let rest = self.message.split_once("Guest-boot-time =")?.1;
+        let (value, unit) = rest.trim_start().split_once(' ')?;
+        if unit.trim_start().starts_with("us") {
+            value.parse().ok()
+        } else {
+            None
         }
+    }
+}
+
+/// Parses a line in the standard Firecracker log format.
+pub fn parse_line(line: &str) -> Result<LogLine, ParseError> {
+    let (timestamp, rest) = line.split_once(' ').ok_or(ParseError::MissingTimestamp)?;
+    let timestamp = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f")
+        .map_err(|_| ParseError::InvalidTimestamp(timestamp.to_string()))?;
+    let (header, message) = rest
+        .strip_prefix('[')
+        .and_then(|r| r.split_once(']'))
+        .ok_or(ParseError::MissingHeader)?;
+
+    let mut parts: Vec<&str> = header.split(':').collect();
+    let instance_id = parts.remove(0).to_string();
+
+    let mut level = None;
+    let mut origin = None;
+    if let Some(i) = parts.iter().position(|p| is_log_level(p)) {
+        level = parts[i].parse().ok();
+        origin = parse_origin(&parts[i + 1..]);
+        parts.truncate(i);
+    } else if let Some(parsed) = parts
+        .len()
+        .checked_sub(2)
+        .and_then(|i| parse_origin(&parts[i..]))
+    {
+        origin = Some(parsed);
+        parts.truncate(parts.len() - 2);
+    }
+    let thread = Some(parts.join(":")).filter(|t| !t.is_empty());
 
-        Ok(())
+    Ok(LogLine {
+        timestamp: Some(timestamp),
+        instance_id: Some(instance_id),
+        thread,
+        level,
+        origin,
+        message: message.strip_prefix(' ').unwrap_or(message).to_string(),
+        raw: line.to_string(),
+    })
+}
+
+fn parse_origin(parts: &[&str]) -> Option<LogOrigin> {
+    match parts {
+        [file, line] if file.ends_with(".rs") => Some(LogOrigin {
+            file: file.to_string(),
+            line: line.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+// Firecracker writes levels in uppercase, which keeps them apart from
+// instance ids and thread names in the line header.
+fn is_log_level(part: &str) -> bool {
+    matches!(part, "ERROR" | "WARN" | "INFO" | "DEBUG" | "TRACE")
+}
+
+/// Creates a named pipe at `path` suitable for use as `Logger::log_path`.
+/// Fails if something already exists at `path` unless `overwrite` is set.
+#[cfg(unix)]
+pub fn create_log_fifo(path: impl AsRef<Path>, overwrite: bool) -> Result<(), FirecrackerError> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = path.as_ref();
+    let fs_error = |source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    if overwrite && path.symlink_metadata().is_ok() {
+        std::fs::remove_file(path).map_err(fs_error)?;
+    }
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| FirecrackerError::InvalidPath(path.display().to_string()))?;
+    // SAFETY: c_path is a valid NUL-terminated string for the duration of the call.
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(fs_error(std::io::Error::last_os_error()));
     }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+enum TailState {
+    Closed(PathBuf),
+    Open(PathBuf, Lines<BufReader<pipe::Receiver>>),
+    Done,
+}
+
+/// Streams lines from a log FIFO as they are written. The pipe is opened
+/// without blocking, so the stream can be started before Firecracker opens
+/// the other end.
+#[cfg(unix)]
+pub fn tail_log(path: impl AsRef<Path>) -> impl Stream<Item = Result<LogLine, FirecrackerError>> {
+    let state = TailState::Closed(path.as_ref().to_path_buf());
+
+    stream::unfold(state, |state| async move {
+        let (path, mut lines) = match state {
+            TailState::Closed(path) => match open_log_fifo(&path) {
+                Ok(receiver) => (path, BufReader::new(receiver).lines()),
+                Err(source) => {
+                    return Some((
+                        Err(FirecrackerError::FileSystem { path, source }),
+                        TailState::Done,
+                    ))
+                }
+            },
+            TailState::Open(path, lines) => (path, lines),
+            TailState::Done => return None,
+        };
+
+        match lines.next_line().await {
+            Ok(Some(line)) => Some((Ok(LogLine::parse(&line)), TailState::Open(path, lines))),
+            Ok(None) => None,
+            Err(source) => Some((
+                Err(FirecrackerError::FileSystem { path, source }),
+                TailState::Done,
+            )),
+        }
+    })
+}
+
+#[cfg(unix)]
+fn open_log_fifo(path: &Path) -> std::io::Result<pipe::Receiver> {
+    let mut options = pipe::OpenOptions::new();
+    // Keep a write end open ourselves so the stream does not see EOF while
+    // Firecracker has not opened the pipe yet.
+    #[cfg(target_os = "linux")]
+    options.read_write(true);
+    options.open_receiver(path)
 }
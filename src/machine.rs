@@ -1,4 +1,5 @@
 use crate::models::MachineConfig;
+use crate::paths;
 use crate::FirecrackerError;
 use async_trait::async_trait;
 
@@ -12,44 +13,15 @@ pub trait MachineConfigOperations {
 #[async_trait]
 impl MachineConfigOperations for crate::FirecrackerClient {
     async fn get_machine_config(&self) -> Result<MachineConfig, FirecrackerError> {
-        let url = self.url("machine-config")?;
-        let response = self.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(response.json().await?)
+        self.get_json(paths::MACHINE_CONFIG).await
     }
 
     async fn put_machine_config(&self, config: &MachineConfig) -> Result<(), FirecrackerError> {
-        let url = self.url("machine-config")?;
-        let response = self.client.put(url).json(config).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        self.put_linted(self.validated_put(paths::MACHINE_CONFIG, config))
+            .await
     }
 
     async fn patch_machine_config(&self, config: &MachineConfig) -> Result<(), FirecrackerError> {
-        let url = self.url("machine-config")?;
-        let response = self.client.patch(url).json(config).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        self.validated_patch(paths::MACHINE_CONFIG, config).await
     }
 }
@@ -0,0 +1,198 @@
+//! A registry of clients for a fleet of VMMs, by VM id, with operations
+//! fanned out over many of them at once.
+
+use crate::error::FirecrackerError;
+use crate::metrics::MetricsOperations;
+use crate::vm::VmOperations;
+use crate::FirecrackerClient;
+use futures_util::stream::{self, StreamExt};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+/// How many VMs [`FirecrackerManager::broadcast`] talks to at a time unless
+/// set with [`FirecrackerManager::with_concurrency`].
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// The VMs a broadcast goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// Every registered VM
+    All,
+    /// These VMs; ids that aren't registered are reported as unknown
+    Ids(Vec<String>),
+}
+
+impl<S: Into<String>> FromIterator<S> for Selector {
+    fn from_iter<I: IntoIterator<Item = S>>(ids: I) -> Self {
+        Selector::Ids(ids.into_iter().map(Into::into).collect())
+    }
+}
+
+/// What a broadcast did on each VM, each list sorted by id.
+#[derive(Debug)]
+pub struct BroadcastReport<T> {
+    pub succeeded: Vec<(String, T)>,
+    pub failed: Vec<(String, FirecrackerError)>,
+    /// Selected ids that aren't registered
+    pub unknown: Vec<String>,
+}
+
+impl<T> BroadcastReport<T> {
+    /// Whether the operation succeeded on every selected VM.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.unknown.is_empty()
+    }
+}
+
+/// Clients of many VMMs, registered by VM id.
+pub struct FirecrackerManager {
+    clients: RwLock<BTreeMap<String, Arc<FirecrackerClient>>>,
+    concurrency: usize,
+}
+
+impl Default for FirecrackerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FirecrackerManager {
+    pub fn new() -> Self {
+        Self {
+            clients: RwLock::new(BTreeMap::new()),
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Limits how many VMs a broadcast talks to at a time; at least one.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Registers `client` under `id` and returns the client it replaces.
+    pub fn register(
+        &self,
+        id: impl Into<String>,
+        client: FirecrackerClient,
+    ) -> Option<Arc<FirecrackerClient>> {
+        self.clients
+            .write()
+            .unwrap()
+            .insert(id.into(), Arc::new(client))
+    }
+
+    pub fn unregister(&self, id: &str) -> Option<Arc<FirecrackerClient>> {
+        self.clients.write().unwrap().remove(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<FirecrackerClient>> {
+        self.clients.read().unwrap().get(id).cloned()
+    }
+
+    /// The registered ids, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        self.clients.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Runs `op` on the client of every selected VM, at most the configured
+    /// number at a time, and collects the results. A failing VM doesn't stop
+    /// the others. VMs registered or unregistered while the broadcast runs
+    /// are not picked up.
+    ///
+    /// ```no_run
+    /// # use firecracker_http_client::manager::{FirecrackerManager, Selector};
+    /// # use firecracker_http_client::balloon::{BalloonOperations, BalloonUpdate};
+    /// # async fn run(manager: FirecrackerManager) {
+    /// let ids: Selector = ["vm-1", "vm-2"].into_iter().collect();
+    /// let report = manager
+    ///     .broadcast(ids, |client| async move {
+    ///         client.patch_balloon_config(&BalloonUpdate::new(256)).await
+    ///     })
+    ///     .await;
+    /// for (id, err) in &report.failed {
+    ///     eprintln!("{}: {}", id, err);
+    /// }
+    /// # }
+    /// ```
+    pub async fn broadcast<F, Fut, T>(&self, selector: Selector, op: F) -> BroadcastReport<T>
+    where
+        F: Fn(Arc<FirecrackerClient>) -> Fut,
+        Fut: Future<Output = Result<T, FirecrackerError>>,
+    {
+        let (targets, mut unknown) = self.select(selector);
+        let mut results: Vec<_> = stream::iter(targets)
+            .map(|(id, client)| {
+                let running = op(client);
+                async move { (id, running.await) }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        unknown.sort();
+
+        let mut report = BroadcastReport {
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+            unknown,
+        };
+        for (id, result) in results {
+            match result {
+                Ok(value) => report.succeeded.push((id, value)),
+                Err(err) => report.failed.push((id, err)),
+            }
+        }
+        report
+    }
+
+    /// Pauses the selected VMs.
+    pub async fn pause_all(&self, selector: Selector) -> BroadcastReport<()> {
+        self.broadcast(selector, |client| async move { client.pause_vm().await })
+            .await
+    }
+
+    /// Resumes the selected VMs.
+    pub async fn resume_all(&self, selector: Selector) -> BroadcastReport<()> {
+        self.broadcast(selector, |client| async move { client.resume_vm().await })
+            .await
+    }
+
+    /// Makes the selected VMs write their metrics now.
+    pub async fn flush_metrics_all(&self, selector: Selector) -> BroadcastReport<()> {
+        self.broadcast(
+            selector,
+            |client| async move { client.flush_metrics().await },
+        )
+        .await
+    }
+
+    // The registered clients among the selected ids, and the ids that
+    // aren't registered
+    fn select(&self, selector: Selector) -> (Vec<(String, Arc<FirecrackerClient>)>, Vec<String>) {
+        let clients = self.clients.read().unwrap();
+        match selector {
+            Selector::All => (
+                clients
+                    .iter()
+                    .map(|(id, client)| (id.clone(), client.clone()))
+                    .collect(),
+                Vec::new(),
+            ),
+            Selector::Ids(mut ids) => {
+                ids.sort();
+                ids.dedup();
+                let mut targets = Vec::new();
+                let mut unknown = Vec::new();
+                for id in ids {
+                    match clients.get(&id) {
+                        Some(client) => targets.push((id, client.clone())),
+                        None => unknown.push(id),
+                    }
+                }
+                (targets, unknown)
+            }
+        }
+    }
+}
@@ -1,35 +1,786 @@
-use crate::validation::validate_writable_path;
+use crate::action::{ActionType, InstanceActionInfo};
+use crate::paths;
+use crate::validation::validate_unix_path;
 use crate::FirecrackerError;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
 use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+#[cfg(unix)]
+use futures_util::stream::{self, Stream};
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct Metrics {
-    #[validate(custom = "validate_writable_path")]
+    /// Older config files call this `metrics_fifo`
+    #[serde(alias = "metrics_fifo")]
+    #[validate(custom = "validate_unix_path")]
     pub metrics_path: String,
 }
 
+impl Metrics {
+    pub fn new(metrics_path: impl Into<String>) -> Self {
+        Self {
+            metrics_path: metrics_path.into(),
+        }
+    }
+
+    /// Starts building a metrics config that writes to `metrics_path`.
+    pub fn builder(metrics_path: impl Into<String>) -> MetricsBuilder {
+        MetricsBuilder {
+            metrics: Metrics::new(metrics_path),
+        }
+    }
+}
+
+/// Builder for [`Metrics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsBuilder {
+    metrics: Metrics,
+}
+
+impl MetricsBuilder {
+    /// Validates and returns the metrics config. Whether the path is
+    /// writable is checked when it is sent.
+    pub fn build(self) -> Result<Metrics, FirecrackerError> {
+        self.metrics.validate()?;
+        Ok(self.metrics)
+    }
+}
+
 #[async_trait]
 pub trait MetricsOperations {
     async fn put_metrics(&self, metrics: &Metrics) -> Result<(), FirecrackerError>;
+    /// Asks Firecracker to write its metrics to `metrics_path` right away by
+    /// issuing the `FlushMetrics` action, instead of waiting for the periodic
+    /// flush.
+    async fn flush_metrics(&self) -> Result<(), FirecrackerError>;
+    /// Flushes the metrics and parses the newest document from `path`, which
+    /// must be a regular file (not a FIFO).
+    async fn flush_and_read_metrics(
+        &self,
+        path: &Path,
+    ) -> Result<FirecrackerMetrics, FirecrackerError>;
 }
 
 #[async_trait]
 impl MetricsOperations for crate::FirecrackerClient {
     async fn put_metrics(&self, metrics: &Metrics) -> Result<(), FirecrackerError> {
-        metrics.validate()?;
+        let metrics = metrics.clone();
+        let metrics = self
+            .on_local_fs(move |local| {
+                local.check_writable_path("metrics_path", &metrics.metrics_path)?;
+                local.resolve_metrics_path(&metrics)
+            })
+            .await?;
+        self.validated_put(paths::METRICS, &metrics).await
+    }
+
+    async fn flush_metrics(&self) -> Result<(), FirecrackerError> {
+        self.create_sync_action(&InstanceActionInfo::from(ActionType::FlushMetrics))
+            .await
+    }
+
+    async fn flush_and_read_metrics(
+        &self,
+        path: &Path,
+    ) -> Result<FirecrackerMetrics, FirecrackerError> {
+        self.flush_metrics().await?;
+        read_latest_metrics(path).await
+    }
+}
+
+/// Parses the newest document in the metrics file at `path` without asking
+/// Firecracker to flush first.
+pub async fn read_latest_metrics(path: &Path) -> Result<FirecrackerMetrics, FirecrackerError> {
+    let contents =
+        tokio::fs::read_to_string(path)
+            .await
+            .map_err(|source| FirecrackerError::FileSystem {
+                path: path.to_path_buf(),
+                source,
+            })?;
+    let last = contents
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .ok_or_else(|| {
+            FirecrackerError::Internal(format!("No metrics written to {}", path.display()))
+        })?;
+
+    FirecrackerMetrics::from_json_str(last)
+}
+
+impl crate::LocalPaths {
+    pub(crate) fn resolve_metrics_path(
+        &self,
+        metrics: &Metrics,
+    ) -> Result<Metrics, FirecrackerError> {
+        Ok(Metrics {
+            metrics_path: self.resolve_path("metrics_path", &metrics.metrics_path)?,
+        })
+    }
+}
+
+/// One metrics document as written by Firecracker on every flush. The stable
+/// groups are typed; anything else, including the per-device `block_<id>` and
+/// `net_<id>` groups, is kept in `other`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct FirecrackerMetrics {
+    pub utc_timestamp_ms: u64,
+    pub api_server: ApiServerMetrics,
+    pub balloon: BalloonMetrics,
+    pub block: BlockDeviceMetrics,
+    pub get_api_requests: BTreeMap<String, u64>,
+    pub put_api_requests: BTreeMap<String, u64>,
+    pub patch_api_requests: BTreeMap<String, u64>,
+    pub latencies_us: BTreeMap<String, Value>,
+    pub logger: LoggerMetrics,
+    pub mmds: MmdsMetrics,
+    pub net: NetDeviceMetrics,
+    pub seccomp: SeccompMetrics,
+    pub signals: SignalMetrics,
+    pub vcpu: VcpuMetrics,
+    pub vmm: VmmMetrics,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ApiServerMetrics {
+    pub process_startup_time_us: u64,
+    pub process_startup_time_cpu_us: u64,
+    pub sync_response_fails: u64,
+    pub sync_vmm_send_timeout_count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct BalloonMetrics {
+    pub activate_fails: u64,
+    pub inflate_count: u64,
+    pub stats_updates_count: u64,
+    pub stats_update_fails: u64,
+    pub deflate_count: u64,
+    pub event_fails: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct BlockDeviceMetrics {
+    pub activate_fails: u64,
+    pub cfg_fails: u64,
+    pub no_avail_buffer: u64,
+    pub event_fails: u64,
+    pub execute_fails: u64,
+    pub invalid_reqs_count: u64,
+    pub flush_count: u64,
+    pub queue_event_count: u64,
+    pub rate_limiter_event_count: u64,
+    pub update_count: u64,
+    pub update_fails: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_count: u64,
+    pub write_count: u64,
+    pub rate_limiter_throttled_events: u64,
+    pub io_engine_throttled_events: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct LoggerMetrics {
+    pub missed_metrics_count: u64,
+    pub metrics_fails: u64,
+    pub missed_log_count: u64,
+    pub log_fails: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct MmdsMetrics {
+    pub rx_accepted: u64,
+    pub rx_accepted_err: u64,
+    pub rx_accepted_unusual: u64,
+    pub rx_bad_eth: u64,
+    pub rx_count: u64,
+    pub tx_bytes: u64,
+    pub tx_count: u64,
+    pub tx_errors: u64,
+    pub tx_frames: u64,
+    pub connections_created: u64,
+    pub connections_destroyed: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct NetDeviceMetrics {
+    pub activate_fails: u64,
+    pub cfg_fails: u64,
+    pub mac_address_updates: u64,
+    pub no_rx_avail_buffer: u64,
+    pub no_tx_avail_buffer: u64,
+    pub event_fails: u64,
+    pub rx_queue_event_count: u64,
+    pub rx_event_rate_limiter_count: u64,
+    pub rx_partial_writes: u64,
+    pub rx_rate_limiter_throttled: u64,
+    pub rx_tap_event_count: u64,
+    pub rx_bytes_count: u64,
+    pub rx_packets_count: u64,
+    pub rx_fails: u64,
+    pub rx_count: u64,
+    pub tap_read_fails: u64,
+    pub tap_write_fails: u64,
+    pub tx_bytes_count: u64,
+    pub tx_malformed_frames: u64,
+    pub tx_fails: u64,
+    pub tx_count: u64,
+    pub tx_packets_count: u64,
+    pub tx_partial_reads: u64,
+    pub tx_queue_event_count: u64,
+    pub tx_rate_limiter_event_count: u64,
+    pub tx_rate_limiter_throttled: u64,
+    pub tx_spoofed_mac_count: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct SeccompMetrics {
+    pub num_faults: u64,
+}
 
-        let url = self.url("metrics")?;
-        let response = self.client.put(url).json(metrics).send().await?;
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct SignalMetrics {
+    pub sigbus: u64,
+    pub sigsegv: u64,
+    pub sigxfsz: u64,
+    pub sigxcpu: u64,
+    pub sigpipe: u64,
+    pub sighup: u64,
+    pub sigill: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct VcpuMetrics {
+    pub exit_io_in: u64,
+    pub exit_io_out: u64,
+    pub exit_mmio_read: u64,
+    pub exit_mmio_write: u64,
+    pub failures: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct VmmMetrics {
+    pub device_events: u64,
+    pub panic_count: u64,
+}
+
+impl FirecrackerMetrics {
+    pub fn from_json_str(s: &str) -> Result<Self, FirecrackerError> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, FirecrackerError> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Per-drive block metrics, keyed by drive id.
+    pub fn block_devices(&self) -> BTreeMap<String, BlockDeviceMetrics> {
+        self.device_groups("block_")
+    }
+
+    /// Per-interface network metrics, keyed by interface id.
+    pub fn net_devices(&self) -> BTreeMap<String, NetDeviceMetrics> {
+        self.device_groups("net_")
+    }
+
+    pub fn block_read_bytes(&self) -> u64 {
+        self.block.read_bytes
+    }
+
+    pub fn block_write_bytes(&self) -> u64 {
+        self.block.write_bytes
+    }
+
+    pub fn net_rx_bytes(&self) -> u64 {
+        self.net.rx_bytes_count
+    }
+
+    pub fn net_tx_bytes(&self) -> u64 {
+        self.net.tx_bytes_count
+    }
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
+    pub fn seccomp_faults(&self) -> u64 {
+        self.seccomp.num_faults
+    }
+
+    fn device_groups<T: serde::de::DeserializeOwned>(&self, prefix: &str) -> BTreeMap<String, T> {
+        self.other
+            .iter()
+            .filter_map(|(key, value)| {
+                let id = key.strip_prefix(prefix)?;
+                let metrics = serde_json::from_value(value.clone()).ok()?;
+                Some((id.to_string(), metrics))
+            })
+            .collect()
+    }
+}
+
+impl FirecrackerMetrics {
+    /// Every counter in the document keyed by its dotted path, such as
+    /// `block.read_bytes` or `put_api_requests.actions_count`. Firecracker
+    /// resets its counters on every flush, so these are the counts since the
+    /// previous document. Gauges and latencies, i.e. anything under a name
+    /// ending in `_us` or `_ms`, are left out, and so is the timestamp.
+    pub fn counters(&self) -> BTreeMap<String, u64> {
+        let mut counters = self.values();
+        counters.retain(|key, _| !is_gauge(key));
+        counters
+    }
+
+    /// Every numeric value in the document, counters and gauges alike,
+    /// keyed like [`counters`](Self::counters). The timestamp is left out.
+    pub fn values(&self) -> BTreeMap<String, u64> {
+        let mut values = BTreeMap::new();
+        if let Ok(Value::Object(groups)) = serde_json::to_value(self) {
+            for (group, value) in groups {
+                if group != "utc_timestamp_ms" {
+                    collect_values(&group, &value, &mut values);
+                }
+            }
+        }
+        values
+    }
+
+    /// What Firecracker counted between `previous` and this document. Every
+    /// flush already holds the counts since the one before, so the values are
+    /// this document's [`counters`](Self::counters); `previous` only supplies
+    /// the elapsed time.
+    pub fn delta(&self, previous: &Self) -> MetricsDelta {
+        MetricsDelta {
+            elapsed: Duration::from_millis(
+                self.utc_timestamp_ms
+                    .saturating_sub(previous.utc_timestamp_ms),
+            ),
+            values: self.counters(),
+        }
+    }
+}
+
+// Times and timestamps are written as they are, not reset on every flush
+fn is_gauge(path: &str) -> bool {
+    path.split('.')
+        .any(|part| part.ends_with("_us") || part.ends_with("_ms"))
+}
+
+fn collect_values(prefix: &str, value: &Value, out: &mut BTreeMap<String, u64>) {
+    match value {
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64() {
+                out.insert(prefix.to_string(), n);
+            }
+        }
+        Value::Object(fields) => {
+            for (field, value) in fields {
+                collect_values(&format!("{}.{}", prefix, field), value, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// What Firecracker counted over a span of flushes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsDelta {
+    /// Time covered, from the documents' `utc_timestamp_ms`
+    pub elapsed: Duration,
+    /// Count of each counter over that time, keyed like
+    /// [`FirecrackerMetrics::counters`]
+    pub values: BTreeMap<String, u64>,
+}
+
+impl MetricsDelta {
+    pub fn get(&self, key: &str) -> u64 {
+        self.values.get(key).copied().unwrap_or(0)
+    }
+
+    /// Per-second rate of every counter, or `None` if no time elapsed.
+    pub fn rates(&self) -> Option<BTreeMap<String, f64>> {
+        let secs = self.elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        Some(
+            self.values
+                .iter()
+                .map(|(key, value)| (key.clone(), *value as f64 / secs))
+                .collect(),
+        )
+    }
+}
+
+/// Keeps the snapshots received within a rolling time window and derives
+/// per-second rates across it.
+#[derive(Debug, Clone)]
+pub struct MetricsWindow {
+    window: Duration,
+    samples: VecDeque<FirecrackerMetrics>,
+}
+
+impl MetricsWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Adds a snapshot and drops the ones that fell out of the window.
+    /// Snapshots older than the newest one already held are ignored.
+    pub fn push(&mut self, metrics: FirecrackerMetrics) {
+        if let Some(newest) = self.samples.back() {
+            if metrics.utc_timestamp_ms < newest.utc_timestamp_ms {
+                return;
+            }
+        }
+        self.samples.push_back(metrics);
+
+        let newest = self.samples.back().map_or(0, |m| m.utc_timestamp_ms);
+        let window_ms = self.window.as_millis() as u64;
+        while self.samples.len() > 2
+            && newest.saturating_sub(self.samples[1].utc_timestamp_ms) >= window_ms
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Counts across the window: the sum of every snapshot's counters after
+    /// the oldest, which only marks where the window starts.
+    pub fn delta(&self) -> Option<MetricsDelta> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+
+        let mut values = BTreeMap::new();
+        for sample in self.samples.iter().skip(1) {
+            for (key, value) in sample.counters() {
+                *values.entry(key).or_default() += value;
+            }
+        }
+        Some(MetricsDelta {
+            elapsed: Duration::from_millis(
+                last.utc_timestamp_ms.saturating_sub(first.utc_timestamp_ms),
+            ),
+            values,
+        })
+    }
+
+    /// Per-second rates across the window, or `None` until two snapshots
+    /// with different timestamps have been pushed.
+    pub fn rates(&self) -> Option<BTreeMap<String, f64>> {
+        self.delta()?.rates()
+    }
+}
+
+/// Longest metrics document accepted by [`stream_metrics`]. Anything longer is
+/// dropped up to the next newline so a runaway writer can't grow the buffer
+/// without bound.
+#[cfg(unix)]
+pub const MAX_METRICS_LINE_BYTES: usize = 1024 * 1024;
+
+#[cfg(unix)]
+const METRICS_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// An item from [`follow_metrics`].
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricsEvent {
+    /// A metrics document was read
+    Metrics(Box<FirecrackerMetrics>),
+    /// The followed file was truncated or replaced and has been reopened
+    Rotated,
+}
+
+// Identity and read position of a followed regular file
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+struct FollowedFile {
+    dev: u64,
+    ino: u64,
+    offset: u64,
+}
+
+#[cfg(unix)]
+struct MetricsReader {
+    path: PathBuf,
+    reader: Option<Box<dyn AsyncRead + Send + Sync + Unpin>>,
+    followed: Option<FollowedFile>,
+    buf: Vec<u8>,
+    discarding: bool,
+    pending: VecDeque<Result<MetricsEvent, FirecrackerError>>,
+    done: bool,
+}
+
+#[cfg(unix)]
+impl MetricsReader {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            reader: None,
+            followed: None,
+            buf: Vec::new(),
+            discarding: false,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    async fn open(&mut self) -> std::io::Result<()> {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+        let metadata = tokio::fs::metadata(&self.path).await?;
+        if metadata.file_type().is_fifo() {
+            let mut options = tokio::net::unix::pipe::OpenOptions::new();
+            #[cfg(target_os = "linux")]
+            options.read_write(true);
+            self.reader = Some(Box::new(options.open_receiver(&self.path)?));
+        } else {
+            let file = tokio::fs::File::open(&self.path).await?;
+            let metadata = file.metadata().await?;
+            self.reader = Some(Box::new(file));
+            self.followed = Some(FollowedFile {
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+                offset: 0,
             });
         }
+        Ok(())
+    }
+
+    // Whether the path now names a different file, or the same file shrank
+    // below what was already read
+    async fn rotated(&self, followed: FollowedFile) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => {
+                metadata.dev() != followed.dev
+                    || metadata.ino() != followed.ino
+                    || metadata.len() < followed.offset
+            }
+            // Renamed away and not recreated yet; keep the old file until
+            // the new one shows up
+            Err(_) => false,
+        }
+    }
+
+    async fn next_item(&mut self) -> Option<Result<MetricsEvent, FirecrackerError>> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fill().await {
+                self.done = true;
+                return Some(Err(self.fs_error(e)));
+            }
+        }
+    }
+
+    // Reads one chunk and queues every complete document it finishes
+    async fn fill(&mut self) -> std::io::Result<()> {
+        if self.reader.is_none() {
+            self.open().await?;
+        }
+        let reader = self.reader.as_mut().expect("reader was just opened");
+
+        let mut chunk = [0u8; 8192];
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            match self.followed {
+                Some(followed) if self.rotated(followed).await => {
+                    self.open().await?;
+                    self.buf.clear();
+                    self.discarding = false;
+                    self.pending.push_back(Ok(MetricsEvent::Rotated));
+                }
+                Some(_) => tokio::time::sleep(METRICS_POLL_INTERVAL).await,
+                None => self.done = true,
+            }
+            return Ok(());
+        }
+        if let Some(followed) = self.followed.as_mut() {
+            followed.offset += n as u64;
+        }
+
+        for &byte in &chunk[..n] {
+            if byte != b'\n' {
+                if !self.discarding {
+                    self.buf.push(byte);
+                }
+                continue;
+            }
+            if std::mem::take(&mut self.discarding) {
+                continue;
+            }
+            let line = std::mem::take(&mut self.buf);
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            self.pending.push_back(
+                serde_json::from_slice(&line)
+                    .map(|metrics| MetricsEvent::Metrics(Box::new(metrics)))
+                    .map_err(FirecrackerError::Serialization),
+            );
+        }
+
+        if self.buf.len() > MAX_METRICS_LINE_BYTES {
+            self.buf.clear();
+            self.discarding = true;
+            self.pending
+                .push_back(Err(FirecrackerError::Internal(format!(
+                    "Metrics document in {} exceeds {} bytes",
+                    self.path.display(),
+                    MAX_METRICS_LINE_BYTES
+                ))));
+        }
 
         Ok(())
     }
+
+    fn fs_error(&self, source: std::io::Error) -> FirecrackerError {
+        FirecrackerError::FileSystem {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+/// Streams metrics documents from `path` as Firecracker flushes them. FIFOs
+/// are read as they are written; regular files are read from the start and
+/// then followed like `tail -f`, reopening the path when the file is rotated.
+/// A document that fails to parse yields an error item and the stream carries
+/// on with the next line; only I/O errors end the stream.
+#[cfg(unix)]
+pub fn stream_metrics(
+    path: impl AsRef<Path>,
+) -> impl Stream<Item = Result<FirecrackerMetrics, FirecrackerError>> {
+    let reader = MetricsReader::new(path.as_ref().to_path_buf());
+
+    stream::unfold(reader, |mut reader| async move {
+        loop {
+            match reader.next_item().await? {
+                Ok(MetricsEvent::Metrics(metrics)) => return Some((Ok(*metrics), reader)),
+                Ok(MetricsEvent::Rotated) => continue,
+                Err(e) => return Some((Err(e), reader)),
+            }
+        }
+    })
+}
+
+/// Like [`stream_metrics`], but also reports when the followed file was
+/// truncated or replaced.
+#[cfg(unix)]
+pub fn follow_metrics(
+    path: impl AsRef<Path>,
+) -> impl Stream<Item = Result<MetricsEvent, FirecrackerError>> {
+    let reader = MetricsReader::new(path.as_ref().to_path_buf());
+
+    stream::unfold(reader, |mut reader| async move {
+        let item = reader.next_item().await?;
+        Some((item, reader))
+    })
+}
+
+/// Rotates the metrics file at `path`: `path.1` becomes `path.2` and so on up
+/// to `keep` old files, `path` becomes `path.1`, and a new empty file with the
+/// original permissions takes its place. With `keep == 0` the old contents are
+/// discarded.
+#[cfg(unix)]
+pub fn rotate(path: impl AsRef<Path>, keep: usize) -> Result<(), FirecrackerError> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let path = path.as_ref();
+    let fs_error = |path: &Path, source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    };
+    let numbered = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    };
+
+    let mode = std::fs::metadata(path)
+        .map_err(|e| fs_error(path, e))?
+        .permissions()
+        .mode();
+
+    if keep == 0 {
+        std::fs::remove_file(path).map_err(|e| fs_error(path, e))?;
+    } else {
+        let oldest = numbered(keep);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest).map_err(|e| fs_error(&oldest, e))?;
+        }
+        for n in (1..keep).rev() {
+            let from = numbered(n);
+            if from.exists() {
+                std::fs::rename(&from, numbered(n + 1)).map_err(|e| fs_error(&from, e))?;
+            }
+        }
+        std::fs::rename(path, numbered(1)).map_err(|e| fs_error(path, e))?;
+    }
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode & 0o7777)
+        .open(path)
+        .map_err(|e| fs_error(path, e))?;
+    // The umask may have masked bits off at creation
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))
+        .map_err(|e| fs_error(path, e))?;
+
+    Ok(())
 }
@@ -0,0 +1,636 @@
+use crate::action::{ActionOperations, ActionType, InstanceActionInfo};
+use crate::config::ConfigOperations;
+use crate::instance::InstanceOperations;
+use crate::models::FullVmConfiguration;
+use crate::orchestration::{ResourceKind, TeardownPlan};
+use crate::snapshot::{SafeSnapshotOptions, SnapshotCreateParams, SnapshotOperations};
+use crate::validation::ValidationReport;
+use crate::vm::VmOperations;
+use crate::{FirecrackerClient, FirecrackerError};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// How many events a subscriber can fall behind before it starts missing
+/// them, for channels [`MicroVm::configure`] creates.
+pub const EVENT_CAPACITY: usize = 64;
+
+/// How long dropping a [`MicroVm`] waits for its [`DropPolicy`] to be
+/// carried out.
+pub const DROP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where a [`MicroVm`] is in its lifecycle, as far as this client has
+/// driven it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmLifecycle {
+    /// Configured and not started yet
+    Configured,
+    Running,
+    Paused,
+    /// Asked to shut down; nothing else can be done with it
+    ShutDown,
+}
+
+impl VmLifecycle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VmLifecycle::Configured => "Configured",
+            VmLifecycle::Running => "Running",
+            VmLifecycle::Paused => "Paused",
+            VmLifecycle::ShutDown => "ShutDown",
+        }
+    }
+}
+
+impl fmt::Display for VmLifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What dropping a [`MicroVm`] without [`MicroVm::close`] does to a VM that
+/// was started and not shut down, e.g. when a test panics halfway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Send Ctrl+Alt+Del, resuming a paused VM first
+    SendCtrlAltDel,
+    /// Pause a running VM
+    Pause,
+    /// Leave the VM as it is
+    #[default]
+    Nothing,
+}
+
+/// What happened to a [`MicroVm`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmEventKind {
+    Configured,
+    Started,
+    Paused,
+    Resumed,
+    SnapshotCreated {
+        snapshot_path: String,
+    },
+    /// Ctrl+Alt+Del was sent; `Halted` follows once the VMM is gone
+    ShutdownRequested,
+    /// The API stopped answering after a shutdown was requested
+    Halted,
+    /// A state poll failed. Sent once per outage; polling carries on.
+    ApiUnreachable {
+        error: String,
+    },
+}
+
+/// What made a [`MicroVm`] send an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTrigger {
+    /// A call on the `MicroVm`, by method name, e.g. `"pause"`
+    Call(&'static str),
+    /// The state watcher noticed a change nobody made through the `MicroVm`
+    Watcher,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmEvent {
+    pub kind: VmEventKind,
+    pub trigger: EventTrigger,
+    pub at: SystemTime,
+}
+
+/// A microVM driven through its client: configured from a spec, then
+/// started, paused, resumed, snapshotted and shut down. The lifecycle is
+/// tracked locally, so a call that doesn't fit the current state fails with
+/// `InvalidState` without sending anything. A call the VMM rejects leaves
+/// the state as it was.
+///
+/// Every change is also sent as a [`VmEvent`] to the receivers from
+/// [`MicroVm::subscribe`], together with the changes the watcher started by
+/// [`MicroVm::watch_state`] notices. Events are sent in the order the state
+/// changed and each change is sent once: a call and the watcher never both
+/// report the same one. A receiver only gets events sent after it
+/// subscribed; pass a sender of your own to
+/// [`MicroVm::configure_with_events`] to see `Configured` too. A receiver
+/// that falls more than the channel's capacity behind gets
+/// `RecvError::Lagged` and continues with the oldest event still kept.
+///
+/// ```no_run
+/// # use firecracker_http_client::{FirecrackerClient, FullVmConfiguration, MicroVm};
+/// # async fn run(spec: FullVmConfiguration) -> Result<(), firecracker_http_client::error::FirecrackerError> {
+/// let client = FirecrackerClient::new("http://localhost:8080").await?;
+/// let mut vm = MicroVm::configure(client, spec).await?;
+/// vm.start().await?;
+/// vm.pause().await?;
+/// vm.resume().await?;
+/// vm.shutdown().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MicroVm {
+    client: FirecrackerClient,
+    spec: FullVmConfiguration,
+    report: ValidationReport,
+    shared: Arc<Shared>,
+    // Files of snapshots that failed, possibly half written
+    failed_snapshots: Vec<PathBuf>,
+    watcher: Option<Watcher>,
+    guard: Option<DropGuard>,
+}
+
+impl MicroVm {
+    /// Applies every section of `spec` with
+    /// [`ConfigOperations::apply_config`].
+    pub async fn configure(
+        client: FirecrackerClient,
+        spec: FullVmConfiguration,
+    ) -> Result<Self, FirecrackerError> {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self::configure_with_events(client, spec, events).await
+    }
+
+    /// Like [`MicroVm::configure`], sending events to `events`, whose
+    /// receivers also get the `Configured` event.
+    pub async fn configure_with_events(
+        client: FirecrackerClient,
+        spec: FullVmConfiguration,
+        events: broadcast::Sender<VmEvent>,
+    ) -> Result<Self, FirecrackerError> {
+        let report = client.apply_config(&spec).await?;
+        let shared = Arc::new(Shared {
+            tracked: Mutex::new(Tracked {
+                state: VmLifecycle::Configured,
+                generation: 0,
+                busy: false,
+            }),
+            events,
+        });
+        shared.emit(VmEventKind::Configured, EventTrigger::Call("configure"));
+        Ok(Self {
+            client,
+            spec,
+            report,
+            shared,
+            failed_snapshots: Vec::new(),
+            watcher: None,
+            guard: None,
+        })
+    }
+
+    /// Applies `spec` again, replacing the current one. Only possible before
+    /// the VM is started.
+    pub async fn reconfigure(&mut self, spec: FullVmConfiguration) -> Result<(), FirecrackerError> {
+        let op = Operation::begin(&self.shared, &[VmLifecycle::Configured])?;
+        self.report = self.client.apply_config(&spec).await?;
+        self.spec = spec;
+        op.finish(
+            VmLifecycle::Configured,
+            VmEventKind::Configured,
+            "reconfigure",
+        );
+        Ok(())
+    }
+
+    /// Boots the VM. If the VMM refuses, the VM stays configured and can be
+    /// reconfigured and started again.
+    pub async fn start(&mut self) -> Result<(), FirecrackerError> {
+        let op = Operation::begin(&self.shared, &[VmLifecycle::Configured])?;
+        self.action(ActionType::InstanceStart).await?;
+        op.finish(VmLifecycle::Running, VmEventKind::Started, "start");
+        Ok(())
+    }
+
+    pub async fn pause(&mut self) -> Result<(), FirecrackerError> {
+        let op = Operation::begin(&self.shared, &[VmLifecycle::Running])?;
+        self.client.pause_vm().await?;
+        op.finish(VmLifecycle::Paused, VmEventKind::Paused, "pause");
+        Ok(())
+    }
+
+    pub async fn resume(&mut self) -> Result<(), FirecrackerError> {
+        let op = Operation::begin(&self.shared, &[VmLifecycle::Paused])?;
+        self.client.resume_vm().await?;
+        op.finish(VmLifecycle::Running, VmEventKind::Resumed, "resume");
+        Ok(())
+    }
+
+    /// Creates a snapshot. A running VM is paused for it and resumed
+    /// afterwards with [`SnapshotOperations::create_snapshot_safe`]; a paused
+    /// one stays paused.
+    pub async fn snapshot(
+        &mut self,
+        params: &SnapshotCreateParams,
+    ) -> Result<(), FirecrackerError> {
+        let shared = self.shared.clone();
+        let op = Operation::begin(&shared, &[VmLifecycle::Running, VmLifecycle::Paused])?;
+        let created = VmEventKind::SnapshotCreated {
+            snapshot_path: params.snapshot_path.clone(),
+        };
+        if op.state == VmLifecycle::Paused {
+            let result = self.client.create_snapshot(params).await;
+            match &result {
+                Ok(()) => op.emit(created, "snapshot"),
+                Err(_) => self.record_failed_snapshot(params),
+            }
+            return result;
+        }
+
+        let outcome = self
+            .client
+            .create_snapshot_safe(params, SafeSnapshotOptions::default())
+            .await?;
+        if outcome.snapshot.is_ok() {
+            op.emit(created, "snapshot");
+        } else {
+            self.record_failed_snapshot(params);
+        }
+        if matches!(outcome.resume, Some(Err(_))) {
+            op.finish(VmLifecycle::Paused, VmEventKind::Paused, "snapshot");
+        }
+        outcome.into_result()
+    }
+
+    /// Asks the guest to shut down with Ctrl+Alt+Del. Firecracker exits once
+    /// the guest has, so the VM can't be used afterwards. Only x86_64 guests
+    /// handle the key press.
+    pub async fn shutdown(&mut self) -> Result<(), FirecrackerError> {
+        let op = Operation::begin(&self.shared, &[VmLifecycle::Running])?;
+        self.action(ActionType::SendCtrlAltDel).await?;
+        op.finish(
+            VmLifecycle::ShutDown,
+            VmEventKind::ShutdownRequested,
+            "shutdown",
+        );
+        Ok(())
+    }
+
+    pub fn state(&self) -> VmLifecycle {
+        self.shared.tracked.lock().unwrap().state
+    }
+
+    /// A receiver for the events sent from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<VmEvent> {
+        self.shared.events.subscribe()
+    }
+
+    /// Polls the VMM's state every `interval` in the background and takes
+    /// over changes made some other way, e.g. a pause through another
+    /// client, sending them as events triggered by the watcher. Failed polls
+    /// send `ApiUnreachable`; once the API is gone after a shutdown request
+    /// the watcher sends `Halted` and stops. Replaces a running watcher; the
+    /// watcher stops when the `MicroVm` is dropped. Must be called from
+    /// within a Tokio runtime.
+    pub fn watch_state(&mut self, interval: Duration) {
+        let task = tokio::spawn(watch(self.client.reader(), self.shared.clone(), interval));
+        self.watcher = Some(Watcher(task));
+    }
+
+    pub fn stop_watching(&mut self) {
+        self.watcher = None;
+    }
+
+    /// Sets what dropping the `MicroVm` does to a VM that is still up.
+    ///
+    /// `Drop` can't await, so the request is sent from a thread of its own
+    /// with a runtime of its own, and the drop blocks for up to
+    /// [`DROP_TIMEOUT`] while it runs. Errors are ignored and the drop never
+    /// panics. On a current-thread runtime that also serves the VMM's API,
+    /// e.g. a test's mock server, the request can't be answered until the
+    /// drop returns and simply times out. Nothing runs if the process
+    /// exits without dropping, e.g. on `std::process::exit` or an abort.
+    ///
+    /// The request goes out through a client of its own with the same
+    /// timeouts, and this fails if that client can't be built.
+    pub fn with_drop_policy(mut self, policy: DropPolicy) -> Result<Self, FirecrackerError> {
+        self.guard = Some(DropGuard {
+            policy,
+            client: Some(self.client.detached()?),
+            shared: self.shared.clone(),
+        });
+        Ok(self)
+    }
+
+    /// Carries out the drop policy now, reporting errors, and disarms it.
+    /// Use it on the clean path instead of relying on the drop.
+    pub async fn close(mut self) -> Result<(), FirecrackerError> {
+        self.watcher = None;
+        let Some(guard) = self.guard.take() else {
+            return Ok(());
+        };
+        let policy = guard.disarm();
+        let op = Operation::begin(&self.shared, &ALL_STATES)?;
+        if let Some((state, kind)) = carry_out(&self.client, policy, op.state).await? {
+            op.finish(state, kind, "close");
+        }
+        Ok(())
+    }
+
+    /// The spec the VM was last configured with.
+    pub fn spec(&self) -> &FullVmConfiguration {
+        &self.spec
+    }
+
+    /// The lint warnings from the last configuration.
+    pub fn report(&self) -> &ValidationReport {
+        &self.report
+    }
+
+    /// The files the VM's devices created on the host (the vsock socket and
+    /// the logger and metrics FIFOs) and those of failed snapshots, for
+    /// [`teardown`](crate::orchestration::teardown) once the VMM is gone.
+    /// Log and metrics paths are only included while they are FIFOs, so
+    /// plain log files are kept. Only paths under `root` will be removed.
+    pub fn teardown_plan(&self, root: impl Into<PathBuf>) -> TeardownPlan {
+        let mut plan = TeardownPlan::new(root);
+        if let Some(vsock) = &self.spec.vsock {
+            plan.add(ResourceKind::VsockSocket, &vsock.uds_path);
+        }
+        let log_paths = [
+            self.spec.logger.as_ref().map(|logger| &logger.log_path),
+            self.spec
+                .metrics
+                .as_ref()
+                .map(|metrics| &metrics.metrics_path),
+        ];
+        for path in log_paths.into_iter().flatten().filter(|path| is_fifo(path)) {
+            plan.add(ResourceKind::LogFifo, path);
+        }
+        for path in &self.failed_snapshots {
+            plan.add(ResourceKind::SnapshotFile, path);
+        }
+        plan
+    }
+
+    pub fn client(&self) -> &FirecrackerClient {
+        &self.client
+    }
+
+    /// Hands over the client, disarming the drop policy.
+    pub fn into_client(mut self) -> FirecrackerClient {
+        if let Some(guard) = self.guard.take() {
+            guard.disarm();
+        }
+        self.client
+    }
+
+    async fn action(&self, action_type: ActionType) -> Result<(), FirecrackerError> {
+        ActionOperations::create_sync_action(&self.client, &InstanceActionInfo::from(action_type))
+            .await
+    }
+
+    fn record_failed_snapshot(&mut self, params: &SnapshotCreateParams) {
+        self.failed_snapshots
+            .push(PathBuf::from(&params.snapshot_path));
+        self.failed_snapshots
+            .push(PathBuf::from(&params.mem_file_path));
+    }
+}
+
+// What the `MicroVm` and its watcher share
+struct Shared {
+    tracked: Mutex<Tracked>,
+    events: broadcast::Sender<VmEvent>,
+}
+
+struct Tracked {
+    state: VmLifecycle,
+    // Bumped whenever a call starts, so the watcher can tell that what it
+    // observed may predate the call
+    generation: u64,
+    // A call is waiting for the VMM, which may already have changed state
+    busy: bool,
+}
+
+impl Shared {
+    // Sending while holding the state lock keeps events in state order;
+    // having no receivers is fine
+    fn emit(&self, kind: VmEventKind, trigger: EventTrigger) {
+        let _ = self.events.send(VmEvent {
+            kind,
+            trigger,
+            at: SystemTime::now(),
+        });
+    }
+}
+
+// A call in progress. Dropping it without `finish`, because the call failed
+// or was cancelled, keeps the state as it was.
+struct Operation<'a> {
+    shared: &'a Shared,
+    state: VmLifecycle,
+}
+
+impl<'a> Operation<'a> {
+    fn begin(shared: &'a Shared, allowed: &[VmLifecycle]) -> Result<Self, FirecrackerError> {
+        let mut tracked = shared.tracked.lock().unwrap();
+        if !allowed.contains(&tracked.state) {
+            return Err(FirecrackerError::InvalidState {
+                current_state: tracked.state.to_string(),
+                expected_states: allowed.iter().map(ToString::to_string).collect(),
+            });
+        }
+        tracked.generation += 1;
+        tracked.busy = true;
+        Ok(Self {
+            shared,
+            state: tracked.state,
+        })
+    }
+
+    fn finish(self, state: VmLifecycle, kind: VmEventKind, operation: &'static str) {
+        let mut tracked = self.shared.tracked.lock().unwrap();
+        tracked.state = state;
+        self.shared.emit(kind, EventTrigger::Call(operation));
+    }
+
+    // An event that doesn't change the state
+    fn emit(&self, kind: VmEventKind, operation: &'static str) {
+        let _tracked = self.shared.tracked.lock().unwrap();
+        self.shared.emit(kind, EventTrigger::Call(operation));
+    }
+}
+
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        self.shared.tracked.lock().unwrap().busy = false;
+    }
+}
+
+const ALL_STATES: [VmLifecycle; 4] = [
+    VmLifecycle::Configured,
+    VmLifecycle::Running,
+    VmLifecycle::Paused,
+    VmLifecycle::ShutDown,
+];
+
+// Sends what `policy` calls for in `state`, and returns the state it leads
+// to with the event to send
+async fn carry_out(
+    client: &FirecrackerClient,
+    policy: DropPolicy,
+    state: VmLifecycle,
+) -> Result<Option<(VmLifecycle, VmEventKind)>, FirecrackerError> {
+    match (policy, state) {
+        (DropPolicy::SendCtrlAltDel, VmLifecycle::Running | VmLifecycle::Paused) => {
+            // A paused guest wouldn't see the key press
+            if state == VmLifecycle::Paused {
+                client.resume_vm().await?;
+            }
+            let action = InstanceActionInfo::from(ActionType::SendCtrlAltDel);
+            client.send_action(&action).await?;
+            Ok(Some((
+                VmLifecycle::ShutDown,
+                VmEventKind::ShutdownRequested,
+            )))
+        }
+        (DropPolicy::Pause, VmLifecycle::Running) => {
+            client.pause_vm().await?;
+            Ok(Some((VmLifecycle::Paused, VmEventKind::Paused)))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Carries out the drop policy when dropped
+struct DropGuard {
+    policy: DropPolicy,
+    // Detached, since the drop runs it on a runtime of its own
+    client: Option<FirecrackerClient>,
+    shared: Arc<Shared>,
+}
+
+impl DropGuard {
+    fn disarm(mut self) -> DropPolicy {
+        std::mem::take(&mut self.policy)
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        let policy = std::mem::take(&mut self.policy);
+        let Ok(tracked) = self.shared.tracked.lock() else {
+            return;
+        };
+        let state = tracked.state;
+        drop(tracked);
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        if policy == DropPolicy::Nothing || state == VmLifecycle::ShutDown {
+            return;
+        }
+
+        let shared = self.shared.clone();
+        let (done, finished) = std::sync::mpsc::channel();
+        let spawned = std::thread::Builder::new()
+            .name("microvm-drop".to_string())
+            .spawn(move || {
+                let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                else {
+                    return;
+                };
+                let outcome = runtime.block_on(async {
+                    tokio::time::timeout(DROP_TIMEOUT, carry_out(&client, policy, state)).await
+                });
+                if let (Ok(Ok(Some((state, kind)))), Ok(mut tracked)) =
+                    (outcome, shared.tracked.lock())
+                {
+                    tracked.state = state;
+                    shared.emit(kind, EventTrigger::Call("drop"));
+                }
+                let _ = done.send(());
+            });
+        if spawned.is_ok() {
+            // A little longer than the request may take, for the runtime
+            let _ = finished.recv_timeout(DROP_TIMEOUT + Duration::from_millis(500));
+        }
+    }
+}
+
+// Aborts the watcher task when dropped
+struct Watcher(JoinHandle<()>);
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn watch(client: FirecrackerClient, shared: Arc<Shared>, interval: Duration) {
+    let mut reachable = true;
+    loop {
+        let generation = shared.tracked.lock().unwrap().generation;
+        let polled = client.describe_instance().await;
+
+        if !observe(&shared, generation, polled, &mut reachable) {
+            return;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+// Takes over what a poll showed; false once the VMM is gone for good
+fn observe(
+    shared: &Shared,
+    generation: u64,
+    polled: Result<crate::models::InstanceInfo, FirecrackerError>,
+    reachable: &mut bool,
+) -> bool {
+    let mut tracked = shared.tracked.lock().unwrap();
+    match polled {
+        // A call started since the poll was sent or is still running;
+        // it reports its own change
+        Ok(_) if tracked.busy || tracked.generation != generation => {}
+        Ok(info) => {
+            *reachable = true;
+            let changes: &[(VmLifecycle, VmEventKind)] = match (tracked.state, info.state.as_str())
+            {
+                (VmLifecycle::Configured, "Running") => {
+                    &[(VmLifecycle::Running, VmEventKind::Started)]
+                }
+                (VmLifecycle::Configured, "Paused") => &[
+                    (VmLifecycle::Running, VmEventKind::Started),
+                    (VmLifecycle::Paused, VmEventKind::Paused),
+                ],
+                (VmLifecycle::Running, "Paused") => &[(VmLifecycle::Paused, VmEventKind::Paused)],
+                (VmLifecycle::Paused, "Running") => &[(VmLifecycle::Running, VmEventKind::Resumed)],
+                _ => &[],
+            };
+            for (state, kind) in changes {
+                tracked.state = *state;
+                shared.emit(kind.clone(), EventTrigger::Watcher);
+            }
+        }
+        Err(_) if tracked.state == VmLifecycle::ShutDown => {
+            shared.emit(VmEventKind::Halted, EventTrigger::Watcher);
+            return false;
+        }
+        Err(err) => {
+            if *reachable {
+                shared.emit(
+                    VmEventKind::ApiUnreachable {
+                        error: err.to_string(),
+                    },
+                    EventTrigger::Watcher,
+                );
+            }
+            *reachable = false;
+        }
+    }
+    true
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &str) -> bool {
+    false
+}
@@ -1,55 +1,243 @@
+use crate::models::MmdsConfig;
+use crate::paths;
+use crate::validation::{
+    field_validation_errors, validate_mmds_pointer, validate_mmds_root, validate_mmds_tag,
+    ValidationTarget,
+};
 use crate::FirecrackerError;
 use async_trait::async_trait;
-use serde_json::Value;
+use reqwest::Method;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+use validator::ValidationError;
+
+/// Firecracker's default MMDS data store size limit in bytes, which
+/// `--mmds-size-limit` changes.
+pub const MMDS_DEFAULT_SIZE_LIMIT: usize = 51200;
+
+/// The MMDS subtree reserved for VM tags, readable by host tooling and the
+/// guest alike.
+pub const TAGS_POINTER: &str = "/fc-client/tags";
+
+/// The longest tag key, in bytes.
+pub const MAX_TAG_KEY_LEN: usize = 64;
+
+/// The longest tag value, in bytes.
+pub const MAX_TAG_VALUE_LEN: usize = 256;
 
 #[async_trait]
 pub trait MmdsOperations {
     async fn put_mmds(&self, data: Value) -> Result<(), FirecrackerError>;
     async fn patch_mmds(&self, data: Value) -> Result<(), FirecrackerError>;
     async fn get_mmds(&self) -> Result<Value, FirecrackerError>;
+    /// Sets which network interfaces the guest can reach MMDS through, and
+    /// the MMDS version and address.
+    async fn put_mmds_config(&self, config: &MmdsConfig) -> Result<(), FirecrackerError>;
+    /// Removes the keys addressed by the given JSON pointers by PATCHing a
+    /// merge patch with `null` at each location. Deleting a key that does not
+    /// exist is a no-op.
+    async fn delete_mmds_keys(&self, pointers: &[&str]) -> Result<(), FirecrackerError>;
+    /// Polls the MMDS store until the value at `pointer` exists and satisfies
+    /// `predicate`, returning that value. `pointer` is validated like the
+    /// other MMDS pointers before the first poll, and `interval` must not be
+    /// zero, which would poll the VMM back to back. Fails with
+    /// [`FirecrackerError::MmdsWaitTimeout`] carrying the last observed value
+    /// if `timeout` elapses first.
+    async fn wait_for_mmds_value<F>(
+        &self,
+        pointer: &str,
+        predicate: F,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Value, FirecrackerError>
+    where
+        F: Fn(&Value) -> bool + Send + Sync;
+    /// Polls the MMDS store until a value exists at `pointer`.
+    async fn wait_for_mmds_key(
+        &self,
+        pointer: &str,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Value, FirecrackerError>;
+    /// Adds or replaces the given tags under [`TAGS_POINTER`], leaving other
+    /// tags and the rest of the MMDS data alone.
+    async fn set_tags(&self, tags: HashMap<String, String>) -> Result<(), FirecrackerError>;
+    /// The tags under [`TAGS_POINTER`]; empty when none were set.
+    async fn get_tags(&self) -> Result<HashMap<String, String>, FirecrackerError>;
+    /// Removes one tag. Removing a tag that isn't set is a no-op.
+    async fn remove_tag(&self, key: &str) -> Result<(), FirecrackerError>;
+}
+
+/// Builds the JSON merge patch that deletes every key addressed by `pointers`.
+///
+/// Every reference token is treated as an object key, since merge patches
+/// cannot address array elements.
+pub fn mmds_delete_patch(pointers: &[&str]) -> Result<Value, FirecrackerError> {
+    let mut patch = Map::new();
+
+    for pointer in pointers {
+        insert_null(&mut patch, &pointer_tokens("pointers", pointer)?);
+    }
+
+    Ok(Value::Object(patch))
+}
+
+/// Builds the JSON merge patch that sets the key addressed by `pointer` to
+/// `value`, creating the objects above it. As with any merge patch, an
+/// object value is merged into an object already at `pointer` and a `null`
+/// value deletes the key.
+pub fn mmds_set_patch(pointer: &str, value: Value) -> Result<Value, FirecrackerError> {
+    Ok(pointer_tokens("pointer", pointer)?
+        .into_iter()
+        .rev()
+        .fold(value, |value, token| {
+            Value::Object(Map::from_iter([(token, value)]))
+        }))
+}
+
+fn pointer_tokens(field: &'static str, pointer: &str) -> Result<Vec<String>, FirecrackerError> {
+    validate_mmds_pointer(pointer).map_err(|e| field_validation_errors(field, e))?;
+
+    Ok(pointer[1..]
+        .split('/')
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn insert_null(node: &mut Map<String, Value>, tokens: &[String]) {
+    match tokens {
+        [] => {}
+        [leaf] => {
+            node.insert(leaf.clone(), Value::Null);
+        }
+        [parent, rest @ ..] => {
+            let entry = node
+                .entry(parent.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            // A non-object entry means an ancestor is already being deleted
+            if let Value::Object(child) = entry {
+                insert_null(child, rest);
+            }
+        }
+    }
 }
 
 #[async_trait]
 impl MmdsOperations for crate::FirecrackerClient {
     async fn put_mmds(&self, data: Value) -> Result<(), FirecrackerError> {
-        let url = self.url("mmds")?;
-        let response = self.client.put(url).json(&data).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
+        self.check_mmds_root(&data)?;
+        self.run_validators(&ValidationTarget::Mmds(&data))?;
 
-        Ok(())
+        self.send_json(Method::PUT, paths::MMDS, &data).await
     }
 
     async fn patch_mmds(&self, data: Value) -> Result<(), FirecrackerError> {
-        let url = self.url("mmds")?;
-        let response = self.client.patch(url).json(&data).send().await?;
+        self.check_mmds_root(&data)?;
+        self.run_validators(&ValidationTarget::MmdsUpdate(&data))?;
+
+        self.send_json(Method::PATCH, paths::MMDS, &data).await
+    }
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+    async fn get_mmds(&self) -> Result<Value, FirecrackerError> {
+        self.get_json(paths::MMDS).await
+    }
+
+    async fn put_mmds_config(&self, config: &MmdsConfig) -> Result<(), FirecrackerError> {
+        self.validated_put(paths::MMDS_CONFIG, config).await
+    }
+
+    async fn delete_mmds_keys(&self, pointers: &[&str]) -> Result<(), FirecrackerError> {
+        let patch = mmds_delete_patch(pointers)?;
+        self.patch_mmds(patch).await
+    }
+
+    async fn wait_for_mmds_value<F>(
+        &self,
+        pointer: &str,
+        predicate: F,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Value, FirecrackerError>
+    where
+        F: Fn(&Value) -> bool + Send + Sync,
+    {
+        validate_mmds_pointer(pointer).map_err(|e| field_validation_errors("pointer", e))?;
+        if interval.is_zero() {
+            let mut err = ValidationError::new("zero_interval");
+            err.message = Some("interval must be greater than zero".into());
+            return Err(field_validation_errors("interval", err).into());
         }
+        let deadline = Instant::now() + timeout;
+        let mut last_value = None;
 
-        Ok(())
+        loop {
+            let data = self.get_mmds().await?;
+            if let Some(value) = data.pointer(pointer) {
+                if predicate(value) {
+                    return Ok(value.clone());
+                }
+                last_value = Some(value.clone());
+            }
+
+            if Instant::now() + interval > deadline {
+                return Err(FirecrackerError::MmdsWaitTimeout {
+                    pointer: pointer.to_string(),
+                    duration_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+                    last_value,
+                });
+            }
+            sleep(interval).await;
+        }
     }
 
-    async fn get_mmds(&self) -> Result<Value, FirecrackerError> {
-        let url = self.url("mmds")?;
-        let response = self.client.get(url).send().await?;
+    async fn wait_for_mmds_key(
+        &self,
+        pointer: &str,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Value, FirecrackerError> {
+        self.wait_for_mmds_value(pointer, |_| true, timeout, interval)
+            .await
+    }
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+    async fn set_tags(&self, tags: HashMap<String, String>) -> Result<(), FirecrackerError> {
+        for (key, value) in &tags {
+            validate_mmds_tag(key, value).map_err(|e| field_validation_errors("tags", e))?;
         }
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        let tags = tags
+            .into_iter()
+            .map(|(key, value)| (key, Value::String(value)))
+            .collect();
+        self.patch_mmds(mmds_set_patch(TAGS_POINTER, Value::Object(tags))?)
+            .await
+    }
+
+    async fn get_tags(&self) -> Result<HashMap<String, String>, FirecrackerError> {
+        match self.get_mmds().await?.pointer(TAGS_POINTER) {
+            Some(tags) => Ok(serde_json::from_value(tags.clone())?),
+            None => Ok(HashMap::new()),
+        }
+    }
 
-        Ok(response.json().await?)
+    async fn remove_tag(&self, key: &str) -> Result<(), FirecrackerError> {
+        validate_mmds_tag(key, "").map_err(|e| field_validation_errors("key", e))?;
+
+        self.delete_mmds_keys(&[&format!("{}/{}", TAGS_POINTER, key)])
+            .await
+    }
+}
+
+impl crate::FirecrackerClient {
+    fn check_mmds_root(&self, data: &Value) -> Result<(), FirecrackerError> {
+        if self.strict_mmds_root {
+            validate_mmds_root(data).map_err(|e| field_validation_errors("data", e))?;
+        }
+        Ok(())
     }
 }
@@ -1,11 +1,15 @@
-use crate::validation::{validate_existing_path, validate_unix_path};
+use crate::validation::{
+    validate_boot_args, validate_host_dev_name, validate_mmds_ipv4_address, validate_uds_path,
+    validate_unix_path, ValidatePatch, ValidationTarget,
+};
+use crate::version::{meets, parse_version, Version, VSOCK_ID_REMOVED_VERSION};
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::{Deserialize, Serialize};
-use validator::Validate;
-
-// Re-exports
-pub use crate::logger::Logger;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 // Core types
 
@@ -13,23 +17,75 @@ pub use crate::logger::Logger;
 /// This device allows for memory overcommitment by reclaiming unused memory from the guest
 /// and making it available to the host or other guests. It's particularly useful in
 /// environments where memory resources need to be managed efficiently across multiple VMs.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct Balloon {
-    /// Target balloon size in MiB
+    /// Target balloon size in MiB, at most `MAX_BALLOON_MIB`
+    #[validate(range(max = "crate::validation::MAX_BALLOON_MIB"))]
     pub amount_mib: u32,
-    /// Whether the balloon should deflate when the guest has memory pressure
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub deflate_on_oom: Option<bool>,
+    /// Whether the balloon should deflate when the guest has memory pressure.
+    /// `PUT /balloon` requires it, so it is always sent; some releases leave
+    /// it out of `GET /balloon`, which reads as `false`.
+    #[serde(default)]
+    pub deflate_on_oom: bool,
     /// Interval in seconds between refreshing statistics. A non-zero value will enable the statistics. Defaults to 0
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(max = "crate::validation::MAX_STATS_POLLING_INTERVAL_S"))]
     pub stats_polling_interval_s: Option<u32>,
 }
 
+impl Balloon {
+    /// A balloon that inflates to `amount_mib`.
+    pub fn new(amount_mib: u32) -> Self {
+        Self {
+            amount_mib,
+            deflate_on_oom: false,
+            stats_polling_interval_s: None,
+        }
+    }
+
+    /// Starts building a balloon that inflates to `amount_mib`.
+    pub fn builder(amount_mib: u32) -> BalloonBuilder {
+        BalloonBuilder {
+            balloon: Balloon::new(amount_mib),
+        }
+    }
+}
+
+/// Builder for [`Balloon`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalloonBuilder {
+    balloon: Balloon,
+}
+
+impl BalloonBuilder {
+    /// Lets the guest deflate the balloon when it runs out of memory.
+    pub fn deflate_on_oom(mut self, enabled: bool) -> Self {
+        self.balloon.deflate_on_oom = enabled;
+        self
+    }
+
+    /// Refreshes the balloon statistics every `seconds`; 0 disables them.
+    pub fn stats_polling_interval_s(mut self, seconds: u32) -> Self {
+        self.balloon.stats_polling_interval_s = Some(seconds);
+        self
+    }
+
+    /// Validates and returns the balloon.
+    pub fn build(self) -> Result<Balloon, crate::FirecrackerError> {
+        self.balloon.validate()?;
+        Ok(self.balloon)
+    }
+}
+
 /// Provides detailed memory statistics from the balloon device, helping monitor
 /// memory usage patterns and performance of the guest VM. These statistics are
 /// essential for making informed decisions about memory allocation and identifying
 /// potential memory-related issues.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct BalloonStats {
     /// Actual amount of memory (in MiB) the device is holding
     pub actual_mib: u32,
@@ -69,39 +125,113 @@ pub struct BalloonStats {
     /// The total amount of memory available (in bytes)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_memory: Option<i64>,
+    /// The number of times the guest OOM killer has run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_kill: Option<i64>,
+    /// The number of allocations that stalled waiting for memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alloc_stall: Option<i64>,
+    /// The amount of memory scanned asynchronously by kswapd
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub async_scan: Option<i64>,
+    /// The amount of memory scanned directly by allocating tasks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct_scan: Option<i64>,
+    /// The amount of memory reclaimed asynchronously by kswapd
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub async_reclaim: Option<i64>,
+    /// The amount of memory reclaimed directly by allocating tasks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct_reclaim: Option<i64>,
 }
 
 /// Used to update the statistics polling interval of a balloon device.
 /// This allows for dynamic adjustment of how frequently memory statistics
 /// are collected without needing to recreate the balloon device.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct BalloonStatsUpdate {
     /// Interval in seconds between refreshing statistics
     pub stats_polling_interval_s: u32,
 }
 
+impl BalloonStatsUpdate {
+    pub fn new(stats_polling_interval_s: u32) -> Self {
+        Self {
+            stats_polling_interval_s,
+        }
+    }
+}
+
 /// Defines the boot configuration for a microVM, specifying the kernel image,
 /// optional initial ramdisk, and kernel boot parameters. This configuration
 /// must be set before starting the microVM and cannot be modified after boot.
-#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct BootSource {
     /// Kernel boot arguments
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_boot_args")]
     pub boot_args: Option<String>,
     /// Host level path to the initrd image used to boot the guest
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[validate(custom = "validate_existing_path")]
+    #[validate(custom = "validate_unix_path")]
     pub initrd_path: Option<String>,
     /// Host level path to the kernel image used to boot the guest
-    #[validate(custom = "validate_existing_path")]
+    #[validate(custom = "validate_unix_path")]
     pub kernel_image_path: String,
 }
 
+impl BootSource {
+    /// A boot source for the kernel at `kernel_image_path`.
+    pub fn new(kernel_image_path: impl Into<String>) -> Self {
+        Self {
+            kernel_image_path: kernel_image_path.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts building a boot source for the kernel at `kernel_image_path`.
+    pub fn builder(kernel_image_path: impl Into<String>) -> BootSourceBuilder {
+        BootSourceBuilder {
+            boot_source: BootSource::new(kernel_image_path),
+        }
+    }
+}
+
+/// Builder for [`BootSource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootSourceBuilder {
+    boot_source: BootSource,
+}
+
+impl BootSourceBuilder {
+    pub fn boot_args(mut self, boot_args: impl Into<String>) -> Self {
+        self.boot_source.boot_args = Some(boot_args.into());
+        self
+    }
+
+    pub fn initrd_path(mut self, initrd_path: impl Into<String>) -> Self {
+        self.boot_source.initrd_path = Some(initrd_path.into());
+        self
+    }
+
+    /// Validates and returns the boot source. Whether the images exist is
+    /// checked when it is sent.
+    pub fn build(self) -> Result<BootSource, crate::FirecrackerError> {
+        self.boot_source.validate()?;
+        Ok(self.boot_source)
+    }
+}
+
 /// Provides fine-grained control over CPU features exposed to the guest VM.
 /// This allows for platform-specific optimizations and security configurations
 /// by enabling or disabling specific CPU capabilities on both x86_64 and aarch64
 /// architectures.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct CpuConfig {
     /// A collection of CPUIDs to be modified (x86_64)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -124,7 +254,8 @@ pub struct CpuConfig {
 /// specific AWS EC2 instance types. This ensures consistent CPU feature
 /// sets across different Firecracker deployments and helps with workload
 /// compatibility.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "PascalCase")]
 pub enum CpuTemplate {
     C3,
@@ -140,48 +271,312 @@ pub enum CpuTemplate {
 /// file or a block device on the host that is exposed to the guest. Supports
 /// both read-only and read-write modes, and can be configured as the root
 /// device for the guest filesystem.
-#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_drive_source", skip_on_field_errors = false))]
+#[serde(from = "DriveWire", into = "DriveWire")]
+#[non_exhaustive]
 pub struct Drive {
     /// Represents the caching strategy for the block device
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub cache_type: Option<String>,
     /// Unique identifier for the drive
+    #[validate(length(min = 1))]
     pub drive_id: String,
     /// Type of IO engine
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub io_engine: Option<String>,
     /// Whether the block device is read-only
     pub is_read_only: bool,
     /// Whether this is the root device
     pub is_root_device: bool,
     /// Unique id of the boot partition (only used if is_root_device is true)
-    #[serde(skip_serializing_if = "Option::is_none")]
     #[validate(regex(path = "PARTUUID_REGEX", message = "Invalid partition UUID format"))]
     pub partuuid: Option<String>,
     /// Host level path for the guest drive
-    #[validate(custom = "validate_existing_path")]
     pub path_on_host: String,
     /// Rate limiter for the drive
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub rate_limiter: Option<RateLimiter>,
     /// Socket path for the drive
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_uds_path")]
     pub socket: Option<String>,
 }
 
+// vhost-user drives are backed by their socket and have no path_on_host
+fn validate_drive_source(drive: &Drive) -> Result<(), ValidationError> {
+    match &drive.socket {
+        Some(_) if drive.path_on_host.is_empty() => Ok(()),
+        Some(_) => Err(ValidationError::new("conflicting_drive_backend")),
+        None => validate_unix_path(&drive.path_on_host),
+    }
+}
+
+// How a drive looks on the wire. Firecracker only accepts, and only reports,
+// the fields of the drive's backend; fields of the other backend may only be
+// null, so a payload that mixes the two matches neither variant.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+enum DriveWire {
+    VhostUser(VhostUserDriveWire),
+    File(FileDriveWire),
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(rename = "FileDrive"))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+struct FileDriveWire {
+    #[cfg_attr(feature = "schemars", schemars(length(min = 1)))]
+    drive_id: String,
+    is_root_device: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schemars", schemars(regex(path = "PARTUUID_REGEX")))]
+    partuuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_type: Option<String>,
+    path_on_host: String,
+    is_read_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    io_engine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate_limiter: Option<RateLimiter>,
+    #[serde(default, skip_serializing, rename = "socket")]
+    _socket: Option<()>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(rename = "VhostUserDrive"))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+struct VhostUserDriveWire {
+    #[cfg_attr(feature = "schemars", schemars(length(min = 1)))]
+    drive_id: String,
+    is_root_device: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schemars", schemars(regex(path = "PARTUUID_REGEX")))]
+    partuuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_type: Option<String>,
+    socket: String,
+    #[serde(default, skip_serializing, rename = "path_on_host")]
+    _path_on_host: Option<()>,
+    #[serde(default, skip_serializing, rename = "is_read_only")]
+    _is_read_only: Option<()>,
+    #[serde(default, skip_serializing, rename = "io_engine")]
+    _io_engine: Option<()>,
+    #[serde(default, skip_serializing, rename = "rate_limiter")]
+    _rate_limiter: Option<()>,
+}
+
+// A drive with a socket is sent as vhost-user; validation rejects one that
+// also has a path
+impl From<Drive> for DriveWire {
+    fn from(drive: Drive) -> Self {
+        match drive.socket {
+            Some(socket) => DriveWire::VhostUser(VhostUserDriveWire {
+                drive_id: drive.drive_id,
+                is_root_device: drive.is_root_device,
+                partuuid: drive.partuuid,
+                cache_type: drive.cache_type,
+                socket,
+                _path_on_host: None,
+                _is_read_only: None,
+                _io_engine: None,
+                _rate_limiter: None,
+            }),
+            None => DriveWire::File(FileDriveWire {
+                drive_id: drive.drive_id,
+                is_root_device: drive.is_root_device,
+                partuuid: drive.partuuid,
+                cache_type: drive.cache_type,
+                path_on_host: drive.path_on_host,
+                is_read_only: drive.is_read_only,
+                io_engine: drive.io_engine,
+                rate_limiter: drive.rate_limiter,
+                _socket: None,
+            }),
+        }
+    }
+}
+
+// The schema of a drive is the schema of its wire form
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for Drive {
+    fn schema_name() -> String {
+        "Drive".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        DriveWire::json_schema(gen)
+    }
+}
+
+impl From<DriveWire> for Drive {
+    fn from(wire: DriveWire) -> Self {
+        match wire {
+            DriveWire::VhostUser(drive) => Drive {
+                drive_id: drive.drive_id,
+                is_root_device: drive.is_root_device,
+                partuuid: drive.partuuid,
+                cache_type: drive.cache_type,
+                socket: Some(drive.socket),
+                ..Default::default()
+            },
+            DriveWire::File(drive) => Drive {
+                drive_id: drive.drive_id,
+                is_root_device: drive.is_root_device,
+                partuuid: drive.partuuid,
+                cache_type: drive.cache_type,
+                path_on_host: drive.path_on_host,
+                is_read_only: drive.is_read_only,
+                io_engine: drive.io_engine,
+                rate_limiter: drive.rate_limiter,
+                socket: None,
+            },
+        }
+    }
+}
+
+impl Drive {
+    /// The drive `drive_id` backed by the host file `path_on_host`.
+    pub fn new(drive_id: impl Into<String>, path_on_host: impl Into<String>) -> Self {
+        Self {
+            drive_id: drive_id.into(),
+            path_on_host: path_on_host.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The vhost-user drive `drive_id` served by the backend at `socket`.
+    pub fn vhost_user(drive_id: impl Into<String>, socket: impl Into<String>) -> Self {
+        Self {
+            drive_id: drive_id.into(),
+            socket: Some(socket.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Starts building the drive `drive_id`. Set either
+    /// [`DriveBuilder::path_on_host`] or, for vhost-user drives,
+    /// [`DriveBuilder::socket`].
+    pub fn builder(drive_id: impl Into<String>) -> DriveBuilder {
+        DriveBuilder {
+            drive: Drive {
+                drive_id: drive_id.into(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Builder for [`Drive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriveBuilder {
+    drive: Drive,
+}
+
+impl DriveBuilder {
+    pub fn path_on_host(mut self, path_on_host: impl Into<String>) -> Self {
+        self.drive.path_on_host = path_on_host.into();
+        self
+    }
+
+    pub fn socket(mut self, socket: impl Into<String>) -> Self {
+        self.drive.socket = Some(socket.into());
+        self
+    }
+
+    pub fn root_device(mut self, is_root_device: bool) -> Self {
+        self.drive.is_root_device = is_root_device;
+        self
+    }
+
+    pub fn read_only(mut self, is_read_only: bool) -> Self {
+        self.drive.is_read_only = is_read_only;
+        self
+    }
+
+    pub fn partuuid(mut self, partuuid: impl Into<String>) -> Self {
+        self.drive.partuuid = Some(partuuid.into());
+        self
+    }
+
+    pub fn cache_type(mut self, cache_type: impl Into<String>) -> Self {
+        self.drive.cache_type = Some(cache_type.into());
+        self
+    }
+
+    pub fn io_engine(mut self, io_engine: impl Into<String>) -> Self {
+        self.drive.io_engine = Some(io_engine.into());
+        self
+    }
+
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.drive.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Validates and returns the drive. Fails if neither a path nor a
+    /// socket was set.
+    pub fn build(self) -> Result<Drive, crate::FirecrackerError> {
+        self.drive.validate()?;
+        Ok(self.drive)
+    }
+}
+
 /// Configures a virtual device that provides entropy/randomness to the guest VM.
 /// This is crucial for applications in the guest that require cryptographic
 /// operations or random number generation.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct EntropyDevice {
     /// Rate limiter for the entropy device
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub rate_limiter: Option<RateLimiter>,
 }
 
+impl EntropyDevice {
+    /// Starts building an entropy device.
+    ///
+    /// The guest sees the device through its virtio-rng driver (usually as
+    /// `/dev/hwrng`). A bandwidth limit caps how many bytes the guest can pull
+    /// per refill period and an ops limit caps how many requests it can make;
+    /// once a bucket is empty, guest reads block until it refills, so keep the
+    /// limits generous enough for early boot when the kernel seeds its pool.
+    pub fn builder() -> EntropyDeviceBuilder {
+        EntropyDeviceBuilder::default()
+    }
+}
+
+/// Builder for [`EntropyDevice`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EntropyDeviceBuilder {
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl EntropyDeviceBuilder {
+    /// Throttles the device with the given rate limiter.
+    pub fn rate(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Validates and returns the device.
+    pub fn build(self) -> Result<EntropyDevice, crate::FirecrackerError> {
+        let device = EntropyDevice {
+            rate_limiter: self.rate_limiter,
+        };
+        device.validate()?;
+        Ok(device)
+    }
+}
+
 /// Represents an error response from the Firecracker API. Used to provide
 /// detailed information about what went wrong during an API operation.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Error {
     /// Error message describing the fault
     pub fault_message: String,
@@ -189,18 +584,88 @@ pub struct Error {
 
 /// Contains version information about the Firecracker service.
 /// Used to ensure compatibility between the client and server.
-#[derive(Debug, Serialize, Deserialize)]
+/// Deserializes from the `{"firecracker_version": ...}` object or a bare
+/// version string, dropping a leading `v` either way.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "VersionBody")]
+#[non_exhaustive]
 pub struct FirecrackerVersion {
     /// Version of the Firecracker service
     pub firecracker_version: String,
+    #[serde(skip)]
+    parsed: OnceLock<Option<Version>>,
+}
+
+impl FirecrackerVersion {
+    pub fn new(firecracker_version: impl Into<String>) -> Self {
+        Self {
+            firecracker_version: firecracker_version.into(),
+            parsed: OnceLock::new(),
+        }
+    }
+
+    /// Parses the version string, caching the result. Prerelease builds
+    /// such as `1.7.0-dev` keep their suffix.
+    pub fn semver(&self) -> Result<Version, crate::FirecrackerError> {
+        self.parsed
+            .get_or_init(|| parse_version(&self.firecracker_version).ok())
+            .clone()
+            .ok_or_else(|| parse_version(&self.firecracker_version).unwrap_err())
+    }
+
+    /// Whether this is release `major.minor` or later, counting prerelease
+    /// builds as the release they lead up to. False if the version can't be
+    /// parsed.
+    pub fn at_least(&self, major: u64, minor: u64) -> bool {
+        self.semver()
+            .is_ok_and(|v| meets(&v, &Version::new(major, minor, 0)))
+    }
+}
+
+// The shapes `GET /version` comes back in: the documented object, or a
+// bare string from some patched builds
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum VersionBody {
+    Object { firecracker_version: String },
+    Bare(String),
+}
+
+impl From<VersionBody> for FirecrackerVersion {
+    fn from(body: VersionBody) -> Self {
+        let (VersionBody::Object {
+            firecracker_version: raw,
+        }
+        | VersionBody::Bare(raw)) = body;
+        let trimmed = raw.trim();
+        FirecrackerVersion::new(trimmed.strip_prefix('v').unwrap_or(trimmed))
+    }
+}
+
+// Compares the version string only, whether or not it was parsed yet
+impl PartialEq for FirecrackerVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.firecracker_version == other.firecracker_version
+    }
+}
+
+impl Eq for FirecrackerVersion {}
+
+impl fmt::Display for FirecrackerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.firecracker_version)
+    }
 }
 
 /// Provides metadata about a Firecracker instance, including its
 /// identity, current state, and version information. This is useful
 /// for monitoring and managing multiple Firecracker instances.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct InstanceInfo {
-    /// Name of the application
+    /// Name of the application. Empty when talking to a release that does
+    /// not report it
+    #[serde(default)]
     pub app_name: String,
     /// Instance identifier
     pub id: String,
@@ -210,10 +675,219 @@ pub struct InstanceInfo {
     pub vmm_version: String,
 }
 
+impl InstanceInfo {
+    /// Parses `vmm_version` the same way as
+    /// [`FirecrackerVersion::semver`].
+    pub fn semver(&self) -> Result<Version, crate::FirecrackerError> {
+        parse_version(&self.vmm_version)
+    }
+
+    /// `state`, parsed.
+    pub fn instance_state(&self) -> InstanceState {
+        InstanceState::from(self.state.as_str())
+    }
+}
+
+/// The `state` reported by `GET /`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InstanceState {
+    NotStarted,
+    Running,
+    Paused,
+    /// A state this client doesn't know, as reported
+    Other(String),
+}
+
+impl InstanceState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            InstanceState::NotStarted => "Not started",
+            InstanceState::Running => "Running",
+            InstanceState::Paused => "Paused",
+            InstanceState::Other(state) => state,
+        }
+    }
+
+    /// Whether the VM has booted, paused or not.
+    pub fn is_started(&self) -> bool {
+        *self != InstanceState::NotStarted
+    }
+}
+
+impl From<&str> for InstanceState {
+    fn from(state: &str) -> Self {
+        match state {
+            "Not started" => InstanceState::NotStarted,
+            "Running" => InstanceState::Running,
+            "Paused" => InstanceState::Paused,
+            other => InstanceState::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for InstanceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Log verbosity accepted by the Firecracker logger. Serializes to the
+/// capitalized spelling Firecracker expects; parsing and deserialization are
+/// case-insensitive and accept `Warn` as an alias for `Warning`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    Debug,
+    Trace,
+    Off,
+}
+
+const LOG_LEVELS: &[&str] = &["Error", "Warning", "Info", "Debug", "Trace", "Off"];
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "Error",
+            LogLevel::Warning => "Warning",
+            LogLevel::Info => "Info",
+            LogLevel::Debug => "Debug",
+            LogLevel::Trace => "Trace",
+            LogLevel::Off => "Off",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = crate::FirecrackerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(LogLevel::Error),
+            "warning" | "warn" => Ok(LogLevel::Warning),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            "off" => Ok(LogLevel::Off),
+            _ => Err(crate::FirecrackerError::Config(format!(
+                "Invalid log level {:?}. Must be one of: {}",
+                s,
+                LOG_LEVELS.join(", ")
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LogLevel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let level = String::deserialize(deserializer)?;
+        level
+            .parse()
+            .map_err(|_| de::Error::unknown_variant(&level, LOG_LEVELS))
+    }
+}
+
+/// Configures the Firecracker logger. The log path can be a regular file or
+/// a named pipe; the level and module filter control which messages are
+/// written, and the show flags control how much context each line carries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Logger {
+    /// Path to the log file or named pipe; older config files call it
+    /// `log_fifo`
+    #[serde(alias = "log_fifo")]
+    #[validate(custom = "validate_unix_path")]
+    pub log_path: String,
+    /// Minimum level of messages to write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<LogLevel>,
+    /// Whether to include the level in each line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_level: Option<bool>,
+    /// Whether to include the originating file and line in each line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_log_origin: Option<bool>,
+    /// Only emit logs from this module path (Firecracker >= 1.3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(regex(
+        path = "LOG_MODULE_REGEX",
+        message = "Invalid module filter. Must be a Rust module path such as api_server::request"
+    ))]
+    pub module: Option<String>,
+}
+
+impl Logger {
+    /// A logger that writes to `log_path` with Firecracker's defaults.
+    pub fn new(log_path: impl Into<String>) -> Self {
+        Self {
+            log_path: log_path.into(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        }
+    }
+
+    /// Starts building a logger that writes to `log_path`.
+    pub fn builder(log_path: impl Into<String>) -> LoggerBuilder {
+        LoggerBuilder {
+            logger: Logger::new(log_path),
+        }
+    }
+}
+
+/// Builder for [`Logger`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggerBuilder {
+    logger: Logger,
+}
+
+impl LoggerBuilder {
+    pub fn level(mut self, level: LogLevel) -> Self {
+        self.logger.level = Some(level);
+        self
+    }
+
+    pub fn show_level(mut self, enabled: bool) -> Self {
+        self.logger.show_level = Some(enabled);
+        self
+    }
+
+    pub fn show_log_origin(mut self, enabled: bool) -> Self {
+        self.logger.show_log_origin = Some(enabled);
+        self
+    }
+
+    pub fn module(mut self, module: impl Into<String>) -> Self {
+        self.logger.module = Some(module.into());
+        self
+    }
+
+    /// Validates and returns the logger.
+    pub fn build(self) -> Result<Logger, crate::FirecrackerError> {
+        self.logger.validate()?;
+        Ok(self.logger)
+    }
+}
+
 /// Defines the core configuration of a microVM, including CPU and memory
 /// resources. These settings determine the computational capacity and
 /// performance characteristics of the VM.
-#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct MachineConfig {
     /// CPU template for configuring guest CPU features
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -223,6 +897,7 @@ pub struct MachineConfig {
     pub huge_pages: Option<String>,
     /// Memory size in MiB
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
     pub mem_size_mib: Option<u32>,
     /// Enable/disable Simultaneous Multi-Threading
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -232,119 +907,632 @@ pub struct MachineConfig {
     pub track_dirty_pages: Option<bool>,
     /// Number of vCPUs
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 32))]
     pub vcpu_count: Option<u32>,
 }
 
-/// Configures the metrics system for Firecracker, allowing for monitoring
-/// of various performance and operational metrics of the microVM.
-#[derive(Debug, Default, Serialize, Deserialize)]
-pub struct Metrics {
-    /// Path to store metrics
-    pub metrics_path: String,
+// Every machine config field is optional, so a PATCH follows the same rules
+impl ValidatePatch for MachineConfig {
+    fn validate_patch(&self) -> Result<(), ValidationErrors> {
+        self.validate()
+    }
+
+    fn patch_target(&self) -> ValidationTarget<'_> {
+        ValidationTarget::MachineConfigUpdate(self)
+    }
 }
 
+impl MachineConfig {
+    /// A machine config with `vcpu_count` vCPUs and `mem_size_mib` MiB of
+    /// memory.
+    pub fn new(vcpu_count: u32, mem_size_mib: u32) -> Self {
+        Self {
+            vcpu_count: Some(vcpu_count),
+            mem_size_mib: Some(mem_size_mib),
+            ..Default::default()
+        }
+    }
+
+    /// Starts building a machine config with `vcpu_count` vCPUs and
+    /// `mem_size_mib` MiB of memory, which a PUT must always set.
+    pub fn builder(vcpu_count: u32, mem_size_mib: u32) -> MachineConfigBuilder {
+        MachineConfigBuilder {
+            config: MachineConfig::new(vcpu_count, mem_size_mib),
+        }
+    }
+}
+
+/// Builder for [`MachineConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineConfigBuilder {
+    config: MachineConfig,
+}
+
+impl MachineConfigBuilder {
+    pub fn cpu_template(mut self, cpu_template: CpuTemplate) -> Self {
+        self.config.cpu_template = Some(cpu_template);
+        self
+    }
+
+    pub fn huge_pages(mut self, huge_pages: impl Into<String>) -> Self {
+        self.config.huge_pages = Some(huge_pages.into());
+        self
+    }
+
+    pub fn smt(mut self, enabled: bool) -> Self {
+        self.config.smt = Some(enabled);
+        self
+    }
+
+    pub fn track_dirty_pages(mut self, enabled: bool) -> Self {
+        self.config.track_dirty_pages = Some(enabled);
+        self
+    }
+
+    /// Validates and returns the machine config.
+    pub fn build(self) -> Result<MachineConfig, crate::FirecrackerError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// The metrics config lives with `put_metrics`; it is re-exported here so
+/// `models::Metrics` and the crate root name the type `put_metrics` takes.
+pub use crate::metrics::Metrics;
+
 /// Configures the Microvm Metadata Service (MMDS), which provides a way
 /// for the guest to securely access metadata and user data. This is similar
 /// to AWS EC2's instance metadata service.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct MmdsConfig {
-    /// IPv4 address for the MMDS
+    /// Link-local IPv4 address the guest reaches MMDS at
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_mmds_ipv4_address")]
     pub ipv4_address: Option<String>,
     /// List of network interfaces for MMDS
     pub network_interfaces: Vec<String>,
-    /// Version of the MMDS
+    /// Version of the MMDS, `V1` or `V2`
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(regex(path = "MMDS_VERSION_REGEX", message = "MMDS version must be V1 or V2"))]
     pub version: Option<String>,
 }
 
+impl MmdsConfig {
+    /// An MMDS config that exposes MMDS on the given network interfaces.
+    pub fn new<I, S>(network_interfaces: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            network_interfaces: network_interfaces.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts building an MMDS config that exposes MMDS on the given
+    /// network interfaces.
+    pub fn builder<I, S>(network_interfaces: I) -> MmdsConfigBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        MmdsConfigBuilder {
+            config: MmdsConfig::new(network_interfaces),
+        }
+    }
+}
+
+/// Builder for [`MmdsConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MmdsConfigBuilder {
+    config: MmdsConfig,
+}
+
+impl MmdsConfigBuilder {
+    pub fn ipv4_address(mut self, ipv4_address: impl Into<String>) -> Self {
+        self.config.ipv4_address = Some(ipv4_address.into());
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.config.version = Some(version.into());
+        self
+    }
+
+    /// Validates and returns the MMDS config.
+    pub fn build(self) -> Result<MmdsConfig, crate::FirecrackerError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
 /// Defines a network interface for the guest VM, allowing for network
 /// connectivity. Supports configuration of MAC addresses and rate limiting
 /// for both receive and transmit traffic.
-#[derive(Debug, Default, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct NetworkInterface {
     /// MAC address of the guest network interface
     #[serde(skip_serializing_if = "Option::is_none")]
     #[validate(regex(path = "MAC_ADDRESS_REGEX", message = "Invalid MAC address format"))]
     pub guest_mac: Option<String>,
-    /// Host level path for the guest network interface
-    #[validate(custom = "validate_unix_path")]
+    /// Name of the host tap device backing the interface
+    #[validate(custom = "validate_host_dev_name")]
     pub host_dev_name: String,
     /// Network interface identifier
     #[validate(length(min = 1))]
     pub iface_id: String,
     /// Rate limiter for received traffic
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub rx_rate_limiter: Option<RateLimiter>,
     /// Rate limiter for transmitted traffic
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub tx_rate_limiter: Option<RateLimiter>,
 }
 
+impl NetworkInterface {
+    /// The interface `iface_id` backed by the host tap device
+    /// `host_dev_name`.
+    pub fn new(iface_id: impl Into<String>, host_dev_name: impl Into<String>) -> Self {
+        Self {
+            iface_id: iface_id.into(),
+            host_dev_name: host_dev_name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts building the interface `iface_id` backed by the host tap
+    /// device `host_dev_name`.
+    pub fn builder(
+        iface_id: impl Into<String>,
+        host_dev_name: impl Into<String>,
+    ) -> NetworkInterfaceBuilder {
+        NetworkInterfaceBuilder {
+            interface: NetworkInterface::new(iface_id, host_dev_name),
+        }
+    }
+}
+
+/// Builder for [`NetworkInterface`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkInterfaceBuilder {
+    interface: NetworkInterface,
+}
+
+impl NetworkInterfaceBuilder {
+    pub fn guest_mac(mut self, guest_mac: impl Into<String>) -> Self {
+        self.interface.guest_mac = Some(guest_mac.into());
+        self
+    }
+
+    pub fn rx_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.interface.rx_rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn tx_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.interface.tx_rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Validates and returns the interface.
+    pub fn build(self) -> Result<NetworkInterface, crate::FirecrackerError> {
+        self.interface.validate()?;
+        Ok(self.interface)
+    }
+}
+
 /// Implements rate limiting for I/O operations, allowing control over
 /// bandwidth and operations per second. This is used by various devices
 /// like network interfaces and block devices to prevent resource exhaustion.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct RateLimiter {
     /// Bandwidth rate limiter
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub bandwidth: Option<TokenBucket>,
     /// Operations rate limiter
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub ops: Option<TokenBucket>,
 }
 
+impl RateLimiter {
+    /// Limits bandwidth to `bytes_per_sec` bytes per second.
+    pub fn bandwidth(bytes_per_sec: u64) -> Self {
+        Self {
+            bandwidth: Some(TokenBucket::per_second(bytes_per_sec)),
+            ops: None,
+        }
+    }
+
+    /// Limits operations to `ops_per_sec` operations per second.
+    pub fn ops(ops_per_sec: u64) -> Self {
+        Self {
+            bandwidth: None,
+            ops: Some(TokenBucket::per_second(ops_per_sec)),
+        }
+    }
+
+    /// Adds a bandwidth limit of `bytes_per_sec` bytes per second.
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.bandwidth = Some(TokenBucket::per_second(bytes_per_sec));
+        self
+    }
+
+    /// Adds an operations limit of `ops_per_sec` operations per second.
+    pub fn with_ops(mut self, ops_per_sec: u64) -> Self {
+        self.ops = Some(TokenBucket::per_second(ops_per_sec));
+        self
+    }
+}
+
 /// Implements the token bucket algorithm for rate limiting. This provides
 /// a way to control both the steady-state rate and burst capacity for
 /// operations or bandwidth.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct TokenBucket {
     /// Initial burst size
-    pub one_time_burst: Option<i64>,
+    pub one_time_burst: Option<u64>,
     /// Refill time in milliseconds
-    pub refill_time: i64,
+    #[validate(range(min = 1, message = "refill_time must be greater than 0"))]
+    pub refill_time: u64,
     /// Bucket size
-    pub size: i64,
+    #[validate(range(min = 1, message = "size must be greater than 0"))]
+    pub size: u64,
+}
+
+impl TokenBucket {
+    /// A bucket holding `size` tokens that refills completely every
+    /// `refill_time_ms` milliseconds. Both must be greater than 0.
+    pub fn new(size: u64, refill_time_ms: u64) -> Result<Self, ValidationError> {
+        if size == 0 {
+            return Err(range_error("size must be greater than 0"));
+        }
+        if refill_time_ms == 0 {
+            return Err(range_error("refill_time must be greater than 0"));
+        }
+        Ok(Self {
+            one_time_burst: None,
+            refill_time: refill_time_ms,
+            size,
+        })
+    }
+
+    /// A bucket holding `tokens` that refills completely every second.
+    pub fn per_second(tokens: u64) -> Self {
+        Self {
+            one_time_burst: None,
+            refill_time: 1000,
+            size: tokens,
+        }
+    }
+
+    /// Adds `one_time_burst` tokens that can be spent once on top of the
+    /// steady rate.
+    pub fn with_burst(mut self, one_time_burst: u64) -> Self {
+        self.one_time_burst = Some(one_time_burst);
+        self
+    }
+
+    /// The steady rate the bucket allows, in tokens per second, saturating
+    /// at `u64::MAX`. `None` if the refill time is 0.
+    pub fn tokens_per_second(&self) -> Option<u64> {
+        if self.refill_time == 0 {
+            return None;
+        }
+        let rate = u128::from(self.size) * 1000 / u128::from(self.refill_time);
+        Some(u64::try_from(rate).unwrap_or(u64::MAX))
+    }
+}
+
+fn range_error(message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new("range");
+    error.message = Some(message.into());
+    error
+}
+
+/// Target state for `PATCH /vm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VmState {
+    Paused,
+    Resumed,
 }
 
 /// Represents the state of a Firecracker microVM. Used primarily in
 /// the context of VM lifecycle management and snapshotting operations.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Vm {
     /// Current state of the VM
-    pub state: String,
+    pub state: VmState,
+}
+
+impl Vm {
+    pub fn new(state: VmState) -> Self {
+        Self { state }
+    }
 }
 
 /// Configures a vsock device, which provides a communication channel
 /// between the host and guest. This is particularly useful for services
 /// that need to communicate across the VM boundary without using traditional
 /// networking.
-#[derive(Debug, Serialize, Deserialize, Validate)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
 pub struct Vsock {
-    /// CID for the guest vsock
+    /// CID for the guest vsock; 0-2 are reserved
+    #[validate(range(min = 3, message = "guest_cid must be at least 3"))]
     pub guest_cid: u32,
-    /// Path to the vsock device
-    #[validate(custom = "validate_unix_path")]
+    /// Host-side Unix socket path, at most 107 bytes
+    #[validate(custom = "validate_uds_path")]
     pub uds_path: String,
     /// Vsock identifier
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vsock_id: Option<String>,
 }
 
-/// Represents the configuration of a Firecracker microVM, including its
-/// boot source, drives, network interfaces, and machine configuration.
-#[derive(Debug, Default, Serialize, Deserialize, Validate)]
-pub struct VmConfig {
+impl Vsock {
+    /// A vsock device for guest CID `guest_cid`, listening on the host at
+    /// `uds_path`.
+    pub fn new(guest_cid: u32, uds_path: impl Into<String>) -> Self {
+        Self {
+            guest_cid,
+            uds_path: uds_path.into(),
+            vsock_id: None,
+        }
+    }
+
+    /// Starts building a vsock device for guest CID `guest_cid`, listening
+    /// on the host at `uds_path`.
+    pub fn builder(guest_cid: u32, uds_path: impl Into<String>) -> VsockBuilder {
+        VsockBuilder {
+            vsock: Vsock::new(guest_cid, uds_path),
+        }
+    }
+
+    /// Returns the body to send to a VMM running `version`: `vsock_id` is
+    /// dropped for releases that reject it and required for those that
+    /// need it.
+    pub fn for_version(&self, version: &Version) -> Result<Vsock, crate::FirecrackerError> {
+        if meets(version, &VSOCK_ID_REMOVED_VERSION) {
+            return Ok(Vsock {
+                vsock_id: None,
+                ..self.clone()
+            });
+        }
+
+        if self.vsock_id.is_none() {
+            return Err(crate::FirecrackerError::Config(format!(
+                "vsock_id is required on firecracker {}",
+                version
+            )));
+        }
+        Ok(self.clone())
+    }
+
+    /// Warnings about settings that `version` ignores or rejects.
+    /// [`put_vsock`](crate::vsock::VsockOperations::put_vsock), and so
+    /// `apply_config`, reports them as `vsock_id_ignored`
+    /// [`ConfigWarning`](crate::validation::ConfigWarning)s.
+    pub fn version_warnings(&self, version: &Version) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.vsock_id.is_some() && meets(version, &VSOCK_ID_REMOVED_VERSION) {
+            warnings.push(format!(
+                "vsock_id is not accepted by firecracker {} and will not be sent",
+                version
+            ));
+        }
+        warnings
+    }
+}
+
+/// Builder for [`Vsock`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VsockBuilder {
+    vsock: Vsock,
+}
+
+impl VsockBuilder {
+    /// Only needed by Firecracker releases before 1.0.
+    pub fn vsock_id(mut self, vsock_id: impl Into<String>) -> Self {
+        self.vsock.vsock_id = Some(vsock_id.into());
+        self
+    }
+
+    /// Validates and returns the vsock device.
+    pub fn build(self) -> Result<Vsock, crate::FirecrackerError> {
+        self.vsock.validate()?;
+        Ok(self.vsock)
+    }
+}
+
+/// The complete configuration of a Firecracker microVM: every section that
+/// can be set before boot. Sections left unset are not configured.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+#[non_exhaustive]
+pub struct FullVmConfiguration {
     /// Balloon configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub balloon: Option<Balloon>,
     /// Boot source configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub boot_source: Option<BootSource>,
     /// List of drives
+    #[serde(default)]
+    #[validate]
     pub drives: Vec<Drive>,
     /// Machine configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
     pub machine_config: Option<MachineConfig>,
     /// List of network interfaces
+    #[serde(default)]
+    #[validate]
     pub network_interfaces: Vec<NetworkInterface>,
+    /// MMDS configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub mmds_config: Option<MmdsConfig>,
+    /// Vsock device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub vsock: Option<Vsock>,
+    /// Logger configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub logger: Option<Logger>,
+    /// Metrics configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub metrics: Option<Metrics>,
+    /// Entropy device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub entropy: Option<EntropyDevice>,
+}
+
+/// Kept so code using the old name keeps compiling.
+#[deprecated(note = "renamed to FullVmConfiguration")]
+pub type VmConfig = FullVmConfiguration;
+
+/// A PATCH field that can be left out, sent as an explicit `null` to clear
+/// the current value, or set. Fields of this type need
+/// `#[serde(default, skip_serializing_if = "Patchable::is_absent")]` so an
+/// absent value is left out of the body.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Patchable<T> {
+    /// Not sent; Firecracker keeps the current value
+    #[default]
+    Absent,
+    /// Sent as `null`; Firecracker removes the current value
+    Null,
+    /// Sent as the new value
+    Value(T),
+}
+
+impl<T> Patchable<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Patchable::Absent)
+    }
+
+    /// The new value, if one is set.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Patchable::Value(value) => Some(value),
+            Patchable::Absent | Patchable::Null => None,
+        }
+    }
+}
+
+impl<T> From<T> for Patchable<T> {
+    fn from(value: T) -> Self {
+        Patchable::Value(value)
+    }
+}
+
+impl<T: Serialize> Serialize for Patchable<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Patchable::Absent | Patchable::Null => serializer.serialize_none(),
+            Patchable::Value(value) => value.serialize(serializer),
+        }
+    }
+}
+
+// Only called for fields that are present, so a missing field falls back to
+// `Absent` through `#[serde(default)]`
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patchable<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Patchable::Value(value),
+            None => Patchable::Null,
+        })
+    }
+}
+
+/// Reads a GET response body into `T`. Models shared between requests and
+/// responses reject unknown fields under `strict-serde`, but fields a newer
+/// Firecracker adds to its responses are dropped rather than refused.
+pub(crate) fn from_response_json<T: de::DeserializeOwned>(
+    mut value: serde_json::Value,
+) -> Result<T, serde_json::Error> {
+    if cfg!(feature = "strict-serde") {
+        if let (serde_json::Value::Object(map), Some(fields)) = (&mut value, struct_fields::<T>()) {
+            map.retain(|key, _| fields.contains(&key.as_str()));
+        }
+    }
+    serde_json::from_value(value)
+}
+
+/// Decodes a GET response body with [`from_response_json`], keeping the body
+/// in the error when it does not fit `T`, e.g. because a field every
+/// supported Firecracker sends is missing.
+pub(crate) fn decode_response<T: de::DeserializeOwned>(
+    body: String,
+) -> Result<T, crate::FirecrackerError> {
+    match serde_json::from_str(&body).and_then(from_response_json) {
+        Ok(value) => Ok(value),
+        Err(err) => Err(crate::FirecrackerError::Decode {
+            message: err.to_string(),
+            body,
+        }),
+    }
+}
+
+// The field names a derived `Deserialize` impl asks for, captured by a
+// deserializer that records them and then bails out. `None` for anything
+// that is not deserialized as a struct.
+fn struct_fields<T: de::DeserializeOwned>() -> Option<&'static [&'static str]> {
+    struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: de::Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = Some(fields);
+            Err(de::Error::custom("fields captured"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields = None;
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
 }
 
 lazy_static! {
@@ -354,4 +1542,7 @@ lazy_static! {
         r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$"
     )
     .unwrap();
+    static ref MMDS_VERSION_REGEX: Regex = Regex::new(r"^V[12]$").unwrap();
+    static ref LOG_MODULE_REGEX: Regex =
+        Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*(::[A-Za-z_][A-Za-z0-9_]*)*$").unwrap();
 }
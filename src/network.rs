@@ -1,6 +1,64 @@
-use crate::models::NetworkInterface;
+use crate::models::{NetworkInterface, Patchable, RateLimiter};
+use crate::paths;
+use crate::validation::{ValidatePatch, ValidationTarget};
 use crate::FirecrackerError;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+/// The rate limiters of a network interface, the only fields that can
+/// change after boot. Set a limiter to [`Patchable::Null`] to remove it.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct NetworkInterfaceUpdate {
+    pub iface_id: String,
+    #[serde(default, skip_serializing_if = "Patchable::is_absent")]
+    pub rx_rate_limiter: Patchable<RateLimiter>,
+    #[serde(default, skip_serializing_if = "Patchable::is_absent")]
+    pub tx_rate_limiter: Patchable<RateLimiter>,
+}
+
+impl NetworkInterfaceUpdate {
+    /// An update to `iface_id` that changes nothing until fields are set.
+    pub fn new(iface_id: impl Into<String>) -> Self {
+        Self {
+            iface_id: iface_id.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl ValidatePatch for NetworkInterfaceUpdate {
+    fn validate_patch(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.iface_id.is_empty() {
+            errors.add("iface_id", ValidationError::new("length"));
+        }
+        let result = if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        };
+        let result = ValidationErrors::merge(
+            result,
+            "rx_rate_limiter",
+            self.rx_rate_limiter
+                .value()
+                .map_or(Ok(()), Validate::validate),
+        );
+        ValidationErrors::merge(
+            result,
+            "tx_rate_limiter",
+            self.tx_rate_limiter
+                .value()
+                .map_or(Ok(()), Validate::validate),
+        )
+    }
+
+    fn patch_target(&self) -> ValidationTarget<'_> {
+        ValidationTarget::NetworkInterfaceUpdate(self)
+    }
+}
 
 #[async_trait]
 pub trait NetworkInterfaceOperations {
@@ -12,7 +70,7 @@ pub trait NetworkInterfaceOperations {
     async fn patch_network_interface(
         &self,
         iface_id: &str,
-        interface: &NetworkInterface,
+        update: &NetworkInterfaceUpdate,
     ) -> Result<(), FirecrackerError>;
 }
 
@@ -23,34 +81,16 @@ impl NetworkInterfaceOperations for crate::FirecrackerClient {
         iface_id: &str,
         interface: &NetworkInterface,
     ) -> Result<(), FirecrackerError> {
-        let url = self.url(&format!("network-interfaces/{}", iface_id))?;
-        let response = self.client.put(url).json(interface).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        let path = self.device_path(paths::NETWORK_INTERFACES, "iface_id", iface_id)?;
+        self.validated_put(&path, interface).await
     }
 
     async fn patch_network_interface(
         &self,
         iface_id: &str,
-        interface: &NetworkInterface,
+        update: &NetworkInterfaceUpdate,
     ) -> Result<(), FirecrackerError> {
-        let url = self.url(&format!("network-interfaces/{}", iface_id))?;
-        let response = self.client.patch(url).json(interface).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
-
-        Ok(())
+        let path = self.device_path(paths::NETWORK_INTERFACES, "iface_id", iface_id)?;
+        self.validated_patch(&path, update).await
     }
 }
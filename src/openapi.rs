@@ -0,0 +1,492 @@
+//! Checks the models against Firecracker's OpenAPI (swagger) spec, so
+//! fields added upstream don't go unnoticed. Only built with the `openapi`
+//! feature.
+//!
+//! Each model's JSON Schema, as derived for the `schemars` feature, is
+//! compared with the spec definition it implements: field names, which
+//! fields are required, and the values of enums both sides list. Fields the
+//! models keep as plain strings aren't compared for enum values.
+//!
+//! The spec is taken as JSON; `openapi/vendor.sh <tag>` fetches upstream's
+//! `firecracker.yaml`, converts it with `yq -o=json` and records its source
+//! URL and SHA-256. A pinned copy ships as [`BUNDLED_SPEC`], with the
+//! intentional differences in [`BUNDLED_ALLOWLIST`].
+
+use crate::error::FirecrackerError;
+use crate::metrics::Metrics;
+use crate::models::{
+    Balloon, BootSource, CpuTemplate, Drive, EntropyDevice, Logger, MachineConfig, MmdsConfig,
+    NetworkInterface, RateLimiter, TokenBucket, Vsock,
+};
+use crate::snapshot::{MemBackend, SnapshotCreateParams, SnapshotLoadParams};
+use schemars::JsonSchema;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// The definitions of Firecracker 1.7.0's `firecracker.yaml` the models
+/// implement, as JSON.
+pub const BUNDLED_SPEC: &str = include_str!("../openapi/firecracker-v1.7.0.json");
+
+/// The differences from [`BUNDLED_SPEC`] the models make on purpose.
+pub const BUNDLED_ALLOWLIST: &str = include_str!("../openapi/allowlist.txt");
+
+/// Each spec definition the models implement, with the model's schema.
+pub fn model_schemas() -> Vec<(&'static str, Value)> {
+    fn schema<T: JsonSchema>() -> Value {
+        serde_json::to_value(schemars::schema_for!(T)).expect("schemas always serialize")
+    }
+
+    vec![
+        ("Balloon", schema::<Balloon>()),
+        ("BootSource", schema::<BootSource>()),
+        ("CpuTemplate", schema::<CpuTemplate>()),
+        ("Drive", schema::<Drive>()),
+        ("EntropyDevice", schema::<EntropyDevice>()),
+        ("Logger", schema::<Logger>()),
+        ("MachineConfiguration", schema::<MachineConfig>()),
+        ("MemoryBackend", schema::<MemBackend>()),
+        ("Metrics", schema::<Metrics>()),
+        ("MmdsConfig", schema::<MmdsConfig>()),
+        ("NetworkInterface", schema::<NetworkInterface>()),
+        ("RateLimiter", schema::<RateLimiter>()),
+        ("SnapshotCreateParams", schema::<SnapshotCreateParams>()),
+        ("SnapshotLoadParams", schema::<SnapshotLoadParams>()),
+        ("TokenBucket", schema::<TokenBucket>()),
+        ("Vsock", schema::<Vsock>()),
+    ]
+}
+
+/// How a model differs from its spec definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriftKind {
+    /// The spec has no definition of that name
+    UnknownDefinition,
+    /// The spec has a field the model lacks
+    MissingField { required: bool },
+    /// The model has a field the spec lacks
+    ExtraField,
+    /// One side requires the field and the other doesn't
+    Optionality { spec_required: bool },
+    /// Both sides enumerate the field's values, differently
+    Enum {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+}
+
+impl DriftKind {
+    /// The name allowlist entries use for this kind.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DriftKind::UnknownDefinition => "unknown-definition",
+            DriftKind::MissingField { .. } => "missing-field",
+            DriftKind::ExtraField => "extra-field",
+            DriftKind::Optionality { .. } => "optionality",
+            DriftKind::Enum { .. } => "enum",
+        }
+    }
+}
+
+/// One difference between a model and the spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    /// The spec definition's name
+    pub definition: String,
+    /// `None` when the difference is in the definition itself
+    pub field: Option<String>,
+    pub kind: DriftKind,
+    /// The allowlist's reason, when the difference is intentional
+    pub allowed: Option<String>,
+}
+
+impl Drift {
+    /// `Definition.field`, or the definition alone.
+    pub fn path(&self) -> String {
+        match &self.field {
+            Some(field) => format!("{}.{}", self.definition, field),
+            None => self.definition.clone(),
+        }
+    }
+
+    /// Whether requests built from the model can be rejected because of
+    /// it: a field the spec requires is missing, or the definition couldn't
+    /// be compared at all.
+    pub fn is_breaking(&self) -> bool {
+        matches!(
+            self.kind,
+            DriftKind::UnknownDefinition | DriftKind::MissingField { required: true }
+        )
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path();
+        match &self.kind {
+            DriftKind::UnknownDefinition => write!(f, "! {}: not defined in the spec", path)?,
+            DriftKind::MissingField { required } => write!(
+                f,
+                "- {}: {}in the spec, missing from the model",
+                path,
+                if *required { "required " } else { "" }
+            )?,
+            DriftKind::ExtraField => write!(f, "+ {}: in the model, not in the spec", path)?,
+            DriftKind::Optionality { spec_required } => {
+                let (spec, model) = if *spec_required {
+                    ("required", "optional")
+                } else {
+                    ("optional", "required")
+                };
+                write!(
+                    f,
+                    "~ {}: {} in the spec, {} in the model",
+                    path, spec, model
+                )?
+            }
+            DriftKind::Enum { missing, extra } => {
+                write!(f, "~ {}: enum values", path)?;
+                if !missing.is_empty() {
+                    write!(f, " missing from the model: {}", missing.join(", "))?;
+                }
+                if !extra.is_empty() {
+                    if !missing.is_empty() {
+                        write!(f, ";")?;
+                    }
+                    write!(f, " not in the spec: {}", extra.join(", "))?;
+                }
+            }
+        }
+        if let Some(reason) = &self.allowed {
+            write!(f, " (allowed: {})", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// An intentional difference, as listed in an allowlist file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowEntry {
+    /// `Definition.field`, or the definition alone
+    pub path: String,
+    /// A [`DriftKind::name`]
+    pub kind: String,
+    pub reason: String,
+}
+
+/// Differences the models make on purpose. The file format is one
+/// `<Definition>[.<field>] <kind> <reason>` per line; blank lines and lines
+/// starting with `#` are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Allowlist {
+    pub entries: Vec<AllowEntry>,
+}
+
+impl AllowEntry {
+    fn allows(&self, drift: &Drift) -> bool {
+        self.path == drift.path() && self.kind == drift.kind.name()
+    }
+}
+
+impl FromStr for Allowlist {
+    type Err = FirecrackerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const KINDS: &[&str] = &[
+            "unknown-definition",
+            "missing-field",
+            "extra-field",
+            "optionality",
+            "enum",
+        ];
+        let mut entries = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let (Some(path), Some(kind), Some(reason)) = (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(FirecrackerError::Config(format!(
+                    "allowlist line {}: expected `<path> <kind> <reason>`",
+                    i + 1
+                )));
+            };
+            if !KINDS.contains(&kind) {
+                return Err(FirecrackerError::Config(format!(
+                    "allowlist line {}: unknown kind `{}`, expected one of {}",
+                    i + 1,
+                    kind,
+                    KINDS.join(", ")
+                )));
+            }
+            entries.push(AllowEntry {
+                path: path.to_string(),
+                kind: kind.to_string(),
+                reason: reason.trim().to_string(),
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// The result of [`check_drift`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DriftReport {
+    /// The spec's `info.version`
+    pub spec_version: String,
+    /// Every difference, allowed or not, by definition and field
+    pub drifts: Vec<Drift>,
+    /// Allowlist entries that matched no difference
+    pub stale: Vec<AllowEntry>,
+}
+
+impl DriftReport {
+    /// Differences that aren't allowlisted.
+    pub fn unexpected(&self) -> impl Iterator<Item = &Drift> {
+        self.drifts.iter().filter(|drift| drift.allowed.is_none())
+    }
+
+    /// Unexpected differences that can get requests rejected.
+    pub fn breaking(&self) -> impl Iterator<Item = &Drift> {
+        self.unexpected().filter(|drift| drift.is_breaking())
+    }
+
+    /// Whether every difference is allowlisted and every entry is in use.
+    pub fn is_clean(&self) -> bool {
+        self.unexpected().next().is_none() && self.stale.is_empty()
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "models against Firecracker API {}: {} differences, {} unexpected, {} breaking",
+            self.spec_version,
+            self.drifts.len(),
+            self.unexpected().count(),
+            self.breaking().count()
+        )?;
+        for drift in &self.drifts {
+            writeln!(f, "{}", drift)?;
+        }
+        for entry in &self.stale {
+            writeln!(f, "? {} {}: stale allowlist entry", entry.path, entry.kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares every model in [`model_schemas`] with the definitions of the
+/// swagger document `spec`.
+pub fn check_drift(spec: &Value, allowlist: &Allowlist) -> Result<DriftReport, FirecrackerError> {
+    let definitions = spec
+        .get("definitions")
+        .and_then(Value::as_object)
+        .ok_or_else(|| FirecrackerError::Config("the spec has no `definitions`".to_string()))?;
+    let spec_version = spec
+        .pointer("/info/version")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown")
+        .to_string();
+
+    let mut drifts = Vec::new();
+    for (name, model) in model_schemas() {
+        let mut push = |field: Option<&str>, kind: DriftKind| {
+            drifts.push(Drift {
+                definition: name.to_string(),
+                field: field.map(str::to_string),
+                kind,
+                allowed: None,
+            })
+        };
+        let Some(definition) = definitions.get(name) else {
+            push(None, DriftKind::UnknownDefinition);
+            continue;
+        };
+        let spec_shape = Shape::of(definition, spec);
+        let model_shape = Shape::of(&model, &model);
+
+        if let Some(kind) = enum_drift(&spec_shape.values, &model_shape.values) {
+            push(None, kind);
+        }
+        let names: BTreeSet<&String> = spec_shape
+            .properties
+            .keys()
+            .chain(model_shape.properties.keys())
+            .collect();
+        for field in names {
+            let spec_required = spec_shape.required.contains(field);
+            let model_required = model_shape.required.contains(field);
+            match (
+                spec_shape.properties.get(field),
+                model_shape.properties.get(field),
+            ) {
+                (Some(_), None) => push(
+                    Some(field),
+                    DriftKind::MissingField {
+                        required: spec_required,
+                    },
+                ),
+                (None, Some(_)) => push(Some(field), DriftKind::ExtraField),
+                (Some(spec_field), Some(model_field)) => {
+                    if spec_required != model_required {
+                        push(Some(field), DriftKind::Optionality { spec_required });
+                    }
+                    let spec_values = field_values(spec_field, spec);
+                    let model_values = field_values(model_field, &model);
+                    if let Some(kind) = enum_drift(&spec_values, &model_values) {
+                        push(Some(field), kind);
+                    }
+                }
+                (None, None) => unreachable!("the field comes from one of the sides"),
+            }
+        }
+    }
+
+    let stale = allowlist
+        .entries
+        .iter()
+        .filter(|entry| !drifts.iter().any(|drift| entry.allows(drift)))
+        .cloned()
+        .collect();
+    for drift in &mut drifts {
+        drift.allowed = allowlist
+            .entries
+            .iter()
+            .find(|entry| entry.allows(drift))
+            .map(|entry| entry.reason.clone());
+    }
+
+    Ok(DriftReport {
+        spec_version,
+        drifts,
+        stale,
+    })
+}
+
+// The fields of an object schema, or the values of an enum one. A union of
+// objects (how schemars describes `Drive` and `MemBackend`) has the fields
+// of any variant, and requires those every variant requires.
+#[derive(Default)]
+struct Shape<'a> {
+    properties: BTreeMap<String, Vec<&'a Value>>,
+    required: BTreeSet<String>,
+    values: Option<BTreeSet<String>>,
+}
+
+impl<'a> Shape<'a> {
+    fn of(schema: &'a Value, root: &'a Value) -> Self {
+        let schema = resolve(schema, root);
+        let variants = variants(schema);
+        if !variants.is_empty() {
+            let mut shape = Shape::default();
+            let mut required: Option<BTreeSet<String>> = None;
+            for variant in variants {
+                let variant = Shape::of(variant, root);
+                for (name, schemas) in variant.properties {
+                    shape.properties.entry(name).or_default().extend(schemas);
+                }
+                required = Some(match required {
+                    Some(required) => &required & &variant.required,
+                    None => variant.required,
+                });
+            }
+            shape.required = required.unwrap_or_default();
+            shape.values = enum_values(schema, root);
+            return shape;
+        }
+
+        let properties = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter(|(_, field)| !is_forbidden(field))
+            .map(|(name, field)| (name.clone(), vec![field]))
+            .collect();
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect();
+        Shape {
+            properties,
+            required,
+            values: enum_values(schema, root),
+        }
+    }
+}
+
+// The values an enum schema lists, following references and unions; `None`
+// when it lists none
+fn enum_values(schema: &Value, root: &Value) -> Option<BTreeSet<String>> {
+    let mut values: Option<BTreeSet<String>> = None;
+    collect_enum_values(schema, root, &mut values);
+    values
+}
+
+fn collect_enum_values(schema: &Value, root: &Value, values: &mut Option<BTreeSet<String>>) {
+    let schema = resolve(schema, root);
+    if let Some(listed) = schema.get("enum").and_then(Value::as_array) {
+        values
+            .get_or_insert_with(BTreeSet::new)
+            .extend(listed.iter().filter_map(Value::as_str).map(str::to_string));
+    }
+    for variant in variants(schema) {
+        collect_enum_values(variant, root, values);
+    }
+}
+
+// A field's values across the variants that have it
+fn field_values(schemas: &[&Value], root: &Value) -> Option<BTreeSet<String>> {
+    let mut values = None;
+    for schema in schemas {
+        collect_enum_values(schema, root, &mut values);
+    }
+    values
+}
+
+fn enum_drift(
+    spec: &Option<BTreeSet<String>>,
+    model: &Option<BTreeSet<String>>,
+) -> Option<DriftKind> {
+    let (Some(spec), Some(model)) = (spec, model) else {
+        return None;
+    };
+    let missing: Vec<String> = spec.difference(model).cloned().collect();
+    let extra: Vec<String> = model.difference(spec).cloned().collect();
+    (!missing.is_empty() || !extra.is_empty()).then_some(DriftKind::Enum { missing, extra })
+}
+
+fn resolve<'a>(schema: &'a Value, root: &'a Value) -> &'a Value {
+    match schema.get("$ref").and_then(Value::as_str) {
+        Some(reference) => reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+            .map(|target| resolve(target, root))
+            .unwrap_or(schema),
+        None => schema,
+    }
+}
+
+fn variants(schema: &Value) -> Vec<&Value> {
+    ["anyOf", "oneOf"]
+        .iter()
+        .filter_map(|key| schema.get(*key).and_then(Value::as_array))
+        .flatten()
+        .filter(|variant| variant.get("type").and_then(Value::as_str) != Some("null"))
+        .collect()
+}
+
+// schemars marks the fields a variant must leave out as write-only nulls
+fn is_forbidden(field: &Value) -> bool {
+    field.get("writeOnly") == Some(&Value::Bool(true))
+        && field.get("type").and_then(Value::as_str) == Some("null")
+}
@@ -0,0 +1,378 @@
+//! Workflows spanning more than one Firecracker process.
+
+use crate::error::FirecrackerError;
+use crate::jailer::normalize;
+use crate::models::InstanceInfo;
+use crate::snapshot::{
+    restore, NetworkOverride, RestoreOptions, SafeSnapshotOptions, SnapshotCreateParams,
+    SnapshotLoadParams, SnapshotOperations, SnapshotType,
+};
+use crate::FirecrackerClient;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Options for [`clone_vm`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// Where the snapshot files are written; both VMMs must be able to reach
+    /// it under the same path
+    pub snapshot_dir: PathBuf,
+    /// Tap devices for the clone, so it doesn't share the source's
+    pub network_overrides: Vec<NetworkOverride>,
+    /// Resume the source once the snapshot is written; otherwise it stays
+    /// paused
+    pub resume_source: bool,
+    /// Resume the clone once it is loaded; otherwise it stays paused
+    pub resume_target: bool,
+    /// Limit for restoring into the target, including waiting for its API
+    pub restore_timeout: Duration,
+}
+
+impl CloneOptions {
+    pub fn new(snapshot_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            snapshot_dir: snapshot_dir.into(),
+            network_overrides: Vec::new(),
+            resume_source: true,
+            resume_target: true,
+            restore_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What [`clone_vm`] did and how long each part took.
+#[derive(Debug, Clone)]
+pub struct CloneReport {
+    /// The snapshot the clone was loaded from. The files are kept, since
+    /// they are all that is needed to make more clones.
+    pub snapshot_path: PathBuf,
+    pub mem_file_path: PathBuf,
+    /// Pausing the source, writing the snapshot and resuming the source
+    pub snapshot_time: Duration,
+    /// Loading the snapshot into the target and resuming it
+    pub restore_time: Duration,
+    pub total_time: Duration,
+    /// The target as it was after the restore
+    pub target: InstanceInfo,
+    /// Why the source couldn't be resumed after the snapshot was written.
+    /// The clone doesn't depend on it, but the source is still paused.
+    pub source_resume_error: Option<String>,
+}
+
+/// Clones the VM behind `source` into the freshly started Firecracker behind
+/// `target`: pauses the source, takes a Full snapshot into
+/// `options.snapshot_dir`, resumes the source and restores the snapshot into
+/// the target with the given network overrides. The target's instance id is
+/// the one its process was started with.
+///
+/// If the snapshot can't be written, whatever was written of it is removed
+/// before the error is returned. Once it is written the files are kept:
+/// when the source fails to resume the clone goes ahead and the failure is
+/// in [`CloneReport::source_resume_error`], and when the restore fails the
+/// target may already have the memory file mapped.
+pub async fn clone_vm(
+    source: &FirecrackerClient,
+    target: &FirecrackerClient,
+    options: CloneOptions,
+) -> Result<CloneReport, FirecrackerError> {
+    let started = Instant::now();
+    let (snapshot_path, mem_file_path) = snapshot_file_paths(&options.snapshot_dir);
+    let mut params = SnapshotCreateParams::new(
+        snapshot_path.display().to_string(),
+        mem_file_path.display().to_string(),
+    );
+    params.snapshot_type = Some(SnapshotType::Full);
+
+    // Fails before anything is written when the source can't be paused
+    let outcome = source
+        .create_snapshot_safe(
+            &params,
+            SafeSnapshotOptions {
+                leave_paused: !options.resume_source,
+                ..Default::default()
+            },
+        )
+        .await?;
+    if let Err(err) = outcome.snapshot {
+        remove_snapshot_files(&[&snapshot_path, &mem_file_path]);
+        return Err(err);
+    }
+    let source_resume_error = match outcome.resume {
+        Some(Err(err)) => Some(err.to_string()),
+        _ => None,
+    };
+    let snapshot_time = started.elapsed();
+
+    let restoring = Instant::now();
+    let load = SnapshotLoadParams {
+        snapshot_path: params.snapshot_path.clone(),
+        mem_file_path: Some(params.mem_file_path.clone()),
+        ..Default::default()
+    };
+    let target = restore(
+        target,
+        load,
+        RestoreOptions {
+            resume: options.resume_target,
+            network_overrides: options.network_overrides,
+            timeout: options.restore_timeout,
+        },
+    )
+    .await?;
+    Ok(CloneReport {
+        snapshot_path,
+        mem_file_path,
+        snapshot_time,
+        restore_time: restoring.elapsed(),
+        total_time: started.elapsed(),
+        target,
+        source_resume_error,
+    })
+}
+
+// Names that don't collide with earlier clones from the same directory
+fn snapshot_file_paths(snapshot_dir: &Path) -> (PathBuf, PathBuf) {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let stem = format!("clone-{}-{}", std::process::id(), nanos);
+    (
+        snapshot_dir.join(format!("{}.snap", stem)),
+        snapshot_dir.join(format!("{}.mem", stem)),
+    )
+}
+
+// Best effort: the files may never have been written, and a file that can't
+// be removed shouldn't hide the error that made the clone fail
+fn remove_snapshot_files(paths: &[&Path]) {
+    for path in paths {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// What a path in a [`TeardownPlan`] is, which decides when it is removed
+/// and what kind of file it must be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResourceKind {
+    /// The VMM's API socket
+    ApiSocket,
+    /// A vsock device's `uds_path` or one of its per-port listeners
+    VsockSocket,
+    /// A logger or metrics FIFO. Plain log files are never removed.
+    LogFifo,
+    /// A snapshot state or memory file
+    SnapshotFile,
+    /// A directory created for the VM, removed only once empty
+    Directory,
+}
+
+/// Files and directories a VM created, collected as they are created and
+/// removed together by [`teardown`]. Only paths under `root` are ever
+/// removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeardownPlan {
+    root: PathBuf,
+    items: Vec<(ResourceKind, PathBuf)>,
+}
+
+impl TeardownPlan {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn add(&mut self, kind: ResourceKind, path: impl Into<PathBuf>) -> &mut Self {
+        self.items.push((kind, path.into()));
+        self
+    }
+
+    /// Adds the items of `other`; its root is ignored.
+    pub fn extend(&mut self, other: TeardownPlan) -> &mut Self {
+        self.items.extend(other.items);
+        self
+    }
+
+    pub fn items(&self) -> &[(ResourceKind, PathBuf)] {
+        &self.items
+    }
+
+    // Sockets first, so nothing can connect to a VM that is half gone, and
+    // directories last, once they are empty. Within a kind the most recently
+    // added goes first, which puts nested directories before their parents.
+    // A path added more than once is removed once, where it was last added.
+    fn ordered(&self) -> Vec<(ResourceKind, PathBuf)> {
+        let mut seen = HashSet::new();
+        let mut items: Vec<_> = self
+            .items
+            .iter()
+            .rev()
+            .filter(|(_, path)| seen.insert(path))
+            .cloned()
+            .collect();
+        items.sort_by_key(|(kind, _)| *kind);
+        items
+    }
+}
+
+/// The outcome of [`teardown`].
+#[derive(Debug, Default)]
+pub struct TeardownReport {
+    pub removed: Vec<PathBuf>,
+    /// Paths that were already gone
+    pub missing: Vec<PathBuf>,
+    pub failures: Vec<TeardownFailure>,
+}
+
+impl TeardownReport {
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct TeardownFailure {
+    pub kind: ResourceKind,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Removes everything in `plan`, sockets first and directories last. A path
+/// that can't be removed is reported and the rest are still tried. Paths
+/// outside the plan's root, also through a symlinked directory, and paths
+/// that aren't the kind of file they were recorded as are left alone and
+/// reported as failures.
+pub fn teardown(plan: TeardownPlan) -> TeardownReport {
+    let mut report = TeardownReport::default();
+    let root = normalize(&plan.root);
+    let canonical_root = std::fs::canonicalize(&root).unwrap_or_else(|_| root.clone());
+
+    for (kind, path) in plan.ordered() {
+        let fail = |reason: String| TeardownFailure {
+            kind,
+            path: path.clone(),
+            reason,
+        };
+        if !is_confined(&path, &root, &canonical_root) {
+            report.failures.push(fail(format!(
+                "outside the teardown root {}",
+                plan.root.display()
+            )));
+            continue;
+        }
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                report.missing.push(path);
+                continue;
+            }
+            Err(e) => {
+                report.failures.push(fail(e.to_string()));
+                continue;
+            }
+        };
+        if !kind_matches(kind, &metadata.file_type()) {
+            report
+                .failures
+                .push(fail(format!("not a {}", kind_description(kind))));
+            continue;
+        }
+        let removed = match kind {
+            ResourceKind::Directory => std::fs::remove_dir(&path),
+            _ => std::fs::remove_file(&path),
+        };
+        match removed {
+            Ok(()) => report.removed.push(path),
+            Err(e) => report.failures.push(fail(e.to_string())),
+        }
+    }
+    report
+}
+
+/// Runs [`teardown`] when dropped, including while unwinding from a panic,
+/// unless it was disarmed or already run.
+#[derive(Debug)]
+pub struct TeardownGuard {
+    plan: Option<TeardownPlan>,
+}
+
+impl TeardownGuard {
+    pub fn new(plan: TeardownPlan) -> Self {
+        Self { plan: Some(plan) }
+    }
+
+    /// The plan, to keep adding resources as they are created.
+    pub fn plan_mut(&mut self) -> &mut TeardownPlan {
+        self.plan.as_mut().expect("teardown guard already used")
+    }
+
+    /// Tears down now and returns the report.
+    pub fn run(mut self) -> TeardownReport {
+        teardown(self.plan.take().expect("teardown guard already used"))
+    }
+
+    /// Keeps everything and hands back the plan.
+    pub fn disarm(mut self) -> TeardownPlan {
+        self.plan.take().expect("teardown guard already used")
+    }
+}
+
+impl Drop for TeardownGuard {
+    fn drop(&mut self) {
+        if let Some(plan) = self.plan.take() {
+            teardown(plan);
+        }
+    }
+}
+
+// Lexically under the root, and still under it once the symlinks in the
+// parent directory are resolved
+fn is_confined(path: &Path, root: &Path, canonical_root: &Path) -> bool {
+    let path = normalize(path);
+    if !path.is_absolute() || path == root || !path.starts_with(root) {
+        return false;
+    }
+    match (path.parent().map(std::fs::canonicalize), path.file_name()) {
+        (Some(Ok(parent)), Some(name)) => parent.join(name).starts_with(canonical_root),
+        // A parent that doesn't exist can't hold the path either
+        _ => true,
+    }
+}
+
+#[cfg(unix)]
+fn kind_matches(kind: ResourceKind, file_type: &std::fs::FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    match kind {
+        ResourceKind::ApiSocket | ResourceKind::VsockSocket => file_type.is_socket(),
+        ResourceKind::LogFifo => file_type.is_fifo(),
+        ResourceKind::SnapshotFile => file_type.is_file(),
+        ResourceKind::Directory => file_type.is_dir(),
+    }
+}
+
+#[cfg(not(unix))]
+fn kind_matches(kind: ResourceKind, file_type: &std::fs::FileType) -> bool {
+    match kind {
+        ResourceKind::Directory => file_type.is_dir(),
+        // FIFOs are Unix only
+        ResourceKind::LogFifo => false,
+        _ => !file_type.is_dir(),
+    }
+}
+
+fn kind_description(kind: ResourceKind) -> &'static str {
+    match kind {
+        ResourceKind::ApiSocket | ResourceKind::VsockSocket => "socket",
+        ResourceKind::LogFifo => "FIFO",
+        ResourceKind::SnapshotFile => "regular file",
+        ResourceKind::Directory => "directory",
+    }
+}
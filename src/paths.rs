@@ -0,0 +1,59 @@
+//! The API's endpoint paths, relative to the client's base URL: lowercase,
+//! without leading or trailing slashes, as Firecracker routes them. Every
+//! request the client sends takes its path from here, so a proxy in front
+//! of the socket that is strict about either never sees a variant.
+
+/// `GET /`, the instance information
+pub const INSTANCE: &str = "";
+pub const VERSION: &str = "version";
+pub const ACTIONS: &str = "actions";
+pub const BOOT_SOURCE: &str = "boot-source";
+pub const MACHINE_CONFIG: &str = "machine-config";
+pub const CPU_CONFIG: &str = "cpu-config";
+/// The collection [`drive`] paths are under
+pub const DRIVES: &str = "drives";
+/// The collection [`network_interface`] paths are under
+pub const NETWORK_INTERFACES: &str = "network-interfaces";
+pub const MMDS: &str = "mmds";
+pub const MMDS_CONFIG: &str = "mmds/config";
+pub const BALLOON: &str = "balloon";
+pub const BALLOON_STATISTICS: &str = "balloon/statistics";
+pub const VM: &str = "vm";
+pub const VM_CONFIG: &str = "vm/config";
+pub const VSOCK: &str = "vsock";
+pub const ENTROPY: &str = "entropy";
+pub const LOGGER: &str = "logger";
+pub const METRICS: &str = "metrics";
+pub const SNAPSHOT_CREATE: &str = "snapshot/create";
+pub const SNAPSHOT_LOAD: &str = "snapshot/load";
+
+/// The path of drive `drive_id`, e.g. `drives/rootfs`.
+pub fn drive(drive_id: &str) -> String {
+    device(DRIVES, drive_id)
+}
+
+/// The path of network interface `iface_id`, e.g. `network-interfaces/eth0`.
+pub fn network_interface(iface_id: &str) -> String {
+    device(NETWORK_INTERFACES, iface_id)
+}
+
+// The path of device `id` under `collection`, with the id percent-encoded
+// into a single segment
+pub(crate) fn device(collection: &str, id: &str) -> String {
+    format!("{}/{}", collection, encode_segment(id))
+}
+
+// `segment` with every byte but the RFC 3986 unreserved characters
+// percent-encoded, so it can't end the path or add segments to it
+pub(crate) fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,314 @@
+//! A pool of paused microVMs restored from a snapshot ahead of time, so a
+//! VM can be handed out in the time it takes to resume it.
+
+use crate::error::FirecrackerError;
+use crate::snapshot::{restore, NetworkOverride, RestoreOptions, SnapshotChain};
+use crate::vm::VmOperations;
+use crate::FirecrackerClient;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+
+/// Starts the VMM processes the pool restores into.
+#[async_trait]
+pub trait VmFactory: Send + Sync + 'static {
+    /// Whatever has to live as long as the VM, e.g. the
+    /// `launcher::FirecrackerProcess`. Dropping it should stop the VMM.
+    type Handle: Send + Sync + 'static;
+
+    /// Starts a fresh VMM and returns a client connected to it.
+    async fn launch(&self) -> Result<(Self::Handle, FirecrackerClient), FirecrackerError>;
+
+    /// The host devices the VM behind `handle` gets instead of the ones
+    /// recorded in the snapshot, e.g. a tap device of its own for every VM.
+    /// None by default.
+    fn network_overrides(&self, handle: &Self::Handle) -> Vec<NetworkOverride> {
+        let _ = handle;
+        Vec::new()
+    }
+}
+
+/// Options for [`WarmPool::start`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    /// How many paused VMs to keep ready
+    pub size: usize,
+    /// Limit for restoring one VM, including waiting for its API
+    pub restore_timeout: Duration,
+    /// Wait after the first failed restore; doubles with every further
+    /// failure in a row
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            restore_timeout: Duration::from_secs(30),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Counters of a [`WarmPool`], from [`WarmPool::metrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// Paused VMs ready to be checked out
+    pub ready: usize,
+    /// Restores in progress
+    pub restoring: usize,
+    pub restores_succeeded: u64,
+    pub restores_failed: u64,
+    /// Failed restores since the last successful one
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub checkouts: u64,
+    pub recycled: u64,
+    pub discarded: u64,
+}
+
+/// A VM from the pool, running once checked out.
+pub struct PooledVm<H> {
+    client: FirecrackerClient,
+    handle: H,
+}
+
+impl<H> PooledVm<H> {
+    pub fn client(&self) -> &FirecrackerClient {
+        &self.client
+    }
+
+    pub fn handle(&self) -> &H {
+        &self.handle
+    }
+
+    /// Takes the VM out of the pool's hands for good.
+    pub fn into_parts(self) -> (H, FirecrackerClient) {
+        (self.handle, self.client)
+    }
+}
+
+struct Inner<F: VmFactory> {
+    factory: F,
+    chain: SnapshotChain,
+    options: PoolOptions,
+    state: Mutex<PoolState<F::Handle>>,
+    // Signalled when a VM leaves the ready set
+    demand_changed: Notify,
+}
+
+struct PoolState<H> {
+    ready: VecDeque<PooledVm<H>>,
+    // Checkouts waiting for the next restore, oldest first
+    waiters: VecDeque<oneshot::Sender<Result<PooledVm<H>, FirecrackerError>>>,
+    metrics: PoolMetrics,
+}
+
+impl<H> PoolState<H> {
+    // Hands a restored or recycled VM to the oldest waiting checkout, or
+    // else keeps it ready
+    fn offer(&mut self, vm: PooledVm<H>) {
+        let mut vm = Ok(vm);
+        while let Some(waiter) = self.waiters.pop_front() {
+            match waiter.send(vm) {
+                Ok(()) => return,
+                // That checkout was given up on
+                Err(unsent) => vm = unsent,
+            }
+        }
+        if let Ok(vm) = vm {
+            self.ready.push_back(vm);
+        }
+    }
+
+    // Fails the oldest waiting checkout with a failed restore's error
+    fn fail_waiter(&mut self, mut err: FirecrackerError) {
+        while let Some(waiter) = self.waiters.pop_front() {
+            match waiter.send(Err(err)) {
+                Err(Err(unsent)) => err = unsent,
+                _ => return,
+            }
+        }
+    }
+}
+
+/// Keeps `size` paused VMs restored from the latest snapshot of a chain and
+/// restores replacements in the background as VMs are checked out or
+/// discarded. Restores run one at a time; after a failure the next attempt
+/// waits with exponential backoff, and the oldest checkout waiting for a VM
+/// fails with the restore's error. Dropping the pool stops the background
+/// task and drops the ready VMs.
+pub struct WarmPool<F: VmFactory> {
+    inner: Arc<Inner<F>>,
+    replenisher: JoinHandle<()>,
+}
+
+impl<F: VmFactory> WarmPool<F> {
+    /// Creates the pool and starts filling it. Must be called from within a
+    /// Tokio runtime.
+    pub fn start(factory: F, chain: SnapshotChain, options: PoolOptions) -> Self {
+        let inner = Arc::new(Inner {
+            factory,
+            chain,
+            options,
+            state: Mutex::new(PoolState {
+                ready: VecDeque::new(),
+                waiters: VecDeque::new(),
+                metrics: PoolMetrics::default(),
+            }),
+            demand_changed: Notify::new(),
+        });
+        let replenisher = tokio::spawn(replenish(inner.clone()));
+        Self { inner, replenisher }
+    }
+
+    /// Takes a ready VM, waiting for the next restore if the pool is empty,
+    /// and resumes it. Fails with the restore's error if that restore fails,
+    /// and right away with `Config` for a pool of size 0. A VM that fails to
+    /// resume is discarded and the error returned.
+    pub async fn checkout(&self) -> Result<PooledVm<F::Handle>, FirecrackerError> {
+        if self.inner.options.size == 0 {
+            return Err(FirecrackerError::Config(
+                "a warm pool of size 0 has no VMs to check out".to_string(),
+            ));
+        }
+        let waiting = {
+            let mut state = self.inner.state.lock().unwrap();
+            match state.ready.pop_front() {
+                Some(vm) => Ok(vm),
+                None => {
+                    let (sender, receiver) = oneshot::channel();
+                    state.waiters.push_back(sender);
+                    Err(receiver)
+                }
+            }
+        };
+        let vm = match waiting {
+            Ok(vm) => vm,
+            Err(receiver) => receiver
+                .await
+                .map_err(|_| FirecrackerError::Internal("the warm pool stopped".to_string()))??,
+        };
+        self.inner.demand_changed.notify_one();
+
+        let resumed = vm.client.resume_vm().await;
+        let mut state = self.inner.state.lock().unwrap();
+        match resumed {
+            Ok(()) => {
+                state.metrics.checkouts += 1;
+                Ok(vm)
+            }
+            Err(err) => {
+                state.metrics.discarded += 1;
+                Err(err)
+            }
+        }
+    }
+
+    /// Pauses a checked-out VM and puts it back in the pool, for guests that
+    /// can safely serve another caller. It is discarded instead if pausing
+    /// fails or the pool is already full.
+    pub async fn recycle(&self, vm: PooledVm<F::Handle>) {
+        let paused = vm.client.pause_vm().await;
+        let mut state = self.inner.state.lock().unwrap();
+        if paused.is_ok() && state.ready.len() < self.inner.options.size {
+            state.offer(vm);
+            state.metrics.recycled += 1;
+        } else {
+            state.metrics.discarded += 1;
+        }
+    }
+
+    /// Drops a checked-out VM, stopping it through its handle.
+    pub fn discard(&self, vm: PooledVm<F::Handle>) {
+        drop(vm);
+        self.inner.state.lock().unwrap().metrics.discarded += 1;
+    }
+
+    pub fn factory(&self) -> &F {
+        &self.inner.factory
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let state = self.inner.state.lock().unwrap();
+        PoolMetrics {
+            ready: state.ready.len(),
+            ..state.metrics.clone()
+        }
+    }
+}
+
+impl<F: VmFactory> Drop for WarmPool<F> {
+    fn drop(&mut self) {
+        self.replenisher.abort();
+    }
+}
+
+async fn replenish<F: VmFactory>(inner: Arc<Inner<F>>) {
+    let mut backoff = inner.options.initial_backoff;
+    loop {
+        let notified = inner.demand_changed.notified();
+        let wanted = {
+            let mut state = inner.state.lock().unwrap();
+            let wanted = state.ready.len() < inner.options.size;
+            if wanted {
+                state.metrics.restoring += 1;
+            }
+            wanted
+        };
+        if !wanted {
+            notified.await;
+            continue;
+        }
+
+        let restored = restore_one(&inner).await;
+        let succeeded = {
+            let mut state = inner.state.lock().unwrap();
+            state.metrics.restoring -= 1;
+            match restored {
+                Ok(vm) => {
+                    state.offer(vm);
+                    state.metrics.restores_succeeded += 1;
+                    state.metrics.consecutive_failures = 0;
+                    true
+                }
+                Err(err) => {
+                    state.metrics.restores_failed += 1;
+                    state.metrics.consecutive_failures += 1;
+                    state.metrics.last_error = Some(err.to_string());
+                    state.fail_waiter(err);
+                    false
+                }
+            }
+        };
+        if succeeded {
+            backoff = inner.options.initial_backoff;
+        } else {
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(inner.options.max_backoff);
+        }
+    }
+}
+
+// The VMM is stopped through its handle if the restore fails
+async fn restore_one<F: VmFactory>(
+    inner: &Inner<F>,
+) -> Result<PooledVm<F::Handle>, FirecrackerError> {
+    let (handle, client) = inner.factory.launch().await?;
+    restore(
+        &client,
+        inner.chain.clone(),
+        RestoreOptions {
+            resume: false,
+            network_overrides: inner.factory.network_overrides(&handle),
+            timeout: inner.options.restore_timeout,
+        },
+    )
+    .await?;
+    Ok(PooledVm { client, handle })
+}
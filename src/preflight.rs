@@ -0,0 +1,164 @@
+//! Whether a VM is ready for `InstanceStart`: it needs a kernel and a root
+//! drive (or an initrd). The machine config isn't checked, since Firecracker
+//! falls back on 1 vCPU and 128 MiB. [`FirecrackerClient::preflight_check`]
+//! answers from `GET /vm/config`, or, when the VMM doesn't serve it, from
+//! what the client itself has sent.
+//!
+//! With [`FirecrackerClient::with_preflight_check`] every `InstanceStart`
+//! the client sends is checked first and fails with
+//! [`InvalidConfig`](FirecrackerError::InvalidConfig) instead of booting a
+//! VM that can't.
+
+use crate::models::{Balloon, BootSource, Drive, FullVmConfiguration, MachineConfig};
+use crate::paths;
+use crate::validation::{validate_vm_config, ConfigWarning, Finding, Severity, ValidationReport};
+use crate::vm::VmOperations;
+use crate::{FirecrackerClient, FirecrackerError};
+use std::future::Future;
+
+/// Where [`FirecrackerClient::preflight_check`] took the configuration from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightSource {
+    /// `GET /vm/config`
+    VmConfig,
+    /// The sections this client sent, used when `GET /vm/config` fails
+    SentByClient,
+}
+
+/// What [`FirecrackerClient::preflight_check`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightReport {
+    pub source: PreflightSource,
+    /// Errors for the missing sections; empty when the VM can boot
+    pub report: ValidationReport,
+}
+
+impl PreflightReport {
+    pub fn is_bootable(&self) -> bool {
+        !self.report.has_errors()
+    }
+
+    /// Fails with [`InvalidConfig`](FirecrackerError::InvalidConfig) unless
+    /// the VM can boot.
+    pub fn into_result(self) -> Result<Self, FirecrackerError> {
+        if self.is_bootable() {
+            Ok(self)
+        } else {
+            Err(FirecrackerError::InvalidConfig(self.report))
+        }
+    }
+}
+
+/// Checks that `config` has what booting needs.
+///
+/// Rules:
+/// - `boot_source_missing`: no kernel to boot. `GET /vm/config` reports a
+///   boot source with an empty `kernel_image_path` until one is set, which
+///   counts as none.
+/// - `root_drive_missing`: no drive is the root device and no initrd is set
+pub fn check_bootable(config: &FullVmConfiguration) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut missing = |rule, path: &str, message: &str| {
+        report.findings.push(Finding {
+            severity: Severity::Error,
+            rule,
+            path: path.to_string(),
+            message: message.to_string(),
+        })
+    };
+
+    let boot_source = config
+        .boot_source
+        .as_ref()
+        .filter(|b| !b.kernel_image_path.is_empty());
+    if boot_source.is_none() {
+        missing(
+            "boot_source_missing",
+            "boot_source",
+            "No boot source is set",
+        );
+    }
+    let has_initrd = boot_source.is_some_and(|b| b.initrd_path.is_some());
+    if !has_initrd && !config.drives.iter().any(|d| d.is_root_device) {
+        missing(
+            "root_drive_missing",
+            "drives",
+            "No drive is marked as the root device and no initrd is set",
+        );
+    }
+    report
+}
+
+impl FirecrackerClient {
+    /// Checks whether the VM can be started, from `GET /vm/config`. A VMM
+    /// that answers it with an error is judged by the boot source and
+    /// drives this client has sent instead.
+    pub async fn preflight_check(&self) -> Result<PreflightReport, FirecrackerError> {
+        let (source, config) = match self.get_vm_config().await {
+            Ok(config) => (PreflightSource::VmConfig, config),
+            Err(FirecrackerError::Api { .. }) => (
+                PreflightSource::SentByClient,
+                self.sent_config.lock().unwrap().clone(),
+            ),
+            Err(err) => return Err(err),
+        };
+        Ok(PreflightReport {
+            source,
+            report: check_bootable(&config),
+        })
+    }
+
+    // Keeps the sections `preflight_check` needs from a successful `PUT` to
+    // `path`
+    pub(crate) fn record_sent(&self, path: &str, body: &[u8]) {
+        let path = path.trim_matches('/');
+        let mut sent = self.sent_config.lock().unwrap();
+        if path == paths::BOOT_SOURCE {
+            if let Ok(boot_source) = serde_json::from_slice::<BootSource>(body) {
+                sent.boot_source = Some(boot_source);
+            }
+        } else if path == paths::MACHINE_CONFIG {
+            if let Ok(machine_config) = serde_json::from_slice::<MachineConfig>(body) {
+                sent.machine_config = Some(machine_config);
+            }
+        } else if path == paths::BALLOON {
+            if let Ok(balloon) = serde_json::from_slice::<Balloon>(body) {
+                sent.balloon = Some(balloon);
+            }
+        } else if path.starts_with(&format!("{}/", paths::DRIVES)) {
+            if let Ok(drive) = serde_json::from_slice::<Drive>(body) {
+                sent.drives.retain(|d| d.drive_id != drive.drive_id);
+                sent.drives.push(drive);
+            }
+        }
+    }
+
+    // Awaits `put` and warns about the lint warnings the sections this
+    // client has sent gained with it, so a `put_*` call that contradicts an
+    // earlier one is reported once, by the later call
+    pub(crate) async fn put_linted(
+        &self,
+        put: impl Future<Output = Result<(), FirecrackerError>>,
+    ) -> Result<(), FirecrackerError> {
+        let before = self.sent_config_warnings();
+        put.await?;
+        for (rule, context, message) in self.sent_config_warnings() {
+            if !before.iter().any(|(r, _, m)| *r == rule && *m == message) {
+                self.warn(ConfigWarning {
+                    rule,
+                    message,
+                    context,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn sent_config_warnings(&self) -> Vec<(&'static str, String, String)> {
+        let sent = self.sent_config.lock().unwrap().clone();
+        validate_vm_config(&sent)
+            .warnings()
+            .map(|f| (f.rule, f.path.clone(), f.message.clone()))
+            .collect()
+    }
+}
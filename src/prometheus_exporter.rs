@@ -0,0 +1,232 @@
+//! Exposes [`FirecrackerMetrics`] snapshots to Prometheus.
+//!
+//! Every numeric field becomes a `firecracker_<group>_<field>` series. Fields
+//! holding times or timestamps are gauges; everything else is a counter with
+//! a `_total` suffix. All series carry a `vm_id` label. Per-device groups are
+//! exported as `firecracker_block_device_*` and `firecracker_net_device_*`
+//! with a `drive` or `iface` label, so they never share a family with the
+//! aggregate `firecracker_block_*` and `firecracker_net_*` series.
+
+use crate::metrics::FirecrackerMetrics;
+use crate::FirecrackerError;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::{Counter, Gauge, LabelPair, Metric, MetricFamily, MetricType};
+use prometheus::Registry;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// A Prometheus collector that reports the most recent metrics snapshot of
+/// one microVM.
+#[derive(Clone)]
+pub struct FirecrackerCollector {
+    vm_id: String,
+    latest: Arc<Mutex<Option<FirecrackerMetrics>>>,
+    desc: Desc,
+}
+
+impl FirecrackerCollector {
+    pub fn new(vm_id: impl Into<String>) -> Result<Self, FirecrackerError> {
+        let vm_id = vm_id.into();
+        let desc = Desc::new(
+            "firecracker_metrics_up".to_string(),
+            "Whether a metrics snapshot has been received for the microVM".to_string(),
+            vec![],
+            [("vm_id".to_string(), vm_id.clone())].into_iter().collect(),
+        )
+        .map_err(|e| FirecrackerError::Config(e.to_string()))?;
+
+        Ok(Self {
+            vm_id,
+            latest: Arc::new(Mutex::new(None)),
+            desc,
+        })
+    }
+
+    /// Replaces the snapshot reported on the next scrape.
+    pub fn update(&self, metrics: FirecrackerMetrics) {
+        *self.latest.lock().unwrap() = Some(metrics);
+    }
+}
+
+impl Collector for FirecrackerCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let latest = self.latest.lock().unwrap();
+        let mut families = vec![family(
+            "firecracker_metrics_up",
+            MetricType::GAUGE,
+            vec![sample(
+                &[("vm_id", &self.vm_id)],
+                MetricType::GAUGE,
+                latest.is_some() as u64 as f64,
+            )],
+        )];
+        if let Some(metrics) = latest.as_ref() {
+            families.extend(metric_families(metrics, &self.vm_id));
+        }
+        families
+    }
+}
+
+/// Converts a snapshot into Prometheus metric families.
+pub fn metric_families(metrics: &FirecrackerMetrics, vm_id: &str) -> Vec<MetricFamily> {
+    let mut series: BTreeMap<String, (MetricType, Vec<Metric>)> = BTreeMap::new();
+    let vm_labels = [("vm_id", vm_id)];
+
+    let document = serde_json::to_value(metrics).unwrap_or(Value::Null);
+    let Value::Object(groups) = document else {
+        return Vec::new();
+    };
+
+    for (group, value) in &groups {
+        if group.starts_with("block_") || group.starts_with("net_") {
+            continue;
+        }
+        match value {
+            Value::Number(n) => add_series(&mut series, "", group, n, &vm_labels),
+            Value::Object(fields) => {
+                for (field, value) in fields {
+                    if let Value::Number(n) = value {
+                        add_series(&mut series, group, field, n, &vm_labels);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let drives = metrics.block_devices();
+    for (drive, device) in &drives {
+        add_device(&mut series, "block_device", ("drive", drive), device, vm_id);
+    }
+    let ifaces = metrics.net_devices();
+    for (iface, device) in &ifaces {
+        add_device(&mut series, "net_device", ("iface", iface), device, vm_id);
+    }
+
+    series
+        .into_iter()
+        .map(|(name, (kind, samples))| family(&name, kind, samples))
+        .collect()
+}
+
+/// Registers a collector for `vm_id` and keeps it updated from the metrics
+/// FIFO or file at `path` until the stream ends. Documents that fail to parse
+/// are skipped.
+#[cfg(unix)]
+pub fn export_metrics(
+    path: impl AsRef<std::path::Path>,
+    vm_id: impl Into<String>,
+    registry: &Registry,
+) -> Result<tokio::task::JoinHandle<()>, FirecrackerError> {
+    use futures_util::StreamExt;
+
+    let collector = FirecrackerCollector::new(vm_id)?;
+    registry
+        .register(Box::new(collector.clone()))
+        .map_err(|e| FirecrackerError::Config(e.to_string()))?;
+
+    let stream = crate::metrics::stream_metrics(path.as_ref().to_path_buf());
+    Ok(tokio::spawn(async move {
+        futures_util::pin_mut!(stream);
+        while let Some(item) = stream.next().await {
+            if let Ok(metrics) = item {
+                collector.update(metrics);
+            }
+        }
+    }))
+}
+
+fn add_device<T: serde::Serialize>(
+    series: &mut BTreeMap<String, (MetricType, Vec<Metric>)>,
+    group: &str,
+    device_label: (&str, &str),
+    device: &T,
+    vm_id: &str,
+) {
+    let labels = [("vm_id", vm_id), device_label];
+    if let Ok(Value::Object(fields)) = serde_json::to_value(device) {
+        for (field, value) in &fields {
+            if let Value::Number(n) = value {
+                add_series(series, group, field, n, &labels);
+            }
+        }
+    }
+}
+
+fn add_series(
+    series: &mut BTreeMap<String, (MetricType, Vec<Metric>)>,
+    group: &str,
+    field: &str,
+    value: &serde_json::Number,
+    labels: &[(&str, &str)],
+) {
+    let is_gauge = field.ends_with("_us") || field.ends_with("_ms");
+    let kind = if is_gauge {
+        MetricType::GAUGE
+    } else {
+        MetricType::COUNTER
+    };
+
+    let mut name = String::from("firecracker");
+    for part in [group, field] {
+        if !part.is_empty() {
+            name.push('_');
+            name.push_str(&sanitize(part));
+        }
+    }
+    if !is_gauge {
+        name.push_str("_total");
+    }
+
+    let value = value.as_f64().unwrap_or_default();
+    series
+        .entry(name)
+        .or_insert_with(|| (kind, Vec::new()))
+        .1
+        .push(sample(labels, kind, value));
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn sample(labels: &[(&str, &str)], kind: MetricType, value: f64) -> Metric {
+    let pairs: Vec<LabelPair> = labels
+        .iter()
+        .map(|(name, value)| {
+            let mut pair = LabelPair::default();
+            pair.set_name(name.to_string());
+            pair.set_value(value.to_string());
+            pair
+        })
+        .collect();
+
+    let mut metric = Metric::default();
+    metric.set_label(pairs);
+    if kind == MetricType::GAUGE {
+        let mut gauge = Gauge::default();
+        gauge.set_value(value);
+        metric.set_gauge(gauge);
+    } else {
+        let mut counter = Counter::default();
+        counter.set_value(value);
+        metric.set_counter(counter);
+    }
+    metric
+}
+
+fn family(name: &str, kind: MetricType, samples: Vec<Metric>) -> MetricFamily {
+    let mut family = MetricFamily::default();
+    family.set_name(name.to_string());
+    family.set_help(format!("Firecracker metric {}", name));
+    family.set_field_type(kind);
+    family.set_metric(samples);
+    family
+}
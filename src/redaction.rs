@@ -0,0 +1,66 @@
+//! Which API paths carry bodies too sensitive to show, such as MMDS data,
+//! which often holds credentials for the guest. Wherever the client
+//! surfaces a body of such a path, in [`Api`](crate::FirecrackerError::Api)
+//! error messages, the audit journal or planned requests, it shows only its
+//! length. Set the policy with
+//! [`FirecrackerClient::with_redaction`](crate::FirecrackerClient::with_redaction).
+
+use crate::paths;
+
+/// The paths whose bodies are redacted. A path ending in `/` covers the
+/// paths under it, e.g. `drives/`; any other path only itself, so `mmds`
+/// leaves `mmds/config` readable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    pub paths: Vec<String>,
+}
+
+impl Default for Redaction {
+    /// Redacts MMDS data.
+    fn default() -> Self {
+        Self {
+            paths: vec![paths::MMDS.to_string()],
+        }
+    }
+}
+
+impl Redaction {
+    /// Redacts nothing, e.g. for debugging against a test VMM.
+    pub fn none() -> Self {
+        Self { paths: Vec::new() }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    pub fn is_sensitive(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        self.paths.iter().any(|rule| path_matches(rule, path))
+    }
+
+    /// `body` as it may be shown for a request to or response from `path`.
+    pub fn apply(&self, path: &str, body: &str) -> String {
+        if self.is_sensitive(path) {
+            redacted(body.as_bytes())
+        } else {
+            body.to_string()
+        }
+    }
+}
+
+/// What is shown instead of a redacted body: only its length, since even an
+/// unkeyed digest lets a guessable secret be checked against it.
+pub fn redacted(body: &[u8]) -> String {
+    format!("<redacted: {} bytes>", body.len())
+}
+
+// Whether `rule` covers `path`, both without leading or trailing slashes
+// except for the trailing one marking a directory rule
+pub(crate) fn path_matches(rule: &str, path: &str) -> bool {
+    match rule.strip_suffix('/') {
+        Some(dir) => path.starts_with(rule) && path.len() > dir.len() + 1,
+        None => path == rule,
+    }
+}
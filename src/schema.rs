@@ -0,0 +1,18 @@
+//! JSON Schema for the request models, for checking VM specs before they
+//! reach the client. Only built with the `schemars` feature.
+
+use crate::models::FullVmConfiguration;
+use schemars::schema::RootSchema;
+
+/// The schema of a [`FullVmConfiguration`] as the client serializes it,
+/// with every section model under `definitions`. Field formats and ranges
+/// come from the models' validation rules; checks against the filesystem
+/// and between sections are not part of it.
+pub fn full_config_schema() -> RootSchema {
+    schemars::schema_for!(FullVmConfiguration)
+}
+
+/// [`full_config_schema`] as pretty-printed JSON.
+pub fn full_config_schema_json() -> String {
+    serde_json::to_string_pretty(&full_config_schema()).expect("schemas always serialize")
+}
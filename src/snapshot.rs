@@ -1,77 +1,1615 @@
-use crate::validation::validate_existing_path;
-use crate::validation::validate_writable_path;
+use crate::instance::InstanceOperations;
+use crate::machine::MachineConfigOperations;
+use crate::models::{FirecrackerVersion, InstanceInfo, InstanceState, MachineConfig};
+use crate::paths;
+use crate::validation::validate_host_dev_name;
+use crate::validation::validate_uds_path;
+use crate::validation::validate_unix_path;
+use crate::validation::ValidationMode;
+use crate::validation::{validate_new_file, validate_nonempty_file};
+use crate::validation::{validate_socket_path, SocketPathOptions};
+use crate::version::{meets, Capabilities, Version, VersionOperations};
+use crate::vm::VmOperations;
 use crate::FirecrackerError;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
-use validator::Validate;
+use reqwest::Method;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+/// Kind of snapshot to create. A `Diff` snapshot only holds the memory pages
+/// dirtied since the previous snapshot, so it needs dirty-page tracking and
+/// a `Full` snapshot to apply on top of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum SnapshotType {
+    Full,
+    Diff,
+}
+
+const SNAPSHOT_TYPES: &[&str] = &["Full", "Diff"];
+
+impl SnapshotType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnapshotType::Full => "Full",
+            SnapshotType::Diff => "Diff",
+        }
+    }
+}
+
+impl fmt::Display for SnapshotType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SnapshotType {
+    type Err = FirecrackerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(SnapshotType::Full),
+            "diff" => Ok(SnapshotType::Diff),
+            _ => Err(FirecrackerError::Config(format!(
+                "Invalid snapshot type {:?}. Must be one of: {}",
+                s,
+                SNAPSHOT_TYPES.join(", ")
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SnapshotType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot_type = String::deserialize(deserializer)?;
+        snapshot_type
+            .parse()
+            .map_err(|_| de::Error::unknown_variant(&snapshot_type, SNAPSHOT_TYPES))
+    }
+}
+
+// Firecracker writes the state and memory files one after the other, so a
+// shared path leaves only the memory behind
+fn validate_distinct_paths(params: &SnapshotCreateParams) -> Result<(), ValidationError> {
+    if std::path::Path::new(&params.snapshot_path) == std::path::Path::new(&params.mem_file_path) {
+        let mut err = ValidationError::new("distinct_paths");
+        err.message = Some(
+            format!(
+                "snapshot_path and mem_file_path both point to {}",
+                params.snapshot_path
+            )
+            .into(),
+        );
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[validate(schema(function = "validate_distinct_paths", skip_on_field_errors = false))]
+#[non_exhaustive]
 pub struct SnapshotCreateParams {
-    #[validate(custom = "validate_writable_path")]
+    #[validate(custom = "validate_unix_path")]
     pub snapshot_path: String,
-    #[validate(custom = "validate_writable_path")]
+    #[validate(custom = "validate_unix_path")]
     pub mem_file_path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[validate(regex(
-        path = "SNAPSHOT_TYPE_REGEX",
-        message = "Invalid snapshot type. Must be one of: Full, Diff"
-    ))]
-    pub snapshot_type: Option<String>,
+    pub snapshot_type: Option<SnapshotType>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub version: Option<String>,
+    /// Let Firecracker replace existing snapshot files. Not sent to the VMM.
+    #[serde(skip)]
+    pub allow_overwrite: bool,
+}
+
+impl SnapshotCreateParams {
+    /// A Full snapshot written to `snapshot_path` and `mem_file_path`.
+    pub fn new(snapshot_path: impl Into<String>, mem_file_path: impl Into<String>) -> Self {
+        Self {
+            snapshot_path: snapshot_path.into(),
+            mem_file_path: mem_file_path.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts building a snapshot written to `snapshot_path` and
+    /// `mem_file_path`.
+    pub fn builder(
+        snapshot_path: impl Into<String>,
+        mem_file_path: impl Into<String>,
+    ) -> SnapshotCreateParamsBuilder {
+        SnapshotCreateParamsBuilder {
+            params: SnapshotCreateParams::new(snapshot_path, mem_file_path),
+        }
+    }
+}
+
+/// Builder for [`SnapshotCreateParams`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotCreateParamsBuilder {
+    params: SnapshotCreateParams,
+}
+
+impl SnapshotCreateParamsBuilder {
+    pub fn snapshot_type(mut self, snapshot_type: SnapshotType) -> Self {
+        self.params.snapshot_type = Some(snapshot_type);
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.params.version = Some(version.into());
+        self
+    }
+
+    pub fn allow_overwrite(mut self, allow: bool) -> Self {
+        self.params.allow_overwrite = allow;
+        self
+    }
+
+    /// Validates and returns the parameters. The files are checked when the
+    /// snapshot is created.
+    pub fn build(self) -> Result<SnapshotCreateParams, FirecrackerError> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+/// Where guest memory is restored from on snapshot load.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "backend_type")]
+pub enum MemBackend {
+    /// The memory file written by snapshot create.
+    File { backend_path: String },
+    /// A Unix socket served by a userfaultfd page-fault handler.
+    Uds { backend_path: String },
+}
+
+/// Points a guest interface at a different host tap device when restoring,
+/// e.g. on another host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct NetworkOverride {
+    #[validate(length(min = 1, message = "iface_id cannot be empty"))]
+    pub iface_id: String,
+    #[validate(custom = "validate_host_dev_name")]
+    pub host_dev_name: String,
+}
+
+impl NetworkOverride {
+    pub fn new(iface_id: impl Into<String>, host_dev_name: impl Into<String>) -> Self {
+        Self {
+            iface_id: iface_id.into(),
+            host_dev_name: host_dev_name.into(),
+        }
+    }
+}
+
+fn validate_mem_backend(backend: &MemBackend) -> Result<(), ValidationError> {
+    match backend {
+        MemBackend::File { backend_path } => validate_unix_path(backend_path),
+        MemBackend::Uds { backend_path } => validate_uds_path(backend_path),
+    }
+}
+
+// Firecracker takes guest memory from exactly one of the two fields
+fn validate_memory_source(params: &SnapshotLoadParams) -> Result<(), ValidationError> {
+    if params.mem_file_path.is_some() == params.mem_backend.is_some() {
+        let mut err = ValidationError::new("memory_source");
+        err.message = Some("Exactly one of mem_file_path and mem_backend must be set".into());
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[validate(schema(function = "validate_memory_source", skip_on_field_errors = false))]
+#[non_exhaustive]
 pub struct SnapshotLoadParams {
-    #[validate(custom = "validate_existing_path")]
+    #[validate(custom = "validate_unix_path")]
     pub snapshot_path: String,
-    #[validate(custom = "validate_existing_path")]
-    pub mem_file_path: String,
+    /// Deprecated by Firecracker in favor of `mem_backend`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_unix_path")]
+    pub mem_file_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(custom = "validate_mem_backend")]
+    pub mem_backend: Option<MemBackend>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enable_diff_snapshots: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_vm: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[validate]
+    pub network_overrides: Vec<NetworkOverride>,
+}
+
+impl SnapshotCreateParams {
+    /// Checks that the fields set match what the VMM accepts: releases
+    /// before 1.5 require `version` and later ones reject it.
+    pub fn check_capabilities(&self, capabilities: &Capabilities) -> Result<(), FirecrackerError> {
+        match (&self.version, capabilities.requires_snapshot_version) {
+            (None, true) => Err(FirecrackerError::Config(format!(
+                "firecracker {} requires a snapshot version",
+                capabilities.version
+            ))),
+            (Some(_), false) => Err(FirecrackerError::Config(format!(
+                "firecracker {} does not accept a snapshot version; leave it unset",
+                capabilities.version
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SnapshotLoadParams {
+    /// Load params that restore memory from the file at `mem_file_path`.
+    pub fn with_mem_file(
+        snapshot_path: impl Into<String>,
+        mem_file_path: impl Into<String>,
+    ) -> Self {
+        Self {
+            snapshot_path: snapshot_path.into(),
+            mem_file_path: Some(mem_file_path.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Load params that restore memory from `backend`.
+    pub fn with_mem_backend(snapshot_path: impl Into<String>, backend: MemBackend) -> Self {
+        Self {
+            snapshot_path: snapshot_path.into(),
+            mem_backend: Some(backend),
+            ..Default::default()
+        }
+    }
+
+    /// Checks that the fields set are supported by the VMM.
+    pub fn check_capabilities(&self, capabilities: &Capabilities) -> Result<(), FirecrackerError> {
+        if self.mem_backend.is_some() && !capabilities.supports_mem_backend {
+            return Err(FirecrackerError::Config(format!(
+                "firecracker {} does not support mem_backend; use mem_file_path",
+                capabilities.version
+            )));
+        }
+        if !self.network_overrides.is_empty() && !capabilities.supports_network_overrides {
+            return Err(FirecrackerError::Config(format!(
+                "firecracker {} does not support network_overrides on snapshot load",
+                capabilities.version
+            )));
+        }
+        if self.resume_vm.is_some() && !capabilities.supports_snapshot_resume_vm {
+            return Err(FirecrackerError::Config(format!(
+                "firecracker {} does not support resume_vm on snapshot load",
+                capabilities.version
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Options for [`SnapshotOperations::create_snapshot_safe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafeSnapshotOptions {
+    /// Keep the VM paused after the snapshot instead of resuming it.
+    pub leave_paused: bool,
+    pub pause_timeout: Duration,
+    pub resume_timeout: Duration,
+}
+
+impl Default for SafeSnapshotOptions {
+    fn default() -> Self {
+        Self {
+            leave_paused: false,
+            pause_timeout: Duration::from_secs(10),
+            resume_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// What happened in [`SnapshotOperations::create_snapshot_safe`] once the
+/// VM was paused. Not `Clone`, since `FirecrackerError` wraps I/O and HTTP
+/// errors that can't be cloned.
+#[derive(Debug)]
+pub struct SafeSnapshotOutcome {
+    pub snapshot: Result<(), FirecrackerError>,
+    /// `None` when the VM was left paused on request.
+    pub resume: Option<Result<(), FirecrackerError>>,
+}
+
+impl SafeSnapshotOutcome {
+    /// Collapses the outcome into the first error, snapshot before resume.
+    pub fn into_result(self) -> Result<(), FirecrackerError> {
+        self.snapshot?;
+        self.resume.unwrap_or(Ok(()))
+    }
+}
+
+/// One snapshot in a [`SnapshotChain`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub snapshot_path: String,
+    pub mem_file_path: String,
+    pub snapshot_type: SnapshotType,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    /// `snapshot_path` of the snapshot this one applies on top of; always
+    /// set for Diff snapshots and never for Full ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+/// What to load to restore the latest snapshot of a chain. Firecracker
+/// loads a single memory file, so the diff layers have to be merged onto
+/// the base memory file in order (e.g. with Firecracker's `rebase-snap`)
+/// before loading `snapshot_path` with that file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestorePlan {
+    pub snapshot_path: String,
+    pub base_mem_file_path: String,
+    pub diff_mem_file_paths: Vec<String>,
+}
+
+/// Tracks a Full snapshot and the Diff snapshots taken on top of it,
+/// persisted as a JSON manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotChain {
+    #[serde(skip)]
+    manifest_path: PathBuf,
+    snapshots: Vec<SnapshotRecord>,
+}
+
+impl SnapshotChain {
+    /// An empty chain saved to `manifest_path`.
+    pub fn new(manifest_path: impl Into<PathBuf>) -> Self {
+        Self {
+            manifest_path: manifest_path.into(),
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Reads and validates the manifest at `manifest_path`.
+    pub fn load(manifest_path: impl Into<PathBuf>) -> Result<Self, FirecrackerError> {
+        let manifest_path = manifest_path.into();
+        let contents =
+            std::fs::read(&manifest_path).map_err(|source| FirecrackerError::FileSystem {
+                path: manifest_path.clone(),
+                source,
+            })?;
+        let mut chain: SnapshotChain = serde_json::from_slice(&contents)?;
+        chain.manifest_path = manifest_path;
+        chain.validate()?;
+        Ok(chain)
+    }
+
+    /// Writes the manifest, replacing the previous one atomically.
+    pub fn save(&self) -> Result<(), FirecrackerError> {
+        let tmp_path = self.manifest_path.with_extension("json.tmp");
+        let fs_error = |path: &Path, source| FirecrackerError::FileSystem {
+            path: path.to_path_buf(),
+            source,
+        };
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)
+            .map_err(|e| fs_error(&tmp_path, e))?;
+        std::fs::rename(&tmp_path, &self.manifest_path)
+            .map_err(|e| fs_error(&self.manifest_path, e))
+    }
+
+    // `save` on a blocking thread, so async callers don't stall the runtime
+    pub(crate) async fn save_async(self) -> Result<(), FirecrackerError> {
+        tokio::task::spawn_blocking(move || self.save())
+            .await
+            .map_err(|e| FirecrackerError::Internal(format!("Saving the manifest failed: {}", e)))?
+    }
+
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    pub fn snapshots(&self) -> &[SnapshotRecord] {
+        &self.snapshots
+    }
+
+    pub fn latest(&self) -> Option<&SnapshotRecord> {
+        self.snapshots.last()
+    }
+
+    /// Appends a snapshot created with `params`. A Diff snapshot is chained
+    /// to the latest snapshot and fails if there is none.
+    pub fn record(
+        &mut self,
+        params: &SnapshotCreateParams,
+    ) -> Result<&SnapshotRecord, FirecrackerError> {
+        let snapshot_type = params.snapshot_type.unwrap_or(SnapshotType::Full);
+        let parent = match snapshot_type {
+            SnapshotType::Full => None,
+            SnapshotType::Diff => Some(
+                self.latest()
+                    .ok_or_else(|| {
+                        FirecrackerError::Snapshot(
+                            "a Diff snapshot needs a parent snapshot in the chain".to_string(),
+                        )
+                    })?
+                    .snapshot_path
+                    .clone(),
+            ),
+        };
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        self.snapshots.push(SnapshotRecord {
+            snapshot_path: params.snapshot_path.clone(),
+            mem_file_path: params.mem_file_path.clone(),
+            snapshot_type,
+            created_at,
+            parent,
+        });
+        Ok(self.snapshots.last().unwrap())
+    }
+
+    /// The latest snapshot and its ancestors back to their Full base,
+    /// oldest first.
+    pub fn ancestry(&self) -> Result<Vec<&SnapshotRecord>, FirecrackerError> {
+        let mut ancestry = Vec::new();
+        let mut current = self.latest();
+        while let Some(record) = current {
+            ancestry.push(record);
+            current = match &record.parent {
+                Some(parent) => Some(self.find(parent)?),
+                None => None,
+            };
+            if ancestry.len() > self.snapshots.len() {
+                return Err(FirecrackerError::Snapshot(
+                    "snapshot chain contains a cycle".to_string(),
+                ));
+            }
+        }
+        ancestry.reverse();
+        Ok(ancestry)
+    }
+
+    /// Describes how to restore the latest snapshot.
+    pub fn restore_plan(&self) -> Result<RestorePlan, FirecrackerError> {
+        let ancestry = self.ancestry()?;
+        let (base, layers) = ancestry
+            .split_first()
+            .ok_or_else(|| FirecrackerError::Snapshot("snapshot chain is empty".to_string()))?;
+
+        Ok(RestorePlan {
+            snapshot_path: ancestry.last().unwrap().snapshot_path.clone(),
+            base_mem_file_path: base.mem_file_path.clone(),
+            diff_mem_file_paths: layers.iter().map(|r| r.mem_file_path.clone()).collect(),
+        })
+    }
+
+    /// Checks that every Diff snapshot has a parent earlier in the chain and
+    /// that Full snapshots have none.
+    pub fn validate(&self) -> Result<(), FirecrackerError> {
+        for (i, record) in self.snapshots.iter().enumerate() {
+            match (record.snapshot_type, &record.parent) {
+                (SnapshotType::Full, None) => {}
+                (SnapshotType::Full, Some(_)) => {
+                    return Err(FirecrackerError::Snapshot(format!(
+                        "Full snapshot {} cannot have a parent",
+                        record.snapshot_path
+                    )))
+                }
+                (SnapshotType::Diff, None) => {
+                    return Err(FirecrackerError::Snapshot(format!(
+                        "Diff snapshot {} has no parent",
+                        record.snapshot_path
+                    )))
+                }
+                (SnapshotType::Diff, Some(parent)) => {
+                    if !self.snapshots[..i]
+                        .iter()
+                        .any(|r| &r.snapshot_path == parent)
+                    {
+                        return Err(FirecrackerError::Snapshot(format!(
+                            "Diff snapshot {} refers to unknown parent {}",
+                            record.snapshot_path, parent
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops every snapshot older than the `keep_full` latest Full snapshots
+    /// and returns the dropped records, oldest first. At least the latest
+    /// Full snapshot is always kept, along with the Diffs on top of it.
+    pub fn prune(&mut self, keep_full: usize) -> Vec<SnapshotRecord> {
+        let fulls: Vec<usize> = self
+            .snapshots
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.snapshot_type == SnapshotType::Full)
+            .map(|(i, _)| i)
+            .collect();
+        match fulls.len().checked_sub(keep_full.max(1)) {
+            Some(first_kept) if !fulls.is_empty() => {
+                self.snapshots.drain(..fulls[first_kept]).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // Diff snapshots taken since the latest Full snapshot
+    fn diffs_since_full(&self) -> usize {
+        self.snapshots
+            .iter()
+            .rev()
+            .take_while(|r| r.snapshot_type == SnapshotType::Diff)
+            .count()
+    }
+
+    // Later records win, since a snapshot path can be reused after a new
+    // Full snapshot
+    fn find(&self, snapshot_path: &str) -> Result<&SnapshotRecord, FirecrackerError> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|r| r.snapshot_path == snapshot_path)
+            .ok_or_else(|| {
+                FirecrackerError::Snapshot(format!("unknown parent snapshot {}", snapshot_path))
+            })
+    }
+}
+
+/// What to restore with [`restore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreSource {
+    Params(SnapshotLoadParams),
+    /// The latest snapshot of the chain. Its diff layers, if any, must have
+    /// been merged onto the base memory file already.
+    Chain(SnapshotChain),
 }
 
-lazy_static::lazy_static! {
-    static ref SNAPSHOT_TYPE_REGEX: regex::Regex = regex::Regex::new(r"^(Full|Diff)$").unwrap();
+impl From<SnapshotLoadParams> for RestoreSource {
+    fn from(params: SnapshotLoadParams) -> Self {
+        RestoreSource::Params(params)
+    }
+}
+
+impl From<SnapshotChain> for RestoreSource {
+    fn from(chain: SnapshotChain) -> Self {
+        RestoreSource::Chain(chain)
+    }
+}
+
+/// Options for [`restore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// Resume the VM after loading; otherwise it stays paused.
+    pub resume: bool,
+    /// Replaces any network overrides in the source when not empty.
+    pub network_overrides: Vec<NetworkOverride>,
+    /// Limit for the whole sequence, including waiting for the API.
+    pub timeout: Duration,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            resume: true,
+            network_overrides: Vec::new(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The steps of [`restore`], in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RestoreStep {
+    /// Working out what to load from a [`SnapshotChain`]
+    Plan,
+    WaitForApi,
+    Load,
+    Resume,
+    Verify,
+}
+
+impl fmt::Display for RestoreStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RestoreStep::Plan => "planning the restore",
+            RestoreStep::WaitForApi => "waiting for the API",
+            RestoreStep::Load => "loading the snapshot",
+            RestoreStep::Resume => "resuming the VM",
+            RestoreStep::Verify => "checking the instance state",
+        })
+    }
+}
+
+/// Restores a snapshot into a freshly started Firecracker process: waits for
+/// its API, loads the snapshot (through `mem_backend` when the VMM supports
+/// it), resumes the VM if asked to and checks the instance ends up in the
+/// expected state.
+///
+/// A failing step is reported as [`Restore`](FirecrackerError::Restore),
+/// running out of `timeout` as a `Restore` wrapping
+/// [`RequestTimeout`](FirecrackerError::RequestTimeout). Once the load has
+/// been sent, a failure pauses the VM, best effort, so a half-restored guest
+/// doesn't run on, e.g. with the network identity of the VM it was
+/// snapshotted from.
+pub async fn restore(
+    client: &crate::FirecrackerClient,
+    source: impl Into<RestoreSource>,
+    options: RestoreOptions,
+) -> Result<InstanceInfo, FirecrackerError> {
+    let timeout = options.timeout;
+    let mut step = RestoreStep::Plan;
+    let steps = restore_steps(client, source.into(), options, &mut step);
+    let result = match tokio::time::timeout(timeout, steps).await {
+        Ok(result) => result,
+        Err(_) => Err(FirecrackerError::Restore {
+            step,
+            source: Box::new(FirecrackerError::RequestTimeout {
+                operation: "snapshot restore".to_string(),
+                budget: crate::timeout::TimeoutBudget::Total,
+                limit: timeout,
+            }),
+        }),
+    };
+
+    if result.is_err() && step >= RestoreStep::Load {
+        // The VMM may already be running the guest; the restore error is
+        // what the caller needs to see
+        let _ = client.pause_vm().await;
+    }
+    result
+}
+
+async fn restore_steps(
+    client: &crate::FirecrackerClient,
+    source: RestoreSource,
+    options: RestoreOptions,
+    step: &mut RestoreStep,
+) -> Result<InstanceInfo, FirecrackerError> {
+    let failed = |step: RestoreStep| {
+        move |source: FirecrackerError| FirecrackerError::Restore {
+            step,
+            source: Box::new(source),
+        }
+    };
+
+    // A chain that can't be restored fails before the VMM is waited for
+    let mut params = match source {
+        RestoreSource::Params(params) => params,
+        RestoreSource::Chain(chain) => {
+            let plan = chain.restore_plan().map_err(failed(RestoreStep::Plan))?;
+            if !plan.diff_mem_file_paths.is_empty() {
+                return Err(failed(RestoreStep::Plan)(FirecrackerError::Snapshot(
+                    format!(
+                        "{} diff layers must be merged onto {} before restoring",
+                        plan.diff_mem_file_paths.len(),
+                        plan.base_mem_file_path
+                    ),
+                )));
+            }
+            SnapshotLoadParams {
+                snapshot_path: plan.snapshot_path,
+                mem_file_path: Some(plan.base_mem_file_path),
+                ..Default::default()
+            }
+        }
+    };
+
+    *step = RestoreStep::WaitForApi;
+    client
+        .wait_for_api(options.timeout)
+        .await
+        .map_err(failed(RestoreStep::WaitForApi))?;
+    // Version discovery is best effort; without it the params are sent as
+    // they are and the resume is a separate call
+    let capabilities = client.negotiate().await.ok();
+    let supports = |check: fn(&Capabilities) -> bool| capabilities.as_ref().is_some_and(check);
+
+    if supports(|c| c.supports_mem_backend) {
+        if let Some(backend_path) = params.mem_file_path.take() {
+            params.mem_backend = Some(MemBackend::File { backend_path });
+        }
+    }
+    if !options.network_overrides.is_empty() {
+        params.network_overrides = options.network_overrides;
+    }
+    let resume_on_load = options.resume && supports(|c| c.supports_snapshot_resume_vm);
+    params.resume_vm = resume_on_load.then_some(true);
+
+    *step = RestoreStep::Load;
+    client
+        .load_snapshot(&params)
+        .await
+        .map_err(failed(RestoreStep::Load))?;
+
+    if options.resume && !resume_on_load {
+        *step = RestoreStep::Resume;
+        client
+            .resume_vm()
+            .await
+            .map_err(failed(RestoreStep::Resume))?;
+    }
+
+    *step = RestoreStep::Verify;
+    let expected = if options.resume {
+        InstanceState::Running
+    } else {
+        InstanceState::Paused
+    };
+    let info = client
+        .describe_instance()
+        .await
+        .map_err(failed(RestoreStep::Verify))?;
+    if info.instance_state() != expected {
+        return Err(failed(RestoreStep::Verify)(
+            FirecrackerError::InvalidState {
+                current_state: info.state,
+                expected_states: vec![expected.as_str().to_string()],
+            },
+        ));
+    }
+
+    Ok(info)
+}
+
+// Firecracker's defaults when the machine config leaves them unset
+const DEFAULT_MEM_SIZE_MIB: u64 = 128;
+const DEFAULT_VCPU_COUNT: u64 = 1;
+
+// Rough size of the VM state file: device and VMM state plus per-vCPU
+// register state
+const STATE_BASE_BYTES: u64 = 512 * 1024;
+const STATE_PER_VCPU_BYTES: u64 = 64 * 1024;
+
+/// Approximate disk space a snapshot needs. These are estimates from the
+/// machine config, not measurements: the memory file of a Full snapshot is
+/// the size of guest memory, while a Diff snapshot only holds the pages
+/// dirtied since the previous one, anywhere from none to all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeEstimate {
+    /// Approximate size of the VM state file
+    pub state_bytes: u64,
+    /// Lower bound for the memory file
+    pub min_mem_bytes: u64,
+    /// Upper bound for the memory file
+    pub max_mem_bytes: u64,
+}
+
+impl SizeEstimate {
+    /// Approximate worst-case total for both files.
+    pub fn max_total_bytes(&self) -> u64 {
+        self.state_bytes + self.max_mem_bytes
+    }
+}
+
+impl fmt::Display for SizeEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MIB: u64 = 1024 * 1024;
+        let min = (self.state_bytes + self.min_mem_bytes).div_ceil(MIB);
+        let max = self.max_total_bytes().div_ceil(MIB);
+        if min == max {
+            write!(f, "~{} MiB (approximate)", max)
+        } else {
+            write!(f, "~{}-{} MiB (approximate)", min, max)
+        }
+    }
+}
+
+/// Estimates how much disk space a snapshot of a VM with `config` needs.
+pub fn estimate_size(config: &MachineConfig, snapshot_type: SnapshotType) -> SizeEstimate {
+    let mem_bytes = config.mem_size_mib.map_or(DEFAULT_MEM_SIZE_MIB, u64::from) * 1024 * 1024;
+    let vcpus = config.vcpu_count.map_or(DEFAULT_VCPU_COUNT, u64::from);
+
+    SizeEstimate {
+        state_bytes: STATE_BASE_BYTES + vcpus * STATE_PER_VCPU_BYTES,
+        min_mem_bytes: match snapshot_type {
+            SnapshotType::Full => mem_bytes,
+            SnapshotType::Diff => 0,
+        },
+        max_mem_bytes: mem_bytes,
+    }
+}
+
+/// Checks that the directories of `params`' snapshot and memory files have
+/// room for the worst case of `estimate`. Files that land on the same
+/// filesystem are checked against its free space together.
+#[cfg(unix)]
+pub fn check_destination_space(
+    params: &SnapshotCreateParams,
+    estimate: &SizeEstimate,
+) -> Result<(), FirecrackerError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let parent = |path: &str| {
+        Path::new(path)
+            .parent()
+            .unwrap_or_else(|| Path::new("/"))
+            .to_path_buf()
+    };
+
+    // (filesystem, first directory on it, bytes needed there)
+    let mut needs: Vec<(u64, PathBuf, u64)> = Vec::new();
+    for (dir, bytes) in [
+        (parent(&params.mem_file_path), estimate.max_mem_bytes),
+        (parent(&params.snapshot_path), estimate.state_bytes),
+    ] {
+        let dev = std::fs::metadata(&dir)
+            .map_err(|source| FirecrackerError::FileSystem {
+                path: dir.clone(),
+                source,
+            })?
+            .dev();
+        match needs.iter_mut().find(|(d, _, _)| *d == dev) {
+            Some((_, _, needed)) => *needed = needed.saturating_add(bytes),
+            None => needs.push((dev, dir, bytes)),
+        }
+    }
+
+    for (_, dir, required_bytes) in needs {
+        let available_bytes = available_space(&dir)?;
+        if available_bytes < required_bytes {
+            return Err(FirecrackerError::InsufficientSpace {
+                path: dir,
+                required_bytes,
+                available_bytes,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Space available to unprivileged users on the filesystem holding `dir`
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Result<u64, FirecrackerError> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let fs_error = |source| FirecrackerError::FileSystem {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let c_path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|_| FirecrackerError::InvalidPath(dir.display().to_string()))?;
+
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is NUL-terminated and stat points to writable memory
+    // large enough for a statvfs struct.
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(fs_error(std::io::Error::last_os_error()));
+    }
+    // SAFETY: statvfs succeeded, so it filled in the struct.
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+// Upper 48 bits of the magic number that starts every snapshot state file,
+// one per architecture. The low 16 bits hold the versionize format version
+// in the legacy format and are zero in the current one.
+const SNAPSHOT_MAGIC_ARCHES: &[(u64, &str)] = &[
+    (0x0710_1984_8664_0000, "x86_64"),
+    (0x0710_1984_AAAA_0000, "aarch64"),
+];
+
+/// First release that writes the versioned snapshot header instead of the
+/// legacy versionize one.
+pub const VERSIONED_SNAPSHOT_MIN_VERSION: Version = Version::new(1, 6, 0);
+
+// Snapshot data format version written by each Firecracker release since
+// the versioned header was introduced; later releases use the last entry
+const SNAPSHOT_FORMAT_VERSIONS: &[((u64, u64), Version)] = &[
+    ((1, 6), Version::new(1, 0, 0)),
+    ((1, 7), Version::new(1, 0, 0)),
+    ((1, 8), Version::new(2, 0, 0)),
+    ((1, 9), Version::new(3, 0, 0)),
+    ((1, 10), Version::new(4, 0, 0)),
+    ((1, 11), Version::new(5, 0, 0)),
+    ((1, 12), Version::new(6, 0, 0)),
+];
+
+/// Version information read from a snapshot state file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// Written by Firecracker before 1.6.
+    Legacy {
+        format_version: u16,
+        data_version: u16,
+    },
+    /// Written by Firecracker 1.6 and later.
+    Versioned(Version),
+}
+
+/// Result of [`check_compat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatReport {
+    pub arch: &'static str,
+    pub format: SnapshotFormat,
+    pub target: Version,
+    /// Snapshot format the target writes, if it uses the versioned header.
+    pub target_format: Option<Version>,
+    pub compatible: bool,
+    /// Why the snapshot can't be loaded, when it can't.
+    pub reason: Option<String>,
+}
+
+/// Snapshot data format version written by `version`, or `None` for
+/// releases that use the legacy header.
+pub fn snapshot_format_version(version: &Version) -> Option<Version> {
+    if !meets(version, &VERSIONED_SNAPSHOT_MIN_VERSION) {
+        return None;
+    }
+    SNAPSHOT_FORMAT_VERSIONS
+        .iter()
+        .rev()
+        .find(|((major, minor), _)| meets(version, &Version::new(*major, *minor, 0)))
+        .map(|(_, format)| format.clone())
+}
+
+/// Reads the header of the snapshot state file at `snapshot_path` and
+/// reports whether a VMM running `target` can load it. A VMM loads
+/// snapshots with the same format major version and a minor version no
+/// newer than its own.
+pub fn check_compat(
+    snapshot_path: impl AsRef<Path>,
+    target: &FirecrackerVersion,
+) -> Result<CompatReport, FirecrackerError> {
+    let path = snapshot_path.as_ref();
+    let target = target.semver()?;
+    let (arch, format) = read_snapshot_header(path)?;
+    let target_format = snapshot_format_version(&target);
+
+    let reason = match (&format, &target_format) {
+        (SnapshotFormat::Legacy { .. }, None) => None,
+        (SnapshotFormat::Legacy { .. }, Some(_)) => Some(format!(
+            "snapshot uses the pre-1.6 format, which firecracker {} cannot load",
+            target
+        )),
+        (SnapshotFormat::Versioned(found), None) => Some(format!(
+            "snapshot format {} needs firecracker 1.6 or later, target is {}",
+            found, target
+        )),
+        (SnapshotFormat::Versioned(found), Some(supported))
+            if found.major != supported.major || found.minor > supported.minor =>
+        {
+            Some(format!(
+                "snapshot format {} is not supported by firecracker {} (format {})",
+                found, target, supported
+            ))
+        }
+        (SnapshotFormat::Versioned(_), Some(_)) => None,
+    };
+
+    Ok(CompatReport {
+        arch,
+        format,
+        target,
+        target_format,
+        compatible: reason.is_none(),
+        reason,
+    })
+}
+
+fn read_snapshot_header(path: &Path) -> Result<(&'static str, SnapshotFormat), FirecrackerError> {
+    use std::io::Read;
+
+    let mut header = Vec::with_capacity(64);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(64).read_to_end(&mut header))
+        .map_err(|source| FirecrackerError::FileSystem {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let invalid = |what: &str| FirecrackerError::Snapshot(format!("{}: {}", path.display(), what));
+    let u64_at = |offset: usize| {
+        header
+            .get(offset..offset + 8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| invalid("truncated snapshot header"))
+    };
+
+    let magic = u64_at(0)?;
+    let arch = SNAPSHOT_MAGIC_ARCHES
+        .iter()
+        .find(|(base, _)| magic & !0xFFFF == *base)
+        .map(|(_, arch)| *arch)
+        .ok_or_else(|| invalid("not a Firecracker snapshot"))?;
+
+    let format_version = (magic & 0xFFFF) as u16;
+    if format_version != 0 {
+        let data_version = header
+            .get(8..10)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| invalid("truncated snapshot header"))?;
+        return Ok((
+            arch,
+            SnapshotFormat::Legacy {
+                format_version,
+                data_version,
+            },
+        ));
+    }
+
+    // The version follows as `bincode` writes a `semver::Version`: a u64
+    // length, then the version as a string
+    let len = usize::try_from(u64_at(8)?).unwrap_or(usize::MAX);
+    let version = header
+        .get(16..16usize.saturating_add(len))
+        .ok_or_else(|| invalid("truncated snapshot header"))?;
+    let version = std::str::from_utf8(version)
+        .ok()
+        .and_then(|version| Version::parse(version).ok())
+        .ok_or_else(|| invalid("invalid snapshot format version"))?;
+    Ok((arch, SnapshotFormat::Versioned(version)))
+}
+
+async fn with_timeout<F>(timeout: Duration, future: F) -> Result<(), FirecrackerError>
+where
+    F: Future<Output = Result<(), FirecrackerError>>,
+{
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| FirecrackerError::Timeout {
+            duration_secs: timeout.as_secs(),
+        })?
 }
 
 #[async_trait]
 pub trait SnapshotOperations {
     async fn create_snapshot(&self, params: &SnapshotCreateParams) -> Result<(), FirecrackerError>;
     async fn load_snapshot(&self, params: &SnapshotLoadParams) -> Result<(), FirecrackerError>;
+
+    /// Pauses the VM, creates the snapshot and resumes the VM again, also
+    /// when the snapshot fails. Returns an error only if the pause fails, in
+    /// which case nothing else was attempted.
+    async fn create_snapshot_safe(
+        &self,
+        params: &SnapshotCreateParams,
+        options: SafeSnapshotOptions,
+    ) -> Result<SafeSnapshotOutcome, FirecrackerError>;
+}
+
+impl crate::LocalPaths {
+    // The params with their paths checked and resolved
+    fn check_create_paths(
+        &self,
+        params: &SnapshotCreateParams,
+    ) -> Result<SnapshotCreateParams, FirecrackerError> {
+        let new_file = |path: &str| validate_new_file(path, params.allow_overwrite);
+        self.check_local_path("snapshot_path", &params.snapshot_path, new_file)?;
+        self.check_local_path("mem_file_path", &params.mem_file_path, new_file)?;
+        Ok(SnapshotCreateParams {
+            snapshot_path: self.resolve_path("snapshot_path", &params.snapshot_path)?,
+            mem_file_path: self.resolve_path("mem_file_path", &params.mem_file_path)?,
+            ..params.clone()
+        })
+    }
+
+    // Like `check_create_paths`, also checking that the snapshot can be
+    // loaded by `compat_target` when there is one
+    #[cfg_attr(feature = "no-fs-validation", allow(unused_variables))]
+    fn check_load_paths(
+        &self,
+        params: &SnapshotLoadParams,
+        compat_target: Option<FirecrackerVersion>,
+    ) -> Result<SnapshotLoadParams, FirecrackerError> {
+        self.check_local_path(
+            "snapshot_path",
+            &params.snapshot_path,
+            validate_nonempty_file,
+        )?;
+        if let Some(path) = &params.mem_file_path {
+            self.check_local_path("mem_file_path", path, validate_nonempty_file)?;
+        }
+        match &params.mem_backend {
+            Some(MemBackend::File { backend_path }) => {
+                self.check_local_path("mem_backend", backend_path, validate_nonempty_file)?;
+            }
+            // Firecracker connects to the page fault handler's socket
+            Some(MemBackend::Uds { backend_path }) => {
+                self.check_local_path("mem_backend", backend_path, |path| {
+                    validate_socket_path(path, &SocketPathOptions::default())
+                })?;
+            }
+            None => {}
+        }
+        #[cfg(not(feature = "no-fs-validation"))]
+        if let Some(target) = compat_target {
+            let report = check_compat(&params.snapshot_path, &target)?;
+            if let Some(reason) = report.reason {
+                return Err(FirecrackerError::Snapshot(reason));
+            }
+        }
+        Ok(SnapshotLoadParams {
+            snapshot_path: self.resolve_path("snapshot_path", &params.snapshot_path)?,
+            mem_file_path: params
+                .mem_file_path
+                .as_deref()
+                .map(|path| self.resolve_path("mem_file_path", path))
+                .transpose()?,
+            mem_backend: match &params.mem_backend {
+                Some(MemBackend::File { backend_path }) => Some(MemBackend::File {
+                    backend_path: self.resolve_path("mem_backend", backend_path)?,
+                }),
+                backend => backend.clone(),
+            },
+            ..params.clone()
+        })
+    }
 }
 
 #[async_trait]
 impl SnapshotOperations for crate::FirecrackerClient {
     async fn create_snapshot(&self, params: &SnapshotCreateParams) -> Result<(), FirecrackerError> {
-        params.validate()?;
+        self.validate_model(params)?;
+        let params = params.clone();
+        let params = &self
+            .on_local_fs(move |local| local.check_create_paths(&params))
+            .await?;
+        // Without known capabilities the params are sent as given
+        if let Some(capabilities) = self.capabilities() {
+            params.check_capabilities(capabilities)?;
+        }
+        if self.strict_snapshot_checks && params.snapshot_type == Some(SnapshotType::Diff) {
+            self.check_diff_prerequisites().await?;
+        }
 
-        let url = self.url("/snapshot/create")?;
-        let response = self.client.put(url).json(params).send().await?;
+        self.send_json(Method::PUT, paths::SNAPSHOT_CREATE, params)
+            .await?;
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+        // Firecracker creates a full snapshot when no type is given
+        if params.snapshot_type != Some(SnapshotType::Diff) {
+            self.full_snapshot_taken.store(true, Ordering::Relaxed);
+        }
+
+        // Saved from a copy, so the lock isn't held across the write
+        let chain = match self.snapshot_chain.lock().unwrap().as_mut() {
+            Some(chain) => {
+                chain.record(params)?;
+                Some(chain.clone())
+            }
+            None => None,
+        };
+        if let Some(chain) = chain {
+            chain.save_async().await?;
         }
 
         Ok(())
     }
 
     async fn load_snapshot(&self, params: &SnapshotLoadParams) -> Result<(), FirecrackerError> {
-        params.validate()?;
+        self.validate_model(params)?;
+        // Reading the header is a filesystem check like the path checks
+        let compat_target = match (
+            self.snapshot_compat_check,
+            self.validation_mode(),
+            self.capabilities(),
+        ) {
+            (true, ValidationMode::StrictLocal, Some(capabilities)) => {
+                Some(FirecrackerVersion::new(capabilities.version.to_string()))
+            }
+            _ => None,
+        };
+        let params = params.clone();
+        let params = &self
+            .on_local_fs(move |local| local.check_load_paths(&params, compat_target))
+            .await?;
+        if let Some(capabilities) = self.capabilities() {
+            params.check_capabilities(capabilities)?;
+        }
+
+        self.send_json(Method::PUT, paths::SNAPSHOT_LOAD, params)
+            .await?;
+        // A loaded VM is past booting, and paused unless told to resume
+        self.observe_state(if params.resume_vm == Some(true) {
+            InstanceState::Running
+        } else {
+            InstanceState::Paused
+        });
+        Ok(())
+    }
 
-        let url = self.url("/snapshot/load")?;
-        let response = self.client.put(url).json(params).send().await?;
+    async fn create_snapshot_safe(
+        &self,
+        params: &SnapshotCreateParams,
+        options: SafeSnapshotOptions,
+    ) -> Result<SafeSnapshotOutcome, FirecrackerError> {
+        with_timeout(options.pause_timeout, self.pause_vm()).await?;
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+        let snapshot = self.create_snapshot(params).await;
+        let resume = if options.leave_paused {
+            None
+        } else {
+            Some(with_timeout(options.resume_timeout, self.resume_vm()).await)
+        };
+
+        Ok(SafeSnapshotOutcome { snapshot, resume })
+    }
+}
+
+impl crate::FirecrackerClient {
+    async fn check_diff_prerequisites(&self) -> Result<(), FirecrackerError> {
+        let chained = self
+            .snapshot_chain
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|chain| chain.latest().is_some());
+        if !chained && !self.full_snapshot_taken.load(Ordering::Relaxed) {
+            return Err(FirecrackerError::Snapshot(
+                "a Diff snapshot needs a Full snapshot taken by this client or in its chain first"
+                    .to_string(),
+            ));
+        }
+
+        let machine_config = self.get_machine_config().await?;
+        if machine_config.track_dirty_pages != Some(true) {
+            return Err(FirecrackerError::Snapshot(
+                "a Diff snapshot needs track_dirty_pages enabled in the machine config".to_string(),
+            ));
         }
 
         Ok(())
     }
 }
+
+/// How many snapshots a [`Scheduler`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Diff snapshots taken on top of a Full one before the next snapshot is
+    /// Full again, which consolidates the chain. 0 takes only Full snapshots
+    pub max_diffs: usize,
+    /// Full snapshots kept on disk along with their Diffs; older files are
+    /// deleted once a newer Full snapshot is taken. At least one
+    pub keep_full: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_diffs: 10,
+            keep_full: 1,
+        }
+    }
+}
+
+/// Options for [`Scheduler::start`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleOptions {
+    /// Where the snapshot and memory files are written
+    pub directory: PathBuf,
+    pub interval: Duration,
+    pub retention: RetentionPolicy,
+    pub snapshot: SafeSnapshotOptions,
+    /// Wait after the first failed snapshot instead of the interval; doubles
+    /// with every further failure in a row
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl ScheduleOptions {
+    /// Snapshots into `directory` every `interval`.
+    pub fn new(directory: impl Into<PathBuf>, interval: Duration) -> Self {
+        Self {
+            directory: directory.into(),
+            interval,
+            retention: RetentionPolicy::default(),
+            snapshot: SafeSnapshotOptions::default(),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+/// What a [`Scheduler`] has done, from [`Scheduler::status`] or as it
+/// changes through [`Scheduler::subscribe`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchedulerStatus {
+    /// Whether scheduled snapshots are paused; triggered ones still run
+    pub paused: bool,
+    pub snapshots_taken: u64,
+    pub snapshots_failed: u64,
+    /// Failed snapshots since the last successful one
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_snapshot: Option<SnapshotRecord>,
+    /// Snapshot and memory files deleted by rotation
+    pub files_removed: u64,
+    /// The last file rotation, or cleaning up after a failed snapshot,
+    /// failed to delete, and why
+    pub last_cleanup_error: Option<String>,
+}
+
+struct SchedulerInner {
+    client: Arc<crate::FirecrackerClient>,
+    options: ScheduleOptions,
+    chain: Mutex<SnapshotChain>,
+    control: Mutex<SchedulerControl>,
+    wake: Notify,
+    status: watch::Sender<SchedulerStatus>,
+}
+
+#[derive(Default)]
+struct SchedulerControl {
+    paused: bool,
+    triggered: bool,
+    stopped: bool,
+}
+
+/// Takes a snapshot of a VM every interval for crash recovery: Diff
+/// snapshots on top of a Full one, with a Full snapshot again once the
+/// retention policy's `max_diffs` is reached, after which the files of Full
+/// snapshots beyond `keep_full` and their Diffs are deleted. Each snapshot
+/// goes through [`SnapshotOperations::create_snapshot_safe`] and is recorded
+/// in the chain, whose manifest is saved after every change; don't attach
+/// the same chain to the client as well. After a failure the next attempt
+/// waits with exponential backoff instead of the interval, and the files of
+/// the failed snapshot are deleted.
+///
+/// Dropping the scheduler stops it after the snapshot in progress, if any,
+/// so the VM isn't left paused.
+pub struct Scheduler {
+    inner: Arc<SchedulerInner>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// Starts taking snapshots, the first one an interval from now. Must be
+    /// called from within a Tokio runtime.
+    pub fn start(
+        client: Arc<crate::FirecrackerClient>,
+        chain: SnapshotChain,
+        options: ScheduleOptions,
+    ) -> Self {
+        let inner = Arc::new(SchedulerInner {
+            client,
+            options,
+            chain: Mutex::new(chain),
+            control: Mutex::new(SchedulerControl::default()),
+            wake: Notify::new(),
+            status: watch::channel(SchedulerStatus::default()).0,
+        });
+        let task = tokio::spawn(run_schedule(inner.clone()));
+        Self {
+            inner,
+            task: Some(task),
+        }
+    }
+
+    /// Takes a snapshot now, also while paused. The schedule carries on an
+    /// interval after it.
+    pub fn trigger(&self) {
+        self.inner.control.lock().unwrap().triggered = true;
+        self.inner.wake.notify_one();
+    }
+
+    /// Stops taking scheduled snapshots until [`Scheduler::resume`].
+    pub fn pause(&self) {
+        self.set_paused(true);
+    }
+
+    pub fn resume(&self) {
+        self.set_paused(false);
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        self.inner.status.borrow().clone()
+    }
+
+    /// Receives the status every time it changes.
+    pub fn subscribe(&self) -> watch::Receiver<SchedulerStatus> {
+        self.inner.status.subscribe()
+    }
+
+    /// A copy of the chain as it is now.
+    pub fn chain(&self) -> SnapshotChain {
+        self.inner.chain.lock().unwrap().clone()
+    }
+
+    /// Stops the scheduler once the snapshot in progress, if any, is done
+    /// and returns the chain.
+    pub async fn stop(mut self) -> SnapshotChain {
+        self.signal_stop();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+        self.chain()
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.inner.control.lock().unwrap().paused = paused;
+        self.inner
+            .status
+            .send_modify(|status| status.paused = paused);
+        self.inner.wake.notify_one();
+    }
+
+    fn signal_stop(&self) {
+        self.inner.control.lock().unwrap().stopped = true;
+        self.inner.wake.notify_one();
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.signal_stop();
+    }
+}
+
+async fn run_schedule(inner: Arc<SchedulerInner>) {
+    let options = &inner.options;
+    let mut next = Instant::now() + options.interval;
+    let mut backoff = options.initial_backoff;
+    let mut sequence = 0u64;
+    loop {
+        let woken = inner.wake.notified();
+        let (due, paused) = {
+            let mut control = inner.control.lock().unwrap();
+            if control.stopped {
+                return;
+            }
+            let triggered = std::mem::take(&mut control.triggered);
+            (
+                triggered || (!control.paused && Instant::now() >= next),
+                control.paused,
+            )
+        };
+        if !due {
+            if paused {
+                woken.await;
+            } else {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(next) => {}
+                    _ = woken => {}
+                }
+            }
+            continue;
+        }
+
+        sequence += 1;
+        let taken = take_scheduled_snapshot(&inner, sequence).await;
+        let succeeded = taken.is_ok();
+        inner.status.send_modify(|status| match taken {
+            Ok(record) => {
+                status.snapshots_taken += 1;
+                status.consecutive_failures = 0;
+                status.last_snapshot = Some(record);
+            }
+            Err(err) => {
+                status.snapshots_failed += 1;
+                status.consecutive_failures += 1;
+                status.last_error = Some(err.to_string());
+            }
+        });
+        if succeeded {
+            backoff = options.initial_backoff;
+            next = Instant::now() + options.interval;
+        } else {
+            next = Instant::now() + backoff;
+            backoff = (backoff * 2).min(options.max_backoff);
+        }
+    }
+}
+
+async fn take_scheduled_snapshot(
+    inner: &Arc<SchedulerInner>,
+    sequence: u64,
+) -> Result<SnapshotRecord, FirecrackerError> {
+    let options = &inner.options;
+    let snapshot_type = {
+        let chain = inner.chain.lock().unwrap();
+        if chain.latest().is_some() && chain.diffs_since_full() < options.retention.max_diffs {
+            SnapshotType::Diff
+        } else {
+            SnapshotType::Full
+        }
+    };
+    tokio::fs::create_dir_all(&options.directory)
+        .await
+        .map_err(|source| FirecrackerError::FileSystem {
+            path: options.directory.clone(),
+            source,
+        })?;
+    let stem = format!(
+        "snapshot-{}-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default(),
+        sequence
+    );
+    let file = |extension: &str| {
+        options
+            .directory
+            .join(format!("{}.{}", stem, extension))
+            .display()
+            .to_string()
+    };
+    let params = SnapshotCreateParams::builder(file("snap"), file("mem"))
+        .snapshot_type(snapshot_type)
+        .build()?;
+
+    let created = async {
+        let outcome = inner
+            .client
+            .create_snapshot_safe(&params, options.snapshot.clone())
+            .await?;
+        outcome.snapshot?;
+        let record = inner.chain.lock().unwrap().record(&params)?.clone();
+        Ok::<_, FirecrackerError>((outcome.resume, record))
+    }
+    .await;
+    // Whatever the VMM wrote of a snapshot that didn't make it into the
+    // chain is of no use to anyone
+    let (resume, record) = match created {
+        Ok(created) => created,
+        Err(err) => {
+            remove_files(
+                inner,
+                vec![params.snapshot_path, params.mem_file_path],
+                false,
+            )
+            .await;
+            return Err(err);
+        }
+    };
+
+    // The manifest is saved from a copy and the files removed off the
+    // runtime, so the lock isn't held across either
+    let (pruned, chain) = {
+        let mut chain = inner.chain.lock().unwrap();
+        let pruned = chain.prune(options.retention.keep_full);
+        (pruned, chain.clone())
+    };
+    chain.save_async().await?;
+    let files = pruned
+        .into_iter()
+        .flat_map(|r| [r.snapshot_path, r.mem_file_path])
+        .collect();
+    remove_files(inner, files, true).await;
+
+    // The snapshot is usable even if the VM failed to resume
+    resume.unwrap_or(Ok(()))?;
+    Ok(record)
+}
+
+// Removes `paths` on a blocking thread; `rotated` counts the removed files
+// in `files_removed`
+async fn remove_files(inner: &Arc<SchedulerInner>, paths: Vec<String>, rotated: bool) {
+    let cleanup = inner.clone();
+    let removed = tokio::task::spawn_blocking(move || {
+        for path in paths {
+            match std::fs::remove_file(&path) {
+                Ok(()) if !rotated => {}
+                Ok(()) => cleanup
+                    .status
+                    .send_modify(|status| status.files_removed += 1),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => cleanup.status.send_modify(|status| {
+                    status.last_cleanup_error = Some(format!("{}: {}", path, e));
+                }),
+            }
+        }
+    })
+    .await;
+    if let Err(e) = removed {
+        inner.status.send_modify(|status| {
+            status.last_cleanup_error = Some(format!("Removing snapshot files failed: {}", e));
+        });
+    }
+}
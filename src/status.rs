@@ -0,0 +1,119 @@
+//! One call that gathers what a VM looks like right now, for support
+//! tooling: instance state, machine config, balloon and the latest metrics.
+
+use crate::balloon::BalloonOperations;
+use crate::instance::InstanceOperations;
+use crate::machine::MachineConfigOperations;
+use crate::metrics::{read_latest_metrics, FirecrackerMetrics};
+use crate::models::{Balloon, BalloonStats, InstanceInfo, MachineConfig};
+use crate::FirecrackerError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// What [`StatusOperations::status_report`] gathers besides the API state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusOptions {
+    /// The VM's metrics file; its newest document is read as is, without
+    /// asking Firecracker to flush first
+    pub metrics_path: Option<PathBuf>,
+}
+
+/// A VM's current picture. Every part but the instance is `None` when it
+/// couldn't be had, with the reason in `unavailable`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VmStatusReport {
+    pub instance: InstanceInfo,
+    pub machine_config: Option<MachineConfig>,
+    pub balloon: Option<Balloon>,
+    pub balloon_stats: Option<BalloonStats>,
+    pub metrics: Option<FirecrackerMetrics>,
+    /// How much of its memory the guest is using, from the balloon stats
+    pub memory_used_percent: Option<f64>,
+    /// How much of the VM's memory the balloon currently holds
+    pub balloon_percent: Option<f64>,
+    /// Why each missing part is missing, by part name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub unavailable: BTreeMap<String, String>,
+}
+
+#[async_trait]
+pub trait StatusOperations {
+    /// Gathers the VM's status with concurrent requests. Only failing to
+    /// describe the instance is an error; any other part that fails, e.g.
+    /// the balloon when none is configured, is left out of the report.
+    async fn status_report(
+        &self,
+        options: StatusOptions,
+    ) -> Result<VmStatusReport, FirecrackerError>;
+}
+
+#[async_trait]
+impl StatusOperations for crate::FirecrackerClient {
+    async fn status_report(
+        &self,
+        options: StatusOptions,
+    ) -> Result<VmStatusReport, FirecrackerError> {
+        let metrics = async {
+            match &options.metrics_path {
+                Some(path) => Some(read_latest_metrics(path).await),
+                None => None,
+            }
+        };
+        let (instance, machine_config, balloon, balloon_stats, metrics) = tokio::join!(
+            self.describe_instance(),
+            self.get_machine_config(),
+            self.get_balloon_config(),
+            self.get_balloon_stats(),
+            metrics,
+        );
+
+        let mut unavailable = BTreeMap::new();
+        let machine_config = available("machine_config", machine_config, &mut unavailable);
+        let balloon = available("balloon", balloon, &mut unavailable);
+        let balloon_stats = available("balloon_stats", balloon_stats, &mut unavailable);
+        let metrics = metrics.and_then(|m| available("metrics", m, &mut unavailable));
+
+        let memory_used_percent = balloon_stats.as_ref().and_then(|stats| {
+            let total = stats.total_memory.filter(|total| *total > 0)?;
+            let available = stats.available_memory?;
+            Some(percent(
+                total.saturating_sub(available) as f64,
+                total as f64,
+            ))
+        });
+        let balloon_percent = machine_config
+            .as_ref()
+            .and_then(|config| config.mem_size_mib)
+            .filter(|mem| *mem > 0)
+            .zip(balloon_stats.as_ref())
+            .map(|(mem, stats)| percent(f64::from(stats.actual_mib), f64::from(mem)));
+
+        Ok(VmStatusReport {
+            instance: instance?,
+            machine_config,
+            balloon,
+            balloon_stats,
+            metrics,
+            memory_used_percent,
+            balloon_percent,
+            unavailable,
+        })
+    }
+}
+
+fn available<T>(
+    part: &str,
+    result: Result<T, FirecrackerError>,
+    unavailable: &mut BTreeMap<String, String>,
+) -> Option<T> {
+    result
+        .map_err(|err| unavailable.insert(part.to_string(), err.to_string()))
+        .ok()
+}
+
+// Rounded to two decimals so reports read well
+fn percent(part: f64, whole: f64) -> f64 {
+    (part / whole * 10_000.0).round() / 100.0
+}
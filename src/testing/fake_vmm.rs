@@ -0,0 +1,605 @@
+use crate::config::ConfigFile;
+use crate::error::FirecrackerError;
+use crate::models::FullVmConfiguration;
+use crate::testing::fixtures::{
+    NOT_SUPPORTED_AFTER_START, NOT_SUPPORTED_BEFORE_START, SNAPSHOT_LOAD_AFTER_CONFIG,
+};
+use crate::FirecrackerClient;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+
+/// The instance state a [`FakeVmm`] reports in `GET /`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeInstanceState {
+    NotStarted,
+    Running,
+    Paused,
+}
+
+impl FakeInstanceState {
+    /// The state as Firecracker spells it.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FakeInstanceState::NotStarted => "Not started",
+            FakeInstanceState::Running => "Running",
+            FakeInstanceState::Paused => "Paused",
+        }
+    }
+}
+
+/// Everything a [`FakeVmm`] has been told, for assertions.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct FakeVmmState {
+    pub instance: FakeInstanceState,
+    /// What `GET /vm/config` returns
+    pub config: FullVmConfiguration,
+    /// The MMDS data store
+    pub mmds: Value,
+    /// The body of `PUT /cpu-config`, which isn't part of `config`
+    pub cpu_config: Option<Value>,
+    /// Action types in the order they succeeded
+    pub actions: Vec<String>,
+    /// Paths of the snapshots created
+    pub snapshots: Vec<String>,
+    /// Every request as `"METHOD /path"`, failed ones included
+    pub requests: Vec<String>,
+    pub vmm_version: String,
+}
+
+impl Default for FakeVmmState {
+    fn default() -> Self {
+        Self {
+            instance: FakeInstanceState::NotStarted,
+            config: FullVmConfiguration::default(),
+            mmds: json!({}),
+            cpu_config: None,
+            actions: Vec::new(),
+            snapshots: Vec::new(),
+            requests: Vec::new(),
+            vmm_version: "1.7.0".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Vmm {
+    state: FakeVmmState,
+    // Requests to fail once, as (method, path, fault message)
+    faults: Vec<(String, String, String)>,
+}
+
+// The status and JSON body of a response
+type Reply = (u16, Option<Value>);
+
+/// An in-process stand-in for the Firecracker API, listening on a local TCP
+/// port. Unlike canned mocks it keeps state: configuration PUTs show up in
+/// `GET /vm/config`, boot-time-only endpoints are refused after
+/// `InstanceStart`, the VM moves between Not started, Running and Paused,
+/// and illegal requests get the fault messages Firecracker sends. Snapshots
+/// are written as files holding the configuration, which `PUT
+/// /snapshot/load` reads back. There is no guest: nothing boots, and
+/// `SendCtrlAltDel` is only recorded.
+///
+/// Dropping the fake stops the server.
+pub struct FakeVmm {
+    url: String,
+    vmm: Arc<Mutex<Vmm>>,
+    server: JoinHandle<()>,
+}
+
+impl FakeVmm {
+    /// Starts serving on a free port of 127.0.0.1.
+    pub async fn start() -> Result<Self, FirecrackerError> {
+        let unavailable =
+            |e: std::io::Error| FirecrackerError::Internal(format!("Fake VMM can't listen: {}", e));
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(unavailable)?;
+        let url = format!("http://{}", listener.local_addr().map_err(unavailable)?);
+        let vmm = Arc::new(Mutex::new(Vmm::default()));
+        let server = tokio::spawn(serve(listener, vmm.clone()));
+        Ok(Self { url, vmm, server })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A client talking to this fake.
+    pub async fn client(&self) -> Result<FirecrackerClient, FirecrackerError> {
+        FirecrackerClient::new(&self.url).await
+    }
+
+    /// A copy of the current state.
+    pub fn state(&self) -> FakeVmmState {
+        self.vmm.lock().unwrap().state.clone()
+    }
+
+    /// Changes the state behind the client's back, e.g. to pause the VM as
+    /// another client would.
+    pub fn update(&self, change: impl FnOnce(&mut FakeVmmState)) {
+        change(&mut self.vmm.lock().unwrap().state);
+    }
+
+    /// Fails the next `method` request to `path` (e.g. `PUT`,
+    /// `/drives/rootfs`) with a 400 and `fault_message`, without acting on
+    /// it.
+    pub fn fail_next(&self, method: &str, path: &str, fault_message: impl Into<String>) {
+        self.vmm.lock().unwrap().faults.push((
+            method.to_string(),
+            path.trim_matches('/').to_string(),
+            fault_message.into(),
+        ));
+    }
+}
+
+impl Drop for FakeVmm {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+// Connections are served until the listener task is aborted, which drops
+// and so aborts them too
+async fn serve(listener: TcpListener, vmm: Arc<Mutex<Vmm>>) {
+    let mut connections = JoinSet::new();
+    while let Ok((stream, _)) = listener.accept().await {
+        connections.spawn(serve_connection(stream, vmm.clone()));
+    }
+}
+
+async fn serve_connection(stream: TcpStream, vmm: Arc<Mutex<Vmm>>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    loop {
+        let Ok(Some((method, path, body))) = read_request(&mut reader).await else {
+            return;
+        };
+        let (status, body) = vmm.lock().unwrap().handle(&method, &path, &body);
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            match status {
+                200 => "OK",
+                204 => "No Content",
+                _ => "Bad Request",
+            },
+            body.len(),
+            body
+        );
+        if writer.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+// Reads one request; `None` once the client closed the connection
+async fn read_request<R>(reader: &mut R) -> std::io::Result<Option<(String, String, Vec<u8>)>>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some((method, path, body)))
+}
+
+fn fault(message: impl Into<String>) -> Reply {
+    (400, Some(json!({ "fault_message": message.into() })))
+}
+
+fn ok() -> Reply {
+    (204, None)
+}
+
+fn found<T: Serialize>(value: &T) -> Reply {
+    (
+        200,
+        Some(serde_json::to_value(value).unwrap_or(Value::Null)),
+    )
+}
+
+fn parse<T: DeserializeOwned>(body: &Value) -> Result<T, Reply> {
+    serde_json::from_value(body.clone()).map_err(|e| {
+        fault(format!(
+            "An error occurred when deserializing the json body of a request: {}.",
+            e
+        ))
+    })
+}
+
+// Applies an RFC 7396 merge patch to a serialized model and reads it back
+fn merged<T: Serialize + DeserializeOwned>(current: &T, patch: &Value) -> Result<T, Reply> {
+    fn merge(target: &mut Value, patch: &Value) {
+        let Value::Object(fields) = patch else {
+            *target = patch.clone();
+            return;
+        };
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        let target = target.as_object_mut().unwrap();
+        for (key, value) in fields {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+
+    let mut value = serde_json::to_value(current).unwrap_or(Value::Null);
+    merge(&mut value, patch);
+    parse(&value)
+}
+
+impl Vmm {
+    fn handle(&mut self, method: &str, path: &str, body: &[u8]) -> Reply {
+        let path = path.trim_matches('/');
+        self.state.requests.push(format!("{} /{}", method, path));
+
+        if let Some(i) = self
+            .faults
+            .iter()
+            .position(|(m, p, _)| m.eq_ignore_ascii_case(method) && p == path)
+        {
+            let (_, _, message) = self.faults.remove(i);
+            return fault(message);
+        }
+
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice(body) {
+                Ok(body) => body,
+                Err(e) => {
+                    return fault(format!(
+                        "An error occurred when deserializing the json body of a request: {}.",
+                        e
+                    ))
+                }
+            }
+        };
+        self.route(method, path, &body)
+            .unwrap_or_else(|reply| reply)
+    }
+
+    fn route(&mut self, method: &str, path: &str, body: &Value) -> Result<Reply, Reply> {
+        let (resource, id) = match path.split_once('/') {
+            Some((resource @ ("drives" | "network-interfaces"), id)) => (resource, Some(id)),
+            _ => (path, None),
+        };
+        let state = &mut self.state;
+        let config = &mut state.config;
+
+        match (method, resource, id) {
+            ("GET", "", None) => Ok(found(&json!({
+                "app_name": "Firecracker",
+                "id": "fake-vmm",
+                "state": state.instance.as_str(),
+                "vmm_version": state.vmm_version,
+            }))),
+            ("GET", "version", None) => {
+                Ok(found(&json!({ "firecracker_version": state.vmm_version })))
+            }
+            ("GET", "vm/config", None) => Ok(found(&ConfigFile::from(config.clone()))),
+            ("GET", "machine-config", None) => {
+                Ok(found(&config.machine_config.clone().unwrap_or_default()))
+            }
+            ("GET", "mmds", None) => Ok(found(&state.mmds)),
+            ("GET", "balloon", None) => match &config.balloon {
+                Some(balloon) => Ok(found(balloon)),
+                None => Err(fault("No balloon device found.")),
+            },
+            ("GET", "balloon/statistics", None) => {
+                let balloon = config
+                    .balloon
+                    .as_ref()
+                    .ok_or_else(|| fault("No balloon device found."))?;
+                if balloon.stats_polling_interval_s.unwrap_or(0) == 0 {
+                    return Err(fault("Statistics for the balloon device are not enabled."));
+                }
+                let pages = balloon.amount_mib * 256;
+                Ok(found(&json!({
+                    "target_mib": balloon.amount_mib,
+                    "actual_mib": balloon.amount_mib,
+                    "target_pages": pages,
+                    "actual_pages": pages,
+                })))
+            }
+
+            ("PUT", "mmds", None) => {
+                state.mmds = body.clone();
+                Ok(ok())
+            }
+            ("PATCH", "mmds", None) => {
+                state.mmds = merged(&state.mmds, body)?;
+                Ok(ok())
+            }
+            ("PATCH", "vm", None) => {
+                if state.instance == FakeInstanceState::NotStarted {
+                    return Err(fault(NOT_SUPPORTED_BEFORE_START));
+                }
+                state.instance = match body.get("state").and_then(Value::as_str) {
+                    Some("Paused") => FakeInstanceState::Paused,
+                    Some("Resumed") => FakeInstanceState::Running,
+                    _ => return Err(fault("Invalid VM state in the request body.")),
+                };
+                Ok(ok())
+            }
+            ("PUT", "actions", None) => self.action(body),
+            ("PUT", "snapshot/create", None) => self.create_snapshot(body),
+            ("PUT", "snapshot/load", None) => self.load_snapshot(body),
+
+            ("PATCH", "drives", Some(id)) => {
+                let started = state.instance != FakeInstanceState::NotStarted;
+                let drive = config
+                    .drives
+                    .iter_mut()
+                    .find(|d| d.drive_id == id)
+                    .ok_or_else(|| fault(format!("Invalid block device ID: {}.", id)))?;
+                if !started {
+                    return Err(fault(NOT_SUPPORTED_BEFORE_START));
+                }
+                *drive = merged(drive, body)?;
+                Ok(ok())
+            }
+            ("PATCH", "network-interfaces", Some(id)) => {
+                let started = state.instance != FakeInstanceState::NotStarted;
+                let iface = config
+                    .network_interfaces
+                    .iter_mut()
+                    .find(|i| i.iface_id == id)
+                    .ok_or_else(|| fault(format!("Invalid network interface ID: {}.", id)))?;
+                if !started {
+                    return Err(fault(NOT_SUPPORTED_BEFORE_START));
+                }
+                *iface = merged(iface, body)?;
+                Ok(ok())
+            }
+            ("PATCH", "balloon", None) => {
+                let balloon = config
+                    .balloon
+                    .as_mut()
+                    .ok_or_else(|| fault("No balloon device found."))?;
+                *balloon = merged(balloon, body)?;
+                Ok(ok())
+            }
+            ("PATCH", "balloon/statistics", None) => {
+                let balloon = config
+                    .balloon
+                    .as_mut()
+                    .ok_or_else(|| fault("No balloon device found."))?;
+                *balloon = merged(balloon, body)?;
+                Ok(ok())
+            }
+
+            // Everything else configures the VM before boot
+            ("PUT" | "PATCH", _, _) if state.instance != FakeInstanceState::NotStarted => {
+                if is_boot_time_resource(resource) {
+                    Err(fault(NOT_SUPPORTED_AFTER_START))
+                } else {
+                    Err(invalid_path(method, path))
+                }
+            }
+            ("PUT", "boot-source", None) => {
+                config.boot_source = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "machine-config", None) => {
+                config.machine_config = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PATCH", "machine-config", None) => {
+                let current = config.machine_config.clone().unwrap_or_default();
+                config.machine_config = Some(merged(&current, body)?);
+                Ok(ok())
+            }
+            ("PUT", "drives", Some(id)) => {
+                let drive: crate::models::Drive = parse(body)?;
+                if drive.drive_id != id {
+                    return Err(fault(
+                        "The id from the path does not match the id from the body!",
+                    ));
+                }
+                match config.drives.iter_mut().find(|d| d.drive_id == id) {
+                    Some(existing) => *existing = drive,
+                    None => config.drives.push(drive),
+                }
+                Ok(ok())
+            }
+            ("PUT", "network-interfaces", Some(id)) => {
+                let iface: crate::models::NetworkInterface = parse(body)?;
+                if iface.iface_id != id {
+                    return Err(fault(
+                        "The id from the path does not match the id from the body!",
+                    ));
+                }
+                match config
+                    .network_interfaces
+                    .iter_mut()
+                    .find(|i| i.iface_id == id)
+                {
+                    Some(existing) => *existing = iface,
+                    None => config.network_interfaces.push(iface),
+                }
+                Ok(ok())
+            }
+            ("PUT", "balloon", None) => {
+                config.balloon = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "logger", None) => {
+                config.logger = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "metrics", None) => {
+                config.metrics = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "mmds/config", None) => {
+                config.mmds_config = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "vsock", None) => {
+                config.vsock = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "entropy", None) => {
+                config.entropy = Some(parse(body)?);
+                Ok(ok())
+            }
+            ("PUT", "cpu-config", None) => {
+                state.cpu_config = Some(body.clone());
+                Ok(ok())
+            }
+            _ => Err(invalid_path(method, path)),
+        }
+    }
+
+    fn action(&mut self, body: &Value) -> Result<Reply, Reply> {
+        let state = &mut self.state;
+        let action = body
+            .get("action_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        match (action, state.instance) {
+            ("InstanceStart", FakeInstanceState::NotStarted) => {
+                if state.config.boot_source.is_none() {
+                    return Err(fault(
+                        "Start microvm error: Cannot start microvm without kernel configuration.",
+                    ));
+                }
+                state.instance = FakeInstanceState::Running;
+            }
+            ("InstanceStart", _) => return Err(fault(NOT_SUPPORTED_AFTER_START)),
+            ("SendCtrlAltDel", FakeInstanceState::NotStarted) => {
+                return Err(fault(NOT_SUPPORTED_BEFORE_START))
+            }
+            ("SendCtrlAltDel", _) => {}
+            ("FlushMetrics", _) => {
+                if state.config.metrics.is_none() {
+                    return Err(fault(
+                        "Flush metrics error: The metrics system was not initialized.",
+                    ));
+                }
+            }
+            _ => {
+                return Err(fault(format!(
+                    "An error occurred when deserializing the json body of a request: unknown \
+                     variant `{}`.",
+                    action
+                )))
+            }
+        }
+        state.actions.push(action.to_string());
+        Ok(ok())
+    }
+
+    fn create_snapshot(&mut self, body: &Value) -> Result<Reply, Reply> {
+        let params: crate::snapshot::SnapshotCreateParams = parse(body)?;
+        match self.state.instance {
+            FakeInstanceState::Paused => {}
+            FakeInstanceState::NotStarted => return Err(fault(NOT_SUPPORTED_BEFORE_START)),
+            FakeInstanceState::Running => {
+                return Err(fault(
+                    "Create snapshot error: The microVM must be paused to create a snapshot.",
+                ))
+            }
+        }
+
+        let contents =
+            serde_json::to_vec(&ConfigFile::from(self.state.config.clone())).unwrap_or_default();
+        std::fs::write(&params.snapshot_path, contents)
+            .and_then(|()| std::fs::write(&params.mem_file_path, b"fake guest memory"))
+            .map_err(|e| fault(format!("Create snapshot error: {}", e)))?;
+        self.state.snapshots.push(params.snapshot_path);
+        Ok(ok())
+    }
+
+    fn load_snapshot(&mut self, body: &Value) -> Result<Reply, Reply> {
+        let params: crate::snapshot::SnapshotLoadParams = parse(body)?;
+        if self.state.instance != FakeInstanceState::NotStarted
+            || self.state.config != FullVmConfiguration::default()
+        {
+            return Err(fault(SNAPSHOT_LOAD_AFTER_CONFIG));
+        }
+
+        let config: ConfigFile = std::fs::read(&params.snapshot_path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| serde_json::from_slice(&contents).map_err(|e| e.to_string()))
+            .map_err(|e| fault(format!("Load snapshot error: {}", e)))?;
+        self.state.config = config.into();
+        for overridden in &params.network_overrides {
+            if let Some(iface) = self
+                .state
+                .config
+                .network_interfaces
+                .iter_mut()
+                .find(|i| i.iface_id == overridden.iface_id)
+            {
+                iface.host_dev_name = overridden.host_dev_name.clone();
+            }
+        }
+        self.state.instance = if params.resume_vm == Some(true) {
+            FakeInstanceState::Running
+        } else {
+            FakeInstanceState::Paused
+        };
+        Ok(ok())
+    }
+}
+
+fn is_boot_time_resource(resource: &str) -> bool {
+    matches!(
+        resource,
+        "boot-source"
+            | "machine-config"
+            | "drives"
+            | "network-interfaces"
+            | "balloon"
+            | "logger"
+            | "metrics"
+            | "mmds/config"
+            | "vsock"
+            | "entropy"
+            | "cpu-config"
+    )
+}
+
+fn invalid_path(method: &str, path: &str) -> Reply {
+    fault(format!(
+        "Invalid request method and/or path: {} /{}.",
+        method, path
+    ))
+}
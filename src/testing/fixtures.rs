@@ -0,0 +1,105 @@
+//! Ready-made payloads for tests, so they don't have to hand-write JSON
+//! that drifts from what Firecracker sends. Each raw sample notes which
+//! release it follows; the typed functions parse them.
+
+use crate::config::ConfigFile;
+use crate::metrics::FirecrackerMetrics;
+use crate::models::{BalloonStats, BootSource, Drive, FullVmConfiguration, MachineConfig};
+
+/// `GET /` from Firecracker 1.0, before boot; 1.0 doesn't report `app_name`.
+pub const INSTANCE_INFO_V1_0: &str = include_str!("fixtures/instance_info_v1.0.json");
+/// `GET /` from Firecracker 1.7, of a running VM.
+pub const INSTANCE_INFO_V1_7: &str = include_str!("fixtures/instance_info_v1.7.json");
+
+/// `GET /machine-config` from Firecracker 1.0, before `huge_pages`.
+pub const MACHINE_CONFIG_V1_0: &str = include_str!("fixtures/machine_config_v1.0.json");
+/// `GET /machine-config` from Firecracker 1.7, with dirty page tracking on.
+pub const MACHINE_CONFIG_V1_7: &str = include_str!("fixtures/machine_config_v1.7.json");
+
+/// `GET /vm/config` from Firecracker 1.4: one drive, one interface, no
+/// MMDS and no `huge_pages` or `cpu-config` yet.
+pub const VM_CONFIG_V1_4: &str = include_str!("fixtures/vm_config_v1.4.json");
+/// `GET /vm/config` from Firecracker 1.7, with MMDS V2 on `eth0`. Unset
+/// sections are `null`.
+pub const VM_CONFIG_V1_7: &str = include_str!("fixtures/vm_config_v1.7.json");
+/// `GET /vm/config` from Firecracker 1.7 before anything is configured: the
+/// boot source is there with an empty kernel path, the machine config holds
+/// the defaults.
+pub const VM_CONFIG_V1_7_PRE_BOOT: &str = include_str!("fixtures/vm_config_v1.7_pre_boot.json");
+
+/// `GET /balloon` from Firecracker 1.0, of an empty balloon, without
+/// `deflate_on_oom`, which older releases leave out.
+pub const BALLOON_V1_0: &str = include_str!("fixtures/balloon_v1.0.json");
+/// `GET /balloon` from Firecracker 1.7, with statistics every 5 seconds.
+pub const BALLOON_V1_7: &str = include_str!("fixtures/balloon_v1.7.json");
+
+/// `GET /balloon/statistics` from Firecracker 1.0, with the basic counters
+/// only.
+pub const BALLOON_STATS_V1_0: &str = include_str!("fixtures/balloon_stats_v1.0.json");
+/// `GET /balloon/statistics` from Firecracker 1.7, which adds the hugetlb,
+/// OOM and reclaim counters, of a 1 GiB guest with 128 MiB in the balloon.
+pub const BALLOON_STATS_V1_7: &str = include_str!("fixtures/balloon_stats_v1.7.json");
+
+/// A metrics document as flushed by Firecracker 1.4, on a single line, with
+/// per-device groups for `rootfs` and `eth0`.
+pub const METRICS_V1_4: &str = include_str!("fixtures/metrics_v1.4.json");
+/// A metrics document as flushed by Firecracker 1.7, on a single line, with
+/// a second `scratch` drive backed by vhost-user and the `entropy` group.
+pub const METRICS_V1_7: &str = include_str!("fixtures/metrics_v1.7.json");
+/// The flush after [`METRICS_V1_7`], a minute later. Counters hold only
+/// what happened since, so several are lower than before; the startup times
+/// in `api_server` are kept.
+pub const METRICS_V1_7_NEXT: &str = include_str!("fixtures/metrics_v1.7_next.json");
+
+/// Firecracker's fault message for boot-time-only requests after
+/// `InstanceStart`.
+pub const NOT_SUPPORTED_AFTER_START: &str =
+    "The requested operation is not supported after starting the microVM.";
+/// Firecracker's fault message for runtime-only requests, such as pausing,
+/// before `InstanceStart`.
+pub const NOT_SUPPORTED_BEFORE_START: &str =
+    "The requested operation is not supported before starting the microVM.";
+/// Firecracker's fault message for `PUT /snapshot/load` on a VMM that was
+/// already configured.
+pub const SNAPSHOT_LOAD_AFTER_CONFIG: &str =
+    "Loading a microVM snapshot not allowed after configuring boot-specific resources.";
+
+/// The body of a 400 response carrying `message`, as Firecracker sends it.
+pub fn fault_body(message: &str) -> String {
+    serde_json::json!({ "fault_message": message }).to_string()
+}
+
+/// The least a VM needs to boot: a kernel, a root drive and its size.
+pub fn bootable_config() -> FullVmConfiguration {
+    FullVmConfiguration {
+        boot_source: Some(BootSource::new("/images/vmlinux")),
+        machine_config: Some(MachineConfig::new(1, 128)),
+        drives: vec![Drive {
+            is_root_device: true,
+            ..Drive::new("rootfs", "/images/rootfs.ext4")
+        }],
+        ..Default::default()
+    }
+}
+
+/// [`VM_CONFIG_V1_7`], parsed.
+pub fn vm_config_v1_7() -> FullVmConfiguration {
+    serde_json::from_str::<ConfigFile>(VM_CONFIG_V1_7)
+        .expect("fixture is valid")
+        .into()
+}
+
+/// [`BALLOON_STATS_V1_7`], parsed.
+pub fn balloon_stats_v1_7() -> BalloonStats {
+    serde_json::from_str(BALLOON_STATS_V1_7).expect("fixture is valid")
+}
+
+/// [`METRICS_V1_7`], parsed.
+pub fn metrics_v1_7() -> FirecrackerMetrics {
+    FirecrackerMetrics::from_json_str(METRICS_V1_7).expect("fixture is valid")
+}
+
+/// [`METRICS_V1_7_NEXT`], parsed.
+pub fn metrics_v1_7_next() -> FirecrackerMetrics {
+    FirecrackerMetrics::from_json_str(METRICS_V1_7_NEXT).expect("fixture is valid")
+}
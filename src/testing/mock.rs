@@ -0,0 +1,149 @@
+//! [mockito] expectations for the endpoints this client calls, so tests
+//! don't have to spell out paths, methods and bodies themselves.
+//!
+//! Every helper registers its mock on `server` and returns it, expecting one
+//! matching request unless noted otherwise. A request whose body doesn't
+//! match gets mockito's 501, so the client call fails and
+//! [`Mock::assert`] reports the miss.
+//!
+//! ```no_run
+//! # async fn example() {
+//! use firecracker_http_client::action::ActionType;
+//! use firecracker_http_client::testing::mock::{expect_action, expect_put_drive};
+//! use serde_json::json;
+//!
+//! let mut server = mockito::Server::new_async().await;
+//! let drive = expect_put_drive(
+//!     &mut server,
+//!     "rootfs",
+//!     mockito::Matcher::PartialJson(json!({"is_root_device": true})),
+//! );
+//! let start = expect_action(&mut server, ActionType::InstanceStart);
+//! // ... exercise the code under test against server.url() ...
+//! drive.assert();
+//! start.assert();
+//! # }
+//! ```
+
+use crate::action::ActionType;
+use crate::models::VmState;
+use crate::paths;
+use crate::testing::fixtures::fault_body;
+use mockito::{Matcher, Mock, Server};
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Matches a body equal to `value` as the client serializes it, e.g. a
+/// [`Drive`](crate::models::Drive) passed to `put_drive`.
+pub fn json_body<T: Serialize>(value: &T) -> Matcher {
+    Matcher::Json(serde_json::to_value(value).expect("value serializes to JSON"))
+}
+
+/// A `PUT` at `path` with a body matching `body`, answered with a 204.
+pub fn expect_put(server: &mut Server, path: &str, body: impl Into<Matcher>) -> Mock {
+    expect_no_content(server, "PUT", path, body.into())
+}
+
+/// A `PATCH` at `path` with a body matching `body`, answered with a 204.
+pub fn expect_patch(server: &mut Server, path: &str, body: impl Into<Matcher>) -> Mock {
+    expect_no_content(server, "PATCH", path, body.into())
+}
+
+/// `PUT /drives/{drive_id}`.
+pub fn expect_put_drive(server: &mut Server, drive_id: &str, body: impl Into<Matcher>) -> Mock {
+    expect_put(server, &format!("/{}", paths::drive(drive_id)), body)
+}
+
+/// `PATCH /drives/{drive_id}`.
+pub fn expect_patch_drive(server: &mut Server, drive_id: &str, body: impl Into<Matcher>) -> Mock {
+    expect_patch(server, &format!("/{}", paths::drive(drive_id)), body)
+}
+
+/// `PUT /network-interfaces/{iface_id}`.
+pub fn expect_put_network_interface(
+    server: &mut Server,
+    iface_id: &str,
+    body: impl Into<Matcher>,
+) -> Mock {
+    let path = format!("/{}", paths::network_interface(iface_id));
+    expect_put(server, &path, body)
+}
+
+/// `PUT /boot-source`.
+pub fn expect_put_boot_source(server: &mut Server, body: impl Into<Matcher>) -> Mock {
+    expect_put(server, &format!("/{}", paths::BOOT_SOURCE), body)
+}
+
+/// `PUT /machine-config`.
+pub fn expect_put_machine_config(server: &mut Server, body: impl Into<Matcher>) -> Mock {
+    expect_put(server, &format!("/{}", paths::MACHINE_CONFIG), body)
+}
+
+/// `PUT /actions` for `action`, e.g. the `InstanceStart` that boots the VM.
+pub fn expect_action(server: &mut Server, action: ActionType) -> Mock {
+    expect_put(
+        server,
+        &format!("/{}", paths::ACTIONS),
+        Matcher::Json(serde_json::json!({ "action_type": action.as_str() })),
+    )
+}
+
+/// `PATCH /vm` moving the VM to `state`.
+pub fn expect_vm_state(server: &mut Server, state: VmState) -> Mock {
+    expect_patch(
+        server,
+        &format!("/{}", paths::VM),
+        Matcher::Json(serde_json::json!({ "state": state })),
+    )
+}
+
+/// `GET /` answered with each of `states` in turn, e.g. `["Not started",
+/// "Running"]`, and the last one from then on. Expects at least one request
+/// per state.
+///
+/// # Panics
+///
+/// If `states` is empty.
+pub fn expect_instance_state_sequence(server: &mut Server, states: &[&str]) -> Mock {
+    assert!(!states.is_empty(), "the sequence needs at least one state");
+    let states: Vec<String> = states.iter().map(|s| s.to_string()).collect();
+    let polls = AtomicUsize::new(0);
+    let expected = states.len();
+    server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body_from_request(move |_| {
+            let poll = polls.fetch_add(1, Ordering::Relaxed);
+            let state = &states[poll.min(states.len() - 1)];
+            serde_json::json!({
+                "id": "anonymous-instance",
+                "state": state,
+                "vmm_version": "1.7.0",
+                "app_name": "Firecracker",
+            })
+            .to_string()
+            .into_bytes()
+        })
+        .expect_at_least(expected)
+        .create()
+}
+
+/// A `method` request at `path`, with any body, refused with a 400 and
+/// Firecracker's `fault_message` body.
+pub fn expect_fault(server: &mut Server, method: &str, path: &str, message: &str) -> Mock {
+    server
+        .mock(method, path)
+        .with_status(400)
+        .with_header("content-type", "application/json")
+        .with_body(fault_body(message))
+        .create()
+}
+
+fn expect_no_content(server: &mut Server, method: &str, path: &str, body: Matcher) -> Mock {
+    server
+        .mock(method, path)
+        .match_body(body)
+        .with_status(204)
+        .create()
+}
@@ -0,0 +1,8 @@
+//! Helpers for testing code built on this crate, behind the `testing`
+//! feature.
+
+mod fake_vmm;
+pub mod fixtures;
+pub mod mock;
+
+pub use fake_vmm::{FakeInstanceState, FakeVmm, FakeVmmState};
@@ -1,35 +1,45 @@
-use std::fs::File;
-use mockito::{Server};
+use mockito::{Matcher, Server, ServerGuard};
 use serde_json::json;
-use validator::Validate;
 
+use crate::action::InstanceActionInfo;
+use crate::instance::InstanceOperations;
+use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams, SnapshotType};
+use crate::validation::ValidationMode;
+use crate::version::VersionOperations;
 use crate::{
-    FirecrackerClient, FirecrackerError,
-    Drive, NetworkInterface, Logger, Metrics,
-    SnapshotCreateParams, SnapshotLoadParams,
-    Vsock, InstanceActionInfo
+    Drive, DriveOperations, FirecrackerClient, FirecrackerError, NetworkInterface,
+    NetworkInterfaceOperations, SnapshotOperations,
 };
 
-fn setup_mock_server() -> Server {
-    Server::new()
+// These tests are about the HTTP exchange, so paths are only checked for syntax
+async fn setup_mock_server() -> (ServerGuard, FirecrackerClient) {
+    let server = Server::new_async().await;
+    let client = FirecrackerClient::new(&server.url())
+        .await
+        .unwrap()
+        .with_validation_mode(ValidationMode::Syntactic);
+    (server, client)
 }
 
 #[tokio::test]
 async fn test_client_instance_info() {
-    let server = setup_mock_server();
-    let mock = mock("GET", "/")
+    let (mut server, client) = setup_mock_server().await;
+    let mock = server
+        .mock("GET", "/")
         .with_status(200)
         .with_header("content-type", "application/json")
-        .with_body(json!({
-            "id": "test-instance",
-            "state": "Running",
-            "memory_size": 512,
-            "vcpu_count": 1
-        }).to_string())
+        .with_body(
+            json!({
+                "app_name": "Firecracker",
+                "id": "test-instance",
+                "state": "Running",
+                "vmm_version": "1.7.0"
+            })
+            .to_string(),
+        )
         .create();
 
-    let client = FirecrackerClient::new(&server.url()).unwrap();
-    let info = client.get_instance_info().await.unwrap();
+    let info = client.describe_instance().await.unwrap();
     assert_eq!(info.id, "test-instance");
     assert_eq!(info.state, "Running");
 
@@ -38,135 +48,150 @@ async fn test_client_instance_info() {
 
 #[tokio::test]
 async fn test_client_error_handling() {
-    let server = setup_mock_server();
+    let (mut server, client) = setup_mock_server().await;
 
     // Test 400 Bad Request
-    let mock_400 = mock("PUT", "/actions")
+    let mock_400 = server
+        .mock("PUT", "/actions")
         .with_status(400)
         .with_header("content-type", "application/json")
-        .with_body(json!({
-            "error": "Invalid request"
-        }).to_string())
+        .with_body(
+            json!({
+                "fault_message": "Invalid request"
+            })
+            .to_string(),
+        )
         .create();
 
-    let client = FirecrackerClient::new(&server.url()).unwrap();
-    let action = InstanceActionInfo {
-        action_type: "Invalid".to_string(),
-    };
-    match client.instance_action(action).await.unwrap_err() {
+    let action = InstanceActionInfo::new("FlushMetrics");
+    match client.create_sync_action(&action).await.unwrap_err() {
         FirecrackerError::Api { status_code, .. } => assert_eq!(status_code, 400),
-        _ => panic!("Expected Api error"),
+        other => panic!("Expected Api error, got {:?}", other),
     }
 
     mock_400.assert();
 
     // Test 404 Not Found
-    let mock_404 = mock("GET", "/non-existent")
+    let mock_404 = server
+        .mock("GET", "/")
         .with_status(404)
         .with_header("content-type", "application/json")
-        .with_body(json!({
-            "error": "Not found"
-        }).to_string())
+        .with_body(
+            json!({
+                "fault_message": "Not found"
+            })
+            .to_string(),
+        )
         .create();
 
-    match client.get_instance_info().await.unwrap_err() {
+    match client.describe_instance().await.unwrap_err() {
         FirecrackerError::Api { status_code, .. } => assert_eq!(status_code, 404),
-        _ => panic!("Expected Api error"),
+        other => panic!("Expected Api error, got {:?}", other),
     }
 
     mock_404.assert();
 
     // Test connection error
-    let client = FirecrackerClient::new("http://invalid-host:1234").unwrap();
-    match client.get_instance_info().await.unwrap_err() {
+    let client = FirecrackerClient::new("http://127.0.0.1:1").await.unwrap();
+    match client.describe_instance().await.unwrap_err() {
         FirecrackerError::HttpClient(_) => (),
-        _ => panic!("Expected HttpClient error"),
+        other => panic!("Expected HttpClient error, got {:?}", other),
     }
 }
 
 #[tokio::test]
 async fn test_client_drive_operations() {
-    let server = setup_mock_server();
-    let mock = mock("PUT", "/drives/rootfs")
-        .match_body(json!({
+    let (mut server, client) = setup_mock_server().await;
+    let mock = server
+        .mock("PUT", "/drives/rootfs")
+        .match_body(Matcher::Json(json!({
             "drive_id": "rootfs",
             "path_on_host": "/path/to/rootfs",
             "is_root_device": true,
             "is_read_only": false
-        }).to_string())
+        })))
         .with_status(204)
         .create();
 
-    let client = FirecrackerClient::new(&server.url()).unwrap();
     let drive = Drive {
         drive_id: "rootfs".to_string(),
         path_on_host: "/path/to/rootfs".to_string(),
         is_root_device: true,
         is_read_only: false,
+        ..Default::default()
     };
-    client.put_drive(&drive).await.unwrap();
+    client.put_drive("rootfs", &drive).await.unwrap();
 
     mock.assert();
 }
 
 #[tokio::test]
 async fn test_client_network_operations() {
-    let server = setup_mock_server();
-    let mock = mock("PUT", "/network-interfaces/eth0")
-        .match_body(json!({
+    let (mut server, client) = setup_mock_server().await;
+    let mock = server
+        .mock("PUT", "/network-interfaces/eth0")
+        .match_body(Matcher::Json(json!({
             "iface_id": "eth0",
             "host_dev_name": "tap0"
-        }).to_string())
+        })))
         .with_status(204)
         .create();
 
-    let client = FirecrackerClient::new(&server.url()).unwrap();
     let interface = NetworkInterface {
         iface_id: "eth0".to_string(),
         host_dev_name: "tap0".to_string(),
+        ..Default::default()
     };
-    client.put_network_interface(&interface).await.unwrap();
+    client
+        .put_network_interface("eth0", &interface)
+        .await
+        .unwrap();
 
     mock.assert();
 }
 
 #[tokio::test]
 async fn test_client_snapshot_operations() {
-    let server = setup_mock_server();
-    let mock_create = mock("PUT", "/snapshot/create")
-        .match_body(json!({
+    let (mut server, client) = setup_mock_server().await;
+    let mock_create = server
+        .mock("PUT", "/snapshot/create")
+        .match_body(Matcher::Json(json!({
             "snapshot_type": "Full",
             "snapshot_path": "/path/to/snapshot",
             "mem_file_path": "/path/to/mem",
             "version": "1.0"
-        }).to_string())
+        })))
         .with_status(204)
         .create();
 
-    let client = FirecrackerClient::new(&server.url()).unwrap();
     let create_params = SnapshotCreateParams {
-        snapshot_type: Some("Full".to_string()),
+        snapshot_type: Some(SnapshotType::Full),
         snapshot_path: "/path/to/snapshot".to_string(),
         mem_file_path: "/path/to/mem".to_string(),
         version: Some("1.0".to_string()),
+        allow_overwrite: false,
     };
     client.create_snapshot(&create_params).await.unwrap();
 
     mock_create.assert();
 
-    let mock_load = mock("PUT", "/snapshot/load")
-        .match_body(json!({
+    let mock_load = server
+        .mock("PUT", "/snapshot/load")
+        .match_body(Matcher::Json(json!({
             "snapshot_path": "/path/to/snapshot",
             "mem_file_path": "/path/to/mem",
             "enable_diff_snapshots": false
-        }).to_string())
+        })))
         .with_status(204)
         .create();
 
     let load_params = SnapshotLoadParams {
         snapshot_path: "/path/to/snapshot".to_string(),
-        mem_file_path: "/path/to/mem".to_string(),
+        mem_file_path: Some("/path/to/mem".to_string()),
+        mem_backend: None,
         enable_diff_snapshots: Some(false),
+        resume_vm: None,
+        network_overrides: Vec::new(),
     };
     client.load_snapshot(&load_params).await.unwrap();
 
@@ -175,20 +200,21 @@ async fn test_client_snapshot_operations() {
 
 #[tokio::test]
 async fn test_client_version() {
-    let server = setup_mock_server();
-    let mock = mock("GET", "/version")
+    let (mut server, client) = setup_mock_server().await;
+    let mock = server
+        .mock("GET", "/version")
         .with_status(200)
         .with_header("content-type", "application/json")
-        .with_body(json!({
-            "firecracker_version": "1.0.0",
-            "api_version": "v1.0"
-        }).to_string())
+        .with_body(
+            json!({
+                "firecracker_version": "1.0.0"
+            })
+            .to_string(),
+        )
         .create();
 
-    let client = FirecrackerClient::new(&server.url()).unwrap();
     let version = client.get_version().await.unwrap();
     assert_eq!(version.firecracker_version, "1.0.0");
-    assert_eq!(version.api_version, "v1.0");
 
     mock.assert();
 }
@@ -1,11 +1,15 @@
+mod client_tests;
+mod validation_tests;
+
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::balloon::BalloonStatsUpdate;
     use crate::cpu::CpuConfig;
-    use crate::entropy::EntropyDevice;
-    use crate::logger::Logger;
     use crate::metrics::Metrics;
+    use crate::models::EntropyDevice;
     use crate::models::Vsock;
+    use crate::models::{LogLevel, Logger};
     use crate::vm::VmConfig;
     use crate::{
         balloon::BalloonOperations, cpu::CpuConfigOperations, entropy::EntropyDeviceOperations,
@@ -14,6 +18,7 @@ mod tests {
     };
     use mockito::{Server, ServerGuard};
     use serde_json::Value;
+    use validator::Validate;
 
     async fn create_test_client() -> (ServerGuard, FirecrackerClient) {
         let server = Server::new_async().await;
@@ -28,9 +33,10 @@ mod tests {
 
         let logger = Logger {
             log_path: "/tmp/firecracker.log".to_string(),
-            level: Some("Info".to_string()),
+            level: Some(LogLevel::Info),
             show_level: Some(true),
             show_log_origin: Some(true),
+            module: None,
         };
 
         client.put_logger(&logger).await.unwrap();
@@ -43,9 +49,10 @@ mod tests {
 
         let logger = Logger {
             log_path: "/tmp/firecracker.log".to_string(),
-            level: Some("Info".to_string()),
+            level: Some(LogLevel::Info),
             show_level: Some(true),
             show_log_origin: Some(true),
+            module: None,
         };
 
         client.put_logger(&logger).await.unwrap();
@@ -56,27 +63,72 @@ mod tests {
         let (_, client) = create_test_client().await;
         let logger = Logger {
             log_path: "invalid/path".to_string(),
-            level: Some("Info".to_string()),
+            level: Some(LogLevel::Info),
             show_level: Some(true),
             show_log_origin: Some(true),
+            module: None,
         };
 
         let result = client.put_logger(&logger).await;
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_logger_invalid_level() {
+        let result = serde_json::from_str::<Logger>(
+            r#"{"log_path": "/tmp/firecracker.log", "level": "InvalidLevel"}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_log_level_serde() {
+        let logger: Logger = serde_json::from_str(
+            r#"{"log_path": "/tmp/firecracker.log", "level": "debug", "module": "api_server"}"#,
+        )
+        .unwrap();
+        assert_eq!(logger.level, Some(LogLevel::Debug));
+
+        let json = serde_json::to_value(&logger).unwrap();
+        assert_eq!(json["level"], "Debug");
+        assert_eq!(json["module"], "api_server");
+
+        assert_eq!(
+            serde_json::to_value(LogLevel::Warning).unwrap(),
+            Value::String("Warning".to_string())
+        );
+        assert_eq!(
+            serde_json::from_str::<LogLevel>(r#""Off""#).unwrap(),
+            LogLevel::Off
+        );
+    }
+
     #[tokio::test]
-    async fn test_logger_invalid_level() {
-        let (_, client) = create_test_client().await;
+    async fn test_logger_module_filter() {
+        let (mut server, client) = create_test_client().await;
+        let _m = server
+            .mock("PUT", "/logger")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "level": "Trace",
+                "module": "api_server::request"
+            })))
+            .with_status(204)
+            .create();
+
         let logger = Logger {
             log_path: "/tmp/firecracker.log".to_string(),
-            level: Some("InvalidLevel".to_string()),
-            show_level: Some(true),
-            show_log_origin: Some(true),
+            level: Some(LogLevel::Trace),
+            show_level: None,
+            show_log_origin: None,
+            module: Some("api_server::request".to_string()),
         };
+        client.put_logger(&logger).await.unwrap();
 
-        let result = client.put_logger(&logger).await;
-        assert!(result.is_err());
+        let invalid = Logger {
+            module: Some("api server".to_string()),
+            ..logger
+        };
+        assert!(client.put_logger(&invalid).await.is_err());
     }
 
     #[tokio::test]
@@ -166,6 +218,68 @@ mod tests {
         client.put_vsock(&vsock).await.unwrap();
     }
 
+    #[test]
+    fn test_vsock_guest_cid_bounds() {
+        let mut vsock = Vsock {
+            guest_cid: 3,
+            uds_path: "/tmp/vsock".to_string(),
+            vsock_id: None,
+        };
+        assert!(vsock.validate().is_ok());
+
+        vsock.guest_cid = 2;
+        let errors = vsock.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("guest_cid"));
+
+        vsock.guest_cid = u32::MAX;
+        assert!(vsock.validate().is_ok());
+    }
+
+    #[test]
+    fn test_vsock_uds_path_bounds() {
+        let mut vsock = Vsock {
+            guest_cid: 3,
+            uds_path: format!("/{}", "a".repeat(106)),
+            vsock_id: None,
+        };
+        assert!(vsock.validate().is_ok());
+
+        vsock.uds_path = format!("/{}", "a".repeat(107));
+        let errors = vsock.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("uds_path"));
+
+        vsock.uds_path = "relative/vsock.sock".to_string();
+        assert!(vsock.validate().is_err());
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_put_vsock_checks_parent_per_policy() {
+        let (mut server, client) = create_test_client().await;
+        let mock = server
+            .mock("PUT", "/vsock")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let vsock = Vsock {
+            guest_cid: 3,
+            uds_path: "/nonexistent-dir/vsock.sock".to_string(),
+            vsock_id: None,
+        };
+
+        match client.put_vsock(&vsock).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                assert!(errors.field_errors().contains_key("uds_path"))
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+        client.put_vsock(&vsock).await.unwrap();
+        mock.assert();
+    }
+
     #[tokio::test]
     async fn test_entropy_device() {
         let (mut server, client) = create_test_client().await;
@@ -178,11 +292,15 @@ mod tests {
 
     #[tokio::test]
     async fn test_instance_actions() {
+        use crate::action::ActionType;
+        use crate::testing::mock::expect_action;
+
         let (mut server, client) = create_test_client().await;
-        let _m = server.mock("PUT", "/actions").with_status(204).create();
+        let m = expect_action(&mut server, ActionType::InstanceStart);
 
         let action = crate::action::InstanceActionInfo::new("InstanceStart");
         client.create_sync_action(&action).await.unwrap();
+        m.assert();
     }
 
     #[tokio::test]
@@ -212,4 +330,8666 @@ mod tests {
         let info = client.get_vm_info().await.unwrap();
         assert!(!info.state.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_mmds_rejects_string_root() {
+        let (_, client) = create_test_client().await;
+
+        let result = client
+            .put_mmds(Value::String("Hello, MMDS!".to_string()))
+            .await;
+        match result {
+            Err(crate::FirecrackerError::Validation(e)) => {
+                assert!(e.to_string().contains("got string"));
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mmds_rejects_array_root() {
+        let (_, client) = create_test_client().await;
+
+        let result = client.patch_mmds(serde_json::json!(["a", "b"])).await;
+        match result {
+            Err(crate::FirecrackerError::Validation(e)) => {
+                assert!(e.to_string().contains("got array"));
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mmds_accepts_object_root() {
+        let (mut server, client) = create_test_client().await;
+        let _m = server.mock("PATCH", "/mmds").with_status(204).create();
+
+        client
+            .patch_mmds(serde_json::json!({ "latest": { "meta-data": {} } }))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mmds_root_check_can_be_disabled() {
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_strict_mmds_root(false);
+        let _m = server.mock("PUT", "/mmds").with_status(204).create();
+
+        client
+            .put_mmds(Value::String("Hello, MMDS!".to_string()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mmds_delete_keys() {
+        let (mut server, client) = create_test_client().await;
+        let _m = server
+            .mock("PATCH", "/mmds")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "latest": { "meta-data": { "boot-done": null, "a/b": null } },
+                "tags": null
+            })))
+            .with_status(204)
+            .create();
+
+        client
+            .delete_mmds_keys(&[
+                "/latest/meta-data/boot-done",
+                "/latest/meta-data/a~1b",
+                "/tags",
+                "/tags/owner",
+            ])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mmds_delete_missing_key_is_noop() {
+        let (mut server, client) = create_test_client().await;
+        let _m = server
+            .mock("PATCH", "/mmds")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({ "missing": null }),
+            ))
+            .with_status(204)
+            .create();
+
+        client.delete_mmds_keys(&["/missing"]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mmds_delete_rejects_root_and_malformed_pointers() {
+        let (_, client) = create_test_client().await;
+
+        for pointer in ["", "latest", "/a~2b", "/a~"] {
+            let result = client.delete_mmds_keys(&[pointer]).await;
+            assert!(
+                matches!(result, Err(crate::FirecrackerError::Validation(_))),
+                "pointer {:?} should be rejected",
+                pointer
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mmds_tags_are_merged_surgically() {
+        use std::collections::HashMap;
+
+        let (mut server, client) = create_test_client().await;
+        let set = server
+            .mock("PATCH", "/mmds")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "fc-client": { "tags": { "owner": "team-a", "created-at": "2024-05-01" } }
+            })))
+            .with_status(204)
+            .create();
+        let remove = server
+            .mock("PATCH", "/mmds")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "fc-client": { "tags": { "purpose": null } }
+            })))
+            .with_status(204)
+            .create();
+        let _get = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body(
+                r#"{"latest": {"meta-data": {}}, "fc-client": {"tags": {"owner": "team-a", "created-at": "2024-05-01"}}}"#,
+            )
+            .create();
+
+        let tags = HashMap::from([
+            ("owner".to_string(), "team-a".to_string()),
+            ("created-at".to_string(), "2024-05-01".to_string()),
+        ]);
+        client.set_tags(tags.clone()).await.unwrap();
+        client.remove_tag("purpose").await.unwrap();
+        assert_eq!(client.get_tags().await.unwrap(), tags);
+        set.assert();
+        remove.assert();
+    }
+
+    #[tokio::test]
+    async fn test_mmds_tags_validation() {
+        use std::collections::HashMap;
+
+        let (mut server, client) = create_test_client().await;
+        let _get = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body(r#"{"latest": {}}"#)
+            .create();
+        assert!(client.get_tags().await.unwrap().is_empty());
+
+        let long = "x".repeat(crate::mmds::MAX_TAG_VALUE_LEN + 1);
+        for (key, value) in [("", "v"), ("a/b", "v"), ("owner~", "v"), ("owner", &long)] {
+            let tags = HashMap::from([(key.to_string(), value.to_string())]);
+            assert!(
+                matches!(
+                    client.set_tags(tags).await,
+                    Err(crate::FirecrackerError::Validation(_))
+                ),
+                "tag {:?} should be rejected",
+                key
+            );
+        }
+        assert!(matches!(
+            client.remove_tag("a/b").await,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_mmds_value() {
+        let (mut server, client) = create_test_client().await;
+        let _pending = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body(r#"{"boot": {"status": "starting"}}"#)
+            .expect(2)
+            .create();
+        let _done = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body(r#"{"boot": {"status": "done"}}"#)
+            .create();
+
+        let value = client
+            .wait_for_mmds_value(
+                "/boot/status",
+                |v| v == "done",
+                std::time::Duration::from_secs(5),
+                std::time::Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(value, "done");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_mmds_key() {
+        let (mut server, client) = create_test_client().await;
+        let _empty = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let _ready = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body(r#"{"ready": true}"#)
+            .create();
+
+        let value = client
+            .wait_for_mmds_key(
+                "/ready",
+                std::time::Duration::from_secs(5),
+                std::time::Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(value, true);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_mmds_value_timeout() {
+        let (mut server, client) = create_test_client().await;
+        let _m = server
+            .mock("GET", "/mmds")
+            .with_status(200)
+            .with_body(r#"{"boot": {"status": "starting"}}"#)
+            .create();
+
+        let result = client
+            .wait_for_mmds_value(
+                "/boot/status",
+                |v| v == "done",
+                std::time::Duration::from_millis(50),
+                std::time::Duration::from_millis(10),
+            )
+            .await;
+        match result {
+            Err(crate::FirecrackerError::MmdsWaitTimeout {
+                duration_ms,
+                last_value,
+                ..
+            }) => {
+                assert_eq!(duration_ms, 50);
+                assert_eq!(last_value, Some(Value::String("starting".to_string())));
+            }
+            other => panic!("expected timeout, got {:?}", other),
+        }
+
+        let result = client
+            .wait_for_mmds_key(
+                "boot/status",
+                std::time::Duration::from_millis(50),
+                std::time::Duration::from_millis(10),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+
+        // A zero interval would poll back to back; nothing is sent
+        let (mut server, client) = create_test_client().await;
+        let untouched = server.mock("GET", "/mmds").expect(0).create();
+        let result = client
+            .wait_for_mmds_key(
+                "/boot/status",
+                std::time::Duration::from_millis(50),
+                std::time::Duration::ZERO,
+            )
+            .await;
+        match result {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                assert!(errors.field_errors().contains_key("interval"))
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        untouched.assert();
+    }
+
+    #[test]
+    fn test_log_line_parse() {
+        use crate::logger::{parse_line, LogLine, LogOrigin, ParseError};
+
+        struct Case {
+            line: &'static str,
+            instance: &'static str,
+            thread: Option<&'static str>,
+            level: Option<LogLevel>,
+            origin: Option<(&'static str, u32)>,
+            message: &'static str,
+        }
+
+        let cases = [
+            // v0.25, default settings
+            Case {
+                line: "2021-03-24T09:21:32.123456789 [anonymous-instance] Running Firecracker v0.25.0",
+                instance: "anonymous-instance",
+                thread: None,
+                level: None,
+                origin: None,
+                message: "Running Firecracker v0.25.0",
+            },
+            // v1.1 with show_level and show_log_origin
+            Case {
+                line: "2022-06-10T14:02:11.008317215 [fc-1:INFO:src/firecracker/src/main.rs:366] Running Firecracker v1.1.0",
+                instance: "fc-1",
+                thread: None,
+                level: Some(LogLevel::Info),
+                origin: Some(("src/firecracker/src/main.rs", 366)),
+                message: "Running Firecracker v1.1.0",
+            },
+            // v1.4+, default settings
+            Case {
+                line: "2023-08-04T10:44:24.123456789 [anonymous-instance:main] Running Firecracker v1.4.0",
+                instance: "anonymous-instance",
+                thread: Some("main"),
+                level: None,
+                origin: None,
+                message: "Running Firecracker v1.4.0",
+            },
+            // v1.4+ with show_level only
+            Case {
+                line: "2023-08-04T10:44:25.000000001 [anonymous-instance:fc_api:WARN] Received unknown request",
+                instance: "anonymous-instance",
+                thread: Some("fc_api"),
+                level: Some(LogLevel::Warning),
+                origin: None,
+                message: "Received unknown request",
+            },
+            // v1.7 with show_log_origin only
+            Case {
+                line: "2024-03-01T08:00:00.000000000 [vm-7:fc_vcpu 0:src/vmm/src/vstate/vcpu/mod.rs:460] Received KVM_EXIT_SHUTDOWN signal",
+                instance: "vm-7",
+                thread: Some("fc_vcpu 0"),
+                level: None,
+                origin: Some(("src/vmm/src/vstate/vcpu/mod.rs", 460)),
+                message: "Received KVM_EXIT_SHUTDOWN signal",
+            },
+            // v1.10 with both flags
+            Case {
+                line: "2024-11-20T17:30:45.987654321 [vm-7:fc_vmm:ERROR:src/vmm/src/lib.rs:912] Failed to write metrics",
+                instance: "vm-7",
+                thread: Some("fc_vmm"),
+                level: Some(LogLevel::Error),
+                origin: Some(("src/vmm/src/lib.rs", 912)),
+                message: "Failed to write metrics",
+            },
+        ];
+
+        for case in cases {
+            let parsed = parse_line(case.line).unwrap();
+            assert!(parsed.timestamp.is_some(), "{}", case.line);
+            assert_eq!(parsed.instance_id.as_deref(), Some(case.instance));
+            assert_eq!(parsed.thread.as_deref(), case.thread, "{}", case.line);
+            assert_eq!(parsed.level, case.level, "{}", case.line);
+            assert_eq!(
+                parsed.origin,
+                case.origin.map(|(file, line)| LogOrigin {
+                    file: file.to_string(),
+                    line
+                }),
+                "{}",
+                case.line
+            );
+            assert_eq!(parsed.message, case.message);
+        }
+
+        assert_eq!(parse_line("kernel"), Err(ParseError::MissingTimestamp));
+        assert!(matches!(
+            parse_line("kernel panic"),
+            Err(ParseError::InvalidTimestamp(_))
+        ));
+        assert_eq!(
+            parse_line("2024-01-01T00:00:00.0 no header"),
+            Err(ParseError::MissingHeader)
+        );
+
+        let raw = LogLine::parse("kernel panic");
+        assert_eq!(raw.timestamp, None);
+        assert_eq!(raw.message, "kernel panic");
+    }
+
+    #[test]
+    fn test_log_line_boot_time() {
+        let line = crate::logger::parse_line(
+            "2023-08-04T10:44:26.500000000 [anonymous-instance:fc_vcpu 0] Guest-boot-time =  125432 us 125 ms,  120011 CPU us 120 CPU ms",
+        )
+        .unwrap();
+        assert_eq!(line.boot_time_us(), Some(125432));
+
+        let line = crate::logger::LogLine::parse("2023-08-04T10:44:26.5 [vm] Running Firecracker");
+        assert_eq!(line.boot_time_us(), None);
+    }
+
+    #[tokio::test]
+    async fn test_tail_log_fifo() {
+        use futures_util::StreamExt;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fc.log");
+        crate::logger::create_log_fifo(&path, false).unwrap();
+        assert!(crate::logger::create_log_fifo(&path, false).is_err());
+        crate::logger::create_log_fifo(&path, true).unwrap();
+
+        let stream = crate::logger::tail_log(&path);
+        futures_util::pin_mut!(stream);
+
+        let writer_path = path.clone();
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut fifo = std::fs::OpenOptions::new()
+                .write(true)
+                .open(writer_path)
+                .unwrap();
+            writeln!(
+                fifo,
+                "2024-01-01T10:00:00.000000000 [vm-1:main:INFO:src/main.rs:7] started"
+            )
+            .unwrap();
+            writeln!(fifo, "plain text").unwrap();
+        });
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.level, Some(LogLevel::Info));
+        assert_eq!(first.message, "started");
+
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.level, None);
+        assert_eq!(second.message, "plain text");
+
+        writer.await.unwrap();
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_logger_and_metrics_paths_follow_validation_mode() {
+        let (mut server, client) = create_test_client().await;
+        let _logger = server.mock("PUT", "/logger").with_status(204).create();
+        let _metrics = server.mock("PUT", "/metrics").with_status(204).create();
+
+        let logger = Logger {
+            log_path: "/nonexistent-dir/firecracker.log".to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        let metrics = Metrics {
+            metrics_path: "/nonexistent-dir/metrics".to_string(),
+        };
+
+        assert!(client.put_logger(&logger).await.is_err());
+        assert!(client.put_metrics(&metrics).await.is_err());
+
+        let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+        client.put_logger(&logger).await.unwrap();
+        client.put_metrics(&metrics).await.unwrap();
+
+        let relative = Metrics {
+            metrics_path: "relative/metrics".to_string(),
+        };
+        assert!(client.put_metrics(&relative).await.is_err());
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_validation_mode_per_endpoint() {
+        use crate::validation::ValidationMode;
+
+        let (mut server, client) = create_test_client().await;
+        let mock = server
+            .mock("PUT", "/logger")
+            .with_status(204)
+            .expect(3)
+            .create();
+
+        let logger = |log_path: &str| Logger {
+            log_path: log_path.to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        let relative = logger("relative/firecracker.log");
+        let missing_dir = logger("/nonexistent-dir/firecracker.log");
+        let dir = tempfile::tempdir().unwrap();
+        let local = logger(dir.path().join("firecracker.log").to_str().unwrap());
+
+        assert_eq!(client.validation_mode(), ValidationMode::StrictLocal);
+        assert!(client.put_logger(&relative).await.is_err());
+        assert!(client.put_logger(&missing_dir).await.is_err());
+        client.put_logger(&local).await.unwrap();
+
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        assert!(client.put_logger(&relative).await.is_err());
+        client.put_logger(&missing_dir).await.unwrap();
+
+        let client = client.with_validation_mode(ValidationMode::Off);
+        client.put_logger(&relative).await.unwrap();
+
+        mock.assert();
+    }
+
+    #[test]
+    fn test_log_level_aliases() {
+        for (input, expected) in [
+            ("error", LogLevel::Error),
+            ("ERROR", LogLevel::Error),
+            ("Warning", LogLevel::Warning),
+            ("warning", LogLevel::Warning),
+            ("Warn", LogLevel::Warning),
+            ("WARN", LogLevel::Warning),
+            ("info", LogLevel::Info),
+            ("Debug", LogLevel::Debug),
+            ("trace", LogLevel::Trace),
+            ("OFF", LogLevel::Off),
+        ] {
+            assert_eq!(input.parse::<LogLevel>().unwrap(), expected);
+            let json = format!("{:?}", input);
+            let level: LogLevel = serde_json::from_str(&json).unwrap();
+            assert_eq!(level, expected);
+            assert_eq!(
+                serde_json::to_value(level).unwrap(),
+                Value::String(expected.to_string())
+            );
+        }
+
+        for input in ["", "verbose", "Warnings", "inf"] {
+            assert!(input.parse::<LogLevel>().is_err());
+            assert!(serde_json::from_str::<LogLevel>(&format!("{:?}", input)).is_err());
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_model_import_paths_are_the_same_type() {
+        fn same_type<T>(_: &T, _: &T) {}
+
+        let from_root = crate::Logger {
+            log_path: "/tmp/firecracker.log".to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        let from_logger = crate::logger::Logger {
+            log_path: "/tmp/firecracker.log".to_string(),
+            level: Some(crate::logger::LogLevel::Info),
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        same_type(&from_root, &from_logger);
+        same_type::<crate::models::Logger>(&from_root, &from_logger);
+
+        let from_root = crate::Metrics::new("/tmp/metrics.json");
+        let from_metrics = crate::metrics::Metrics::new("/tmp/metrics.json");
+        same_type(&from_root, &from_metrics);
+        same_type::<crate::models::Metrics>(&from_root, &from_metrics);
+    }
+
+    #[tokio::test]
+    async fn test_flush_metrics() {
+        use crate::action::ActionType;
+        use crate::testing::mock::expect_action;
+
+        let (mut server, client) = create_test_client().await;
+        let m = expect_action(&mut server, ActionType::FlushMetrics);
+
+        client.flush_metrics().await.unwrap();
+        m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_flush_and_read_metrics() {
+        use crate::action::ActionType;
+        use crate::testing::mock::{expect_action, expect_fault};
+
+        let (mut server, client) = create_test_client().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+        std::fs::write(
+            &path,
+            "{\"utc_timestamp_ms\":1}\n{\"utc_timestamp_ms\":2}\n",
+        )
+        .unwrap();
+
+        // Nothing is read when the flush itself fails
+        let failed = expect_fault(&mut server, "PUT", "/actions", "metrics are not configured");
+        let missing = dir.path().join("missing.json");
+        assert!(matches!(
+            client.flush_and_read_metrics(&missing).await,
+            Err(crate::FirecrackerError::Api {
+                status_code: 400,
+                ..
+            })
+        ));
+        failed.remove();
+
+        let flushed = expect_action(&mut server, ActionType::FlushMetrics);
+        let metrics = client.flush_and_read_metrics(&path).await.unwrap();
+        assert_eq!(metrics.utc_timestamp_ms, 2);
+        flushed.assert();
+    }
+
+    #[tokio::test]
+    async fn test_instance_action_validation() {
+        let (_, client) = create_test_client().await;
+        let action = crate::action::InstanceActionInfo::new("Pause");
+        assert!(matches!(
+            client.create_sync_action(&action).await,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_firecracker_metrics_v1_4() {
+        use crate::metrics::FirecrackerMetrics;
+
+        let metrics =
+            FirecrackerMetrics::from_json_str(crate::testing::fixtures::METRICS_V1_4).unwrap();
+        assert_eq!(metrics.utc_timestamp_ms, 1691145864123);
+        assert_eq!(metrics.block_read_bytes(), 1572864);
+        assert_eq!(metrics.block_write_bytes(), 16384);
+        assert_eq!(metrics.net_rx_bytes(), 1024);
+        assert_eq!(metrics.net_tx_bytes(), 512);
+        assert_eq!(metrics.seccomp_faults(), 0);
+        assert_eq!(metrics.put_api_requests["actions_count"], 1);
+        assert_eq!(metrics.block_devices()["rootfs"].read_count, 37);
+        assert_eq!(metrics.net_devices()["eth0"].tx_count, 4);
+        assert!(metrics.other.contains_key("rtc"));
+    }
+
+    #[test]
+    fn test_firecracker_metrics_v1_7() {
+        use crate::metrics::FirecrackerMetrics;
+
+        let metrics =
+            FirecrackerMetrics::from_reader(crate::testing::fixtures::METRICS_V1_7.as_bytes())
+                .unwrap();
+        assert_eq!(metrics.seccomp_faults(), 1);
+        assert_eq!(metrics.block_read_bytes(), 3145728);
+        let drives = metrics.block_devices();
+        assert_eq!(drives.len(), 2);
+        assert_eq!(drives["scratch"].read_bytes, 1048576);
+        assert!(metrics.other.contains_key("entropy"));
+        assert!(metrics.other.contains_key("vhost_user_block_scratch"));
+
+        let sparse = FirecrackerMetrics::from_json_str(r#"{"utc_timestamp_ms": 5}"#).unwrap();
+        assert_eq!(sparse.block_read_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_metrics_fifo() {
+        use futures_util::StreamExt;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.fifo");
+        crate::logger::create_log_fifo(&path, false).unwrap();
+
+        let stream = crate::metrics::stream_metrics(&path);
+        futures_util::pin_mut!(stream);
+
+        let writer_path = path.clone();
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut fifo = std::fs::OpenOptions::new()
+                .write(true)
+                .open(writer_path)
+                .unwrap();
+            // First document arrives in two partial writes
+            fifo.write_all(b"{\"utc_timestamp_ms\":1,\"seccomp\":")
+                .unwrap();
+            fifo.flush().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            fifo.write_all(b"{\"num_faults\":3}}\n").unwrap();
+            fifo.write_all(b"not json\n").unwrap();
+            fifo.write_all(b"{\"utc_timestamp_ms\":2}\n").unwrap();
+        });
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.utc_timestamp_ms, 1);
+        assert_eq!(first.seccomp_faults(), 3);
+
+        assert!(stream.next().await.unwrap().is_err());
+
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.utc_timestamp_ms, 2);
+
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_metrics_follows_regular_file() {
+        use futures_util::StreamExt;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+        std::fs::write(&path, "{\"utc_timestamp_ms\":1}\n{\"utc_timestamp_ms\"").unwrap();
+
+        let stream = crate::metrics::stream_metrics(&path);
+        futures_util::pin_mut!(stream);
+        assert_eq!(stream.next().await.unwrap().unwrap().utc_timestamp_ms, 1);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b":2}\n").unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap().utc_timestamp_ms, 2);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_prometheus_exposition() {
+        use crate::prometheus_exporter::FirecrackerCollector;
+        use prometheus::{Encoder, Registry, TextEncoder};
+
+        let metrics = crate::testing::fixtures::metrics_v1_7();
+        let registry = Registry::new();
+        let collector = FirecrackerCollector::new("vm-1").unwrap();
+        registry.register(Box::new(collector.clone())).unwrap();
+        collector.update(metrics);
+
+        let mut out = Vec::new();
+        TextEncoder::new()
+            .encode(&registry.gather(), &mut out)
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.contains("# TYPE firecracker_block_read_bytes_total counter"));
+        assert!(text.contains("firecracker_block_read_bytes_total{vm_id=\"vm-1\"} 3145728"));
+        assert!(text.contains("# TYPE firecracker_block_device_read_bytes_total counter"));
+        assert!(text.contains(
+            "firecracker_block_device_read_bytes_total{vm_id=\"vm-1\",drive=\"rootfs\"} 2097152"
+        ));
+        assert!(text.contains(
+            "firecracker_block_device_read_bytes_total{vm_id=\"vm-1\",drive=\"scratch\"} 1048576"
+        ));
+        assert!(!text.contains("firecracker_block_read_bytes_total{vm_id=\"vm-1\",drive="));
+        assert!(text.contains(
+            "firecracker_net_device_tx_bytes_count_total{vm_id=\"vm-1\",iface=\"eth0\"} 2048"
+        ));
+        assert!(text.contains("firecracker_seccomp_num_faults_total{vm_id=\"vm-1\"} 1"));
+        assert!(text.contains("# TYPE firecracker_utc_timestamp_ms gauge"));
+        assert!(text.contains("firecracker_metrics_up{vm_id=\"vm-1\"} 1"));
+
+        // A second VM can share the registry
+        registry
+            .register(Box::new(FirecrackerCollector::new("vm-2").unwrap()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_metrics_delta() {
+        use crate::metrics::FirecrackerMetrics;
+
+        // Firecracker resets its counters on every flush
+        let previous = FirecrackerMetrics::from_json_str(
+            r#"{"utc_timestamp_ms": 1000, "block": {"read_bytes": 100, "write_bytes": 50},
+                "put_api_requests": {"actions_count": 2}}"#,
+        )
+        .unwrap();
+        let current = FirecrackerMetrics::from_json_str(
+            r#"{"utc_timestamp_ms": 3000, "block": {"read_bytes": 400, "write_bytes": 20},
+                "put_api_requests": {"actions_count": 1},
+                "api_server": {"process_startup_time_us": 8455},
+                "latencies_us": {"pause_vm": 120}}"#,
+        )
+        .unwrap();
+
+        let counters = current.counters();
+        assert_eq!(counters["block.read_bytes"], 400);
+        assert!(!counters.contains_key("utc_timestamp_ms"));
+        assert!(!counters.contains_key("api_server.process_startup_time_us"));
+        assert!(!counters.contains_key("latencies_us.pause_vm"));
+        assert_eq!(current.values()["latencies_us.pause_vm"], 120);
+
+        let delta = current.delta(&previous);
+        assert_eq!(delta.elapsed, std::time::Duration::from_secs(2));
+        assert_eq!(delta.get("block.read_bytes"), 400);
+        assert_eq!(delta.get("block.write_bytes"), 20);
+        assert_eq!(delta.get("put_api_requests.actions_count"), 1);
+
+        let rates = delta.rates().unwrap();
+        assert_eq!(rates["block.read_bytes"], 200.0);
+
+        // No elapsed time means no rate
+        assert!(current.delta(&current).rates().is_none());
+        assert!(previous.delta(&current).rates().is_none());
+    }
+
+    #[test]
+    fn test_metrics_window() {
+        use crate::metrics::{FirecrackerMetrics, MetricsWindow};
+
+        let snapshot = |ts: u64, read: u64| {
+            FirecrackerMetrics::from_json_str(&format!(
+                r#"{{"utc_timestamp_ms": {}, "block": {{"read_bytes": {}}}}}"#,
+                ts, read
+            ))
+            .unwrap()
+        };
+
+        let mut window = MetricsWindow::new(std::time::Duration::from_secs(10));
+        assert!(window.rates().is_none());
+
+        window.push(snapshot(0, 0));
+        assert!(window.rates().is_none());
+        window.push(snapshot(0, 10));
+        assert!(window.rates().is_none());
+
+        window.push(snapshot(5_000, 1_000));
+        window.push(snapshot(10_000, 500));
+        // The first snapshot is no longer needed to cover the last 10 seconds
+        assert_eq!(window.len(), 3);
+        let rates = window.rates().unwrap();
+        assert_eq!(rates["block.read_bytes"], 150.0);
+
+        window.push(snapshot(20_000, 2_000));
+        assert_eq!(window.len(), 2);
+        assert_eq!(window.rates().unwrap()["block.read_bytes"], 200.0);
+    }
+
+    #[test]
+    fn test_validate_output_paths() {
+        use crate::validation::validate_output_paths;
+
+        let logger = |path: &str| Logger {
+            log_path: path.to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        let metrics = |path: &str| Metrics {
+            metrics_path: path.to_string(),
+        };
+        let check = |err: validator::ValidationError| err.params["check"].clone();
+
+        assert!(validate_output_paths(&logger("/tmp/fc.log"), &metrics("/tmp/fc.metrics")).is_ok());
+        assert!(validate_output_paths(&logger("/tmp/fc"), &metrics("/tmp/fc-metrics")).is_ok());
+
+        let err =
+            validate_output_paths(&logger("/tmp/fc.out"), &metrics("/tmp/fc.out")).unwrap_err();
+        assert_eq!(check(err), "distinct_outputs");
+        let err =
+            validate_output_paths(&logger("/tmp/fc.out/"), &metrics("/tmp/fc.out")).unwrap_err();
+        assert_eq!(check(err), "distinct_outputs");
+
+        let err =
+            validate_output_paths(&logger("/tmp/fc"), &metrics("/tmp/fc/metrics")).unwrap_err();
+        assert_eq!(check(err), "nested_outputs");
+        let err =
+            validate_output_paths(&logger("/tmp/fc/logs/fc.log"), &metrics("/tmp/fc")).unwrap_err();
+        assert_eq!(check(err), "nested_outputs");
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[test]
+    fn test_writable_path_names_failing_check() {
+        use crate::validation::validate_writable_path;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+
+        let missing = dir.path().join("missing/metrics");
+        let err = validate_writable_path(missing.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.params["check"], "parent_exists");
+        assert!(err.message.unwrap().contains("missing"));
+
+        let under_file = file.join("metrics");
+        let err = validate_writable_path(under_file.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.params["check"], "parent_is_dir");
+    }
+
+    #[tokio::test]
+    async fn test_follow_metrics_rotation() {
+        use crate::metrics::{follow_metrics, rotate, MetricsEvent};
+        use futures_util::StreamExt;
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+
+        let next_timestamp = |event: MetricsEvent| match event {
+            MetricsEvent::Metrics(m) => m.utc_timestamp_ms,
+            MetricsEvent::Rotated => panic!("unexpected rotation"),
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+        std::fs::write(
+            &path,
+            "{\"utc_timestamp_ms\":1}\n{\"utc_timestamp_ms\":2}\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let stream = follow_metrics(&path);
+        futures_util::pin_mut!(stream);
+        assert_eq!(next_timestamp(stream.next().await.unwrap().unwrap()), 1);
+        assert_eq!(next_timestamp(stream.next().await.unwrap().unwrap()), 2);
+
+        // Truncation in place
+        std::fs::write(&path, "{\"utc_timestamp_ms\":3}\n").unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), MetricsEvent::Rotated);
+        assert_eq!(next_timestamp(stream.next().await.unwrap().unwrap()), 3);
+
+        // Rename and recreate
+        rotate(&path, 2).unwrap();
+        assert!(dir.path().join("metrics.json.1").exists());
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"utc_timestamp_ms\":4}\n").unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), MetricsEvent::Rotated);
+        assert_eq!(next_timestamp(stream.next().await.unwrap().unwrap()), 4);
+    }
+
+    #[test]
+    fn test_rotate_keeps_numbered_files() {
+        use crate::metrics::rotate;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+        for content in ["a", "b", "c"] {
+            std::fs::write(&path, content).unwrap();
+            rotate(&path, 2).unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("metrics.json.1")).unwrap(),
+            "c"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("metrics.json.2")).unwrap(),
+            "b"
+        );
+        assert!(!dir.path().join("metrics.json.3").exists());
+
+        std::fs::write(&path, "d").unwrap();
+        rotate(&path, 0).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("metrics.json.1")).unwrap(),
+            "c"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entropy_device_rate_limiter_validation() {
+        use crate::models::{RateLimiter, TokenBucket};
+
+        let (mut server, client) = create_test_client().await;
+        let m = server
+            .mock("PUT", "/entropy")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "rate_limiter": { "bandwidth": { "size": 1000, "refill_time": 100 } }
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let invalid = EntropyDevice {
+            rate_limiter: Some(RateLimiter {
+                bandwidth: Some(TokenBucket {
+                    one_time_burst: None,
+                    refill_time: 100,
+                    size: 0,
+                }),
+                ops: None,
+            }),
+        };
+        assert!(matches!(
+            client.put_entropy_device(&invalid).await,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+
+        let negative_burst = serde_json::json!({
+            "rate_limiter": { "ops": { "one_time_burst": -1, "refill_time": 100, "size": 10 } }
+        });
+        assert!(serde_json::from_value::<EntropyDevice>(negative_burst).is_err());
+
+        let valid = EntropyDevice {
+            rate_limiter: Some(RateLimiter {
+                bandwidth: Some(TokenBucket {
+                    one_time_burst: None,
+                    refill_time: 100,
+                    size: 1000,
+                }),
+                ops: None,
+            }),
+        };
+        client.put_entropy_device(&valid).await.unwrap();
+        m.assert();
+    }
+
+    #[test]
+    fn test_entropy_device_builder() {
+        use crate::models::RateLimiter;
+
+        let device = EntropyDevice::builder()
+            .rate(RateLimiter::bandwidth(1_000))
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&device).unwrap(),
+            serde_json::json!({
+                "rate_limiter": {
+                    "bandwidth": { "one_time_burst": null, "refill_time": 1000, "size": 1000 }
+                }
+            })
+        );
+
+        let unthrottled = EntropyDevice::builder().build().unwrap();
+        assert_eq!(
+            serde_json::to_value(&unthrottled).unwrap(),
+            serde_json::json!({})
+        );
+
+        assert!(EntropyDevice::builder()
+            .rate(RateLimiter::ops(0))
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_entropy_device_builder_request() {
+        use crate::models::RateLimiter;
+
+        let (mut server, client) = create_test_client().await;
+        let m = server
+            .mock("PUT", "/entropy")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "rate_limiter": {
+                    "bandwidth": { "size": 4096, "refill_time": 1000 },
+                    "ops": { "size": 10, "refill_time": 1000 }
+                }
+            })))
+            .with_status(204)
+            .create();
+
+        let device = EntropyDevice::builder()
+            .rate(RateLimiter::bandwidth(4096).with_ops(10))
+            .build()
+            .unwrap();
+        client.put_entropy_device(&device).await.unwrap();
+        m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_entropy_unsupported_on_old_firecracker() {
+        use crate::version::VersionOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.1.4"}"#)
+            .create();
+        let entropy = server
+            .mock("PUT", "/entropy")
+            .with_status(204)
+            .expect(0)
+            .create();
+
+        assert_eq!(client.supports_entropy(), None);
+        client.get_version().await.unwrap();
+        assert_eq!(client.supports_entropy(), Some(false));
+
+        let result = client.put_entropy_device(&EntropyDevice::default()).await;
+        match result {
+            Err(crate::FirecrackerError::Unsupported { version, .. }) => {
+                assert_eq!(version, "1.1.4")
+            }
+            other => panic!("expected unsupported error, got {:?}", other),
+        }
+        entropy.assert();
+    }
+
+    #[tokio::test]
+    async fn test_entropy_supported_on_new_firecracker() {
+        use crate::version::VersionOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.5.0"}"#)
+            .create();
+        let entropy = server.mock("PUT", "/entropy").with_status(204).create();
+
+        client.get_version().await.unwrap();
+        assert_eq!(client.supports_entropy(), Some(true));
+        client
+            .put_entropy_device(&EntropyDevice::default())
+            .await
+            .unwrap();
+        entropy.assert();
+    }
+
+    #[cfg(unix)]
+    async fn spawn_vsock_muxer(reply: &'static str) -> (tempfile::TempDir, std::path::PathBuf) {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "CONNECT 52\n");
+            if !reply.is_empty() {
+                stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+            }
+            // Hold the connection open so a silent peer hits the timeout
+            let _ = stream.read_line(&mut line).await;
+        });
+        (dir, path)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vsock_connect_to_guest() {
+        use tokio::io::AsyncReadExt;
+
+        let (_dir, path) = spawn_vsock_muxer("OK 1073741824\nhello").await;
+        let mut stream = crate::vsock::connect_to_guest(&path, 52).await.unwrap();
+
+        let mut payload = [0u8; 5];
+        stream.read_exact(&mut payload).await.unwrap();
+        assert_eq!(&payload, b"hello");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vsock_connect_to_guest_rejected() {
+        let (_dir, path) = spawn_vsock_muxer("FAILURE\n").await;
+        match crate::vsock::connect_to_guest(&path, 52).await {
+            Err(crate::FirecrackerError::VsockHandshake { port, response }) => {
+                assert_eq!(port, 52);
+                assert_eq!(response, "FAILURE");
+            }
+            other => panic!("expected handshake error, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vsock_connect_to_guest_transport_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.sock");
+        assert!(matches!(
+            crate::vsock::connect_to_guest(&missing, 52).await,
+            Err(crate::FirecrackerError::FileSystem { .. })
+        ));
+
+        let (_dir, path) = spawn_vsock_muxer("").await;
+        let result = crate::vsock::connect_to_guest_timeout(
+            &path,
+            52,
+            std::time::Duration::from_millis(100),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(crate::FirecrackerError::Timeout { .. })
+        ));
+    }
+
+    #[test]
+    fn test_vsock_guest_listener_path() {
+        assert_eq!(
+            crate::vsock::guest_listener_path("/run/fc/v.sock", 52),
+            std::path::PathBuf::from("/run/fc/v.sock_52")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vsock_listen_for_guest() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let uds_path = dir.path().join("v.sock");
+        let socket_path = dir.path().join("v.sock_52");
+
+        let (listener, guard) = crate::vsock::listen_for_guest(&uds_path, 52, false).unwrap();
+        assert_eq!(guard.path(), socket_path);
+        let mode = std::fs::metadata(&socket_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let client = tokio::net::UnixStream::connect(&socket_path);
+        let (accepted, client) = tokio::join!(listener.accept(), client);
+        accepted.unwrap();
+        client.unwrap();
+
+        drop(guard);
+        assert!(!socket_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vsock_listen_for_guest_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let uds_path = dir.path().join("v.sock");
+        let socket_path = dir.path().join("v.sock_52");
+
+        // A socket left behind by a listener that was never cleaned up
+        let stale = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+        drop(stale);
+
+        assert!(matches!(
+            crate::vsock::listen_for_guest(&uds_path, 52, false),
+            Err(crate::FirecrackerError::FileSystem { .. })
+        ));
+        let (_listener, _guard) = crate::vsock::listen_for_guest(&uds_path, 52, true).unwrap();
+
+        // Regular files are never removed
+        let file_path = dir.path().join("v.sock_53");
+        std::fs::write(&file_path, b"data").unwrap();
+        assert!(crate::vsock::listen_for_guest(&uds_path, 53, true).is_err());
+        assert!(file_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_put_vsock_strips_vsock_id_on_modern_firecracker() {
+        use crate::version::VersionOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.4.0"}"#)
+            .create();
+        let mock = server
+            .mock("PUT", "/vsock")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "guest_cid": 3,
+                "uds_path": "/tmp/vsock",
+            })))
+            .with_status(204)
+            .expect(2)
+            .create();
+
+        let vsock = Vsock {
+            guest_cid: 3,
+            uds_path: "/tmp/vsock".to_string(),
+            vsock_id: Some("vsock0".to_string()),
+        };
+        client.get_version().await.unwrap();
+        assert_eq!(
+            vsock.version_warnings(&client.vmm_version().unwrap()).len(),
+            1
+        );
+        client.put_vsock(&vsock).await.unwrap();
+        let warnings = client.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].rule, "vsock_id_ignored");
+        assert_eq!(warnings[0].context, "vsock.vsock_id");
+
+        // apply_config warns the same way
+        use crate::config::{ApplyOptions, ConfigOperations};
+        let config = crate::FullVmConfiguration {
+            vsock: Some(vsock),
+            ..Default::default()
+        };
+        let options = ApplyOptions {
+            lint: false,
+            ..Default::default()
+        };
+        client.apply_config_with(&config, options).await.unwrap();
+        let warnings = client.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].rule, "vsock_id_ignored");
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_put_vsock_requires_vsock_id_on_old_firecracker() {
+        use crate::version::VersionOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "0.25.2"}"#)
+            .create();
+        let mock = server
+            .mock("PUT", "/vsock")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "vsock_id": "vsock0",
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let mut vsock = Vsock {
+            guest_cid: 3,
+            uds_path: "/tmp/vsock".to_string(),
+            vsock_id: None,
+        };
+        client.get_version().await.unwrap();
+        assert!(matches!(
+            client.put_vsock(&vsock).await,
+            Err(crate::FirecrackerError::Config(_))
+        ));
+
+        vsock.vsock_id = Some("vsock0".to_string());
+        assert!(vsock
+            .version_warnings(&client.vmm_version().unwrap())
+            .is_empty());
+        client.put_vsock(&vsock).await.unwrap();
+        mock.assert();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_vsock_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let uds_path = dir.path().join("v.sock");
+        for name in ["v.sock", "v.sock_52", "v.sock_1024", "other.sock_52"] {
+            std::os::unix::net::UnixListener::bind(dir.path().join(name)).unwrap();
+        }
+        std::fs::write(dir.path().join("v.sock_notes"), b"keep").unwrap();
+
+        let mut removed = crate::vsock::cleanup(&uds_path).unwrap();
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![
+                dir.path().join("v.sock"),
+                dir.path().join("v.sock_1024"),
+                dir.path().join("v.sock_52"),
+            ]
+        );
+        assert!(dir.path().join("other.sock_52").exists());
+        assert!(dir.path().join("v.sock_notes").exists());
+        assert!(crate::vsock::cleanup(&uds_path).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_vsock_cleanup_refuses_non_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+        let uds_path = dir.path().join("v.sock");
+        std::os::unix::net::UnixListener::bind(&uds_path).unwrap();
+        std::fs::write(dir.path().join("v.sock_52"), b"data").unwrap();
+
+        match crate::vsock::cleanup(&uds_path) {
+            Err(crate::FirecrackerError::FileSystem { path, .. }) => {
+                assert_eq!(path, dir.path().join("v.sock_52"))
+            }
+            other => panic!("expected file system error, got {:?}", other),
+        }
+        assert!(uds_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_vsock_wait_for_uds() {
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let uds_path = dir.path().join("v.sock");
+
+        let result = crate::vsock::wait_for_uds(&uds_path, Duration::from_millis(150)).await;
+        match result {
+            Err(crate::FirecrackerError::FileSystem { path, source }) => {
+                assert_eq!(path, uds_path);
+                assert_eq!(source.kind(), std::io::ErrorKind::TimedOut);
+            }
+            other => panic!("expected timeout, got {:?}", other),
+        }
+
+        let bind_path = uds_path.clone();
+        let listener = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let listener = tokio::net::UnixListener::bind(bind_path).unwrap();
+            let _ = listener.accept().await;
+        });
+        crate::vsock::wait_for_uds(&uds_path, Duration::from_secs(5))
+            .await
+            .unwrap();
+        listener.await.unwrap();
+    }
+
+    #[test]
+    fn test_firecracker_version_semver() {
+        use crate::models::FirecrackerVersion;
+        use crate::version::Version;
+
+        let release = FirecrackerVersion::new("1.7.0");
+        assert_eq!(release.semver().unwrap(), Version::new(1, 7, 0));
+        assert!(release.at_least(1, 7));
+        assert!(release.at_least(1, 4));
+        assert!(!release.at_least(1, 8));
+        assert_eq!(release.to_string(), "1.7.0");
+
+        let dev = FirecrackerVersion::new("1.8.0-dev");
+        assert_eq!(dev.semver().unwrap().pre.as_str(), "dev");
+        assert!(dev.at_least(1, 8));
+
+        let rc = FirecrackerVersion::new("v1.5.0-rc1");
+        assert_eq!(rc.semver().unwrap().pre.as_str(), "rc1");
+        assert!(rc.semver().unwrap() < Version::new(1, 5, 0));
+        assert!(rc.at_least(1, 5));
+
+        let short = FirecrackerVersion::new("1.10");
+        assert_eq!(short.semver().unwrap(), Version::new(1, 10, 0));
+        assert!(short.at_least(1, 9));
+
+        let old = FirecrackerVersion::new("0.25.2");
+        assert!(!old.at_least(1, 0));
+
+        let garbage = FirecrackerVersion::new("unknown");
+        assert!(garbage.semver().is_err());
+        assert!(!garbage.at_least(0, 0));
+    }
+
+    #[test]
+    fn test_firecracker_version_deserialize() {
+        use crate::models::FirecrackerVersion;
+
+        let version: FirecrackerVersion =
+            serde_json::from_str(r#"{"firecracker_version": "1.4.1"}"#).unwrap();
+        assert!(version.at_least(1, 4));
+        assert_eq!(
+            serde_json::to_value(&version).unwrap(),
+            serde_json::json!({"firecracker_version": "1.4.1"})
+        );
+    }
+
+    #[test]
+    fn test_capabilities_table() {
+        use crate::version::{Capabilities, Version};
+
+        let v1_1 = Capabilities::for_version(Version::new(1, 1, 0));
+        assert!(!v1_1.supports_entropy);
+        assert!(!v1_1.supports_vhost_user_block);
+        assert!(v1_1.supports_snapshot_resume_vm);
+        assert!(v1_1.supports_mmds_v2);
+
+        let v1_4 = Capabilities::for_version(Version::new(1, 4, 1));
+        assert!(v1_4.supports_entropy);
+        assert!(!v1_4.supports_vhost_user_block);
+        assert!(v1_4.supports_snapshot_resume_vm);
+
+        let v1_7 = Capabilities::for_version(Version::parse("1.7.0-dev").unwrap());
+        assert!(v1_7.supports_entropy);
+        assert!(v1_7.supports_vhost_user_block);
+        assert!(v1_7.supports_snapshot_resume_vm);
+        assert!(v1_7.supports_mmds_v2);
+    }
+
+    #[test]
+    fn test_capabilities_for_version() {
+        use crate::capabilities::{self, Feature};
+        use crate::version::{Version, VersionSource};
+
+        let supported = |version: Version| -> Vec<&str> {
+            let capabilities = capabilities::for_version(&version);
+            assert_eq!(capabilities.version, version);
+            assert_eq!(capabilities.source, VersionSource::Manual);
+            Feature::ALL
+                .iter()
+                .filter(|feature| capabilities.supports(**feature))
+                .map(|feature| feature.as_str())
+                .collect()
+        };
+        assert!(supported(Version::new(0, 25, 2)).is_empty());
+        assert_eq!(
+            supported(Version::new(1, 0, 0)),
+            ["snapshot_resume_vm", "mmds_v2"]
+        );
+        assert_eq!(
+            supported(Version::new(1, 3, 0)),
+            [
+                "entropy",
+                "snapshot_resume_vm",
+                "snapshot_mem_backend",
+                "mmds_v2"
+            ]
+        );
+        assert_eq!(
+            supported(Version::new(1, 6, 1)),
+            [
+                "entropy",
+                "snapshot_resume_vm",
+                "snapshot_mem_backend",
+                "network_overrides",
+                "mmds_v2"
+            ]
+        );
+        assert_eq!(supported(Version::parse("1.7.0-dev").unwrap()).len(), 7);
+        assert_eq!(supported(Version::new(1, 10, 1)).len(), 7);
+
+        assert_eq!(Feature::HugePages.introduced_in(), Version::new(1, 7, 0));
+        assert!(!Feature::HugePages.is_supported_by(&Version::new(1, 6, 9)));
+        assert_eq!(Feature::VhostUserBlock.to_string(), "vhost_user_block");
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_fetches_version_once() {
+        use crate::version::{Version, VersionOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.4.0"}"#)
+            .expect(1)
+            .create();
+
+        assert!(client.capabilities().is_none());
+        let capabilities = client.negotiate().await.unwrap();
+        assert!(capabilities.supports_entropy);
+        assert!(!capabilities.supports_vhost_user_block);
+
+        assert_eq!(client.negotiate().await.unwrap(), capabilities);
+        assert_eq!(client.capabilities(), Some(&capabilities));
+        version.assert();
+
+        // Recording the same version again is fine, a different one is not
+        client.set_vmm_version(Version::new(1, 4, 0)).unwrap();
+        assert!(matches!(
+            client.set_vmm_version(Version::new(1, 7, 0)),
+            Err(crate::FirecrackerError::Config(_))
+        ));
+        assert_eq!(client.vmm_version(), Some(Version::new(1, 4, 0)));
+    }
+
+    #[tokio::test]
+    async fn test_require_version() {
+        use crate::version::{VersionOperations, VersionReq};
+
+        let (mut server, client) = create_test_client().await;
+        let version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.5.0-dev"}"#)
+            .expect(1)
+            .create();
+
+        let capabilities = client
+            .require_version(&VersionReq::parse("^1.4").unwrap())
+            .await
+            .unwrap();
+        assert!(capabilities.supports_entropy);
+
+        match client
+            .require_version(&VersionReq::parse(">=1.7").unwrap())
+            .await
+        {
+            Err(crate::FirecrackerError::UnsupportedVmmVersion { found, required }) => {
+                assert_eq!(found, "1.5.0-dev");
+                assert_eq!(required, ">=1.7");
+            }
+            other => panic!("expected unsupported version, got {:?}", other),
+        }
+        version.assert();
+    }
+
+    #[tokio::test]
+    async fn test_min_version_checked_whenever_the_version_is_learned() {
+        use crate::version::VersionOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.1.0"}"#)
+            .create();
+
+        assert!(client.with_min_version("not a requirement").is_err());
+
+        let client = FirecrackerClient::new(&server.url())
+            .await
+            .unwrap()
+            .with_min_version("^1.4")
+            .unwrap();
+        assert!(matches!(
+            client.negotiate().await,
+            Err(crate::FirecrackerError::UnsupportedVmmVersion { .. })
+        ));
+        assert!(matches!(
+            client.get_version().await,
+            Err(crate::FirecrackerError::UnsupportedVmmVersion { .. })
+        ));
+
+        let client = FirecrackerClient::new(&server.url())
+            .await
+            .unwrap()
+            .with_min_version("^1.4")
+            .unwrap();
+        assert!(matches!(
+            client.set_vmm_version(crate::version::Version::new(1, 1, 0)),
+            Err(crate::FirecrackerError::UnsupportedVmmVersion { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_require_version_unreachable() {
+        use crate::version::{VersionOperations, VersionReq};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client = FirecrackerClient::new(&format!("http://{}", addr))
+            .await
+            .unwrap();
+        assert!(matches!(
+            client
+                .require_version(&VersionReq::parse("^1.4").unwrap())
+                .await,
+            Err(crate::FirecrackerError::HttpClient(_))
+        ));
+        assert!(client.capabilities().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_falls_back_to_instance_info() {
+        use crate::version::{VersionOperations, VersionReq, VersionSource};
+
+        let (mut server, client) = create_test_client().await;
+        let version = server
+            .mock("GET", "/version")
+            .with_status(404)
+            .with_body("blocked")
+            .expect(1)
+            .create();
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(
+                r#"{"app_name": "Firecracker", "id": "vm-1", "state": "Running", "vmm_version": "1.6.0"}"#,
+            )
+            .create();
+
+        let capabilities = client.negotiate().await.unwrap();
+        assert_eq!(capabilities.source, VersionSource::InstanceInfo);
+        assert_eq!(capabilities.version, crate::version::Version::new(1, 6, 0));
+        assert!(capabilities.supports_entropy);
+
+        client
+            .require_version(&VersionReq::parse("^1.4").unwrap())
+            .await
+            .unwrap();
+        version.assert();
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_records_version_endpoint_source() {
+        use crate::version::{VersionOperations, VersionSource};
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.6.0"}"#)
+            .create();
+        let instance = server.mock("GET", "/").expect(0).create();
+
+        let capabilities = client.negotiate().await.unwrap();
+        assert_eq!(capabilities.source, VersionSource::VersionEndpoint);
+        instance.assert();
+    }
+
+    fn snapshot_params() -> (
+        crate::snapshot::SnapshotCreateParams,
+        crate::snapshot::SnapshotLoadParams,
+    ) {
+        let create = crate::snapshot::SnapshotCreateParams {
+            snapshot_path: "/tmp/vm.snap".to_string(),
+            mem_file_path: "/tmp/vm.mem".to_string(),
+            snapshot_type: Some(crate::snapshot::SnapshotType::Full),
+            version: None,
+            allow_overwrite: false,
+        };
+        let load = crate::snapshot::SnapshotLoadParams {
+            snapshot_path: "/tmp".to_string(),
+            mem_file_path: Some("/tmp".to_string()),
+            mem_backend: None,
+            enable_diff_snapshots: None,
+            resume_vm: Some(true),
+            network_overrides: Vec::new(),
+        };
+        (create, load)
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_fields_on_old_firecracker() {
+        use crate::snapshot::SnapshotOperations;
+        use crate::version::Version;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+        let create_mock = server
+            .mock("PUT", "/snapshot/create")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": "/tmp/vm.snap",
+                "mem_file_path": "/tmp/vm.mem",
+                "snapshot_type": "Full",
+                "version": "0.25.0",
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let load_mock = server
+            .mock("PUT", "/snapshot/load")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": "/tmp",
+                "mem_file_path": "/tmp",
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        client.set_vmm_version(Version::new(0, 25, 0)).unwrap();
+
+        let (mut create, mut load) = snapshot_params();
+        assert!(matches!(
+            client.create_snapshot(&create).await,
+            Err(crate::FirecrackerError::Config(_))
+        ));
+        create.version = Some("0.25.0".to_string());
+        client.create_snapshot(&create).await.unwrap();
+
+        assert!(matches!(
+            client.load_snapshot(&load).await,
+            Err(crate::FirecrackerError::Config(_))
+        ));
+        load.resume_vm = None;
+        client.load_snapshot(&load).await.unwrap();
+
+        create_mock.assert();
+        load_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_fields_on_new_firecracker() {
+        use crate::snapshot::SnapshotOperations;
+        use crate::version::Version;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+        let create_mock = server
+            .mock("PUT", "/snapshot/create")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": "/tmp/vm.snap",
+                "mem_file_path": "/tmp/vm.mem",
+                "snapshot_type": "Full",
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let load_mock = server
+            .mock("PUT", "/snapshot/load")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": "/tmp",
+                "mem_file_path": "/tmp",
+                "resume_vm": true,
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        client.set_vmm_version(Version::new(1, 7, 0)).unwrap();
+
+        let (mut create, load) = snapshot_params();
+        create.version = Some("1.0".to_string());
+        match client.create_snapshot(&create).await {
+            Err(crate::FirecrackerError::Config(message)) => {
+                assert!(message.contains("1.7.0"), "{}", message)
+            }
+            other => panic!("expected config error, got {:?}", other),
+        }
+        create.version = None;
+        client.create_snapshot(&create).await.unwrap();
+        client.load_snapshot(&load).await.unwrap();
+
+        create_mock.assert();
+        load_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_fields_sent_as_given_without_version() {
+        use crate::snapshot::SnapshotOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let create_mock = server
+            .mock("PUT", "/snapshot/create")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": "/tmp/vm.snap",
+                "mem_file_path": "/tmp/vm.mem",
+                "snapshot_type": "Full",
+                "version": "1.0",
+            })))
+            .with_status(204)
+            .create();
+
+        let (mut create, _) = snapshot_params();
+        create.version = Some("1.0".to_string());
+        client.create_snapshot(&create).await.unwrap();
+        create_mock.assert();
+    }
+
+    #[test]
+    fn test_snapshot_load_mem_backend_file() {
+        use crate::snapshot::{MemBackend, SnapshotLoadParams};
+
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("vm.snap");
+        let mem = dir.path().join("vm.mem");
+        std::fs::write(&snapshot, b"state").unwrap();
+        std::fs::write(&mem, b"memory").unwrap();
+
+        let params = SnapshotLoadParams {
+            resume_vm: Some(true),
+            ..SnapshotLoadParams::with_mem_backend(
+                snapshot.to_str().unwrap(),
+                MemBackend::File {
+                    backend_path: mem.to_str().unwrap().to_string(),
+                },
+            )
+        };
+        assert!(params.validate().is_ok());
+        assert_eq!(
+            serde_json::to_value(&params).unwrap(),
+            serde_json::json!({
+                "snapshot_path": snapshot.to_str().unwrap(),
+                "mem_backend": {"backend_type": "File", "backend_path": mem.to_str().unwrap()},
+                "resume_vm": true,
+            })
+        );
+
+        let relative = SnapshotLoadParams::with_mem_backend(
+            snapshot.to_str().unwrap(),
+            MemBackend::File {
+                backend_path: "vm.mem".to_string(),
+            },
+        );
+        assert!(relative
+            .validate()
+            .unwrap_err()
+            .field_errors()
+            .contains_key("mem_backend"));
+    }
+
+    #[test]
+    fn test_snapshot_load_mem_backend_uds() {
+        use crate::snapshot::{MemBackend, SnapshotLoadParams};
+
+        let mut params = SnapshotLoadParams::with_mem_backend(
+            "/tmp",
+            MemBackend::Uds {
+                backend_path: "/run/uffd.sock".to_string(),
+            },
+        );
+        assert!(params.validate().is_ok());
+        assert_eq!(
+            serde_json::to_value(&params).unwrap()["mem_backend"],
+            serde_json::json!({"backend_type": "Uds", "backend_path": "/run/uffd.sock"})
+        );
+
+        params.mem_backend = Some(MemBackend::Uds {
+            backend_path: format!("/{}", "a".repeat(120)),
+        });
+        assert!(params.validate().is_err());
+
+        params.mem_backend = Some(MemBackend::Uds {
+            backend_path: "uffd.sock".to_string(),
+        });
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_snapshot_load_memory_source_conflicts() {
+        use crate::snapshot::{MemBackend, SnapshotLoadParams};
+
+        let both = SnapshotLoadParams {
+            mem_file_path: Some("/tmp".to_string()),
+            ..SnapshotLoadParams::with_mem_backend(
+                "/tmp",
+                MemBackend::Uds {
+                    backend_path: "/run/uffd.sock".to_string(),
+                },
+            )
+        };
+        let errors = both.validate().unwrap_err();
+        assert_eq!(
+            errors.errors()["__all__"],
+            validator::ValidationErrorsKind::Field(vec![validator::ValidationError {
+                code: "memory_source".into(),
+                message: Some("Exactly one of mem_file_path and mem_backend must be set".into()),
+                params: Default::default(),
+            }])
+        );
+
+        let neither = SnapshotLoadParams {
+            snapshot_path: "/tmp".to_string(),
+            ..Default::default()
+        };
+        assert!(neither.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_vm() {
+        let (mut server, client) = create_test_client().await;
+        let pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .create();
+        let resume = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Resumed"}),
+            ))
+            .with_status(204)
+            .create();
+
+        client.pause_vm().await.unwrap();
+        client.resume_vm().await.unwrap();
+        pause.assert();
+        resume.assert();
+    }
+
+    #[tokio::test]
+    async fn test_create_snapshot_safe() {
+        use crate::snapshot::{SafeSnapshotOptions, SnapshotOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .expect(2)
+            .create();
+        let resume = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Resumed"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let create = server
+            .mock("PUT", "/snapshot/create")
+            .with_status(204)
+            .expect(2)
+            .create();
+
+        let (params, _) = snapshot_params();
+        client
+            .create_snapshot_safe(&params, SafeSnapshotOptions::default())
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+
+        let outcome = client
+            .create_snapshot_safe(
+                &params,
+                SafeSnapshotOptions {
+                    leave_paused: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(outcome.snapshot.is_ok());
+        assert!(outcome.resume.is_none());
+
+        pause.assert();
+        resume.assert();
+        create.assert();
+    }
+
+    #[tokio::test]
+    async fn test_create_snapshot_safe_resumes_after_failed_create() {
+        use crate::snapshot::{SafeSnapshotOptions, SnapshotOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let _pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .create();
+        let resume = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Resumed"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let _create = server
+            .mock("PUT", "/snapshot/create")
+            .with_status(400)
+            .with_body("disk full")
+            .create();
+
+        let (params, _) = snapshot_params();
+        let outcome = client
+            .create_snapshot_safe(&params, SafeSnapshotOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(
+            outcome.snapshot,
+            Err(crate::FirecrackerError::Api {
+                status_code: 400,
+                ..
+            })
+        ));
+        assert!(matches!(outcome.resume, Some(Ok(()))));
+        assert!(outcome.into_result().is_err());
+        resume.assert();
+    }
+
+    #[tokio::test]
+    async fn test_create_snapshot_safe_stops_when_pause_fails() {
+        use crate::snapshot::{SafeSnapshotOptions, SnapshotOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let _pause = server.mock("PATCH", "/vm").with_status(400).create();
+        let create = server.mock("PUT", "/snapshot/create").expect(0).create();
+
+        let (params, _) = snapshot_params();
+        assert!(client
+            .create_snapshot_safe(&params, SafeSnapshotOptions::default())
+            .await
+            .is_err());
+        create.assert();
+    }
+
+    #[test]
+    fn test_snapshot_type_serde() {
+        use crate::snapshot::{SnapshotCreateParams, SnapshotType};
+
+        assert_eq!(
+            serde_json::to_value(SnapshotType::Diff).unwrap(),
+            serde_json::json!("Diff")
+        );
+        for (raw, expected) in [
+            ("\"Full\"", SnapshotType::Full),
+            ("\"diff\"", SnapshotType::Diff),
+            ("\"DIFF\"", SnapshotType::Diff),
+        ] {
+            assert_eq!(serde_json::from_str::<SnapshotType>(raw).unwrap(), expected);
+        }
+        assert!(serde_json::from_str::<SnapshotType>("\"Incremental\"").is_err());
+
+        let params: SnapshotCreateParams = serde_json::from_value(serde_json::json!({
+            "snapshot_path": "/tmp/vm.snap",
+            "mem_file_path": "/tmp/vm.mem",
+            "snapshot_type": "full",
+        }))
+        .unwrap();
+        assert_eq!(params.snapshot_type, Some(SnapshotType::Full));
+        assert_eq!(
+            serde_json::to_value(&params).unwrap()["snapshot_type"],
+            serde_json::json!("Full")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_diff_snapshot_checks() {
+        use crate::snapshot::{SnapshotOperations, SnapshotType};
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_strict_snapshot_checks(true);
+        let _machine = server
+            .mock("GET", "/machine-config")
+            .with_status(200)
+            .with_body(r#"{"vcpu_count": 2, "mem_size_mib": 256, "track_dirty_pages": false}"#)
+            .create();
+        let create = server
+            .mock("PUT", "/snapshot/create")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"snapshot_type": "Full"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let (mut params, _) = snapshot_params();
+        params.snapshot_type = Some(SnapshotType::Diff);
+        match client.create_snapshot(&params).await {
+            Err(crate::FirecrackerError::Snapshot(message)) => {
+                assert!(message.contains("Full snapshot"), "{}", message)
+            }
+            other => panic!("expected snapshot error, got {:?}", other),
+        }
+
+        params.snapshot_type = Some(SnapshotType::Full);
+        client.create_snapshot(&params).await.unwrap();
+
+        params.snapshot_type = Some(SnapshotType::Diff);
+        match client.create_snapshot(&params).await {
+            Err(crate::FirecrackerError::Snapshot(message)) => {
+                assert!(message.contains("track_dirty_pages"), "{}", message)
+            }
+            other => panic!("expected snapshot error, got {:?}", other),
+        }
+        create.assert();
+    }
+
+    fn chain_params(
+        dir: &std::path::Path,
+        name: &str,
+        snapshot_type: crate::snapshot::SnapshotType,
+    ) -> crate::snapshot::SnapshotCreateParams {
+        crate::snapshot::SnapshotCreateParams {
+            snapshot_path: dir
+                .join(format!("{}.snap", name))
+                .to_str()
+                .unwrap()
+                .to_string(),
+            mem_file_path: dir
+                .join(format!("{}.mem", name))
+                .to_str()
+                .unwrap()
+                .to_string(),
+            snapshot_type: Some(snapshot_type),
+            version: None,
+            allow_overwrite: false,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_chain_manifest_round_trip() {
+        use crate::snapshot::{SnapshotChain, SnapshotType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("chain.json");
+        let mut chain = SnapshotChain::new(&manifest);
+        chain
+            .record(&chain_params(dir.path(), "base", SnapshotType::Full))
+            .unwrap();
+        chain
+            .record(&chain_params(dir.path(), "d1", SnapshotType::Diff))
+            .unwrap();
+        let latest = chain
+            .record(&chain_params(dir.path(), "d2", SnapshotType::Diff))
+            .unwrap();
+        assert_eq!(
+            latest.parent.as_deref(),
+            dir.path().join("d1.snap").to_str()
+        );
+        chain.save().unwrap();
+
+        let loaded = SnapshotChain::load(&manifest).unwrap();
+        assert_eq!(loaded, chain);
+        assert_eq!(loaded.ancestry().unwrap().len(), 3);
+
+        let plan = loaded.restore_plan().unwrap();
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        assert_eq!(plan.snapshot_path, path("d2.snap"));
+        assert_eq!(plan.base_mem_file_path, path("base.mem"));
+        assert_eq!(
+            plan.diff_mem_file_paths,
+            vec![path("d1.mem"), path("d2.mem")]
+        );
+
+        // A new Full snapshot starts a new base
+        chain
+            .record(&chain_params(dir.path(), "base2", SnapshotType::Full))
+            .unwrap();
+        let plan = chain.restore_plan().unwrap();
+        assert_eq!(plan.base_mem_file_path, path("base2.mem"));
+        assert!(plan.diff_mem_file_paths.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_chain_rejects_invalid_chains() {
+        use crate::snapshot::{SnapshotChain, SnapshotType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut chain = SnapshotChain::new(dir.path().join("chain.json"));
+        assert!(chain
+            .record(&chain_params(dir.path(), "d1", SnapshotType::Diff))
+            .is_err());
+        assert!(chain.latest().is_none());
+        assert!(chain.restore_plan().is_err());
+
+        let orphan = dir.path().join("orphan.json");
+        std::fs::write(
+            &orphan,
+            r#"{"snapshots": [{"snapshot_path": "/s/d1", "mem_file_path": "/s/d1.mem", "snapshot_type": "Diff", "created_at": 0}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            SnapshotChain::load(&orphan),
+            Err(crate::FirecrackerError::Snapshot(_))
+        ));
+
+        let unknown_parent = dir.path().join("unknown.json");
+        std::fs::write(
+            &unknown_parent,
+            r#"{"snapshots": [
+                {"snapshot_path": "/s/base", "mem_file_path": "/s/base.mem", "snapshot_type": "Full", "created_at": 0},
+                {"snapshot_path": "/s/d1", "mem_file_path": "/s/d1.mem", "snapshot_type": "Diff", "created_at": 1, "parent": "/s/other"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(SnapshotChain::load(&unknown_parent).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_scheduler_rotates_old_chains() {
+        use crate::snapshot::{
+            RetentionPolicy, ScheduleOptions, Scheduler, SnapshotChain, SnapshotType,
+        };
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("chain.json");
+        let mut chain = SnapshotChain::new(&manifest);
+        for (name, snapshot_type) in [("base", SnapshotType::Full), ("d1", SnapshotType::Diff)] {
+            chain
+                .record(&chain_params(dir.path(), name, snapshot_type))
+                .unwrap();
+            std::fs::write(dir.path().join(format!("{}.snap", name)), b"s").unwrap();
+            std::fs::write(dir.path().join(format!("{}.mem", name)), b"m").unwrap();
+        }
+        chain.save().unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let _vm = server.mock("PATCH", "/vm").with_status(204).create();
+        let create = server
+            .mock("PUT", "/snapshot/create")
+            .with_status(204)
+            .expect(2)
+            .create();
+
+        let mut options =
+            ScheduleOptions::new(dir.path().join("scheduled"), Duration::from_secs(3600));
+        options.retention = RetentionPolicy {
+            max_diffs: 1,
+            keep_full: 1,
+        };
+        let scheduler = Scheduler::start(Arc::new(client), chain, options);
+        let mut status = scheduler.subscribe();
+
+        // The chain already has max_diffs Diffs, so this one is Full and the
+        // old chain's files go
+        scheduler.trigger();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            status.wait_for(|s| s.snapshots_taken == 1),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(scheduler.status().files_removed, 4);
+        assert!(!dir.path().join("base.mem").exists());
+        assert!(!dir.path().join("d1.snap").exists());
+        let loaded = SnapshotChain::load(&manifest).unwrap();
+        assert_eq!(loaded.snapshots().len(), 1);
+        assert_eq!(loaded.snapshots()[0].snapshot_type, SnapshotType::Full);
+
+        scheduler.trigger();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            status.wait_for(|s| s.snapshots_taken == 2),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let chain = scheduler.stop().await;
+        assert_eq!(SnapshotChain::load(&manifest).unwrap(), chain);
+        let latest = chain.latest().unwrap();
+        assert_eq!(latest.snapshot_type, SnapshotType::Diff);
+        assert_eq!(
+            latest.parent.as_ref(),
+            Some(&chain.snapshots()[0].snapshot_path)
+        );
+        assert!(latest
+            .snapshot_path
+            .starts_with(dir.path().join("scheduled").to_str().unwrap()));
+        create.assert();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_scheduler_backs_off_and_pauses() {
+        use crate::snapshot::{ScheduleOptions, Scheduler, SnapshotChain};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut server, client) = create_test_client().await;
+        let _pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .create();
+        let resume = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Resumed"}),
+            ))
+            .with_status(204)
+            .expect_at_least(2)
+            .create();
+        // Firecracker got as far as writing the files before it failed
+        let _create = server
+            .mock("PUT", "/snapshot/create")
+            .with_status(400)
+            .with_body_from_request(|request| {
+                let body: serde_json::Value =
+                    serde_json::from_slice(request.body().unwrap()).unwrap();
+                for key in ["snapshot_path", "mem_file_path"] {
+                    std::fs::write(body[key].as_str().unwrap(), b"partial").unwrap();
+                }
+                b"disk full".to_vec()
+            })
+            .create();
+
+        let mut options = ScheduleOptions::new(dir.path(), Duration::from_secs(3600));
+        options.initial_backoff = Duration::from_millis(10);
+        let scheduler = Scheduler::start(
+            Arc::new(client),
+            SnapshotChain::new(dir.path().join("chain.json")),
+            options,
+        );
+        let mut status = scheduler.subscribe();
+
+        // Failures are retried after the backoff, well before the interval
+        scheduler.trigger();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            status.wait_for(|s| s.consecutive_failures >= 2),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let current = scheduler.status();
+        assert_eq!(current.snapshots_taken, 0);
+        assert!(current.last_error.unwrap().contains("disk full"));
+        // The failed snapshots' files are gone and aren't counted as rotated
+        assert_eq!(current.files_removed, 0);
+        assert_eq!(current.last_cleanup_error, None);
+
+        scheduler.pause();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let failed = scheduler.status().snapshots_failed;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(scheduler.status().snapshots_failed, failed);
+        assert!(scheduler.status().paused);
+
+        let chain = scheduler.stop().await;
+        assert!(chain.snapshots().is_empty());
+        let left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert!(left.is_empty(), "{:?}", left);
+        // Every failed snapshot resumed the VM again
+        resume.assert();
+    }
+
+    #[tokio::test]
+    async fn test_create_snapshot_appends_to_chain() {
+        use crate::snapshot::{
+            SafeSnapshotOptions, SnapshotChain, SnapshotOperations, SnapshotType,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("chain.json");
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_snapshot_chain(SnapshotChain::new(&manifest));
+        let _vm = server.mock("PATCH", "/vm").with_status(204).create();
+        let _create = server
+            .mock("PUT", "/snapshot/create")
+            .with_status(204)
+            .create();
+
+        client
+            .create_snapshot(&chain_params(dir.path(), "base", SnapshotType::Full))
+            .await
+            .unwrap();
+        client
+            .create_snapshot_safe(
+                &chain_params(dir.path(), "d1", SnapshotType::Diff),
+                SafeSnapshotOptions::default(),
+            )
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+
+        let saved = SnapshotChain::load(&manifest).unwrap();
+        assert_eq!(saved.snapshots().len(), 2);
+        assert_eq!(Some(saved), client.snapshot_chain());
+    }
+
+    #[test]
+    fn test_network_override_validation() {
+        use crate::snapshot::NetworkOverride;
+
+        let valid = NetworkOverride {
+            iface_id: "eth0".to_string(),
+            host_dev_name: "tap-vm1".to_string(),
+        };
+        assert!(valid.validate().is_ok());
+
+        for host_dev_name in ["", "tap/0", "tap 0", "a-very-long-tap-name", ".."] {
+            let invalid = NetworkOverride {
+                host_dev_name: host_dev_name.to_string(),
+                ..valid.clone()
+            };
+            assert!(invalid.validate().is_err(), "{:?}", host_dev_name);
+        }
+
+        let (_, mut load) = snapshot_params();
+        load.network_overrides = vec![NetworkOverride {
+            iface_id: String::new(),
+            ..valid
+        }];
+        assert!(load.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_load_network_overrides() {
+        use crate::snapshot::{NetworkOverride, SnapshotOperations};
+        use crate::version::Version;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+        let load_mock = server
+            .mock("PUT", "/snapshot/load")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": "/tmp",
+                "mem_file_path": "/tmp",
+                "network_overrides": [{"iface_id": "eth0", "host_dev_name": "tap1"}],
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        client.set_vmm_version(Version::new(1, 6, 0)).unwrap();
+
+        let (_, mut load) = snapshot_params();
+        load.resume_vm = None;
+        load.network_overrides = vec![NetworkOverride {
+            iface_id: "eth0".to_string(),
+            host_dev_name: "tap1".to_string(),
+        }];
+        client.load_snapshot(&load).await.unwrap();
+        load_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_load_network_overrides_old_firecracker() {
+        use crate::snapshot::{NetworkOverride, SnapshotOperations};
+        use crate::version::Version;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+        let load_mock = server.mock("PUT", "/snapshot/load").expect(0).create();
+        client.set_vmm_version(Version::new(1, 5, 0)).unwrap();
+
+        let (_, mut load) = snapshot_params();
+        load.network_overrides = vec![NetworkOverride {
+            iface_id: "eth0".to_string(),
+            host_dev_name: "tap1".to_string(),
+        }];
+        match client.load_snapshot(&load).await {
+            Err(crate::FirecrackerError::Config(message)) => {
+                assert!(message.contains("network_overrides"), "{}", message)
+            }
+            other => panic!("expected config error, got {:?}", other),
+        }
+        load_mock.assert();
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_snapshot_create_local_path_checks() {
+        use crate::snapshot::{SnapshotOperations, SnapshotType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut server, client) = create_test_client().await;
+        let create = server
+            .mock("PUT", "/snapshot/create")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let mut params = chain_params(dir.path(), "vm", SnapshotType::Full);
+        params.mem_file_path = params.snapshot_path.clone();
+        let errors = params.validate().unwrap_err();
+        assert!(errors.errors().contains_key("__all__"));
+
+        let check_of = |result: Result<(), crate::FirecrackerError>| match result {
+            Err(crate::FirecrackerError::Validation(errors)) => errors
+                .field_errors()
+                .values()
+                .flat_map(|errors| errors.iter())
+                .map(|e| e.params["check"].as_str().unwrap().to_string())
+                .next()
+                .unwrap(),
+            other => panic!("expected validation error, got {:?}", other),
+        };
+
+        let mut params = chain_params(dir.path(), "vm", SnapshotType::Full);
+        params.snapshot_path = dir
+            .path()
+            .join("missing/vm.snap")
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            check_of(client.create_snapshot(&params).await),
+            "parent_exists"
+        );
+
+        let params = chain_params(dir.path(), "vm", SnapshotType::Full);
+        std::fs::write(&params.mem_file_path, b"old memory").unwrap();
+        assert_eq!(
+            check_of(client.create_snapshot(&params).await),
+            "no_overwrite"
+        );
+
+        let params = crate::snapshot::SnapshotCreateParams {
+            allow_overwrite: true,
+            ..params
+        };
+        client.create_snapshot(&params).await.unwrap();
+        create.assert();
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_snapshot_load_local_path_checks() {
+        use crate::snapshot::{MemBackend, SnapshotLoadParams, SnapshotOperations};
+
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("vm.snap");
+        let mem = dir.path().join("vm.mem");
+        std::fs::write(&snapshot, b"state").unwrap();
+        std::fs::write(&mem, b"").unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let load = server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let params = SnapshotLoadParams::with_mem_backend(
+            snapshot.to_str().unwrap(),
+            MemBackend::File {
+                backend_path: mem.to_str().unwrap().to_string(),
+            },
+        );
+        match client.load_snapshot(&params).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                let errors = &errors.field_errors()["mem_backend"];
+                assert_eq!(errors[0].params["check"], "non_empty");
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        let params = SnapshotLoadParams {
+            snapshot_path: dir
+                .path()
+                .join("missing.snap")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            ..params
+        };
+        match client.load_snapshot(&params).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                let errors = &errors.field_errors()["snapshot_path"];
+                assert_eq!(errors[0].params["check"], "exists");
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        std::fs::write(&mem, b"memory").unwrap();
+        let params = SnapshotLoadParams {
+            snapshot_path: snapshot.to_str().unwrap().to_string(),
+            ..params
+        };
+        client.load_snapshot(&params).await.unwrap();
+        load.assert();
+    }
+
+    fn instance_body(state: &str) -> String {
+        serde_json::json!({
+            "app_name": "Firecracker",
+            "id": "vm-1",
+            "state": state,
+            "vmm_version": "1.7.0",
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_into_new_vm() {
+        use crate::snapshot::{NetworkOverride, RestoreOptions, SnapshotChain, SnapshotType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let params = chain_params(dir.path(), "base", SnapshotType::Full);
+        std::fs::write(&params.snapshot_path, b"state").unwrap();
+        std::fs::write(&params.mem_file_path, b"memory").unwrap();
+        let mut chain = SnapshotChain::new(dir.path().join("chain.json"));
+        chain.record(&params).unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let starting = server.mock("GET", "/").with_status(503).expect(1).create();
+        let _not_started = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .expect(1)
+            .create();
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+            .create();
+        let load = server
+            .mock("PUT", "/snapshot/load")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": params.snapshot_path,
+                "mem_backend": {"backend_type": "File", "backend_path": params.mem_file_path},
+                "resume_vm": true,
+                "network_overrides": [{"iface_id": "eth0", "host_dev_name": "tap7"}],
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let _running = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+
+        let info = crate::snapshot::restore(
+            &client,
+            chain,
+            RestoreOptions {
+                network_overrides: vec![NetworkOverride {
+                    iface_id: "eth0".to_string(),
+                    host_dev_name: "tap7".to_string(),
+                }],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(info.state, "Running");
+        starting.assert();
+        load.assert();
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_resumes_separately_on_old_firecracker() {
+        use crate::snapshot::{RestoreOptions, RestoreStep, SnapshotLoadParams};
+
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("vm.snap");
+        let mem = dir.path().join("vm.mem");
+        std::fs::write(&snapshot, b"state").unwrap();
+        std::fs::write(&mem, b"memory").unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "0.25.0"}"#)
+            .create();
+        let _not_started = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .expect(1)
+            .create();
+        let load = server
+            .mock("PUT", "/snapshot/load")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "snapshot_path": snapshot.to_str().unwrap(),
+                "mem_file_path": mem.to_str().unwrap(),
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let resume = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Resumed"}),
+            ))
+            .with_status(400)
+            .with_body("vcpu error")
+            .expect(1)
+            .create();
+
+        let params = SnapshotLoadParams {
+            snapshot_path: snapshot.to_str().unwrap().to_string(),
+            mem_file_path: Some(mem.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+        match crate::snapshot::restore(&client, params, RestoreOptions::default()).await {
+            Err(crate::FirecrackerError::Restore { step, source }) => {
+                assert_eq!(step, RestoreStep::Resume);
+                assert!(
+                    matches!(
+                        *source,
+                        crate::FirecrackerError::Api {
+                            status_code: 400,
+                            ..
+                        }
+                    ),
+                    "{:?}",
+                    source
+                );
+            }
+            other => panic!("expected restore error, got {:?}", other),
+        }
+        load.assert();
+        resume.assert();
+        pause.assert();
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_checks_final_state() {
+        use crate::snapshot::{RestoreOptions, RestoreStep, SnapshotLoadParams};
+
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("vm.snap");
+        let mem = dir.path().join("vm.mem");
+        std::fs::write(&snapshot, b"state").unwrap();
+        std::fs::write(&mem, b"memory").unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+            .create();
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        let _load = server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .create();
+
+        let params = SnapshotLoadParams {
+            snapshot_path: snapshot.to_str().unwrap().to_string(),
+            mem_file_path: Some(mem.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let options = RestoreOptions {
+            resume: false,
+            ..Default::default()
+        };
+        // The VM runs although it should be paused, so it gets paused
+        let pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+        match crate::snapshot::restore(&client, params, options).await {
+            Err(crate::FirecrackerError::Restore { step, source }) => {
+                assert_eq!(step, RestoreStep::Verify);
+                match *source {
+                    crate::FirecrackerError::InvalidState {
+                        current_state,
+                        expected_states,
+                    } => {
+                        assert_eq!(current_state, "Running");
+                        assert_eq!(expected_states, vec!["Paused".to_string()]);
+                    }
+                    other => panic!("expected invalid state, got {:?}", other),
+                }
+            }
+            other => panic!("expected restore error, got {:?}", other),
+        }
+        pause.assert();
+
+        // A load that never answers runs out of the restore's own budget
+        let (mut server, client) = create_test_client().await;
+        let _version = server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+            .create();
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .create();
+        let _load = server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .with_body_from_request(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                Vec::new()
+            })
+            .create();
+        let params = SnapshotLoadParams {
+            snapshot_path: snapshot.to_str().unwrap().to_string(),
+            mem_file_path: Some(mem.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let options = RestoreOptions {
+            timeout: std::time::Duration::from_millis(200),
+            ..Default::default()
+        };
+        match crate::snapshot::restore(&client, params, options).await {
+            Err(crate::FirecrackerError::Restore { step, source }) => {
+                assert_eq!(step, RestoreStep::Load);
+                match *source {
+                    crate::FirecrackerError::RequestTimeout { limit, .. } => {
+                        assert_eq!(limit, std::time::Duration::from_millis(200))
+                    }
+                    other => panic!("expected a timeout, got {:?}", other),
+                }
+            }
+            other => panic!("expected restore error, got {:?}", other),
+        }
+
+        // A chain with nothing to restore fails before anything is sent
+        let (mut server, client) = create_test_client().await;
+        let untouched = server.mock("GET", mockito::Matcher::Any).expect(0).create();
+        let empty = crate::snapshot::SnapshotChain::new("/tmp/chain.json");
+        match crate::snapshot::restore(&client, empty, RestoreOptions::default()).await {
+            Err(crate::FirecrackerError::Restore { step, source }) => {
+                assert_eq!(step, RestoreStep::Plan);
+                assert!(matches!(*source, crate::FirecrackerError::Snapshot(_)));
+            }
+            other => panic!("expected restore error, got {:?}", other),
+        }
+        untouched.assert();
+    }
+
+    fn fixture_path(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_snapshot_check_compat() {
+        use crate::models::FirecrackerVersion;
+        use crate::snapshot::{check_compat, SnapshotFormat};
+        use crate::version::Version;
+
+        let v1 = fixture_path("snapshot_header_v1.bin");
+        let report = check_compat(&v1, &FirecrackerVersion::new("1.7.0")).unwrap();
+        assert_eq!(report.arch, "x86_64");
+        assert_eq!(
+            report.format,
+            SnapshotFormat::Versioned(Version::new(1, 0, 0))
+        );
+        assert_eq!(report.target_format, Some(Version::new(1, 0, 0)));
+        assert!(report.compatible);
+
+        let report = check_compat(&v1, &FirecrackerVersion::new("1.10.1")).unwrap();
+        assert!(!report.compatible);
+        assert!(report.reason.unwrap().contains("1.0.0"));
+
+        let report = check_compat(&v1, &FirecrackerVersion::new("1.5.0")).unwrap();
+        assert!(!report.compatible);
+        assert_eq!(report.target_format, None);
+
+        let v4 = fixture_path("snapshot_header_v4_aarch64.bin");
+        let report = check_compat(&v4, &FirecrackerVersion::new("1.10.0-dev")).unwrap();
+        assert_eq!(report.arch, "aarch64");
+        assert!(report.compatible);
+
+        let legacy = fixture_path("snapshot_header_legacy.bin");
+        let report = check_compat(&legacy, &FirecrackerVersion::new("1.4.1")).unwrap();
+        assert_eq!(
+            report.format,
+            SnapshotFormat::Legacy {
+                format_version: 2,
+                data_version: 5
+            }
+        );
+        assert!(report.compatible);
+        assert!(
+            !check_compat(&legacy, &FirecrackerVersion::new("1.7.0"))
+                .unwrap()
+                .compatible
+        );
+
+        assert!(matches!(
+            check_compat(
+                fixture_path("full_config_schema.json"),
+                &FirecrackerVersion::new("1.7.0")
+            ),
+            Err(crate::FirecrackerError::Snapshot(_))
+        ));
+
+        // The version is a length-prefixed string, not three integers
+        let dir = tempfile::tempdir().unwrap();
+        let garbled = dir.path().join("garbled.snap");
+        let mut header = std::fs::read(&v1).unwrap();
+        header[16..21].copy_from_slice(&[1, 0, 0, 0, 0]);
+        std::fs::write(&garbled, header).unwrap();
+        assert!(matches!(
+            check_compat(&garbled, &FirecrackerVersion::new("1.7.0")),
+            Err(crate::FirecrackerError::Snapshot(_))
+        ));
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_load_snapshot_runs_compat_check() {
+        use crate::snapshot::{SnapshotLoadParams, SnapshotOperations};
+        use crate::version::Version;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_snapshot_compat_check(true);
+        let load = server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .expect(1)
+            .create();
+        client.set_vmm_version(Version::new(1, 10, 0)).unwrap();
+
+        let params = SnapshotLoadParams {
+            snapshot_path: fixture_path("snapshot_header_v1.bin")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            mem_file_path: Some(
+                fixture_path("snapshot_header_v4_aarch64.bin")
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        match client.load_snapshot(&params).await {
+            Err(crate::FirecrackerError::Snapshot(message)) => {
+                assert!(message.contains("not supported"), "{}", message)
+            }
+            other => panic!("expected snapshot error, got {:?}", other),
+        }
+
+        let params = SnapshotLoadParams {
+            snapshot_path: fixture_path("snapshot_header_v4_aarch64.bin")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            ..params
+        };
+        client.load_snapshot(&params).await.unwrap();
+        load.assert();
+    }
+
+    #[test]
+    fn test_snapshot_estimate_size() {
+        use crate::snapshot::{estimate_size, SnapshotType};
+        use crate::MachineConfig;
+
+        const MIB: u64 = 1024 * 1024;
+        let config = MachineConfig {
+            mem_size_mib: Some(1024),
+            vcpu_count: Some(2),
+            ..Default::default()
+        };
+
+        let full = estimate_size(&config, SnapshotType::Full);
+        assert_eq!(full.min_mem_bytes, 1024 * MIB);
+        assert_eq!(full.max_mem_bytes, 1024 * MIB);
+        assert_eq!(full.state_bytes, 512 * 1024 + 2 * 64 * 1024);
+        assert_eq!(full.max_total_bytes(), 1024 * MIB + 640 * 1024);
+        assert_eq!(full.to_string(), "~1025 MiB (approximate)");
+
+        let diff = estimate_size(&config, SnapshotType::Diff);
+        assert_eq!(diff.min_mem_bytes, 0);
+        assert_eq!(diff.max_mem_bytes, 1024 * MIB);
+        assert_eq!(diff.to_string(), "~1-1025 MiB (approximate)");
+
+        let defaults = estimate_size(&MachineConfig::default(), SnapshotType::Full);
+        assert_eq!(defaults.max_mem_bytes, 128 * MIB);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_snapshot_check_destination_space() {
+        use crate::snapshot::{check_destination_space, SizeEstimate, SnapshotType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let params = chain_params(dir.path(), "vm", SnapshotType::Full);
+
+        let small = SizeEstimate {
+            state_bytes: 1024,
+            min_mem_bytes: 4096,
+            max_mem_bytes: 4096,
+        };
+        check_destination_space(&params, &small).unwrap();
+
+        let huge = SizeEstimate {
+            max_mem_bytes: u64::MAX / 2,
+            ..small
+        };
+        match check_destination_space(&params, &huge) {
+            Err(crate::FirecrackerError::InsufficientSpace {
+                path,
+                required_bytes,
+                available_bytes,
+            }) => {
+                assert_eq!(path, dir.path());
+                assert_eq!(required_bytes, u64::MAX / 2 + 1024);
+                assert!(available_bytes < required_bytes);
+            }
+            other => panic!("expected insufficient space, got {:?}", other),
+        }
+
+        // Separate directories on one filesystem share its free space
+        let state_dir = dir.path().join("state");
+        let mem_dir = dir.path().join("mem");
+        std::fs::create_dir(&state_dir).unwrap();
+        std::fs::create_dir(&mem_dir).unwrap();
+        let params = crate::snapshot::SnapshotCreateParams::new(
+            state_dir.join("vm.snap").display().to_string(),
+            mem_dir.join("vm.mem").display().to_string(),
+        );
+        let split = SizeEstimate {
+            state_bytes: u64::MAX / 4,
+            min_mem_bytes: 0,
+            max_mem_bytes: u64::MAX / 4,
+        };
+        match check_destination_space(&params, &split) {
+            Err(crate::FirecrackerError::InsufficientSpace {
+                path,
+                required_bytes,
+                ..
+            }) => {
+                assert_eq!(path, mem_dir);
+                assert_eq!(required_bytes, u64::MAX / 4 * 2);
+            }
+            other => panic!("expected insufficient space, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate_unix_path_parent_components() {
+        use crate::validation::validate_unix_path;
+
+        for valid in [
+            "/srv/images/ubuntu-22.04..base.ext4",
+            "/data/v1..2/disk.img",
+            "/data/..hidden",
+            "/data/disk...img",
+            "/var/lib/firecracker/",
+            "/var/lib//firecracker/./rootfs.ext4",
+        ] {
+            assert!(validate_unix_path(valid).is_ok(), "{:?}", valid);
+        }
+
+        for invalid in [
+            "/data/../etc/passwd",
+            "/..",
+            "/data/..",
+            "/data/../",
+            "/data//../disk.img",
+            "relative/disk.img",
+            "../disk.img",
+            "/data/disk\0.img",
+            "",
+        ] {
+            assert!(validate_unix_path(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dotted_file_names_pass_endpoint_validation() {
+        let (mut server, client) = create_test_client().await;
+        let _mock = server.mock("PUT", "/logger").with_status(204).create();
+
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("ubuntu-22.04..base.ext4");
+        std::fs::write(&image, b"rootfs").unwrap();
+
+        let drive = crate::Drive {
+            drive_id: "rootfs".to_string(),
+            path_on_host: image.to_str().unwrap().to_string(),
+            is_root_device: true,
+            is_read_only: false,
+            ..Default::default()
+        };
+        assert!(drive.validate().is_ok());
+
+        let logger = Logger {
+            log_path: dir.path().join("v1..2.log").to_str().unwrap().to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        client.put_logger(&logger).await.unwrap();
+
+        let vsock = Vsock {
+            guest_cid: 3,
+            uds_path: "/data/v1..2/vsock.sock".to_string(),
+            vsock_id: None,
+        };
+        assert!(vsock.validate().is_ok());
+
+        let traversal = crate::Drive {
+            path_on_host: format!("{}/../../etc/shadow", dir.path().display()),
+            ..drive
+        };
+        assert!(traversal.validate().is_err());
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_socket_path() {
+        use crate::validation::{validate_socket_path, SocketPathOptions, MAX_UDS_PATH_LEN};
+        use std::os::unix::net::UnixListener;
+
+        let dir = tempfile::tempdir().unwrap();
+        let create = SocketPathOptions {
+            create: true,
+            ..Default::default()
+        };
+        let connect = SocketPathOptions::default();
+        let check = |err: validator::ValidationError| err.params["check"].clone();
+
+        // Happy case: a new socket in a writable directory, and a live one to
+        // connect to
+        let new_socket = dir.path().join("new.sock");
+        validate_socket_path(new_socket.to_str().unwrap(), &create).unwrap();
+        let err = validate_socket_path(new_socket.to_str().unwrap(), &connect).unwrap_err();
+        assert_eq!(check(err), "exists");
+
+        let live = dir.path().join("live.sock");
+        let _listener = UnixListener::bind(&live).unwrap();
+        validate_socket_path(live.to_str().unwrap(), &connect).unwrap();
+        validate_socket_path(live.to_str().unwrap(), &create).unwrap();
+
+        // A regular file where the socket should be
+        let file = dir.path().join("file.sock");
+        std::fs::write(&file, b"not a socket").unwrap();
+        for options in [create, connect] {
+            let err = validate_socket_path(file.to_str().unwrap(), &options).unwrap_err();
+            assert_eq!(check(err), "is_socket");
+        }
+        let replace = SocketPathOptions {
+            allow_replace: true,
+            ..create
+        };
+        validate_socket_path(file.to_str().unwrap(), &replace).unwrap();
+
+        // Too long for sockaddr_un
+        let long = format!("{}/{}", dir.path().display(), "s".repeat(MAX_UDS_PATH_LEN));
+        assert!(validate_socket_path(&long, &create).is_err());
+
+        // Missing parent directory
+        let orphan = dir.path().join("missing").join("vm.sock");
+        let err = validate_socket_path(orphan.to_str().unwrap(), &create).unwrap_err();
+        assert_eq!(check(err), "parent_exists");
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_fields_checked_before_request() {
+        use crate::snapshot::{MemBackend, SnapshotLoadParams};
+        use crate::vsock::VsockOperations;
+        use crate::{DriveOperations, SnapshotOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let vsock_mock = server.mock("PUT", "/vsock").expect(0).create();
+        let drive_mock = server.mock("PUT", "/drives/data").expect(0).create();
+        let load_mock = server.mock("PUT", "/snapshot/load").expect(0).create();
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("regular");
+        std::fs::write(&file, b"data").unwrap();
+        let file = file.to_str().unwrap().to_string();
+
+        let vsock = Vsock {
+            guest_cid: 3,
+            uds_path: file.clone(),
+            vsock_id: None,
+        };
+        assert!(client.put_vsock(&vsock).await.is_err());
+
+        let drive = crate::Drive {
+            drive_id: "data".to_string(),
+            socket: Some(file.clone()),
+            ..Default::default()
+        };
+        assert!(client.put_drive("data", &drive).await.is_err());
+
+        let snapshot = dir.path().join("vm.snap");
+        std::fs::write(&snapshot, b"state").unwrap();
+        let params = SnapshotLoadParams::with_mem_backend(
+            snapshot.to_str().unwrap(),
+            MemBackend::Uds { backend_path: file },
+        );
+        assert!(client.load_snapshot(&params).await.is_err());
+
+        vsock_mock.assert();
+        drive_mock.assert();
+        load_mock.assert();
+    }
+
+    fn bad_rate_limiter() -> crate::RateLimiter {
+        crate::RateLimiter {
+            bandwidth: Some(crate::TokenBucket {
+                one_time_burst: None,
+                refill_time: 0,
+                size: 1,
+            }),
+            ops: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_endpoint_rejects_invalid_models_locally() {
+        use crate::action::ActionOperations;
+        use crate::boot::BootSourceOperations;
+        use crate::drive::DriveUpdate;
+        use crate::machine::MachineConfigOperations;
+        use crate::network::NetworkInterfaceUpdate;
+        use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams};
+        use crate::{BootSource, Drive, MachineConfig, NetworkInterface};
+        use crate::{DriveOperations, NetworkInterfaceOperations, SnapshotOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let mocks: Vec<_> = [
+            ("PUT", "/boot-source"),
+            ("PUT", "/drives/rootfs"),
+            ("PATCH", "/drives/rootfs"),
+            ("PUT", "/network-interfaces/eth0"),
+            ("PATCH", "/network-interfaces/eth0"),
+            ("PUT", "/machine-config"),
+            ("PATCH", "/machine-config"),
+            ("PUT", "/vm/config"),
+            ("PUT", "/balloon"),
+            ("PATCH", "/balloon"),
+            ("PATCH", "/balloon/statistics"),
+            ("PUT", "/cpu-config"),
+            ("PUT", "/entropy"),
+            ("PUT", "/actions"),
+            ("PUT", "/logger"),
+            ("PUT", "/metrics"),
+            ("PUT", "/vsock"),
+            ("PUT", "/snapshot/create"),
+            ("PUT", "/snapshot/load"),
+        ]
+        .into_iter()
+        .map(|(method, path)| server.mock(method, path).expect(0).create())
+        .collect();
+
+        let rejected = |result: Result<(), crate::FirecrackerError>, endpoint: &str| match result {
+            Err(crate::FirecrackerError::Validation(_)) => {}
+            other => panic!("{} was not rejected locally: {:?}", endpoint, other),
+        };
+
+        let boot = BootSource {
+            kernel_image_path: "vmlinux".to_string(),
+            ..Default::default()
+        };
+        rejected(client.put_boot_source(&boot).await, "PUT /boot-source");
+
+        let drive = Drive {
+            drive_id: "rootfs".to_string(),
+            path_on_host: "/data/../etc/shadow".to_string(),
+            ..Default::default()
+        };
+        rejected(client.put_drive("rootfs", &drive).await, "PUT /drives");
+        let drive_patch = DriveUpdate {
+            drive_id: "rootfs".to_string(),
+            path_on_host: Some("/data/../etc/shadow".to_string()),
+            ..Default::default()
+        };
+        rejected(
+            client.patch_drive("rootfs", &drive_patch).await,
+            "PATCH /drives",
+        );
+
+        let iface = NetworkInterface {
+            iface_id: "eth0".to_string(),
+            host_dev_name: "tap0".to_string(),
+            guest_mac: Some("not-a-mac".to_string()),
+            ..Default::default()
+        };
+        rejected(
+            client.put_network_interface("eth0", &iface).await,
+            "PUT /network-interfaces",
+        );
+        let iface_patch = NetworkInterfaceUpdate {
+            iface_id: "eth0".to_string(),
+            rx_rate_limiter: bad_rate_limiter().into(),
+            ..Default::default()
+        };
+        rejected(
+            client.patch_network_interface("eth0", &iface_patch).await,
+            "PATCH /network-interfaces",
+        );
+
+        let machine = MachineConfig {
+            vcpu_count: Some(0),
+            ..Default::default()
+        };
+        rejected(
+            client.put_machine_config(&machine).await,
+            "PUT /machine-config",
+        );
+        rejected(
+            client.patch_machine_config(&machine).await,
+            "PATCH /machine-config",
+        );
+
+        let vm_config = VmConfig {
+            vcpu_count: Some(64),
+            mem_size_mib: None,
+            ht_enabled: None,
+            track_dirty_pages: None,
+        };
+        rejected(client.put_vm_config(&vm_config).await, "PUT /vm/config");
+
+        // Firecracker counts the balloon in 4 KiB pages held in a u32
+        let balloon = crate::Balloon::new(crate::validation::MAX_BALLOON_MIB + 1);
+        rejected(client.put_balloon_config(&balloon).await, "PUT /balloon");
+        let balloon_patch = crate::balloon::BalloonUpdate::new(u32::MAX);
+        rejected(
+            client.patch_balloon_config(&balloon_patch).await,
+            "PATCH /balloon",
+        );
+        let stats_patch =
+            BalloonStatsUpdate::new(crate::validation::MAX_STATS_POLLING_INTERVAL_S + 1);
+        rejected(
+            client.patch_balloon_stats(&stats_patch).await,
+            "PATCH /balloon/statistics",
+        );
+
+        rejected(
+            client.put_cpu_config(&CpuConfig::new("T3")).await,
+            "PUT /cpu-config",
+        );
+
+        let entropy = EntropyDevice {
+            rate_limiter: Some(bad_rate_limiter()),
+        };
+        rejected(client.put_entropy_device(&entropy).await, "PUT /entropy");
+
+        let action = crate::action::InstanceActionInfo::new("Reboot");
+        rejected(
+            ActionOperations::create_sync_action(&client, &action).await,
+            "PUT /actions",
+        );
+
+        let logger = Logger {
+            log_path: "relative.log".to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        rejected(client.put_logger(&logger).await, "PUT /logger");
+
+        let metrics = Metrics {
+            metrics_path: "relative.metrics".to_string(),
+        };
+        rejected(client.put_metrics(&metrics).await, "PUT /metrics");
+
+        let vsock = Vsock {
+            guest_cid: 2,
+            uds_path: "/tmp/vsock.sock".to_string(),
+            vsock_id: None,
+        };
+        rejected(client.put_vsock(&vsock).await, "PUT /vsock");
+
+        let create = SnapshotCreateParams {
+            snapshot_path: "/tmp/vm.snap".to_string(),
+            mem_file_path: "/tmp/vm.snap".to_string(),
+            ..Default::default()
+        };
+        rejected(
+            client.create_snapshot(&create).await,
+            "PUT /snapshot/create",
+        );
+
+        let load = SnapshotLoadParams {
+            snapshot_path: "/tmp/vm.snap".to_string(),
+            ..Default::default()
+        };
+        rejected(client.load_snapshot(&load).await, "PUT /snapshot/load");
+
+        for mock in mocks {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_patch_requests_use_relaxed_rules() {
+        use crate::drive::DriveUpdate;
+        use crate::network::NetworkInterfaceUpdate;
+        use crate::validation::ValidationMode;
+        use crate::NetworkInterface;
+        use crate::{DriveOperations, NetworkInterfaceOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let drive_patch = server
+            .mock("PATCH", "/drives/rootfs")
+            .with_status(204)
+            .expect(2)
+            .create();
+        let iface_put = server
+            .mock("PUT", "/network-interfaces/eth0")
+            .with_status(204)
+            .expect(1)
+            .create();
+        let iface_patch = server
+            .mock("PATCH", "/network-interfaces/eth0")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        // Only the rate limiter changes, so no backing file is given
+        let drive = DriveUpdate {
+            drive_id: "rootfs".to_string(),
+            rate_limiter: crate::RateLimiter::bandwidth(1024 * 1024).into(),
+            ..Default::default()
+        };
+        client.patch_drive("rootfs", &drive).await.unwrap();
+
+        // host_dev_name can't change after boot, so a PATCH doesn't carry it
+        let iface = NetworkInterface {
+            iface_id: "eth0".to_string(),
+            tx_rate_limiter: Some(crate::RateLimiter::bandwidth(1024 * 1024)),
+            ..Default::default()
+        };
+        assert!(client.put_network_interface("eth0", &iface).await.is_err());
+        let iface_update = NetworkInterfaceUpdate {
+            iface_id: "eth0".to_string(),
+            tx_rate_limiter: crate::RateLimiter::bandwidth(1024 * 1024).into(),
+            ..Default::default()
+        };
+        client
+            .patch_network_interface("eth0", &iface_update)
+            .await
+            .unwrap();
+
+        // With validation off even an invalid model is sent
+        let client = client.with_validation_mode(ValidationMode::Off);
+        let iface = NetworkInterface {
+            guest_mac: Some("not-a-mac".to_string()),
+            ..iface
+        };
+        client.put_network_interface("eth0", &iface).await.unwrap();
+        let drive = DriveUpdate {
+            drive_id: String::new(),
+            ..drive
+        };
+        client.patch_drive("rootfs", &drive).await.unwrap();
+
+        drive_patch.assert();
+        iface_put.assert();
+        iface_patch.assert();
+    }
+
+    // Paths that only the filesystem checks reject
+    fn fs_only_failures() -> Vec<Result<(), validator::ValidationError>> {
+        use crate::validation::{
+            validate_existing_path, validate_nonempty_file, validate_socket_path,
+            validate_writable_path, SocketPathOptions,
+        };
+
+        vec![
+            validate_existing_path("/nonexistent-dir/vmlinux"),
+            validate_writable_path("/nonexistent-dir/firecracker.log"),
+            validate_nonempty_file("/nonexistent-dir/vm.snap"),
+            validate_socket_path("/nonexistent-dir/uffd.sock", &SocketPathOptions::default()),
+        ]
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[test]
+    fn test_fs_validation_enabled() {
+        for result in fs_only_failures() {
+            assert!(result.is_err());
+        }
+    }
+
+    #[cfg(feature = "no-fs-validation")]
+    #[tokio::test]
+    async fn test_no_fs_validation_only_checks_syntax() {
+        use crate::validation::{validate_existing_path, ValidationMode};
+
+        for result in fs_only_failures() {
+            assert!(result.is_ok(), "{:?}", result);
+        }
+        assert!(validate_existing_path("relative/vmlinux").is_err());
+        assert!(validate_existing_path("/images/../vmlinux").is_err());
+
+        let (mut server, client) = create_test_client().await;
+        let _mock = server.mock("PUT", "/logger").with_status(204).create();
+        assert_eq!(client.validation_mode(), ValidationMode::StrictLocal);
+        let logger = Logger {
+            log_path: "/nonexistent-dir/firecracker.log".to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        client.put_logger(&logger).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_put_network_interface_accepts_tap_names() {
+        use crate::{NetworkInterface, NetworkInterfaceOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let mock = server
+            .mock("PUT", "/network-interfaces/eth0")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let iface = NetworkInterface {
+            iface_id: "eth0".to_string(),
+            host_dev_name: "tap0".to_string(),
+            ..Default::default()
+        };
+        client.put_network_interface("eth0", &iface).await.unwrap();
+
+        let bad = NetworkInterface {
+            host_dev_name: "/dev/net/tap0".to_string(),
+            ..iface
+        };
+        assert!(client.put_network_interface("eth0", &bad).await.is_err());
+
+        mock.assert();
+    }
+
+    // A configuration that passes validate_vm_config
+    fn lint_clean_config() -> crate::FullVmConfiguration {
+        use crate::{BootSource, Drive, MachineConfig, NetworkInterface};
+
+        crate::FullVmConfiguration {
+            boot_source: Some(BootSource {
+                kernel_image_path: "/images/vmlinux".to_string(),
+                boot_args: Some("console=ttyS0".to_string()),
+                initrd_path: None,
+            }),
+            machine_config: Some(MachineConfig {
+                vcpu_count: Some(2),
+                mem_size_mib: Some(512),
+                ..Default::default()
+            }),
+            drives: vec![
+                Drive {
+                    drive_id: "rootfs".to_string(),
+                    path_on_host: "/images/rootfs.ext4".to_string(),
+                    is_root_device: true,
+                    ..Default::default()
+                },
+                Drive {
+                    drive_id: "data".to_string(),
+                    path_on_host: "/images/data.ext4".to_string(),
+                    ..Default::default()
+                },
+            ],
+            network_interfaces: vec![
+                NetworkInterface {
+                    iface_id: "eth0".to_string(),
+                    host_dev_name: "tap0".to_string(),
+                    guest_mac: Some("AA:FC:00:00:00:01".to_string()),
+                    ..Default::default()
+                },
+                NetworkInterface {
+                    iface_id: "eth1".to_string(),
+                    host_dev_name: "tap1".to_string(),
+                    guest_mac: Some("AA:FC:00:00:00:02".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn lint_rules(config: &crate::FullVmConfiguration) -> Vec<(&'static str, String)> {
+        crate::validation::validate_vm_config(config)
+            .findings
+            .into_iter()
+            .map(|f| (f.rule, f.path))
+            .collect()
+    }
+
+    #[test]
+    fn test_vm_config_lint_clean() {
+        let report = crate::validation::validate_vm_config(&lint_clean_config());
+        assert!(report.findings.is_empty(), "{}", report);
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn test_vm_config_lint_root_drive_missing() {
+        let mut config = lint_clean_config();
+        config.drives[0].is_root_device = false;
+        assert_eq!(
+            lint_rules(&config),
+            vec![("root_drive_missing", "drives".to_string())]
+        );
+
+        // Booting from an initrd needs no root drive
+        config.boot_source.as_mut().unwrap().initrd_path = Some("/images/initrd".to_string());
+        assert!(lint_rules(&config).is_empty());
+    }
+
+    #[test]
+    fn test_vm_config_lint_multiple_root_drives() {
+        let mut config = lint_clean_config();
+        config.drives[1].is_root_device = true;
+        assert_eq!(
+            lint_rules(&config),
+            vec![(
+                "multiple_root_drives",
+                "drives[1].is_root_device".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_duplicate_drive_id() {
+        let mut config = lint_clean_config();
+        config.drives[1].drive_id = "rootfs".to_string();
+        assert_eq!(
+            lint_rules(&config),
+            vec![("duplicate_drive_id", "drives[1].drive_id".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_duplicate_iface_id() {
+        let mut config = lint_clean_config();
+        config.network_interfaces[1].iface_id = "eth0".to_string();
+        assert_eq!(
+            lint_rules(&config),
+            vec![(
+                "duplicate_iface_id",
+                "network_interfaces[1].iface_id".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_duplicate_guest_mac() {
+        let mut config = lint_clean_config();
+        // Same address in another spelling
+        config.network_interfaces[1].guest_mac = Some("aa-fc-00-00-00-01".to_string());
+        assert_eq!(
+            lint_rules(&config),
+            vec![(
+                "duplicate_guest_mac",
+                "network_interfaces[1].guest_mac".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_duplicate_host_dev_name() {
+        let mut config = lint_clean_config();
+        config.network_interfaces[1].host_dev_name = "tap0".to_string();
+        assert_eq!(
+            lint_rules(&config),
+            vec![(
+                "duplicate_host_dev_name",
+                "network_interfaces[1].host_dev_name".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_balloon_exceeds_memory() {
+        let mut config = lint_clean_config();
+        config.balloon = Some(crate::Balloon {
+            amount_mib: 460,
+            deflate_on_oom: false,
+            stats_polling_interval_s: None,
+        });
+        assert!(lint_rules(&config).is_empty());
+
+        // From 90% of guest memory on the guest is left too little
+        for amount_mib in [461, 512] {
+            config.balloon.as_mut().unwrap().amount_mib = amount_mib;
+            assert_eq!(
+                lint_rules(&config),
+                vec![("balloon_near_memory", "balloon.amount_mib".to_string())]
+            );
+        }
+
+        config.balloon.as_mut().unwrap().amount_mib = 513;
+        assert_eq!(
+            lint_rules(&config),
+            vec![("balloon_exceeds_memory", "balloon.amount_mib".to_string())]
+        );
+
+        // Without a machine config the 128 MiB default applies
+        config.machine_config = None;
+        config.balloon.as_mut().unwrap().amount_mib = 256;
+        assert_eq!(
+            lint_rules(&config),
+            vec![("balloon_exceeds_memory", "balloon.amount_mib".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_mmds_without_interface() {
+        let mut config = lint_clean_config();
+        config.mmds_config = Some(crate::MmdsConfig::default());
+        assert_eq!(
+            lint_rules(&config),
+            vec![(
+                "mmds_without_interface",
+                "mmds_config.network_interfaces".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_mmds_unknown_interface() {
+        let mut config = lint_clean_config();
+        config.mmds_config = Some(crate::MmdsConfig {
+            network_interfaces: vec!["eth0".to_string(), "eth9".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(
+            lint_rules(&config),
+            vec![(
+                "mmds_unknown_interface",
+                "mmds_config.network_interfaces[1]".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_lint_shared_output_path() {
+        let mut config = lint_clean_config();
+        config.logger = Some(Logger {
+            log_path: "/var/log/fc/out".to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        });
+        config.metrics = Some(Metrics {
+            metrics_path: "/var/log/fc/out".to_string(),
+        });
+        assert_eq!(
+            lint_rules(&config),
+            vec![("shared_output_path", "metrics.metrics_path".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_vm_config_report_into_result() {
+        use crate::validation::{Finding, Severity, ValidationReport};
+
+        let warning = Finding {
+            severity: Severity::Warning,
+            rule: "example",
+            path: "drives".to_string(),
+            message: "just a warning".to_string(),
+        };
+        let report = ValidationReport {
+            findings: vec![warning.clone()],
+        };
+        assert_eq!(report.clone().into_result().unwrap(), report);
+
+        let mut config = lint_clean_config();
+        config.drives[1].drive_id = "rootfs".to_string();
+        match crate::validation::validate_vm_config(&config).into_result() {
+            Err(crate::FirecrackerError::InvalidConfig(report)) => {
+                assert_eq!(report.errors().count(), 1);
+                assert!(report.to_string().contains("duplicate_drive_id"));
+            }
+            other => panic!("expected InvalidConfig, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_lints_before_sending() {
+        use crate::config::{ApplyOptions, ConfigOperations};
+        use crate::validation::ValidationMode;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let mock = server
+            .mock("PUT", mockito::Matcher::Any)
+            .with_status(204)
+            .expect(6)
+            .create();
+
+        let mut config = lint_clean_config();
+        config.drives[1].drive_id = "rootfs".to_string();
+        assert!(matches!(
+            client.apply_config(&config).await,
+            Err(crate::FirecrackerError::InvalidConfig(_))
+        ));
+
+        // machine config, boot source, two drives and two interfaces
+        let report = client
+            .apply_config_with(
+                &config,
+                ApplyOptions {
+                    lint: false,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(report.findings.is_empty());
+
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_sends_sections_in_order() {
+        use crate::config::ConfigOperations;
+        use crate::validation::ValidationMode;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let mut config = lint_clean_config();
+        config.mmds_config = Some(crate::MmdsConfig {
+            network_interfaces: vec!["eth0".to_string()],
+            ..Default::default()
+        });
+
+        let mut mocks = Vec::new();
+        for path in [
+            "/machine-config",
+            "/boot-source",
+            "/drives/rootfs",
+            "/drives/data",
+            "/network-interfaces/eth0",
+            "/network-interfaces/eth1",
+            "/mmds/config",
+        ] {
+            mocks.push(server.mock("PUT", path).with_status(204).create());
+        }
+
+        client.apply_config(&config).await.unwrap();
+        for mock in mocks {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_rolls_back_after_a_failed_section() {
+        use crate::config::{ApplyOptions, ConfigOperations, RollbackOutcome};
+        use crate::validation::ValidationMode;
+        use serde_json::json;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let config = lint_clean_config();
+        let previous_machine = json!({"vcpu_count": 1, "mem_size_mib": 128, "smt": false});
+        let previous_rootfs = json!({
+            "drive_id": "rootfs",
+            "path_on_host": "/images/old-rootfs.ext4",
+            "is_root_device": true,
+            "is_read_only": false
+        });
+        let _get = server
+            .mock("GET", "/vm/config")
+            .with_status(200)
+            .with_body(
+                json!({
+                    "machine-config": previous_machine,
+                    "drives": [previous_rootfs],
+                })
+                .to_string(),
+            )
+            .create();
+
+        let machine = server
+            .mock("PUT", "/machine-config")
+            .match_body(mockito::Matcher::PartialJson(json!({"vcpu_count": 2})))
+            .with_status(204)
+            .expect(2)
+            .create();
+        let boot = server
+            .mock("PUT", "/boot-source")
+            .with_status(204)
+            .expect(2)
+            .create();
+        let rootfs = server
+            .mock("PUT", "/drives/rootfs")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"path_on_host": "/images/rootfs.ext4"}),
+            ))
+            .with_status(204)
+            .expect(2)
+            .create();
+        let data = server
+            .mock("PUT", "/drives/data")
+            .with_status(400)
+            .with_body(r#"{"fault_message": "bad drive"}"#)
+            .expect(2)
+            .create();
+        let interfaces = server
+            .mock(
+                "PUT",
+                mockito::Matcher::Regex("^/network-interfaces/".to_string()),
+            )
+            .expect(0)
+            .create();
+        // The rollback puts the old values back, newest first
+        let machine_back = server
+            .mock("PUT", "/machine-config")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"vcpu_count": 1, "mem_size_mib": 128}),
+            ))
+            .with_status(204)
+            .create();
+        let rootfs_back = server
+            .mock("PUT", "/drives/rootfs")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"path_on_host": "/images/old-rootfs.ext4"}),
+            ))
+            .with_status(204)
+            .create();
+
+        let options = ApplyOptions {
+            rollback: true,
+            ..Default::default()
+        };
+        let (section, source, rollback) = match client.apply_config_with(&config, options).await {
+            Err(crate::FirecrackerError::ConfigApply {
+                section,
+                source,
+                rollback,
+            }) => (section, source, rollback),
+            other => panic!("expected ConfigApply, got {:?}", other),
+        };
+        assert_eq!(section, "drives[1]");
+        assert!(matches!(
+            *source,
+            crate::FirecrackerError::Api {
+                status_code: 400,
+                ..
+            }
+        ));
+        let names: Vec<_> = rollback
+            .sections
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["machine_config", "boot_source", "drives[0]"]);
+        assert_eq!(rollback.sections[0].1, RollbackOutcome::RolledBack);
+        assert!(matches!(
+            &rollback.sections[1].1,
+            RollbackOutcome::Unrevertible(reason) if reason.contains("boot source")
+        ));
+        assert_eq!(rollback.sections[2].1, RollbackOutcome::RolledBack);
+        assert!(!rollback.is_clean());
+
+        // Without rollback the failure comes back as it is
+        match client.apply_config(&config).await {
+            Err(crate::FirecrackerError::Api {
+                status_code: 400, ..
+            }) => {}
+            other => panic!("expected the API error, got {:?}", other),
+        }
+
+        data.assert();
+        interfaces.assert();
+        machine_back.assert();
+        rootfs_back.assert();
+        for mock in [machine, boot, rootfs] {
+            mock.assert();
+        }
+    }
+
+    #[cfg(all(unix, not(feature = "no-fs-validation")))]
+    #[test]
+    fn test_path_policy_combinations() {
+        use crate::validation::{apply_path_policy, PathPolicy};
+
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        let target = base.join("rootfs.ext4");
+        std::fs::write(&target, b"rootfs").unwrap();
+        let link = base.join("current.ext4");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let linked_dir = base.join("images");
+        std::os::unix::fs::symlink(&base, &linked_dir).unwrap();
+
+        let target = target.to_str().unwrap();
+        let link = link.to_str().unwrap();
+        let through_dir = format!("{}/rootfs.ext4", linked_dir.display());
+        let dotted = format!("{}/./rootfs.ext4", base.display());
+        let policy = |canonicalize, allow_symlinks| PathPolicy {
+            canonicalize,
+            allow_symlinks,
+        };
+        let apply =
+            |path: &str, policy: PathPolicy| apply_path_policy("path_on_host", path, &policy);
+
+        // Default: paths are sent as given
+        assert_eq!(PathPolicy::default(), policy(false, true));
+        assert_eq!(apply(link, policy(false, true)).unwrap(), link);
+        assert_eq!(apply(&dotted, policy(false, true)).unwrap(), dotted);
+
+        // Canonicalize resolves symlinks and `.` components
+        assert_eq!(apply(link, policy(true, true)).unwrap(), target);
+        assert_eq!(apply(&through_dir, policy(true, true)).unwrap(), target);
+        assert_eq!(apply(&dotted, policy(true, true)).unwrap(), target);
+
+        // Files that don't exist yet resolve through their directory
+        let new_file = format!("{}/vm.snap", linked_dir.display());
+        assert_eq!(
+            apply(&new_file, policy(true, true)).unwrap(),
+            format!("{}/vm.snap", base.display())
+        );
+
+        // Symlinks anywhere along the path are refused, with or without
+        // canonicalization
+        for canonicalize in [false, true] {
+            for path in [link, through_dir.as_str()] {
+                match apply(path, policy(canonicalize, false)) {
+                    Err(crate::FirecrackerError::Validation(errors)) => {
+                        let errors = errors.field_errors();
+                        assert_eq!(errors["path_on_host"][0].params["check"], "no_symlinks");
+                    }
+                    other => panic!("expected a symlink error, got {:?}", other),
+                }
+            }
+            assert!(apply(target, policy(canonicalize, false)).is_ok());
+        }
+
+        // Unresolvable paths are filesystem errors
+        let missing = format!("{}/missing/vm.snap", base.display());
+        assert!(matches!(
+            apply(&missing, policy(true, true)),
+            Err(crate::FirecrackerError::FileSystem { .. })
+        ));
+    }
+
+    #[cfg(all(unix, not(feature = "no-fs-validation")))]
+    #[tokio::test]
+    async fn test_path_policy_applies_to_endpoints() {
+        use crate::boot::BootSourceOperations;
+        use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams};
+        use crate::validation::{PathPolicy, ValidationMode};
+        use crate::{BootSource, Drive, DriveOperations, SnapshotOperations};
+        use mockito::Matcher;
+        use serde_json::json;
+
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().canonicalize().unwrap().join("real");
+        std::fs::create_dir(&real).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        for name in ["vmlinux", "rootfs.ext4", "vm.snap", "vm.mem"] {
+            std::fs::write(real.join(name), b"data").unwrap();
+        }
+        let linked = |name: &str| link.join(name).to_str().unwrap().to_string();
+        let resolved = |name: &str| real.join(name).to_str().unwrap().to_string();
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_path_policy(PathPolicy {
+            canonicalize: true,
+            allow_symlinks: true,
+        });
+        let mut mocks = Vec::new();
+        for (path, body) in [
+            (
+                "/boot-source",
+                json!({ "kernel_image_path": resolved("vmlinux") }),
+            ),
+            (
+                "/drives/rootfs",
+                json!({ "path_on_host": resolved("rootfs.ext4") }),
+            ),
+            ("/logger", json!({ "log_path": resolved("fc.log") })),
+            (
+                "/metrics",
+                json!({ "metrics_path": resolved("fc.metrics") }),
+            ),
+            (
+                "/snapshot/create",
+                json!({ "snapshot_path": resolved("new.snap"), "mem_file_path": resolved("new.mem") }),
+            ),
+            (
+                "/snapshot/load",
+                json!({ "snapshot_path": resolved("vm.snap"), "mem_file_path": resolved("vm.mem") }),
+            ),
+        ] {
+            mocks.push(
+                server
+                    .mock("PUT", path)
+                    .match_body(Matcher::PartialJson(body))
+                    .with_status(204)
+                    .expect(1)
+                    .create(),
+            );
+        }
+
+        let boot = BootSource {
+            kernel_image_path: linked("vmlinux"),
+            ..Default::default()
+        };
+        client.put_boot_source(&boot).await.unwrap();
+        let drive = Drive {
+            drive_id: "rootfs".to_string(),
+            path_on_host: linked("rootfs.ext4"),
+            is_root_device: true,
+            ..Default::default()
+        };
+        client.put_drive("rootfs", &drive).await.unwrap();
+        let logger = Logger {
+            log_path: linked("fc.log"),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        client.put_logger(&logger).await.unwrap();
+        let metrics = Metrics {
+            metrics_path: linked("fc.metrics"),
+        };
+        client.put_metrics(&metrics).await.unwrap();
+        let create = SnapshotCreateParams {
+            snapshot_path: linked("new.snap"),
+            mem_file_path: linked("new.mem"),
+            ..Default::default()
+        };
+        client.create_snapshot(&create).await.unwrap();
+        let load = SnapshotLoadParams {
+            snapshot_path: linked("vm.snap"),
+            mem_file_path: Some(linked("vm.mem")),
+            ..Default::default()
+        };
+        client.load_snapshot(&load).await.unwrap();
+
+        for mock in mocks {
+            mock.assert();
+        }
+
+        // Refusing symlinks stops every one of them before sending
+        let client = client.with_path_policy(PathPolicy {
+            canonicalize: false,
+            allow_symlinks: false,
+        });
+        assert!(client.put_boot_source(&boot).await.is_err());
+        assert!(client.put_drive("rootfs", &drive).await.is_err());
+        assert!(client.put_logger(&logger).await.is_err());
+        assert!(client.put_metrics(&metrics).await.is_err());
+        assert!(client.create_snapshot(&create).await.is_err());
+        assert!(client.load_snapshot(&load).await.is_err());
+
+        // The policy needs the local filesystem, so it is off under Syntactic
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let _any = server
+            .mock("PUT", "/drives/rootfs")
+            .match_body(Matcher::PartialJson(
+                json!({ "path_on_host": linked("rootfs.ext4") }),
+            ))
+            .with_status(204)
+            .create();
+        client.put_drive("rootfs", &drive).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_custom_validators_run_per_model() {
+        use crate::drive::DriveUpdate;
+        use crate::validation::{ValidationMode, ValidationTarget};
+        use crate::{Drive, DriveOperations};
+        use validator::ValidationError;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .add_validator(|target| match target {
+                ValidationTarget::Drive(drive)
+                    if !drive.path_on_host.starts_with("/srv/images/") =>
+                {
+                    vec![ValidationError::new("drive_outside_image_dir")]
+                }
+                _ => Vec::new(),
+            })
+            .add_validator(|target| match target {
+                ValidationTarget::Mmds(data) if data.get("instance-id").is_none() => {
+                    vec![ValidationError::new("missing_instance_id")]
+                }
+                _ => Vec::new(),
+            });
+
+        let rejected = server.mock("PUT", "/drives/rootfs").expect(0).create();
+        let drive = Drive {
+            drive_id: "rootfs".to_string(),
+            path_on_host: "/tmp/rootfs.ext4".to_string(),
+            is_root_device: true,
+            ..Default::default()
+        };
+        match client.put_drive("rootfs", &drive).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                let errors = errors.field_errors();
+                assert_eq!(errors["__all__"][0].code, "drive_outside_image_dir");
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        rejected.assert();
+
+        let accepted = server
+            .mock("PUT", "/drives/rootfs")
+            .with_status(204)
+            .expect(1)
+            .create();
+        let drive = Drive {
+            path_on_host: "/srv/images/rootfs.ext4".to_string(),
+            ..drive
+        };
+        client.put_drive("rootfs", &drive).await.unwrap();
+        accepted.assert();
+
+        // The drive rule does not apply to other models, or to PATCHes
+        let logger_mock = server.mock("PUT", "/logger").with_status(204).create();
+        let logger = Logger {
+            log_path: "/tmp/fc.log".to_string(),
+            level: None,
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+        };
+        client.put_logger(&logger).await.unwrap();
+        logger_mock.assert();
+
+        let patch_mock = server
+            .mock("PATCH", "/drives/rootfs")
+            .with_status(204)
+            .create();
+        let update = DriveUpdate {
+            drive_id: "rootfs".to_string(),
+            ..Default::default()
+        };
+        client.patch_drive("rootfs", &update).await.unwrap();
+        patch_mock.assert();
+
+        let mmds_mock = server.mock("PUT", "/mmds").expect(0).create();
+        let result = client
+            .put_mmds(serde_json::json!({ "hostname": "vm1" }))
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+        mmds_mock.assert();
+
+        // Like the built-in checks, custom validators are skipped when validation is off
+        let client = client.with_validation_mode(ValidationMode::Off);
+        let drive = Drive {
+            path_on_host: "/tmp/rootfs.ext4".to_string(),
+            ..drive
+        };
+        let off_mock = server
+            .mock("PUT", "/drives/rootfs")
+            .with_status(204)
+            .create();
+        client.put_drive("rootfs", &drive).await.unwrap();
+        off_mock.assert();
+    }
+
+    // Errors nested under `section`, or under item `index` of a list section
+    fn section_errors<'a>(
+        errors: &'a validator::ValidationErrors,
+        section: &str,
+        index: Option<usize>,
+    ) -> &'a validator::ValidationErrors {
+        use validator::ValidationErrorsKind;
+
+        match (&errors.errors()[section], index) {
+            (ValidationErrorsKind::Struct(errors), None) => errors,
+            (ValidationErrorsKind::List(items), Some(index)) => &items[&index],
+            (kind, _) => panic!("unexpected errors for {}: {:?}", section, kind),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_reports_every_failure() {
+        use crate::config::ConfigOperations;
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let mock = server.mock("PUT", mockito::Matcher::Any).expect(0).create();
+
+        let mut config = lint_clean_config();
+        config.machine_config.as_mut().unwrap().vcpu_count = Some(0);
+        config.drives[1].path_on_host = "images/data.ext4".to_string();
+        config.network_interfaces[0].guest_mac = Some("not-a-mac".to_string());
+
+        let errors = match client.apply_config(&config).await {
+            Err(crate::FirecrackerError::Validation(errors)) => errors,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        assert_eq!(errors.errors().len(), 3);
+        let machine = section_errors(&errors, "machine_config", None).field_errors();
+        assert_eq!(machine["vcpu_count"][0].code, "range");
+        let drive = section_errors(&errors, "drives", Some(1)).field_errors();
+        assert_eq!(drive["path_on_host"][0].code, "invalid_path");
+        let interface = section_errors(&errors, "network_interfaces", Some(0)).field_errors();
+        assert!(interface.contains_key("guest_mac"));
+
+        // Lint errors come along with the field errors
+        config.drives[0].drive_id = "data".to_string();
+        let errors = match client.validate_config(&config) {
+            Err(crate::FirecrackerError::Validation(errors)) => errors,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        let lint = errors.field_errors();
+        assert_eq!(lint["__all__"][0].code, "duplicate_drive_id");
+        assert_eq!(lint["__all__"][0].params["path"], "drives[1].drive_id");
+
+        // Single calls still stop at the first failing model
+        match client.put_drive("data", &config.drives[1]).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                assert_eq!(errors.errors().len(), 1);
+                assert!(errors.field_errors().contains_key("path_on_host"));
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_apply_config_file_reports_every_failure() {
+        use crate::config::ConfigOperations;
+        use crate::validation::ValidationMode;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vm.json");
+        let mut config = lint_clean_config();
+        config.boot_source.as_mut().unwrap().kernel_image_path = "vmlinux".to_string();
+        config.machine_config.as_mut().unwrap().mem_size_mib = Some(0);
+        config.drives[0].drive_id = String::new();
+        crate::config::write_config_file(&config, &path).unwrap();
+
+        let errors = match client.apply_config_file(&path).await {
+            Err(crate::FirecrackerError::Validation(errors)) => errors,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        assert!(section_errors(&errors, "boot_source", None)
+            .field_errors()
+            .contains_key("kernel_image_path"));
+        assert!(section_errors(&errors, "machine_config", None)
+            .field_errors()
+            .contains_key("mem_size_mib"));
+        assert!(section_errors(&errors, "drives", Some(0))
+            .field_errors()
+            .contains_key("drive_id"));
+
+        let mock = server
+            .mock("PUT", mockito::Matcher::Any)
+            .with_status(204)
+            .expect(6)
+            .create();
+        crate::config::write_config_file(&lint_clean_config(), &path).unwrap();
+        client.apply_config_file(&path).await.unwrap();
+        mock.assert();
+
+        assert!(matches!(
+            client
+                .apply_config_file(&dir.path().join("missing.json"))
+                .await,
+            Err(crate::FirecrackerError::FileSystem { .. })
+        ));
+    }
+
+    #[test]
+    fn test_model_builders() {
+        use crate::snapshot::{SnapshotCreateParams, SnapshotType};
+        use crate::{Balloon, BootSource, CpuTemplate, Drive, MachineConfig, MmdsConfig};
+        use crate::{NetworkInterface, RateLimiter};
+
+        let machine = MachineConfig::builder(2, 1024)
+            .smt(false)
+            .track_dirty_pages(true)
+            .cpu_template(CpuTemplate::T2)
+            .build()
+            .unwrap();
+        assert_eq!(machine.vcpu_count, Some(2));
+        assert_eq!(machine.mem_size_mib, Some(1024));
+        assert_eq!(machine.smt, Some(false));
+        assert_eq!(machine.track_dirty_pages, Some(true));
+
+        let boot = BootSource::builder("/images/vmlinux")
+            .boot_args("console=ttyS0")
+            .initrd_path("/images/initrd")
+            .build()
+            .unwrap();
+        assert_eq!(boot.kernel_image_path, "/images/vmlinux");
+        assert_eq!(boot.boot_args.as_deref(), Some("console=ttyS0"));
+        assert_eq!(boot.initrd_path.as_deref(), Some("/images/initrd"));
+
+        let balloon = Balloon::builder(256)
+            .deflate_on_oom(true)
+            .stats_polling_interval_s(5)
+            .build()
+            .unwrap();
+        assert_eq!(balloon.amount_mib, 256);
+        assert!(balloon.deflate_on_oom);
+        assert_eq!(balloon.stats_polling_interval_s, Some(5));
+
+        let logger = Logger::builder("/var/log/fc.log")
+            .level(LogLevel::Debug)
+            .show_level(true)
+            .module("api_server")
+            .build()
+            .unwrap();
+        assert_eq!(logger.level, Some(LogLevel::Debug));
+        assert_eq!(logger.module.as_deref(), Some("api_server"));
+
+        let metrics = Metrics::builder("/var/log/fc.metrics").build().unwrap();
+        assert_eq!(metrics.metrics_path, "/var/log/fc.metrics");
+
+        let vsock = Vsock::builder(3, "/run/fc/v.sock")
+            .vsock_id("vsock0")
+            .build()
+            .unwrap();
+        assert_eq!(vsock.guest_cid, 3);
+        assert_eq!(vsock.vsock_id.as_deref(), Some("vsock0"));
+
+        let snapshot = SnapshotCreateParams::builder("/snap/vm.snap", "/snap/vm.mem")
+            .snapshot_type(SnapshotType::Diff)
+            .allow_overwrite(true)
+            .build()
+            .unwrap();
+        assert_eq!(snapshot.snapshot_type, Some(SnapshotType::Diff));
+        assert!(snapshot.allow_overwrite);
+
+        let mmds = MmdsConfig::builder(["eth0"])
+            .version("V2")
+            .ipv4_address("169.254.169.254")
+            .build()
+            .unwrap();
+        assert_eq!(mmds.network_interfaces, vec!["eth0".to_string()]);
+
+        let drive = Drive::builder("rootfs")
+            .path_on_host("/images/rootfs.ext4")
+            .root_device(true)
+            .read_only(true)
+            .rate_limiter(RateLimiter::default())
+            .build()
+            .unwrap();
+        assert!(drive.is_root_device && drive.is_read_only);
+        let vhost_user = Drive::builder("data")
+            .socket("/run/fc/vhost.sock")
+            .build()
+            .unwrap();
+        assert!(vhost_user.path_on_host.is_empty());
+
+        let interface = NetworkInterface::builder("eth0", "tap0")
+            .guest_mac("AA:FC:00:00:00:01")
+            .build()
+            .unwrap();
+        assert_eq!(interface.host_dev_name, "tap0");
+    }
+
+    #[test]
+    fn test_model_builders_validate() {
+        use crate::snapshot::SnapshotCreateParams;
+        use crate::{BootSource, Drive, MachineConfig, MmdsConfig, NetworkInterface};
+
+        fn invalid_fields<T: std::fmt::Debug>(
+            result: Result<T, crate::FirecrackerError>,
+        ) -> Vec<&'static str> {
+            match result {
+                Err(crate::FirecrackerError::Validation(errors)) => {
+                    let mut fields: Vec<_> = errors.errors().keys().copied().collect();
+                    fields.sort();
+                    fields
+                }
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        }
+
+        // Required fields can't be left empty
+        assert_eq!(
+            invalid_fields(Drive::builder("rootfs").build()),
+            vec!["__all__"]
+        );
+        assert_eq!(
+            invalid_fields(
+                Drive::builder("")
+                    .path_on_host("/images/rootfs.ext4")
+                    .build()
+            ),
+            vec!["drive_id"]
+        );
+        assert_eq!(
+            invalid_fields(BootSource::builder("").build()),
+            vec!["kernel_image_path"]
+        );
+        assert_eq!(
+            invalid_fields(MachineConfig::builder(0, 1024).build()),
+            vec!["vcpu_count"]
+        );
+        assert_eq!(
+            invalid_fields(MachineConfig::builder(2, 0).build()),
+            vec!["mem_size_mib"]
+        );
+        assert_eq!(
+            invalid_fields(Logger::builder("").build()),
+            vec!["log_path"]
+        );
+        assert_eq!(
+            invalid_fields(Metrics::builder("").build()),
+            vec!["metrics_path"]
+        );
+        assert_eq!(
+            invalid_fields(NetworkInterface::builder("", "tap0").build()),
+            vec!["iface_id"]
+        );
+        assert_eq!(
+            invalid_fields(SnapshotCreateParams::builder("", "/snap/vm.mem").build()),
+            vec!["snapshot_path"]
+        );
+
+        // One rule per builder beyond the required fields
+        assert_eq!(
+            invalid_fields(
+                BootSource::builder("/images/vmlinux")
+                    .initrd_path("initrd")
+                    .build()
+            ),
+            vec!["initrd_path"]
+        );
+        assert_eq!(
+            invalid_fields(
+                Drive::builder("rootfs")
+                    .path_on_host("/images/rootfs.ext4")
+                    .partuuid("not-a-uuid")
+                    .build()
+            ),
+            vec!["partuuid"]
+        );
+        assert_eq!(
+            invalid_fields(MachineConfig::builder(64, 1024).build()),
+            vec!["vcpu_count"]
+        );
+        assert_eq!(
+            invalid_fields(
+                Logger::builder("/var/log/fc.log")
+                    .module("not a module")
+                    .build()
+            ),
+            vec!["module"]
+        );
+        assert_eq!(
+            invalid_fields(Metrics::builder("metrics").build()),
+            vec!["metrics_path"]
+        );
+        assert_eq!(
+            invalid_fields(Vsock::builder(2, "/run/fc/v.sock").build()),
+            vec!["guest_cid"]
+        );
+        assert_eq!(
+            invalid_fields(SnapshotCreateParams::builder("/snap/vm", "/snap/vm").build()),
+            vec!["__all__"]
+        );
+        assert_eq!(
+            invalid_fields(MmdsConfig::builder(["eth0"]).version("V3").build()),
+            vec!["version"]
+        );
+        assert_eq!(
+            invalid_fields(
+                MmdsConfig::builder(["eth0"])
+                    .ipv4_address("10.0.0.1")
+                    .build()
+            ),
+            vec!["ipv4_address"]
+        );
+        assert_eq!(
+            invalid_fields(
+                NetworkInterface::builder("eth0", "tap0")
+                    .guest_mac("not-a-mac")
+                    .build()
+            ),
+            vec!["guest_mac"]
+        );
+        // Firecracker checks the size against guest memory; locally it only
+        // has to fit its page count
+        let max = crate::validation::MAX_BALLOON_MIB;
+        assert!(crate::Balloon::builder(max).build().is_ok());
+        assert_eq!(
+            invalid_fields(crate::Balloon::builder(max + 1).build()),
+            vec!["amount_mib"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_serde_config_import() {
+        use crate::machine::MachineConfigOperations;
+        use crate::FullVmConfiguration;
+
+        let config = r#"{"machine_config": {"vcpu_count": 2, "mem_size_mb": 1024}}"#;
+        let parsed = serde_json::from_str::<FullVmConfiguration>(config);
+        #[cfg(feature = "strict-serde")]
+        assert!(parsed.unwrap_err().to_string().contains("mem_size_mb"));
+        #[cfg(not(feature = "strict-serde"))]
+        assert_eq!(parsed.unwrap().machine_config.unwrap().mem_size_mib, None);
+
+        // Responses stay lenient in both modes
+        let (mut server, client) = create_test_client().await;
+        let mock = server
+            .mock("GET", "/machine-config")
+            .with_status(200)
+            .with_body(r#"{"vcpu_count": 2, "mem_size_mib": 1024, "new_field": true}"#)
+            .create();
+        let machine = client.get_machine_config().await.unwrap();
+        assert_eq!(machine.mem_size_mib, Some(1024));
+        mock.assert();
+
+        let stats: crate::BalloonStats = serde_json::from_str(
+            r#"{"target_pages": 1, "actual_pages": 1, "target_mib": 4, "actual_mib": 4, "new_stat": 7}"#,
+        )
+        .unwrap();
+        assert_eq!(stats.target_mib, 4);
+    }
+
+    #[tokio::test]
+    async fn test_patchable_fields() {
+        use crate::drive::DriveUpdate;
+        use crate::network::NetworkInterfaceUpdate;
+        use crate::{DriveOperations, NetworkInterfaceOperations, Patchable, RateLimiter};
+        use serde_json::json;
+
+        let limiter = RateLimiter::bandwidth(1024);
+        let mut update = DriveUpdate {
+            drive_id: "rootfs".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({ "drive_id": "rootfs" })
+        );
+        update.rate_limiter = Patchable::Null;
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({ "drive_id": "rootfs", "rate_limiter": null })
+        );
+        update.rate_limiter = limiter.clone().into();
+        assert_eq!(
+            serde_json::to_value(&update).unwrap()["rate_limiter"],
+            serde_json::to_value(&limiter).unwrap()
+        );
+
+        // Deserializing tells a missing field from an explicit null
+        let parse = |body| serde_json::from_value::<NetworkInterfaceUpdate>(body).unwrap();
+        let iface = parse(json!({ "iface_id": "eth0", "rx_rate_limiter": null }));
+        assert_eq!(iface.rx_rate_limiter, Patchable::Null);
+        assert_eq!(iface.tx_rate_limiter, Patchable::Absent);
+        let iface = parse(
+            serde_json::to_value(NetworkInterfaceUpdate {
+                iface_id: "eth0".to_string(),
+                tx_rate_limiter: limiter.clone().into(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        assert_eq!(iface.tx_rate_limiter, Patchable::Value(limiter));
+
+        // The null reaches Firecracker so it removes the limiter
+        let (mut server, client) = create_test_client().await;
+        let drive_mock = server
+            .mock("PATCH", "/drives/rootfs")
+            .match_body(mockito::Matcher::Json(
+                json!({ "drive_id": "rootfs", "rate_limiter": null }),
+            ))
+            .with_status(204)
+            .create();
+        let update = DriveUpdate {
+            drive_id: "rootfs".to_string(),
+            rate_limiter: Patchable::Null,
+            ..Default::default()
+        };
+        client.patch_drive("rootfs", &update).await.unwrap();
+        drive_mock.assert();
+
+        let iface_mock = server
+            .mock("PATCH", "/network-interfaces/eth0")
+            .match_body(mockito::Matcher::Json(
+                json!({ "iface_id": "eth0", "tx_rate_limiter": null }),
+            ))
+            .with_status(204)
+            .create();
+        let update = NetworkInterfaceUpdate {
+            iface_id: "eth0".to_string(),
+            tx_rate_limiter: Patchable::Null,
+            ..Default::default()
+        };
+        client
+            .patch_network_interface("eth0", &update)
+            .await
+            .unwrap();
+        iface_mock.assert();
+    }
+
+    #[test]
+    fn test_drive_wire_round_trip() {
+        use crate::Drive;
+        use serde_json::{json, Value};
+
+        // Drives as GET /vm/config reports them, with every unset field null
+        let captured = json!([
+            {
+                "drive_id": "rootfs",
+                "partuuid": null,
+                "is_root_device": true,
+                "cache_type": "Unsafe",
+                "is_read_only": false,
+                "path_on_host": "/srv/images/rootfs.ext4",
+                "rate_limiter": null,
+                "io_engine": "Sync",
+                "socket": null
+            },
+            {
+                "drive_id": "data",
+                "partuuid": null,
+                "is_root_device": false,
+                "cache_type": "Writeback",
+                "is_read_only": null,
+                "path_on_host": null,
+                "rate_limiter": null,
+                "io_engine": null,
+                "socket": "/run/vhost/data.sock"
+            }
+        ]);
+        let drives: Vec<Drive> = serde_json::from_value(captured.clone()).unwrap();
+        assert_eq!(drives[0].path_on_host, "/srv/images/rootfs.ext4");
+        assert_eq!(drives[0].socket, None);
+        assert_eq!(drives[1].socket.as_deref(), Some("/run/vhost/data.sock"));
+        assert!(drives[1].path_on_host.is_empty());
+
+        // Serializing gives back what Firecracker emitted, minus the nulls
+        let without_nulls = |value: Value| -> Value {
+            let drives = value.as_array().unwrap().iter().map(|drive| {
+                let fields = drive.as_object().unwrap().clone();
+                Value::Object(fields.into_iter().filter(|(_, v)| !v.is_null()).collect())
+            });
+            Value::Array(drives.collect())
+        };
+        let serialized = serde_json::to_value(&drives).unwrap();
+        assert_eq!(serialized, without_nulls(captured));
+        assert_eq!(
+            serde_json::from_value::<Vec<Drive>>(serialized).unwrap(),
+            drives
+        );
+
+        // A payload mixing both backends matches neither
+        let mixed = json!({
+            "drive_id": "data",
+            "is_root_device": false,
+            "is_read_only": false,
+            "path_on_host": "/srv/images/data.ext4",
+            "socket": "/run/vhost/data.sock"
+        });
+        assert!(serde_json::from_value::<Drive>(mixed).is_err());
+        let conflicting = Drive {
+            path_on_host: "/srv/images/data.ext4".to_string(),
+            ..drives[1].clone()
+        };
+        let errors = conflicting.validate().unwrap_err();
+        assert_eq!(
+            errors.field_errors()["__all__"][0].code,
+            "conflicting_drive_backend"
+        );
+    }
+
+    #[test]
+    fn test_token_bucket_constructors() {
+        use crate::models::{RateLimiter, TokenBucket};
+
+        let bucket = TokenBucket::new(1, 1).unwrap();
+        assert!(bucket.validate().is_ok());
+        assert_eq!(bucket.tokens_per_second(), Some(1000));
+        for (size, refill_time) in [(0, 1000), (1000, 0), (0, 0)] {
+            let error = TokenBucket::new(size, refill_time).unwrap_err();
+            assert_eq!(error.code, "range");
+        }
+
+        let bucket = TokenBucket::new(u64::MAX, 1000)
+            .unwrap()
+            .with_burst(u64::MAX);
+        assert_eq!(bucket.one_time_burst, Some(u64::MAX));
+        assert_eq!(bucket.tokens_per_second(), Some(u64::MAX));
+        // The intermediate size * 1000 overflows u64 but the rate does not
+        assert_eq!(
+            TokenBucket::new(u64::MAX, u64::MAX)
+                .unwrap()
+                .tokens_per_second(),
+            Some(1000)
+        );
+        assert_eq!(
+            TokenBucket::per_second(4096).tokens_per_second(),
+            Some(4096)
+        );
+        assert_eq!(TokenBucket::default().tokens_per_second(), None);
+
+        // Integer JSON still reads, and negatives are refused at parse time
+        let bucket: TokenBucket = serde_json::from_value(serde_json::json!({
+            "size": 4096, "refill_time": 1000, "one_time_burst": 8192
+        }))
+        .unwrap();
+        assert_eq!(bucket, TokenBucket::per_second(4096).with_burst(8192));
+        assert!(serde_json::from_value::<TokenBucket>(serde_json::json!({
+            "size": -1, "refill_time": 1000
+        }))
+        .is_err());
+
+        let limiter = RateLimiter::bandwidth(u64::MAX).with_ops(1);
+        assert!(limiter.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_file_round_trip() {
+        use crate::config::{read_config_file, write_config_file, ConfigFile};
+        use serde_json::Value;
+
+        fn without_nulls(value: Value) -> Value {
+            match value {
+                Value::Object(map) => Value::Object(
+                    map.into_iter()
+                        .filter(|(_, v)| !v.is_null())
+                        .map(|(k, v)| (k, without_nulls(v)))
+                        .collect(),
+                ),
+                Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+                other => other,
+            }
+        }
+
+        let golden = crate::testing::fixtures::VM_CONFIG_V1_7;
+        let file: ConfigFile = serde_json::from_str(golden).unwrap();
+        let config = crate::FullVmConfiguration::from(file.clone());
+        assert_eq!(config.machine_config.as_ref().unwrap().vcpu_count, Some(2));
+        assert_eq!(config.drives[0].path_on_host, "bionic.rootfs.ext4");
+        assert_eq!(config.network_interfaces[0].host_dev_name, "tap0");
+        assert_eq!(
+            config.mmds_config.as_ref().unwrap().network_interfaces,
+            vec!["eth0".to_string()]
+        );
+
+        // Writing it back gives the golden file minus its nulls
+        assert_eq!(
+            serde_json::to_value(&file).unwrap(),
+            without_nulls(serde_json::from_str(golden).unwrap())
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vm_config.json");
+        write_config_file(&config, &path).unwrap();
+        assert_eq!(read_config_file(&path).unwrap(), config);
+
+        // The API form keeps snake_case section names
+        let api = serde_json::to_value(&config).unwrap();
+        assert!(api.get("boot_source").is_some() && api.get("boot-source").is_none());
+        assert!(api.get("machine_config").is_some());
+        // and reads back as a config file
+        let file: ConfigFile = serde_json::from_value(api).unwrap();
+        assert_eq!(crate::FullVmConfiguration::from(file), config);
+
+        // Older spellings of the logger and metrics paths are still read
+        let file: ConfigFile = serde_json::from_value(serde_json::json!({
+            "logger": { "log_fifo": "/tmp/fc.log" },
+            "metrics": { "metrics_fifo": "/tmp/fc.metrics" }
+        }))
+        .unwrap();
+        assert_eq!(file.logger.unwrap().log_path, "/tmp/fc.log");
+        assert_eq!(file.metrics.unwrap().metrics_path, "/tmp/fc.metrics");
+    }
+
+    #[test]
+    fn test_model_constructors() {
+        use crate::drive::DriveUpdate;
+        use crate::snapshot::{NetworkOverride, SnapshotCreateParams, SnapshotLoadParams};
+        use crate::{Balloon, BootSource, Drive, Logger, MachineConfig, MmdsConfig};
+        use crate::{NetworkInterface, Vsock};
+
+        // `new` gives the same model as a builder with no optional fields set
+        assert_eq!(Balloon::new(256), Balloon::builder(256).build().unwrap());
+        assert_eq!(
+            BootSource::new("/vmlinux"),
+            BootSource::builder("/vmlinux").build().unwrap()
+        );
+        assert_eq!(
+            Drive::new("rootfs", "/rootfs.ext4"),
+            Drive::builder("rootfs")
+                .path_on_host("/rootfs.ext4")
+                .build()
+                .unwrap()
+        );
+        assert_eq!(
+            Drive::vhost_user("data", "/run/vhost.sock"),
+            Drive::builder("data")
+                .socket("/run/vhost.sock")
+                .build()
+                .unwrap()
+        );
+        assert_eq!(
+            Logger::new("/tmp/fc.log"),
+            Logger::builder("/tmp/fc.log").build().unwrap()
+        );
+        assert_eq!(
+            MachineConfig::new(2, 1024),
+            MachineConfig::builder(2, 1024).build().unwrap()
+        );
+        assert_eq!(
+            MmdsConfig::new(["eth0"]),
+            MmdsConfig::builder(["eth0"]).build().unwrap()
+        );
+        assert_eq!(
+            NetworkInterface::new("eth0", "tap0"),
+            NetworkInterface::builder("eth0", "tap0").build().unwrap()
+        );
+        assert_eq!(
+            Vsock::new(3, "/tmp/v.sock"),
+            Vsock::builder(3, "/tmp/v.sock").build().unwrap()
+        );
+        assert_eq!(
+            SnapshotCreateParams::new("/tmp/vm.snap", "/tmp/vm.mem"),
+            SnapshotCreateParams::builder("/tmp/vm.snap", "/tmp/vm.mem")
+                .build()
+                .unwrap()
+        );
+
+        let load = SnapshotLoadParams::with_mem_file("/tmp/vm.snap", "/tmp/vm.mem");
+        assert!(load.validate().is_ok());
+        assert!(NetworkOverride::new("eth0", "tap1").validate().is_ok());
+        assert_eq!(DriveUpdate::new("rootfs").drive_id, "rootfs");
+    }
+
+    #[cfg(feature = "openapi")]
+    #[test]
+    fn test_models_match_bundled_openapi_spec() {
+        use crate::openapi::{check_drift, Allowlist, BUNDLED_ALLOWLIST, BUNDLED_SPEC};
+
+        let spec: Value = serde_json::from_str(BUNDLED_SPEC).unwrap();
+        let allowlist: Allowlist = BUNDLED_ALLOWLIST.parse().unwrap();
+        let report = check_drift(&spec, &allowlist).unwrap();
+        assert_eq!(report.spec_version, "1.7.0");
+        assert_eq!(report.breaking().count(), 0, "{}", report);
+        assert!(report.stale.is_empty(), "{}", report);
+        // Known drift, kept visible until the models change
+        let unexpected: Vec<String> = report.unexpected().map(|drift| drift.path()).collect();
+        assert_eq!(unexpected, ["MemoryBackend.backend_type"], "{}", report);
+    }
+
+    #[cfg(feature = "openapi")]
+    #[test]
+    fn test_openapi_drift_report() {
+        use crate::openapi::{check_drift, Allowlist, DriftKind, BUNDLED_SPEC};
+        use serde_json::json;
+
+        let mut spec: Value = serde_json::from_str(BUNDLED_SPEC).unwrap();
+        let definitions = &mut spec["definitions"];
+        definitions["Vsock"]["properties"]["port"] = json!({"type": "integer"});
+        definitions["Vsock"]["required"] = json!(["guest_cid", "uds_path", "port"]);
+        definitions["BootSource"]["required"] = json!(["kernel_image_path", "boot_args"]);
+        definitions["CpuTemplate"]["enum"] =
+            json!(["C3", "T2", "T2S", "T2CL", "T2A", "T3", "None"]);
+        definitions["Logger"]["properties"]["level"]["enum"] = json!(["Error", "Info", "Off"]);
+        definitions.as_object_mut().unwrap().remove("Metrics");
+
+        let allowlist: Allowlist = "\
+            # Differences this test allows\n\
+            BootSource.boot_args optionality Always set by this test\n\
+            Drive.socket extra-field No longer a difference\n"
+            .parse()
+            .unwrap();
+        let report = check_drift(&spec, &allowlist).unwrap();
+        let find = |path: &str| {
+            report
+                .drifts
+                .iter()
+                .find(|drift| drift.path() == path)
+                .unwrap_or_else(|| panic!("no drift at {path}: {report}"))
+        };
+
+        let port = find("Vsock.port");
+        assert_eq!(port.kind, DriftKind::MissingField { required: true });
+        assert!(port.is_breaking());
+        assert_eq!(
+            find("CpuTemplate").kind,
+            DriftKind::Enum {
+                missing: vec!["T3".to_string()],
+                extra: vec!["V1N1".to_string()],
+            }
+        );
+        assert_eq!(
+            find("Logger.level").kind,
+            DriftKind::Enum {
+                missing: vec![],
+                extra: vec![
+                    "Debug".to_string(),
+                    "Trace".to_string(),
+                    "Warning".to_string()
+                ],
+            }
+        );
+        assert_eq!(find("Metrics").kind, DriftKind::UnknownDefinition);
+        let boot_args = find("BootSource.boot_args");
+        assert_eq!(
+            boot_args.kind,
+            DriftKind::Optionality {
+                spec_required: true
+            }
+        );
+        assert_eq!(
+            boot_args.allowed.as_deref(),
+            Some("Always set by this test")
+        );
+
+        let breaking: Vec<String> = report.breaking().map(|drift| drift.path()).collect();
+        assert_eq!(breaking, ["Metrics", "Vsock.port"]);
+        assert_eq!(report.stale.len(), 1);
+        assert_eq!(report.stale[0].path, "Drive.socket");
+        assert!(!report.is_clean());
+
+        let text = report.to_string();
+        assert!(text.contains("- Vsock.port: required in the spec, missing from the model\n"));
+        assert!(text.contains(
+            "~ CpuTemplate: enum values missing from the model: T3; not in the spec: V1N1\n"
+        ));
+        assert!(text.contains(
+            "~ BootSource.boot_args: required in the spec, optional in the model (allowed: Always set by this test)\n"
+        ));
+        assert!(text.contains("! Metrics: not defined in the spec\n"));
+        assert!(text.contains("? Drive.socket extra-field: stale allowlist entry\n"));
+
+        assert!(matches!(
+            "Drive.socket removed-field whatever".parse::<Allowlist>(),
+            Err(crate::FirecrackerError::Config(_))
+        ));
+        assert!(matches!(
+            "Drive.socket".parse::<Allowlist>(),
+            Err(crate::FirecrackerError::Config(_))
+        ));
+        assert!(matches!(
+            check_drift(&json!({"swagger": "2.0"}), &Allowlist::default()),
+            Err(crate::FirecrackerError::Config(_))
+        ));
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_full_config_schema() {
+        use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams};
+
+        let schema = serde_json::to_value(crate::schema::full_config_schema()).unwrap();
+        let required = |schema: &serde_json::Value, name: &str| -> Vec<String> {
+            let object = if name.is_empty() {
+                schema
+            } else {
+                &schema["definitions"][name]
+            };
+            let mut fields: Vec<String> = object["required"]
+                .as_array()
+                .map(|fields| {
+                    fields
+                        .iter()
+                        .map(|f| f.as_str().unwrap().to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            fields.sort();
+            fields
+        };
+
+        // Required fields follow the serde attributes: anything with a default
+        // or skipped when `None` is optional.
+        assert_eq!(required(&schema, "BootSource"), ["kernel_image_path"]);
+        assert_eq!(required(&schema, "Balloon"), ["amount_mib"]);
+        assert!(required(&schema, "MachineConfig").is_empty());
+        assert_eq!(
+            required(&schema, "NetworkInterface"),
+            ["host_dev_name", "iface_id"]
+        );
+        assert_eq!(required(&schema, "MmdsConfig"), ["network_interfaces"]);
+        assert_eq!(required(&schema, "TokenBucket"), ["refill_time", "size"]);
+        assert_eq!(
+            required(&schema, "FileDrive"),
+            ["drive_id", "is_read_only", "is_root_device", "path_on_host"]
+        );
+        assert_eq!(
+            required(&schema, "VhostUserDrive"),
+            ["drive_id", "is_root_device", "socket"]
+        );
+        assert_eq!(
+            schema["definitions"]["Drive"]["anyOf"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let create = serde_json::to_value(schemars::schema_for!(SnapshotCreateParams)).unwrap();
+        assert_eq!(required(&create, ""), ["mem_file_path", "snapshot_path"]);
+        let load = serde_json::to_value(schemars::schema_for!(SnapshotLoadParams)).unwrap();
+        assert_eq!(required(&load, ""), ["snapshot_path"]);
+
+        #[cfg(feature = "strict-serde")]
+        assert_eq!(
+            schema["definitions"]["MachineConfig"]["additionalProperties"],
+            false
+        );
+        // The snapshot is generated without `strict-serde`, which adds
+        // `additionalProperties: false` to every section.
+        #[cfg(not(feature = "strict-serde"))]
+        assert_eq!(
+            crate::schema::full_config_schema_json() + "\n",
+            include_str!("fixtures/full_config_schema.json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_balloon_config_round_trips() {
+        use crate::balloon::BalloonOperations;
+        use crate::testing::fixtures::{BALLOON_V1_0, BALLOON_V1_7};
+        use crate::testing::mock::{expect_put, json_body};
+
+        let (mut server, client) = create_test_client().await;
+        for (fixture, expected) in [
+            (
+                BALLOON_V1_0,
+                serde_json::json!({
+                    "amount_mib": 0,
+                    "deflate_on_oom": false,
+                    "stats_polling_interval_s": 0
+                }),
+            ),
+            (
+                BALLOON_V1_7,
+                serde_json::json!({
+                    "amount_mib": 128,
+                    "deflate_on_oom": true,
+                    "stats_polling_interval_s": 5
+                }),
+            ),
+        ] {
+            let get = server
+                .mock("GET", "/balloon")
+                .with_status(200)
+                .with_body(fixture)
+                .create();
+            let put = expect_put(&mut server, "/balloon", json_body(&expected));
+
+            let balloon = client.get_balloon_config().await.unwrap();
+            client.put_balloon_config(&balloon).await.unwrap();
+            get.assert();
+            put.assert();
+            get.remove();
+            put.remove();
+        }
+
+        // Nothing unset is sent as null
+        let body = serde_json::to_value(crate::Balloon::new(64)).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "amount_mib": 64, "deflate_on_oom": false })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_responses_across_releases() {
+        use crate::balloon::BalloonOperations;
+        use crate::instance::InstanceOperations;
+        use crate::machine::MachineConfigOperations;
+
+        let (mut server, client) = create_test_client().await;
+
+        let mock = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::INSTANCE_INFO_V1_0)
+            .create();
+        let info = client.describe_instance().await.unwrap();
+        assert_eq!(info.app_name, "");
+        assert_eq!(info.vmm_version, "1.0.0");
+        mock.remove();
+        let mock = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::INSTANCE_INFO_V1_7)
+            .create();
+        let info = client.describe_instance().await.unwrap();
+        assert_eq!(info.app_name, "Firecracker");
+        assert_eq!(info.state, "Running");
+        mock.remove();
+
+        let mock = server
+            .mock("GET", "/machine-config")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::MACHINE_CONFIG_V1_0)
+            .create();
+        let config = client.get_machine_config().await.unwrap();
+        assert_eq!(config.vcpu_count, Some(2));
+        assert_eq!(config.huge_pages, None);
+        mock.remove();
+        let mock = server
+            .mock("GET", "/machine-config")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::MACHINE_CONFIG_V1_7)
+            .create();
+        let config = client.get_machine_config().await.unwrap();
+        assert_eq!(config.huge_pages.as_deref(), Some("None"));
+        assert_eq!(config.track_dirty_pages, Some(true));
+        mock.remove();
+
+        let mock = server
+            .mock("GET", "/balloon/statistics")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::BALLOON_STATS_V1_0)
+            .create();
+        let stats = client.get_balloon_stats().await.unwrap();
+        assert_eq!(stats.actual_mib, 256);
+        assert_eq!(stats.disk_caches, None);
+        assert_eq!(stats.hugetlb_allocations, None);
+        assert_eq!(stats.oom_kill, None);
+        mock.remove();
+        let mock = server
+            .mock("GET", "/balloon/statistics")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::BALLOON_STATS_V1_7)
+            .create();
+        let stats = client.get_balloon_stats().await.unwrap();
+        assert_eq!(stats.actual_mib, 128);
+        assert_eq!(stats.hugetlb_failures, Some(0));
+        assert_eq!(stats.async_scan, Some(4096));
+        mock.remove();
+
+        let mock = server
+            .mock("GET", "/vm/config")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::VM_CONFIG_V1_4)
+            .create();
+        let config = client.get_vm_config().await.unwrap();
+        assert_eq!(config.machine_config.unwrap().huge_pages, None);
+        assert!(config.mmds_config.is_none());
+        mock.remove();
+        let mock = server
+            .mock("GET", "/vm/config")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::VM_CONFIG_V1_7)
+            .create();
+        let config = client.get_vm_config().await.unwrap();
+        assert_eq!(config, crate::testing::fixtures::vm_config_v1_7());
+        assert_eq!(
+            config.machine_config.unwrap().huge_pages.as_deref(),
+            Some("None")
+        );
+        mock.remove();
+
+        // Fields every release sends are still required, and the error keeps
+        // the body for debugging
+        let body = r#"{"target_mib": 256, "actual_mib": 128}"#;
+        server
+            .mock("GET", "/balloon/statistics")
+            .with_status(200)
+            .with_body(body)
+            .create();
+        match client.get_balloon_stats().await {
+            Err(crate::FirecrackerError::Decode { message, body: got }) => {
+                assert!(message.contains("actual_pages"), "{message}");
+                assert_eq!(got, body);
+            }
+            other => panic!("expected a decode error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_version_body_shapes() {
+        use crate::version::{Version, VersionOperations};
+
+        let (mut server, client) = create_test_client().await;
+
+        for body in [
+            r#"{"firecracker_version": "v1.7.0"}"#,
+            r#""1.7.0""#,
+            "v1.7.0\n",
+        ] {
+            let mock = server
+                .mock("GET", "/version")
+                .with_status(200)
+                .with_body(body)
+                .create();
+            let version = client.get_version().await.unwrap();
+            assert_eq!(version.firecracker_version, "1.7.0", "{body}");
+            assert_eq!(version.semver().unwrap(), Version::new(1, 7, 0));
+            assert!(version.at_least(1, 7));
+            mock.remove();
+        }
+
+        server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body("firecracker")
+            .create();
+        match client.get_version().await {
+            Err(crate::FirecrackerError::Decode { body, .. }) => assert_eq!(body, "firecracker"),
+            other => panic!("expected a decode error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_action_type_serde() {
+        use crate::action::{ActionType, InstanceActionInfo};
+
+        for (raw, action_type) in [
+            ("FlushMetrics", ActionType::FlushMetrics),
+            ("InstanceStart", ActionType::InstanceStart),
+            ("InstanceHalt", ActionType::InstanceHalt),
+            ("SendCtrlAltDel", ActionType::SendCtrlAltDel),
+            ("Whatever", ActionType::Custom("Whatever".to_string())),
+            // Firecracker is case-sensitive, so this is not InstanceStart
+            (
+                "instancestart",
+                ActionType::Custom("instancestart".to_string()),
+            ),
+        ] {
+            let body = serde_json::json!({ "action_type": raw });
+            let info: InstanceActionInfo = serde_json::from_value(body.clone()).unwrap();
+            assert_eq!(info.action_type, action_type);
+            assert_eq!(info, InstanceActionInfo::new(raw));
+            assert_eq!(info, InstanceActionInfo::from(action_type));
+            assert_eq!(serde_json::to_value(&info).unwrap(), body);
+        }
+
+        assert_eq!(ActionType::SendCtrlAltDel.to_string(), "SendCtrlAltDel");
+        assert!(InstanceActionInfo::from(ActionType::InstanceStart)
+            .validate()
+            .is_ok());
+        assert!(InstanceActionInfo::new("Whatever").validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_action_sent_verbatim() {
+        use crate::action::{ActionOperations, ActionType, InstanceActionInfo};
+        use crate::validation::ValidationMode;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Off);
+        let mock = server
+            .mock("PUT", "/actions")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({ "action_type": "Whatever" }),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let action = InstanceActionInfo::from(ActionType::Custom("Whatever".to_string()));
+        ActionOperations::create_sync_action(&client, &action)
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_plan_config_and_error_paths() {
+        use crate::config::ConfigOperations;
+        use crate::models::{BootSource, Drive, MachineConfig};
+        use crate::validation::{error_paths, PathPolicy, ValidationMode};
+
+        let (_, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::StrictLocal)
+            .with_path_policy(PathPolicy {
+                canonicalize: true,
+                ..Default::default()
+            });
+        let dir = tempfile::tempdir().unwrap();
+        let kernel = dir.path().join("vmlinux");
+        std::fs::write(&kernel, b"kernel").unwrap();
+        let config = crate::FullVmConfiguration {
+            machine_config: Some(MachineConfig::new(2, 256)),
+            boot_source: Some(BootSource::new(format!(
+                "{}/./vmlinux",
+                dir.path().display()
+            ))),
+            drives: vec![Drive::new(
+                "rootfs",
+                dir.path().join("rootfs.ext4").to_str().unwrap(),
+            )],
+            ..Default::default()
+        };
+
+        // In apply order, with paths as the client would send them
+        let plan = client.plan_config(&config).unwrap();
+        let requests: Vec<_> = plan
+            .iter()
+            .map(|r| (r.section.as_str(), r.method, r.path.as_str()))
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                ("machine_config", "PUT", "machine-config"),
+                ("boot_source", "PUT", "boot-source"),
+                ("drives[0]", "PUT", "drives/rootfs"),
+            ]
+        );
+        #[cfg(not(feature = "no-fs-validation"))]
+        assert_eq!(
+            plan[1].body["kernel_image_path"],
+            kernel.canonicalize().unwrap().to_str().unwrap()
+        );
+
+        let mut bad = config.clone();
+        bad.drives[0].is_root_device = true;
+        bad.machine_config = Some(MachineConfig::new(0, 256));
+        bad.drives[0].drive_id = String::new();
+        match client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .validate_config(&bad)
+        {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                let paths: Vec<_> = error_paths(&errors)
+                    .into_iter()
+                    .map(|(path, _)| path)
+                    .collect();
+                assert_eq!(paths, ["drives[0].drive_id", "machine_config.vcpu_count"]);
+            }
+            other => panic!("expected validation errors, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_helpers_reject_wrong_requests() {
+        use crate::action::{ActionType, InstanceActionInfo};
+        use crate::instance::InstanceOperations;
+        use crate::testing::mock::{
+            expect_action, expect_instance_state_sequence, expect_put_drive, json_body,
+        };
+        use crate::validation::ValidationMode;
+        use crate::{Drive, DriveOperations};
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let drive = Drive::new("rootfs", "/images/rootfs.ext4");
+        let put_drive = expect_put_drive(&mut server, "rootfs", json_body(&drive));
+        let start = expect_action(&mut server, ActionType::InstanceStart);
+
+        // A different body, drive id or action doesn't match
+        let other = Drive::new("rootfs", "/images/other.ext4");
+        let api_status = |result: Result<(), crate::FirecrackerError>| match result {
+            Err(crate::FirecrackerError::Api { status_code, .. }) => status_code,
+            other => panic!("expected an API error, got {:?}", other),
+        };
+        assert_eq!(api_status(client.put_drive("rootfs", &other).await), 501);
+        let data = Drive::new("data", "/images/rootfs.ext4");
+        assert_eq!(api_status(client.put_drive("data", &data).await), 501);
+        let halt = InstanceActionInfo::from(ActionType::InstanceHalt);
+        assert_eq!(api_status(client.create_sync_action(&halt).await), 501);
+        assert!(!put_drive.matched());
+        assert!(!start.matched());
+
+        client.put_drive("rootfs", &drive).await.unwrap();
+        client
+            .create_sync_action(&InstanceActionInfo::from(ActionType::InstanceStart))
+            .await
+            .unwrap();
+        put_drive.assert();
+        start.assert();
+
+        // The last state sticks, and too few polls don't satisfy the sequence
+        let states = expect_instance_state_sequence(&mut server, &["Not started", "Running"]);
+        assert_eq!(
+            client.describe_instance().await.unwrap().state,
+            "Not started"
+        );
+        assert!(!states.matched());
+        for _ in 0..2 {
+            assert_eq!(client.describe_instance().await.unwrap().state, "Running");
+        }
+        states.assert();
+    }
+
+    #[tokio::test]
+    async fn test_watch_instance_reports_transitions() {
+        use crate::instance::watch_instance;
+        use crate::testing::mock::expect_instance_state_sequence;
+        use futures_util::StreamExt;
+        use std::time::Duration;
+
+        let (mut server, client) = create_test_client().await;
+        let polls = expect_instance_state_sequence(
+            &mut server,
+            &[
+                "Not started",
+                "Not started",
+                "Not started",
+                "Running",
+                "Paused",
+            ],
+        );
+
+        let states: Vec<_> = watch_instance(&client, Duration::from_millis(10))
+            .take(3)
+            .map(|info| info.unwrap().state)
+            .collect()
+            .await;
+        assert_eq!(states, ["Not started", "Running", "Paused"]);
+        polls.assert();
+    }
+
+    #[tokio::test]
+    async fn test_get_vm_config() {
+        let (mut server, client) = create_test_client().await;
+        let _m = server
+            .mock("GET", "/vm/config")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::VM_CONFIG_V1_7)
+            .create();
+
+        let config = client.get_vm_config().await.unwrap();
+        assert_eq!(config.drives[0].drive_id, "rootfs");
+        assert_eq!(config.machine_config.unwrap().vcpu_count, Some(2));
+        assert_eq!(config.network_interfaces[0].host_dev_name, "tap0");
+        assert!(config.balloon.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_boot_and_parse() {
+        use crate::action::InstanceActionInfo;
+        use crate::config::ConfigOperations;
+        use crate::testing::{fixtures, FakeInstanceState, FakeVmm};
+        use crate::validation::{validate_vm_config, ValidationMode};
+
+        let config = fixtures::bootable_config();
+        assert_eq!(validate_vm_config(&config).findings.len(), 0);
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        client.apply_config(&config).await.unwrap();
+        client
+            .create_sync_action(&InstanceActionInfo::new("InstanceStart"))
+            .await
+            .unwrap();
+        assert_eq!(fake.state().instance, FakeInstanceState::Running);
+
+        let stats = fixtures::balloon_stats_v1_7();
+        assert_eq!(stats.target_mib, 256);
+        assert_eq!(stats.total_memory, Some(1032900608));
+        let metrics = fixtures::metrics_v1_7();
+        assert_eq!(metrics.utc_timestamp_ms, 1709280000456);
+        assert_eq!(
+            fixtures::vm_config_v1_7()
+                .mmds_config
+                .unwrap()
+                .network_interfaces,
+            ["eth0"]
+        );
+
+        let body: Value =
+            serde_json::from_str(&fixtures::fault_body(fixtures::SNAPSHOT_LOAD_AFTER_CONFIG))
+                .unwrap();
+        assert_eq!(
+            body["fault_message"],
+            "Loading a microVM snapshot not allowed after configuring boot-specific resources."
+        );
+    }
+
+    #[test]
+    fn test_mmds_set_patch_and_size() {
+        use crate::mmds::{mmds_set_patch, MMDS_DEFAULT_SIZE_LIMIT};
+        use crate::validation::validate_mmds_size;
+        use serde_json::json;
+
+        assert_eq!(
+            mmds_set_patch("/latest/meta-data/instance-id", json!("i-123")).unwrap(),
+            json!({ "latest": { "meta-data": { "instance-id": "i-123" } } })
+        );
+        assert_eq!(
+            mmds_set_patch("/a~1b/c~0d", json!(1)).unwrap(),
+            json!({ "a/b": { "c~d": 1 } })
+        );
+        assert_eq!(mmds_set_patch("/", json!(1)).unwrap(), json!({ "": 1 }));
+        assert_eq!(
+            mmds_set_patch("/a//b", json!(1)).unwrap(),
+            json!({ "a": { "": { "b": 1 } } })
+        );
+        assert!(mmds_set_patch("", json!(1)).is_err());
+        assert!(mmds_set_patch("latest", json!(1)).is_err());
+
+        let small = json!({ "id": "i-1" });
+        assert!(validate_mmds_size(&small, MMDS_DEFAULT_SIZE_LIMIT).is_ok());
+        let err = validate_mmds_size(&small, 4).unwrap_err();
+        assert_eq!(err.code, "mmds_too_large");
+        assert_eq!(err.params["size"], 12);
+    }
+
+    // Answers every PUT that applying `lint_clean_config` sends
+    fn mock_microvm_config(server: &mut ServerGuard) -> mockito::Mock {
+        server
+            .mock(
+                "PUT",
+                mockito::Matcher::Regex(
+                    "^/(machine-config|boot-source|drives/|network-interfaces/)".to_string(),
+                ),
+            )
+            .with_status(204)
+            .expect(6)
+            .create()
+    }
+
+    fn assert_invalid_state<T: std::fmt::Debug>(
+        result: Result<T, crate::FirecrackerError>,
+        current: &str,
+    ) {
+        match result {
+            Err(crate::FirecrackerError::InvalidState { current_state, .. }) => {
+                assert_eq!(current_state, current)
+            }
+            other => panic!("expected InvalidState, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fake_vmm_models_firecracker_state() {
+        use crate::action::InstanceActionInfo;
+        use crate::boot::BootSourceOperations;
+        use crate::instance::InstanceOperations;
+        use crate::machine::MachineConfigOperations;
+        use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams, SnapshotOperations};
+        use crate::testing::{FakeInstanceState, FakeVmm};
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        let config = lint_clean_config();
+        let api_error = |result: Result<(), crate::FirecrackerError>| match result {
+            Err(crate::FirecrackerError::Api {
+                status_code: 400,
+                message,
+            }) => message,
+            other => panic!("expected a fault, got {:?}", other),
+        };
+
+        // Runtime-only operations are refused before the start
+        assert!(api_error(client.pause_vm().await).contains("before starting the microVM"));
+        assert!(api_error(
+            client
+                .create_sync_action(&InstanceActionInfo::new("InstanceStart"))
+                .await
+        )
+        .contains("without kernel configuration"));
+
+        for drive in &config.drives {
+            client.put_drive(&drive.drive_id, drive).await.unwrap();
+        }
+        client
+            .put_boot_source(config.boot_source.as_ref().unwrap())
+            .await
+            .unwrap();
+        let described = client.get_vm_config().await.unwrap();
+        assert_eq!(described.drives, config.drives);
+        assert_eq!(described.boot_source, config.boot_source);
+        assert!(client.get_balloon_config().await.is_err());
+
+        client
+            .create_sync_action(&InstanceActionInfo::new("InstanceStart"))
+            .await
+            .unwrap();
+        assert_eq!(client.describe_instance().await.unwrap().state, "Running");
+        // Boot-time-only endpoints are refused after it
+        let message = api_error(
+            client
+                .put_machine_config(&crate::MachineConfig::new(2, 256))
+                .await,
+        );
+        assert!(message
+            .contains("The requested operation is not supported after starting the microVM."));
+
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("vm.snap").to_str().unwrap().to_string();
+        let memory = dir.path().join("vm.mem").to_str().unwrap().to_string();
+        let params = SnapshotCreateParams::new(&snapshot, &memory);
+        assert!(api_error(client.create_snapshot(&params).await).contains("must be paused"));
+        client.pause_vm().await.unwrap();
+        client.create_snapshot(&params).await.unwrap();
+        assert_eq!(fake.state().instance, FakeInstanceState::Paused);
+
+        // A second VMM restores the configuration from the snapshot
+        let restored = FakeVmm::start().await.unwrap();
+        let restored_client = restored
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        restored_client
+            .load_snapshot(&SnapshotLoadParams::with_mem_file(&snapshot, &memory))
+            .await
+            .unwrap();
+        let state = restored.state();
+        assert_eq!(state.instance, FakeInstanceState::Paused);
+        assert_eq!(state.config, fake.state().config);
+        assert!(api_error(
+            restored_client
+                .load_snapshot(&SnapshotLoadParams::with_mem_file(&snapshot, &memory))
+                .await
+        )
+        .contains("not allowed after configuring"));
+    }
+
+    #[tokio::test]
+    async fn test_microvm_lifecycle() {
+        use crate::microvm::{EventTrigger, VmEventKind, VmLifecycle};
+        use crate::testing::{FakeInstanceState, FakeVmm};
+        use crate::validation::ValidationMode;
+        use crate::MicroVm;
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        let dir = tempfile::tempdir().unwrap();
+
+        let (events, mut received) = tokio::sync::broadcast::channel(16);
+        let mut vm = MicroVm::configure_with_events(client, lint_clean_config(), events)
+            .await
+            .unwrap();
+        assert_eq!(vm.state(), VmLifecycle::Configured);
+        vm.start().await.unwrap();
+        assert_eq!(vm.state(), VmLifecycle::Running);
+
+        vm.pause().await.unwrap();
+        assert_eq!(vm.state(), VmLifecycle::Paused);
+        vm.resume().await.unwrap();
+        assert_eq!(vm.state(), VmLifecycle::Running);
+
+        // Paused for the snapshot and resumed afterwards
+        let params = crate::snapshot::SnapshotCreateParams::new(
+            dir.path().join("vm.snap").to_str().unwrap(),
+            dir.path().join("vm.mem").to_str().unwrap(),
+        );
+        vm.snapshot(&params).await.unwrap();
+        assert_eq!(vm.state(), VmLifecycle::Running);
+        assert_eq!(fake.state().instance, FakeInstanceState::Running);
+
+        vm.shutdown().await.unwrap();
+        assert_eq!(vm.state(), VmLifecycle::ShutDown);
+
+        // One event per step, in order, each naming the call that caused it
+        let mut sequence = Vec::new();
+        while let Ok(event) = received.try_recv() {
+            sequence.push((event.kind, event.trigger));
+        }
+        assert_eq!(
+            sequence,
+            [
+                (VmEventKind::Configured, EventTrigger::Call("configure")),
+                (VmEventKind::Started, EventTrigger::Call("start")),
+                (VmEventKind::Paused, EventTrigger::Call("pause")),
+                (VmEventKind::Resumed, EventTrigger::Call("resume")),
+                (
+                    VmEventKind::SnapshotCreated {
+                        snapshot_path: params.snapshot_path.clone()
+                    },
+                    EventTrigger::Call("snapshot")
+                ),
+                (
+                    VmEventKind::ShutdownRequested,
+                    EventTrigger::Call("shutdown")
+                ),
+            ]
+        );
+
+        let state = fake.state();
+        assert_eq!(state.config, lint_clean_config());
+        assert_eq!(state.actions, ["InstanceStart", "SendCtrlAltDel"]);
+        assert_eq!(state.snapshots, [params.snapshot_path]);
+        let vm_patches = state.requests.iter().filter(|r| *r == "PATCH /vm");
+        assert_eq!(vm_patches.count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_microvm_rejects_illegal_transitions() {
+        use crate::microvm::VmLifecycle;
+        use crate::testing::FakeVmm;
+        use crate::validation::ValidationMode;
+        use crate::MicroVm;
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+
+        let mut vm = MicroVm::configure(client, lint_clean_config())
+            .await
+            .unwrap();
+        // Nothing to pause or resume before the start
+        assert_invalid_state(vm.pause().await, "Configured");
+        assert_invalid_state(vm.resume().await, "Configured");
+        assert_invalid_state(vm.shutdown().await, "Configured");
+
+        vm.start().await.unwrap();
+        assert_invalid_state(vm.reconfigure(lint_clean_config()).await, "Running");
+        assert_invalid_state(vm.resume().await, "Running");
+
+        vm.shutdown().await.unwrap();
+        assert_invalid_state(vm.pause().await, "ShutDown");
+        let (params, _) = snapshot_params();
+        assert_invalid_state(vm.snapshot(&params).await, "ShutDown");
+        assert_eq!(vm.state(), VmLifecycle::ShutDown);
+
+        // None of the refused calls reached the VMM
+        let state = fake.state();
+        assert_eq!(state.actions, ["InstanceStart", "SendCtrlAltDel"]);
+        assert!(!state.requests.iter().any(|r| r == "PATCH /vm"));
+        assert!(!state.requests.iter().any(|r| r == "PUT /snapshot/create"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_microvm_teardown_plan_keeps_plain_log_files() {
+        use crate::metrics::Metrics;
+        use crate::orchestration::ResourceKind;
+        use crate::testing::FakeVmm;
+        use crate::validation::ValidationMode;
+        use crate::{Logger, MicroVm};
+
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("firecracker.log");
+        std::fs::write(&log_file, b"").unwrap();
+        let metrics_fifo = dir.path().join("metrics.fifo");
+        crate::logger::create_log_fifo(&metrics_fifo, false).unwrap();
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        let config = crate::FullVmConfiguration {
+            logger: Some(Logger::new(log_file.to_str().unwrap())),
+            metrics: Some(Metrics {
+                metrics_path: metrics_fifo.to_str().unwrap().to_string(),
+            }),
+            ..lint_clean_config()
+        };
+        let vm = MicroVm::configure(client, config).await.unwrap();
+
+        let plan = vm.teardown_plan(dir.path());
+        assert_eq!(plan.items(), [(ResourceKind::LogFifo, metrics_fifo)]);
+    }
+
+    #[tokio::test]
+    async fn test_microvm_keeps_state_when_the_vmm_refuses() {
+        use crate::microvm::VmLifecycle;
+        use crate::testing::FakeVmm;
+        use crate::validation::ValidationMode;
+        use crate::MicroVm;
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        fake.fail_next("PUT", "/actions", "Cannot start microvm");
+        fake.fail_next("PATCH", "/vm", "The microVM is not running");
+
+        let mut vm = MicroVm::configure(client, lint_clean_config())
+            .await
+            .unwrap();
+        // A refused start keeps the VM, still configured
+        assert!(matches!(
+            vm.start().await,
+            Err(crate::FirecrackerError::Api {
+                status_code: 400,
+                ..
+            })
+        ));
+        assert_eq!(vm.state(), VmLifecycle::Configured);
+        vm.start().await.unwrap();
+        assert!(matches!(
+            vm.pause().await,
+            Err(crate::FirecrackerError::Api {
+                status_code: 400,
+                ..
+            })
+        ));
+        assert_eq!(vm.state(), VmLifecycle::Running);
+    }
+
+    #[tokio::test]
+    async fn test_microvm_watcher_reports_out_of_band_changes() {
+        use crate::microvm::{EventTrigger, VmEvent, VmEventKind, VmLifecycle};
+        use crate::validation::ValidationMode;
+        use crate::MicroVm;
+        use std::time::Duration;
+
+        async fn next(received: &mut tokio::sync::broadcast::Receiver<VmEvent>) -> VmEvent {
+            tokio::time::timeout(Duration::from_secs(5), received.recv())
+                .await
+                .expect("no event")
+                .unwrap()
+        }
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let _config = mock_microvm_config(&mut server);
+        let _actions = server.mock("PUT", "/actions").with_status(204).create();
+        let running = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+
+        let mut vm = MicroVm::configure(client, lint_clean_config())
+            .await
+            .unwrap();
+        vm.start().await.unwrap();
+        let mut received = vm.subscribe();
+        vm.watch_state(Duration::from_millis(10));
+
+        // Someone else pauses the VM
+        running.remove();
+        let paused = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Paused"))
+            .create();
+        let event = next(&mut received).await;
+        assert_eq!(event.kind, VmEventKind::Paused);
+        assert_eq!(event.trigger, EventTrigger::Watcher);
+        assert_eq!(vm.state(), VmLifecycle::Paused);
+
+        // The API going away is reported once
+        paused.remove();
+        let event = next(&mut received).await;
+        assert!(matches!(event.kind, VmEventKind::ApiUnreachable { .. }));
+        let resumed = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        assert_eq!(next(&mut received).await.kind, VmEventKind::Resumed);
+
+        // After a shutdown request the API disappearing means the VM is gone
+        vm.shutdown().await.unwrap();
+        assert_eq!(
+            next(&mut received).await.kind,
+            VmEventKind::ShutdownRequested
+        );
+        resumed.remove();
+        let event = next(&mut received).await;
+        assert_eq!(event.kind, VmEventKind::Halted);
+        assert_eq!(event.trigger, EventTrigger::Watcher);
+    }
+
+    #[tokio::test]
+    async fn test_microvm_drop_policy() {
+        use crate::microvm::{DropPolicy, VmLifecycle};
+        use crate::validation::ValidationMode;
+        use crate::MicroVm;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let _config = mock_microvm_config(&mut server);
+        let _start = server
+            .mock("PUT", "/actions")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"action_type": "InstanceStart"}),
+            ))
+            .with_status(204)
+            .create();
+        let ctrl_alt_del = server
+            .mock("PUT", "/actions")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"action_type": "SendCtrlAltDel"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let mut vm = MicroVm::configure(client, lint_clean_config())
+            .await
+            .unwrap()
+            .with_drop_policy(DropPolicy::SendCtrlAltDel)
+            .unwrap();
+        vm.start().await.unwrap();
+        assert_eq!(vm.state(), VmLifecycle::Running);
+        // Dropping halfway, as a panicking test would, stops the VM
+        let dropped = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _vm = vm;
+            panic!("test failed halfway");
+        }));
+        assert!(dropped.is_err());
+        ctrl_alt_del.assert();
+
+        // Closing carries the policy out once; nothing is left for the drop
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let _config = mock_microvm_config(&mut server);
+        let _start = server.mock("PUT", "/actions").with_status(204).create();
+        let pause = server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Paused"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let mut vm = MicroVm::configure(client, lint_clean_config())
+            .await
+            .unwrap()
+            .with_drop_policy(DropPolicy::Pause)
+            .unwrap();
+        vm.start().await.unwrap();
+        vm.close().await.unwrap();
+        pause.assert();
+    }
+
+    // Writes an executable shell script standing in for the firecracker binary
+    #[cfg(all(unix, feature = "launcher"))]
+    fn stub_firecracker(dir: &std::path::Path, script: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("firecracker");
+        std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    // Serves `GET /` on `api_sock` once `ready` exists, the way Firecracker
+    // creates its socket some time after starting
+    #[cfg(unix)]
+    fn serve_stub_api(api_sock: std::path::PathBuf, ready: std::path::PathBuf) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        tokio::spawn(async move {
+            while !ready.exists() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            let listener = tokio::net::UnixListener::bind(&api_sock).unwrap();
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let body = r#"{"id":"vm-1","state":"Not started","vmm_version":"1.7.0","app_name":"Firecracker"}"#;
+                    let mut buf = [0u8; 4096];
+                    while let Ok(n) = stream.read(&mut buf).await {
+                        if n == 0 {
+                            break;
+                        }
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        if stream.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[cfg(all(unix, feature = "launcher"))]
+    #[tokio::test]
+    async fn test_launcher_spawns_and_kills() {
+        use crate::instance::InstanceOperations;
+        use crate::launcher::{FirecrackerProcess, LaunchOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let args_file = dir.path().join("args");
+        let binary = stub_firecracker(
+            dir.path(),
+            &format!(
+                "echo \"$@\" > {}\necho 'Running Firecracker v1.7.0'\nexec sleep 30\n",
+                args_file.display()
+            ),
+        );
+        let api_sock = dir.path().join("run").join("api.sock");
+        serve_stub_api(api_sock.clone(), args_file.clone());
+
+        let mut options = LaunchOptions::new(&binary, &api_sock);
+        options.id = Some("vm-1".to_string());
+        options.log_fifo = Some(dir.path().join("log.fifo"));
+        options.extra_args = vec!["--level".to_string(), "Debug".to_string()];
+        let (mut process, client) = FirecrackerProcess::spawn(options).await.unwrap();
+
+        assert_eq!(client.describe_instance().await.unwrap().id, "vm-1");
+        assert_eq!(
+            std::fs::read_to_string(&args_file).unwrap().trim(),
+            format!(
+                "--api-sock {} --id vm-1 --log-path {} --level Debug",
+                api_sock.display(),
+                dir.path().join("log.fifo").display()
+            )
+        );
+        process.check().await.unwrap();
+        assert_eq!(process.output(), vec!["Running Firecracker v1.7.0"]);
+
+        process.kill().await.unwrap();
+        assert!(!api_sock.exists());
+        assert!(matches!(
+            process.check().await,
+            Err(crate::FirecrackerError::ProcessExited { .. })
+        ));
+
+        // The launcher created the socket's directory, so it goes too
+        let plan = process.teardown_plan();
+        assert_eq!(plan.root(), dir.path());
+        let report = crate::orchestration::teardown(plan);
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!(report.removed, vec![dir.path().join("run")]);
+    }
+
+    #[cfg(all(unix, feature = "launcher"))]
+    #[tokio::test]
+    async fn test_launcher_reports_crash_before_api() {
+        use crate::launcher::{FirecrackerProcess, LaunchOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = stub_firecracker(
+            dir.path(),
+            "echo 'Error: Invalid value for --level' >&2\nexit 1\n",
+        );
+        let api_sock = dir.path().join("api.sock");
+
+        match FirecrackerProcess::spawn(LaunchOptions::new(&binary, &api_sock)).await {
+            Err(crate::FirecrackerError::ProcessExited { status, output }) => {
+                assert!(status.contains('1'), "{}", status);
+                assert_eq!(output, "Error: Invalid value for --level");
+            }
+            Err(e) => panic!("expected ProcessExited, got {:?}", e),
+            Ok(_) => panic!("expected ProcessExited"),
+        }
+    }
+
+    #[cfg(all(unix, feature = "launcher"))]
+    #[tokio::test]
+    async fn test_launcher_gives_up_when_the_api_never_answers() {
+        use crate::launcher::{FirecrackerProcess, LaunchOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = stub_firecracker(dir.path(), "exec sleep 30\n");
+        let mut options = LaunchOptions::new(&binary, dir.path().join("api.sock"));
+        options.api_timeout = std::time::Duration::from_millis(200);
+
+        assert!(matches!(
+            FirecrackerProcess::spawn(options).await,
+            Err(crate::FirecrackerError::ApiSocket { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_client_talks_to_the_api_socket() {
+        use crate::instance::InstanceOperations;
+
+        let dir = tempfile::tempdir().unwrap();
+        let api_sock = dir.path().join("api.sock");
+        let client = FirecrackerClient::new_unix(&api_sock).await.unwrap();
+        match client.describe_instance().await {
+            Err(crate::FirecrackerError::ApiSocket { path, source }) => {
+                assert_eq!(path, api_sock);
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            other => panic!("expected ApiSocket, got {:?}", other),
+        }
+
+        serve_stub_api(api_sock.clone(), dir.path().to_path_buf());
+        let client = client.with_connect_retry(
+            std::time::Duration::from_secs(2),
+            std::time::Duration::from_millis(10),
+        );
+        assert_eq!(client.describe_instance().await.unwrap().id, "vm-1");
+    }
+
+    #[tokio::test]
+    async fn test_jailer_translates_paths() {
+        use crate::jailer::JailerContext;
+        use crate::snapshot::SnapshotOperations;
+        use crate::validation::ValidationMode;
+        use crate::{Drive, DriveOperations};
+
+        let jailer = JailerContext::new("/srv/jailer", "vm-1", 123, 100);
+        assert_eq!(
+            jailer.chroot_dir(),
+            std::path::Path::new("/srv/jailer/firecracker/vm-1/root")
+        );
+        assert_eq!(
+            jailer
+                .translate("/srv/jailer/firecracker/vm-1/root/images/./rootfs.ext4")
+                .unwrap(),
+            "/images/rootfs.ext4"
+        );
+        assert!(jailer.translate("/images/rootfs.ext4").is_err());
+        assert!(jailer
+            .translate("/srv/jailer/firecracker/vm-1/root/../../vm-2/root/rootfs.ext4")
+            .is_err());
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_jailer(jailer.clone());
+        let drive_mock = server
+            .mock("PUT", "/drives/rootfs")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"path_on_host": "/rootfs.ext4"}),
+            ))
+            .with_status(204)
+            .create();
+        let snapshot_mock = server
+            .mock("PUT", "/snapshot/create")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "snapshot_path": "/snapshots/vm.snap",
+                "mem_file_path": "/snapshots/vm.mem",
+            })))
+            .with_status(204)
+            .create();
+
+        let drive = Drive {
+            drive_id: "rootfs".to_string(),
+            path_on_host: jailer.host_path("rootfs.ext4").display().to_string(),
+            is_root_device: true,
+            ..Default::default()
+        };
+        client.put_drive("rootfs", &drive).await.unwrap();
+
+        let params = crate::snapshot::SnapshotCreateParams::new(
+            jailer.host_path("/snapshots/vm.snap").display().to_string(),
+            jailer.host_path("/snapshots/vm.mem").display().to_string(),
+        );
+        client.create_snapshot(&params).await.unwrap();
+
+        // Outside the chroot the VMM could never open it
+        let outside = Drive {
+            path_on_host: "/images/rootfs.ext4".to_string(),
+            ..drive
+        };
+        match client.put_drive("rootfs", &outside).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                assert!(errors.field_errors().contains_key("path_on_host"))
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+
+        drive_mock.assert();
+        snapshot_mock.assert();
+    }
+
+    #[cfg(not(feature = "no-fs-validation"))]
+    #[tokio::test]
+    async fn test_jailer_detects_files_missing_from_the_chroot() {
+        use crate::jailer::JailerContext;
+        use crate::{Drive, DriveOperations};
+
+        let base = tempfile::tempdir().unwrap();
+        let jailer = JailerContext::new(base.path(), "vm-1", 123, 100);
+        std::fs::create_dir_all(jailer.chroot_dir()).unwrap();
+        std::fs::write(jailer.host_path("rootfs.ext4"), b"rootfs").unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_jailer(jailer.clone());
+        let mock = server
+            .mock("PUT", "/drives/rootfs")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"path_on_host": "/rootfs.ext4"}),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        let drive = Drive {
+            drive_id: "rootfs".to_string(),
+            path_on_host: jailer.host_path("rootfs.ext4").display().to_string(),
+            is_root_device: true,
+            ..Default::default()
+        };
+        client.put_drive("rootfs", &drive).await.unwrap();
+
+        // Never linked into the chroot
+        let missing = Drive {
+            path_on_host: jailer.host_path("data.ext4").display().to_string(),
+            ..drive
+        };
+        assert!(matches!(
+            client.put_drive("rootfs", &missing).await,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+        mock.assert();
+    }
+
+    #[cfg(all(unix, feature = "launcher"))]
+    #[tokio::test]
+    async fn test_launcher_runs_the_jailer() {
+        use crate::jailer::JailerContext;
+        use crate::launcher::{FirecrackerProcess, LaunchOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let jailer = JailerContext::new(dir.path().join("jail"), "vm-1", 123, 100);
+        let api_sock = jailer.host_path("run/firecracker.socket");
+        let args_file = dir.path().join("args");
+        let jailer_binary = stub_firecracker(
+            dir.path(),
+            &format!(
+                "mkdir -p {}\necho \"$@\" > {}\nexec sleep 30\n",
+                api_sock.parent().unwrap().display(),
+                args_file.display()
+            ),
+        );
+        serve_stub_api(api_sock.clone(), args_file.clone());
+
+        let mut options = LaunchOptions::new("/usr/bin/firecracker", &api_sock);
+        options.jailer = Some(jailer.clone());
+        options.jailer_binary = jailer_binary;
+        options.log_fifo = Some(jailer.host_path("log.fifo"));
+        let (mut process, client) = FirecrackerProcess::spawn(options).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&args_file).unwrap().trim(),
+            format!(
+                "--id vm-1 --exec-file /usr/bin/firecracker --uid 123 --gid 100 \
+                 --chroot-base-dir {} -- --api-sock /run/firecracker.socket --log-path /log.fifo",
+                dir.path().join("jail").display()
+            )
+        );
+        assert_eq!(client.jailer(), Some(&jailer));
+        process.kill().await.unwrap();
+
+        // The exec file name is part of the chroot path
+        let mut options = LaunchOptions::new("/usr/bin/firecracker-v1.7", &api_sock);
+        options.jailer = Some(jailer);
+        assert!(matches!(
+            FirecrackerProcess::spawn(options).await,
+            Err(crate::FirecrackerError::Config(_))
+        ));
+    }
+
+    // Source VMM for a clone: the snapshot request writes the files it names
+    fn mock_clone_source(server: &mut ServerGuard) -> Vec<mockito::Mock> {
+        vec![
+            server
+                .mock("PATCH", "/vm")
+                .match_body(mockito::Matcher::Json(
+                    serde_json::json!({"state": "Paused"}),
+                ))
+                .with_status(204)
+                .expect(1)
+                .create(),
+            server
+                .mock("PUT", "/snapshot/create")
+                .match_body(mockito::Matcher::PartialJson(
+                    serde_json::json!({"snapshot_type": "Full"}),
+                ))
+                .with_status(204)
+                .with_body_from_request(|request| {
+                    let body: Value = serde_json::from_slice(request.body().unwrap()).unwrap();
+                    std::fs::write(body["snapshot_path"].as_str().unwrap(), b"state").unwrap();
+                    std::fs::write(body["mem_file_path"].as_str().unwrap(), b"memory").unwrap();
+                    Vec::new()
+                })
+                .expect(1)
+                .create(),
+            server
+                .mock("PATCH", "/vm")
+                .match_body(mockito::Matcher::Json(
+                    serde_json::json!({"state": "Resumed"}),
+                ))
+                .with_status(204)
+                .expect(1)
+                .create(),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_clone_vm() {
+        use crate::orchestration::{clone_vm, CloneOptions};
+        use crate::snapshot::NetworkOverride;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut source_server, source) = create_test_client().await;
+        let (mut target_server, target) = create_test_client().await;
+        let source_mocks = mock_clone_source(&mut source_server);
+        let _version = target_server
+            .mock("GET", "/version")
+            .with_status(200)
+            .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+            .create();
+        let _not_started = target_server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .expect(1)
+            .create();
+        let load = target_server
+            .mock("PUT", "/snapshot/load")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "resume_vm": true,
+                "network_overrides": [{"iface_id": "eth0", "host_dev_name": "tap-clone"}],
+            })))
+            .with_status(204)
+            .expect(1)
+            .create();
+        let _running = target_server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+
+        let mut options = CloneOptions::new(dir.path());
+        options.network_overrides = vec![NetworkOverride::new("eth0", "tap-clone")];
+        let report = clone_vm(&source, &target, options).await.unwrap();
+
+        assert_eq!(report.target.state, "Running");
+        assert_eq!(report.source_resume_error, None);
+        assert!(report.snapshot_path.starts_with(dir.path()));
+        assert!(report.snapshot_path.exists());
+        assert!(report.mem_file_path.exists());
+        assert!(report.total_time >= report.snapshot_time + report.restore_time);
+        for mock in source_mocks {
+            mock.assert();
+        }
+        load.assert();
+    }
+
+    #[tokio::test]
+    async fn test_clone_vm_keeps_the_snapshot_when_the_restore_fails() {
+        use crate::orchestration::{clone_vm, CloneOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut source_server, source) = create_test_client().await;
+        let (mut target_server, target) = create_test_client().await;
+        let source_mocks = mock_clone_source(&mut source_server);
+        let _not_started = target_server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .create();
+        let load = target_server
+            .mock("PUT", "/snapshot/load")
+            .with_status(400)
+            .with_body(r#"{"fault_message": "Cannot open tap device"}"#)
+            .expect(1)
+            .create();
+
+        let err = clone_vm(&source, &target, CloneOptions::new(dir.path()))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                crate::FirecrackerError::Restore {
+                    step: crate::snapshot::RestoreStep::Load,
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        // The target may have the memory file mapped, so both stay
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        for mock in source_mocks {
+            mock.assert();
+        }
+        load.assert();
+    }
+
+    #[tokio::test]
+    async fn test_clone_vm_reports_a_source_that_fails_to_resume() {
+        use crate::orchestration::{clone_vm, CloneOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut source_server, source) = create_test_client().await;
+        let (mut target_server, target) = create_test_client().await;
+        let mut source_mocks = mock_clone_source(&mut source_server);
+        source_mocks.pop().unwrap().remove();
+        let resume = source_server
+            .mock("PATCH", "/vm")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"state": "Resumed"}),
+            ))
+            .with_status(400)
+            .with_body(r#"{"fault_message": "Resume failed"}"#)
+            .expect(1)
+            .create();
+        let _not_started = target_server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .expect(1)
+            .create();
+        let load = target_server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .expect(1)
+            .create();
+        let _running = target_server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+
+        let report = clone_vm(&source, &target, CloneOptions::new(dir.path()))
+            .await
+            .unwrap();
+        assert!(report
+            .source_resume_error
+            .unwrap()
+            .contains("Resume failed"));
+        assert!(report.snapshot_path.exists());
+        assert!(report.mem_file_path.exists());
+        assert_eq!(report.target.state, "Running");
+        for mock in source_mocks {
+            mock.assert();
+        }
+        resume.assert();
+        load.assert();
+    }
+
+    // Launches a mock VMM per VM; `fails` picks the launches that fail.
+    // With a `tap`, the VMs' `eth0` is overridden with it and loading the
+    // snapshot only works if the override is sent.
+    struct MockVmFactory {
+        launches: std::sync::atomic::AtomicUsize,
+        fails: fn(usize) -> bool,
+        tap: Option<&'static str>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::pool::VmFactory for MockVmFactory {
+        type Handle = ServerGuard;
+
+        async fn launch(
+            &self,
+        ) -> Result<(ServerGuard, FirecrackerClient), crate::FirecrackerError> {
+            use std::sync::atomic::Ordering;
+
+            if (self.fails)(self.launches.fetch_add(1, Ordering::SeqCst)) {
+                return Err(crate::FirecrackerError::Internal(
+                    "firecracker exited".to_string(),
+                ));
+            }
+            let (mut server, client) = create_test_client().await;
+            server
+                .mock("GET", "/")
+                .with_status(200)
+                .with_body(instance_body("Paused"))
+                .create();
+            server
+                .mock("GET", "/version")
+                .with_status(200)
+                .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+                .create();
+            let load = match self.tap {
+                Some(tap) => mockito::Matcher::PartialJson(serde_json::json!({
+                    "network_overrides": [{"iface_id": "eth0", "host_dev_name": tap}],
+                })),
+                None => mockito::Matcher::Any,
+            };
+            server
+                .mock("PUT", "/snapshot/load")
+                .match_body(load)
+                .with_status(204)
+                .create();
+            server.mock("PATCH", "/vm").with_status(204).create();
+            let client = client.with_validation_mode(crate::validation::ValidationMode::Syntactic);
+            Ok((server, client))
+        }
+
+        fn network_overrides(&self, _: &ServerGuard) -> Vec<crate::snapshot::NetworkOverride> {
+            self.tap
+                .map(|tap| vec![crate::snapshot::NetworkOverride::new("eth0", tap)])
+                .unwrap_or_default()
+        }
+    }
+
+    fn pool_chain() -> crate::snapshot::SnapshotChain {
+        let mut chain = crate::snapshot::SnapshotChain::new("/tmp/chain.json");
+        chain
+            .record(&crate::snapshot::SnapshotCreateParams::new(
+                "/tmp/base.snap",
+                "/tmp/base.mem",
+            ))
+            .unwrap();
+        chain
+    }
+
+    async fn wait_for_pool<F: crate::pool::VmFactory>(
+        pool: &crate::pool::WarmPool<F>,
+        done: impl Fn(&crate::pool::PoolMetrics) -> bool,
+    ) -> crate::pool::PoolMetrics {
+        for _ in 0..500 {
+            let metrics = pool.metrics();
+            if done(&metrics) {
+                return metrics;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("pool never got there: {:?}", pool.metrics());
+    }
+
+    #[tokio::test]
+    async fn test_warm_pool_replenishes() {
+        use crate::pool::{PoolOptions, WarmPool};
+        use std::sync::atomic::Ordering;
+
+        fn shared<T: Send + Sync>() {}
+        shared::<WarmPool<MockVmFactory>>();
+
+        let pool = WarmPool::start(
+            MockVmFactory {
+                launches: Default::default(),
+                fails: |_| false,
+                tap: Some("tap-pool"),
+            },
+            pool_chain(),
+            PoolOptions {
+                size: 2,
+                ..Default::default()
+            },
+        );
+        let metrics = wait_for_pool(&pool, |m| m.ready == 2).await;
+        assert_eq!(metrics.restores_succeeded, 2);
+
+        let first = pool.checkout().await.unwrap();
+        let second = pool.checkout().await.unwrap();
+        let metrics = wait_for_pool(&pool, |m| m.ready == 2).await;
+        assert_eq!(metrics.checkouts, 2);
+        assert_eq!(metrics.restores_succeeded, 4);
+
+        // The pool is full again, so the recycled VM has no room
+        pool.recycle(first).await;
+        pool.discard(second);
+        let metrics = pool.metrics();
+        assert_eq!((metrics.recycled, metrics.discarded), (0, 2));
+
+        assert_eq!(pool.factory().launches.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_warm_pool_backs_off_after_failed_restores() {
+        use crate::instance::InstanceOperations;
+        use crate::pool::{PoolOptions, WarmPool};
+        use std::time::Duration;
+
+        let pool = WarmPool::start(
+            MockVmFactory {
+                launches: Default::default(),
+                // Only the fourth launch works
+                fails: |launch| launch != 3,
+                tap: None,
+            },
+            pool_chain(),
+            PoolOptions {
+                size: 1,
+                initial_backoff: Duration::from_millis(20),
+                max_backoff: Duration::from_millis(50),
+                ..Default::default()
+            },
+        );
+        let metrics = wait_for_pool(&pool, |m| m.restores_failed == 3).await;
+        assert_eq!(metrics.consecutive_failures, 3);
+        assert_eq!(
+            metrics.last_error.as_deref(),
+            Some("Internal error: firecracker exited")
+        );
+
+        // The checkout waits for the restore that finally works
+        let started = tokio::time::Instant::now();
+        let vm = pool.checkout().await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        vm.client().describe_instance().await.unwrap();
+        assert_eq!(pool.metrics().restores_succeeded, 1);
+
+        // With every later restore failing, the recycled VM is the only one
+        pool.recycle(vm).await;
+        let metrics = pool.metrics();
+        assert_eq!((metrics.ready, metrics.recycled), (1, 1));
+        pool.checkout().await.unwrap();
+        assert_eq!(pool.metrics().checkouts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_warm_pool_checkout_fails_with_the_restore_error() {
+        use crate::pool::{PoolOptions, WarmPool};
+        use std::time::Duration;
+
+        let factory = || MockVmFactory {
+            launches: Default::default(),
+            fails: |_| true,
+            tap: None,
+        };
+        let options = PoolOptions {
+            size: 1,
+            initial_backoff: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let pool = WarmPool::start(factory(), pool_chain(), options.clone());
+        match pool.checkout().await {
+            Err(crate::FirecrackerError::Internal(message)) => {
+                assert_eq!(message, "firecracker exited")
+            }
+            Err(e) => panic!("expected the restore error, got {:?}", e),
+            Ok(_) => panic!("expected the restore error"),
+        }
+
+        let empty = WarmPool::start(factory(), pool_chain(), PoolOptions { size: 0, ..options });
+        assert!(matches!(
+            empty.checkout().await,
+            Err(crate::FirecrackerError::Config(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_teardown_removes_in_order_and_reports_failures() {
+        use crate::orchestration::{teardown, ResourceKind, TeardownPlan};
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("vm");
+        let run = root.join("run");
+        std::fs::create_dir_all(&run).unwrap();
+        let api_sock = run.join("api.sock");
+        let _listener = std::os::unix::net::UnixListener::bind(&api_sock).unwrap();
+        let fifo = run.join("log.fifo");
+        crate::logger::create_log_fifo(&fifo, false).unwrap();
+        let snapshot = root.join("vm.snap");
+        std::fs::write(&snapshot, b"half").unwrap();
+        let outside = dir.path().join("keep.snap");
+        std::fs::write(&outside, b"keep").unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("escape")).unwrap();
+
+        // Added in the order they were created, plus some mistakes and a
+        // path added twice
+        let mut plan = TeardownPlan::new(&root);
+        plan.add(ResourceKind::Directory, &run)
+            .add(ResourceKind::SnapshotFile, &snapshot)
+            .add(ResourceKind::ApiSocket, &api_sock)
+            .add(ResourceKind::LogFifo, &fifo)
+            .add(ResourceKind::VsockSocket, run.join("vsock.sock"))
+            .add(ResourceKind::SnapshotFile, &outside)
+            .add(ResourceKind::SnapshotFile, &snapshot)
+            .add(ResourceKind::SnapshotFile, root.join("../keep.snap"))
+            .add(ResourceKind::SnapshotFile, root.join("escape/keep.snap"))
+            .add(ResourceKind::SnapshotFile, &root);
+
+        let report = teardown(plan);
+        assert_eq!(report.removed, vec![api_sock, fifo, snapshot, run]);
+        assert_eq!(report.missing, vec![root.join("run/vsock.sock")]);
+        let failures: Vec<_> = report
+            .failures
+            .iter()
+            .map(|f| (f.path.clone(), f.reason.clone()))
+            .collect();
+        let outside_root = format!("outside the teardown root {}", root.display());
+        assert_eq!(
+            failures,
+            vec![
+                (root.clone(), outside_root.clone()),
+                (root.join("escape/keep.snap"), outside_root.clone()),
+                (root.join("../keep.snap"), outside_root.clone()),
+                (outside.clone(), outside_root),
+            ]
+        );
+        assert!(!report.is_clean());
+        assert!(outside.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_teardown_keeps_going_after_a_failure() {
+        use crate::orchestration::{teardown, ResourceKind, TeardownPlan};
+
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = dir.path().join("snapshots");
+        std::fs::create_dir(&snapshots).unwrap();
+        // Not in the plan, so the directory can't be emptied
+        std::fs::write(snapshots.join("base.snap"), b"keep").unwrap();
+        let mem = snapshots.join("diff.mem");
+        std::fs::write(&mem, b"half").unwrap();
+        let not_a_socket = dir.path().join("api.sock");
+        std::fs::write(&not_a_socket, b"").unwrap();
+        let log_file = dir.path().join("firecracker.log");
+        std::fs::write(&log_file, b"kept").unwrap();
+
+        let mut plan = TeardownPlan::new(dir.path());
+        plan.add(ResourceKind::Directory, &snapshots)
+            .add(ResourceKind::ApiSocket, &not_a_socket)
+            .add(ResourceKind::LogFifo, &log_file)
+            .add(ResourceKind::SnapshotFile, &mem);
+        let report = teardown(plan);
+
+        assert_eq!(report.removed, vec![mem]);
+        assert_eq!(report.failures.len(), 3);
+        assert_eq!(report.failures[0].path, not_a_socket);
+        assert_eq!(report.failures[0].reason, "not a socket");
+        // A plain log file is never taken for a FIFO
+        assert_eq!(report.failures[1].path, log_file);
+        assert_eq!(report.failures[1].reason, "not a FIFO");
+        assert_eq!(report.failures[2].kind, ResourceKind::Directory);
+        assert!(snapshots.join("base.snap").exists());
+        assert!(log_file.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_teardown_guard_runs_on_panic() {
+        use crate::orchestration::{ResourceKind, TeardownGuard, TeardownPlan};
+
+        let dir = tempfile::tempdir().unwrap();
+        let fifo = dir.path().join("metrics.fifo");
+        let kept = dir.path().join("kept.fifo");
+
+        let result = std::panic::catch_unwind(|| {
+            let mut guard = TeardownGuard::new(TeardownPlan::new(dir.path()));
+            crate::logger::create_log_fifo(&fifo, false).unwrap();
+            guard.plan_mut().add(ResourceKind::LogFifo, &fifo);
+            panic!("boot failed");
+        });
+        assert!(result.is_err());
+        assert!(!fifo.exists());
+
+        let mut guard = TeardownGuard::new(TeardownPlan::new(dir.path()));
+        crate::logger::create_log_fifo(&kept, false).unwrap();
+        guard.plan_mut().add(ResourceKind::LogFifo, &kept);
+        let plan = guard.disarm();
+        assert!(kept.exists());
+        assert_eq!(plan.items().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_tracker_rejects_boot_time_requests_after_start() {
+        use crate::action::{ActionType, InstanceActionInfo};
+        use crate::balloon::BalloonUpdate;
+        use crate::boot::BootSourceOperations;
+        use crate::drive::DriveUpdate;
+        use crate::machine::MachineConfigOperations;
+        use crate::testing::mock::{
+            expect_action, expect_patch, expect_patch_drive, expect_put_boot_source,
+            expect_put_drive, expect_put_machine_config, json_body,
+        };
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_lifecycle_tracking(true);
+        let config = lint_clean_config();
+        let kernel = config.boot_source.unwrap();
+        let machine = config.machine_config.unwrap();
+        let drive = config.drives[0].clone();
+
+        let boot_source = expect_put_boot_source(&mut server, json_body(&kernel));
+        let machine_config = expect_put_machine_config(&mut server, json_body(&machine));
+        let put_drive = expect_put_drive(&mut server, "rootfs", json_body(&drive));
+        let start = expect_action(&mut server, ActionType::InstanceStart);
+        let patch_drive = expect_patch_drive(
+            &mut server,
+            "rootfs",
+            mockito::Matcher::PartialJson(
+                serde_json::json!({"path_on_host": "/images/rootfs-v2.ext4"}),
+            ),
+        );
+        let balloon = expect_patch(&mut server, "/balloon", json_body(&BalloonUpdate::new(256)));
+        let mmds = expect_patch(
+            &mut server,
+            "/mmds",
+            mockito::Matcher::Json(serde_json::json!({"task": "run"})),
+        );
+
+        // Before the start everything goes through
+        assert!(!client.lifecycle().unwrap().is_started());
+        client.put_boot_source(&kernel).await.unwrap();
+        client.put_machine_config(&machine).await.unwrap();
+        client.put_drive("rootfs", &drive).await.unwrap();
+        client
+            .create_sync_action(&InstanceActionInfo::from(ActionType::InstanceStart))
+            .await
+            .unwrap();
+        assert!(client.lifecycle().unwrap().is_started());
+
+        for result in [
+            client.put_boot_source(&kernel).await,
+            client.put_machine_config(&machine).await,
+            client.patch_machine_config(&machine).await,
+            client.put_drive("rootfs", &drive).await,
+        ] {
+            assert!(
+                matches!(result, Err(crate::FirecrackerError::InvalidState { .. })),
+                "{:?}",
+                result
+            );
+        }
+
+        // Runtime updates still work
+        let mut update = DriveUpdate::new("rootfs");
+        update.path_on_host = Some("/images/rootfs-v2.ext4".to_string());
+        client.patch_drive("rootfs", &update).await.unwrap();
+        client
+            .patch_balloon_config(&BalloonUpdate::new(256))
+            .await
+            .unwrap();
+        client
+            .patch_mmds(serde_json::json!({"task": "run"}))
+            .await
+            .unwrap();
+
+        boot_source.assert();
+        machine_config.assert();
+        put_drive.assert();
+        start.assert();
+        patch_drive.assert();
+        balloon.assert();
+        mmds.assert();
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_tracker_resyncs_from_describe_instance() {
+        use crate::instance::InstanceOperations;
+        use crate::machine::MachineConfigOperations;
+        use crate::validation::ValidationMode;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_lifecycle_tracking(true);
+        let machine = lint_clean_config().machine_config.unwrap();
+        let put = server
+            .mock("PUT", "/machine-config")
+            .with_status(204)
+            .expect(1)
+            .create();
+
+        // Started by another client
+        let running = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        client.describe_instance().await.unwrap();
+        assert!(matches!(
+            client.put_machine_config(&machine).await,
+            Err(crate::FirecrackerError::InvalidState { .. })
+        ));
+        running.remove();
+
+        // A fresh VMM behind the same address
+        let _not_started = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Not started"))
+            .create();
+        client.describe_instance().await.unwrap();
+        client.put_machine_config(&machine).await.unwrap();
+        put.assert();
+    }
+
+    #[tokio::test]
+    async fn test_state_changes_update_the_last_known_state() {
+        use crate::action::{ActionType, InstanceActionInfo};
+        use crate::instance::InstanceOperations;
+        use crate::snapshot::{SnapshotLoadParams, SnapshotOperations};
+        use crate::validation::ValidationMode;
+        use crate::vm::VmOperations;
+        use crate::InstanceState;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_lifecycle_tracking(true);
+        let _actions = server.mock("PUT", "/actions").with_status(204).create();
+        let vm = server.mock("PATCH", "/vm").with_status(204).create();
+        let state = || client.last_known_state().map(|(state, _)| state);
+
+        assert_eq!(state(), None);
+        client
+            .create_sync_action(&InstanceActionInfo::from(ActionType::InstanceStart))
+            .await
+            .unwrap();
+        assert_eq!(state(), Some(InstanceState::Running));
+        assert!(client.lifecycle().unwrap().is_started());
+        client.pause_vm().await.unwrap();
+        assert_eq!(state(), Some(InstanceState::Paused));
+        client.resume_vm().await.unwrap();
+        assert_eq!(state(), Some(InstanceState::Running));
+
+        // What `GET /` reported besides the state is kept
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        client.describe_instance().await.unwrap();
+        client.pause_vm().await.unwrap();
+        let (info, _) = client.last_instance_info().unwrap();
+        assert_eq!(info.state, "Paused");
+        assert_eq!(info.id, "vm-1");
+
+        // A failed request leaves the state alone
+        vm.remove();
+        server
+            .mock("PATCH", "/vm")
+            .with_status(400)
+            .with_body(r#"{"fault_message": "busy"}"#)
+            .create();
+        assert!(client.resume_vm().await.is_err());
+        assert_eq!(state(), Some(InstanceState::Paused));
+
+        // A loaded VM is paused unless it was told to resume
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_lifecycle_tracking(true);
+        let _load = server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .create();
+        let mut params = SnapshotLoadParams::with_mem_file("/tmp/vm.snap", "/tmp/vm.mem");
+        client.load_snapshot(&params).await.unwrap();
+        assert_eq!(client.last_known_state().unwrap().0, InstanceState::Paused);
+        assert!(client.lifecycle().unwrap().is_started());
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let _load = server
+            .mock("PUT", "/snapshot/load")
+            .with_status(204)
+            .create();
+        params.resume_vm = Some(true);
+        client.load_snapshot(&params).await.unwrap();
+        assert_eq!(client.last_known_state().unwrap().0, InstanceState::Running);
+    }
+
+    #[tokio::test]
+    async fn test_manager_broadcast_reports_each_vm() {
+        use crate::action::ActionType;
+        use crate::manager::{FirecrackerManager, Selector};
+        use crate::testing::fixtures::NOT_SUPPORTED_BEFORE_START;
+        use crate::testing::mock::{expect_action, expect_fault, expect_vm_state};
+        use crate::VmState;
+
+        let (mut healthy, healthy_client) = create_test_client().await;
+        let (mut failing, failing_client) = create_test_client().await;
+        let paused = expect_vm_state(&mut healthy, VmState::Paused);
+        let refused = expect_fault(&mut failing, "PATCH", "/vm", NOT_SUPPORTED_BEFORE_START);
+        let flushed = expect_action(&mut healthy, ActionType::FlushMetrics);
+
+        let manager = FirecrackerManager::new().with_concurrency(1);
+        manager.register("vm-b", failing_client);
+        manager.register("vm-a", healthy_client);
+        assert_eq!(manager.ids(), ["vm-a", "vm-b"]);
+
+        let report = manager.pause_all(Selector::All).await;
+        assert!(!report.is_success());
+        assert_eq!(report.succeeded, [("vm-a".to_string(), ())]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "vm-b");
+        assert!(matches!(
+            report.failed[0].1,
+            crate::FirecrackerError::Api {
+                status_code: 400,
+                ..
+            }
+        ));
+        assert!(report.unknown.is_empty());
+
+        // Unknown ids are skipped and reported
+        let selector: Selector = ["vm-a", "vm-z"].into_iter().collect();
+        let report = manager.flush_metrics_all(selector).await;
+        assert_eq!(report.succeeded, [("vm-a".to_string(), ())]);
+        assert!(report.failed.is_empty());
+        assert_eq!(report.unknown, ["vm-z"]);
+
+        // Any operation can be broadcast, with its results collected per VM
+        manager.unregister("vm-b");
+        let report = manager
+            .broadcast(
+                Selector::All,
+                |client| async move { Ok(client.url("")?.port()) },
+            )
+            .await;
+        assert!(report.is_success());
+        assert_eq!(report.succeeded[0].1, Some(healthy.socket_address().port()));
+
+        paused.assert();
+        refused.assert();
+        flushed.assert();
+    }
+
+    #[tokio::test]
+    async fn test_audit_journal_records_every_request() {
+        use crate::audit::{read_journal, AuditPolicy, BodyRecording, BodySummary, JsonlAuditLog};
+        use crate::instance::InstanceOperations;
+        use crate::machine::MachineConfigOperations;
+        use crate::mmds::MmdsOperations;
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("audit.jsonl");
+        let log = Arc::new(JsonlAuditLog::open(&journal).unwrap());
+        let (mut server, client) = create_test_client().await;
+        let policy = AuditPolicy::default().with_rule("drives/", BodyRecording::Omit);
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_audit_log_policy(log.clone(), policy);
+        let _machine = server
+            .mock("PUT", "/machine-config")
+            .with_status(204)
+            .create();
+        let _mmds = server.mock("PUT", "/mmds").with_status(204).create();
+        let _drive = server
+            .mock("PUT", "/drives/rootfs")
+            .with_status(400)
+            .with_body(r#"{"fault_message": "bad drive"}"#)
+            .create();
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+
+        let config = crate::MachineConfig {
+            vcpu_count: Some(2),
+            mem_size_mib: Some(512),
+            ..Default::default()
+        };
+        client.put_machine_config(&config).await.unwrap();
+        let secret = serde_json::json!({"user-data": "password=hunter2"});
+        client.put_mmds(secret.clone()).await.unwrap();
+        let drive = lint_clean_config().drives.remove(0);
+        assert!(client.put_drive("rootfs", &drive).await.is_err());
+        client.describe_instance().await.unwrap();
+        log.flush();
+        assert_eq!(log.last_error(), None);
+
+        let contents = std::fs::read_to_string(&journal).unwrap();
+        assert!(!contents.contains("hunter2"));
+        let records = read_journal(&journal).unwrap();
+        let requests: Vec<_> = records
+            .iter()
+            .map(|r| (r.method.as_str(), r.path.as_str(), r.status))
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                ("PUT", "machine-config", Some(204)),
+                ("PUT", "mmds", Some(204)),
+                ("PUT", "drives/rootfs", Some(400)),
+                ("GET", "", Some(200)),
+            ]
+        );
+        assert!(records.iter().all(|r| r.vm == server.url()));
+        assert!(records
+            .windows(2)
+            .all(|w| w[0].timestamp_ms <= w[1].timestamp_ms));
+
+        // The same body always has the same digest, and omitted bodies keep
+        // only their length
+        let machine_body = serde_json::to_vec(&config).unwrap();
+        match &records[0].body {
+            Some(BodySummary::Digest { len, sha256 }) => {
+                assert_eq!(*len, machine_body.len());
+                assert_eq!(sha256.len(), 64);
+                assert_eq!(
+                    *sha256,
+                    match AuditPolicy::default().summarize("machine-config", &machine_body) {
+                        BodySummary::Digest { sha256, .. } => sha256,
+                        other => panic!("expected a digest, got {:?}", other),
+                    }
+                );
+            }
+            other => panic!("expected a digest, got {:?}", other),
+        }
+        let mmds_len = serde_json::to_vec(&secret).unwrap().len();
+        assert_eq!(
+            records[1].body,
+            Some(BodySummary::Omitted { len: mmds_len })
+        );
+        assert!(matches!(records[2].body, Some(BodySummary::Omitted { .. })));
+        assert_eq!(records[3].body, None);
+    }
+
+    #[tokio::test]
+    async fn test_status_report_gathers_every_part() {
+        use crate::status::{StatusOperations, StatusOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let metrics_path = dir.path().join("metrics.json");
+        let metrics = crate::testing::fixtures::METRICS_V1_7.trim_end();
+        std::fs::write(&metrics_path, format!("{}\n{}\n", metrics, metrics)).unwrap();
+
+        let (mut server, client) = create_test_client().await;
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::INSTANCE_INFO_V1_7)
+            .create();
+        let _machine = server
+            .mock("GET", "/machine-config")
+            .with_status(200)
+            .with_body(r#"{"vcpu_count": 2, "mem_size_mib": 1024}"#)
+            .create();
+        let _balloon = server
+            .mock("GET", "/balloon")
+            .with_status(200)
+            .with_body(
+                r#"{"amount_mib": 256, "deflate_on_oom": true, "stats_polling_interval_s": 1}"#,
+            )
+            .create();
+        let _stats = server
+            .mock("GET", "/balloon/statistics")
+            .with_status(200)
+            .with_body(crate::testing::fixtures::BALLOON_STATS_V1_7)
+            .create();
+
+        let report = client
+            .status_report(StatusOptions {
+                metrics_path: Some(metrics_path),
+            })
+            .await
+            .unwrap();
+        assert!(report.unavailable.is_empty());
+        assert_eq!(report.machine_config.unwrap().mem_size_mib, Some(1024));
+        assert_eq!(report.balloon.unwrap().amount_mib, 256);
+        assert!(report.metrics.unwrap().utc_timestamp_ms > 0);
+        // (1032900608 - 748826624) / 1032900608 and 128 / 1024
+        assert_eq!(report.memory_used_percent, Some(27.5));
+        assert_eq!(report.balloon_percent, Some(12.5));
+    }
+
+    #[tokio::test]
+    async fn test_status_report_leaves_out_missing_parts() {
+        use crate::status::{StatusOperations, StatusOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (mut server, client) = create_test_client().await;
+        let _instance = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        let _machine = server
+            .mock("GET", "/machine-config")
+            .with_status(200)
+            .with_body(r#"{"vcpu_count": 2, "mem_size_mib": 1024}"#)
+            .create();
+        let _balloon = server
+            .mock("GET", mockito::Matcher::Regex("^/balloon".to_string()))
+            .with_status(400)
+            .with_body(r#"{"fault_message": "No balloon device found."}"#)
+            .create();
+
+        let report = client
+            .status_report(StatusOptions {
+                metrics_path: Some(dir.path().join("absent.json")),
+            })
+            .await
+            .unwrap();
+        assert_eq!(report.instance.state, "Running");
+        assert!(report.machine_config.is_some());
+        assert_eq!(report.balloon, None);
+        assert_eq!(report.balloon_stats, None);
+        assert_eq!(report.metrics, None);
+        assert_eq!(report.memory_used_percent, None);
+        assert_eq!(
+            report.unavailable.keys().collect::<Vec<_>>(),
+            ["balloon", "balloon_stats", "metrics"]
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["balloon"], Value::Null);
+        assert!(json["unavailable"]["balloon"]
+            .as_str()
+            .unwrap()
+            .contains("No balloon device found"));
+
+        // Without an instance there is no report
+        server.reset();
+        assert!(client
+            .status_report(StatusOptions::default())
+            .await
+            .is_err());
+    }
+
+    fn alert_metrics(
+        at_ms: u64,
+        execute_fails: u64,
+        rx_fails: u64,
+    ) -> crate::metrics::FirecrackerMetrics {
+        crate::metrics::FirecrackerMetrics::from_json_str(&format!(
+            r#"{{"utc_timestamp_ms": {}, "block": {{"execute_fails": {}}}, "net": {{"rx_fails": {}}}}}"#,
+            at_ms, execute_fails, rx_fails
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_alert_engine_debounces_firing_and_resolution() {
+        use crate::alert::{AlertEngine, AlertRule, AlertState, Comparison};
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let engine = AlertEngine::new();
+        engine.add_rule(
+            AlertRule::new("rx-fails", "net.rx_fails", Comparison::Above, 5.0)
+                .on_delta()
+                .for_duration(Duration::from_secs(2))
+                .resolve_after(Duration::from_secs(2)),
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        engine.on_event(move |event| sink.lock().unwrap().push(event.clone()));
+        let mut events = engine.subscribe();
+
+        // Firecracker writes the failures since the previous flush
+        let sequence = [0, 10, 10, 10, 1, 9, 1, 1, 1];
+        let mut transitions = Vec::new();
+        for (i, rx_fails) in sequence.into_iter().enumerate() {
+            for event in engine.observe(&alert_metrics(i as u64 * 1000, 0, rx_fails)) {
+                transitions.push((event.at_ms, event.state, event.value));
+            }
+        }
+        // Fires once the growth has stayed high for two seconds, rides out the
+        // short dip at 4000 and resolves two seconds after it last dropped
+        assert_eq!(
+            transitions,
+            [
+                (3000, AlertState::Firing, 10.0),
+                (8000, AlertState::Resolved, 1.0)
+            ]
+        );
+        assert_eq!(seen.lock().unwrap().len(), 2);
+        assert_eq!(events.recv().await.unwrap().state, AlertState::Firing);
+        assert_eq!(events.recv().await.unwrap().state, AlertState::Resolved);
+        assert!(engine.firing().is_empty());
+    }
+
+    #[test]
+    fn test_alert_engine_delta_uses_flushed_counts() {
+        use crate::alert::{AlertEngine, AlertRule, AlertState, Comparison};
+        use crate::testing::fixtures;
+
+        let engine = AlertEngine::new();
+        engine.add_rule(
+            AlertRule::new("reads", "block.read_bytes", Comparison::AtLeast, 1048576.0).on_delta(),
+        );
+        engine.add_rule(AlertRule::new(
+            "startup",
+            "api_server.process_startup_time_us",
+            Comparison::Above,
+            0.0,
+        ));
+        engine.add_rule(
+            AlertRule::new(
+                "startup-delta",
+                "api_server.process_startup_time_us",
+                Comparison::Above,
+                0.0,
+            )
+            .on_delta(),
+        );
+
+        // The first flush already carries counts to evaluate
+        let first = engine.observe(&fixtures::metrics_v1_7());
+        let mut fired: Vec<_> = first.iter().map(|e| (e.rule.as_str(), e.value)).collect();
+        fired.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(fired, [("reads", 3145728.0), ("startup", 8455.0)]);
+
+        // The next flush read less than the first; that's its own count, not
+        // a counter reset
+        let next = engine.observe(&fixtures::metrics_v1_7_next());
+        assert!(next.is_empty(), "{:?}", next);
+        let mut firing = engine.firing();
+        firing.sort();
+        assert_eq!(firing, ["reads", "startup"]);
+
+        let mut quiet = fixtures::metrics_v1_7_next();
+        quiet.utc_timestamp_ms += 60_000;
+        quiet.block.read_bytes = 0;
+        let resolved = engine.observe(&quiet);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(
+            (resolved[0].rule.as_str(), resolved[0].state),
+            ("reads", AlertState::Resolved)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alert_engine_rules_change_at_runtime() {
+        use crate::alert::{AlertEngine, AlertRule, AlertState, Comparison};
+
+        let engine = AlertEngine::new();
+        let rule = AlertRule::new(
+            "block-errors",
+            "block.execute_fails",
+            Comparison::AtLeast,
+            1.0,
+        );
+        engine.add_rule(rule.clone());
+
+        assert!(engine.observe(&alert_metrics(0, 0, 0)).is_empty());
+        let fired = engine.observe(&alert_metrics(1000, 2, 0));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].rule, "block-errors");
+        assert_eq!(fired[0].state, AlertState::Firing);
+        assert_eq!(engine.firing(), ["block-errors"]);
+        // Still true, so nothing new
+        assert!(engine.observe(&alert_metrics(2000, 2, 0)).is_empty());
+
+        assert_eq!(engine.remove_rule("block-errors"), Some(rule.clone()));
+        assert!(engine.firing().is_empty());
+        assert!(engine.observe(&alert_metrics(3000, 3, 0)).is_empty());
+
+        // A rule added back starts out clear, and documents older than the
+        // last one are ignored
+        engine.add_rule(rule);
+        assert!(engine.firing().is_empty());
+        assert!(engine.observe(&alert_metrics(2500, 3, 0)).is_empty());
+        assert_eq!(engine.rules().len(), 1);
+
+        let documents = vec![
+            Ok(alert_metrics(4000, 3, 0)),
+            Err(crate::FirecrackerError::Internal("garbled".to_string())),
+            Ok(alert_metrics(5000, 0, 0)),
+        ];
+        let mut events = engine.subscribe();
+        engine.run(futures_util::stream::iter(documents)).await;
+        assert_eq!(events.recv().await.unwrap().state, AlertState::Firing);
+        let resolved = events.recv().await.unwrap();
+        assert_eq!(
+            (resolved.state, resolved.at_ms),
+            (AlertState::Resolved, 5000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_provider_refreshes_once_on_401() {
+        use crate::auth::{StaticToken, TokenError, TokenProvider};
+        use crate::instance::InstanceOperations;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Default)]
+        struct CountingProvider {
+            tokens: AtomicUsize,
+            refreshes: AtomicUsize,
+        }
+
+        #[async_trait::async_trait]
+        impl TokenProvider for CountingProvider {
+            async fn token(&self) -> Result<String, TokenError> {
+                self.tokens.fetch_add(1, Ordering::SeqCst);
+                Ok(format!("token-{}", self.refreshes.load(Ordering::SeqCst)))
+            }
+
+            async fn refresh(&self) -> Result<String, TokenError> {
+                let refreshes = self.refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(format!("token-{}", refreshes))
+            }
+        }
+
+        let (mut server, client) = create_test_client().await;
+        let provider = Arc::new(CountingProvider::default());
+        let client = client.with_token_provider(provider.clone());
+        let expired = server
+            .mock("GET", "/")
+            .match_header("authorization", "Bearer token-0")
+            .with_status(401)
+            .expect(1)
+            .create();
+        let accepted = server
+            .mock("GET", "/")
+            .match_header("authorization", "Bearer token-1")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .expect(2)
+            .create();
+
+        // The rejected token is refreshed and the request retried
+        assert_eq!(client.describe_instance().await.unwrap().state, "Running");
+        assert_eq!(provider.tokens.load(Ordering::SeqCst), 1);
+        assert_eq!(provider.refreshes.load(Ordering::SeqCst), 1);
+        // The next request asks the provider again and gets the new token
+        client.describe_instance().await.unwrap();
+        assert_eq!(provider.tokens.load(Ordering::SeqCst), 2);
+        assert_eq!(provider.refreshes.load(Ordering::SeqCst), 1);
+        expired.assert();
+        accepted.assert();
+
+        // A second 401 is returned rather than retried again
+        let refused = server
+            .mock("PUT", "/actions")
+            .with_status(401)
+            .with_body("token revoked")
+            .expect(2)
+            .create();
+        match client.flush_metrics().await {
+            Err(crate::FirecrackerError::Api {
+                status_code: 401,
+                message,
+            }) => assert_eq!(message, "token revoked"),
+            other => panic!("expected a 401, got {:?}", other),
+        }
+        assert_eq!(provider.refreshes.load(Ordering::SeqCst), 2);
+        refused.assert();
+
+        // Provider failures don't reach the VMM
+        struct Unavailable;
+
+        #[async_trait::async_trait]
+        impl TokenProvider for Unavailable {
+            async fn token(&self) -> Result<String, TokenError> {
+                Err("identity service unavailable".into())
+            }
+        }
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_token_provider(Arc::new(Unavailable));
+        let untouched = server.mock("GET", "/").expect(0).create();
+        match client.describe_instance().await {
+            Err(crate::FirecrackerError::TokenProvider(err)) => {
+                assert_eq!(err.to_string(), "identity service unavailable")
+            }
+            other => panic!("expected a token provider error, got {:?}", other),
+        }
+        untouched.assert();
+
+        let client = client.with_token_provider(Arc::new(StaticToken("secret".to_string())));
+        let authorized = server
+            .mock("GET", "/")
+            .match_header("authorization", "Bearer secret")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        client.describe_instance().await.unwrap();
+        authorized.assert();
+    }
+
+    #[tokio::test]
+    async fn test_connect_retry_waits_for_late_server() {
+        use crate::instance::InstanceOperations;
+        use std::time::{Duration, Instant};
+
+        // A port nothing listens on until the server starts below
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = FirecrackerClient::new(&format!("http://127.0.0.1:{}", port))
+            .await
+            .unwrap()
+            .with_connect_retry(Duration::from_secs(5), Duration::from_millis(50));
+
+        let late_server = async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let mut server = Server::new_with_opts_async(mockito::ServerOpts {
+                port,
+                ..Default::default()
+            })
+            .await;
+            let mock = server
+                .mock("GET", "/")
+                .with_status(200)
+                .with_body(instance_body("Not started"))
+                .expect(1)
+                .create();
+            (server, mock)
+        };
+        let (info, (mut server, mock)) = tokio::join!(client.describe_instance(), late_server);
+        assert_eq!(info.unwrap().state, "Not started");
+        mock.assert();
+
+        // HTTP errors are returned without retrying
+        let unavailable = server
+            .mock("PUT", "/actions")
+            .with_status(503)
+            .expect(1)
+            .create();
+        match client.flush_metrics().await {
+            Err(crate::FirecrackerError::Api {
+                status_code: 503, ..
+            }) => {}
+            other => panic!("expected a 503, got {:?}", other),
+        }
+        unavailable.assert();
+
+        // Once the window has passed, the connection error is returned
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = FirecrackerClient::new(&format!("http://127.0.0.1:{}", port))
+            .await
+            .unwrap()
+            .with_connect_retry(Duration::from_millis(200), Duration::from_millis(50));
+        let started = Instant::now();
+        match client.describe_instance().await {
+            Err(crate::FirecrackerError::HttpClient(err)) => assert!(err.is_connect()),
+            other => panic!("expected a connection error, got {:?}", other),
+        }
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+        // Other connection errors aren't retried
+        let client = FirecrackerClient::new("http://firecracker.invalid")
+            .await
+            .unwrap()
+            .with_connect_retry(Duration::from_secs(10), Duration::from_millis(50));
+        let started = Instant::now();
+        match client.describe_instance().await {
+            Err(crate::FirecrackerError::HttpClient(err)) => assert!(err.is_connect()),
+            other => panic!("expected a connection error, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_validate_boot_args() {
+        use crate::validation::{validate_boot_args, MAX_BOOT_ARGS_LEN};
+
+        let longest = "a".repeat(MAX_BOOT_ARGS_LEN);
+        let too_long = "a".repeat(MAX_BOOT_ARGS_LEN + 1);
+        for (args, expected) in [
+            ("", None),
+            ("console=ttyS0 reboot=k panic=1 pci=off", None),
+            (
+                "init=/sbin/init quiet\tloglevel=3",
+                Some("invalid_boot_args"),
+            ),
+            ("console=ttyS0 reboot=k\n", Some("invalid_boot_args")),
+            ("console=ttyS0\0 panic=1", Some("invalid_boot_args")),
+            ("console=ttyS0\r", Some("invalid_boot_args")),
+            ("dyndbg=\"file ü.c +p\"", None),
+            (&longest, None),
+            (&too_long, Some("boot_args_too_long")),
+        ] {
+            let result = validate_boot_args(args);
+            assert_eq!(
+                result.as_ref().err().map(|e| e.code.as_ref()),
+                expected,
+                "{:?}: {:?}",
+                args,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_vm_config_lint_boot_args() {
+        use crate::validation::Severity;
+
+        for (args, root_drive, expected) in [
+            ("console=ttyS0", true, vec![]),
+            ("console=ttyS0 root=/dev/vda", false, vec![]),
+            (
+                "console=ttyS0 root=/dev/vda",
+                true,
+                vec!["boot_args_root_conflict"],
+            ),
+            // Only a whole `root=` clause counts
+            ("console=ttyS0 noroot=1 nfsroot=/srv", true, vec![]),
+            ("dyndbg=\"file x.c +p\"", true, vec![]),
+            (
+                "dyndbg=\"file x.c +p",
+                true,
+                vec!["boot_args_unbalanced_quotes"],
+            ),
+            (
+                "root=/dev/vdb dyndbg=\"",
+                true,
+                vec!["boot_args_unbalanced_quotes", "boot_args_root_conflict"],
+            ),
+        ] {
+            let mut config = lint_clean_config();
+            config.boot_source.as_mut().unwrap().boot_args = Some(args.to_string());
+            if !root_drive {
+                config.drives[0].is_root_device = false;
+                config.boot_source.as_mut().unwrap().initrd_path =
+                    Some("/images/initrd".to_string());
+            }
+            let report = crate::validation::validate_vm_config(&config);
+            assert!(report.warnings().all(|f| f.path == "boot_source.boot_args"));
+            assert!(!report.has_errors(), "{}", report);
+            let rules: Vec<_> = report.findings.iter().map(|f| f.rule).collect();
+            assert_eq!(rules, expected, "{:?}", args);
+            assert!(report
+                .findings
+                .iter()
+                .all(|f| f.severity == Severity::Warning));
+        }
+    }
+
+    #[test]
+    fn test_vm_config_lint_root_read_only_mismatch() {
+        for (args, read_only, expected) in [
+            ("console=ttyS0", true, false),
+            ("console=ttyS0 ro", true, false),
+            ("console=ttyS0 rw", false, false),
+            ("console=ttyS0 rw", true, true),
+            ("console=ttyS0 ro", false, true),
+            // The kernel goes by the last one
+            ("ro console=ttyS0 rw", true, true),
+            ("rw console=ttyS0 ro", true, false),
+            // Only whole words count
+            ("console=ttyS0 rootflags=rw", true, false),
+        ] {
+            let mut config = lint_clean_config();
+            config.boot_source.as_mut().unwrap().boot_args = Some(args.to_string());
+            config.drives[0].is_read_only = read_only;
+            let expected = if expected {
+                vec![(
+                    "root_read_only_mismatch",
+                    "drives[0].is_read_only".to_string(),
+                )]
+            } else {
+                vec![]
+            };
+            assert_eq!(lint_rules(&config), expected, "{:?} {}", args, read_only);
+        }
+    }
+
+    #[test]
+    fn test_validate_host_support() {
+        use crate::validation::validate_host_support;
+
+        let mut config = lint_clean_config();
+        config.drives[1].io_engine = Some("Async".to_string());
+        for (release, warned) in [
+            ("5.10.51", false),
+            ("6.1.0-18-amd64", false),
+            ("5.10.186-179.751.amzn2.x86_64", false),
+            ("5.10.50", true),
+            ("4.14.336-257.566.amzn2.x86_64", true),
+            ("5.4", true),
+            ("unknown", false),
+        ] {
+            let report = validate_host_support(&config, release);
+            let rules: Vec<_> = report
+                .findings
+                .iter()
+                .map(|f| (f.rule, f.path.as_str()))
+                .collect();
+            let expected = if warned {
+                vec![("async_io_engine_old_kernel", "drives[1].io_engine")]
+            } else {
+                vec![]
+            };
+            assert_eq!(rules, expected, "{}", release);
+        }
+        config.drives[1].io_engine = Some("Sync".to_string());
+        assert!(validate_host_support(&config, "4.14.0").findings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_put_calls_warn_about_what_they_contradict() {
+        use crate::balloon::BalloonOperations;
+        use crate::boot::BootSourceOperations;
+        use crate::machine::MachineConfigOperations;
+        use crate::testing::FakeVmm;
+        use crate::validation::ValidationMode;
+        use crate::{Balloon, BootSource, Drive, DriveOperations, MachineConfig};
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        let rules = |client: &FirecrackerClient| -> Vec<_> {
+            client.take_warnings().iter().map(|w| w.rule).collect()
+        };
+
+        let boot_source = BootSource {
+            boot_args: Some("console=ttyS0 rw".to_string()),
+            ..BootSource::new("/images/vmlinux")
+        };
+        client.put_boot_source(&boot_source).await.unwrap();
+        assert!(rules(&client).is_empty());
+        let rootfs = Drive {
+            is_root_device: true,
+            is_read_only: true,
+            ..Drive::new("rootfs", "/images/rootfs.ext4")
+        };
+        client.put_drive("rootfs", &rootfs).await.unwrap();
+        assert_eq!(rules(&client), ["root_read_only_mismatch"]);
+        // Reported once, by the call that brought it about
+        let data = Drive::new("data", "/images/data.ext4");
+        client.put_drive("data", &data).await.unwrap();
+        assert!(rules(&client).is_empty());
+
+        client
+            .put_machine_config(&MachineConfig::new(1, 256))
+            .await
+            .unwrap();
+        let balloon = Balloon {
+            amount_mib: 240,
+            deflate_on_oom: true,
+            stats_polling_interval_s: None,
+        };
+        client.put_balloon_config(&balloon).await.unwrap();
+        assert_eq!(rules(&client), ["balloon_near_memory"]);
+
+        // A failed call warns about nothing
+        fake.fail_next("PUT", "/balloon", "busy");
+        let balloon = Balloon {
+            amount_mib: 250,
+            ..balloon
+        };
+        client.put_balloon_config(&balloon).await.unwrap_err();
+        assert!(rules(&client).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_put_boot_source_rejects_control_characters() {
+        use crate::boot::BootSourceOperations;
+        use crate::testing::mock::expect_put_boot_source;
+        use crate::validation::ValidationMode;
+        use crate::BootSource;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let mut boot_source = BootSource::new("/images/vmlinux");
+        boot_source.boot_args = Some("console=ttyS0 reboot=k\n".to_string());
+        let untouched = server.mock("PUT", "/boot-source").expect(0).create();
+        match client.put_boot_source(&boot_source).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                assert!(errors.field_errors().contains_key("boot_args"))
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        untouched.assert();
+
+        // With validation off the arguments are sent as given
+        let client = client.with_validation_mode(ValidationMode::Off);
+        let sent = expect_put_boot_source(
+            &mut server,
+            mockito::Matcher::PartialJson(serde_json::json!({
+                "boot_args": "console=ttyS0 reboot=k\n"
+            })),
+        );
+        client.put_boot_source(&boot_source).await.unwrap();
+        sent.assert();
+    }
+
+    #[tokio::test]
+    async fn test_redaction_keeps_mmds_out_of_errors_and_journal() {
+        use crate::audit::JsonlAuditLog;
+        use crate::mmds::MmdsOperations;
+        use crate::redaction::Redaction;
+        use crate::testing::fixtures::fault_body;
+        use serde_json::json;
+        use std::sync::Arc;
+
+        const SECRET: &str = "hunter2-cloud-init-password";
+        let data = json!({"latest": {"user-data": format!("password: {}", SECRET)}});
+        // Firecracker quotes what it couldn't parse back in its fault message
+        let echo = fault_body(&format!("Invalid MMDS data: {}", data));
+
+        let dir = tempfile::tempdir().unwrap();
+        let journal = dir.path().join("audit.jsonl");
+        let (mut server, client) = create_test_client().await;
+        let log = Arc::new(JsonlAuditLog::open(&journal).unwrap());
+        let client = client.with_audit_log(log.clone());
+        server
+            .mock("PUT", "/mmds")
+            .with_status(400)
+            .with_body(&echo)
+            .create();
+        server
+            .mock("PATCH", "/mmds")
+            .with_status(400)
+            .with_body(&echo)
+            .create();
+        server
+            .mock("GET", "/mmds")
+            .with_status(500)
+            .with_body(&echo)
+            .create();
+
+        let errors = [
+            client.put_mmds(data.clone()).await.unwrap_err(),
+            client.patch_mmds(data.clone()).await.unwrap_err(),
+            client.get_mmds().await.unwrap_err(),
+        ];
+        for err in &errors {
+            let shown = format!("{} {:?}", err, err);
+            assert!(!shown.contains(SECRET), "{}", shown);
+            assert!(shown.contains("<redacted: "), "{}", shown);
+        }
+        log.flush();
+        let recorded = std::fs::read_to_string(&journal).unwrap();
+        assert_eq!(recorded.lines().count(), 3);
+        assert!(!recorded.contains(SECRET), "{}", recorded);
+
+        // Other paths, and MMDS with redaction off, keep the fault as sent
+        server
+            .mock("PUT", "/mmds/config")
+            .with_status(400)
+            .with_body(&echo)
+            .create();
+        let config = crate::MmdsConfig::new(vec!["eth0".to_string()]);
+        match client.put_mmds_config(&config).await {
+            Err(crate::FirecrackerError::Api { message, .. }) => assert_eq!(message, echo),
+            other => panic!("expected an API error, got {:?}", other),
+        }
+        let client = client.with_redaction(Redaction::none());
+        match client.put_mmds(data).await {
+            Err(crate::FirecrackerError::Api { message, .. }) => assert_eq!(message, echo),
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redaction_applies_to_planned_requests() {
+        use crate::config::ConfigOperations;
+        use crate::redaction::Redaction;
+        use crate::validation::ValidationMode;
+
+        let mut config = crate::testing::fixtures::bootable_config();
+        config.boot_source.as_mut().unwrap().boot_args = Some("api_key=s3cr3t".to_string());
+        let (_server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+
+        let plan = client.plan_config(&config).unwrap();
+        assert!(serde_json::to_string(&plan).unwrap().contains("s3cr3t"));
+
+        let client = client.with_redaction(Redaction::default().with_path("boot-source"));
+        let plan = client.plan_config(&config).unwrap();
+        assert!(!serde_json::to_string(&plan).unwrap().contains("s3cr3t"));
+        let boot_source = plan.iter().find(|r| r.path == "boot-source").unwrap();
+        assert!(boot_source
+            .body
+            .as_str()
+            .unwrap()
+            .starts_with("<redacted: "));
+        // Other sections are planned as usual
+        let drive = plan.iter().find(|r| r.path == "drives/rootfs").unwrap();
+        assert_eq!(drive.body["is_root_device"], true);
+    }
+
+    #[test]
+    fn test_redaction_paths() {
+        use crate::redaction::Redaction;
+
+        let redaction = Redaction::default().with_path("drives/");
+        for (path, sensitive) in [
+            ("mmds", true),
+            ("/mmds", true),
+            ("mmds/config", false),
+            ("drives/rootfs", true),
+            ("drives", false),
+            ("boot-source", false),
+        ] {
+            assert_eq!(redaction.is_sensitive(path), sensitive, "{}", path);
+        }
+        assert!(!Redaction::none().is_sensitive("mmds"));
+        let shown = redaction.apply("mmds", r#"{"token":"t"}"#);
+        assert_eq!(shown, "<redacted: 13 bytes>");
+        assert_eq!(redaction.apply("drives", "{}"), "{}");
+    }
+
+    #[tokio::test]
+    async fn test_last_known_state_follows_observations() {
+        use crate::instance::{watch_instance, InstanceOperations};
+        use crate::testing::mock::expect_instance_state_sequence;
+        use crate::InstanceState;
+        use futures_util::StreamExt;
+        use std::time::{Duration, Instant};
+
+        let (mut server, client) = create_test_client().await;
+        assert_eq!(client.last_known_state(), None);
+
+        let states =
+            expect_instance_state_sequence(&mut server, &["Not started", "Running", "Paused"]);
+        let before = Instant::now();
+        client.describe_instance().await.unwrap();
+        let (state, first_seen) = client.last_known_state().unwrap();
+        assert_eq!(state, InstanceState::NotStarted);
+        assert!(first_seen >= before);
+        // Reading the cache doesn't ask the VMM again
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(client.last_known_state().unwrap().1, first_seen);
+
+        assert_eq!(
+            client.refresh_state().await.unwrap(),
+            InstanceState::Running
+        );
+        let (state, refreshed) = client.last_known_state().unwrap();
+        assert_eq!(state, InstanceState::Running);
+        assert!(refreshed >= first_seen + Duration::from_millis(20));
+
+        // A background reader's polls land in the same cache
+        let reader = client.reader();
+        let mut watch = Box::pin(watch_instance(&reader, Duration::from_millis(10)));
+        assert_eq!(watch.next().await.unwrap().unwrap().state, "Paused");
+        let (state, watched) = client.last_known_state().unwrap();
+        assert_eq!(state, InstanceState::Paused);
+        assert!(watched >= refreshed);
+        assert_eq!(
+            client.last_instance_info().unwrap().0.id,
+            "anonymous-instance"
+        );
+        states.assert();
+
+        // A tracker attached later starts from the cached state
+        let client = client.with_lifecycle_tracking(true);
+        assert!(client.lifecycle().unwrap().is_started());
+
+        // Failed polls keep the last state
+        let (mut server, client) = create_test_client().await;
+        let running = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_body(instance_body("Running"))
+            .create();
+        client.describe_instance().await.unwrap();
+        running.remove();
+        server.mock("GET", "/").with_status(500).create();
+        assert!(client.refresh_state().await.is_err());
+        assert_eq!(client.last_known_state().unwrap().0, InstanceState::Running);
+    }
+
+    #[test]
+    fn test_instance_state_parsing() {
+        use crate::InstanceState;
+
+        for (raw, state) in [
+            ("Not started", InstanceState::NotStarted),
+            ("Running", InstanceState::Running),
+            ("Paused", InstanceState::Paused),
+            ("Halted", InstanceState::Other("Halted".to_string())),
+        ] {
+            assert_eq!(InstanceState::from(raw), state);
+            assert_eq!(state.to_string(), raw);
+        }
+        assert!(!InstanceState::NotStarted.is_started());
+        assert!(InstanceState::Paused.is_started());
+    }
+
+    #[tokio::test]
+    async fn test_body_size_guard() {
+        use crate::mmds::{MmdsOperations, MMDS_DEFAULT_SIZE_LIMIT};
+        use crate::testing::mock::{expect_put, expect_put_drive};
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+        use serde_json::json;
+
+        // `{"k":"..."}` is 8 bytes around the value
+        let sized = |size: usize| json!({ "k": "x".repeat(size - 8) });
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+
+        let at_limit = expect_put(&mut server, "/mmds", mockito::Matcher::Any);
+        client
+            .put_mmds(sized(MMDS_DEFAULT_SIZE_LIMIT))
+            .await
+            .unwrap();
+        at_limit.assert();
+
+        let refused = server.mock("PATCH", "/mmds").expect(0).create();
+        match client.patch_mmds(sized(MMDS_DEFAULT_SIZE_LIMIT + 1)).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                let error = &errors.field_errors()["body"][0];
+                assert_eq!(error.code, "body_too_large");
+                assert_eq!(
+                    error.message.as_deref(),
+                    Some("PATCH /mmds body is 51201 bytes, over the 51200 byte limit")
+                );
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        refused.assert();
+
+        // A VMM with a larger data store takes a larger limit
+        let client = client.with_max_mmds_body_size(2 * MMDS_DEFAULT_SIZE_LIMIT);
+        let raised = expect_put(&mut server, "/mmds", mockito::Matcher::Any);
+        client
+            .put_mmds(sized(MMDS_DEFAULT_SIZE_LIMIT + 1))
+            .await
+            .unwrap();
+        raised.assert();
+
+        // Other operations get the general limit, which no real drive reaches
+        let mut drive = crate::Drive::new("rootfs", format!("/{}", "d".repeat(4000)));
+        drive.is_root_device = true;
+        let sent = expect_put_drive(
+            &mut server,
+            "rootfs",
+            crate::testing::mock::json_body(&drive),
+        );
+        client.put_drive("rootfs", &drive).await.unwrap();
+        sent.assert();
+
+        let client = client.with_max_body_size(64);
+        let refused = server.mock("PUT", "/drives/rootfs").expect(0).create();
+        match client.put_drive("rootfs", &drive).await {
+            Err(crate::FirecrackerError::Validation(errors)) => {
+                let message = errors.field_errors()["body"][0].message.clone().unwrap();
+                assert!(
+                    message.starts_with("PUT /drives/rootfs body is "),
+                    "{}",
+                    message
+                );
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        refused.assert();
+    }
+
+    #[tokio::test]
+    async fn test_config_warnings_are_collected_and_drained() {
+        use crate::config::ConfigOperations;
+        use crate::testing::{fixtures, FakeVmm};
+        use crate::validation::{ConfigWarning, ValidationMode};
+        use std::sync::{Arc, Mutex};
+
+        let mut config = fixtures::bootable_config();
+        config.boot_source.as_mut().unwrap().boot_args =
+            Some("console=ttyS0 root=/dev/vda".to_string());
+        let expected = ConfigWarning {
+            rule: "boot_args_root_conflict",
+            message: "Boot arguments set root= while drive \"rootfs\" is the root device"
+                .to_string(),
+            context: "boot_source.boot_args".to_string(),
+        };
+
+        let fake = FakeVmm::start().await.unwrap();
+        let client = fake
+            .client()
+            .await
+            .unwrap()
+            .with_validation_mode(ValidationMode::Syntactic);
+        assert!(client.take_warnings().is_empty());
+        let report = client.validate_config(&config).unwrap();
+        assert_eq!(report.warnings().count(), 1);
+        assert_eq!(client.take_warnings(), vec![expected.clone()]);
+        assert!(client.take_warnings().is_empty());
+
+        // Applying succeeds and leaves the warning behind
+        client.apply_config(&config).await.unwrap();
+        assert_eq!(fake.state().config.boot_source, config.boot_source);
+        assert_eq!(client.take_warnings(), vec![expected.clone()]);
+
+        // With a callback nothing is collected
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let client = client.with_warning_callback({
+            let seen = seen.clone();
+            move |warning| seen.lock().unwrap().push(warning.clone())
+        });
+        client.validate_config(&config).unwrap();
+        assert!(client.take_warnings().is_empty());
+        assert_eq!(*seen.lock().unwrap(), vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_device_ids_are_percent_encoded() {
+        use crate::network::NetworkInterfaceOperations;
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+
+        let (mut server, client) = create_test_client().await;
+        for (id, path) in [
+            ("rootfs", "drives/rootfs"),
+            ("scratch_1.v-2~", "drives/scratch_1.v-2~"),
+            ("my drive", "drives/my%20drive"),
+            ("a/b", "drives/a%2Fb"),
+            ("../actions", "drives/..%2Factions"),
+            ("data#1", "drives/data%231"),
+            ("x?y=1", "drives/x%3Fy%3D1"),
+            ("100%", "drives/100%25"),
+            ("dísk", "drives/d%C3%ADsk"),
+            ("...", "drives/..."),
+        ] {
+            assert_eq!(
+                client.device_path("drives", "drive_id", id).unwrap(),
+                path,
+                "{:?}",
+                id
+            );
+            // The encoded path survives URL parsing unchanged
+            assert_eq!(
+                client.url(path).unwrap().path(),
+                format!("/{}", path),
+                "{:?}",
+                id
+            );
+        }
+        for id in ["", ".", ".."] {
+            match client.device_path("drives", "drive_id", id) {
+                Err(crate::FirecrackerError::Validation(errors)) => {
+                    assert_eq!(
+                        errors.field_errors()["drive_id"][0].code,
+                        "invalid_path_segment"
+                    )
+                }
+                other => panic!("expected a validation error for {:?}, got {:?}", id, other),
+            }
+        }
+
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        let drive = crate::Drive::new("my drive#1", "/images/data.ext4");
+        let encoded = server
+            .mock("PUT", "/drives/my%20drive%231")
+            .with_status(204)
+            .create();
+        client.put_drive("my drive#1", &drive).await.unwrap();
+        encoded.assert();
+
+        let iface = crate::NetworkInterface::new("eth/0", "tap0");
+        let encoded = server
+            .mock("PUT", "/network-interfaces/eth%2F0")
+            .with_status(204)
+            .create();
+        client.put_network_interface("eth/0", &iface).await.unwrap();
+        encoded.assert();
+
+        let untouched = server.mock("PUT", mockito::Matcher::Any).expect(0).create();
+        assert!(matches!(
+            client.put_network_interface("..", &iface).await,
+            Err(crate::FirecrackerError::Validation(_))
+        ));
+        untouched.assert();
+    }
+
+    #[tokio::test]
+    async fn test_request_timeouts_name_their_budget() {
+        use crate::instance::InstanceOperations;
+        use crate::snapshot::{SnapshotCreateParams, SnapshotOperations};
+        use crate::timeout::{OperationKind, TimeoutBudget};
+        use crate::validation::ValidationMode;
+        use std::time::{Duration, Instant};
+
+        let slow = |server: &mut ServerGuard, method: &str, path: &str, delay: Duration| {
+            server
+                .mock(method, path)
+                .with_status(200)
+                .with_body_from_request(move |_| {
+                    std::thread::sleep(delay);
+                    instance_body("Running").into_bytes()
+                })
+                .create()
+        };
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_read_timeout(Duration::from_millis(100));
+        slow(&mut server, "GET", "/", Duration::from_millis(500));
+        let started = Instant::now();
+        match client.describe_instance().await {
+            Err(err @ crate::FirecrackerError::RequestTimeout { .. }) => {
+                assert_eq!(err.to_string(), "GET / exceeded the read timeout of 100ms");
+                assert!(matches!(
+                    err,
+                    crate::FirecrackerError::RequestTimeout {
+                        budget: TimeoutBudget::Read,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("expected a read timeout, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_millis(400));
+
+        // Snapshots get a longer budget by default, other kinds by override
+        slow(
+            &mut server,
+            "PUT",
+            "/snapshot/create",
+            Duration::from_millis(300),
+        );
+        let params = SnapshotCreateParams::new("/tmp/vm.snap", "/tmp/vm.mem");
+        client.create_snapshot(&params).await.unwrap();
+        let client = client.with_operation_timeout(OperationKind::Query, Duration::from_secs(2));
+        assert_eq!(client.describe_instance().await.unwrap().state, "Running");
+        assert_eq!(
+            client.read_timeout(OperationKind::Configure),
+            Some(Duration::from_millis(100))
+        );
+
+        // A listener whose accept queue is full never completes the handshake;
+        // waiting for it counts toward the connect timeout, not the read one
+        let listener = tokio::net::TcpSocket::new_v4().unwrap();
+        listener.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = listener.listen(0).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _queued: Vec<_> = (0..4)
+            .filter_map(|_| {
+                std::net::TcpStream::connect_timeout(&addr, Duration::from_millis(50)).ok()
+            })
+            .collect();
+        let client = FirecrackerClient::new(&format!("http://{}", addr))
+            .await
+            .unwrap()
+            .with_connect_timeout(Duration::from_millis(100))
+            .unwrap()
+            .with_read_timeout(Duration::from_millis(50))
+            .with_timeout(Duration::from_secs(5));
+        match client.describe_instance().await {
+            Err(err @ crate::FirecrackerError::RequestTimeout { .. }) => {
+                assert_eq!(
+                    err.to_string(),
+                    "GET / exceeded the connect timeout of 100ms"
+                )
+            }
+            other => panic!("expected a connect timeout, got {:?}", other),
+        }
+        drop(listener);
+
+        // Nothing listening at all fails fast as a plain connection error
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = FirecrackerClient::new(&format!("http://127.0.0.1:{}", port))
+            .await
+            .unwrap()
+            .with_connect_timeout(Duration::from_millis(100))
+            .unwrap();
+        match client.describe_instance().await {
+            Err(crate::FirecrackerError::HttpClient(err)) => assert!(err.is_connect()),
+            other => panic!("expected a connection error, got {:?}", other),
+        }
+
+        // The total timeout still caps everything
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_timeout(Duration::from_millis(100));
+        slow(&mut server, "GET", "/", Duration::from_millis(500));
+        match client.describe_instance().await {
+            Err(err @ crate::FirecrackerError::RequestTimeout { .. }) => {
+                assert_eq!(err.to_string(), "GET / exceeded the total timeout of 100ms")
+            }
+            other => panic!("expected a total timeout, got {:?}", other),
+        }
+
+        // ... except for snapshots, which get at least their read timeout
+        let client = client.with_validation_mode(ValidationMode::Syntactic);
+        slow(
+            &mut server,
+            "PUT",
+            "/snapshot/create",
+            Duration::from_millis(300),
+        );
+        client.create_snapshot(&params).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_every_operation_uses_its_canonical_path() {
+        use crate::balloon::{BalloonOperations, BalloonStatsUpdate, BalloonUpdate};
+        use crate::boot::BootSourceOperations;
+        use crate::cpu::{CpuConfig, CpuConfigOperations};
+        use crate::drive::DriveUpdate;
+        use crate::entropy::EntropyDeviceOperations;
+        use crate::instance::InstanceOperations;
+        use crate::logger::LoggerOperations;
+        use crate::machine::MachineConfigOperations;
+        use crate::metrics::{Metrics, MetricsOperations};
+        use crate::mmds::MmdsOperations;
+        use crate::models::{
+            Balloon, BootSource, EntropyDevice, Logger, MachineConfig, MmdsConfig, Vm, VmState,
+            Vsock,
+        };
+        use crate::network::{NetworkInterfaceOperations, NetworkInterfaceUpdate};
+        use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams, SnapshotOperations};
+        use crate::validation::ValidationMode;
+        use crate::version::VersionOperations;
+        use crate::vm::{VmConfig, VmOperations};
+        use crate::vsock::VsockOperations;
+        use crate::{paths, DriveOperations};
+        use std::sync::{Arc, Mutex};
+
+        let (mut server, client) = create_test_client().await;
+        let client = client.with_validation_mode(ValidationMode::Off);
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let _mocks: Vec<_> = ["GET", "PUT", "PATCH"]
+            .into_iter()
+            .map(|method| {
+                let recorded = recorded.clone();
+                server
+                    .mock(method, mockito::Matcher::Any)
+                    .with_status(204)
+                    .with_body_from_request(move |request| {
+                        recorded.lock().unwrap().push(format!(
+                            "{} {}",
+                            request.method(),
+                            request.path()
+                        ));
+                        Vec::new()
+                    })
+                    .create()
+            })
+            .collect();
+
+        // Only the paths matter here; most responses don't decode
+        let _ = client.describe_instance().await;
+        let _ = client.get_version().await;
+        let _ = client
+            .create_sync_action(&crate::InstanceActionInfo::new("InstanceStart"))
+            .await;
+        let _ = client
+            .put_boot_source(&BootSource::new("/images/vmlinux"))
+            .await;
+        let _ = client.get_machine_config().await;
+        let _ = client.put_machine_config(&MachineConfig::new(2, 256)).await;
+        let _ = client
+            .patch_machine_config(&MachineConfig::new(2, 256))
+            .await;
+        let _ = client.put_cpu_config(&CpuConfig::new("T2")).await;
+        let drive = crate::Drive::new("rootfs", "/images/rootfs.ext4");
+        let _ = client.put_drive("rootfs", &drive).await;
+        let update = DriveUpdate {
+            drive_id: "rootfs".to_string(),
+            ..Default::default()
+        };
+        let _ = client.patch_drive("rootfs", &update).await;
+        let iface = crate::NetworkInterface::new("eth0", "tap0");
+        let _ = client.put_network_interface("eth0", &iface).await;
+        let update = NetworkInterfaceUpdate {
+            iface_id: "eth0".to_string(),
+            ..Default::default()
+        };
+        let _ = client.patch_network_interface("eth0", &update).await;
+        let _ = client.put_mmds(serde_json::json!({ "a": 1 })).await;
+        let _ = client.patch_mmds(serde_json::json!({ "a": 2 })).await;
+        let _ = client.get_mmds().await;
+        let _ = client.put_mmds_config(&MmdsConfig::new(["eth0"])).await;
+        let _ = client.get_balloon_config().await;
+        let _ = client.put_balloon_config(&Balloon::new(64)).await;
+        let _ = client.patch_balloon_config(&BalloonUpdate::new(32)).await;
+        let _ = client.get_balloon_stats().await;
+        let _ = client
+            .patch_balloon_stats(&BalloonStatsUpdate::new(1))
+            .await;
+        let _ = client.get_vm_info().await;
+        let _ = client.get_vm_config().await;
+        let _ = client.put_vm_config(&VmConfig::default()).await;
+        let _ = client.patch_vm(&Vm::new(VmState::Paused)).await;
+        let _ = client.put_vsock(&Vsock::new(3, "/tmp/v.sock")).await;
+        let _ = client
+            .put_entropy_device(&EntropyDevice::builder().build().unwrap())
+            .await;
+        let _ = client.put_logger(&Logger::new("/tmp/fc.log")).await;
+        let _ = client.put_metrics(&Metrics::new("/tmp/fc.metrics")).await;
+        let _ = client
+            .create_snapshot(&SnapshotCreateParams::new("/tmp/snap", "/tmp/mem"))
+            .await;
+        let _ = client
+            .load_snapshot(&SnapshotLoadParams::with_mem_file("/tmp/snap", "/tmp/mem"))
+            .await;
+
+        let expected = [
+            ("GET", paths::INSTANCE, "/"),
+            ("GET", paths::VERSION, "/version"),
+            ("PUT", paths::ACTIONS, "/actions"),
+            ("PUT", paths::BOOT_SOURCE, "/boot-source"),
+            ("GET", paths::MACHINE_CONFIG, "/machine-config"),
+            ("PUT", paths::MACHINE_CONFIG, "/machine-config"),
+            ("PATCH", paths::MACHINE_CONFIG, "/machine-config"),
+            ("PUT", paths::CPU_CONFIG, "/cpu-config"),
+            ("PUT", &paths::drive("rootfs"), "/drives/rootfs"),
+            ("PATCH", &paths::drive("rootfs"), "/drives/rootfs"),
+            (
+                "PUT",
+                &paths::network_interface("eth0"),
+                "/network-interfaces/eth0",
+            ),
+            (
+                "PATCH",
+                &paths::network_interface("eth0"),
+                "/network-interfaces/eth0",
+            ),
+            ("PUT", paths::MMDS, "/mmds"),
+            ("PATCH", paths::MMDS, "/mmds"),
+            ("GET", paths::MMDS, "/mmds"),
+            ("PUT", paths::MMDS_CONFIG, "/mmds/config"),
+            ("GET", paths::BALLOON, "/balloon"),
+            ("PUT", paths::BALLOON, "/balloon"),
+            ("PATCH", paths::BALLOON, "/balloon"),
+            ("GET", paths::BALLOON_STATISTICS, "/balloon/statistics"),
+            ("PATCH", paths::BALLOON_STATISTICS, "/balloon/statistics"),
+            ("GET", paths::VM, "/vm"),
+            ("GET", paths::VM_CONFIG, "/vm/config"),
+            ("PUT", paths::VM_CONFIG, "/vm/config"),
+            ("PATCH", paths::VM, "/vm"),
+            ("PUT", paths::VSOCK, "/vsock"),
+            ("PUT", paths::ENTROPY, "/entropy"),
+            ("PUT", paths::LOGGER, "/logger"),
+            ("PUT", paths::METRICS, "/metrics"),
+            ("PUT", paths::SNAPSHOT_CREATE, "/snapshot/create"),
+            ("PUT", paths::SNAPSHOT_LOAD, "/snapshot/load"),
+        ];
+        let recorded = recorded.lock().unwrap().clone();
+        assert_eq!(
+            recorded,
+            expected
+                .iter()
+                .map(|(method, _, path)| format!("{} {}", method, path))
+                .collect::<Vec<_>>()
+        );
+        for (_, constant, path) in expected {
+            assert_eq!(format!("/{}", constant), path);
+            assert_eq!(client.url(constant).unwrap().path(), path);
+            assert_eq!(client.url(&format!("/{}/", constant)).unwrap().path(), path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preflight_check_from_vm_config() {
+        use crate::preflight::PreflightSource;
+        use crate::testing::fixtures::{VM_CONFIG_V1_7, VM_CONFIG_V1_7_PRE_BOOT};
+
+        let (mut server, client) = create_test_client().await;
+        let complete = server
+            .mock("GET", "/vm/config")
+            .with_status(200)
+            .with_body(VM_CONFIG_V1_7)
+            .create();
+        let report = client.preflight_check().await.unwrap();
+        assert_eq!(report.source, PreflightSource::VmConfig);
+        assert!(report.is_bootable(), "{}", report.report);
+        complete.remove();
+
+        // Before boot Firecracker reports an empty kernel path, not none
+        let incomplete = server
+            .mock("GET", "/vm/config")
+            .with_status(200)
+            .with_body(VM_CONFIG_V1_7_PRE_BOOT)
+            .create();
+        let report = client.preflight_check().await.unwrap();
+        assert!(!report.is_bootable());
+        let rules: Vec<_> = report.report.errors().map(|f| f.rule).collect();
+        assert_eq!(rules, ["boot_source_missing", "root_drive_missing"]);
+        assert!(matches!(
+            report.into_result(),
+            Err(crate::FirecrackerError::InvalidConfig(_))
+        ));
+        incomplete.assert();
+    }
+
+    #[tokio::test]
+    async fn test_preflight_check_falls_back_to_sent_config() {
+        use crate::boot::BootSourceOperations;
+        use crate::preflight::PreflightSource;
+        use crate::validation::ValidationMode;
+        use crate::DriveOperations;
+
+        let (mut server, client) = create_test_client().await;
+        let client = client
+            .with_validation_mode(ValidationMode::Syntactic)
+            .with_preflight_check(true);
+        let _vm_config = server.mock("GET", "/vm/config").with_status(404).create();
+        let _puts = server
+            .mock(
+                "PUT",
+                mockito::Matcher::Regex("^/(boot-source|drives/.*)$".into()),
+            )
+            .with_status(204)
+            .create();
+        let start = crate::InstanceActionInfo::from(crate::action::ActionType::InstanceStart);
+        let not_sent = server
+            .mock("PUT", "/actions")
+            .with_status(204)
+            .expect(0)
+            .create();
+
+        let report = client.preflight_check().await.unwrap();
+        assert_eq!(report.source, PreflightSource::SentByClient);
+        assert_eq!(report.report.errors().count(), 2);
+
+        client
+            .put_boot_source(&crate::BootSource::new("/images/vmlinux"))
+            .await
+            .unwrap();
+        client
+            .put_drive("data", &crate::Drive::new("data", "/images/data.ext4"))
+            .await
+            .unwrap();
+        let report = client.preflight_check().await.unwrap();
+        let rules: Vec<_> = report.report.errors().map(|f| f.rule).collect();
+        assert_eq!(rules, ["root_drive_missing"]);
+
+        // InstanceStart isn't sent while the VM can't boot
+        match client.create_sync_action(&start).await {
+            Err(crate::FirecrackerError::InvalidConfig(report)) => {
+                assert_eq!(report.findings[0].rule, "root_drive_missing")
+            }
+            other => panic!("expected InvalidConfig, got {:?}", other),
+        }
+        not_sent.assert();
+        not_sent.remove();
+
+        let root = crate::Drive {
+            is_root_device: true,
+            ..crate::Drive::new("rootfs", "/images/rootfs.ext4")
+        };
+        client.put_drive("rootfs", &root).await.unwrap();
+        assert!(client.preflight_check().await.unwrap().is_bootable());
+        let sent = server.mock("PUT", "/actions").with_status(204).create();
+        client.create_sync_action(&start).await.unwrap();
+        sent.assert();
+    }
 }
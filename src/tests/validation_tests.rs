@@ -1,12 +1,10 @@
-use std::fs::{self, File};
-use std::os::unix::fs::PermissionsExt;
+use std::fs::File;
 use tempfile::tempdir;
 use validator::Validate;
 
-use crate::{
-    validate_unix_path, validate_existing_path, validate_writable_path,
-    Drive, NetworkInterface, Logger, Metrics, Vsock, SnapshotCreateParams, SnapshotLoadParams
-};
+use crate::snapshot::{SnapshotCreateParams, SnapshotLoadParams, SnapshotType};
+use crate::validation::validate_unix_path;
+use crate::{Drive, LogLevel, Logger, NetworkInterface, Vsock};
 
 #[test]
 fn test_unix_path_validation() {
@@ -30,15 +28,19 @@ fn test_unix_path_validation() {
     assert!(validate_unix_path("\0").is_err()); // Just null character
 }
 
+#[cfg(not(feature = "no-fs-validation"))]
 #[test]
 fn test_existing_path_validation() {
+    use crate::validation::validate_existing_path;
+    use std::fs;
+
     let temp_dir = tempdir().unwrap();
     let temp_path = temp_dir.path();
 
     // Create some test files and directories
     let existing_file = temp_path.join("existing.txt");
     File::create(&existing_file).unwrap();
-    
+
     let existing_dir = temp_path.join("existing_dir");
     fs::create_dir(&existing_dir).unwrap();
 
@@ -50,23 +52,28 @@ fn test_existing_path_validation() {
     // Invalid cases
     let non_existent = temp_path.join("non_existent.txt");
     assert!(validate_existing_path(non_existent.to_str().unwrap()).is_err());
-    
+
     let non_existent_dir = temp_path.join("non_existent_dir");
     assert!(validate_existing_path(non_existent_dir.to_str().unwrap()).is_err());
-    
+
     assert!(validate_existing_path("").is_err());
     assert!(validate_existing_path("relative/path").is_err());
 }
 
+#[cfg(not(feature = "no-fs-validation"))]
 #[test]
 fn test_writable_path_validation() {
+    use crate::validation::validate_writable_path;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
     let temp_dir = tempdir().unwrap();
     let temp_path = temp_dir.path();
 
     // Create test files with different permissions
     let writable_file = temp_path.join("writable.txt");
     File::create(&writable_file).unwrap();
-    
+
     let readonly_file = temp_path.join("readonly.txt");
     File::create(&readonly_file).unwrap();
     let mut perms = fs::metadata(&readonly_file).unwrap().permissions();
@@ -75,7 +82,7 @@ fn test_writable_path_validation() {
 
     let writable_dir = temp_path.join("writable_dir");
     fs::create_dir(&writable_dir).unwrap();
-    
+
     let readonly_dir = temp_path.join("readonly_dir");
     fs::create_dir(&readonly_dir).unwrap();
     let mut perms = fs::metadata(&readonly_dir).unwrap().permissions();
@@ -85,7 +92,7 @@ fn test_writable_path_validation() {
     // Valid cases
     assert!(validate_writable_path(writable_file.to_str().unwrap()).is_ok());
     assert!(validate_writable_path(writable_dir.to_str().unwrap()).is_ok());
-    
+
     // New file in writable directory
     let new_file = writable_dir.join("new_file.txt");
     assert!(validate_writable_path(new_file.to_str().unwrap()).is_ok());
@@ -93,34 +100,29 @@ fn test_writable_path_validation() {
     // Invalid cases
     assert!(validate_writable_path(readonly_file.to_str().unwrap()).is_err());
     assert!(validate_writable_path(readonly_dir.to_str().unwrap()).is_err());
-    
+
     // New file in readonly directory
     let new_file_readonly = readonly_dir.join("new_file.txt");
     assert!(validate_writable_path(new_file_readonly.to_str().unwrap()).is_err());
-    
+
     // Non-existent parent
     let non_existent_parent = temp_path.join("non_existent_dir/file.txt");
     assert!(validate_writable_path(non_existent_parent.to_str().unwrap()).is_err());
-    
+
     assert!(validate_writable_path("").is_err());
     assert!(validate_writable_path("relative/path").is_err());
 }
 
 #[test]
 fn test_drive_validation() {
-    let temp_dir = tempdir().unwrap();
-    let temp_path = temp_dir.path();
-    let existing_file = temp_path.join("disk.img");
-    File::create(&existing_file).unwrap();
-
     // Valid cases
     let valid_drive = Drive {
         drive_id: "rootfs".to_string(),
-        path_on_host: existing_file.to_str().unwrap().to_string(),
+        path_on_host: "/images/disk.img".to_string(),
         is_root_device: true,
         is_read_only: false,
         partuuid: Some("00000000-1234-1234-1234-123456789abc".to_string()),
-        rate_limiter: None,
+        ..Default::default()
     };
     assert!(valid_drive.validate().is_ok());
 
@@ -131,11 +133,12 @@ fn test_drive_validation() {
     };
     assert!(invalid_drive_empty_id.validate().is_err());
 
-    let invalid_drive_non_existent = Drive {
-        path_on_host: "/non/existent/path".to_string(),
+    // Whether the file exists is checked by put_drive, not the model
+    let invalid_drive_relative = Drive {
+        path_on_host: "relative/disk.img".to_string(),
         ..valid_drive.clone()
     };
-    assert!(invalid_drive_non_existent.validate().is_err());
+    assert!(invalid_drive_relative.validate().is_err());
 
     let invalid_drive_partuuid = Drive {
         partuuid: Some("invalid-uuid".to_string()),
@@ -149,7 +152,7 @@ fn test_network_interface_validation() {
     // Valid cases
     let valid_interface = NetworkInterface {
         iface_id: "eth0".to_string(),
-        host_dev_name: "/dev/tap0".to_string(),
+        host_dev_name: "tap0".to_string(),
         guest_mac: Some("12:34:56:78:9A:BC".to_string()),
         rx_rate_limiter: None,
         tx_rate_limiter: None,
@@ -163,11 +166,11 @@ fn test_network_interface_validation() {
     };
     assert!(invalid_interface_empty_id.validate().is_err());
 
-    let invalid_interface_relative_path = NetworkInterface {
+    let invalid_interface_dev_name = NetworkInterface {
         host_dev_name: "dev/tap0".to_string(),
         ..valid_interface.clone()
     };
-    assert!(invalid_interface_relative_path.validate().is_err());
+    assert!(invalid_interface_dev_name.validate().is_err());
 
     let invalid_interface_mac = NetworkInterface {
         guest_mac: Some("invalid:mac:address".to_string()),
@@ -178,16 +181,13 @@ fn test_network_interface_validation() {
 
 #[test]
 fn test_logger_validation() {
-    let temp_dir = tempdir().unwrap();
-    let temp_path = temp_dir.path();
-    let log_file = temp_path.join("firecracker.log");
-
     // Valid cases
     let valid_logger = Logger {
-        log_path: log_file.to_str().unwrap().to_string(),
-        level: Some("Info".to_string()),
+        log_path: "/var/log/firecracker.log".to_string(),
+        level: Some(LogLevel::Info),
         show_level: Some(true),
         show_log_origin: Some(true),
+        module: None,
     };
     assert!(valid_logger.validate().is_ok());
 
@@ -204,21 +204,22 @@ fn test_logger_validation() {
     };
     assert!(invalid_logger_relative_path.validate().is_err());
 
-    let invalid_logger_level = Logger {
-        level: Some("InvalidLevel".to_string()),
-        ..valid_logger.clone()
-    };
-    assert!(invalid_logger_level.validate().is_err());
+    // Levels are an enum, so an unknown one fails to deserialize instead
+    let invalid_logger_level = serde_json::json!({
+        "log_path": "/var/log/firecracker.log",
+        "level": "InvalidLevel",
+    });
+    assert!(serde_json::from_value::<Logger>(invalid_logger_level).is_err());
 }
 
 #[test]
 fn test_snapshot_validation() {
     let temp_dir = tempdir().unwrap();
     let temp_path = temp_dir.path();
-    
+
     let existing_file = temp_path.join("existing.snap");
     File::create(&existing_file).unwrap();
-    
+
     let existing_mem = temp_path.join("existing.mem");
     File::create(&existing_mem).unwrap();
 
@@ -226,16 +227,20 @@ fn test_snapshot_validation() {
     let valid_create = SnapshotCreateParams {
         snapshot_path: temp_path.join("new.snap").to_str().unwrap().to_string(),
         mem_file_path: temp_path.join("new.mem").to_str().unwrap().to_string(),
-        snapshot_type: Some("Full".to_string()),
+        snapshot_type: Some(SnapshotType::Full),
         version: None,
+        allow_overwrite: false,
     };
     assert!(valid_create.validate().is_ok());
 
     // Valid cases for load
     let valid_load = SnapshotLoadParams {
         snapshot_path: existing_file.to_str().unwrap().to_string(),
-        mem_file_path: existing_mem.to_str().unwrap().to_string(),
+        mem_file_path: Some(existing_mem.to_str().unwrap().to_string()),
+        mem_backend: None,
         enable_diff_snapshots: Some(true),
+        resume_vm: None,
+        network_overrides: Vec::new(),
     };
     assert!(valid_load.validate().is_ok());
 
@@ -246,19 +251,12 @@ fn test_snapshot_validation() {
     };
     assert!(invalid_create_relative.validate().is_err());
 
-    let invalid_create_type = SnapshotCreateParams {
-        snapshot_type: Some("Invalid".to_string()),
-        ..valid_create.clone()
-    };
-    assert!(invalid_create_type.validate().is_err());
-
     // Invalid cases for load
-    let invalid_load_non_existent = SnapshotLoadParams {
-        snapshot_path: "/non/existent/path".to_string(),
-        mem_file_path: "/non/existent/mem".to_string(),
-        enable_diff_snapshots: None,
+    let invalid_load_relative = SnapshotLoadParams {
+        snapshot_path: "relative/path.snap".to_string(),
+        ..valid_load.clone()
     };
-    assert!(invalid_load_non_existent.validate().is_err());
+    assert!(invalid_load_relative.validate().is_err());
 }
 
 #[test]
@@ -270,6 +268,7 @@ fn test_vsock_validation() {
     let valid_vsock = Vsock {
         guest_cid: 3,
         uds_path: temp_path.join("vsock.sock").to_str().unwrap().to_string(),
+        vsock_id: None,
     };
     assert!(valid_vsock.validate().is_ok());
 
@@ -280,11 +279,11 @@ fn test_vsock_validation() {
     };
     assert!(invalid_vsock_cid_low.validate().is_err());
 
-    let invalid_vsock_cid_high = Vsock {
-        guest_cid: 4294967296,
+    let invalid_vsock_long_path = Vsock {
+        uds_path: format!("/{}", "a".repeat(107)),
         ..valid_vsock.clone()
     };
-    assert!(invalid_vsock_cid_high.validate().is_err());
+    assert!(invalid_vsock_long_path.validate().is_err());
 
     let invalid_vsock_relative_path = Vsock {
         uds_path: "relative/path.sock".to_string(),
@@ -292,3 +291,25 @@ fn test_vsock_validation() {
     };
     assert!(invalid_vsock_relative_path.validate().is_err());
 }
+
+#[test]
+fn test_models_compare_by_value() {
+    let drive = Drive {
+        drive_id: "rootfs".to_string(),
+        path_on_host: "/images/rootfs.ext4".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(drive.clone(), drive);
+    assert_ne!(
+        Drive {
+            is_read_only: true,
+            ..drive.clone()
+        },
+        drive
+    );
+
+    // The cached parse result doesn't take part in comparisons
+    let parsed = crate::FirecrackerVersion::new("1.7.0");
+    parsed.semver().unwrap();
+    assert_eq!(parsed, crate::FirecrackerVersion::new("1.7.0"));
+}
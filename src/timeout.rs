@@ -0,0 +1,73 @@
+//! Separate time budgets for the stages of a request, so a client can give
+//! up quickly on a VMM whose socket isn't there while still waiting out a
+//! slow snapshot load:
+//!
+//! - connect: [`FirecrackerClient::with_connect_timeout`](crate::FirecrackerClient::with_connect_timeout)
+//! - read, once connected, per request and [`OperationKind`]:
+//!   [`FirecrackerClient::with_read_timeout`](crate::FirecrackerClient::with_read_timeout)
+//!   and [`FirecrackerClient::with_operation_timeout`](crate::FirecrackerClient::with_operation_timeout)
+//! - total, connecting included, and for snapshot operations at least their
+//!   read timeout:
+//!   [`FirecrackerClient::with_timeout`](crate::FirecrackerClient::with_timeout)
+//!
+//! A request that runs out of one fails with
+//! [`FirecrackerError::RequestTimeout`](crate::FirecrackerError::RequestTimeout)
+//! naming it.
+
+use crate::paths;
+use reqwest::Method;
+use std::fmt;
+use std::time::Duration;
+
+/// The least read timeout snapshot operations get when a read timeout is
+/// set and they have no override: writing or loading guest memory can take
+/// minutes.
+pub const DEFAULT_SNAPSHOT_READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What a request does, for per-operation read timeouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OperationKind {
+    /// `GET` requests
+    Query,
+    /// `PUT` and `PATCH` requests that set up or update the VM
+    Configure,
+    /// `PUT /actions`
+    Action,
+    /// Creating and loading snapshots
+    Snapshot,
+}
+
+impl OperationKind {
+    /// The kind of a `method` request to `path`.
+    pub fn of(method: &Method, path: &str) -> Self {
+        let path = path.trim_matches('/');
+        if path == paths::SNAPSHOT_CREATE || path == paths::SNAPSHOT_LOAD {
+            OperationKind::Snapshot
+        } else if path == paths::ACTIONS {
+            OperationKind::Action
+        } else if method == Method::GET {
+            OperationKind::Query
+        } else {
+            OperationKind::Configure
+        }
+    }
+}
+
+/// Which time budget a request ran out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutBudget {
+    Connect,
+    Read,
+    Total,
+}
+
+impl fmt::Display for TimeoutBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutBudget::Connect => "connect",
+            TimeoutBudget::Read => "read",
+            TimeoutBudget::Total => "total",
+        })
+    }
+}
@@ -0,0 +1,243 @@
+//! How requests reach the VMM: over HTTP with `reqwest`, or straight over
+//! Firecracker's API socket for clients from
+//! [`FirecrackerClient::new_unix`](crate::FirecrackerClient::new_unix).
+
+use crate::error::FirecrackerError;
+use crate::timeout::TimeoutBudget;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, StatusCode};
+use std::future::Future;
+use std::time::Duration;
+use url::Url;
+
+#[cfg(unix)]
+use reqwest::header::{AUTHORIZATION, HOST};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+#[derive(Clone)]
+pub(crate) enum Transport {
+    Http(reqwest::Client),
+    /// The API socket; every request opens a connection of its own
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Why a request got no response, for `send_request` to tell refused
+/// connections and timeouts from the rest.
+#[derive(Debug)]
+pub(crate) struct SendError {
+    refused: bool,
+    timed_out: Option<TimeoutBudget>,
+    error: FirecrackerError,
+}
+
+impl SendError {
+    /// Nothing was listening yet: the connection was refused or, for the
+    /// API socket, the socket doesn't exist
+    pub(crate) fn is_refused(&self) -> bool {
+        self.refused
+    }
+
+    /// The budget the request ran out of, if that is why it failed
+    pub(crate) fn timed_out(&self) -> Option<TimeoutBudget> {
+        self.timed_out
+    }
+
+    fn timeout(budget: TimeoutBudget, limit: Duration) -> Self {
+        Self {
+            refused: false,
+            timed_out: Some(budget),
+            error: FirecrackerError::Timeout {
+                duration_secs: limit.as_secs(),
+            },
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendError {
+    fn from(err: reqwest::Error) -> Self {
+        let mut source = std::error::Error::source(&err);
+        let mut refused = false;
+        while let Some(cause) = source {
+            if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                refused = io.kind() == std::io::ErrorKind::ConnectionRefused;
+                break;
+            }
+            source = cause.source();
+        }
+        // The connect timeout is the client's, the total timeout the
+        // request's own
+        let timed_out = match (err.is_timeout(), err.is_connect()) {
+            (true, true) => Some(TimeoutBudget::Connect),
+            (true, false) => Some(TimeoutBudget::Total),
+            (false, _) => None,
+        };
+        Self {
+            refused: err.is_connect() && refused,
+            timed_out,
+            error: err.into(),
+        }
+    }
+}
+
+impl From<SendError> for FirecrackerError {
+    fn from(err: SendError) -> Self {
+        err.error
+    }
+}
+
+// A request as `FirecrackerClient::send_once` hands it over
+pub(crate) struct Outgoing<'a> {
+    pub(crate) method: &'a Method,
+    pub(crate) url: &'a Url,
+    pub(crate) body: Option<&'a [u8]>,
+    pub(crate) token: Option<&'a str>,
+}
+
+/// The time budgets of one request; see [`timeout`](crate::timeout).
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Budgets {
+    pub(crate) connect: Option<Duration>,
+    pub(crate) read: Option<Duration>,
+    pub(crate) total: Option<Duration>,
+}
+
+impl Budgets {
+    pub(crate) fn limit(&self, budget: TimeoutBudget) -> Option<Duration> {
+        match budget {
+            TimeoutBudget::Connect => self.connect,
+            TimeoutBudget::Read => self.read,
+            TimeoutBudget::Total => self.total,
+        }
+    }
+}
+
+impl Transport {
+    pub(crate) async fn send(
+        &self,
+        request: Outgoing<'_>,
+        budgets: Budgets,
+    ) -> Result<(StatusCode, String), SendError> {
+        match self {
+            Transport::Http(client) => {
+                let mut builder = client.request(request.method.clone(), request.url.clone());
+                if let Some(token) = request.token {
+                    builder = builder.bearer_auth(token);
+                }
+                if let Some(body) = request.body {
+                    builder = builder
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(body.to_vec());
+                }
+                // The client applies the connect timeout it was built with
+                if let Some(total) = budgets.total {
+                    builder = builder.timeout(total);
+                }
+                let exchange = async {
+                    let response = builder.send().await?;
+                    let status = response.status();
+                    Ok((status, response.text().await?))
+                };
+                // `reqwest` doesn't tell when the connection is up, so the
+                // read timeout gets the connect budget on top: however long
+                // connecting takes within it, it never reads as a slow
+                // response
+                let read = budgets
+                    .read
+                    .map(|read| read + budgets.connect.unwrap_or_default());
+                within(read, TimeoutBudget::Read, exchange).await
+            }
+            #[cfg(unix)]
+            Transport::Unix(socket) => {
+                within(
+                    budgets.total,
+                    TimeoutBudget::Total,
+                    send_unix(socket, request, budgets),
+                )
+                .await
+            }
+        }
+    }
+}
+
+// Runs `f`, failing with a timeout of `budget` once `limit` has passed
+async fn within<T>(
+    limit: Option<Duration>,
+    budget: TimeoutBudget,
+    f: impl Future<Output = Result<T, SendError>>,
+) -> Result<T, SendError> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, f)
+            .await
+            .map_err(|_| SendError::timeout(budget, limit))?,
+        None => f.await,
+    }
+}
+
+#[cfg(unix)]
+async fn send_unix(
+    socket: &Path,
+    request: Outgoing<'_>,
+    budgets: Budgets,
+) -> Result<(StatusCode, String), SendError> {
+    let socket_error = |connect: bool, source: std::io::Error| SendError {
+        refused: connect
+            && matches!(
+                source.kind(),
+                std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotFound
+            ),
+        timed_out: None,
+        error: FirecrackerError::ApiSocket {
+            path: socket.to_path_buf(),
+            source,
+        },
+    };
+    let connect = async {
+        UnixStream::connect(socket)
+            .await
+            .map_err(|e| socket_error(true, e))
+    };
+    let stream = within(budgets.connect, TimeoutBudget::Connect, connect).await?;
+
+    // Connected: the rest is the read timeout's
+    let exchange = async {
+        let hyper_error = |e: hyper::Error| socket_error(false, std::io::Error::other(e));
+        let (mut sender, connection) = hyper::client::conn::handshake(stream)
+            .await
+            .map_err(hyper_error)?;
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+
+        let mut builder = hyper::Request::builder()
+            .method(request.method.clone())
+            .uri(&request.url[url::Position::BeforePath..])
+            .header(HOST, "localhost");
+        if let Some(token) = request.token {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = match request.body {
+            Some(body) => {
+                builder = builder.header(CONTENT_TYPE, "application/json");
+                hyper::Body::from(body.to_vec())
+            }
+            None => hyper::Body::empty(),
+        };
+        let request = builder.body(body).map_err(|e| SendError {
+            refused: false,
+            timed_out: None,
+            error: FirecrackerError::Internal(e.to_string()),
+        })?;
+
+        let response = sender.send_request(request).await.map_err(hyper_error)?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(hyper_error)?;
+        Ok((status, String::from_utf8_lossy(&body).into_owned()))
+    };
+    within(budgets.read, TimeoutBudget::Read, exchange).await
+}
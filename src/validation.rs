@@ -1,6 +1,30 @@
+use crate::metrics::Metrics;
+use crate::models::{FullVmConfiguration, Logger};
+use serde_json::Value;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
-use validator::ValidationError;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+/// Controls how much of the request validation the client performs locally
+/// before sending a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationMode {
+    /// Send requests as given and leave all validation to Firecracker.
+    Off,
+    /// Run the model checks that need nothing but the request itself: field
+    /// ranges and formats, and path syntax (absolute, no `..`, no NUL). Use
+    /// this when the client does not run on the VMM host.
+    Syntactic,
+    /// Everything in `Syntactic`, plus checks against the local filesystem:
+    /// files Firecracker reads must exist, files it writes must be writable
+    /// and not clobber existing ones, and snapshot headers must match the VMM.
+    /// Builds with the `no-fs-validation` feature skip the filesystem checks,
+    /// so this mode behaves like `Syntactic` there.
+    #[default]
+    StrictLocal,
+}
 
 pub fn path_validation_error(message: impl Into<Cow<'static, str>>) -> ValidationError {
     let mut err = ValidationError::new("invalid_path");
@@ -19,8 +43,9 @@ pub fn validate_unix_path(path: &str) -> Result<(), ValidationError> {
         return Err(path_validation_error("Path must be absolute"));
     }
 
-    // Check if path contains invalid characters
-    if path.contains('\0') || path.contains("..") {
+    // Check for null characters and parent directory components; dots inside
+    // a file name such as "disk..base.ext4" are fine
+    if path.contains('\0') || path.split('/').any(|component| component == "..") {
         return Err(path_validation_error(
             "Path cannot contain parent directory references or null characters",
         ));
@@ -34,40 +59,321 @@ pub fn validate_unix_path(path: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
-// Custom validation function for paths that should exist
-pub fn validate_existing_path(path: &str) -> Result<(), ValidationError> {
+/// Longest path that fits in `sockaddr_un.sun_path` with its trailing NUL.
+pub const MAX_UDS_PATH_LEN: usize = 107;
+
+// Unix domain socket paths must be absolute and fit in sockaddr_un
+pub fn validate_uds_path(path: &str) -> Result<(), ValidationError> {
     validate_unix_path(path)?;
 
-    if !Path::new(path).exists() {
-        return Err(path_validation_error("Path does not exist"));
+    if path.len() > MAX_UDS_PATH_LEN {
+        return Err(path_validation_error(format!(
+            "Socket path is {} bytes, longer than the {} byte limit",
+            path.len(),
+            MAX_UDS_PATH_LEN
+        )));
+    }
+
+    Ok(())
+}
+
+/// Longest kernel command line `boot_args` may take up. The kernel's own
+/// limit depends on the architecture and Firecracker appends to the
+/// arguments, so this is a practical bound rather than an exact one.
+pub const MAX_BOOT_ARGS_LEN: usize = 4096;
+
+// A newline or NUL read along with the arguments from a file would cut the
+// command line short without any error
+pub fn validate_boot_args(args: &str) -> Result<(), ValidationError> {
+    let invalid = |code: &'static str, message: String| {
+        let mut err = ValidationError::new(code);
+        err.message = Some(message.into());
+        err
+    };
+
+    if let Some((i, c)) = args.char_indices().find(|(_, c)| c.is_control()) {
+        return Err(invalid(
+            "invalid_boot_args",
+            format!(
+                "Boot arguments contain the control character {:?} at byte {}",
+                c, i
+            ),
+        ));
+    }
+    if args.len() > MAX_BOOT_ARGS_LEN {
+        let mut err = invalid(
+            "boot_args_too_long",
+            format!(
+                "Boot arguments are {} bytes, over the {} byte limit",
+                args.len(),
+                MAX_BOOT_ARGS_LEN
+            ),
+        );
+        err.add_param("size".into(), &args.len());
+        err.add_param("limit".into(), &MAX_BOOT_ARGS_LEN);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Largest balloon Firecracker accepts: it counts the balloon in 4 KiB
+/// pages held in a `u32`.
+pub const MAX_BALLOON_MIB: u32 = u32::MAX / 256;
+
+/// Longest balloon statistics interval; Firecracker keeps it in a `u16`.
+pub const MAX_STATS_POLLING_INTERVAL_S: u32 = u16::MAX as u32;
+
+// The names `PUT /cpu-config` accepts are those of the static templates
+pub fn validate_cpu_template_name(template: &str) -> Result<(), ValidationError> {
+    serde_json::from_value::<crate::models::CpuTemplate>(serde_json::Value::from(template))
+        .map(|_| ())
+        .map_err(|_| {
+            let mut err = ValidationError::new("unknown_cpu_template");
+            err.message = Some(format!("Unknown CPU template {:?}", template).into());
+            err
+        })
+}
+
+/// Longest Linux network interface name (IFNAMSIZ without the NUL).
+pub const MAX_IFACE_NAME_LEN: usize = 15;
+
+// Mirrors the kernel's dev_valid_name check for host interface names
+pub fn validate_host_dev_name(name: &str) -> Result<(), ValidationError> {
+    let invalid = |message: String| {
+        let mut err = ValidationError::new("invalid_host_dev_name");
+        err.message = Some(message.into());
+        err
+    };
+
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(invalid(format!("{:?} is not a valid interface name", name)));
+    }
+    if name.len() > MAX_IFACE_NAME_LEN {
+        return Err(invalid(format!(
+            "Interface name {} is longer than {} bytes",
+            name, MAX_IFACE_NAME_LEN
+        )));
+    }
+    if name
+        .chars()
+        .any(|c| c == '/' || c == ':' || c.is_whitespace())
+    {
+        return Err(invalid(format!(
+            "Interface name {:?} contains '/', ':' or whitespace",
+            name
+        )));
     }
 
     Ok(())
 }
 
+// Firecracker only serves MMDS on a link-local address
+pub fn validate_mmds_ipv4_address(address: &str) -> Result<(), ValidationError> {
+    match address.parse::<std::net::Ipv4Addr>() {
+        Ok(ip) if ip.is_link_local() => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("invalid_mmds_address");
+            err.message = Some(format!("{:?} is not a link-local IPv4 address", address).into());
+            Err(err)
+        }
+    }
+}
+
+// Custom validation function for paths that should exist
+pub fn validate_existing_path(path: &str) -> Result<(), ValidationError> {
+    validate_unix_path(path)?;
+    fs_checks::existing(Path::new(path))
+}
+
+// Error for a failed filesystem check; the check name is kept in the
+// "check" param so callers can tell the failures apart
+#[cfg_attr(feature = "no-fs-validation", allow(dead_code))]
+fn failed_check(check: &'static str, message: String) -> ValidationError {
+    let mut err = path_validation_error(message);
+    err.add_param(Cow::from("check"), &check);
+    err
+}
+
 // Custom validation function for paths that should be writable
 pub fn validate_writable_path(path: &str) -> Result<(), ValidationError> {
     validate_unix_path(path)?;
+    fs_checks::writable(Path::new(path))
+}
 
-    let path = Path::new(path);
+/// How [`validate_socket_path`] checks the filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SocketPathOptions {
+    /// Firecracker creates the socket, so its parent directory must be
+    /// writable. Otherwise Firecracker connects to it and it must exist.
+    pub create: bool,
+    /// Accept something other than a socket at the path, for callers that
+    /// remove it before Firecracker starts.
+    pub allow_replace: bool,
+}
 
-    // If path exists, check if it's writable
-    if path.exists() {
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::MetadataExt;
-            if let Ok(metadata) = path.metadata() {
-                let mode = metadata.mode();
-                if mode & 0o200 == 0 {
-                    return Err(path_validation_error("Path is not writable"));
+// A Unix domain socket path: absolute, short enough for sockaddr_un, and
+// either a socket or nothing at all
+pub fn validate_socket_path(
+    path: &str,
+    options: &SocketPathOptions,
+) -> Result<(), ValidationError> {
+    validate_uds_path(path)?;
+    fs_checks::socket(Path::new(path), options)
+}
+
+// A file Firecracker will create; an existing one is only accepted when the
+// caller allows it to be overwritten
+pub fn validate_new_file(path: &str, allow_overwrite: bool) -> Result<(), ValidationError> {
+    validate_unix_path(path)?;
+    fs_checks::new_file(Path::new(path), allow_overwrite)
+}
+
+// A file Firecracker will read, which must be a regular non-empty file
+pub fn validate_nonempty_file(path: &str) -> Result<(), ValidationError> {
+    validate_unix_path(path)?;
+    fs_checks::nonempty_file(Path::new(path))
+}
+
+/// How host paths are resolved before they are sent. Symlinks resolve
+/// differently once the jailer chroots the VMM, so deployments can either
+/// send fully resolved paths or refuse symlinks altogether. Only applies
+/// under [`ValidationMode::StrictLocal`], since it needs the files on this
+/// host, and not at all in `no-fs-validation` builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathPolicy {
+    /// Resolve symlinks and `.` components and send the resulting path. A
+    /// path that doesn't exist yet is resolved through its parent directory.
+    pub canonicalize: bool,
+    /// Accept paths that pass through a symlink.
+    pub allow_symlinks: bool,
+}
+
+impl Default for PathPolicy {
+    fn default() -> Self {
+        Self {
+            canonicalize: false,
+            allow_symlinks: true,
+        }
+    }
+}
+
+/// Applies `policy` to `path`, returning the path to send in its place.
+/// Symlinks are rejected with a validation error on `field`; a path that
+/// can't be resolved is a `FileSystem` error.
+///
+/// In `no-fs-validation` builds neither check can run: `path` is returned
+/// as given, unresolved, and symlinks are not looked for even when
+/// `allow_symlinks` is off.
+pub fn apply_path_policy(
+    field: &'static str,
+    path: &str,
+    policy: &PathPolicy,
+) -> Result<String, crate::FirecrackerError> {
+    if !policy.allow_symlinks {
+        fs_checks::no_symlinks(Path::new(path)).map_err(|e| field_validation_errors(field, e))?;
+    }
+    if !policy.canonicalize {
+        return Ok(path.to_string());
+    }
+
+    let resolved = fs_checks::canonicalize(Path::new(path)).map_err(|source| {
+        crate::FirecrackerError::FileSystem {
+            path: path.into(),
+            source,
+        }
+    })?;
+    resolved
+        .into_os_string()
+        .into_string()
+        .map_err(|p| crate::FirecrackerError::InvalidPath(p.to_string_lossy().into_owned()))
+}
+
+// The parts of the path validators that touch the filesystem. Building with
+// the no-fs-validation feature replaces them with no-ops, for processes whose
+// sandbox forbids stat() on arbitrary paths; the syntax checks still run.
+#[cfg(not(feature = "no-fs-validation"))]
+mod fs_checks {
+    use super::{failed_check, path_validation_error, SocketPathOptions};
+    use std::path::{Path, PathBuf};
+    use validator::ValidationError;
+
+    // Fails if the path or any directory leading to it is a symlink
+    pub fn no_symlinks(path: &Path) -> Result<(), ValidationError> {
+        let mut prefix = PathBuf::new();
+        for component in path.components() {
+            prefix.push(component);
+            let is_symlink = prefix
+                .symlink_metadata()
+                .is_ok_and(|m| m.file_type().is_symlink());
+            if is_symlink {
+                return Err(failed_check(
+                    "no_symlinks",
+                    format!("{} is a symlink", prefix.display()),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+        if path.symlink_metadata().is_ok() {
+            return path.canonicalize();
+        }
+        // Files Firecracker creates don't exist yet; resolve their directory
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Ok(parent.canonicalize()?.join(name)),
+            _ => path.canonicalize(),
+        }
+    }
+
+    pub fn existing(path: &Path) -> Result<(), ValidationError> {
+        if !path.exists() {
+            return Err(path_validation_error("Path does not exist"));
+        }
+
+        Ok(())
+    }
+
+    pub fn writable(path: &Path) -> Result<(), ValidationError> {
+        // If path exists, check if it's writable
+        if path.exists() {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                if let Ok(metadata) = path.metadata() {
+                    let mode = metadata.mode();
+                    if mode & 0o200 == 0 {
+                        return Err(failed_check(
+                            "writable",
+                            format!("{} is not writable", path.display()),
+                        ));
+                    }
                 }
             }
+        } else {
+            // If path doesn't exist, check if parent directory is writable
+            parent_writable(path)?;
         }
-    } else {
-        // If path doesn't exist, check if parent directory is writable
+
+        Ok(())
+    }
+
+    // The directory a new file will be created in must exist and be writable
+    fn parent_writable(path: &Path) -> Result<(), ValidationError> {
         if let Some(parent) = path.parent() {
             if !parent.exists() {
-                return Err(path_validation_error("Parent directory does not exist"));
+                return Err(failed_check(
+                    "parent_exists",
+                    format!("Parent directory {} does not exist", parent.display()),
+                ));
+            }
+            if !parent.is_dir() {
+                return Err(failed_check(
+                    "parent_is_dir",
+                    format!("Parent {} is not a directory", parent.display()),
+                ));
             }
             #[cfg(unix)]
             {
@@ -75,16 +381,880 @@ pub fn validate_writable_path(path: &str) -> Result<(), ValidationError> {
                 if let Ok(metadata) = parent.metadata() {
                     let mode = metadata.mode();
                     if mode & 0o200 == 0 {
-                        return Err(path_validation_error("Parent directory is not writable"));
+                        return Err(failed_check(
+                            "parent_writable",
+                            format!("Parent directory {} is not writable", parent.display()),
+                        ));
                     }
                 }
             }
         }
+
+        Ok(())
+    }
+
+    pub fn socket(path: &Path, options: &SocketPathOptions) -> Result<(), ValidationError> {
+        match path.symlink_metadata() {
+            Ok(metadata) => {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::FileTypeExt;
+                    if !metadata.file_type().is_socket() && !options.allow_replace {
+                        return Err(failed_check(
+                            "is_socket",
+                            format!("{} exists and is not a socket", path.display()),
+                        ));
+                    }
+                }
+                #[cfg(not(unix))]
+                let _ = metadata;
+            }
+            Err(_) if !options.create => {
+                return Err(failed_check(
+                    "exists",
+                    format!("{} does not exist", path.display()),
+                ));
+            }
+            Err(_) => {}
+        }
+
+        if options.create {
+            parent_writable(path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn new_file(path: &Path, allow_overwrite: bool) -> Result<(), ValidationError> {
+        writable(path)?;
+
+        if !allow_overwrite && path.exists() {
+            return Err(failed_check(
+                "no_overwrite",
+                format!(
+                    "{} already exists; set allow_overwrite to replace it",
+                    path.display()
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn nonempty_file(path: &Path) -> Result<(), ValidationError> {
+        let metadata = match path.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                return Err(failed_check(
+                    "exists",
+                    format!("{} does not exist", path.display()),
+                ))
+            }
+        };
+        if !metadata.is_file() {
+            return Err(failed_check(
+                "is_file",
+                format!("{} is not a regular file", path.display()),
+            ));
+        }
+        if metadata.len() == 0 {
+            return Err(failed_check(
+                "non_empty",
+                format!("{} is empty", path.display()),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "no-fs-validation")]
+mod fs_checks {
+    use super::SocketPathOptions;
+    use std::path::{Path, PathBuf};
+    use validator::ValidationError;
+
+    // Skipped, not passed: the path may well go through a symlink.
+    // `apply_path_policy` documents this.
+    pub fn no_symlinks(_path: &Path) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    // The path is sent unresolved
+    pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    pub fn existing(_path: &Path) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    pub fn writable(_path: &Path) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    pub fn socket(_path: &Path, _options: &SocketPathOptions) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    pub fn new_file(_path: &Path, _allow_overwrite: bool) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    pub fn nonempty_file(_path: &Path) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
+
+// Logger and metrics output must not share a file, and neither may live
+// inside the other when one of them is a directory
+pub fn validate_output_paths(logger: &Logger, metrics: &Metrics) -> Result<(), ValidationError> {
+    let log_path = Path::new(&logger.log_path);
+    let metrics_path = Path::new(&metrics.metrics_path);
+
+    if log_path == metrics_path {
+        return Err(failed_check(
+            "distinct_outputs",
+            format!(
+                "log_path and metrics_path both point to {}",
+                log_path.display()
+            ),
+        ));
+    }
+
+    if metrics_path.starts_with(log_path) || log_path.starts_with(metrics_path) {
+        return Err(failed_check(
+            "nested_outputs",
+            format!(
+                "log_path {} and metrics_path {} are nested inside each other",
+                log_path.display(),
+                metrics_path.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// How serious a [`Finding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Firecracker will reject the configuration or the VM won't boot.
+    Error,
+    /// Allowed, but probably not what was meant.
+    Warning,
+}
+
+/// A single problem found by [`validate_vm_config`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Stable identifier of the rule that fired, e.g. `duplicate_drive_id`
+    pub rule: &'static str,
+    /// Where in the configuration the problem is, e.g. `drives[1].drive_id`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{} [{}] {}: {}",
+            severity, self.rule, self.path, self.message
+        )
+    }
+}
+
+// Lets lint findings be reported alongside field errors
+impl From<&Finding> for ValidationError {
+    fn from(finding: &Finding) -> Self {
+        let mut error = ValidationError::new(finding.rule);
+        error.message = Some(format!("{}: {}", finding.path, finding.message).into());
+        error.add_param("path".into(), &finding.path);
+        error
+    }
+}
+
+/// A problem that doesn't fail the call that found it, such as a lint
+/// warning from `validate_config` or `apply_config`, or from a `put_*` call
+/// that makes the sections the client has sent inconsistent. The client
+/// collects them until
+/// [`take_warnings`](crate::FirecrackerClient::take_warnings), or hands them
+/// to the callback set with
+/// [`with_warning_callback`](crate::FirecrackerClient::with_warning_callback).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigWarning {
+    /// Stable identifier of the rule that fired, e.g.
+    /// `boot_args_unbalanced_quotes`
+    pub rule: &'static str,
+    pub message: String,
+    /// What it is about, e.g. `boot_source.boot_args`
+    pub context: String,
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.rule, self.context, self.message)
+    }
+}
+
+impl From<&Finding> for ConfigWarning {
+    fn from(finding: &Finding) -> Self {
+        Self {
+            rule: finding.rule,
+            message: finding.message.clone(),
+            context: finding.path.clone(),
+        }
+    }
+}
+
+/// Findings from checking a whole VM configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Fails if any finding is an error; warnings alone pass and are handed
+    /// back with the report.
+    pub fn into_result(self) -> Result<Self, crate::FirecrackerError> {
+        if self.has_errors() {
+            Err(crate::FirecrackerError::InvalidConfig(self))
+        } else {
+            Ok(self)
+        }
+    }
+
+    fn push(&mut self, severity: Severity, rule: &'static str, path: String, message: String) {
+        self.findings.push(Finding {
+            severity,
+            rule,
+            path,
+            message,
+        });
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, finding) in self.findings.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+// Firecracker's default guest memory when machine_config leaves it unset
+const DEFAULT_MEM_SIZE_MIB: u32 = 128;
+
+// A balloon target from this share of guest memory on leaves the guest too
+// little to run on
+const BALLOON_NEAR_MEMORY_PERCENT: u64 = 90;
+
+// The first host kernel whose io_uring Firecracker's Async IO engine can use
+const ASYNC_IO_ENGINE_MIN_KERNEL: (u32, u32, u32) = (5, 10, 51);
+
+/// Checks a whole VM configuration for problems that per-field validation
+/// can't see, because they involve more than one section or item.
+///
+/// Rules:
+/// - `root_drive_missing`: no root drive and no initrd to boot from
+/// - `multiple_root_drives`: more than one drive has `is_root_device` set
+/// - `duplicate_drive_id`, `duplicate_iface_id`: ids must be unique
+/// - `duplicate_guest_mac`: two interfaces share a MAC address
+/// - `duplicate_host_dev_name`: two interfaces share a tap device
+/// - `balloon_exceeds_memory`: the balloon target is larger than guest memory
+/// - `mmds_without_interface`: MMDS is configured but reachable from no interface
+/// - `mmds_unknown_interface`: MMDS names an interface that isn't configured
+/// - `shared_output_path`: logger and metrics write to the same place
+/// - `boot_args_unbalanced_quotes` (warning): the boot arguments have an odd
+///   number of `"`, so the kernel reads the rest of the line as one value
+/// - `boot_args_root_conflict` (warning): the boot arguments set `root=`
+///   while a drive is the root device, for which Firecracker adds its own
+/// - `root_read_only_mismatch` (warning): the boot arguments mount the root
+///   drive `rw` while it is read-only, or `ro` while it is writable
+/// - `balloon_near_memory` (warning): the balloon target is at least 90% of
+///   guest memory
+pub fn validate_vm_config(config: &FullVmConfiguration) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let has_initrd = config
+        .boot_source
+        .as_ref()
+        .is_some_and(|b| b.initrd_path.is_some());
+    let root_drives: Vec<usize> = config
+        .drives
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.is_root_device)
+        .map(|(i, _)| i)
+        .collect();
+    if root_drives.is_empty() && !has_initrd {
+        report.push(
+            Severity::Error,
+            "root_drive_missing",
+            "drives".to_string(),
+            "No drive is marked as the root device and no initrd is set".to_string(),
+        );
+    }
+    for &i in root_drives.iter().skip(1) {
+        report.push(
+            Severity::Error,
+            "multiple_root_drives",
+            format!("drives[{}].is_root_device", i),
+            format!(
+                "Drive {:?} is a second root device; drive {:?} already is",
+                config.drives[i].drive_id, config.drives[root_drives[0]].drive_id
+            ),
+        );
+    }
+
+    check_unique(
+        &mut report,
+        "duplicate_drive_id",
+        config
+            .drives
+            .iter()
+            .enumerate()
+            .map(|(i, d)| (format!("drives[{}].drive_id", i), d.drive_id.clone())),
+    );
+    check_unique(
+        &mut report,
+        "duplicate_iface_id",
+        config.network_interfaces.iter().enumerate().map(|(i, n)| {
+            (
+                format!("network_interfaces[{}].iface_id", i),
+                n.iface_id.clone(),
+            )
+        }),
+    );
+    check_unique(
+        &mut report,
+        "duplicate_guest_mac",
+        config
+            .network_interfaces
+            .iter()
+            .enumerate()
+            .filter_map(|(i, n)| {
+                let mac = n.guest_mac.as_ref()?.to_ascii_lowercase().replace('-', ":");
+                Some((format!("network_interfaces[{}].guest_mac", i), mac))
+            }),
+    );
+    check_unique(
+        &mut report,
+        "duplicate_host_dev_name",
+        config.network_interfaces.iter().enumerate().map(|(i, n)| {
+            (
+                format!("network_interfaces[{}].host_dev_name", i),
+                n.host_dev_name.clone(),
+            )
+        }),
+    );
+
+    if let Some(balloon) = &config.balloon {
+        let mem_size_mib = config
+            .machine_config
+            .as_ref()
+            .and_then(|m| m.mem_size_mib)
+            .unwrap_or(DEFAULT_MEM_SIZE_MIB);
+        if balloon.amount_mib > mem_size_mib {
+            report.push(
+                Severity::Error,
+                "balloon_exceeds_memory",
+                "balloon.amount_mib".to_string(),
+                format!(
+                    "Balloon target of {} MiB is larger than the {} MiB of guest memory",
+                    balloon.amount_mib, mem_size_mib
+                ),
+            );
+        } else if u64::from(balloon.amount_mib) * 100
+            >= u64::from(mem_size_mib) * BALLOON_NEAR_MEMORY_PERCENT
+        {
+            report.push(
+                Severity::Warning,
+                "balloon_near_memory",
+                "balloon.amount_mib".to_string(),
+                format!(
+                    "Balloon target of {} MiB leaves the guest {} of its {} MiB",
+                    balloon.amount_mib,
+                    mem_size_mib - balloon.amount_mib,
+                    mem_size_mib
+                ),
+            );
+        }
+    }
+
+    if let Some(mmds) = &config.mmds_config {
+        if mmds.network_interfaces.is_empty() {
+            report.push(
+                Severity::Error,
+                "mmds_without_interface",
+                "mmds_config.network_interfaces".to_string(),
+                "MMDS is configured but no network interface can reach it".to_string(),
+            );
+        }
+        for (i, iface_id) in mmds.network_interfaces.iter().enumerate() {
+            if !config
+                .network_interfaces
+                .iter()
+                .any(|n| &n.iface_id == iface_id)
+            {
+                report.push(
+                    Severity::Error,
+                    "mmds_unknown_interface",
+                    format!("mmds_config.network_interfaces[{}]", i),
+                    format!("Network interface {:?} is not configured", iface_id),
+                );
+            }
+        }
+    }
+
+    if let (Some(logger), Some(metrics)) = (&config.logger, &config.metrics) {
+        if let Err(e) = validate_output_paths(logger, metrics) {
+            report.push(
+                Severity::Error,
+                "shared_output_path",
+                "metrics.metrics_path".to_string(),
+                e.message.map(|m| m.into_owned()).unwrap_or_default(),
+            );
+        }
+    }
+
+    if let Some(args) = config
+        .boot_source
+        .as_ref()
+        .and_then(|b| b.boot_args.as_ref())
+    {
+        if args.matches('"').count() % 2 != 0 {
+            report.push(
+                Severity::Warning,
+                "boot_args_unbalanced_quotes",
+                "boot_source.boot_args".to_string(),
+                "Boot arguments have an unmatched '\"'".to_string(),
+            );
+        }
+        if let Some(&i) = root_drives.first() {
+            if args.split_whitespace().any(|arg| arg.starts_with("root=")) {
+                report.push(
+                    Severity::Warning,
+                    "boot_args_root_conflict",
+                    "boot_source.boot_args".to_string(),
+                    format!(
+                        "Boot arguments set root= while drive {:?} is the root device",
+                        config.drives[i].drive_id
+                    ),
+                );
+            }
+            // The kernel goes by the last of them
+            let root = &config.drives[i];
+            let mode = args
+                .split_whitespace()
+                .rfind(|arg| *arg == "ro" || *arg == "rw");
+            if mode == Some(if root.is_read_only { "rw" } else { "ro" }) {
+                report.push(
+                    Severity::Warning,
+                    "root_read_only_mismatch",
+                    format!("drives[{}].is_read_only", i),
+                    format!(
+                        "Boot arguments mount the root file system {} but drive {:?} is {}",
+                        mode.unwrap_or_default(),
+                        root.drive_id,
+                        if root.is_read_only {
+                            "read-only"
+                        } else {
+                            "writable"
+                        }
+                    ),
+                );
+            }
+        }
+    }
+
+    report
+}
+
+/// Checks what `config` needs of the host the VMM runs on, given the host's
+/// kernel release as `uname -r` prints it. A release that doesn't start with
+/// a version number passes.
+///
+/// Rules:
+/// - `async_io_engine_old_kernel` (warning): a drive uses the `Async` IO
+///   engine, whose io_uring support needs host kernel 5.10.51 or later
+pub fn validate_host_support(
+    config: &FullVmConfiguration,
+    kernel_release: &str,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let Some(kernel) = kernel_version(kernel_release) else {
+        return report;
+    };
+    if kernel >= ASYNC_IO_ENGINE_MIN_KERNEL {
+        return report;
+    }
+    for (i, drive) in config.drives.iter().enumerate() {
+        if drive.io_engine.as_deref() == Some("Async") {
+            report.push(
+                Severity::Warning,
+                "async_io_engine_old_kernel",
+                format!("drives[{}].io_engine", i),
+                format!(
+                    "Drive {:?} uses the Async IO engine, which host kernel {} is too old for; it needs 5.10.51 or later",
+                    drive.drive_id, kernel_release
+                ),
+            );
+        }
+    }
+    report
+}
+
+// The leading `major.minor.patch` of a kernel release such as
+// `5.10.186-179.751.amzn2.x86_64`; a missing patch level reads as 0
+fn kernel_version(release: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = release
+        .split(|c: char| !c.is_ascii_digit())
+        .take(3)
+        .map(|part| part.parse::<u32>().ok());
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+// The running kernel's release, as `uname -r` prints it
+pub(crate) fn host_kernel_release() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|release| release.trim().to_string())
+}
+
+// Reports every item whose key was already used by an earlier one
+fn check_unique(
+    report: &mut ValidationReport,
+    rule: &'static str,
+    items: impl Iterator<Item = (String, String)>,
+) {
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for (path, key) in items {
+        match seen.get(&key) {
+            Some(first) => report.push(
+                Severity::Error,
+                rule,
+                path,
+                format!("{:?} is already used by {}", key, first),
+            ),
+            None => {
+                seen.insert(key, path);
+            }
+        }
+    }
+}
+
+// Firecracker requires the MMDS store root to be a JSON object
+pub fn validate_mmds_root(data: &Value) -> Result<(), ValidationError> {
+    let actual = match data {
+        Value::Object(_) => return Ok(()),
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+    };
+
+    let mut err = ValidationError::new("invalid_mmds_root");
+    err.message = Some(format!("MMDS root must be a JSON object, got {}", actual).into());
+    Err(err)
+}
+
+// The serialized size of an MMDS body against the VMM's data store limit
+pub fn validate_mmds_size(data: &Value, limit: usize) -> Result<(), ValidationError> {
+    let size = serde_json::to_vec(data)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0);
+    if size <= limit {
+        return Ok(());
+    }
+
+    let mut err = ValidationError::new("mmds_too_large");
+    err.message = Some(
+        format!(
+            "MMDS data is {} bytes, over the {} byte limit of the data store",
+            size, limit
+        )
+        .into(),
+    );
+    err.add_param("size".into(), &size);
+    err.add_param("limit".into(), &limit);
+    Err(err)
+}
+
+/// Largest request body a client sends unless told otherwise, in bytes.
+/// Firecracker handles API requests on one thread, so a runaway body stalls
+/// every other request to the VMM.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+// The serialized size of a request body against the client's limit;
+// `operation` is e.g. `PUT /mmds`
+pub fn validate_body_size(
+    operation: &str,
+    size: usize,
+    limit: usize,
+) -> Result<(), ValidationError> {
+    if size <= limit {
+        return Ok(());
+    }
+
+    let mut err = ValidationError::new("body_too_large");
+    err.message = Some(
+        format!(
+            "{} body is {} bytes, over the {} byte limit",
+            operation, size, limit
+        )
+        .into(),
+    );
+    err.add_param("operation".into(), &operation);
+    err.add_param("size".into(), &size);
+    err.add_param("limit".into(), &limit);
+    Err(err)
+}
+
+// Validates an RFC 6901 JSON pointer addressing a key below the MMDS root.
+// Empty reference tokens are keys named "" as the RFC allows, so `/` and
+// `/a//b` are accepted.
+pub fn validate_mmds_pointer(pointer: &str) -> Result<(), ValidationError> {
+    let invalid = |message: &'static str| {
+        let mut err = ValidationError::new("invalid_mmds_pointer");
+        err.message = Some(message.into());
+        err
+    };
+
+    if pointer.is_empty() {
+        return Err(invalid("Pointer cannot address the MMDS root"));
+    }
+    if !pointer.starts_with('/') {
+        return Err(invalid("Pointer must start with '/'"));
+    }
+    let mut chars = pointer.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' && !matches!(chars.next(), Some('0') | Some('1')) {
+            return Err(invalid("Pointer contains an invalid '~' escape"));
+        }
+    }
+    Ok(())
+}
+
+// Validates a VM tag kept under the reserved MMDS subtree: a key of ASCII
+// letters, digits, '-', '_', '.' and ':', and a value no longer than the cap
+pub fn validate_mmds_tag(key: &str, value: &str) -> Result<(), ValidationError> {
+    use crate::mmds::{MAX_TAG_KEY_LEN, MAX_TAG_VALUE_LEN};
+
+    let key_ok = !key.is_empty()
+        && key.len() <= MAX_TAG_KEY_LEN
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    if !key_ok {
+        let mut err = ValidationError::new("invalid_tag_key");
+        err.message = Some(
+            format!(
+                "Tag key {:?} must be 1 to {} ASCII letters, digits, '-', '_', '.' or ':'",
+                key, MAX_TAG_KEY_LEN
+            )
+            .into(),
+        );
+        return Err(err);
+    }
+
+    if value.len() > MAX_TAG_VALUE_LEN {
+        let mut err = ValidationError::new("tag_too_large");
+        err.message = Some(
+            format!(
+                "Tag {:?} is {} bytes, over the {} byte limit",
+                key,
+                value.len(),
+                MAX_TAG_VALUE_LEN
+            )
+            .into(),
+        );
+        err.add_param("size".into(), &value.len());
+        err.add_param("limit".into(), &MAX_TAG_VALUE_LEN);
+        return Err(err);
     }
 
     Ok(())
 }
 
+/// Validation for PATCH bodies. A PATCH only changes some fields of a
+/// resource that was validated in full when it was created, so the rules
+/// cover just the fields Firecracker lets a PATCH update.
+pub trait ValidatePatch {
+    fn validate_patch(&self) -> Result<(), ValidationErrors>;
+
+    /// How the body is shown to custom validators. PATCH bodies get their
+    /// own variants since most of their fields may be left empty.
+    fn patch_target(&self) -> ValidationTarget<'_>;
+}
+
+/// A request body as seen by the custom validators registered with
+/// [`crate::FirecrackerClient::add_validator`].
+#[derive(Debug, Clone, Copy)]
+pub enum ValidationTarget<'a> {
+    Action(&'a crate::action::InstanceActionInfo),
+    Balloon(&'a crate::models::Balloon),
+    BalloonUpdate(&'a crate::balloon::BalloonUpdate),
+    BalloonStatsUpdate(&'a crate::balloon::BalloonStatsUpdate),
+    BootSource(&'a crate::models::BootSource),
+    CpuConfig(&'a crate::cpu::CpuConfig),
+    Drive(&'a crate::models::Drive),
+    DriveUpdate(&'a crate::drive::DriveUpdate),
+    EntropyDevice(&'a crate::models::EntropyDevice),
+    Logger(&'a Logger),
+    MachineConfig(&'a crate::models::MachineConfig),
+    MachineConfigUpdate(&'a crate::models::MachineConfig),
+    Metrics(&'a Metrics),
+    Mmds(&'a Value),
+    MmdsUpdate(&'a Value),
+    MmdsConfig(&'a crate::models::MmdsConfig),
+    NetworkInterface(&'a crate::models::NetworkInterface),
+    NetworkInterfaceUpdate(&'a crate::network::NetworkInterfaceUpdate),
+    SnapshotCreate(&'a crate::snapshot::SnapshotCreateParams),
+    SnapshotLoad(&'a crate::snapshot::SnapshotLoadParams),
+    VmConfig(&'a crate::vm::VmConfig),
+    Vsock(&'a crate::models::Vsock),
+}
+
+/// Maps a request model to the [`ValidationTarget`] custom validators see.
+pub trait AsValidationTarget {
+    fn validation_target(&self) -> ValidationTarget<'_>;
+}
+
+macro_rules! validation_targets {
+    ($($model:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl AsValidationTarget for $model {
+                fn validation_target(&self) -> ValidationTarget<'_> {
+                    ValidationTarget::$variant(self)
+                }
+            }
+        )*
+    };
+}
+
+validation_targets! {
+    crate::action::InstanceActionInfo => Action,
+    crate::models::Balloon => Balloon,
+    crate::models::BootSource => BootSource,
+    crate::cpu::CpuConfig => CpuConfig,
+    crate::models::Drive => Drive,
+    crate::models::EntropyDevice => EntropyDevice,
+    Logger => Logger,
+    crate::models::MachineConfig => MachineConfig,
+    Metrics => Metrics,
+    crate::models::MmdsConfig => MmdsConfig,
+    crate::models::NetworkInterface => NetworkInterface,
+    crate::snapshot::SnapshotCreateParams => SnapshotCreate,
+    crate::snapshot::SnapshotLoadParams => SnapshotLoad,
+    crate::vm::VmConfig => VmConfig,
+    crate::models::Vsock => Vsock,
+}
+
+/// A house rule checked after the built-in validation of each request. The
+/// errors it returns are reported as a `Validation` error.
+pub type CustomValidator = Box<dyn Fn(&ValidationTarget<'_>) -> Vec<ValidationError> + Send + Sync>;
+
+// Wraps a single field error so it can be returned as a FirecrackerError
+pub fn field_validation_errors(field: &'static str, error: ValidationError) -> ValidationErrors {
+    let mut errors = ValidationErrors::new();
+    errors.add(field, error);
+    errors
+}
+
+/// Merges validation results for the same struct, keeping every error. A
+/// nested entry present in more than one result keeps the last one.
+pub fn combine_errors(
+    results: impl IntoIterator<Item = Result<(), ValidationErrors>>,
+) -> Result<(), ValidationErrors> {
+    let mut combined = ValidationErrors::new();
+    for errors in results.into_iter().filter_map(Result::err) {
+        for (field, kind) in errors.into_errors() {
+            match kind {
+                ValidationErrorsKind::Field(errors) => {
+                    for error in errors {
+                        combined.add(field, error);
+                    }
+                }
+                nested => {
+                    combined.errors_mut().insert(field, nested);
+                }
+            }
+        }
+    }
+
+    if combined.is_empty() {
+        Ok(())
+    } else {
+        Err(combined)
+    }
+}
+
+/// Flattens nested validation errors into one entry per error, keyed by a
+/// path such as `drives[1].path_on_host` and sorted by it. Errors the
+/// validation layer adds for a whole struct are keyed by the struct's path.
+pub fn error_paths(errors: &ValidationErrors) -> Vec<(String, &ValidationError)> {
+    fn walk<'a>(
+        prefix: &str,
+        errors: &'a ValidationErrors,
+        out: &mut Vec<(String, &'a ValidationError)>,
+    ) {
+        for (field, kind) in errors.errors() {
+            let path = match (prefix.is_empty(), *field) {
+                (true, field) => field.to_string(),
+                (false, "__all__") => prefix.to_string(),
+                (false, field) => format!("{}.{}", prefix, field),
+            };
+            match kind {
+                ValidationErrorsKind::Field(field_errors) => {
+                    out.extend(field_errors.iter().map(|e| (path.clone(), e)));
+                }
+                ValidationErrorsKind::Struct(nested) => walk(&path, nested, out),
+                ValidationErrorsKind::List(items) => {
+                    for (i, nested) in items {
+                        walk(&format!("{}[{}]", path, i), nested, out);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    walk("", errors, &mut out);
+    out.sort_by(|a, b| a.0.cmp(&b.0));
+    out
+}
+
 // Macro to implement path validation for a struct field
 #[macro_export]
 macro_rules! validate_path {
@@ -1,25 +1,226 @@
-use crate::models::FirecrackerVersion;
+use crate::capabilities::Feature;
+use crate::instance::InstanceOperations;
+use crate::models::{decode_response, FirecrackerVersion, VersionBody};
+use crate::paths;
 use crate::FirecrackerError;
 use async_trait::async_trait;
+use reqwest::Method;
+
+pub use semver::{Version, VersionReq};
+
+/// Parses a Firecracker version string. Besides plain semver this accepts a
+/// leading `v` and a missing patch number, and keeps suffixes such as
+/// `-dev` or `-rc1` as the prerelease part.
+pub fn parse_version(raw: &str) -> Result<Version, FirecrackerError> {
+    let trimmed = raw.trim().trim_start_matches('v');
+    let (release, suffix) = match trimmed.find(['-', '+']) {
+        Some(i) => trimmed.split_at(i),
+        None => (trimmed, ""),
+    };
+    let release = match release.matches('.').count() {
+        1 => format!("{}.0", release),
+        _ => release.to_string(),
+    };
+
+    Version::parse(&format!("{}{}", release, suffix)).map_err(|e| {
+        FirecrackerError::Config(format!("Invalid Firecracker version {:?}: {}", raw, e))
+    })
+}
+
+// Decodes a `GET /version` body. Besides the JSON shapes this takes an
+// unquoted version as plain text, as long as it parses.
+fn decode_version(body: String) -> Result<FirecrackerVersion, FirecrackerError> {
+    match decode_response::<FirecrackerVersion>(body) {
+        Err(FirecrackerError::Decode { message, body }) => {
+            let version = FirecrackerVersion::from(VersionBody::Bare(body.clone()));
+            match version.semver() {
+                Ok(_) => Ok(version),
+                Err(_) => Err(FirecrackerError::Decode { message, body }),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Whether `version` is release `min` or later. Prerelease builds count as
+/// the release they lead up to, so `1.7.0-dev` meets a `1.7.0` minimum.
+pub fn meets(version: &Version, min: &Version) -> bool {
+    (version.major, version.minor, version.patch) >= (min.major, min.minor, min.patch)
+}
+
+/// Whether `version` satisfies `req`, treating prerelease builds as the
+/// release they lead up to.
+pub fn satisfies(version: &Version, req: &VersionReq) -> bool {
+    req.matches(&Version::new(version.major, version.minor, version.patch))
+}
+
+/// First release that exposes `PUT /entropy`.
+pub const ENTROPY_MIN_VERSION: Version = Version::new(1, 3, 0);
+
+/// First release that rejects `vsock_id` in `PUT /vsock`; older releases
+/// require it.
+pub const VSOCK_ID_REMOVED_VERSION: Version = Version::new(1, 0, 0);
+
+/// First release with the vhost-user block device backend.
+pub const VHOST_USER_BLOCK_MIN_VERSION: Version = Version::new(1, 7, 0);
+
+/// First release whose snapshot load accepts `resume_vm`.
+pub const SNAPSHOT_RESUME_VM_MIN_VERSION: Version = Version::new(1, 0, 0);
+
+/// First release that rejects `version` in snapshot create requests;
+/// older releases require it.
+pub const SNAPSHOT_VERSION_REMOVED_VERSION: Version = Version::new(1, 5, 0);
+
+/// First release whose snapshot load accepts `mem_backend`.
+pub const MEM_BACKEND_MIN_VERSION: Version = Version::new(1, 1, 0);
+
+/// First release whose snapshot load accepts `network_overrides`.
+pub const NETWORK_OVERRIDES_MIN_VERSION: Version = Version::new(1, 6, 0);
+
+/// First release with MMDS version 2 (session tokens).
+pub const MMDS_V2_MIN_VERSION: Version = Version::new(1, 0, 0);
+
+/// First release whose machine config accepts `huge_pages`.
+pub const HUGE_PAGES_MIN_VERSION: Version = Version::new(1, 7, 0);
+
+/// Where the client learned the VMM version from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSource {
+    /// `GET /version`
+    VersionEndpoint,
+    /// The `vmm_version` field of `GET /`, used when `/version` is blocked
+    InstanceInfo,
+    /// Set with `FirecrackerClient::set_vmm_version`
+    Manual,
+}
+
+/// What the connected VMM supports, derived from its version through the
+/// [`capabilities`](crate::capabilities) table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: Version,
+    pub source: VersionSource,
+    pub supports_entropy: bool,
+    pub supports_vhost_user_block: bool,
+    pub supports_snapshot_resume_vm: bool,
+    pub requires_snapshot_version: bool,
+    pub supports_mem_backend: bool,
+    pub supports_network_overrides: bool,
+    pub supports_mmds_v2: bool,
+    pub supports_huge_pages: bool,
+}
+
+impl Capabilities {
+    pub fn for_version(version: Version) -> Self {
+        Self {
+            supports_entropy: Feature::Entropy.is_supported_by(&version),
+            supports_vhost_user_block: Feature::VhostUserBlock.is_supported_by(&version),
+            supports_snapshot_resume_vm: Feature::SnapshotResumeVm.is_supported_by(&version),
+            requires_snapshot_version: !meets(&version, &SNAPSHOT_VERSION_REMOVED_VERSION),
+            supports_mem_backend: Feature::SnapshotMemBackend.is_supported_by(&version),
+            supports_network_overrides: Feature::NetworkOverrides.is_supported_by(&version),
+            supports_mmds_v2: Feature::MmdsV2.is_supported_by(&version),
+            supports_huge_pages: Feature::HugePages.is_supported_by(&version),
+            version,
+            source: VersionSource::Manual,
+        }
+    }
+
+    /// Whether `feature` is supported.
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::Entropy => self.supports_entropy,
+            Feature::VhostUserBlock => self.supports_vhost_user_block,
+            Feature::SnapshotResumeVm => self.supports_snapshot_resume_vm,
+            Feature::SnapshotMemBackend => self.supports_mem_backend,
+            Feature::NetworkOverrides => self.supports_network_overrides,
+            Feature::MmdsV2 => self.supports_mmds_v2,
+            Feature::HugePages => self.supports_huge_pages,
+        }
+    }
+
+    fn discovered(version: Version, source: VersionSource) -> Self {
+        Self {
+            source,
+            ..Self::for_version(version)
+        }
+    }
+}
 
 #[async_trait]
 pub trait VersionOperations {
     async fn get_version(&self) -> Result<FirecrackerVersion, FirecrackerError>;
+
+    /// Finds the VMM version from `GET /version`, falling back to the
+    /// instance info when the endpoint answers with an error.
+    async fn discover_version(&self) -> Result<(Version, VersionSource), FirecrackerError>;
+
+    /// Fetches the VMM version unless it is already known and returns the
+    /// capabilities derived from it.
+    async fn negotiate(&self) -> Result<Capabilities, FirecrackerError>;
+
+    /// Negotiates if needed and fails unless the VMM version satisfies `req`.
+    async fn require_version(&self, req: &VersionReq) -> Result<Capabilities, FirecrackerError>;
 }
 
 #[async_trait]
 impl VersionOperations for crate::FirecrackerClient {
     async fn get_version(&self) -> Result<FirecrackerVersion, FirecrackerError> {
-        let url = self.url("version")?;
-        let response = self.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
+        let version = decode_version(self.execute(Method::GET, paths::VERSION, None).await?)?;
+        if let Ok(parsed) = version.semver() {
+            let capabilities = Capabilities::discovered(parsed, VersionSource::VersionEndpoint);
+            self.set_capabilities(capabilities.clone());
+            if let Some(req) = self.min_version() {
+                check_version(&capabilities, req)?;
+            }
+        }
+        Ok(version)
+    }
+
+    async fn discover_version(&self) -> Result<(Version, VersionSource), FirecrackerError> {
+        match self.get_version().await {
+            Ok(version) => Ok((version.semver()?, VersionSource::VersionEndpoint)),
+            Err(err @ FirecrackerError::Api { .. }) => match self.describe_instance().await {
+                Ok(info) => Ok((info.semver()?, VersionSource::InstanceInfo)),
+                Err(_) => Err(err),
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn negotiate(&self) -> Result<Capabilities, FirecrackerError> {
+        let capabilities = match self.capabilities() {
+            Some(capabilities) => capabilities.clone(),
+            None => {
+                let (version, source) = self.discover_version().await?;
+                let capabilities = Capabilities::discovered(version, source);
+                self.set_capabilities(capabilities.clone());
+                capabilities
+            }
+        };
+
+        if let Some(req) = self.min_version() {
+            check_version(&capabilities, req)?;
         }
+        Ok(capabilities)
+    }
+
+    async fn require_version(&self, req: &VersionReq) -> Result<Capabilities, FirecrackerError> {
+        let capabilities = self.negotiate().await?;
+        check_version(&capabilities, req)?;
+        Ok(capabilities)
+    }
+}
 
-        Ok(response.json().await?)
+pub(crate) fn check_version(
+    capabilities: &Capabilities,
+    req: &VersionReq,
+) -> Result<(), FirecrackerError> {
+    if satisfies(&capabilities.version, req) {
+        return Ok(());
     }
+    Err(FirecrackerError::UnsupportedVmmVersion {
+        found: capabilities.version.to_string(),
+        required: req.to_string(),
+    })
 }
@@ -1,11 +1,19 @@
+use crate::config::ConfigFile;
+use crate::models::{FullVmConfiguration, InstanceState, Vm, VmState};
+use crate::paths;
 use async_trait::async_trait;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, Validate)]
+#[non_exhaustive]
 pub struct VmConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 32))]
     pub vcpu_count: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
     pub mem_size_mib: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ht_enabled: Option<bool>,
@@ -13,7 +21,8 @@ pub struct VmConfig {
     pub track_dirty_pages: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct VmInfo {
     pub state: String,
     pub id: String,
@@ -22,36 +31,50 @@ pub struct VmInfo {
 #[async_trait]
 pub trait VmOperations {
     async fn get_vm_info(&self) -> Result<VmInfo, crate::FirecrackerError>;
+    /// Reads back the configuration applied so far. Firecracker names the
+    /// sections as in a config file, e.g. `machine-config`.
+    async fn get_vm_config(&self) -> Result<FullVmConfiguration, crate::FirecrackerError>;
     async fn put_vm_config(&self, config: &VmConfig) -> Result<(), crate::FirecrackerError>;
+    async fn patch_vm(&self, vm: &Vm) -> Result<(), crate::FirecrackerError>;
+
+    async fn pause_vm(&self) -> Result<(), crate::FirecrackerError> {
+        self.patch_vm(&Vm {
+            state: VmState::Paused,
+        })
+        .await
+    }
+
+    async fn resume_vm(&self) -> Result<(), crate::FirecrackerError> {
+        self.patch_vm(&Vm {
+            state: VmState::Resumed,
+        })
+        .await
+    }
 }
 
 #[async_trait]
 impl VmOperations for crate::FirecrackerClient {
     async fn get_vm_info(&self) -> Result<VmInfo, crate::FirecrackerError> {
-        let url = self.url("vm")?;
-        let response = self.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            return Err(crate::FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
+        self.get_json(paths::VM).await
+    }
 
-        Ok(response.json().await?)
+    async fn get_vm_config(&self) -> Result<FullVmConfiguration, crate::FirecrackerError> {
+        self.get_json::<ConfigFile>(paths::VM_CONFIG)
+            .await
+            .map(Into::into)
     }
 
     async fn put_vm_config(&self, config: &VmConfig) -> Result<(), crate::FirecrackerError> {
-        let url = self.url("vm/config")?;
-        let response = self.client.put(url).json(config).send().await?;
-
-        if !response.status().is_success() {
-            return Err(crate::FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
-            });
-        }
+        self.validated_put(paths::VM_CONFIG, config).await
+    }
 
+    async fn patch_vm(&self, vm: &Vm) -> Result<(), crate::FirecrackerError> {
+        // VmState only has values Firecracker accepts, so there is nothing to validate
+        self.send_json(Method::PATCH, paths::VM, vm).await?;
+        self.observe_state(match vm.state {
+            VmState::Paused => InstanceState::Paused,
+            VmState::Resumed => InstanceState::Running,
+        });
         Ok(())
     }
 }
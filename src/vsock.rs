@@ -1,7 +1,83 @@
 use crate::models::Vsock;
+use crate::paths;
+use crate::validation::{
+    field_validation_errors, validate_socket_path, ConfigWarning, SocketPathOptions,
+};
+use crate::version::Version;
 use crate::FirecrackerError;
 use async_trait::async_trait;
-use validator::Validate;
+use reqwest::Method;
+use validator::ValidationErrors;
+
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::time::Duration;
+#[cfg(unix)]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+/// How long [`connect_to_guest`] waits for the `OK` reply.
+#[cfg(unix)]
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl crate::LocalPaths {
+    // Without a known version the body is sent as given. The socket doesn't
+    // exist yet, so the path policy doesn't apply, only the jailer's chroot.
+    pub(crate) fn vsock_body(
+        &self,
+        vsock: &Vsock,
+        version: Option<&Version>,
+    ) -> Result<Vsock, FirecrackerError> {
+        let mut body = match version {
+            Some(version) => vsock.for_version(version)?,
+            None => vsock.clone(),
+        };
+        if let Some(jailer) = &self.jailer {
+            body.uds_path = jailer
+                .translate(&body.uds_path)
+                .map_err(|e| field_validation_errors("uds_path", e))?;
+        }
+        Ok(body)
+    }
+
+    // Firecracker binds the socket itself
+    pub(crate) fn check_vsock_path(&self, vsock: &Vsock) -> Result<(), ValidationErrors> {
+        let options = SocketPathOptions {
+            create: true,
+            ..Default::default()
+        };
+        self.check_local_path("uds_path", &vsock.uds_path, |path| {
+            validate_socket_path(path, &options)
+        })
+    }
+}
+
+impl crate::FirecrackerClient {
+    // Sends `body`, the resolved form of `vsock`, warning about what the
+    // VMM's version ignores
+    pub(crate) async fn send_vsock(
+        &self,
+        vsock: &Vsock,
+        body: &Vsock,
+    ) -> Result<(), FirecrackerError> {
+        if let Some(version) = self.vmm_version() {
+            for message in vsock.version_warnings(&version) {
+                self.warn(ConfigWarning {
+                    rule: "vsock_id_ignored",
+                    message,
+                    context: "vsock.vsock_id".to_string(),
+                });
+            }
+        }
+        self.send_json(Method::PUT, paths::VSOCK, body).await
+    }
+}
+
+// Replies are "OK <port>\n"; anything much longer is not a handshake reply
+#[cfg(unix)]
+const MAX_HANDSHAKE_LINE: usize = 64;
 
 #[async_trait]
 pub trait VsockOperations {
@@ -11,18 +87,243 @@ pub trait VsockOperations {
 #[async_trait]
 impl VsockOperations for crate::FirecrackerClient {
     async fn put_vsock(&self, vsock: &Vsock) -> Result<(), FirecrackerError> {
-        vsock.validate()?;
+        self.validate_model(vsock)?;
 
-        let url = self.url("vsock")?;
-        let response = self.client.put(url).json(vsock).send().await?;
+        let (checked, version) = (vsock.clone(), self.vmm_version());
+        let body = self
+            .on_local_fs(move |local| {
+                local.check_vsock_path(&checked)?;
+                local.vsock_body(&checked, version.as_ref())
+            })
+            .await?;
+        self.send_vsock(vsock, &body).await
+    }
+}
 
-        if !response.status().is_success() {
-            return Err(FirecrackerError::Api {
-                status_code: response.status().as_u16(),
-                message: response.text().await?,
+/// Opens a host-initiated connection to `port` on the guest through the
+/// vsock device's `uds_path`, using [`CONNECT_TIMEOUT`].
+#[cfg(unix)]
+pub async fn connect_to_guest(
+    uds_path: impl AsRef<Path>,
+    port: u32,
+) -> Result<UnixStream, FirecrackerError> {
+    connect_to_guest_timeout(uds_path, port, CONNECT_TIMEOUT).await
+}
+
+/// Like [`connect_to_guest`] with an explicit handshake timeout. The returned
+/// stream is positioned right after the `OK <assigned_port>` line.
+#[cfg(unix)]
+pub async fn connect_to_guest_timeout(
+    uds_path: impl AsRef<Path>,
+    port: u32,
+    timeout: Duration,
+) -> Result<UnixStream, FirecrackerError> {
+    let path = uds_path.as_ref();
+    let fs_error = |source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let handshake = async {
+        let mut stream = UnixStream::connect(path).await.map_err(fs_error)?;
+        stream
+            .write_all(format!("CONNECT {}\n", port).as_bytes())
+            .await
+            .map_err(fs_error)?;
+
+        let line = read_handshake_line(&mut stream).await.map_err(fs_error)?;
+        match line.strip_prefix("OK ") {
+            Some(assigned) if assigned.parse::<u32>().is_ok() => Ok(stream),
+            _ => Err(FirecrackerError::VsockHandshake {
+                port,
+                response: line,
+            }),
+        }
+    };
+
+    tokio::time::timeout(timeout, handshake)
+        .await
+        .map_err(|_| FirecrackerError::Timeout {
+            duration_secs: timeout.as_secs(),
+        })?
+}
+
+// Reads one byte at a time so nothing past the newline is consumed
+#[cfg(unix)]
+async fn read_handshake_line(stream: &mut UnixStream) -> std::io::Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let byte = stream.read_u8().await?;
+        if byte == b'\n' {
+            break;
+        }
+        line.push(byte);
+        if line.len() > MAX_HANDSHAKE_LINE {
+            break;
+        }
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Path Firecracker connects to on the host when the guest opens a
+/// connection to `port`.
+pub fn guest_listener_path(uds_path: impl AsRef<std::path::Path>, port: u32) -> std::path::PathBuf {
+    let mut path = uds_path.as_ref().as_os_str().to_owned();
+    path.push(format!("_{}", port));
+    path.into()
+}
+
+/// Removes the listener socket when dropped. Not `Clone`, so the socket
+/// is removed exactly once.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct SocketGuard {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl SocketGuard {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Binds the host-side socket for guest-initiated connections to `port`.
+/// A socket left over at that path is removed first if `replace_stale` is
+/// set; anything other than a socket is never removed. The socket is
+/// created with mode 0600 and unlinked when the returned guard is dropped.
+#[cfg(unix)]
+pub fn listen_for_guest(
+    uds_path: impl AsRef<Path>,
+    port: u32,
+    replace_stale: bool,
+) -> Result<(UnixListener, SocketGuard), FirecrackerError> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let path = guest_listener_path(uds_path, port);
+    let fs_error = |source| FirecrackerError::FileSystem {
+        path: path.clone(),
+        source,
+    };
+
+    if replace_stale {
+        if let Ok(metadata) = path.symlink_metadata() {
+            if !metadata.file_type().is_socket() {
+                return Err(fs_error(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    "path exists and is not a socket",
+                )));
+            }
+            std::fs::remove_file(&path).map_err(fs_error)?;
+        }
+    }
+
+    let listener = UnixListener::bind(&path).map_err(fs_error)?;
+    let guard = SocketGuard { path };
+    std::fs::set_permissions(&guard.path, std::fs::Permissions::from_mode(0o600)).map_err(
+        |source| FirecrackerError::FileSystem {
+            path: guard.path.clone(),
+            source,
+        },
+    )?;
+
+    Ok((listener, guard))
+}
+
+/// Waits until the vsock device's `uds_path` exists and accepts
+/// connections, which only happens once the instance has started.
+#[cfg(unix)]
+pub async fn wait_for_uds(
+    path: impl AsRef<Path>,
+    timeout: Duration,
+) -> Result<(), FirecrackerError> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    let path = path.as_ref();
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let last_error = match UnixStream::connect(path).await {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+
+        if tokio::time::Instant::now() + POLL_INTERVAL > deadline {
+            return Err(FirecrackerError::FileSystem {
+                path: path.to_path_buf(),
+                source: std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!(
+                        "socket not ready after {:?} (last error: {})",
+                        timeout, last_error
+                    ),
+                ),
             });
         }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
 
-        Ok(())
+/// Removes the socket at `uds_path` and every `<uds_path>_<port>` listener
+/// socket next to it, returning the paths that were removed. Nothing is
+/// removed if any of the matching paths is not a socket.
+#[cfg(unix)]
+pub fn cleanup(uds_path: impl AsRef<Path>) -> Result<Vec<PathBuf>, FirecrackerError> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let uds_path = uds_path.as_ref();
+    let fs_error = |path: &Path, source| FirecrackerError::FileSystem {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let mut candidates = Vec::new();
+    if uds_path.symlink_metadata().is_ok() {
+        candidates.push(uds_path.to_path_buf());
     }
+
+    if let (Some(dir), Some(name)) = (uds_path.parent(), uds_path.file_name()) {
+        let prefix = format!("{}_", name.to_string_lossy());
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        for entry in std::fs::read_dir(dir).map_err(|e| fs_error(dir, e))? {
+            let entry = entry.map_err(|e| fs_error(dir, e))?;
+            let file_name = entry.file_name();
+            let is_listener = file_name
+                .to_str()
+                .and_then(|n| n.strip_prefix(&prefix))
+                .is_some_and(|port| port.parse::<u32>().is_ok());
+            if is_listener {
+                candidates.push(entry.path());
+            }
+        }
+    }
+
+    for path in &candidates {
+        let metadata = path.symlink_metadata().map_err(|e| fs_error(path, e))?;
+        if !metadata.file_type().is_socket() {
+            return Err(fs_error(
+                path,
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "refusing to remove a non-socket file",
+                ),
+            ));
+        }
+    }
+
+    for path in &candidates {
+        std::fs::remove_file(path).map_err(|e| fs_error(path, e))?;
+    }
+
+    Ok(candidates)
 }
@@ -0,0 +1,951 @@
+#![cfg(feature = "cli")]
+
+use assert_cmd::Command;
+use mockito::{Matcher, Server};
+use serde_json::json;
+
+// fcctl without any connection settings from the environment running the
+// tests, and without their profiles
+fn fcctl_isolated() -> Command {
+    let mut cmd = Command::cargo_bin("fcctl").unwrap();
+    for var in ["FCCTL_URL", "FCCTL_PROFILE", "FCCTL_TIMEOUT"] {
+        cmd.env_remove(var);
+    }
+    cmd.env("FCCTL_PROFILES", "/nonexistent/fcctl/profiles.toml");
+    cmd
+}
+
+fn fcctl(server: &Server) -> Command {
+    let mut cmd = fcctl_isolated();
+    cmd.args(["--url", &server.url()]);
+    cmd
+}
+
+fn stdout_json(output: &std::process::Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn version_and_instance_print_json() {
+    let mut server = Server::new();
+    let _version = server
+        .mock("GET", "/version")
+        .with_status(200)
+        .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+        .create();
+    let _instance = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_body(
+            r#"{"id": "vm-1", "state": "Running", "vmm_version": "1.7.0", "app_name": "Firecracker"}"#,
+        )
+        .create();
+
+    let output = fcctl(&server).arg("version").output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        stdout_json(&output),
+        json!({ "firecracker_version": "1.7.0" })
+    );
+
+    let output = fcctl(&server)
+        .args(["--output", "json", "instance"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    assert_eq!(stdout_json(&output)["state"], "Running");
+}
+
+#[test]
+fn set_commands_send_request_bodies() {
+    let mut server = Server::new();
+    let machine = server
+        .mock("PUT", "/machine-config")
+        .match_body(Matcher::Json(json!({
+            "vcpu_count": 2,
+            "mem_size_mib": 1024,
+            "track_dirty_pages": true
+        })))
+        .with_status(204)
+        .create();
+    let start = server
+        .mock("PUT", "/actions")
+        .match_body(Matcher::Json(json!({ "action_type": "InstanceStart" })))
+        .with_status(204)
+        .create();
+    let net = server
+        .mock("PATCH", "/network-interfaces/eth0")
+        .match_body(Matcher::Json(json!({
+            "iface_id": "eth0",
+            "rx_rate_limiter": null
+        })))
+        .with_status(204)
+        .create();
+
+    fcctl(&server)
+        .args([
+            "machine-config",
+            "set",
+            "--vcpus",
+            "2",
+            "--mem-mib",
+            "1024",
+            "--track-dirty-pages",
+        ])
+        .assert()
+        .success();
+    fcctl(&server).args(["action", "start"]).assert().success();
+    fcctl(&server)
+        .args(["net", "patch", "eth0", "--no-rx-rate-limiter"])
+        .assert()
+        .success();
+
+    machine.assert();
+    start.assert();
+    net.assert();
+}
+
+#[test]
+fn mmds_put_reads_the_file() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("mmds.json");
+    std::fs::write(&file, r#"{"latest": {"meta-data": {"id": "i-1"}}}"#).unwrap();
+    let put = server
+        .mock("PUT", "/mmds")
+        .match_body(Matcher::Json(
+            json!({ "latest": { "meta-data": { "id": "i-1" } } }),
+        ))
+        .with_status(204)
+        .create();
+
+    fcctl(&server)
+        .args(["mmds", "put", "-f", file.to_str().unwrap()])
+        .assert()
+        .success();
+    put.assert();
+}
+
+#[test]
+fn exit_codes_follow_the_error() {
+    let mut server = Server::new();
+    let _fault = server
+        .mock("PUT", "/actions")
+        .with_status(400)
+        .with_body(r#"{"fault_message": "The requested operation is not supported"}"#)
+        .create();
+    let untouched = server.mock("PUT", "/machine-config").expect(0).create();
+
+    // Firecracker refused the request
+    let output = fcctl(&server)
+        .args(["action", "ctrl-alt-del"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not supported"));
+
+    // Rejected locally, nothing is sent
+    fcctl(&server)
+        .args(["machine-config", "set", "--vcpus", "0", "--mem-mib", "128"])
+        .assert()
+        .code(65);
+    untouched.assert();
+
+    // Unparseable arguments are usage errors
+    fcctl(&server)
+        .args([
+            "snapshot", "create", "--path", "/tmp/s", "--mem", "/tmp/m", "--type", "half",
+        ])
+        .assert()
+        .code(2);
+
+    // Nothing listening
+    fcctl_isolated()
+        .args(["--url", "http://127.0.0.1:1", "version"])
+        .assert()
+        .code(69);
+}
+
+// Writes the fixture config into `dir` with its images created there, so the
+// local path checks pass
+fn apply_fixture(dir: &std::path::Path) -> std::path::PathBuf {
+    std::fs::write(dir.join("vmlinux.bin"), b"kernel").unwrap();
+    std::fs::write(dir.join("rootfs.ext4"), b"rootfs").unwrap();
+    let config =
+        include_str!("fixtures/apply_config.json").replace("/srv/vm", dir.to_str().unwrap());
+    let path = dir.join("vm_config.json");
+    std::fs::write(&path, config).unwrap();
+    path
+}
+
+#[test]
+fn apply_sends_every_section() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let config = apply_fixture(dir.path());
+    let mocks = [
+        (
+            "/machine-config",
+            json!({ "vcpu_count": 2, "mem_size_mib": 1024 }),
+        ),
+        (
+            "/boot-source",
+            json!({
+                "kernel_image_path": dir.path().join("vmlinux.bin"),
+                "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+            }),
+        ),
+        (
+            "/drives/rootfs",
+            json!({
+                "drive_id": "rootfs",
+                "is_root_device": true,
+                "is_read_only": false,
+                "path_on_host": dir.path().join("rootfs.ext4")
+            }),
+        ),
+        (
+            "/network-interfaces/eth0",
+            json!({
+                "iface_id": "eth0",
+                "host_dev_name": "tap0",
+                "guest_mac": "06:00:c0:a8:00:02"
+            }),
+        ),
+    ]
+    .map(|(path, body)| {
+        server
+            .mock("PUT", path)
+            .match_body(Matcher::Json(body))
+            .with_status(204)
+            .expect(1)
+            .create()
+    });
+
+    let output = fcctl(&server)
+        .args(["apply", "-f", config.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report = stdout_json(&output);
+    let sections: Vec<_> = report["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| {
+            (
+                s["section"].as_str().unwrap(),
+                s["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            ("machine_config", "applied"),
+            ("boot_source", "applied"),
+            ("drives[0]", "applied"),
+            ("network_interfaces[0]", "applied"),
+        ]
+    );
+    for mock in mocks {
+        mock.assert();
+    }
+}
+
+#[test]
+fn apply_dry_run_sends_nothing() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let config = apply_fixture(dir.path());
+    let untouched = server.mock("PUT", Matcher::Any).expect(0).create();
+
+    let output = fcctl(&server)
+        .args(["apply", "--dry-run", "-f", config.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report = stdout_json(&output);
+    assert_eq!(report["dry_run"], true);
+    let drive = &report["sections"][2];
+    assert_eq!(drive["request"], "PUT /drives/rootfs");
+    assert_eq!(drive["status"], "planned");
+    assert_eq!(drive["body"]["drive_id"], "rootfs");
+    untouched.assert();
+}
+
+#[test]
+fn apply_reports_warnings_once() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let config = apply_fixture(dir.path());
+    let contents = std::fs::read_to_string(&config)
+        .unwrap()
+        .replace("pci=off", "pci=off root=/dev/vda");
+    std::fs::write(&config, contents).unwrap();
+    let accepted = server
+        .mock("PUT", Matcher::Any)
+        .with_status(204)
+        .expect(4)
+        .create();
+
+    for args in [&["apply"][..], &["apply", "--dry-run"][..]] {
+        let output = fcctl(&server)
+            .args(args)
+            .args(["-f", config.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let report = stdout_json(&output);
+        let warnings = report["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0]
+            .as_str()
+            .unwrap()
+            .starts_with("warning [boot_args_root_conflict] boot_source.boot_args: "));
+        // Already in the report, so not repeated on stderr
+        assert!(output.stderr.is_empty(), "{:?}", output);
+    }
+    accepted.assert();
+}
+
+#[test]
+fn apply_lists_every_validation_error() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let config = apply_fixture(dir.path());
+    let rootfs = dir.path().join("rootfs.ext4");
+    let contents = std::fs::read_to_string(&config)
+        .unwrap()
+        .replace(rootfs.to_str().unwrap(), "rootfs.ext4")
+        .replace("\"vcpu_count\": 2", "\"vcpu_count\": 0");
+    std::fs::write(&config, contents).unwrap();
+    let untouched = server.mock("PUT", Matcher::Any).expect(0).create();
+
+    let output = fcctl(&server)
+        .args(["apply", "-f", config.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("drives[0].path_on_host"), "{stderr}");
+    assert!(stderr.contains("machine_config.vcpu_count"), "{stderr}");
+    untouched.assert();
+}
+
+#[test]
+fn snapshot_create_pauses_and_records_the_chain() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("vm.snap");
+    let mem = dir.path().join("vm.mem");
+    let manifest = dir.path().join("chain.json");
+    let pause = server
+        .mock("PATCH", "/vm")
+        .match_body(Matcher::Json(json!({ "state": "Paused" })))
+        .with_status(204)
+        .create();
+    // Stands in for Firecracker writing the snapshot files
+    let (state_file, mem_file) = (state.clone(), mem.clone());
+    let create = server
+        .mock("PUT", "/snapshot/create")
+        .match_body(Matcher::Json(json!({
+            "snapshot_type": "Full",
+            "snapshot_path": state,
+            "mem_file_path": mem
+        })))
+        .with_status(204)
+        .with_body_from_request(move |_| {
+            std::fs::write(&state_file, [0; 16]).unwrap();
+            std::fs::write(&mem_file, [0; 4096]).unwrap();
+            Vec::new()
+        })
+        .create();
+    let resume = server
+        .mock("PATCH", "/vm")
+        .match_body(Matcher::Json(json!({ "state": "Resumed" })))
+        .with_status(204)
+        .create();
+
+    let output = fcctl(&server)
+        .args(["snapshot", "create", "--pause", "--path"])
+        .arg(&state)
+        .arg("--mem")
+        .arg(&mem)
+        .arg("--chain")
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report = stdout_json(&output);
+    assert_eq!(report["state"], "Running");
+    assert_eq!(report["files"][0]["size_bytes"], 16);
+    assert_eq!(report["files"][1]["size_bytes"], 4096);
+    pause.assert();
+    create.assert();
+    resume.assert();
+
+    let chain: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&manifest).unwrap()).unwrap();
+    assert_eq!(chain["snapshots"][0]["snapshot_path"], json!(state));
+    assert_eq!(chain["snapshots"][0]["snapshot_type"], "Full");
+}
+
+#[test]
+fn snapshot_load_sends_backend_and_overrides() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("vm.snap");
+    std::fs::write(&state, [0; 16]).unwrap();
+    let socket = dir.path().join("uffd.sock");
+    let _handler = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+    let load = server
+        .mock("PUT", "/snapshot/load")
+        .match_body(Matcher::Json(json!({
+            "snapshot_path": state,
+            "mem_backend": { "backend_type": "Uds", "backend_path": socket },
+            "resume_vm": true,
+            "network_overrides": [{ "iface_id": "eth0", "host_dev_name": "tap9" }]
+        })))
+        .with_status(204)
+        .create();
+
+    let output = fcctl(&server)
+        .args([
+            "snapshot",
+            "load",
+            "--resume",
+            "--net-override",
+            "eth0=tap9",
+        ])
+        .arg("--path")
+        .arg(&state)
+        .arg("--mem-uds")
+        .arg(&socket)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report = stdout_json(&output);
+    assert_eq!(report["mem_uds_path"], json!(socket));
+    assert_eq!(report["files"].as_array().unwrap().len(), 1);
+    load.assert();
+
+    // A memory source is required, and only one of them
+    fcctl(&server)
+        .args(["snapshot", "load", "--path", "/tmp/s"])
+        .assert()
+        .code(2);
+    fcctl(&server)
+        .args([
+            "snapshot",
+            "load",
+            "--path",
+            "/tmp/s",
+            "--mem",
+            "/tmp/m",
+            "--mem-uds",
+            "/tmp/u",
+        ])
+        .assert()
+        .code(2);
+    fcctl(&server)
+        .args(["snapshot", "load", "--path", "/tmp/s", "--mem", "/tmp/m"])
+        .args(["--net-override", "eth0"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn snapshot_restore_loads_the_chain() {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir().unwrap();
+    let state = dir.path().join("vm.snap");
+    let mem = dir.path().join("vm.mem");
+    std::fs::write(&state, [0; 16]).unwrap();
+    std::fs::write(&mem, [0; 4096]).unwrap();
+    let manifest = dir.path().join("chain.json");
+    std::fs::write(
+        &manifest,
+        serde_json::to_vec(&json!({
+            "snapshots": [{
+                "snapshot_path": state,
+                "mem_file_path": mem,
+                "snapshot_type": "Full",
+                "created_at": 0
+            }]
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+    let _instance = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_body(
+            r#"{"id": "vm-1", "state": "Running", "vmm_version": "1.7.0", "app_name": "Firecracker"}"#,
+        )
+        .create();
+    let _version = server
+        .mock("GET", "/version")
+        .with_status(200)
+        .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+        .create();
+    let load = server
+        .mock("PUT", "/snapshot/load")
+        .match_body(Matcher::Json(json!({
+            "snapshot_path": state,
+            "mem_backend": { "backend_type": "File", "backend_path": mem },
+            "resume_vm": true,
+            "network_overrides": [{ "iface_id": "eth0", "host_dev_name": "tap9" }]
+        })))
+        .with_status(204)
+        .create();
+
+    let output = fcctl(&server)
+        .args([
+            "snapshot",
+            "restore",
+            "--net-override",
+            "eth0=tap9",
+            "--chain",
+        ])
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let report = stdout_json(&output);
+    assert_eq!(report["state"], "Running");
+    assert_eq!(report["mem_file_path"], json!(mem));
+    assert_eq!(report["files"][1]["size_bytes"], 4096);
+    load.assert();
+}
+
+#[test]
+fn watch_streams_events_until_interrupted() {
+    use std::io::Write;
+
+    let mut server = Server::new();
+    let _instance = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_body(
+            r#"{"id": "vm-1", "state": "Running", "vmm_version": "1.7.0", "app_name": "Firecracker"}"#,
+        )
+        .create();
+    let _balloon = server
+        .mock("GET", "/balloon/statistics")
+        .with_status(200)
+        .with_body(r#"{"target_pages": 0, "actual_pages": 0, "target_mib": 0, "actual_mib": 0}"#)
+        .create();
+    let dir = tempfile::tempdir().unwrap();
+    let fifo = dir.path().join("metrics.fifo");
+    firecracker_http_client::logger::create_log_fifo(&fifo, false).unwrap();
+
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("fcctl"))
+        .env_remove("FCCTL_PROFILE")
+        .env_remove("FCCTL_TIMEOUT")
+        .env("FCCTL_PROFILES", "/nonexistent/fcctl/profiles.toml")
+        .args([
+            "--url",
+            &server.url(),
+            "watch",
+            "--json",
+            "--interval",
+            "100ms",
+        ])
+        .arg("--metrics")
+        .arg(&fifo)
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Blocks until fcctl opens the FIFO
+    let mut writer = std::fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+    writer
+        .write_all(b"{\"utc_timestamp_ms\":1000,\"seccomp\":{\"num_faults\":0}}\n")
+        .unwrap();
+    writer
+        .write_all(b"{\"utc_timestamp_ms\":2000,\"seccomp\":{\"num_faults\":2}}\n")
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // The open FIFO writer must not keep fcctl from exiting
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    drop(writer);
+
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    // Unchanged state and statistics are reported once
+    assert_eq!(kinds.iter().filter(|k| **k == "instance").count(), 1);
+    assert_eq!(kinds.iter().filter(|k| **k == "balloon").count(), 1);
+    let metrics = events.iter().find(|e| e["event"] == "metrics").unwrap();
+    assert_eq!(metrics["elapsed_ms"], 1000);
+    assert_eq!(metrics["changes"], json!({ "seccomp.num_faults": 2 }));
+}
+
+#[test]
+fn read_commands_render_every_format() {
+    let mut server = Server::new();
+    let cases = [
+        (
+            "instance",
+            "/",
+            include_str!("../src/testing/fixtures/instance_info_v1.7.json"),
+            "instance",
+        ),
+        (
+            "machine-config get",
+            "/machine-config",
+            include_str!("../src/testing/fixtures/machine_config_v1.7.json"),
+            "machine_config",
+        ),
+        (
+            "balloon stats",
+            "/balloon/statistics",
+            include_str!("../src/testing/fixtures/balloon_stats_v1.7.json"),
+            "balloon_stats",
+        ),
+        (
+            "vm-config get",
+            "/vm/config",
+            include_str!("../src/testing/fixtures/vm_config_v1.7.json"),
+            "vm_config",
+        ),
+    ];
+    let _mocks: Vec<_> = cases
+        .iter()
+        .map(|(_, path, body, _)| server.mock("GET", *path).with_body(*body).create())
+        .collect();
+
+    for (command, _, _, fixture) in cases {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/output");
+        for format in ["yaml", "table"] {
+            let output = fcctl(&server)
+                .args(["--output", format])
+                .args(command.split(' '))
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+            let expected =
+                std::fs::read_to_string(dir.join(format!("{}.{}", fixture, format))).unwrap();
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                expected,
+                "{} --output {}",
+                command,
+                format
+            );
+        }
+
+        let output = fcctl(&server)
+            .args(["--output", "json"])
+            .args(command.split(' '))
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+        assert!(stdout_json(&output).is_object());
+    }
+}
+
+#[test]
+fn yaml_output_quotes_strings_that_read_back_as_numbers() {
+    let mut server = Server::new();
+    let _instance = server
+        .mock("GET", "/")
+        .with_body(
+            json!({
+                "id": "0x1F",
+                "state": "Running",
+                "vmm_version": "1:20",
+                "app_name": "1_000",
+            })
+            .to_string(),
+        )
+        .create();
+
+    let output = fcctl(&server)
+        .args(["--output", "yaml", "instance"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for line in [
+        r#"id: "0x1F""#,
+        "state: Running",
+        r#"vmm_version: "1:20""#,
+        r#"app_name: "1_000""#,
+    ] {
+        assert!(stdout.lines().any(|l| l == line), "{}", stdout);
+    }
+}
+
+#[test]
+fn json_output_reports_errors_as_json() {
+    let mut server = Server::new();
+    let _fault = server
+        .mock("GET", "/machine-config")
+        .with_status(400)
+        .with_body(r#"{"fault_message": "boom"}"#)
+        .create();
+
+    let output = fcctl(&server)
+        .args(["--output", "json", "machine-config", "get"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["exit_code"], 1);
+    assert!(error["message"].as_str().unwrap().contains("boom"));
+
+    let output = fcctl(&server)
+        .args(["--output", "json", "machine-config", "set"])
+        .args(["--vcpus", "0", "--mem-mib", "128"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["fields"][0]["field"], "vcpu_count");
+}
+
+#[test]
+fn profiles_add_list_remove() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("fcctl").join("profiles.toml");
+    let profiles = |args: &[&str]| {
+        let mut cmd = fcctl_isolated();
+        cmd.env("FCCTL_PROFILES", &path).arg("profiles").args(args);
+        cmd
+    };
+
+    profiles(&[
+        "add",
+        "vm42",
+        "--url",
+        "http://10.0.0.42:8080",
+        "--timeout",
+        "5",
+    ])
+    .arg("--default")
+    .assert()
+    .success();
+    profiles(&["add", "vm43", "--url", "http://10.0.0.43:8080"])
+        .assert()
+        .success();
+    let saved: toml::Table = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved["default"].as_str(), Some("vm42"));
+    assert_eq!(
+        saved["profiles"]["vm42"]["timeout_secs"].as_integer(),
+        Some(5)
+    );
+
+    let output = profiles(&["list"]).output().unwrap();
+    assert_eq!(
+        stdout_json(&output),
+        json!({
+            "vm42": { "url": "http://10.0.0.42:8080", "timeout_secs": 5, "default": true },
+            "vm43": { "url": "http://10.0.0.43:8080", "default": false }
+        })
+    );
+
+    let output = profiles(&["remove", "vm42"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout_json(&output),
+        json!({ "vm43": { "url": "http://10.0.0.43:8080", "default": false } })
+    );
+    let saved: toml::Table = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(saved.get("default").is_none());
+
+    profiles(&["remove", "vm42"]).assert().code(65);
+    profiles(&["add", "vm44"]).assert().code(65);
+}
+
+#[test]
+fn connection_settings_follow_precedence() {
+    let mut servers: Vec<_> = (0..4).map(|_| Server::new()).collect();
+    let mocks: Vec<_> = servers
+        .iter_mut()
+        .map(|server| {
+            server
+                .mock("GET", "/version")
+                .with_body(r#"{"firecracker_version": "1.7.0"}"#)
+                .expect(1)
+                .create()
+        })
+        .collect();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("profiles.toml");
+    std::fs::write(
+        &path,
+        format!(
+            "default = \"vm-a\"\n\n[profiles.vm-a]\nurl = \"{}\"\n\n[profiles.vm-b]\nurl = \"{}\"\n",
+            servers[0].url(),
+            servers[1].url()
+        ),
+    )
+    .unwrap();
+    let version = || {
+        let mut cmd = fcctl_isolated();
+        cmd.env("FCCTL_PROFILES", &path).arg("version");
+        cmd
+    };
+
+    // The default profile
+    version().assert().success();
+    // A profile selected in the environment
+    version().env("FCCTL_PROFILE", "vm-b").assert().success();
+    // The URL from the environment wins over the profile
+    version()
+        .env("FCCTL_PROFILE", "vm-b")
+        .env("FCCTL_URL", servers[2].url())
+        .assert()
+        .success();
+    // And the flag over the environment
+    version()
+        .env("FCCTL_URL", servers[2].url())
+        .args(["--url", &servers[3].url()])
+        .assert()
+        .success();
+    for mock in mocks {
+        mock.assert();
+    }
+
+    // Naming a profile that doesn't exist is an error even with --url
+    version()
+        .args(["--profile", "vm-z", "--url", &servers[3].url()])
+        .assert()
+        .code(65);
+}
+
+#[test]
+fn profile_timeout_limits_requests() {
+    let mut server = Server::new();
+    let _slow = server
+        .mock("GET", "/version")
+        .with_body_from_request(|_| {
+            std::thread::sleep(std::time::Duration::from_secs(3));
+            br#"{"firecracker_version": "1.7.0"}"#.to_vec()
+        })
+        .create();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("profiles.toml");
+    std::fs::write(
+        &path,
+        format!(
+            "[profiles.slow]\nurl = \"{}\"\ntimeout_secs = 1\n",
+            server.url()
+        ),
+    )
+    .unwrap();
+
+    let started = std::time::Instant::now();
+    fcctl_isolated()
+        .env("FCCTL_PROFILES", &path)
+        .args(["--profile", "slow", "version"])
+        .assert()
+        .code(69);
+    assert!(started.elapsed() < std::time::Duration::from_secs(3));
+}
+
+#[test]
+fn mmds_reads_stdin_and_sets_pointers() {
+    let mut server = Server::new();
+    let put = server
+        .mock("PUT", "/mmds")
+        .match_body(Matcher::Json(json!({ "latest": { "meta-data": {} } })))
+        .with_status(204)
+        .create();
+    let set = server
+        .mock("PATCH", "/mmds")
+        .match_body(Matcher::Json(
+            json!({ "latest": { "meta-data": { "instance-id": "i-123" } } }),
+        ))
+        .with_status(204)
+        .create();
+    let set_json = server
+        .mock("PATCH", "/mmds")
+        .match_body(Matcher::Json(json!({ "latest": { "ready": true } })))
+        .with_status(204)
+        .create();
+    let _get = server
+        .mock("GET", "/mmds")
+        .with_body(r#"{"latest": {"meta-data": {"instance-id": "i-123"}}}"#)
+        .create();
+
+    fcctl(&server)
+        .args(["mmds", "put", "-f", "-"])
+        .write_stdin(r#"{"latest": {"meta-data": {}}}"#)
+        .assert()
+        .success();
+    fcctl(&server)
+        .args(["mmds", "set", "/latest/meta-data/instance-id", "i-123"])
+        .assert()
+        .success();
+    fcctl(&server)
+        .args(["mmds", "set", "/latest/ready", "true"])
+        .assert()
+        .success();
+    put.assert();
+    set.assert();
+    set_json.assert();
+
+    let output = fcctl(&server)
+        .args(["mmds", "get", "--pointer", "/latest/meta-data"])
+        .output()
+        .unwrap();
+    assert_eq!(stdout_json(&output), json!({ "instance-id": "i-123" }));
+    fcctl(&server)
+        .args(["mmds", "get", "--pointer", "/latest/user-data"])
+        .assert()
+        .code(65);
+}
+
+#[test]
+fn mmds_rejects_bad_input_before_sending() {
+    let mut server = Server::new();
+    let untouched = ["PUT", "PATCH"].map(|method| server.mock(method, "/mmds").expect(0).create());
+
+    let big = json!({ "blob": "x".repeat(60 * 1024) }).to_string();
+    let output = fcctl(&server)
+        .args(["mmds", "put", "-f", "-"])
+        .write_stdin(big.clone())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("51200 byte limit"), "{stderr}");
+
+    fcctl(&server)
+        .args(["mmds", "put", "-f", "-"])
+        .write_stdin("{\"latest\": ")
+        .assert()
+        .code(65);
+    fcctl(&server)
+        .args(["mmds", "set", "latest/meta-data", "x"])
+        .assert()
+        .code(65);
+    for mock in untouched {
+        mock.assert();
+        mock.remove();
+    }
+
+    // A VMM started with a larger --mmds-size-limit
+    let put = server.mock("PUT", "/mmds").with_status(204).create();
+    fcctl(&server)
+        .args(["mmds", "put", "-f", "-", "--size-limit", "102400"])
+        .write_stdin(big)
+        .assert()
+        .success();
+    put.assert();
+}
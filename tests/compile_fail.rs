@@ -0,0 +1,7 @@
+// Models are `#[non_exhaustive]` so new Firecracker fields aren't breaking
+// changes; these check that they can't be built with literals downstream.
+#[test]
+fn models_reject_struct_literals() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,187 @@
+#![cfg(all(unix, feature = "launcher"))]
+//! Tests against a real `firecracker` binary, to catch what mocks can't:
+//! requests Firecracker rejects, or responses that don't parse. They only
+//! run when every artifact below is given, and pass without doing anything
+//! otherwise. Nothing is downloaded.
+//!
+//! - `FIRECRACKER_BIN`: the `firecracker` binary, 1.7 or later
+//! - `FC_KERNEL`: an uncompressed guest kernel (`vmlinux`) with the virtio
+//!   balloon driver
+//! - `FC_ROOTFS`: an ext4 root filesystem image; it is attached read-only
+//!
+//! The user running the tests needs read-write access to `/dev/kvm`:
+//!
+//!     FIRECRACKER_BIN=... FC_KERNEL=... FC_ROOTFS=... \
+//!         cargo test --features launcher --test it
+
+use firecracker_http_client::action::{ActionType, InstanceActionInfo};
+use firecracker_http_client::balloon::{BalloonOperations, BalloonUpdate};
+use firecracker_http_client::config::ConfigOperations;
+use firecracker_http_client::instance::InstanceOperations;
+use firecracker_http_client::launcher::{FirecrackerProcess, LaunchOptions};
+use firecracker_http_client::mmds::MmdsOperations;
+use firecracker_http_client::snapshot::{
+    SnapshotCreateParams, SnapshotLoadParams, SnapshotOperations, SnapshotType,
+};
+use firecracker_http_client::{
+    Balloon, BootSource, Drive, FirecrackerClient, FullVmConfiguration, MachineConfig, VmOperations,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The files a test VM is made of, from the environment.
+struct Artifacts {
+    firecracker: PathBuf,
+    kernel: PathBuf,
+    rootfs: PathBuf,
+}
+
+impl Artifacts {
+    /// `None`, with a note on stderr, unless every variable is set.
+    fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var_os(name).map(PathBuf::from);
+        match (var("FIRECRACKER_BIN"), var("FC_KERNEL"), var("FC_ROOTFS")) {
+            (Some(firecracker), Some(kernel), Some(rootfs)) => Some(Self {
+                firecracker,
+                kernel,
+                rootfs,
+            }),
+            _ => {
+                eprintln!("skipped: set FIRECRACKER_BIN, FC_KERNEL and FC_ROOTFS to run");
+                None
+            }
+        }
+    }
+
+    /// The least that boots, plus a balloon to exercise.
+    fn config(&self) -> FullVmConfiguration {
+        let mut boot_source = BootSource::new(self.kernel.display().to_string());
+        boot_source.boot_args = Some("console=ttyS0 reboot=k panic=1 pci=off ro".to_string());
+        let mut rootfs = Drive::new("rootfs", self.rootfs.display().to_string());
+        rootfs.is_root_device = true;
+        rootfs.is_read_only = true;
+        let mut config = FullVmConfiguration::default();
+        config.boot_source = Some(boot_source);
+        config.machine_config = Some(MachineConfig::new(1, 128));
+        config.drives = vec![rootfs];
+        config.balloon = Some(
+            Balloon::builder(0)
+                .deflate_on_oom(true)
+                .stats_polling_interval_s(1)
+                .build()
+                .unwrap(),
+        );
+        config
+    }
+
+    async fn spawn(&self, dir: &Path, name: &str) -> (FirecrackerProcess, FirecrackerClient) {
+        let options = LaunchOptions::new(&self.firecracker, dir.join(format!("{}.sock", name)));
+        FirecrackerProcess::spawn(options).await.unwrap()
+    }
+
+    async fn boot(&self, dir: &Path) -> (FirecrackerProcess, FirecrackerClient) {
+        let (process, client) = self.spawn(dir, "vm").await;
+        client.apply_config(&self.config()).await.unwrap();
+        client
+            .create_sync_action(&InstanceActionInfo::from(ActionType::InstanceStart))
+            .await
+            .unwrap();
+        (process, client)
+    }
+}
+
+#[tokio::test]
+async fn boots_and_reports_its_config() {
+    let Some(artifacts) = Artifacts::from_env() else {
+        return;
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let (mut process, client) = artifacts.boot(dir.path()).await;
+
+    assert_eq!(client.describe_instance().await.unwrap().state, "Running");
+    let config = client.get_vm_config().await.unwrap();
+    let expected = artifacts.config();
+    assert_eq!(config.boot_source, expected.boot_source);
+    let machine = config.machine_config.unwrap();
+    assert_eq!(machine.vcpu_count, Some(1));
+    assert_eq!(machine.mem_size_mib, Some(128));
+    assert_eq!(
+        config.drives[0].path_on_host,
+        expected.drives[0].path_on_host
+    );
+    assert!(config.drives[0].is_read_only);
+    assert!(config.balloon.unwrap().deflate_on_oom);
+
+    process.kill().await.unwrap();
+}
+
+#[tokio::test]
+async fn balloon_and_mmds_round_trip() {
+    let Some(artifacts) = Artifacts::from_env() else {
+        return;
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let (mut process, client) = artifacts.boot(dir.path()).await;
+
+    client
+        .patch_balloon_config(&BalloonUpdate::new(32))
+        .await
+        .unwrap();
+    assert_eq!(client.get_balloon_config().await.unwrap().amount_mib, 32);
+    let stats = client.get_balloon_stats().await.unwrap();
+    assert_eq!(stats.target_mib, 32);
+
+    let data = json!({"latest": {"meta-data": {"instance-id": "i-1234"}}});
+    client.put_mmds(data.clone()).await.unwrap();
+    assert_eq!(client.get_mmds().await.unwrap(), data);
+    client
+        .patch_mmds(json!({"latest": {"user-data": "hello"}}))
+        .await
+        .unwrap();
+    let mmds = client.get_mmds().await.unwrap();
+    assert_eq!(mmds["latest"]["meta-data"]["instance-id"], "i-1234");
+    assert_eq!(mmds["latest"]["user-data"], "hello");
+
+    let tags = HashMap::from([("team".to_string(), "storage".to_string())]);
+    client.set_tags(tags.clone()).await.unwrap();
+    assert_eq!(client.get_tags().await.unwrap(), tags);
+    client.remove_tag("team").await.unwrap();
+    assert!(client.get_tags().await.unwrap().is_empty());
+
+    process.kill().await.unwrap();
+}
+
+#[tokio::test]
+async fn snapshot_restores_into_a_new_vmm() {
+    let Some(artifacts) = Artifacts::from_env() else {
+        return;
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let (mut process, client) = artifacts.boot(dir.path()).await;
+
+    let snapshot_path = dir.path().join("vm.snap").display().to_string();
+    let mem_path = dir.path().join("vm.mem").display().to_string();
+    client.pause_vm().await.unwrap();
+    let params = SnapshotCreateParams::builder(&snapshot_path, &mem_path)
+        .snapshot_type(SnapshotType::Full)
+        .build()
+        .unwrap();
+    client.create_snapshot(&params).await.unwrap();
+    process.kill().await.unwrap();
+
+    let (mut restored, client) = artifacts.spawn(dir.path(), "restored").await;
+    let mut load = SnapshotLoadParams::with_mem_file(&snapshot_path, &mem_path);
+    load.resume_vm = Some(true);
+    client.load_snapshot(&load).await.unwrap();
+    assert_eq!(client.describe_instance().await.unwrap().state, "Running");
+    let machine = client
+        .get_vm_config()
+        .await
+        .unwrap()
+        .machine_config
+        .unwrap();
+    assert_eq!(machine.mem_size_mib, Some(128));
+
+    restored.kill().await.unwrap();
+}
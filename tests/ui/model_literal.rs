@@ -0,0 +1,14 @@
+use firecracker_http_client::{Drive, MachineConfig};
+
+fn main() {
+    let _ = MachineConfig {
+        vcpu_count: Some(2),
+        mem_size_mib: Some(1024),
+        ..Default::default()
+    };
+    let _ = Drive {
+        drive_id: "rootfs".to_string(),
+        path_on_host: "/images/rootfs.ext4".to_string(),
+        ..Default::default()
+    };
+}
@@ -0,0 +1,13 @@
+use firecracker_http_client::drive::DriveUpdate;
+use firecracker_http_client::snapshot::SnapshotLoadParams;
+
+fn main() {
+    let _ = DriveUpdate {
+        drive_id: "rootfs".to_string(),
+        ..Default::default()
+    };
+    let _ = SnapshotLoadParams {
+        snapshot_path: "/tmp/vm.snap".to_string(),
+        ..Default::default()
+    };
+}